@@ -0,0 +1,10 @@
+//! Generates the gRPC service of `proto/news.proto`, see `src/grpc.rs`.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // A vendored protoc, so building needs no system install.
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+    tonic_build::compile_protos("proto/news.proto")?;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Embedding the provider clients: one MarketAux and one FMP request, sharing a response cache.
+//!
+//! Reads `config.toml` from the working directory, like the service.
+//!
+//! ```text
+//! cargo run --example clients
+//! ```
+
+use std::sync::Arc;
+
+use reqwest::Client;
+use serde_json::json;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use news_data::logging::setup_logger;
+use news_data::marketaux::{MarketAuxResponse, ALL_NEWS_ENDPOINT};
+use news_data::{FMPClient, HTTPClient, MarketAuxApiClient, SharedLockedCache, ValueConfig};
+
+#[tokio::main]
+async fn main() {
+    setup_logger("debug");
+
+    let config = Arc::new(ValueConfig::new().expect("Failed to read config file"));
+    let cache = Arc::new(Mutex::new(SharedLockedCache::new(100)));
+
+    let marketaux = MarketAuxApiClient::new(Arc::new(Client::new()), cache.clone(), config.clone());
+    let query = json!({ "endpoint": ALL_NEWS_ENDPOINT, "symbols": "DIS" });
+    match marketaux.poll(Arc::new(query)).await.map(serde_json::from_value::<MarketAuxResponse>) {
+        Ok(Ok(news)) => info!("MarketAux returned {} articles. | Meta: {:?}", news.data.len(), news.meta),
+        Ok(Err(e)) => error!("Unexpected MarketAux response: {}", e),
+        Err(e) => error!("MarketAux request failed: {}", e),
+    }
+
+    let http_client = Arc::new(HTTPClient::new().expect("Failed to initialize HTTP client."));
+    let fmp = FMPClient::new(http_client, cache, config);
+    match fmp.poll(Arc::new(json!({ "function": "stock news" }))).await {
+        Ok(news) => info!("FMP returned {}", news),
+        Err(e) => error!("FMP request failed: {}", e),
+    }
+}
@@ -0,0 +1,106 @@
+//! At-least-once delivery of the pushed articles.
+//!
+//! A connection subscribing with `"ack": true` acknowledges every `article` event pushed to it,
+//! by its sequence number (or a list of them):
+//!
+//! ```text
+//! {"caller": {...}, "target": "subscription", "args": {"command": "subscribe", "params": {"tickers": ["NVDA"], "ack": true}}}
+//! {"caller": {...}, "target": "subscription", "args": {"command": "ack", "params": {"seq": 1042}}}
+//! {"caller": {...}, "target": "subscription", "args": {"command": "parked"}}
+//! ```
+//!
+//! An event not acknowledged within `server.ack_timeout_secs` is pushed again, flagged
+//! `"redelivered": true`, up to `server.ack_max_attempts` pushes in all. It is then parked: `parked`
+//! answers with the parked events, and forgets them. No new article is pushed while
+//! `server.max_unacked` events wait for an acknowledgement, so a slow consumer is not flooded.
+//!
+//! The pending and parked events belong to the subscriptions of the connection: resumed with them
+//! (see `resume.rs`), the pending ones are pushed again on the new connection once due.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// Event waiting for an acknowledgement.
+#[derive(Debug, Clone)]
+struct Delivery {
+    event: Value,
+    attempts: u32,
+    sent_at: Instant,
+}
+
+/// Events pushed to a connection in acknowledgement mode.
+#[derive(Debug, Clone, Default)]
+pub struct Deliveries {
+    pending: BTreeMap<u64, Delivery>,
+    parked: Vec<Value>,
+}
+impl Deliveries {
+    /// Records the push of `event`, the article `seq`.
+    pub fn sent(&mut self, seq: u64, event: Value) {
+        self.pending.insert(seq, Delivery { event, attempts: 1, sent_at: Instant::now() });
+    }
+
+    /// Acknowledges the events of `seqs`. Returns how many were pending.
+    pub fn ack(&mut self, seqs: &[u64]) -> usize {
+        seqs.iter().filter(|seq| self.pending.remove(seq).is_some()).count()
+    }
+
+    /// Events to push again, unacknowledged for `timeout`, oldest first. The ones pushed
+    /// `max_attempts` times already are parked instead.
+    pub fn due(&mut self, timeout: Duration, max_attempts: u32) -> Vec<Value> {
+        let expired: Vec<u64> = self.pending.iter().filter(|(_, d)| d.sent_at.elapsed() >= timeout).map(|(seq, _)| *seq).collect();
+        let mut due = Vec::new();
+        for seq in expired {
+            let Some(delivery) = self.pending.get_mut(&seq) else {
+                continue;
+            };
+            if delivery.attempts >= max_attempts {
+                if let Some(delivery) = self.pending.remove(&seq) {
+                    self.parked.push(delivery.event);
+                }
+                continue;
+            }
+            delivery.attempts += 1;
+            delivery.sent_at = Instant::now();
+            let mut event = delivery.event.clone();
+            event["redelivered"] = Value::Bool(true);
+            due.push(event);
+        }
+        due
+    }
+
+    /// Events waiting for an acknowledgement.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Takes the parked events.
+    pub fn take_parked(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.parked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redelivers_then_parks_the_unacknowledged_events() {
+        let mut deliveries = Deliveries::default();
+        deliveries.sent(1, json!({ "seq": 1 }));
+        deliveries.sent(2, json!({ "seq": 2 }));
+        deliveries.sent(3, json!({ "seq": 3 }));
+        assert_eq!(deliveries.ack(&[2, 4]), 1);
+        assert!(deliveries.due(Duration::from_secs(60), 2).is_empty());
+
+        assert_eq!(deliveries.due(Duration::ZERO, 2), [json!({ "seq": 1, "redelivered": true }), json!({ "seq": 3, "redelivered": true })]);
+        assert_eq!(deliveries.ack(&[3]), 1);
+        assert!(deliveries.due(Duration::ZERO, 2).is_empty());
+        assert_eq!(deliveries.pending(), 0);
+        assert_eq!(deliveries.take_parked(), [json!({ "seq": 1 })]);
+        assert!(deliveries.take_parked().is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+//! Runtime management of the service, over the `admin` target of the websocket API.
+//!
+//! Operators manage a running server with these commands rather than restarting it:
+//! - `cache_flush`: empties the provider response cache and the public response cache,
+//! - `stats`: open connections, subscribers of the polling results, cache sizes and uptime,
+//! - `reload_config`: reads the config file again and applies the sections read at runtime
+//!   (`logging.level`, `logging.filters` and the sections listed in `configure`), the others
+//!   taking effect on the next start,
+//! - `set_log_level`: sets the `level` of the logs (`error`, `warn`, `info`, `debug` or `trace`)
+//!   and/or the per-module `filters` (e.g. `marketaux=debug,db=warn`, see `logging.rs`) until the
+//!   next one or the next reload.
+//!
+//! Like every admin command, they are only accepted on the admin listeners, from authenticated
+//! clients when authentication is enabled (see `auth.rs`).
+
+use std::time::Instant;
+
+use serde_json::{json, Value};
+use thiserror::Error;
+use tracing::info;
+
+use crate::config::ValueConfig;
+use crate::logging::{self, LogLevel};
+use crate::websocket::PollState;
+use crate::{api_calls, autoscale, captures, chaos, clock, costs, freshness, latency, limiter, quota, sentiment, taxonomy, watermark};
+
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("Invalid params: {0}")]
+    Params(String),
+
+    #[error("Failed to read the config: {0}")]
+    Config(String),
+
+    #[error("Failed to set the log level: {0}")]
+    Logging(String),
+}
+
+/// Applies the sections of `config` read at runtime, returning their names.
+pub fn configure(config: &ValueConfig) -> Vec<&'static str> {
+    clock::configure(&config.clock);
+    chaos::configure(&config.chaos);
+    autoscale::configure(&config.autoscale);
+    costs::configure(&config.costs);
+    freshness::configure(&config.freshness);
+    latency::configure(&config.latency);
+    watermark::configure(&config.watermark);
+    sentiment::configure(&config.sentiment);
+    taxonomy::configure(&config.taxonomy);
+    api_calls::configure(&config.api_calls);
+    limiter::configure(&config.task);
+    quota::configure(&config.quota);
+    captures::configure(&config.captures);
+    vec!["clock", "chaos", "autoscale", "costs", "freshness", "latency", "watermark", "sentiment", "taxonomy", "api_calls", "task.max_concurrent_fetches", "quota", "captures"]
+}
+
+/// Handles the runtime management commands of a server.
+#[derive(Debug)]
+pub struct AdminHandler {
+    started_at: Instant,
+}
+impl Default for AdminHandler {
+    fn default() -> Self {
+        Self { started_at: Instant::now() }
+    }
+}
+impl AdminHandler {
+    /// Empties the response caches, returning the entries removed from each.
+    pub async fn cache_flush(&self, state: &PollState) -> Value {
+        let providers = state.cache.lock().await.remove_where(|_| true).await;
+        let public = state.public.flush().await;
+        info!("Flushed {} provider and {} public cache entries", providers, public);
+        json!({ "flushed": { "providers": providers, "public": public } })
+    }
+
+    pub async fn stats(&self, state: &PollState) -> Value {
+        let provider_entries = state.cache.lock().await.read().await.len();
+        json!({
+            "uptime_secs": self.started_at.elapsed().as_secs(),
+            "connections": state.maintenance.connections(),
+            "max_connections": state.config.server.max_connections,
+            "draining": state.maintenance.is_draining(),
+            "news_subscribers": state.news.receiver_count(),
+            "providers": state.providers.iter().map(|provider| provider.name()).collect::<Vec<_>>(),
+            "cache_entries": { "providers": provider_entries, "public": state.public.entries().await },
+        })
+    }
+
+    /// Reads the config file again and applies what can be at runtime.
+    pub fn reload_config(&self) -> Result<Value, AdminError> {
+        let config = ValueConfig::new().map_err(|e| AdminError::Config(e.to_string()))?;
+        let mut applied = configure(&config);
+        let level = LogLevel::parse(&config.logging.level)
+            .ok_or_else(|| AdminError::Config(format!("Unknown `logging.level` `{}`", config.logging.level)))?;
+        logging::env_filter(level.as_str(), &config.logging.filters).map_err(AdminError::Config)?;
+        logging::set_directives(level, &config.logging.filters).map_err(AdminError::Logging)?;
+        applied.push("logging");
+        info!("Reloaded the config: {}", applied.join(", "));
+        Ok(json!({ "applied": applied }))
+    }
+
+    /// Sets the level of the logs to `level` and/or the per-module `filters`, the params of the
+    /// command. The one not given is kept.
+    pub fn set_log_level(&self, level: Option<&str>, filters: Option<&str>) -> Result<Value, AdminError> {
+        if level.is_none() && filters.is_none() {
+            return Err(AdminError::Params("Missing 'level' or 'filters' param".to_string()));
+        }
+        let level = level
+            .map(|name| LogLevel::parse(name).ok_or_else(|| AdminError::Params(format!("Unknown log level `{}`", name))))
+            .transpose()?;
+        if let Some(filters) = filters {
+            logging::env_filter(LogLevel::Info.as_str(), filters).map_err(AdminError::Params)?;
+        }
+        match (level, filters) {
+            (Some(level), Some(filters)) => logging::set_directives(level, filters),
+            (Some(level), None) => logging::set_level(level),
+            (None, filters) => logging::set_filters(filters.unwrap_or_default()),
+        }
+        .map_err(AdminError::Logging)?;
+        let (level, filters) = logging::directives();
+        info!("Log level set to `{}`, filters to `{}`", level, filters);
+        Ok(json!({ "level": level, "filters": filters }))
+    }
+}
@@ -0,0 +1,532 @@
+//! Alerting subsystem.
+//!
+//! Checks running in the background (coverage gaps, ...) raise an `Alert`. Every alert is logged
+//! and posted to the channels listed in `[[alerts.channels]]` as a `{"text": ...}` payload, which
+//! Slack incoming webhooks and most chat bridges accept as is.
+//!
+//! So that an alert storm during a volatile session does not flood a channel, each channel has a
+//! notification policy:
+//!
+//! - `quiet_hours`: a `HH:MM-HH:MM` range (UTC, may wrap past midnight) during which nothing is
+//!   posted,
+//! - `max_per_hour`: alerts posted per rolling hour,
+//! - `dedupe_secs`: an alert of the same rule and subject as one posted less than this ago is
+//!   not posted again.
+//!
+//! Alerts held back by the policy are still logged, and counted in the next alert posted to the
+//! channel. A channel may also post its own body format, see `templates.rs`. Stored articles
+//! matching `[[alerts.rules]]` are posted in periodic digests, see `digest.rs`.
+//!
+//! With a storage, the lifecycle of each rule and subject is tracked in the `alerts` collection:
+//!
+//! - `fired`: raised, and notified each time it is raised again (within the channel policies);
+//! - `acknowledged`: an operator knows about it (`ack_alert` admin command). It is still counted,
+//!   but no longer notified;
+//! - `resolved`: the check passed again, or an operator said so (`resolve_alert`). Raising it again
+//!   starts a new `fired` occurrence.
+//!
+//! Operators can also mute a rule, a subject or both, for some hours or until unmuted
+//! (`mute_alerts`, `unmute_alerts`). Muted alerts are recorded, not notified.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use chrono::{DateTime, Duration as UtcDuration, NaiveTime, SecondsFormat, Timelike, Utc};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, error, info, warn};
+
+use crate::clock;
+use crate::config::{AlertChannelConfig, AlertsConfig};
+use crate::db::OpError;
+use crate::normalize::parse_timestamp;
+use crate::storage::Storage;
+use crate::templates::PayloadTemplate;
+use crate::utils::now;
+
+/// Something an operator should know about.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Alert {
+    /// Name of the check that raised the alert (e.g. `coverage_gap`).
+    pub rule: String,
+    /// What the alert is about (a provider, a ticker, ...).
+    pub subject: String,
+    pub message: String,
+    pub at: String,
+}
+impl Alert {
+    pub fn new(rule: &str, subject: &str, message: impl Into<String>) -> Self {
+        Self { rule: rule.to_string(), subject: subject.to_string(), message: message.into(), at: now() }
+    }
+
+    /// One-line rendering used for logs and channel payloads.
+    pub fn text(&self) -> String {
+        format!("[{}] {}: {}", self.rule, self.subject, self.message)
+    }
+}
+
+/// `HH:MM-HH:MM` range of the day (UTC), wrapping past midnight when the end is before the start.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+impl QuietHours {
+    pub fn parse(range: &str) -> Result<Self, String> {
+        let time = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|_| format!("invalid time `{}` in quiet hours `{}`", t.trim(), range));
+        let (start, end) = range.split_once('-').ok_or_else(|| format!("quiet hours `{}` must be HH:MM-HH:MM", range))?;
+        Ok(Self { start: time(start)?, end: time(end)? })
+    }
+
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let time = NaiveTime::from_hms_opt(at.hour(), at.minute(), at.second()).unwrap_or_default();
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Why an alert was not posted to a channel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HeldBack {
+    QuietHours,
+    RateLimited,
+    Duplicate,
+}
+impl HeldBack {
+    pub fn to_str(&self) -> &str {
+        match self {
+            HeldBack::QuietHours => "quiet hours",
+            HeldBack::RateLimited => "rate limited",
+            HeldBack::Duplicate => "duplicate",
+        }
+    }
+}
+
+/// Notification policy of a channel and what it has posted.
+#[derive(Debug, Default)]
+pub struct ChannelPolicy {
+    quiet_hours: Option<QuietHours>,
+    max_per_hour: usize,
+    dedupe: UtcDuration,
+    /// Times of the alerts posted in the last hour, oldest first.
+    posted: VecDeque<DateTime<Utc>>,
+    /// Last post of each rule and subject.
+    last_posted: HashMap<(String, String), DateTime<Utc>>,
+    held_back: usize,
+}
+impl ChannelPolicy {
+    pub fn new(config: &AlertChannelConfig) -> Self {
+        let quiet_hours = config.quiet_hours.as_deref().and_then(|range| {
+            QuietHours::parse(range)
+                .inspect_err(|e| error!("Channel `{}`: {}. Quiet hours ignored.", config.name, e))
+                .ok()
+        });
+        Self {
+            quiet_hours,
+            max_per_hour: config.max_per_hour,
+            dedupe: UtcDuration::seconds(config.dedupe_secs as i64),
+            ..Self::default()
+        }
+    }
+
+    /// Whether `alert` may be posted `at`: the alerts held back since the last post if so.
+    pub fn admit(&mut self, alert: &Alert, at: DateTime<Utc>) -> Result<usize, HeldBack> {
+        let key = (alert.rule.clone(), alert.subject.clone());
+        let verdict = if self.quiet_hours.is_some_and(|quiet| quiet.contains(at)) {
+            Err(HeldBack::QuietHours)
+        } else if self.last_posted.get(&key).is_some_and(|last| at - *last < self.dedupe) {
+            Err(HeldBack::Duplicate)
+        } else {
+            while self.posted.front().is_some_and(|posted| at - *posted >= UtcDuration::hours(1)) {
+                self.posted.pop_front();
+            }
+            if self.max_per_hour > 0 && self.posted.len() >= self.max_per_hour {
+                Err(HeldBack::RateLimited)
+            } else {
+                Ok(())
+            }
+        };
+        match verdict {
+            Ok(()) => {
+                self.posted.push_back(at);
+                self.last_posted.insert(key, at);
+                self.last_posted.retain(|_, last| at - *last < self.dedupe);
+                Ok(std::mem::take(&mut self.held_back))
+            }
+            Err(reason) => {
+                self.held_back += 1;
+                Err(reason)
+            }
+        }
+    }
+}
+
+/// Stage of the lifecycle of an alert.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Fired,
+    Acknowledged,
+    Resolved,
+}
+impl AlertState {
+    pub fn to_str(&self) -> &str {
+        match self {
+            AlertState::Fired => "fired",
+            AlertState::Acknowledged => "acknowledged",
+            AlertState::Resolved => "resolved",
+        }
+    }
+}
+impl std::str::FromStr for AlertState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fired" => Ok(AlertState::Fired),
+            "acknowledged" => Ok(AlertState::Acknowledged),
+            "resolved" => Ok(AlertState::Resolved),
+            _ => Err(format!("Unknown alert state `{}`", s)),
+        }
+    }
+}
+
+/// Lifecycle of the alerts of a rule and subject, from their first firing to their resolution.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AlertRecord {
+    /// `rule:subject`.
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub rule: String,
+    pub subject: String,
+    /// Message of the last alert.
+    pub message: String,
+    pub state: AlertState,
+    pub fired_at: String,
+    pub last_fired_at: String,
+    /// Alerts raised since `fired_at`.
+    pub count: u64,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<String>,
+    pub resolved_at: Option<String>,
+}
+impl AlertRecord {
+    pub fn id_of(rule: &str, subject: &str) -> String {
+        format!("{}:{}", rule, subject)
+    }
+
+    fn fired(alert: &Alert) -> Self {
+        Self {
+            id: Self::id_of(&alert.rule, &alert.subject),
+            rule: alert.rule.clone(),
+            subject: alert.subject.clone(),
+            message: alert.message.clone(),
+            state: AlertState::Fired,
+            fired_at: alert.at.clone(),
+            last_fired_at: alert.at.clone(),
+            count: 1,
+            acknowledged_by: None,
+            acknowledged_at: None,
+            resolved_at: None,
+        }
+    }
+}
+
+/// Alerts not to notify: those of `rule`, about `subject`, or both, until `until` if set.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AlertMute {
+    /// `rule:subject`, `*` standing for any.
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub rule: Option<String>,
+    pub subject: Option<String>,
+    pub until: Option<String>,
+    pub by: String,
+    pub at: String,
+}
+impl AlertMute {
+    pub fn id_of(rule: Option<&str>, subject: Option<&str>) -> String {
+        format!("{}:{}", rule.unwrap_or("*"), subject.unwrap_or("*"))
+    }
+
+    /// Whether `alert` is muted `at`. Subjects are compared ignoring case, tickers and providers
+    /// being written either way.
+    pub fn matches(&self, alert: &Alert, at: DateTime<Utc>) -> bool {
+        let active = self.until.as_deref().and_then(parse_timestamp).is_none_or(|until| at < until);
+        active
+            && self.rule.as_deref().is_none_or(|rule| rule == alert.rule)
+            && self.subject.as_deref().is_none_or(|subject| subject.eq_ignore_ascii_case(&alert.subject))
+    }
+}
+
+/// Records `alert` in `storage`. Returns whether it should be notified: neither acknowledged nor
+/// muted.
+pub async fn record(storage: &dyn Storage, alert: &Alert) -> Result<bool, OpError> {
+    let muted = storage.alert_mutes().await?.iter().any(|mute| mute.matches(alert, clock::now()));
+    let record = match storage.alert(&AlertRecord::id_of(&alert.rule, &alert.subject)).await? {
+        Some(mut record) if record.state != AlertState::Resolved => {
+            record.message = alert.message.clone();
+            record.last_fired_at = alert.at.clone();
+            record.count += 1;
+            record
+        }
+        _ => AlertRecord::fired(alert),
+    };
+    storage.save_alert(&record).await?;
+    Ok(!muted && record.state == AlertState::Fired)
+}
+
+/// Acknowledges the alert of `rule` and `subject`. Returns `None` when it is not fired.
+pub async fn acknowledge(storage: &dyn Storage, rule: &str, subject: &str, by: &str) -> Result<Option<AlertRecord>, OpError> {
+    let Some(mut record) = storage.alert(&AlertRecord::id_of(rule, subject)).await? else {
+        return Ok(None);
+    };
+    if record.state != AlertState::Fired {
+        return Ok(None);
+    }
+    record.state = AlertState::Acknowledged;
+    record.acknowledged_by = Some(by.to_string());
+    record.acknowledged_at = Some(now());
+    storage.save_alert(&record).await?;
+    Ok(Some(record))
+}
+
+/// Resolves the alert of `rule` and `subject`. Returns `None` when it is not fired nor acknowledged.
+pub async fn resolve(storage: &dyn Storage, rule: &str, subject: &str) -> Result<Option<AlertRecord>, OpError> {
+    let Some(mut record) = storage.alert(&AlertRecord::id_of(rule, subject)).await? else {
+        return Ok(None);
+    };
+    if record.state == AlertState::Resolved {
+        return Ok(None);
+    }
+    record.state = AlertState::Resolved;
+    record.resolved_at = Some(now());
+    storage.save_alert(&record).await?;
+    Ok(Some(record))
+}
+
+/// Mutes the alerts of `rule`, about `subject`, or both, for `hours` or until unmuted.
+pub async fn mute(storage: &dyn Storage, rule: Option<&str>, subject: Option<&str>, hours: Option<i64>, by: &str) -> Result<AlertMute, OpError> {
+    let mute = AlertMute {
+        id: AlertMute::id_of(rule, subject),
+        rule: rule.map(str::to_string),
+        subject: subject.map(str::to_string),
+        until: hours.map(|hours| (clock::now() + UtcDuration::hours(hours)).to_rfc3339_opts(SecondsFormat::Secs, true)),
+        by: by.to_string(),
+        at: now(),
+    };
+    storage.save_alert_mute(&mute).await?;
+    Ok(mute)
+}
+
+/// Removes the mute of `rule` and `subject`. Returns `false` when there is none.
+pub async fn unmute(storage: &dyn Storage, rule: Option<&str>, subject: Option<&str>) -> Result<bool, OpError> {
+    storage.remove_alert_mute(&AlertMute::id_of(rule, subject)).await
+}
+
+/// Variables of a body template, see `templates.rs`.
+fn payload_context(alert: &Alert, text: &str, held_back: usize, channel: &str) -> serde_json::Value {
+    json!({
+        "rule": alert.rule,
+        "subject": alert.subject,
+        "message": alert.message,
+        "at": alert.at,
+        "text": text,
+        "held_back": held_back,
+        "channel": channel,
+    })
+}
+
+/// Dispatches alerts to the configured channels.
+pub struct Alerter {
+    client: Arc<Client>,
+    config: AlertsConfig,
+    /// One per channel, in the order of `config.channels`.
+    policies: Vec<StdMutex<ChannelPolicy>>,
+    /// Body template of each channel, `None` for the default body.
+    templates: Vec<Option<PayloadTemplate>>,
+    /// Where the lifecycle of the alerts is tracked, when set.
+    storage: Option<Arc<dyn Storage>>,
+}
+impl Alerter {
+    pub fn new(client: Arc<Client>, config: AlertsConfig) -> Self {
+        let policies = config.channels.iter().map(|channel| StdMutex::new(ChannelPolicy::new(channel))).collect();
+        let templates = config
+            .channels
+            .iter()
+            .map(|channel| {
+                PayloadTemplate::of_channel(channel)
+                    .inspect_err(|e| error!("Channel `{}`: {}. Default body posted.", channel.name, e))
+                    .ok()
+                    .flatten()
+            })
+            .collect();
+        Self { client, config, policies, templates, storage: None }
+    }
+
+    /// Tracks the lifecycle of the alerts in `storage`, so acknowledged and muted alerts are not
+    /// notified.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Logs `alert` and posts it to every channel whose policy admits it, unless it is
+    /// acknowledged or muted. Delivery failures are logged, not returned.
+    pub async fn raise(&self, alert: &Alert) {
+        warn!("Alert raised. | {}", alert.text());
+        if let Some(storage) = &self.storage {
+            match record(storage.as_ref(), alert).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!("Alert acknowledged or muted, not posted. | {}", alert.text());
+                    return;
+                }
+                // Better a notification too many than a missed one.
+                Err(e) => error!("Failed to record alert: {}", e),
+            }
+        }
+        let at = clock::now();
+        for index in 0..self.config.channels.len() {
+            self.post(index, alert, at).await;
+        }
+    }
+
+    /// Posts `alert` to the channel named `channel` if its policy admits it, without tracking its
+    /// lifecycle. Returns whether it was admitted.
+    pub async fn notify(&self, alert: &Alert, channel: &str) -> bool {
+        match self.config.channels.iter().position(|c| c.name == channel) {
+            Some(index) => self.post(index, alert, clock::now()).await,
+            None => {
+                error!("Unknown alert channel `{}`.", channel);
+                false
+            }
+        }
+    }
+
+    /// Names of the channels, in the config order.
+    pub fn channels(&self) -> Vec<String> {
+        self.config.channels.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// Posts `alert` to the channel at `index` if its policy admits it `at`. Returns whether it was
+    /// admitted, delivered or not.
+    async fn post(&self, index: usize, alert: &Alert, at: DateTime<Utc>) -> bool {
+        let channel = &self.config.channels[index];
+        let admitted = self.policies[index].lock().unwrap_or_else(|e| e.into_inner()).admit(alert, at);
+        let (text, held_back) = match admitted {
+            Ok(0) => (alert.text(), 0),
+            Ok(held_back) => (format!("{} ({} more alerts held back)", alert.text(), held_back), held_back),
+            Err(reason) => {
+                debug!("Alert not posted to channel `{}`: {}", channel.name, reason.to_str());
+                return false;
+            }
+        };
+        let body = self.templates[index].as_ref().and_then(|template| {
+            template
+                .render(payload_context(alert, &text, held_back, &channel.name))
+                .inspect_err(|e| error!("Channel `{}`: {}. Default body posted.", channel.name, e))
+                .ok()
+                .map(|body| (template.content_type.as_str(), body))
+        });
+        let request = match body {
+            Some((content_type, body)) => self.client.post(&channel.webhook_url).header(CONTENT_TYPE, content_type).body(body),
+            None => self.client.post(&channel.webhook_url).json(&json!({ "text": text })),
+        };
+        let result = request.send().await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            error!("Failed to deliver alert to channel `{}`: {}", channel.name, e);
+        }
+        true
+    }
+
+    /// Resolves the alert of `rule` and `subject`, if any, once its check passes again.
+    pub async fn resolve(&self, rule: &str, subject: &str) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+        match resolve(storage.as_ref(), rule, subject).await {
+            Ok(Some(record)) => info!("Alert resolved. | [{}] {} ({} alerts)", rule, subject, record.count),
+            Ok(None) => {}
+            Err(e) => error!("Failed to resolve alert: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    fn policy(quiet_hours: Option<&str>, max_per_hour: usize, dedupe_secs: u64) -> ChannelPolicy {
+        ChannelPolicy::new(&AlertChannelConfig {
+            name: "ops".to_string(),
+            webhook_url: String::new(),
+            quiet_hours: quiet_hours.map(str::to_string),
+            max_per_hour,
+            dedupe_secs,
+            template: None,
+            template_file: None,
+            content_type: "application/json".to_string(),
+        })
+    }
+
+    #[test]
+    fn quiet_hours_wrap_past_midnight() {
+        let quiet = QuietHours::parse("22:00-07:00").unwrap();
+        assert!(quiet.contains(at("2024-05-01T23:30:00Z")));
+        assert!(quiet.contains(at("2024-05-02T06:59:59Z")));
+        assert!(!quiet.contains(at("2024-05-02T07:00:00Z")));
+        assert!(!QuietHours::parse("12:00-13:00").unwrap().contains(at("2024-05-01T23:30:00Z")));
+        assert!(QuietHours::parse("22h-7h").is_err());
+    }
+
+    #[test]
+    fn holds_back_storms_and_counts_them() {
+        let mut policy = policy(Some("22:00-07:00"), 2, 600);
+        let gap = |subject: &str| Alert::new("coverage_gap", subject, "gap");
+
+        assert_eq!(policy.admit(&gap("finnhub"), at("2024-05-01T23:00:00Z")), Err(HeldBack::QuietHours));
+        assert_eq!(policy.admit(&gap("finnhub"), at("2024-05-02T08:00:00Z")), Ok(1));
+        assert_eq!(policy.admit(&gap("finnhub"), at("2024-05-02T08:05:00Z")), Err(HeldBack::Duplicate));
+        assert_eq!(policy.admit(&gap("polygon"), at("2024-05-02T08:06:00Z")), Ok(1));
+        assert_eq!(policy.admit(&gap("gdelt"), at("2024-05-02T08:07:00Z")), Err(HeldBack::RateLimited));
+        // An hour after the first post, and past the dedupe window.
+        assert_eq!(policy.admit(&gap("finnhub"), at("2024-05-02T09:00:00Z")), Ok(1));
+    }
+
+    #[tokio::test]
+    async fn tracks_acknowledged_resolved_and_muted_alerts() {
+        let storage = MemoryStorage::new();
+        let gap = |subject: &str| Alert::new("coverage_gap", subject, "gap");
+
+        assert!(record(&storage, &gap("finnhub")).await.unwrap());
+        assert!(record(&storage, &gap("finnhub")).await.unwrap());
+        let acked = acknowledge(&storage, "coverage_gap", "finnhub", "oncall").await.unwrap().unwrap();
+        assert_eq!((acked.state, acked.count), (AlertState::Acknowledged, 2));
+        assert!(!record(&storage, &gap("finnhub")).await.unwrap());
+        assert!(acknowledge(&storage, "coverage_gap", "finnhub", "oncall").await.unwrap().is_none());
+
+        assert_eq!(resolve(&storage, "coverage_gap", "finnhub").await.unwrap().unwrap().count, 3);
+        assert!(resolve(&storage, "coverage_gap", "finnhub").await.unwrap().is_none());
+        assert!(record(&storage, &gap("finnhub")).await.unwrap());
+        assert_eq!(storage.alerts(Some(AlertState::Fired)).await.unwrap()[0].count, 1);
+
+        mute(&storage, None, Some("POLYGON"), Some(2), "oncall").await.unwrap();
+        assert!(!record(&storage, &gap("polygon")).await.unwrap());
+        assert_eq!(storage.alerts(None).await.unwrap().len(), 2);
+        assert!(unmute(&storage, None, Some("POLYGON")).await.unwrap());
+        assert!(!unmute(&storage, None, Some("POLYGON")).await.unwrap());
+        assert!(record(&storage, &gap("polygon")).await.unwrap());
+    }
+}
@@ -6,6 +6,15 @@
 //! This API, combined with our core stock API, fundamental data, and technical indicator APIs, 
 //! can provide you with a 360-degree view of the financial market and the broader economy.
 //! 
+//! Besides `NEWS_SENTIMENT`, the other Alpha Intelligence functions are available through `poll` with
+//! their fetch type: `alphavantage_top_movers` (`TOP_GAINERS_LOSERS`), `alphavantage_insider_transactions`
+//! (`INSIDER_TRANSACTIONS`, with a `symbol`) and `alphavantage_earnings_calendar` (`EARNINGS_CALENDAR`,
+//! with an optional `symbol` and `horizon`). The earnings calendar is only served as CSV and is
+//! returned as a JSON array.
+//!
+//! The `topics` filter is checked against the documented topics before sending a request, once
+//! `enumerations.enabled` is set (see `enumerations.rs`).
+//!
 //! ## Reference:
 //! 
 //! [official Alpha Vantage Documentation](https://www.alphavantage.co/documentation/).
@@ -19,25 +28,46 @@ use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use mongodb::bson::de;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, from_str, to_value};
 use reqwest::{Client, Response, StatusCode};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, instrument, warn};
 use twitter_v2::oauth2::helpers::variant_name;
 use tokio::sync::Mutex;
 
 use crate::cache::SharedLockedCache;
+use crate::chaos;
+use crate::api_calls;
+use crate::captures;
+use crate::enumerations::{self, Enumerations};
 use crate::config::ValueConfig;
-use crate::utils::{get_resp_value_from_cache_or_fetch, time_yyyy_mmdd_thhmm};
-use crate::options::FetchType;
-use crate::errors::{AbstractApiError, ApiError};
+use crate::utils::{get_resp_value_from_cache_or_fetch, retry};
+use crate::options::{AVFunctionQueryParams, FetchType};
+use crate::encoding::{decode_json_response, decode_text};
+use crate::fixtures;
+use crate::errors::{AbstractApiError, ApiError, ProviderError};
 use crate::options::AVQueryParams as QueryParams;
+use crate::lenient;
+use crate::watermark;
+use crate::normalize::NormalizedArticle;
+use crate::provider::{HealthTracker, NewsProvider, ProviderFuture, ProviderHealth};
 
 
 const BASE_URL: &str = "https://www.alphavantage.co/query";
 pub const BASE_FUNCTION: &str = "NEWS_SENTIMENT";
+pub const TOP_GAINERS_LOSERS_FUNCTION: &str = "TOP_GAINERS_LOSERS";
+pub const INSIDER_TRANSACTIONS_FUNCTION: &str = "INSIDER_TRANSACTIONS";
+pub const EARNINGS_CALENDAR_FUNCTION: &str = "EARNINGS_CALENDAR";
 const FETCH_TYPE_KEY_MAP: &str = "fetch_type";
+pub const PROVIDER_NAME: &str = "alphavantage";
+/// Values of the `topics` filter. The API does not list them, these are the documented ones.
+const TOPICS: [&str; 15] = [
+    "blockchain", "earnings", "ipo", "mergers_and_acquisitions", "financial_markets", "economy_fiscal",
+    "economy_monetary", "economy_macro", "energy_transportation", "finance", "life_sciences",
+    "manufacturing", "real_estate", "retail_wholesale", "technology",
+];
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -89,15 +119,19 @@ pub struct FeedItem {
     pub title: Option<String>,
     pub url: Option<String>,
     pub time_published: Option<String>,
+    #[serde(default, deserialize_with = "lenient::list")]
     pub authors: Vec<String>,
     pub summary: Option<String>,
     pub banner_image: Option<String>,
     pub source: Option<String>,
     pub category_within_source: Option<String>,
     pub source_domain: Option<String>,
+    #[serde(default, deserialize_with = "lenient::list")]
     pub topics: Vec<Topic>,
+    #[serde(default, deserialize_with = "lenient::number")]
     pub overall_sentiment_score: f64,
     pub overall_sentiment_label: Option<String>,
+    #[serde(default, deserialize_with = "lenient::list")]
     pub ticker_sentiment: Vec<TickerSentiment>,
 }
 impl Hash for FeedItem{
@@ -118,25 +152,117 @@ impl PartialEq for FeedItem {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Topic {
     pub topic: Option<String>,
+    #[serde(default, deserialize_with = "lenient::opt_string")]
     pub relevance_score: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TickerSentiment {
     pub ticker: Option<String>,
+    #[serde(default, deserialize_with = "lenient::opt_string")]
     pub relevance_score: Option<String>,
+    #[serde(default, deserialize_with = "lenient::opt_string")]
     pub ticker_sentiment_score: Option<String>,
     pub ticker_sentiment_label: Option<String>,
 }
 
+/// Response of the `TOP_GAINERS_LOSERS` function.
+///
+/// [See example here](https://www.alphavantage.co/query?function=TOP_GAINERS_LOSERS&apikey=demo).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TopMoversResponse {
+    pub metadata: Option<String>,
+    pub last_updated: Option<String>,
+    pub top_gainers: Vec<Mover>,
+    pub top_losers: Vec<Mover>,
+    pub most_actively_traded: Vec<Mover>,
+}
+
+/// A ticker of the top gainers, losers or most traded. Figures are strings, as returned.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Mover {
+    pub ticker: String,
+    pub price: Option<String>,
+    pub change_amount: Option<String>,
+    /// E.g. `12.5%`.
+    pub change_percentage: Option<String>,
+    pub volume: Option<String>,
+}
+
+/// Response of the `INSIDER_TRANSACTIONS` function.
+///
+/// [See example here](https://www.alphavantage.co/query?function=INSIDER_TRANSACTIONS&symbol=IBM&apikey=demo).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct InsiderTransactionsResponse {
+    pub data: Vec<InsiderTransaction>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct InsiderTransaction {
+    pub transaction_date: Option<String>,
+    pub ticker: Option<String>,
+    pub executive: Option<String>,
+    pub executive_title: Option<String>,
+    pub security_type: Option<String>,
+    /// `A` (acquisition) or `D` (disposal).
+    pub acquisition_or_disposal: Option<String>,
+    pub shares: Option<String>,
+    pub share_price: Option<String>,
+}
+
+/// A row of the `EARNINGS_CALENDAR` CSV.
+///
+/// [See example here](https://www.alphavantage.co/query?function=EARNINGS_CALENDAR&horizon=3month&apikey=demo).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsCalendarEntry {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub report_date: Option<String>,
+    pub fiscal_date_ending: Option<String>,
+    /// EPS estimate, empty when there is none.
+    pub estimate: Option<String>,
+    pub currency: Option<String>,
+}
+
+/// Parses the `EARNINGS_CALENDAR` CSV.
+///
+/// Errors and rate limit notices are still answered in JSON, they surface as a `RequestError`.
+/// The error is boxed to keep the result small.
+pub fn parse_earnings_calendar(text: &str) -> Result<Vec<EarningsCalendarEntry>, Box<ApiError>> {
+    if text.trim_start().starts_with('{') {
+        return Err(Box::new(ApiError::RequestError {
+            message: "Earnings calendar request was rejected.".to_string(),
+            status: None,
+            headers: None,
+            body: Some(text.to_string()),
+        }));
+    }
+    csv::Reader::from_reader(text.as_bytes())
+        .deserialize()
+        .collect::<Result<Vec<EarningsCalendarEntry>, _>>()
+        .map_err(|e| Box::new(ApiError::JsonParseError { message: e.to_string() }))
+}
+
+/// Alpha Vantage function of `fetch_type`.
+pub fn function_of(fetch_type: &FetchType) -> &'static str {
+    match fetch_type {
+        FetchType::AlphaVantageTopMovers => TOP_GAINERS_LOSERS_FUNCTION,
+        FetchType::AlphaVantageInsiderTransactions => INSIDER_TRANSACTIONS_FUNCTION,
+        FetchType::AlphaVantageEarningsCalendar => EARNINGS_CALENDAR_FUNCTION,
+        _ => BASE_FUNCTION,
+    }
+}
+
 pub struct AlphaVantageApiClient {
     client: Arc<Client>,
     cache: Arc<Mutex<SharedLockedCache>>,
     config: Arc<ValueConfig>,
+    health: HealthTracker,
 }
 impl AlphaVantageApiClient {
         pub fn new(client: Arc<Client>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Self {
-        Self {client, cache, config}
+        Self {client, cache, config, health: HealthTracker::default()}
     }
 
     async fn get(
@@ -166,36 +292,12 @@ impl AlphaVantageApiClient {
         }
     }
 
-    pub async fn get_(
-        &self, 
-        url: &str, 
-        query_params: QueryParams
-    ) -> Result<Value, ApiError> {
+    /// Sends a GET request and checks the response status.
+    async fn send<Q: Serialize>(&self, url: &str, query_params: &Q) -> Result<Response, ApiError> {
         // Send GET request
-        let response = self
-            .client
-            .get(url)
-            .query(&query_params)
-            .send()
-            .await.map_err(|e| {
-                warn!("AlphaVantage client encountered an error during GET request.");
-                // Check if the error is a network error
-                if e.is_timeout() || e.is_connect() {
-                    ApiError::NetworkError {
-                        message: e.to_string(),
-                        status: Some(StatusCode::REQUEST_TIMEOUT), //Error: 408 - substitutes to `None`: normaly error is not received here, as the rea did not even go through,
-                        headers: None,
-                        body: None,
-                    }
-                } else {
-                    ApiError::RequestError{
-                        message: e.to_string(),
-                        status: Some(StatusCode::BAD_REQUEST),  // Error 400
-                        headers: None,
-                        body: None
-                    }
-                }
-            })?; // Handle request error
+        let response = api_calls::send(PROVIDER_NAME, self.client.get(url).query(query_params))
+            .await
+            .inspect_err(|_| warn!("AlphaVantage client encountered an error during GET request."))?;
 
         // Check for rate limit error in response
         if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
@@ -221,6 +323,15 @@ impl AlphaVantageApiClient {
             ).await;
             return Err(error);
         }
+        Ok(response)
+    }
+
+    pub async fn get_(
+        &self, 
+        url: &str, 
+        query_params: QueryParams
+    ) -> Result<Value, ApiError> {
+        let response = self.send(url, &query_params).await?;
         
         // # Attempt to parse the JSON response.
         // ** The following lines can have performance implications, especially if the response body is large. 
@@ -236,8 +347,15 @@ impl AlphaVantageApiClient {
         // Attempt to parse the JSON response directly.
         // Also the only place the Response super-struct `AlphavantageApiResponse` is Actually used.
         // For data integrity reasons.
-        let response_json: AlphaVantageApiResponse = response.json().await.map_err(|e| {
-            error!("Failed to read body: {:?}", e);
+        // The body is decoded first so HTML error pages and odd encodings surface as `EncodingError`.
+        let body = decode_json_response(PROVIDER_NAME, response).await.map_err(|e| {
+            error!("Failed to read body: {}", e);
+            e
+        })?;
+        fixtures::record("alphavantage", &query_params.function.to_lowercase(), &body);
+        let body = chaos::response_body(PROVIDER_NAME, body);
+        let response_json: AlphaVantageApiResponse = serde_json::from_value(body).map_err(|e| {
+            error!("Failed to parse body: {:?}", e);
             ApiError::JsonParseError { message: e.to_string() }
         })?; // Handle JSON parsing error
         // Bact to Value.
@@ -291,27 +409,88 @@ impl AlphaVantageApiClient {
         }
     }
 
-    fn insert_apikey_and_function(&self, value: Arc<Value>) -> Value{
+    fn insert_apikey_and_function(&self, value: Arc<Value>, function: &str) -> Value{
         let mut value = Arc::try_unwrap(value).unwrap_or_else(|v| (*v).clone());
         if let Value::Object(ref mut map) = value {
             map.insert("apikey".to_string(), Value::String(self.config.api.alphavantage.clone()));
-            map.insert("function".to_string(), Value::String(BASE_FUNCTION.to_string()));
+            map.insert("function".to_string(), Value::String(function.to_string()));
         }
         value
     }
 
+    /// Calls one of the intelligence functions other than `NEWS_SENTIMENT`, with a cache and retries.
+    pub async fn intelligence(&self, fetch_type: &FetchType, query_params: AVFunctionQueryParams) -> Result<Value, ApiError> {
+        let key = format!("{}_{:?}", variant_name(fetch_type), &query_params);
+        retry(PROVIDER_NAME, &self.config, || async {
+            get_resp_value_from_cache_or_fetch(
+                &self.cache,
+                &key,
+                || async { self.intelligence_(fetch_type, &query_params).await },
+                self.config.task.cache_ttl,
+            ).await
+        }).await
+    }
+
+    async fn intelligence_(&self, fetch_type: &FetchType, query_params: &AVFunctionQueryParams) -> Result<Value, ApiError> {
+        let response = self.send(BASE_URL, query_params).await?;
+        let fixture = query_params.function.to_lowercase();
+        let parse_error = |e: serde_json::Error| ApiError::JsonParseError { message: e.to_string() };
+
+        let value = match fetch_type {
+            FetchType::AlphaVantageEarningsCalendar => {
+                let content_type = response.headers().get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_ascii_lowercase());
+                let (url, status) = (response.url().clone(), response.status());
+                let bytes = response.bytes().await.map_err(|e| ApiError::NetworkError {
+                    message: format!("Failed to read body: {}", e),
+                    status: None,
+                    headers: None,
+                    body: None,
+                })?;
+                captures::record(PROVIDER_NAME, &url, status, content_type.as_deref(), &bytes);
+                let text = decode_text(&bytes, content_type.as_deref())?;
+                to_value(parse_earnings_calendar(&text).map_err(|e| *e)?).map_err(parse_error)?
+            }
+            FetchType::AlphaVantageTopMovers | FetchType::AlphaVantageInsiderTransactions => {
+                let body = decode_json_response(PROVIDER_NAME, response).await
+                    .inspect_err(|e| error!("Failed to read body: {}", e))?;
+                let body = chaos::response_body(PROVIDER_NAME, body);
+                if let FetchType::AlphaVantageTopMovers = fetch_type {
+                    to_value(serde_json::from_value::<TopMoversResponse>(body).map_err(parse_error)?)
+                } else {
+                    to_value(serde_json::from_value::<InsiderTransactionsResponse>(body).map_err(parse_error)?)
+                }.map_err(parse_error)?
+            }
+            _ => return Err(ApiError::RequestError {
+                message: format!("Unsupported task: {:?}", fetch_type),
+                status: None,
+                headers: None,
+                body: None,
+            }),
+        };
+        fixtures::record(PROVIDER_NAME, &fixture, &value);
+        Ok(value)
+    }
+
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME, function = args["function"].as_str()))]
     pub async fn poll(&self, args: Arc<Value>) -> Result<Value, ApiError> {
-        // Insert API key & the BASE_FUNVTION into the request body.
-        let args = self.insert_apikey_and_function(args);
+        let fetch_type = args.get(FETCH_TYPE_KEY_MAP) // which does not get popped out of the query params
+            .and_then(|s| s.as_str())
+            .map(FetchType::from_str)
+            .unwrap_or(FetchType::Unknown);
+        // Insert API key & the function of the fetch type into the request body.
+        let args = self.insert_apikey_and_function(args, function_of(&fetch_type));
+        if function_of(&fetch_type) != BASE_FUNCTION {
+            return self.intelligence(&fetch_type, AVFunctionQueryParams::try_from(args)?).await;
+        }
+        enumerations::check(PROVIDER_NAME, &args)
+            .map_err(|message| ApiError::RequestError { message, status: None, headers: None, body: None })?;
         // Retry the request up to the maximum number of retries.
         let mut retry_count = 0;
         let max_retries = self.config.task.max_retries;
         let delay_ms = self.config.task.base_delay_ms as u64;
         let delay = Duration::from_millis(delay_ms);
-        let fetch_type = args.get(FETCH_TYPE_KEY_MAP) // which does not get popped out of the query params
-            .and_then(|s| s.as_str())
-            .map(FetchType::from_str)
-            .unwrap_or(FetchType::Unknown);
         loop {
             match self.get(&fetch_type, BASE_URL, QueryParams::try_from(args.clone())?).await {
                 Ok(api_response) => {
@@ -334,32 +513,132 @@ impl AlphaVantageApiClient {
             }
         }
     }
+
+
+    /// Start of the polling window, from the watermark of the provider, as `time_from` expects it.
+    fn time_from(&self) -> String {
+        watermark::since(PROVIDER_NAME, self.config.request.delay_secs).format("%Y%m%dT%H%M").to_string()
+    }
+
+    /// Fetches the news published since the last polling cycle.
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME))]
+    pub async fn latest(&self) -> Result<Value, ApiError> {
+        // Query parmaters
+        let query = QueryParams::new(
+            &self.config.api.alphavantage, 
+            BASE_FUNCTION,   // You should not use anything else
+            None, // Tickers
+            None, // Topics 
+            Some(&self.time_from()), // Time_from 
+            None, // Time_to
+            None, // Sort
+            None  // Limit
+        );
+
+        // Make the GET request here.
+        self.get_(BASE_URL, query).await
+            .map_err(|e| {
+                error!("Error during GET request: {}", e); // Log the error
+                e // Re-propagate the error without changes
+            })
+    }
+
+    /// Fetches the news published between `from` and `to`.
+    pub async fn window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<NormalizedArticle>, ApiError> {
+        let query = QueryParams::new(
+            &self.config.api.alphavantage,
+            BASE_FUNCTION,
+            None, // Tickers
+            None, // Topics
+            Some(&from.format("%Y%m%dT%H%M").to_string()), // Time_from
+            Some(&to.format("%Y%m%dT%H%M").to_string()), // Time_to
+            None, // Sort
+            Some(1000)  // Limit
+        );
+
+        let value = self.get_(BASE_URL, query).await?;
+        let response: AlphaVantageApiResponse = serde_json::from_value(value)
+            .map_err(|e| ApiError::JsonParseError { message: e.to_string() })?;
+        Ok(response.feed.iter().map(NormalizedArticle::from).collect())
+    }
+
+    /// Fetches the news published since the last cycle mentioning `symbol`.
+    pub async fn symbol_news(&self, symbol: &str) -> Result<Vec<NormalizedArticle>, ApiError> {
+        let query = QueryParams::new(
+            &self.config.api.alphavantage,
+            BASE_FUNCTION,
+            Some(symbol), // Tickers
+            None, // Topics
+            Some(&self.time_from()), // Time_from
+            None, // Time_to
+            None, // Sort
+            None  // Limit
+        );
+
+        let value = self.get_(BASE_URL, query).await?;
+        let response: AlphaVantageApiResponse = serde_json::from_value(value)
+            .map_err(|e| ApiError::JsonParseError { message: e.to_string() })?;
+        Ok(response.feed.iter().map(NormalizedArticle::from).collect())
+    }
+}
+
+impl NewsProvider for AlphaVantageApiClient {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn supports(&self, fetch_type: &FetchType) -> bool {
+        matches!(
+            fetch_type,
+            FetchType::AlphaVantage
+                | FetchType::AlphaVantageTopMovers
+                | FetchType::AlphaVantageInsiderTransactions
+                | FetchType::AlphaVantageEarningsCalendar
+        )
+    }
+
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.poll(args).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.latest().await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn health(&self) -> ProviderHealth {
+        self.health.health(&self.config.api.alphavantage)
+    }
+
+    fn fetch_window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async move { Ok(self.window(from, to).await?) })
+    }
+
+    fn fetch_symbol<'a>(&'a self, symbol: &'a str) -> ProviderFuture<'a, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async move {
+            let result = self.symbol_news(symbol).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_enumerations(&self) -> ProviderFuture<'_, Result<Enumerations, ProviderError>> {
+        let topics = TOPICS.iter().map(|topic| topic.to_string()).collect();
+        Box::pin(async move { Ok(Enumerations::from([("topics".to_string(), topics)])) })
+    }
 }
 
 /// Example function to demonstrate how to use the Alpha Vantage API.
 pub async fn run(client: Arc<Client>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Result<Value, ApiError> {
-    // Create configuration.
-    // Query parmaters
-    let query = QueryParams::new(
-        &config.api.alphavantage, 
-        BASE_FUNCTION,   // You should not use anything else
-        None, // Tickers
-        None, // Topics 
-        Some(&time_yyyy_mmdd_thhmm(config.request.delay_secs).as_str()), // Time_from 
-        None, // Time_to
-        None, // Sort
-        None  // Limit
-    );
-    
     // Request Manger
     let req_manager = AlphaVantageApiClient::new(client, cache, config);
-    // Make the GET request here.
-    let result = req_manager.get_(BASE_URL, query).await
-        .map_err(|e| {
-            error!("Error during GET request: {}", e); // Log the error
-            e // Re-propagate the error without changes
-        })?;
-
-    // Return that result
-    Ok(result)
-}
\ No newline at end of file
+    // Make the GET request and return that result.
+    req_manager.latest().await
+}
@@ -0,0 +1,217 @@
+//! Audit log of the requests sent to the provider APIs.
+//!
+//! With `api_calls.enabled`, every request sent to a provider API is recorded with its provider,
+//! endpoint (the path of the URL, and the `function` of the Alpha Vantage ones), a hash of its
+//! query parameters, HTTP status (or error, without a response), duration and body size (its
+//! `Content-Length`, when announced). The records are logged as `info` events of the `api_calls`
+//! target, which `logging.filters` can keep at another level than the rest (`api_calls=info`).
+//!
+//! With `api_calls.store` as well, the polling loop (or the websocket server, without
+//! `server.poll`) writes them to the `database.collections.api_calls` collection in the
+//! background, for quota audits and provider disputes. Records are dropped, with a warning, when
+//! the database falls `QUEUE` records behind.
+//!
+//! The hash is the SHA-256 of the sorted `key=value` query parameters, API keys left out: identical
+//! requests share it, without the keys being stored.
+//!
+//! The provider API clients send their requests with `send`, which also runs the hooks of the other
+//! modules: `chaos` and `quota` before the request, `clock`, `autoscale` and `costs` on the response.
+//! The feeds (RSS, Reddit) are only recorded, with `execute`.
+
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::ApiCallsConfig;
+use crate::db::DatabaseOps;
+use crate::errors::ApiError;
+use crate::{autoscale, chaos, clock, costs, quota};
+
+/// Records waiting to be written before new ones are dropped.
+const QUEUE: usize = 10_000;
+
+/// Records written at once.
+const BATCH: usize = 100;
+
+/// Query parameters carrying the API keys of the providers.
+pub const SECRET_PARAMS: [&str; 5] = ["apikey", "api_token", "token", "key", "access_key"];
+
+/// A request sent to a provider API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiCall {
+    pub provider: String,
+    pub endpoint: String,
+    pub params_hash: String,
+    /// `None` when no response was received, see `error`.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub bytes: Option<u64>,
+    /// When the request was sent (RFC 3339).
+    pub sent_at: String,
+}
+impl ApiCall {
+    /// Call of `provider` to `url`, answered with `result` after `duration`.
+    pub fn new(provider: &str, url: &Url, result: &Result<Response, reqwest::Error>, duration: Duration) -> Self {
+        let (status, error, bytes) = match result {
+            Ok(response) => (Some(response.status().as_u16()), None, response.content_length()),
+            Err(e) => (e.status().map(|s| s.as_u16()), Some(e.to_string()), None),
+        };
+        let sent_at = clock::now() - chrono::Duration::from_std(duration).unwrap_or_default();
+        Self {
+            provider: provider.to_string(),
+            endpoint: endpoint(url),
+            params_hash: params_hash(url),
+            status,
+            error,
+            duration_ms: duration.as_millis() as u64,
+            bytes,
+            sent_at: sent_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Endpoint of a request to `url`: its path, with its `function` parameter if any.
+pub fn endpoint(url: &Url) -> String {
+    match url.query_pairs().find(|(key, _)| key == "function") {
+        Some((_, function)) => format!("{}?function={}", url.path(), function),
+        None => url.path().to_string(),
+    }
+}
+
+/// Hash of the query parameters of `url`, the API keys left out.
+pub fn params_hash(url: &Url) -> String {
+    let mut params: Vec<String> = url
+        .query_pairs()
+        .filter(|(key, _)| !SECRET_PARAMS.contains(&key.to_ascii_lowercase().as_str()))
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    params.sort();
+    hex::encode(Sha256::digest(params.join("&")))
+}
+
+#[derive(Debug, Default)]
+struct State {
+    enabled: bool,
+    writer: Option<mpsc::Sender<ApiCall>>,
+}
+
+fn state() -> &'static RwLock<State> {
+    static STATE: OnceLock<RwLock<State>> = OnceLock::new();
+    STATE.get_or_init(RwLock::default)
+}
+
+/// Applies the `[api_calls]` section of the config.
+pub fn configure(config: &ApiCallsConfig) {
+    state().write().unwrap().enabled = config.enabled;
+}
+
+/// Writes the records to `db_ops` in a background task, with `api_calls.store`.
+pub fn spawn(db_ops: Arc<DatabaseOps>, config: &ApiCallsConfig) -> Option<JoinHandle<()>> {
+    if !config.enabled || !config.store {
+        return None;
+    }
+    let (tx, mut rx) = mpsc::channel(QUEUE);
+    state().write().unwrap().writer = Some(tx);
+    Some(tokio::spawn(async move {
+        let mut batch = Vec::with_capacity(BATCH);
+        while rx.recv_many(&mut batch, BATCH).await > 0 {
+            if let Err(e) = db_ops.insert_api_calls(&batch).await {
+                warn!("Failed to store {} API call records: {}", batch.len(), e);
+            }
+            batch.clear();
+        }
+    }))
+}
+
+/// Logs `call`, and queues it for the database when stored.
+pub fn record(call: ApiCall) {
+    info!(
+        target: "api_calls",
+        provider = %call.provider,
+        endpoint = %call.endpoint,
+        params_hash = %call.params_hash,
+        status = call.status,
+        duration_ms = call.duration_ms,
+        bytes = call.bytes,
+        "{} {} answered {} in {} ms.",
+        call.provider,
+        call.endpoint,
+        call.status.map(|s| s.to_string()).or_else(|| call.error.clone()).unwrap_or_default(),
+        call.duration_ms
+    );
+    let state = state().read().unwrap();
+    if let Some(writer) = &state.writer {
+        if let Err(mpsc::error::TrySendError::Full(call)) = writer.try_send(call) {
+            warn!("The API call records are not stored fast enough, dropping the call of {} to {}.", call.provider, call.endpoint);
+        }
+    }
+}
+
+/// Sends `request` to the API of `provider` through the hooks, recording the call when enabled.
+///
+/// The request can be failed by `chaos` or held back by `quota` before it is sent. The response
+/// headers are observed for the clock skew and the quota pressure, and the call is counted.
+pub async fn send(provider: &str, request: RequestBuilder) -> Result<Response, ApiError> {
+    chaos::before_request(provider).await?;
+    quota::before_request(provider).await?;
+    let response = execute(provider, request).await?;
+    clock::observe(provider, response.headers());
+    autoscale::observe(provider, response.headers());
+    costs::record_call(provider);
+    Ok(response)
+}
+
+/// Error of a response whose status is not a success, with its status, headers and body.
+pub async fn status_error(response: Response) -> ApiError {
+    let status = response.status();
+    let headers = Some(response.headers().clone());
+    let body = Some(response.text().await.unwrap_or_else(|_| String::from("Failed to read body")));
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        ApiError::RateLimitError { message: "Rate limit exceeded.".to_string(), status: Some(status), headers, body }
+    } else if status.is_server_error() {
+        ApiError::ServerError { message: "Internal server error.".to_string(), status: Some(status), headers, body }
+    } else {
+        ApiError::UnhandledError { message: "Unhandled error.".to_string(), status: Some(status), headers, body }
+    }
+}
+
+/// Sends `request` to `provider` without the hooks of `send`, recording the call when enabled.
+pub async fn execute(provider: &str, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+    if !state().read().unwrap().enabled {
+        return request.send().await;
+    }
+    let (client, request) = request.build_split();
+    let request = request?;
+    let url = request.url().clone();
+    let started = Instant::now();
+    let result = client.execute(request).await;
+    record(ApiCall::new(provider, &url, &result, started.elapsed()));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_the_params_without_the_keys() {
+        let url = Url::parse("https://www.alphavantage.co/query?function=NEWS_SENTIMENT&tickers=AAPL&apikey=one").unwrap();
+        let reordered = Url::parse("https://www.alphavantage.co/query?apikey=two&tickers=AAPL&function=NEWS_SENTIMENT").unwrap();
+        let other = Url::parse("https://www.alphavantage.co/query?function=NEWS_SENTIMENT&tickers=MSFT&apikey=one").unwrap();
+        assert_eq!(params_hash(&url), params_hash(&reordered));
+        assert_ne!(params_hash(&url), params_hash(&other));
+        assert_eq!(params_hash(&url).len(), 64);
+
+        assert_eq!(endpoint(&url), "/query?function=NEWS_SENTIMENT");
+        let marketaux = Url::parse("https://api.marketaux.com/v1/news/all?api_token=secret&symbols=TSLA").unwrap();
+        assert_eq!(endpoint(&marketaux), "/v1/news/all");
+    }
+}
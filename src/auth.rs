@@ -0,0 +1,95 @@
+//! Token authentication of the websocket connections.
+//!
+//! When `[server.auth.tokens]` or `server.auth.signing_key` is set, connections to the TCP
+//! listeners must present a token, except on the public ones (see `public.rs`) and on the Unix
+//! socket, which file permissions protect. The token is taken from the HTTP upgrade, as an
+//! `Authorization: Bearer` header or an `access_token` query parameter, or else from the first
+//! message:
+//!
+//! ```text
+//! {"auth": "<token>"}
+//! ```
+//!
+//! A token is either one of `[server.auth.tokens]` or a token signed with `signing_key`, as issued
+//! by the `issue_token` admin command: `<client>.<expires_at>.<signature>`, where `expires_at` is a
+//! Unix time and `signature` the hex HMAC-SHA256 of `<client>.<expires_at>`. Unauthorized
+//! connections are answered with a `401` response, then closed.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::config::AuthConfig;
+use crate::webhook::authorize_token;
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenAuth {
+    config: AuthConfig,
+}
+impl TokenAuth {
+    pub fn new(config: &AuthConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    /// Whether connections must present a token.
+    pub fn enabled(&self) -> bool {
+        !self.config.tokens.is_empty() || !self.config.signing_key.is_empty()
+    }
+
+    /// Name of the client presenting `token` at the Unix time `now`, if it is valid.
+    pub fn check(&self, token: &str, now: i64) -> Option<String> {
+        if let Some(name) = authorize_token(token, &self.config.tokens) {
+            return Some(name.to_string());
+        }
+        if self.config.signing_key.is_empty() {
+            return None;
+        }
+        let (payload, signature) = token.trim().rsplit_once('.')?;
+        let (client, expires_at) = payload.rsplit_once('.')?;
+        let expires_at: i64 = expires_at.parse().ok()?;
+        let signature = hex::decode(signature).ok()?;
+        mac(&self.config.signing_key, payload).verify_slice(&signature).ok()?;
+        (expires_at > now && !client.is_empty()).then(|| client.to_string())
+    }
+
+    /// Token of `client` valid until the Unix time `expires_at`, `None` without a signing key.
+    pub fn sign(&self, client: &str, expires_at: i64) -> Option<String> {
+        if self.config.signing_key.is_empty() {
+            return None;
+        }
+        let payload = format!("{}.{}", client, expires_at);
+        let signature = hex::encode(mac(&self.config.signing_key, &payload).finalize().into_bytes());
+        Some(format!("{}.{}", payload, signature))
+    }
+}
+
+fn mac(key: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_listed_and_signed_tokens() {
+        let mut config = AuthConfig::default();
+        assert!(!TokenAuth::new(&config).enabled());
+        config.tokens.insert("dashboard".to_string(), "s3cret".to_string());
+        let auth = TokenAuth::new(&config);
+        assert!(auth.enabled());
+        assert_eq!(auth.check("s3cret", 0).as_deref(), Some("dashboard"));
+        assert_eq!(auth.check("other", 0), None);
+        assert_eq!(auth.sign("desk", 100), None);
+
+        config.signing_key = "key".to_string();
+        let auth = TokenAuth::new(&config);
+        let token = auth.sign("trading.desk", 100).unwrap();
+        assert_eq!(auth.check(&token, 99).as_deref(), Some("trading.desk"));
+        // Expired, or tampered with.
+        assert_eq!(auth.check(&token, 100), None);
+        assert_eq!(auth.check(&token.replace(".100.", ".200."), 99), None);
+        assert_eq!(auth.check("desk.100.zz", 99), None);
+    }
+}
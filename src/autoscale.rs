@@ -0,0 +1,308 @@
+//! Polling-interval hints derived from the load of the providers.
+//!
+//! Each provider gets a pressure between 0 (idle) and 1 (saturated), the highest of:
+//!
+//! - its fetch latency (moving average) relative to `autoscale.max_latency_ms`,
+//! - its fetches in flight relative to `autoscale.max_in_flight`,
+//! - the share of its quota used, from the `X-RateLimit-Remaining` / `X-RateLimit-Limit` headers
+//!   of its responses, when it sends them.
+//!
+//! The overall pressure is the highest of the providers. Above `autoscale.high_pressure` a polling
+//! interval `autoscale.step` times longer is suggested, below `autoscale.low_pressure` one
+//! `autoscale.step` times shorter, always within `[min_interval_secs, max_interval_secs]`. The
+//! `pressure` admin command returns the metrics and the suggestion; with `autoscale.auto_tune`,
+//! the polling loop applies the suggestion after every cycle.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use tracing::info;
+
+use crate::config::AutoscaleConfig;
+
+/// Weight of the latest fetch in the latency moving average.
+const LATENCY_WEIGHT: f64 = 0.3;
+const REMAINING_HEADERS: [&str; 2] = ["x-ratelimit-remaining", "ratelimit-remaining"];
+const LIMIT_HEADERS: [&str; 2] = ["x-ratelimit-limit", "ratelimit-limit"];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Load {
+    latency_ms: Option<f64>,
+    in_flight: usize,
+    quota_remaining: Option<u64>,
+    quota_limit: Option<u64>,
+}
+
+/// Metrics and pressure of a provider.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ProviderPressure {
+    pub pressure: f64,
+    pub latency_ms: Option<u64>,
+    pub in_flight: usize,
+    pub quota_remaining: Option<u64>,
+    pub quota_limit: Option<u64>,
+}
+
+/// Suggested adjustment of the polling interval.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Adjustment {
+    SlowDown,
+    Keep,
+    SpeedUp,
+}
+
+/// Answer of the `pressure` admin command.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Hint {
+    pub pressure: f64,
+    pub providers: BTreeMap<String, ProviderPressure>,
+    pub interval_secs: u64,
+    pub suggested_interval_secs: u64,
+    pub adjustment: Adjustment,
+    pub auto_tune: bool,
+}
+
+/// Load of every provider, given an `AutoscaleConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct Pressure {
+    config: AutoscaleConfig,
+    loads: HashMap<String, Load>,
+    /// Interval set by the auto-tuning, if it ran.
+    tuned_interval_secs: Option<u64>,
+}
+impl Pressure {
+    pub fn new(config: &AutoscaleConfig) -> Self {
+        Self { config: config.clone(), ..Self::default() }
+    }
+
+    fn load(&mut self, provider: &str) -> &mut Load {
+        self.loads.entry(provider.to_string()).or_default()
+    }
+
+    pub fn record_latency(&mut self, provider: &str, latency: Duration) {
+        let sample = latency.as_secs_f64() * 1000.0;
+        let load = self.load(provider);
+        load.latency_ms = Some(match load.latency_ms {
+            Some(average) => average + LATENCY_WEIGHT * (sample - average),
+            None => sample,
+        });
+    }
+
+    pub fn record_quota(&mut self, provider: &str, remaining: Option<u64>, limit: Option<u64>) {
+        let load = self.load(provider);
+        load.quota_remaining = remaining.or(load.quota_remaining);
+        load.quota_limit = limit.or(load.quota_limit);
+    }
+
+    fn pressure_of(&self, load: &Load) -> f64 {
+        let latency = load.latency_ms.unwrap_or(0.0) / self.config.max_latency_ms.max(1) as f64;
+        let queue = load.in_flight as f64 / self.config.max_in_flight.max(1) as f64;
+        let quota = match (load.quota_remaining, load.quota_limit) {
+            (Some(remaining), Some(limit)) if limit > 0 => 1.0 - remaining.min(limit) as f64 / limit as f64,
+            (Some(0), _) => 1.0,
+            _ => 0.0,
+        };
+        latency.max(queue).max(quota).clamp(0.0, 1.0)
+    }
+
+    pub fn providers(&self) -> BTreeMap<String, ProviderPressure> {
+        self.loads
+            .iter()
+            .map(|(provider, load)| {
+                let pressure = ProviderPressure {
+                    pressure: self.pressure_of(load),
+                    latency_ms: load.latency_ms.map(|ms| ms.round() as u64),
+                    in_flight: load.in_flight,
+                    quota_remaining: load.quota_remaining,
+                    quota_limit: load.quota_limit,
+                };
+                (provider.clone(), pressure)
+            })
+            .collect()
+    }
+
+    /// Highest pressure of the providers.
+    pub fn overall(&self) -> f64 {
+        self.loads.values().map(|load| self.pressure_of(load)).fold(0.0, f64::max)
+    }
+
+    /// Current polling interval: the tuned one, or `configured_secs`, within the bounds.
+    pub fn interval_secs(&self, configured_secs: u64) -> u64 {
+        let (min, max) = self.bounds();
+        self.tuned_interval_secs.unwrap_or(configured_secs).clamp(min, max)
+    }
+
+    fn bounds(&self) -> (u64, u64) {
+        let min = self.config.min_interval_secs.max(1);
+        (min, self.config.max_interval_secs.max(min))
+    }
+
+    /// Metrics and suggested interval, the configured one being `configured_secs`.
+    pub fn hint(&self, configured_secs: u64) -> Hint {
+        let pressure = self.overall();
+        let interval_secs = self.interval_secs(configured_secs);
+        let step = self.config.step.max(1.0);
+        let (adjustment, suggested) = if pressure > self.config.high_pressure {
+            (Adjustment::SlowDown, (interval_secs as f64 * step).ceil() as u64)
+        } else if pressure < self.config.low_pressure {
+            (Adjustment::SpeedUp, (interval_secs as f64 / step).floor() as u64)
+        } else {
+            (Adjustment::Keep, interval_secs)
+        };
+        let (min, max) = self.bounds();
+        let suggested_interval_secs = suggested.clamp(min, max);
+        Hint {
+            pressure,
+            providers: self.providers(),
+            interval_secs,
+            suggested_interval_secs,
+            adjustment: if suggested_interval_secs == interval_secs { Adjustment::Keep } else { adjustment },
+            auto_tune: self.config.auto_tune,
+        }
+    }
+
+    /// Applies the suggested interval when `autoscale.auto_tune` is set, returning the interval to
+    /// wait before the next cycle.
+    pub fn tune(&mut self, configured_secs: u64) -> u64 {
+        if !self.config.auto_tune {
+            return configured_secs;
+        }
+        let hint = self.hint(configured_secs);
+        if hint.adjustment != Adjustment::Keep {
+            info!(
+                "Polling pressure is {:.2}: interval moved from {}s to {}s.",
+                hint.pressure, hint.interval_secs, hint.suggested_interval_secs
+            );
+        }
+        self.tuned_interval_secs = Some(hint.suggested_interval_secs);
+        hint.suggested_interval_secs
+    }
+}
+
+fn state() -> &'static Mutex<Pressure> {
+    static STATE: OnceLock<Mutex<Pressure>> = OnceLock::new();
+    STATE.get_or_init(Mutex::default)
+}
+
+fn with_state<T>(f: impl FnOnce(&mut Pressure) -> T) -> T {
+    f(&mut state().lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Applies the `[autoscale]` section of the config.
+pub fn configure(config: &AutoscaleConfig) {
+    with_state(|pressure| pressure.config = config.clone());
+}
+
+/// Records the quota headers of a `provider` response.
+pub fn observe(provider: &str, headers: &HeaderMap) {
+    let header = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| headers.get(*name))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+    };
+    let (remaining, limit) = (header(&REMAINING_HEADERS), header(&LIMIT_HEADERS));
+    if remaining.is_some() || limit.is_some() {
+        with_state(|pressure| pressure.record_quota(provider, remaining, limit));
+    }
+}
+
+/// Counts a fetch of `provider` in flight until dropped, so cancelled fetches are counted out.
+struct InFlight<'a> {
+    provider: &'a str,
+}
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        with_state(|pressure| {
+            let load = pressure.load(self.provider);
+            load.in_flight = load.in_flight.saturating_sub(1);
+        });
+    }
+}
+
+/// Awaits a `fetch` of `provider`, recording its latency and counting it in flight meanwhile.
+pub async fn track<F: Future>(provider: &str, fetch: F) -> F::Output {
+    with_state(|pressure| pressure.load(provider).in_flight += 1);
+    let _in_flight = InFlight { provider };
+    let started = Instant::now();
+    let output = fetch.await;
+    with_state(|pressure| pressure.record_latency(provider, started.elapsed()));
+    output
+}
+
+/// Metrics and suggested interval, the configured one being `configured_secs`.
+pub fn hint(configured_secs: u64) -> Hint {
+    with_state(|pressure| pressure.hint(configured_secs))
+}
+
+/// Interval to wait before the next polling cycle, see `Pressure::tune`.
+pub fn tune(configured_secs: u64) -> Duration {
+    Duration::from_secs(with_state(|pressure| pressure.tune(configured_secs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AutoscaleConfig {
+        AutoscaleConfig {
+            max_latency_ms: 1_000,
+            max_in_flight: 4,
+            min_interval_secs: 60,
+            max_interval_secs: 900,
+            step: 2.0,
+            auto_tune: true,
+            ..AutoscaleConfig::default()
+        }
+    }
+
+    #[test]
+    fn pressure_is_the_highest_of_latency_queue_and_quota() {
+        let mut pressure = Pressure::new(&config());
+        pressure.record_latency("marketaux", Duration::from_millis(200));
+        pressure.record_latency("marketaux", Duration::from_millis(1_200));
+        pressure.load("marketaux").in_flight = 1;
+        pressure.record_quota("alphavantage", Some(10), Some(100));
+
+        let providers = pressure.providers();
+        assert_eq!(providers["marketaux"].latency_ms, Some(500));
+        assert_eq!(providers["marketaux"].pressure, 0.5);
+        assert_eq!(providers["alphavantage"].pressure, 0.9);
+        assert_eq!(pressure.overall(), 0.9);
+
+        pressure.record_quota("fmp", Some(0), None);
+        assert_eq!(pressure.overall(), 1.0);
+    }
+
+    #[test]
+    fn suggests_intervals_within_the_bounds() {
+        let mut pressure = Pressure::new(&config());
+        let idle = pressure.hint(300);
+        assert_eq!((idle.adjustment, idle.suggested_interval_secs), (Adjustment::SpeedUp, 150));
+
+        pressure.record_quota("marketaux", Some(5), Some(100));
+        let busy = pressure.hint(300);
+        assert_eq!((busy.adjustment, busy.suggested_interval_secs), (Adjustment::SlowDown, 600));
+        assert_eq!(pressure.hint(600).suggested_interval_secs, 900);
+        assert_eq!(pressure.hint(900).adjustment, Adjustment::Keep);
+    }
+
+    #[test]
+    fn auto_tuning_applies_the_suggestions() {
+        let mut pressure = Pressure::new(&config());
+        pressure.record_quota("marketaux", Some(5), Some(100));
+        assert_eq!(pressure.tune(300), 600);
+        assert_eq!(pressure.tune(300), 900);
+        assert_eq!(pressure.interval_secs(300), 900);
+
+        let mut manual = Pressure::new(&AutoscaleConfig { auto_tune: false, ..config() });
+        manual.record_quota("marketaux", Some(5), Some(100));
+        assert_eq!(manual.tune(300), 300);
+    }
+}
@@ -0,0 +1,356 @@
+//! Historical backfill.
+//!
+//! Fetches a past date range day by day and stores the articles found, like the polling loop
+//! does. Each day is fetched with `NewsProvider::fetch_day`: Alpha Vantage with `time_from` /
+//! `time_to` over the day, MarketAux with `published_on`, following the pagination up to
+//! `backfill.max_pages_per_day` pages.
+//!
+//! Requests to a provider are spaced by its `backfill.pause_ms` (`backfill.default_pause_ms` for
+//! the others). A rate-limited day is retried up to `backfill.max_retries` times, waiting
+//! `backfill.rate_limit_backoff_secs` more before each retry. Days that still fail are listed in
+//! the `BackfillProgress` and logged, the backfill goes on with the next day.
+//!
+//! ## Usage
+//!
+//! ```text
+//! news_data backfill --from YYYY-MM-DD [--to YYYY-MM-DD] [--providers marketaux,alphavantage]
+//! ```
+//!
+//! The `backfill` admin command takes the same params (`from`, `to`, `providers` as a list), runs the
+//! backfill in the background and returns its progress; without params it only returns the
+//! progress of the latest backfill.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{NaiveDate, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::config::BackfillConfig;
+use crate::errors::{ApiError, ProviderError};
+use crate::limiter;
+use crate::normalize::NormalizedArticle;
+use crate::provider::NewsProvider;
+use crate::storage::Storage;
+use crate::utils::now;
+
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Error)]
+pub enum BackfillError {
+    #[error("Invalid arguments: {0}")]
+    Usage(String),
+
+    #[error("A backfill is already running: {0}/{1} days done")]
+    Running(usize, usize),
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+/// Days and providers to backfill, as sent to the `backfill` admin command.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackfillRequest {
+    pub from: NaiveDate,
+    /// Today when omitted.
+    #[serde(default = "today")]
+    pub to: NaiveDate,
+    /// `backfill.providers` when omitted.
+    #[serde(default)]
+    pub providers: Vec<String>,
+}
+impl BackfillRequest {
+    /// Reads the params of the `backfill` admin command, e.g.
+    /// `{"from": "2024-01-30", "to": "2024-02-02", "providers": ["marketaux"]}`.
+    pub fn from_params(params: Value, config: &BackfillConfig) -> Result<Self, BackfillError> {
+        let request: Self = serde_json::from_value(params).map_err(|e| BackfillError::Usage(e.to_string()))?;
+        request.checked(config)
+    }
+
+    /// Checks the range and providers. `to` defaults to today, `providers` to `backfill.providers`.
+    pub fn new(from: NaiveDate, to: Option<NaiveDate>, providers: Option<Vec<String>>, config: &BackfillConfig) -> Result<Self, BackfillError> {
+        Self { from, to: to.unwrap_or_else(today), providers: providers.unwrap_or_default() }.checked(config)
+    }
+
+    fn checked(mut self, config: &BackfillConfig) -> Result<Self, BackfillError> {
+        if self.providers.is_empty() {
+            self.providers = config.providers.clone();
+        }
+        if self.from > self.to {
+            return Err(BackfillError::Usage(format!("`from` {} is after `to` {}", self.from, self.to)));
+        }
+        if self.to > today() {
+            return Err(BackfillError::Usage(format!("`to` {} is in the future", self.to)));
+        }
+        if self.providers.is_empty() {
+            return Err(BackfillError::Usage("No provider to backfill".to_string()));
+        }
+        Ok(self)
+    }
+
+    /// Days of the range, oldest first.
+    pub fn days(&self) -> Vec<NaiveDate> {
+        self.from.iter_days().take_while(|day| *day <= self.to).collect()
+    }
+}
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+/// Progress of a backfill, updated after every request.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct BackfillProgress {
+    pub from: String,
+    pub to: String,
+    pub providers: Vec<String>,
+    pub days: usize,
+    pub days_done: usize,
+    /// Day being fetched.
+    pub current_day: Option<String>,
+    pub requests: u64,
+    pub fetched: usize,
+    /// New articles stored.
+    pub stored: usize,
+    /// Days that could not be fetched or stored, as `provider day: error`.
+    pub failures: Vec<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+impl BackfillProgress {
+    pub fn is_running(&self) -> bool {
+        !self.started_at.is_empty() && self.finished_at.is_none()
+    }
+}
+
+/// Whether `e` means the provider asked to slow down.
+fn is_rate_limited(e: &ProviderError) -> bool {
+    match e {
+        ProviderError::Api(ApiError::RateLimitError { .. }) => true,
+        ProviderError::Api(ApiError::NetworkError { status, .. }) => *status == Some(StatusCode::TOO_MANY_REQUESTS),
+        _ => false,
+    }
+}
+
+/// Fetches and stores a `BackfillRequest`.
+pub struct Backfill {
+    providers: Vec<Arc<dyn NewsProvider>>,
+    db: Arc<dyn Storage>,
+    config: BackfillConfig,
+    days: Vec<NaiveDate>,
+    progress: Arc<Mutex<BackfillProgress>>,
+}
+impl Backfill {
+    /// Prepares the backfill of `request` among `providers`, reporting to `progress`. Fails when
+    /// a provider is unknown or another backfill reporting to `progress` is running.
+    pub fn new(
+        providers: &[Arc<dyn NewsProvider>],
+        db: Arc<dyn Storage>,
+        request: &BackfillRequest,
+        config: &BackfillConfig,
+        progress: Arc<Mutex<BackfillProgress>>,
+    ) -> Result<Self, BackfillError> {
+        let mut selected = Vec::new();
+        for name in &request.providers {
+            let provider = providers
+                .iter()
+                .find(|p| p.name() == name)
+                .ok_or_else(|| BackfillError::Usage(format!("Unknown provider `{}`", name)))?;
+            selected.push(provider.clone());
+        }
+
+        let days = request.days();
+        {
+            let mut current = progress.lock().unwrap_or_else(|e| e.into_inner());
+            if current.is_running() {
+                return Err(BackfillError::Running(current.days_done, current.days));
+            }
+            *current = BackfillProgress {
+                from: request.from.format(DAY_FORMAT).to_string(),
+                to: request.to.format(DAY_FORMAT).to_string(),
+                providers: request.providers.clone(),
+                days: days.len(),
+                started_at: now(),
+                ..BackfillProgress::default()
+            };
+        }
+        Ok(Self { providers: selected, db, config: config.clone(), days, progress })
+    }
+
+    fn update(&self, f: impl FnOnce(&mut BackfillProgress)) {
+        f(&mut self.progress.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    pub fn progress(&self) -> BackfillProgress {
+        self.progress.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn pause(&self, provider: &str) -> Duration {
+        Duration::from_millis(self.config.pause_ms.get(provider).copied().unwrap_or(self.config.default_pause_ms))
+    }
+
+    /// Fetches and stores every day of the request, returning the final progress.
+    pub async fn run(&self) -> BackfillProgress {
+        let mut next_request: HashMap<&str, Instant> = HashMap::new();
+        for day in &self.days {
+            self.update(|p| p.current_day = Some(day.format(DAY_FORMAT).to_string()));
+            for provider in &self.providers {
+                if let Some(at) = next_request.get(provider.name()) {
+                    sleep_until(*at).await;
+                }
+                let result = self.fetch_day(provider.as_ref(), *day).await;
+                next_request.insert(provider.name(), Instant::now() + self.pause(provider.name()));
+                self.store_day(provider.name(), *day, result).await;
+            }
+
+            self.update(|p| p.days_done += 1);
+            let progress = self.progress();
+            info!(
+                "Backfill {}/{} days ({}%) | {} | {} articles fetched, {} new.",
+                progress.days_done,
+                progress.days,
+                progress.days_done * 100 / progress.days.max(1),
+                day,
+                progress.fetched,
+                progress.stored
+            );
+        }
+        self.update(|p| {
+            p.current_day = None;
+            p.finished_at = Some(now());
+        });
+        self.progress()
+    }
+
+    /// Fetches `day` from `provider`, retrying when rate limited.
+    async fn fetch_day(&self, provider: &dyn NewsProvider, day: NaiveDate) -> Result<Vec<NormalizedArticle>, ProviderError> {
+        let mut retries = 0;
+        loop {
+            self.update(|p| p.requests += 1);
+            match limiter::run(provider.name(), provider.fetch_day(day)).await {
+                Err(e) if is_rate_limited(&e) && retries < self.config.max_retries => {
+                    retries += 1;
+                    let backoff = Duration::from_secs(self.config.rate_limit_backoff_secs * u64::from(retries));
+                    warn!("Backfill of {} from `{}` rate limited, retrying in {:?}.", day, provider.name(), backoff);
+                    sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn store_day(&self, provider: &str, day: NaiveDate, fetched: Result<Vec<NormalizedArticle>, ProviderError>) {
+        let stored = match fetched {
+            Ok(articles) => {
+                self.update(|p| p.fetched += articles.len());
+                self.db.store_articles(&articles).await.map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        match stored {
+            Ok(stored) => self.update(|p| p.stored += stored),
+            Err(e) => {
+                error!("Backfill of {} from `{}` failed: {}", day, provider, e);
+                self.update(|p| p.failures.push(format!("{} {}: {}", provider, day, e)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::DateTime;
+    use serde_json::json;
+
+    use crate::options::FetchType;
+    use crate::provider::{ProviderFuture, ProviderHealth};
+    use crate::storage::MemoryStorage;
+
+    fn date(raw: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(raw, DAY_FORMAT).unwrap()
+    }
+
+    /// Provider publishing one article a day, rate limited on its first request.
+    #[derive(Default)]
+    struct Archive {
+        requests: AtomicUsize,
+    }
+    impl NewsProvider for Archive {
+        fn name(&self) -> &str {
+            "archive"
+        }
+
+        fn supports(&self, _fetch_type: &FetchType) -> bool {
+            false
+        }
+
+        fn fetch(&self, _args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async { Ok(Value::Null) })
+        }
+
+        fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async { Ok(Value::Null) })
+        }
+
+        fn health(&self) -> ProviderHealth {
+            ProviderHealth::Healthy
+        }
+
+        fn fetch_window(&self, from: DateTime<Utc>, _to: DateTime<Utc>) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+            if self.requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                let limited = ApiError::RateLimitError { message: "slow down".to_string(), status: None, headers: None, body: None };
+                return Box::pin(async { Err(ProviderError::Api(limited)) });
+            }
+            let article = NormalizedArticle::test(&from.date_naive().to_string()).provider("archive");
+            Box::pin(async move { Ok(vec![article]) })
+        }
+    }
+
+    #[test]
+    fn parses_the_date_range() {
+        let config = BackfillConfig::default();
+        let parse = |params: Value| BackfillRequest::from_params(params, &config);
+        let request = parse(json!({ "from": "2024-01-30", "to": "2024-02-02", "providers": ["marketaux"] })).unwrap();
+        assert_eq!(request.providers, vec!["marketaux"]);
+        assert_eq!(request.days(), vec![date("2024-01-30"), date("2024-01-31"), date("2024-02-01"), date("2024-02-02")]);
+        let request = parse(json!({ "from": "2024-01-30" })).unwrap();
+        assert_eq!((request.to, request.providers), (today(), config.providers.clone()));
+
+        assert!(parse(json!({ "to": "2024-01-01" })).is_err());
+        assert!(parse(json!({ "from": "2024-02-01", "to": "2024-01-01" })).is_err());
+        assert!(parse(json!({ "from": "01/02/2024" })).is_err());
+        assert!(parse(json!({ "from": "2999-01-01" })).is_err());
+        assert!(parse(json!({ "from": "2024-01-30", "provider": "marketaux" })).is_err());
+    }
+
+    #[tokio::test]
+    async fn backfills_every_day_retrying_rate_limits() {
+        let config = BackfillConfig { default_pause_ms: 0, rate_limit_backoff_secs: 0, ..BackfillConfig::default() };
+        let request = BackfillRequest { from: date("2024-01-01"), to: date("2024-01-03"), providers: vec!["archive".to_string()] };
+        let providers: Vec<Arc<dyn NewsProvider>> = vec![Arc::new(Archive::default())];
+        let storage = Arc::new(MemoryStorage::new());
+        let progress = Arc::new(Mutex::new(BackfillProgress::default()));
+
+        let backfill = Backfill::new(&providers, storage.clone(), &request, &config, progress.clone()).unwrap();
+        assert!(matches!(
+            Backfill::new(&providers, storage.clone(), &request, &config, progress.clone()),
+            Err(BackfillError::Running(0, 3))
+        ));
+        let unknown = BackfillRequest { providers: vec!["fmp".to_string()], ..request.clone() };
+        assert!(Backfill::new(&providers, storage.clone(), &unknown, &config, Arc::default()).is_err());
+
+        let done = backfill.run().await;
+        assert_eq!((done.days_done, done.requests, done.stored), (3, 4, 3));
+        assert!(done.failures.is_empty() && !done.is_running());
+        assert_eq!(storage.articles().len(), 3);
+    }
+}
@@ -0,0 +1,268 @@
+//! Backup and restore of the stored data without `mongodump`.
+//!
+//! A backup is a gzip-compressed JSON Lines file. Each line holds one document in relaxed
+//! Extended JSON, tagged with the data kind it was read from:
+//!
+//! ```text
+//! {"kind":"articles","document":{"_id":{"$oid":"..."},"provider":"marketaux",...}}
+//! ```
+//!
+//! Restoring upserts every document by `_id`, so restoring the same archive twice is harmless.
+//!
+//! ## Usage
+//!
+//! ```text
+//! news_data backup <file> [--kinds articles,social_sentiment,trending] [--since DATE] [--until DATE]
+//! news_data restore <file>
+//! ```
+//!
+//! `--since` is inclusive and `--until` exclusive. Both are compared with the article `published_at`
+//! or the sentiment `date`, so any prefix of an RFC 3339 timestamp (e.g. `2024-08-01`) works.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::ReplaceOptions;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tracing::info;
+
+use crate::db::{DataKind, DatabaseOps};
+
+/// Kinds included in a backup unless `--kinds` says otherwise.
+pub const DEFAULT_KINDS: [DataKind; 3] = [DataKind::Articles, DataKind::SocialSentiment, DataKind::Trending];
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Malformed archive at line {line}: {message}")]
+    Format { line: usize, message: String },
+}
+
+/// Options of the `backup` command.
+#[derive(Debug, Clone)]
+pub struct BackupOptions {
+    pub kinds: Vec<DataKind>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+impl Default for BackupOptions {
+    fn default() -> Self {
+        Self {
+            kinds: DEFAULT_KINDS.to_vec(),
+            since: None,
+            until: None,
+        }
+    }
+}
+impl BackupOptions {
+    /// Date filter for `kind`, empty when no bound was given.
+    fn filter(&self, kind: DataKind) -> Document {
+        let field = match kind {
+            DataKind::Articles => "published_at",
+            DataKind::SocialSentiment | DataKind::Trending => "date",
+            _ => return Document::new(),
+        };
+        let mut range = Document::new();
+        if let Some(since) = &self.since {
+            range.insert("$gte", since);
+        }
+        if let Some(until) = &self.until {
+            range.insert("$lt", until);
+        }
+        if range.is_empty() {
+            Document::new()
+        } else {
+            doc! { field: range }
+        }
+    }
+}
+
+/// Number of documents written or restored per data kind.
+#[derive(Debug, Default)]
+pub struct BackupSummary {
+    pub counts: Vec<(DataKind, u64)>,
+}
+impl BackupSummary {
+    fn add(&mut self, kind: DataKind) {
+        match self.counts.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, count)) => *count += 1,
+            None => self.counts.push((kind, 1)),
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|(_, c)| c).sum()
+    }
+}
+
+/// Dumps the selected collections into a compressed archive at `path`.
+pub async fn backup(db_ops: &DatabaseOps, path: &Path, options: &BackupOptions) -> Result<BackupSummary, BackupError> {
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(GzEncoder::new(file, Compression::default()));
+    let mut summary = BackupSummary::default();
+
+    for kind in &options.kinds {
+        info!("Backing up {}...", kind.to_str());
+        let mut cursor = db_ops
+            .collection(*kind)
+            .find(options.filter(*kind), None)
+            .await
+            .map_err(|e| BackupError::Database(e.to_string()))?;
+        while let Some(document) = cursor.try_next().await.map_err(|e| BackupError::Database(e.to_string()))? {
+            let line = json!({
+                "kind": kind.to_str(),
+                "document": Bson::Document(document).into_relaxed_extjson(),
+            });
+            writeln!(writer, "{}", line)?;
+            summary.add(*kind);
+        }
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| BackupError::Io(e.into_error()))?
+        .finish()?
+        .flush()?;
+    Ok(summary)
+}
+
+/// Loads an archive written by `backup`, upserting every document by `_id`.
+pub async fn restore(db_ops: &DatabaseOps, path: &Path) -> Result<BackupSummary, BackupError> {
+    let reader = BufReader::new(GzDecoder::new(File::open(path)?));
+    let mut summary = BackupSummary::default();
+    let upsert = ReplaceOptions::builder().upsert(true).build();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (kind, document) = parse_line(&line).map_err(|message| BackupError::Format { line: index + 1, message })?;
+        let filter = match document.get("_id") {
+            Some(id) => doc! { "_id": id.clone() },
+            None => document.clone(),
+        };
+        db_ops
+            .collection(kind)
+            .replace_one(filter, document, upsert.clone())
+            .await
+            .map_err(|e| BackupError::Database(e.to_string()))?;
+        summary.add(kind);
+    }
+    Ok(summary)
+}
+
+/// Rewrites an archive in place, passing every document to `edit` with its kind.
+///
+/// `edit` returns `false` to drop the document; it may also modify it. Returns how many documents
+/// were dropped or modified. The archive is replaced atomically once fully written.
+pub fn rewrite_archive<F>(path: &Path, mut edit: F) -> Result<u64, BackupError>
+where
+    F: FnMut(&str, &mut Value) -> bool,
+{
+    let reader = BufReader::new(GzDecoder::new(File::open(path)?));
+    let tmp_path = path.with_extension("rewrite.tmp");
+    let mut writer = BufWriter::new(GzEncoder::new(File::create(&tmp_path)?, Compression::default()));
+    let mut changed = 0;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut value: Value = serde_json::from_str(&line)
+            .map_err(|e| BackupError::Format { line: index + 1, message: e.to_string() })?;
+        let kind = value.get("kind").and_then(Value::as_str).unwrap_or_default().to_string();
+        let Some(document) = value.get_mut("document") else {
+            return Err(BackupError::Format { line: index + 1, message: "Missing `document`".to_string() });
+        };
+        let before = document.clone();
+        if !edit(&kind, document) {
+            changed += 1;
+            continue;
+        }
+        if *document != before {
+            changed += 1;
+        }
+        writeln!(writer, "{}", value)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| BackupError::Io(e.into_error()))?
+        .finish()?
+        .flush()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(changed)
+}
+
+fn parse_line(line: &str) -> Result<(DataKind, Document), String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let kind = value
+        .get("kind")
+        .and_then(Value::as_str)
+        .and_then(|kind| kind.parse::<DataKind>().ok())
+        .ok_or("Missing or unknown `kind`")?;
+    let document = value.get("document").cloned().ok_or("Missing `document`")?;
+    match Bson::try_from(document).map_err(|e| e.to_string())? {
+        Bson::Document(document) => Ok((kind, document)),
+        _ => Err("`document` is not an object".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_on_the_given_bounds_only() {
+        assert_eq!(BackupOptions::default().filter(DataKind::Articles), Document::new());
+
+        let since = BackupOptions { since: Some("2024-08-01".to_string()), ..BackupOptions::default() };
+        assert_eq!(since.filter(DataKind::Articles), doc! { "published_at": { "$gte": "2024-08-01" } });
+        let range = BackupOptions { until: Some("2024-09-01".to_string()), ..since };
+        assert_eq!(range.filter(DataKind::Trending), doc! { "date": { "$gte": "2024-08-01", "$lt": "2024-09-01" } });
+        // Kinds without a date are dumped whole.
+        assert_eq!(range.filter(DataKind::Polls), Document::new());
+    }
+
+    #[test]
+    fn parses_the_archive_lines() {
+        let (kind, document) = parse_line(r#"{"kind":"articles","document":{"id":"1"}}"#).unwrap();
+        assert_eq!((kind, document), (DataKind::Articles, doc! { "id": "1" }));
+
+        assert!(parse_line("{").is_err());
+        assert_eq!(parse_line(r#"{"kind":"unknown","document":{}}"#).unwrap_err(), "Missing or unknown `kind`");
+        assert_eq!(parse_line(r#"{"kind":"articles"}"#).unwrap_err(), "Missing `document`");
+        assert_eq!(parse_line(r#"{"kind":"articles","document":1}"#).unwrap_err(), "`document` is not an object");
+    }
+
+    #[test]
+    fn reports_the_line_of_a_malformed_archive() {
+        let path = std::env::temp_dir().join(format!("news_data_backup_{}.jsonl.gz", std::process::id()));
+        let mut writer = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        writeln!(writer, r#"{{"kind":"articles","document":{{"id":"1"}}}}"#).unwrap();
+        writeln!(writer).unwrap();
+        writeln!(writer, r#"{{"kind":"articles"}}"#).unwrap();
+        writer.finish().unwrap();
+
+        match rewrite_archive(&path, |_, _| true) {
+            Err(BackupError::Format { line, .. }) => assert_eq!(line, 3),
+            other => panic!("expected a format error, got {:?}", other),
+        }
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("rewrite.tmp")).ok();
+    }
+}
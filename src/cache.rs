@@ -128,6 +128,19 @@ impl SharedLockedCache {
             ))),
         }
     }
+
+    /// Removes every entry whose value matches `predicate`, returning how many were removed.
+    pub async fn remove_where<F: Fn(&Value) -> bool>(&self, predicate: F) -> usize {
+        let mut cache = self.inner.write().await;
+        let keys: Vec<String> = cache.iter()
+            .filter(|(_, (value, _))| predicate(value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &keys {
+            cache.pop(key);
+        }
+        keys.len()
+    }
 }
 
 impl Cache for SharedLockedCache {
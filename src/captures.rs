@@ -0,0 +1,326 @@
+//! Raw provider responses captured to disk, for debugging.
+//!
+//! With `captures.enabled`, the body of every provider response read for parsing (the error
+//! statuses are kept in the `ApiError` instead) is written as received, before any decoding, to a
+//! file of `captures.dir`: a first line of JSON with the provider, URL (API keys
+//! left out), status, `Content-Type` and capture time, then the raw bytes. The files are named
+//! after their capture time, and the oldest ones are removed past `captures.max_files` files or
+//! `captures.max_bytes` bytes: a ring buffer of the latest responses.
+//!
+//! A parsing failure seen in production can then be reproduced offline, from the exact bytes the
+//! provider sent: `load` a capture and feed its body to `encoding::decode_json_bytes` with its
+//! content type, then to the model of the provider. Unlike the fixtures (see `fixtures.rs`), the
+//! responses that failed to decode are kept as well.
+//!
+//! Redacting an article deletes the captures containing its id (see `purge`), so a redacted story
+//! does not survive in the raw responses.
+//!
+//! The files are written by a dedicated thread, off the fetches: captures are dropped, with a
+//! warning, when the disk falls `QUEUE` captures behind. The thread lists the directory once, then
+//! keeps the names and sizes of the captures in memory. Failures to write are logged and otherwise
+//! ignored: capturing must never break a fetch.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::api_calls::SECRET_PARAMS;
+use crate::clock;
+use crate::config::CapturesConfig;
+
+const EXTENSION: &str = "capture";
+
+/// Captures waiting to be written before new ones are dropped.
+const QUEUE: usize = 100;
+
+/// What a captured body is the response of.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Capture {
+    pub provider: String,
+    /// URL of the request, API keys left out.
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    /// RFC 3339.
+    pub captured_at: String,
+}
+impl Capture {
+    pub fn new(provider: &str, url: &Url, status: StatusCode, content_type: Option<&str>, captured_at: DateTime<Utc>) -> Self {
+        Self {
+            provider: provider.to_string(),
+            url: redact(url),
+            status: status.as_u16(),
+            content_type: content_type.map(str::to_string),
+            captured_at: captured_at.to_rfc3339(),
+        }
+    }
+}
+
+/// `url` without the query parameters carrying API keys.
+fn redact(url: &Url) -> String {
+    let mut redacted = url.clone();
+    let params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !SECRET_PARAMS.contains(&key.to_ascii_lowercase().as_str()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    redacted.set_query(None);
+    if !params.is_empty() {
+        redacted.query_pairs_mut().extend_pairs(params);
+    }
+    redacted.to_string()
+}
+
+/// Captures of `config.dir`, written in turn.
+#[derive(Debug, Default)]
+pub struct Captures {
+    config: CapturesConfig,
+    /// Captures written, to tell apart the ones of the same microsecond.
+    written: u64,
+    /// Captures of the directory with their size, oldest first. Listed at the first write.
+    index: Option<VecDeque<(PathBuf, u64)>>,
+    /// Total size of the captures of `index`.
+    total: u64,
+}
+impl Captures {
+    pub fn new(config: &CapturesConfig) -> Self {
+        Self { config: config.clone(), ..Self::default() }
+    }
+
+    /// Applies a new `[captures]` section, the directory being listed again when it changed.
+    pub fn configure(&mut self, config: &CapturesConfig) {
+        if config.dir != self.config.dir {
+            self.index = None;
+        }
+        self.config = config.clone();
+    }
+
+    /// Captures already in the directory, oldest first.
+    fn list(dir: &Path) -> io::Result<VecDeque<(PathBuf, u64)>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.path().extension().is_some_and(|extension| extension == EXTENSION) {
+                files.push((entry.path(), entry.metadata()?.len()));
+            }
+        }
+        // Named after their capture time.
+        files.sort();
+        Ok(files.into())
+    }
+
+    /// Writes `body` with its `capture` line, then removes the oldest captures past the limits.
+    /// Returns the path of the new capture.
+    pub fn write(&mut self, capture: &Capture, body: &[u8], at: DateTime<Utc>) -> io::Result<PathBuf> {
+        let dir = PathBuf::from(&self.config.dir);
+        if self.index.is_none() {
+            fs::create_dir_all(&dir)?;
+            let index = Self::list(&dir)?;
+            self.total = index.iter().map(|(_, len)| len).sum();
+            self.index = Some(index);
+        }
+        let name = format!("{}-{:06}-{}.{}", at.format("%Y%m%dT%H%M%S%.6fZ"), self.written % 1_000_000, capture.provider, EXTENSION);
+        self.written += 1;
+        let mut contents = serde_json::to_vec(capture).map_err(io::Error::other)?;
+        contents.push(b'\n');
+        contents.extend_from_slice(body);
+        let path = dir.join(name);
+        fs::write(&path, &contents)?;
+        if let Some(index) = &mut self.index {
+            index.push_back((path.clone(), contents.len() as u64));
+        }
+        self.total += contents.len() as u64;
+        self.prune()?;
+        Ok(path)
+    }
+
+    /// Removes the oldest captures until at most `max_files` of at most `max_bytes` in total are left,
+    /// the newest one kept regardless.
+    fn prune(&mut self) -> io::Result<()> {
+        let Some(index) = &mut self.index else {
+            return Ok(());
+        };
+        while index.len() > 1 && (index.len() > self.config.max_files.max(1) || self.total > self.config.max_bytes) {
+            let Some((path, len)) = index.pop_front() else {
+                break;
+            };
+            self.total -= len;
+            match fs::remove_file(&path) {
+                Ok(()) => debug!("Removed capture {}", path.display()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads back a capture written to `path`, with its raw body.
+pub fn load(path: &Path) -> io::Result<(Capture, Vec<u8>)> {
+    let contents = fs::read(path)?;
+    let split = contents.iter().position(|b| *b == b'\n').ok_or_else(|| io::Error::other("Missing capture line"))?;
+    let capture = serde_json::from_slice(&contents[..split]).map_err(io::Error::other)?;
+    Ok((capture, contents[split + 1..].to_vec()))
+}
+
+/// Deletes the captures of `dir` whose body contains `needle`, as is or JSON-escaped (`\/`).
+/// Returns how many were deleted.
+fn remove_mentioning(dir: &Path, needle: &str) -> io::Result<usize> {
+    let escaped = needle.replace('/', "\\/");
+    let mut removed = 0;
+    for (path, _) in Captures::list(dir)? {
+        let (_, body) = load(&path)?;
+        let body = String::from_utf8_lossy(&body);
+        if body.contains(needle) || body.contains(&escaped) {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Deletes the captures of `captures.dir` containing `needle`, e.g. the id of a redacted article.
+/// The writer skips the deleted files when pruning. Returns how many were deleted.
+pub fn purge(needle: &str) -> usize {
+    let dir = PathBuf::from(&state().lock().unwrap_or_else(|e| e.into_inner()).config.dir);
+    if needle.is_empty() || !dir.is_dir() {
+        return 0;
+    }
+    remove_mentioning(&dir, needle).unwrap_or_else(|e| {
+        warn!("Failed to purge the captures of {}: {}", dir.display(), e);
+        0
+    })
+}
+
+/// A capture to write, with the config to write it with.
+struct Job {
+    config: CapturesConfig,
+    capture: Capture,
+    body: Vec<u8>,
+    at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct State {
+    config: CapturesConfig,
+    writer: Option<SyncSender<Job>>,
+}
+
+fn state() -> &'static Mutex<State> {
+    static STATE: OnceLock<Mutex<State>> = OnceLock::new();
+    STATE.get_or_init(Mutex::default)
+}
+
+/// Applies the `[captures]` section of the config.
+pub fn configure(config: &CapturesConfig) {
+    state().lock().unwrap_or_else(|e| e.into_inner()).config = config.clone();
+}
+
+/// Starts the thread writing the captures.
+fn start_writer() -> io::Result<SyncSender<Job>> {
+    let (tx, rx) = mpsc::sync_channel::<Job>(QUEUE);
+    std::thread::Builder::new().name("captures".to_string()).spawn(move || {
+        let mut captures = Captures::default();
+        for job in rx {
+            captures.configure(&job.config);
+            match captures.write(&job.capture, &job.body, job.at) {
+                Ok(path) => debug!("Captured the response of {} to {}", job.capture.provider, path.display()),
+                Err(e) => warn!("Failed to capture the response of {} in {}: {}", job.capture.provider, job.config.dir, e),
+            }
+        }
+    })?;
+    Ok(tx)
+}
+
+/// Queues the raw `body` of a response of `provider` to `url` for writing, with `captures.enabled`.
+pub fn record(provider: &str, url: &Url, status: StatusCode, content_type: Option<&str>, body: &[u8]) {
+    let mut state = state().lock().unwrap_or_else(|e| e.into_inner());
+    if !state.config.enabled {
+        return;
+    }
+    if state.writer.is_none() {
+        match start_writer() {
+            Ok(writer) => state.writer = Some(writer),
+            Err(e) => {
+                warn!("Failed to start the capture writer: {}", e);
+                return;
+            }
+        }
+    }
+    let at = clock::now();
+    let job = Job { config: state.config.clone(), capture: Capture::new(provider, url, status, content_type, at), body: body.to_vec(), at };
+    let Some(writer) = &state.writer else {
+        return;
+    };
+    match writer.try_send(job) {
+        Err(TrySendError::Full(job)) => warn!("The captures are not written fast enough, dropping the response of {}.", job.capture.provider),
+        // Started again by the next capture.
+        Err(TrySendError::Disconnected(_)) => state.writer = None,
+        Ok(()) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_raw_responses() {
+        let dir = std::env::temp_dir().join(format!("news_data_captures_{}", std::process::id()));
+        let config = CapturesConfig { enabled: true, dir: dir.to_string_lossy().to_string(), max_files: 3, max_bytes: 1_000 };
+        let mut captures = Captures::new(&config);
+        let url = Url::parse("https://api.marketaux.com/v1/news/all?api_token=secret&symbols=TSLA").unwrap();
+        let at = DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        let mut paths = Vec::new();
+        for (seconds, body) in [b"{\"data\": [".as_slice(), b"\xEF\xBB\xBF{}", b"<html>", b"{}"].into_iter().enumerate() {
+            let capture = Capture::new("marketaux", &url, StatusCode::OK, Some("application/json"), at + chrono::Duration::seconds(seconds as i64));
+            paths.push(captures.write(&capture, body, at + chrono::Duration::seconds(seconds as i64)).unwrap());
+        }
+
+        // The oldest one is gone, the others are read back byte for byte.
+        assert!(!paths[0].exists());
+        let (capture, body) = load(&paths[1]).unwrap();
+        assert_eq!(body, b"\xEF\xBB\xBF{}");
+        assert_eq!(capture.url, "https://api.marketaux.com/v1/news/all?symbols=TSLA");
+        assert_eq!((capture.status, capture.content_type.as_deref()), (200, Some("application/json")));
+        assert_eq!(capture.captured_at, "2024-05-01T10:00:01+00:00");
+
+        // Past `max_bytes`, fewer are kept.
+        let mut captures = Captures::new(&CapturesConfig { max_bytes: 600, ..config });
+        let capture = Capture::new("marketaux", &url, StatusCode::OK, None, at);
+        captures.write(&capture, &[b'x'; 300], at + chrono::Duration::seconds(10)).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removes_the_captures_mentioning_an_article() {
+        let dir = std::env::temp_dir().join(format!("news_data_captures_purge_{}", std::process::id()));
+        let config = CapturesConfig { enabled: true, dir: dir.to_string_lossy().to_string(), max_files: 10, max_bytes: 10_000 };
+        let mut captures = Captures::new(&config);
+        let url = Url::parse("https://www.alphavantage.co/query?function=NEWS_SENTIMENT").unwrap();
+        let at = DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        let bodies = [
+            br#"{"feed": [{"url": "https://example.com/a"}]}"#.as_slice(),
+            br#"{"feed": [{"url": "https:\/\/example.com\/a"}]}"#,
+            br#"{"feed": [{"url": "https://example.com/b"}]}"#,
+        ];
+        for (seconds, body) in bodies.into_iter().enumerate() {
+            let at = at + chrono::Duration::seconds(seconds as i64);
+            captures.write(&Capture::new("alphavantage", &url, StatusCode::OK, None, at), body, at).unwrap();
+        }
+
+        assert_eq!(remove_mentioning(&dir, "https://example.com/a").unwrap(), 2);
+        let (_, body) = load(&Captures::list(&dir).unwrap()[0].0).unwrap();
+        assert_eq!(body, bodies[2]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+//! Fault injection (chaos mode).
+//!
+//! Retries, partial cycles and outbox writes are only exercised when something fails, which
+//! upstream providers and MongoDB rarely do on demand. With `chaos.enabled`, faults are injected
+//! at random at the configured rates:
+//!
+//! - upstream timeouts, after `chaos.timeout_delay_ms`, surfacing as `ApiError::NetworkError` (408),
+//! - rate limiting, surfacing as `ApiError::RateLimitError` (429),
+//! - malformed response bodies, in place of the decoded body (failing the model parsing),
+//! - MongoDB write failures, surfacing as `OpError::InsertionError`.
+//!
+//! Every injected fault is logged with a `Chaos:` prefix. Never enable it in production.
+
+use std::sync::{OnceLock, RwLock};
+
+use rand::{thread_rng, Rng};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::time::{sleep, Duration};
+use tracing::warn;
+
+use crate::config::ChaosConfig;
+use crate::errors::ApiError;
+
+/// Fault to inject before an upstream request is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamFault {
+    Timeout,
+    RateLimited,
+}
+
+/// Decides which faults to inject, given a `ChaosConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct Chaos {
+    config: ChaosConfig,
+}
+impl Chaos {
+    pub fn new(config: &ChaosConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    fn targets(&self, provider: &str) -> bool {
+        self.config.enabled && (self.config.providers.is_empty() || self.config.providers.iter().any(|p| p == provider))
+    }
+
+    fn roll(rate: f64) -> bool {
+        rate > 0.0 && thread_rng().gen::<f64>() < rate
+    }
+
+    /// Fault to inject in the next request to `provider`, if any.
+    pub fn upstream_fault(&self, provider: &str) -> Option<UpstreamFault> {
+        if !self.targets(provider) {
+            None
+        } else if Self::roll(self.config.timeout_rate) {
+            Some(UpstreamFault::Timeout)
+        } else if Self::roll(self.config.rate_limit_rate) {
+            Some(UpstreamFault::RateLimited)
+        } else {
+            None
+        }
+    }
+
+    /// Whether to replace the next response body of `provider` with a malformed one.
+    pub fn malforms_body(&self, provider: &str) -> bool {
+        self.targets(provider) && Self::roll(self.config.malformed_body_rate)
+    }
+
+    /// Whether to fail the next database write.
+    pub fn fails_write(&self) -> bool {
+        self.config.enabled && Self::roll(self.config.write_failure_rate)
+    }
+}
+
+fn state() -> &'static RwLock<Chaos> {
+    static STATE: OnceLock<RwLock<Chaos>> = OnceLock::new();
+    STATE.get_or_init(RwLock::default)
+}
+
+fn chaos() -> Chaos {
+    state().read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Applies the `[chaos]` section of the config.
+pub fn configure(config: &ChaosConfig) {
+    if config.enabled {
+        warn!("Chaos mode is enabled: faults will be injected in upstream requests and database writes.");
+    }
+    *state().write().unwrap_or_else(|e| e.into_inner()) = Chaos::new(config);
+}
+
+/// Fails a request to `provider` before it is sent, when a fault is injected.
+pub async fn before_request(provider: &str) -> Result<(), ApiError> {
+    let chaos = chaos();
+    match chaos.upstream_fault(provider) {
+        Some(UpstreamFault::Timeout) => {
+            warn!("Chaos: injected a timeout of the request to {}.", provider);
+            sleep(Duration::from_millis(chaos.config.timeout_delay_ms)).await;
+            Err(ApiError::NetworkError {
+                message: "Chaos: request timed out".to_string(),
+                status: Some(StatusCode::REQUEST_TIMEOUT),
+                headers: None,
+                body: None,
+            })
+        }
+        Some(UpstreamFault::RateLimited) => {
+            warn!("Chaos: injected a rate limit of the request to {}.", provider);
+            Err(ApiError::RateLimitError {
+                message: "Chaos: rate limit exceeded".to_string(),
+                status: Some(StatusCode::TOO_MANY_REQUESTS),
+                headers: None,
+                body: None,
+            })
+        }
+        None => Ok(()),
+    }
+}
+
+/// `body` of a `provider` response, or a malformed body when a fault is injected.
+pub fn response_body(provider: &str, body: Value) -> Value {
+    if !chaos().malforms_body(provider) {
+        return body;
+    }
+    warn!("Chaos: injected a malformed body in the response of {}.", provider);
+    malformed(&body)
+}
+
+/// A body of the wrong shape: scalars where objects are expected and the other way around.
+fn malformed(body: &Value) -> Value {
+    match body {
+        Value::Array(_) => json!({ "chaos": "malformed body" }),
+        Value::Object(map) => Value::Object(map.keys().map(|k| (k.clone(), json!("chaos"))).collect()),
+        _ => json!([{ "chaos": "malformed body" }]),
+    }
+}
+
+/// Error message of a database write to `collection`, when a fault is injected.
+pub fn write_failure(collection: &str) -> Option<String> {
+    if !chaos().fails_write() {
+        return None;
+    }
+    warn!("Chaos: injected a failure of a write to {}.", collection);
+    Some(format!("Chaos: write to `{}` failed", collection))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(rate: f64, providers: &[&str]) -> ChaosConfig {
+        ChaosConfig {
+            enabled: true,
+            providers: providers.iter().map(|p| p.to_string()).collect(),
+            timeout_rate: 0.0,
+            rate_limit_rate: rate,
+            malformed_body_rate: rate,
+            write_failure_rate: rate,
+            ..ChaosConfig::default()
+        }
+    }
+
+    #[test]
+    fn injects_faults_at_the_configured_rates() {
+        let always = Chaos::new(&config(1.0, &[]));
+        assert_eq!(always.upstream_fault("fmp"), Some(UpstreamFault::RateLimited));
+        assert!(always.malforms_body("fmp") && always.fails_write());
+
+        let never = Chaos::new(&config(0.0, &[]));
+        assert_eq!(never.upstream_fault("fmp"), None);
+        assert!(!never.malforms_body("fmp") && !never.fails_write());
+
+        let disabled = Chaos::new(&ChaosConfig { enabled: false, ..config(1.0, &[]) });
+        assert_eq!(disabled.upstream_fault("fmp"), None);
+        assert!(!disabled.fails_write());
+    }
+
+    #[test]
+    fn only_targets_the_configured_providers() {
+        let chaos = Chaos::new(&config(1.0, &["marketaux"]));
+        assert!(chaos.upstream_fault("marketaux").is_some());
+        assert!(chaos.upstream_fault("alphavantage").is_none());
+        assert!(!chaos.malforms_body("alphavantage"));
+    }
+
+    #[test]
+    fn malformed_bodies_fail_the_models() {
+        let raw = json!({ "meta": { "found": 1, "returned": 1, "limit": 3, "page": 1 }, "data": [] });
+        assert!(serde_json::from_value::<crate::marketaux::MarketAuxResponse>(raw.clone()).is_ok());
+        assert!(serde_json::from_value::<crate::marketaux::MarketAuxResponse>(malformed(&raw)).is_err());
+        let articles = json!([{ "id": 1, "headline": "Chipmaker raises guidance" }]);
+        assert!(serde_json::from_value::<Vec<crate::finnhub::FinnhubArticle>>(malformed(&articles)).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Clock skew detection against provider `Date` headers.
+//!
+//! Polling windows (`published_after`, `time_from`, ...) are computed from the local clock, so a
+//! skewed clock silently shifts them and articles fall between two windows. Every provider response
+//! is compared with the local time; when the estimated skew exceeds `clock.max_skew_secs` a warning is
+//! logged and, with `clock.auto_correct`, the windows are computed from the corrected time instead.
+//!
+//! The estimate is the median of the latest skew seen per provider, so one provider with a wrong
+//! clock (or a slow response) does not move it much. `Date` headers have a one second resolution.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use chrono::{DateTime, Duration as UtcDuration, Utc};
+use reqwest::header::{HeaderMap, DATE};
+use tracing::{debug, warn};
+
+use crate::config::ClockConfig;
+
+#[derive(Default)]
+struct ClockState {
+    config: RwLock<ClockConfig>,
+    /// Latest `provider time - local time` per provider, in seconds.
+    skews: Mutex<HashMap<String, i64>>,
+}
+impl ClockState {
+    fn observe(&self, provider: &str, headers: &HeaderMap, local: DateTime<Utc>) {
+        let Some(remote) = headers
+            .get(DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+        else {
+            return;
+        };
+        let skew = (remote.with_timezone(&Utc) - local).num_seconds();
+        debug!("Clock skew against {}: {}s", provider, skew);
+        self.skews.lock().unwrap_or_else(|e| e.into_inner()).insert(provider.to_string(), skew);
+
+        let max_skew = self.config.read().unwrap_or_else(|e| e.into_inner()).max_skew_secs;
+        if skew.unsigned_abs() > max_skew {
+            warn!(
+                "Local clock is {}s {} {}. Polling windows are shifted by as much.",
+                skew.abs(),
+                if skew > 0 { "behind" } else { "ahead of" },
+                provider
+            );
+        }
+    }
+
+    fn skew_secs(&self) -> Option<i64> {
+        let skews = self.skews.lock().unwrap_or_else(|e| e.into_inner());
+        let mut values: Vec<i64> = skews.values().copied().collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable();
+        Some(values[values.len() / 2])
+    }
+
+    fn now(&self, local: DateTime<Utc>) -> DateTime<Utc> {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner()).clone();
+        match self.skew_secs() {
+            Some(skew) if config.auto_correct && skew.unsigned_abs() > config.max_skew_secs => local + UtcDuration::seconds(skew),
+            _ => local,
+        }
+    }
+}
+
+fn state() -> &'static ClockState {
+    static STATE: OnceLock<ClockState> = OnceLock::new();
+    STATE.get_or_init(ClockState::default)
+}
+
+/// Applies the `[clock]` section of the config.
+pub fn configure(config: &ClockConfig) {
+    *state().config.write().unwrap_or_else(|e| e.into_inner()) = config.clone();
+}
+
+/// Records the skew between the `Date` header of a `provider` response and the local clock.
+pub fn observe(provider: &str, headers: &HeaderMap) {
+    state().observe(provider, headers, Utc::now());
+}
+
+/// Estimated `provider time - local time` in seconds, if any response was observed.
+pub fn skew_secs() -> Option<i64> {
+    state().skew_secs()
+}
+
+/// Current time used for polling windows: the local time, corrected by the estimated skew when
+/// `clock.auto_correct` is set and the skew exceeds `clock.max_skew_secs`.
+pub fn now() -> DateTime<Utc> {
+    state().now(Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn local() -> DateTime<Utc> {
+        "2024-05-01T10:00:00Z".parse().unwrap()
+    }
+
+    fn observe(clock: &ClockState, provider: &str, skew_secs: i64) {
+        let remote = local() + UtcDuration::seconds(skew_secs);
+        let mut headers = HeaderMap::new();
+        headers.insert(DATE, HeaderValue::from_str(&remote.to_rfc2822()).unwrap());
+        clock.observe(provider, &headers, local());
+    }
+
+    #[test]
+    fn estimates_the_skew_by_the_median_provider() {
+        let clock = ClockState::default();
+        assert_eq!(clock.skew_secs(), None);
+        observe(&clock, "finnhub", 120);
+        observe(&clock, "polygon", 118);
+        // One provider with a wrong clock does not move the estimate much.
+        observe(&clock, "gdelt", -3600);
+        assert_eq!(clock.skew_secs(), Some(118));
+        // Only the latest skew of a provider counts.
+        observe(&clock, "gdelt", 121);
+        assert_eq!(clock.skew_secs(), Some(120));
+
+        clock.observe("rss", &HeaderMap::new(), local());
+        assert_eq!(clock.skew_secs(), Some(120));
+    }
+
+    #[test]
+    fn corrects_the_time_beyond_the_tolerated_skew() {
+        let clock = ClockState::default();
+        observe(&clock, "finnhub", 120);
+        assert_eq!(clock.now(local()), local());
+
+        *clock.config.write().unwrap() = ClockConfig { max_skew_secs: 30, auto_correct: true };
+        assert_eq!(clock.now(local()), local() + UtcDuration::seconds(120));
+
+        observe(&clock, "finnhub", 20);
+        assert_eq!(clock.now(local()), local());
+    }
+}
@@ -0,0 +1,97 @@
+//! Stored articles as Arrow record batches, for applications embedding the crate (`arrow-ipc`
+//! feature).
+//!
+//! Large result sets are handed to analytics tooling (DataFusion, Polars, DuckDB, pyarrow...)
+//! without going through JSON: either as a [`RecordBatch`] in the same process, or as an Arrow IPC
+//! stream buffer to send elsewhere.
+//!
+//! ```no_run
+//! use news_data::columnar;
+//! use news_data::storage::ArticleFilter;
+//! # async fn run(storage: &dyn news_data::Storage) -> Result<(), columnar::ColumnarError> {
+//! let filter = ArticleFilter { tickers: vec!["NVDA".to_string()], ..ArticleFilter::default() };
+//! let batch = columnar::query(storage, &filter, 10_000).await?;
+//! let buffer = columnar::to_ipc_stream(&[batch])?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Batches have one row per article, with the typed columns of the Parquet exports (see
+//! `export.rs`): `published_at` is a UTC timestamp, lists such as `tickers` are lists of strings.
+
+use arrow_array::RecordBatch;
+use arrow_ipc::reader::StreamReader;
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::ArrowError;
+use thiserror::Error;
+
+use crate::export::typed_record_batch;
+use crate::normalize::NormalizedArticle;
+use crate::storage::{ArticleFilter, Storage};
+
+#[derive(Debug, Error)]
+pub enum ColumnarError {
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] ArrowError),
+}
+
+/// One row per article, in the order given.
+pub fn to_record_batch(articles: &[NormalizedArticle]) -> Result<RecordBatch, ArrowError> {
+    typed_record_batch(&articles.iter().collect::<Vec<_>>())
+}
+
+/// Stored articles matching `filter`, newest first, at most `limit` (every one when 0).
+pub async fn query(storage: &dyn Storage, filter: &ArticleFilter, limit: i64) -> Result<RecordBatch, ColumnarError> {
+    let articles = storage.filtered_articles(filter, limit).await.map_err(|e| ColumnarError::Database(e.to_string()))?;
+    Ok(to_record_batch(&articles)?)
+}
+
+/// `batches` as an Arrow IPC stream. They must share the schema of the first one.
+pub fn to_ipc_stream(batches: &[RecordBatch]) -> Result<Vec<u8>, ArrowError> {
+    let schema = match batches.first() {
+        Some(batch) => batch.schema(),
+        None => to_record_batch(&[])?.schema(),
+    };
+    let mut writer = StreamWriter::try_new(Vec::new(), &schema)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.into_inner()
+}
+
+/// Batches of an Arrow IPC stream, e.g. one written by `to_ipc_stream`.
+pub fn from_ipc_stream(buffer: &[u8]) -> Result<Vec<RecordBatch>, ArrowError> {
+    StreamReader::try_new(buffer, None)?.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, StringArray, TimestampMicrosecondArray};
+    use serde_json::json;
+
+    use crate::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn round_trips_query_results_through_ipc() {
+        let storage = MemoryStorage::new();
+        let article = |id: &str, tickers: &[&str]| NormalizedArticle::test(id).published_at(&format!("2024-05-01T0{}:00:00Z", id)).tickers(tickers);
+        storage.store_articles(&[article("1", &["NVDA"]), article("2", &["AMD"]), article("3", &["NVDA"])]).await.unwrap();
+
+        let filter = ArticleFilter { tickers: vec!["NVDA".to_string()], ..ArticleFilter::default() };
+        let batch = query(&storage, &filter, 0).await.unwrap();
+        assert_eq!(batch.num_rows(), 2);
+
+        let batches = from_ipc_stream(&to_ipc_stream(std::slice::from_ref(&batch)).unwrap()).unwrap();
+        assert_eq!(batches, vec![batch]);
+        let ids = batches[0].column_by_name("id").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!((ids.value(0), ids.value(1)), ("3", "1"));
+        let published_at = batches[0].column_by_name("published_at").unwrap();
+        assert!(published_at.as_any().downcast_ref::<TimestampMicrosecondArray>().is_some());
+
+        assert!(from_ipc_stream(&to_ipc_stream(&[]).unwrap()).unwrap().is_empty());
+    }
+}
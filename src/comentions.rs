@@ -0,0 +1,150 @@
+//! Ticker co-mentions.
+//!
+//! Tickers tagged on the same article are related: a supplier and its customer, two competitors,
+//! a sector. Over a rolling window (the last `comentions.window_hours` by default), each pair of
+//! tickers counts the articles mentioning both, and each ticker the articles mentioning it. A pair
+//! is kept as an edge from `comentions.min_articles` articles, weighted by its count and by its
+//! Jaccard index (articles mentioning both over articles mentioning either), which tells a strong
+//! tie between two rarely covered tickers from the overlap of two heavily covered ones.
+//!
+//! Articles tagging more than `comentions.max_tickers_per_article` tickers, such as market
+//! wrap-ups, relate everything to everything and are skipped. The `co_mentions` function of the
+//! websocket server returns the graph as nodes and edges, ready for a relationship map, optionally
+//! restricted to the neighbours of some tickers.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::config::CoMentionsConfig;
+use crate::db::OpError;
+use crate::storage::Storage;
+
+/// A ticker and the number of articles mentioning it.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Node {
+    pub ticker: String,
+    pub articles: u64,
+}
+
+/// Two tickers mentioned together, `source` first in alphabetical order.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    /// Articles mentioning both.
+    pub articles: u64,
+    /// Articles mentioning both over articles mentioning either.
+    pub jaccard: f64,
+}
+
+/// Co-mention graph of the articles published between `from` and `to`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CoMentionGraph {
+    pub from: String,
+    pub to: String,
+    /// Articles counted, i.e. tagging between 2 and `max_tickers_per_article` tickers.
+    pub articles: usize,
+    pub nodes: Vec<Node>,
+    /// Most mentioned pairs first.
+    pub edges: Vec<Edge>,
+}
+impl CoMentionGraph {
+    /// Graph of the articles tagged with `ticker_sets`, limited to the edges of `focus` when not empty.
+    pub fn build(from: &str, to: &str, ticker_sets: &[Vec<String>], focus: &[String], config: &CoMentionsConfig) -> Self {
+        let focus: BTreeSet<String> = focus.iter().map(|t| t.to_uppercase()).collect();
+        let mut mentions: BTreeMap<String, u64> = BTreeMap::new();
+        let mut pairs: BTreeMap<(String, String), u64> = BTreeMap::new();
+        let mut articles = 0;
+        for tickers in ticker_sets {
+            let tickers: BTreeSet<String> = tickers.iter().map(|t| t.trim().to_uppercase()).filter(|t| !t.is_empty()).collect();
+            if tickers.len() < 2 || tickers.len() > config.max_tickers_per_article {
+                continue;
+            }
+            articles += 1;
+            let tickers: Vec<String> = tickers.into_iter().collect();
+            for (i, ticker) in tickers.iter().enumerate() {
+                *mentions.entry(ticker.clone()).or_default() += 1;
+                for other in &tickers[i + 1..] {
+                    *pairs.entry((ticker.clone(), other.clone())).or_default() += 1;
+                }
+            }
+        }
+
+        let mut edges: Vec<Edge> = pairs
+            .into_iter()
+            .filter(|((a, b), count)| {
+                *count >= config.min_articles.max(1) && (focus.is_empty() || focus.contains(a) || focus.contains(b))
+            })
+            .map(|((source, target), count)| {
+                let either = mentions[&source] + mentions[&target] - count;
+                Edge { jaccard: count as f64 / either as f64, source, target, articles: count }
+            })
+            .collect();
+        // Stable sort: ties stay in alphabetical order.
+        edges.sort_by(|a, b| b.articles.cmp(&a.articles).then_with(|| b.jaccard.total_cmp(&a.jaccard)));
+        if config.max_edges > 0 {
+            edges.truncate(config.max_edges);
+        }
+
+        let linked: BTreeSet<&String> = edges.iter().flat_map(|e| [&e.source, &e.target]).collect();
+        let nodes = mentions
+            .iter()
+            .filter(|(ticker, _)| linked.contains(ticker))
+            .map(|(ticker, count)| Node { ticker: ticker.clone(), articles: *count })
+            .collect();
+        Self { from: from.to_string(), to: to.to_string(), articles, nodes, edges }
+    }
+}
+
+/// Co-mention graph of the stored articles published between `from` and `to`.
+pub async fn graph(storage: &dyn Storage, from: DateTime<Utc>, to: DateTime<Utc>, focus: &[String], config: &CoMentionsConfig) -> Result<CoMentionGraph, OpError> {
+    let (from, to) = (from.to_rfc3339_opts(SecondsFormat::Secs, true), to.to_rfc3339_opts(SecondsFormat::Secs, true));
+    let ticker_sets = storage.ticker_sets(&from, &to).await?;
+    Ok(CoMentionGraph::build(&from, &to, &ticker_sets, focus, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sets(sets: &[&[&str]]) -> Vec<Vec<String>> {
+        sets.iter().map(|set| set.iter().map(|t| t.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn counts_pairs_and_their_overlap() {
+        let config = CoMentionsConfig { min_articles: 2, max_tickers_per_article: 3, ..CoMentionsConfig::default() };
+        let ticker_sets = sets(&[
+            &["NVDA", "TSM"],
+            &["tsm", "NVDA", "AMD"],
+            &["AMD", "NVDA"],
+            &["NVDA"],
+            &["AAPL", "MSFT", "GOOGL", "AMZN"],
+        ]);
+        let graph = CoMentionGraph::build("from", "to", &ticker_sets, &[], &config);
+        assert_eq!(graph.articles, 3);
+        let edges: Vec<(&str, &str, u64)> = graph.edges.iter().map(|e| (e.source.as_str(), e.target.as_str(), e.articles)).collect();
+        assert_eq!(edges, vec![("AMD", "NVDA", 2), ("NVDA", "TSM", 2)]);
+        // NVDA in 3 counted articles, AMD in 2: both in 2.
+        assert_eq!(graph.edges[0].jaccard, 2.0 / 3.0);
+        assert_eq!(graph.nodes.iter().map(|n| (n.ticker.as_str(), n.articles)).collect::<Vec<_>>(), vec![("AMD", 2), ("NVDA", 3), ("TSM", 2)]);
+
+        let focused = CoMentionGraph::build("from", "to", &ticker_sets, &["tsm".to_string()], &config);
+        assert_eq!(focused.edges.len(), 1);
+        assert_eq!(focused.nodes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn reads_the_window_from_storage() {
+        let storage = crate::storage::MemoryStorage::new();
+        let article = |id: &str, published_at: &str| crate::normalize::NormalizedArticle::test(id).published_at(published_at).tickers(&["NVDA", "TSM"]);
+        storage.store_articles(&[article("1", "2024-05-01T10:00:00Z"), article("2", "2024-05-02T10:00:00Z"), article("3", "2024-04-01T10:00:00Z")]).await.unwrap();
+
+        let at = |t: &str| crate::normalize::parse_timestamp(t).unwrap();
+        let graph = graph(&storage, at("2024-04-30T00:00:00Z"), at("2024-05-07T00:00:00Z"), &[], &CoMentionsConfig::default()).await.unwrap();
+        assert_eq!((graph.articles, graph.edges.len()), (2, 1));
+        assert_eq!(graph.from, "2024-04-30T00:00:00Z");
+    }
+}
@@ -0,0 +1,265 @@
+//! Side-by-side comparison of two providers, to support choosing between redundant feeds.
+//!
+//! The `compare` command fetches the same window from both providers with
+//! `NewsProvider::fetch_window` and reports:
+//! - coverage overlap: the articles found by both (same URL, or else same title), and the ones
+//!   found by only one of them;
+//! - latency to publication: percentiles of the time between the publication of each article and
+//!   its fetch, meaningful for recent windows (the last hour by default);
+//! - sentiment disagreement: among the shared articles labelled by both providers, how many got a
+//!   different harmonized label, and how many an opposite one (bullish against bearish).
+//!
+//! ## Usage
+//!
+//! ```text
+//! news_data compare marketaux alphavantage [--hours 1] [--from RFC3339 --to RFC3339]
+//! ```
+//!
+//! The comparison is printed as JSON.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::clock;
+use crate::freshness::Percentiles;
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+use crate::provider::NewsProvider;
+use crate::sentiment::SentimentLabel;
+
+#[derive(Debug, Error)]
+pub enum CompareError {
+    #[error("Invalid arguments: {0}")]
+    Usage(String),
+
+    #[error("{0} provider fetch failed: {1}")]
+    Provider(String, String),
+}
+
+/// What one of the providers returned.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ProviderSummary {
+    pub provider: String,
+    pub articles: usize,
+    /// Articles the other provider did not return.
+    pub exclusive: usize,
+    pub fetch_ms: u64,
+    /// Time from publication to fetch.
+    pub latency_to_publication_secs: Percentiles,
+}
+
+/// Harmonized sentiment of the shared articles.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq)]
+pub struct SentimentDisagreement {
+    /// Shared articles labelled by both providers.
+    pub compared: usize,
+    /// Labelled differently.
+    pub disagreements: usize,
+    /// Labelled bullish by one provider and bearish by the other.
+    pub opposite: usize,
+    /// Share of `compared` labelled differently.
+    pub rate: Option<f64>,
+}
+
+/// Answer of the `compare` command.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Comparison {
+    pub from: String,
+    pub to: String,
+    pub providers: [ProviderSummary; 2],
+    /// Articles returned by both providers.
+    pub shared: usize,
+    /// `shared` over the articles returned by either provider.
+    pub overlap: Option<f64>,
+    pub sentiment: SentimentDisagreement,
+}
+
+/// Articles of a provider and when they were fetched.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub provider: String,
+    pub articles: Vec<NormalizedArticle>,
+    pub fetched_at: DateTime<Utc>,
+    pub fetch_ms: u64,
+}
+
+/// `url` without scheme, `www.`, query, fragment and trailing slash, lowercased.
+fn url_key(url: &str) -> Option<String> {
+    let url = url.trim().to_lowercase();
+    let url = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let url = url.strip_prefix("www.").unwrap_or(url).trim_end_matches('/');
+    (!url.is_empty()).then(|| url.to_string())
+}
+
+/// Lowercased words of `title`, without punctuation.
+fn title_key(title: &str) -> Option<String> {
+    let words: Vec<String> = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Index of the article of the other sample matching `article`, by URL first, then by title.
+fn find_match(article: &NormalizedArticle, by_url: &HashMap<String, usize>, by_title: &HashMap<String, usize>) -> Option<usize> {
+    let url = article.url.as_deref().and_then(url_key).and_then(|key| by_url.get(&key));
+    url.or_else(|| article.title.as_deref().and_then(title_key).and_then(|key| by_title.get(&key))).copied()
+}
+
+fn is_bullish(label: SentimentLabel) -> bool {
+    matches!(label, SentimentLabel::Bullish | SentimentLabel::SomewhatBullish)
+}
+
+fn is_bearish(label: SentimentLabel) -> bool {
+    matches!(label, SentimentLabel::Bearish | SentimentLabel::SomewhatBearish)
+}
+
+fn latency(sample: &Sample) -> Percentiles {
+    let ages = sample
+        .articles
+        .iter()
+        .filter_map(|a| a.published_at.as_deref().and_then(parse_timestamp))
+        .map(|published| (sample.fetched_at - published).num_seconds().max(0))
+        .collect();
+    Percentiles::of(ages)
+}
+
+/// Compares the articles of `first` and `second` over `[from, to]`.
+pub fn compare_samples(first: &Sample, second: &Sample, from: DateTime<Utc>, to: DateTime<Utc>) -> Comparison {
+    let mut by_url = HashMap::new();
+    let mut by_title = HashMap::new();
+    for (index, article) in second.articles.iter().enumerate() {
+        if let Some(key) = article.url.as_deref().and_then(url_key) {
+            by_url.entry(key).or_insert(index);
+        }
+        if let Some(key) = article.title.as_deref().and_then(title_key) {
+            by_title.entry(key).or_insert(index);
+        }
+    }
+
+    let mut matched = vec![false; second.articles.len()];
+    let mut shared = 0;
+    let mut sentiment = SentimentDisagreement::default();
+    for article in &first.articles {
+        let Some(index) = find_match(article, &by_url, &by_title) else {
+            continue;
+        };
+        if std::mem::replace(&mut matched[index], true) {
+            continue;
+        }
+        shared += 1;
+        if let (Some(a), Some(b)) = (article.sentiment, second.articles[index].sentiment) {
+            sentiment.compared += 1;
+            sentiment.disagreements += usize::from(a != b);
+            sentiment.opposite += usize::from((is_bullish(a) && is_bearish(b)) || (is_bearish(a) && is_bullish(b)));
+        }
+    }
+    sentiment.rate = (sentiment.compared > 0).then(|| sentiment.disagreements as f64 / sentiment.compared as f64);
+
+    let union = first.articles.len() + second.articles.len() - shared;
+    let summary = |sample: &Sample| ProviderSummary {
+        provider: sample.provider.clone(),
+        articles: sample.articles.len(),
+        exclusive: sample.articles.len() - shared,
+        fetch_ms: sample.fetch_ms,
+        latency_to_publication_secs: latency(sample),
+    };
+    Comparison {
+        from: from.to_rfc3339_opts(SecondsFormat::Secs, true),
+        to: to.to_rfc3339_opts(SecondsFormat::Secs, true),
+        providers: [summary(first), summary(second)],
+        shared,
+        overlap: (union > 0).then(|| shared as f64 / union as f64),
+        sentiment,
+    }
+}
+
+async fn sample(provider: &dyn NewsProvider, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Sample, CompareError> {
+    let started = Instant::now();
+    let articles = provider
+        .fetch_window(from, to)
+        .await
+        .map_err(|e| CompareError::Provider(provider.name().to_string(), e.to_string()))?;
+    Ok(Sample {
+        provider: provider.name().to_string(),
+        articles,
+        fetched_at: clock::now(),
+        fetch_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Fetches `[from, to]` from the providers named `first` and `second` at once, and compares them.
+pub async fn compare(
+    providers: &[Arc<dyn NewsProvider>],
+    first: &str,
+    second: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Comparison, CompareError> {
+    if first == second {
+        return Err(CompareError::Usage("Compare two different providers".to_string()));
+    }
+    if from >= to {
+        return Err(CompareError::Usage(format!("`from` ({}) must be before `to` ({})", from, to)));
+    }
+    let find = |name: &str| {
+        providers
+            .iter()
+            .find(|p| p.name() == name)
+            .ok_or_else(|| CompareError::Usage(format!("Unknown provider `{}`", name)))
+    };
+    let (first, second) = (find(first)?, find(second)?);
+    let (first, second) = tokio::join!(sample(first.as_ref(), from, to), sample(second.as_ref(), from, to));
+    Ok(compare_samples(&first?, &second?, from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as UtcDuration;
+    use crate::sentiment::SentimentLabel;
+
+    #[test]
+    fn reports_overlap_latency_and_sentiment_disagreement() {
+        let fetched_at = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        let article = |provider: &str, url: &str, title: &str, published_at: &str, sentiment: Option<SentimentLabel>| {
+            NormalizedArticle::test(url).provider(provider).url(url).title(title).published_at(published_at).sentiment(sentiment)
+        };
+        let first = Sample {
+            provider: "marketaux".to_string(),
+            articles: vec![
+                article("marketaux", "https://www.example.com/a?utm=x", "Chips rally", "2024-05-01T09:50:00Z", Some(SentimentLabel::Bullish)),
+                article("marketaux", "https://example.com/b", "NVDA beats estimates!", "2024-05-01T09:00:00Z", Some(SentimentLabel::Neutral)),
+                article("marketaux", "https://example.com/c", "Only here", "2024-05-01T09:30:00Z", None),
+            ],
+            fetched_at,
+            fetch_ms: 120,
+        };
+        let second = Sample {
+            provider: "alphavantage".to_string(),
+            articles: vec![
+                article("alphavantage", "http://example.com/a/", "Chips Rally", "2024-05-01T09:50:00Z", Some(SentimentLabel::SomewhatBearish)),
+                article("alphavantage", "https://other.com/b", "NVDA beats estimates", "2024-05-01T09:00:00Z", Some(SentimentLabel::Neutral)),
+            ],
+            fetched_at,
+            fetch_ms: 300,
+        };
+        let comparison = compare_samples(&first, &second, fetched_at - UtcDuration::hours(1), fetched_at);
+
+        assert_eq!(comparison.shared, 2);
+        assert_eq!(comparison.overlap, Some(2.0 / 3.0));
+        assert_eq!((comparison.providers[0].exclusive, comparison.providers[1].exclusive), (1, 0));
+        let latency = comparison.providers[0].latency_to_publication_secs;
+        assert_eq!((latency.p50, latency.p90, latency.max), (Some(1_800), Some(3_600), Some(3_600)));
+        assert_eq!(
+            comparison.sentiment,
+            SentimentDisagreement { compared: 2, disagreements: 1, opposite: 1, rate: Some(0.5) }
+        );
+    }
+}
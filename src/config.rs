@@ -1,9 +1,10 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
 use std::time::Duration;
 
 use serde::Deserialize;
-use config::{builder::DefaultState, ConfigBuilder, ConfigError, File};
+use config::{builder::DefaultState, ConfigBuilder, ConfigError, File, FileFormat};
 
 
 #[derive(Clone, Debug, Deserialize)]
@@ -11,30 +12,1264 @@ pub struct DatabaseConfig {
     pub uri: String,
     pub name: String,
     pub database_name: String,
+    /// Collection receiving the raw aggregated `NewsResult` documents.
     pub collection_name: String,
+    #[serde(default)]
+    pub collections: CollectionsConfig,
+    #[serde(default)]
+    pub diagnostics: DiagnosticsConfig,
+}
+
+/// Query diagnostics: `explain` every query and record the slow or unindexed ones.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DiagnosticsConfig {
+    pub explain: bool,
+    pub slow_query_ms: u64,
+    /// Explains running at once, the queries past it are not explained.
+    pub max_concurrent: usize,
+}
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            explain: false,
+            slow_query_ms: 100,
+            max_concurrent: 4,
+        }
+    }
+}
+
+/// Collection names per data kind, all optional in the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CollectionsConfig {
+    pub articles: String,
+    pub social_sentiment: String,
+    pub trending: String,
+    pub audit: String,
+    pub outbox: String,
+    pub dedup: String,
+    pub watermarks: String,
+    pub timelines: String,
+    pub alerts: String,
+    pub alert_mutes: String,
+    pub notifications: String,
+    pub export_runs: String,
+    pub export_watermarks: String,
+    pub sentiment_snapshots: String,
+    pub counters: String,
+    pub api_calls: String,
+    pub quota: String,
+    pub polls: String,
+    pub diagnostics: String,
+}
+impl Default for CollectionsConfig {
+    fn default() -> Self {
+        Self {
+            articles: "articles".to_string(),
+            social_sentiment: "social_sentiment".to_string(),
+            trending: "trending_sentiment".to_string(),
+            audit: "audit_log".to_string(),
+            outbox: "outbox".to_string(),
+            dedup: "article_dedup".to_string(),
+            watermarks: "watermarks".to_string(),
+            timelines: "timelines".to_string(),
+            alerts: "alerts".to_string(),
+            alert_mutes: "alert_mutes".to_string(),
+            notifications: "article_notifications".to_string(),
+            export_runs: "export_runs".to_string(),
+            export_watermarks: "export_watermarks".to_string(),
+            sentiment_snapshots: "sentiment_snapshots".to_string(),
+            counters: "counters".to_string(),
+            api_calls: "api_calls".to_string(),
+            quota: "api_quota".to_string(),
+            polls: "polls".to_string(),
+            diagnostics: "query_diagnostics".to_string(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// Additional listeners, e.g. a localhost-only admin port.
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+    /// Serves the websocket API on a Unix domain socket as well (Unix only).
+    #[serde(default)]
+    pub unix_socket: Option<UnixSocketConfig>,
+    /// Time given to open connections to close once maintenance mode is entered.
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+    /// How often stored articles are matched against the live subscriptions, see `subscriptions.rs`.
+    #[serde(default = "default_subscription_interval_secs")]
+    pub subscription_interval_secs: u64,
+    /// Live subscriptions a connection may hold at once.
+    #[serde(default = "default_max_subscriptions")]
+    pub max_subscriptions: usize,
+    /// How long the subscriptions of a closed connection can be resumed, see `resume.rs`. Never when 0.
+    #[serde(default = "default_resume_ttl_secs")]
+    pub resume_ttl_secs: u64,
+    /// Pushed articles not acknowledged for this long are pushed again, see `acks.rs`.
+    #[serde(default = "default_ack_timeout_secs")]
+    pub ack_timeout_secs: u64,
+    /// Pushes of an article never acknowledged, before it is parked.
+    #[serde(default = "default_ack_max_attempts")]
+    pub ack_max_attempts: u32,
+    /// Articles waiting for an acknowledgement, no other one is pushed meanwhile.
+    #[serde(default = "default_max_unacked")]
+    pub max_unacked: usize,
+    /// Runs the polling loop in the server process, forwarding every polling result to the clients.
+    #[serde(default)]
+    pub poll: bool,
+    /// Polling results kept for the clients that fell behind, older ones are dropped.
+    #[serde(default = "default_broadcast_capacity")]
+    pub broadcast_capacity: usize,
+    /// How often connections are pinged, never when 0.
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    /// Connections silent for this long (no message nor pong) are closed, never when 0.
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// Requests with an `id` answered at once on a connection, further ones wait.
+    #[serde(default = "default_max_requests_in_flight")]
+    pub max_requests_in_flight: usize,
+    /// Identical task requests of a client answered with the same result, see `dedup.rs`. Never when 0.
+    #[serde(default = "default_dedup_window_ms")]
+    pub dedup_window_ms: u64,
+    /// Items per message of the responses streamed in chunks, see `protocol.rs`. Never when 0.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// Connections open at once over every listener, unlimited when 0. Further ones are turned away.
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// Serves the main listener in public read-only mode, see `public.rs`.
+    #[serde(default)]
+    pub public: bool,
+    /// Tokens required on the TCP listeners, see `auth.rs`.
+    #[serde(default)]
+    pub auth: AuthConfig,
+}
+impl ServerConfig {
+    /// Name of the listener bound to `host`:`port`.
+    pub const MAIN_LISTENER: &'static str = "main";
+
+    /// Every listener, the main one first.
+    pub fn all_listeners(&self) -> Vec<ListenerConfig> {
+        std::iter::once(ListenerConfig {
+            name: Self::MAIN_LISTENER.to_string(),
+            host: self.host.clone(),
+            port: self.port,
+            admin: false,
+            public: self.public,
+        })
+        .chain(self.listeners.iter().cloned())
+        .collect()
+    }
+}
+
+fn default_drain_timeout_secs() -> u64 {
+    30
+}
+
+fn default_subscription_interval_secs() -> u64 {
+    2
+}
+
+fn default_max_subscriptions() -> usize {
+    20
+}
+
+fn default_resume_ttl_secs() -> u64 {
+    300
+}
+
+fn default_ack_timeout_secs() -> u64 {
+    30
+}
+
+fn default_ack_max_attempts() -> u32 {
+    3
+}
+
+fn default_max_unacked() -> usize {
+    100
+}
+
+fn default_broadcast_capacity() -> usize {
+    16
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    30
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_max_requests_in_flight() -> usize {
+    16
+}
+
+fn default_dedup_window_ms() -> u64 {
+    2_000
+}
+
+fn default_chunk_size() -> usize {
+    100
+}
+
+fn default_max_connections() -> usize {
+    1024
+}
+
+/// Websocket authentication, see `auth.rs`. Disabled without tokens nor signing key.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Client name to token.
+    pub tokens: HashMap<String, String>,
+    /// Key of the tokens issued by the `issue_token` admin command, none when empty.
+    pub signing_key: String,
+    /// Time given to a connection without a token in its upgrade request to send one.
+    pub timeout_secs: u64,
+}
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            tokens: HashMap::new(),
+            signing_key: String::new(),
+            timeout_secs: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct UnixSocketConfig {
+    pub path: String,
+    /// Permissions of the socket file, e.g. `0o660` to restrict access to a group.
+    #[serde(default = "default_unix_socket_mode")]
+    pub mode: u32,
+}
+
+fn default_unix_socket_mode() -> u32 {
+    0o660
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ListenerConfig {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    /// Whether `admin` requests are accepted on this listener. Bind admin listeners to localhost.
+    #[serde(default)]
+    pub admin: bool,
+    /// Whether the listener is in public read-only mode, see `public.rs`.
+    #[serde(default)]
+    pub public: bool,
+}
+impl ListenerConfig {
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// A notification channel of the alerting subsystem, see `alerts.rs`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AlertChannelConfig {
+    pub name: String,
+    /// Incoming webhook receiving `{"text": ...}` payloads (Slack-compatible), or the body of `template`.
+    pub webhook_url: String,
+    /// `HH:MM-HH:MM` (UTC) during which nothing is posted, e.g. `"22:00-07:00"`.
+    #[serde(default)]
+    pub quiet_hours: Option<String>,
+    /// Alerts posted per rolling hour, 0 for no limit.
+    #[serde(default)]
+    pub max_per_hour: usize,
+    /// An alert of the same rule and subject as one posted less than this ago is not posted.
+    #[serde(default)]
+    pub dedupe_secs: u64,
+    /// minijinja template of the posted body, see `templates.rs`.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// File holding the template, when not given inline.
+    #[serde(default)]
+    pub template_file: Option<String>,
+    /// Content type of a templated body.
+    #[serde(default = "default_content_type")]
+    pub content_type: String,
+}
+
+fn default_content_type() -> String {
+    "application/json".to_string()
+}
+
+/// Alerting subsystem, see `alerts.rs`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub channels: Vec<AlertChannelConfig>,
+    /// Rules notifying the matching stored articles, see `digest.rs`.
+    #[serde(default)]
+    pub rules: Vec<ArticleRuleConfig>,
+    #[serde(default)]
+    pub digest: DigestConfig,
+}
+
+/// Articles to notify, see `digest.rs`. Criteria given must all match, a list matching any of
+/// its values.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ArticleRuleConfig {
+    pub name: String,
+    #[serde(default)]
+    pub tickers: Vec<String>,
+    /// Keywords of the article, or words of its title.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Harmonized sentiment labels, e.g. `bearish`.
+    #[serde(default)]
+    pub sentiment: Vec<String>,
+    /// Channels notified, all of them when empty.
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+/// Digests of the articles matching `alerts.rules`, see `digest.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    pub interval_secs: u64,
+    /// Articles listed in a digest, the others counted.
+    pub max_articles: usize,
+    /// An article notified to a channel may be notified to it again after this many hours.
+    /// Never when unset.
+    pub repeat_after_hours: Option<u64>,
+}
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 300,
+            max_articles: 20,
+            repeat_after_hours: None,
+        }
+    }
+}
+
+/// Scheduled exports of the stored articles, see `export.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ExportsConfig {
+    /// Directory of the exported files, one subdirectory per job.
+    pub directory: String,
+    pub jobs: Vec<ExportJobConfig>,
+}
+impl Default for ExportsConfig {
+    fn default() -> Self {
+        Self {
+            directory: "exports".to_string(),
+            jobs: Vec::new(),
+        }
+    }
+}
+
+/// A recurring export. `cron` wins over `interval_secs`, as in `[schedule.<name>]`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExportJobConfig {
+    pub name: String,
+    /// `parquet` or `jsonl`.
+    #[serde(default = "default_export_format")]
+    pub format: String,
+    /// Articles published during the previous `hour` or `day` (UTC) are exported.
+    #[serde(default = "default_export_period")]
+    pub period: String,
+    #[serde(default)]
+    pub cron: Option<String>,
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    /// One file per ticker rather than one per period.
+    #[serde(default)]
+    pub per_ticker: bool,
+    /// Tickers exported, every one when empty.
+    #[serde(default)]
+    pub tickers: Vec<String>,
+    /// `flat`, or `hive` for `provider=/date=/` partitions (Parquet only).
+    #[serde(default = "default_export_layout")]
+    pub layout: String,
+}
+
+fn default_export_layout() -> String {
+    "flat".to_string()
+}
+
+fn default_export_format() -> String {
+    "parquet".to_string()
+}
+
+fn default_export_period() -> String {
+    "day".to_string()
+}
+
+/// Coverage gap detection, see `coverage.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CoverageConfig {
+    pub enabled: bool,
+    pub check_interval_secs: u64,
+    /// How far back each check looks for gaps.
+    pub lookback_secs: u64,
+    /// Largest tolerated interval between two successful polls of a provider. Defaults to twice the
+    /// interval of its `[schedule]`.
+    pub max_gap_secs: Option<u64>,
+    /// Re-fetches the missing window of each gap found.
+    pub catch_up: bool,
+}
+impl Default for CoverageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            check_interval_secs: 3600,
+            lookback_secs: 86400,
+            max_gap_secs: None,
+            catch_up: true,
+        }
+    }
+}
+
+/// A feed polled by `rss.rs`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FeedConfig {
+    /// Name of the feed, stored as the article `source`.
+    pub name: String,
+    pub url: String,
+    /// Tickers attached to every entry of the feed (e.g. for a company blog).
+    #[serde(default)]
+    pub tickers: Vec<String>,
+}
+
+/// RSS/Atom feed ingestion, see `rss.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RssConfig {
+    pub enabled: bool,
+    pub poll_interval_secs: u64,
+    pub feeds: Vec<FeedConfig>,
+}
+impl Default for RssConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval_secs: 900,
+            feeds: Vec::new(),
+        }
+    }
+}
+
+/// Reddit source, see `reddit.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RedditConfig {
+    pub enabled: bool,
+    pub poll_interval_secs: u64,
+    pub subreddits: Vec<String>,
+    /// Submissions fetched per subreddit and poll (100 at most).
+    pub limit: u32,
+    /// Reddit rejects requests with a generic user agent.
+    pub user_agent: String,
+    /// Symbols also recognized without a `$` prefix.
+    pub tickers: Vec<String>,
+}
+impl Default for RedditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval_secs: 600,
+            subreddits: vec!["stocks".to_string(), "wallstreetbets".to_string()],
+            limit: 100,
+            user_agent: "news_data/0.1".to_string(),
+            tickers: Vec::new(),
+        }
+    }
+}
+
+/// Right-to-forget purges, see `purge.rs`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PurgeConfig {
+    /// Key signing the purge audit records. Records are left unsigned when empty.
+    #[serde(default)]
+    pub signing_key: String,
+}
+
+/// Base URLs of the FMP endpoint groups, and which group is fetched first, see `fmp.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FmpConfig {
+    pub base_url_v3: String,
+    pub base_url_v4: String,
+    pub base_url_stable: String,
+    /// Fetches the `stable` endpoint first when there is one, the legacy one otherwise.
+    pub prefer_stable: bool,
+    /// Fetches the other endpoint when the first one fails.
+    pub fallback: bool,
+    /// Pages of the social sentiment history of a symbol fetched at most, unless the request
+    /// sets `max_pages`.
+    pub max_history_pages: u64,
+    /// Keeps the latest trending and changes record of each symbol, see `snapshots.rs`.
+    pub sentiment_snapshots: bool,
+}
+impl Default for FmpConfig {
+    fn default() -> Self {
+        Self {
+            base_url_v3: "https://financialmodelingprep.com/api/v3/".to_string(),
+            base_url_v4: "https://financialmodelingprep.com/api/v4/".to_string(),
+            base_url_stable: "https://financialmodelingprep.com/stable/".to_string(),
+            prefer_stable: true,
+            fallback: true,
+            max_history_pages: 50,
+            sentiment_snapshots: false,
+        }
+    }
+}
+
+/// GDELT query of the polling cycle, see `gdelt.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct GdeltConfig {
+    /// Free keywords, e.g. `"interest rates"`.
+    pub keywords: Option<String>,
+    /// GKG themes, e.g. `ECON_INFLATION`.
+    pub themes: Vec<String>,
+    /// FIPS country codes of the sources, e.g. `US`.
+    pub countries: Vec<String>,
+    pub max_records: u32,
+}
+impl Default for GdeltConfig {
+    fn default() -> Self {
+        Self {
+            keywords: None,
+            themes: Vec::new(),
+            countries: Vec::new(),
+            max_records: 250,
+        }
+    }
+}
+
+/// Limits of the public listeners, see `public.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PublicConfig {
+    /// Task functions public connections may call.
+    pub functions: Vec<String>,
+    /// Sustained requests per client IP.
+    pub requests_per_minute: u32,
+    /// Requests a client IP may send at once.
+    pub burst: u32,
+    /// Largest `limit` param accepted.
+    pub max_limit: i64,
+    pub cache_ttl_secs: u64,
+    /// Responses kept in the cache.
+    pub cache_size: usize,
+}
+impl Default for PublicConfig {
+    fn default() -> Self {
+        Self {
+            functions: ["keyword_news", "topic_news", "entity_news", "trending_keywords"].map(String::from).to_vec(),
+            requests_per_minute: 30,
+            burst: 10,
+            max_limit: 100,
+            cache_ttl_secs: 60,
+            cache_size: 1000,
+        }
+    }
+}
+
+/// HTTP endpoint receiving pushed articles, see `webhook.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// Pusher name to bearer token. The name stands in for the `source` of articles without one.
+    pub tokens: HashMap<String, String>,
+    /// Articles accepted per push.
+    pub max_articles: usize,
+}
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 8090,
+            tokens: HashMap::new(),
+            max_articles: 500,
+        }
+    }
+}
+
+/// REST API served alongside the websocket server, see `http.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// Client name to bearer token. Requests need no token when empty.
+    pub tokens: HashMap<String, String>,
+    /// Largest `limit` param.
+    pub max_limit: usize,
+    /// How often `/news/stream` looks for newly stored articles.
+    pub stream_interval_secs: u64,
+}
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 8091,
+            tokens: HashMap::new(),
+            max_limit: 500,
+            stream_interval_secs: 2,
+        }
+    }
+}
+
+/// GraphQL queries served by the REST API, see `graphql.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct GraphqlConfig {
+    pub enabled: bool,
+    /// Window of a query without `from`.
+    pub window_hours: u64,
+    /// Articles an aggregate (entities, topics, sentiment) is computed over, at most.
+    pub max_articles: usize,
+    /// Depth of a query, at most.
+    pub max_depth: usize,
+}
+impl Default for GraphqlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_hours: 24,
+            max_articles: 5000,
+            max_depth: 8,
+        }
+    }
+}
+
+/// gRPC service served alongside the websocket server, see `grpc.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// Client name to bearer token. Calls need no token when empty.
+    pub tokens: HashMap<String, String>,
+    /// Largest `limit` of a search.
+    pub max_limit: usize,
+    /// How often `Subscribe` looks for newly stored articles.
+    pub stream_interval_secs: u64,
+}
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 8092,
+            tokens: HashMap::new(),
+            max_limit: 500,
+            stream_interval_secs: 2,
+        }
+    }
+}
+
+/// Incremental polling, see `watermark.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WatermarkConfig {
+    /// Polls from the newest stored article rather than from `request.delay_secs` ago.
+    pub enabled: bool,
+    /// Time before the watermark fetched again, for articles indexed late.
+    pub overlap_secs: u64,
+    /// Oldest start of a polling window, after a long downtime.
+    pub max_lookback_secs: u64,
+}
+impl Default for WatermarkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            overlap_secs: 60,
+            max_lookback_secs: 86_400,
+        }
+    }
+}
+
+/// Polling schedule of a source, see `scheduler.rs`. `cron` wins over `interval_secs`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SourceSchedule {
+    pub interval_secs: Option<u64>,
+    /// Cron expression with a seconds field, in UTC.
+    pub cron: Option<String>,
+}
+
+/// Historical backfill, see `backfill.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct BackfillConfig {
+    /// Providers backfilled when the request names none.
+    pub providers: Vec<String>,
+    /// Pause between two requests to a provider, by provider name.
+    pub pause_ms: HashMap<String, u64>,
+    /// Pause for the providers missing from `pause_ms`.
+    pub default_pause_ms: u64,
+    /// MarketAux pages fetched per day at most.
+    pub max_pages_per_day: u64,
+    /// Retries of a rate-limited day, waiting `rate_limit_backoff_secs` more each time.
+    pub max_retries: u32,
+    pub rate_limit_backoff_secs: u64,
+}
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            providers: vec!["marketaux".to_string(), "alphavantage".to_string()],
+            // Alpha Vantage free tier: 5 requests per minute.
+            pause_ms: HashMap::from([("alphavantage".to_string(), 12_000)]),
+            default_pause_ms: 1_000,
+            max_pages_per_day: 5,
+            max_retries: 3,
+            rate_limit_backoff_secs: 60,
+        }
+    }
+}
+
+/// Polling-interval hints from the load of the providers, see `autoscale.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AutoscaleConfig {
+    /// Fetch latency at which a provider is saturated.
+    pub max_latency_ms: u64,
+    /// Fetches in flight at which a provider is saturated.
+    pub max_in_flight: usize,
+    /// Pressure above which a longer interval is suggested.
+    pub high_pressure: f64,
+    /// Pressure below which a shorter interval is suggested.
+    pub low_pressure: f64,
+    /// Factor of each interval adjustment.
+    pub step: f64,
+    pub min_interval_secs: u64,
+    pub max_interval_secs: u64,
+    /// Applies the suggested interval after every polling cycle.
+    pub auto_tune: bool,
+}
+impl Default for AutoscaleConfig {
+    fn default() -> Self {
+        Self {
+            max_latency_ms: 10_000,
+            max_in_flight: 4,
+            high_pressure: 0.8,
+            low_pressure: 0.3,
+            step: 1.5,
+            min_interval_secs: 60,
+            max_interval_secs: 3_600,
+            auto_tune: false,
+        }
+    }
+}
+
+/// Estimated cost of the provider APIs, see `costs.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CostsConfig {
+    /// Unit of the prices, e.g. `USD` or `credits`.
+    pub unit: String,
+    /// Price of a request by provider name.
+    pub prices: HashMap<String, f64>,
+    /// Days of calls kept.
+    pub retention_days: usize,
+}
+impl Default for CostsConfig {
+    fn default() -> Self {
+        Self {
+            unit: "USD".to_string(),
+            prices: HashMap::new(),
+            retention_days: 31,
+        }
+    }
+}
+
+/// Latency to publication of each provider, see `freshness.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FreshnessConfig {
+    /// Latest latencies kept per provider.
+    pub window: usize,
+    /// Articles published longer before their ingestion are not recorded.
+    pub max_age_secs: u64,
+}
+impl Default for FreshnessConfig {
+    fn default() -> Self {
+        Self {
+            window: 1_000,
+            max_age_secs: 86_400,
+        }
+    }
+}
+
+/// Latency of the articles from publication to subscriber delivery, see `latency.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LatencyConfig {
+    /// Share of the articles followed through the stages, 0 to 1.
+    pub sample_rate: f64,
+    /// Latest latencies kept per stage.
+    pub window: usize,
+    /// Articles published longer before a stage are not recorded for it.
+    pub max_age_secs: u64,
+    /// Alert when the p90 latency to delivery exceeds it, 0 for no alert.
+    pub slo_secs: u64,
+    /// Delivered samples needed before the SLO is checked.
+    pub min_samples: usize,
+    pub check_interval_secs: u64,
+}
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0.1,
+            window: 1_000,
+            max_age_secs: 86_400,
+            slo_secs: 0,
+            min_samples: 20,
+            check_interval_secs: 60,
+        }
+    }
+}
+
+/// Valid values of the provider filters, see `enumerations.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct EnumerationsConfig {
+    pub enabled: bool,
+    /// Pause between two fetches of the lists.
+    pub refresh_secs: u64,
+}
+impl Default for EnumerationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            refresh_secs: 86_400,
+        }
+    }
+}
+
+/// Audit log of the requests sent to the provider APIs, see `api_calls.rs`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ApiCallsConfig {
+    pub enabled: bool,
+    /// Writes the records to the `api_calls` collection as well as to the logs.
+    pub store: bool,
+}
+
+/// Daily quotas of the provider APIs, see `quota.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    pub enabled: bool,
+    /// Calls allowed per UTC day by provider name.
+    pub limits: HashMap<String, u64>,
+    /// Share of a limit from which the requests are spaced out.
+    pub throttle_at: f64,
+    /// Share of a limit from which the requests are refused until the next day.
+    pub pause_at: f64,
+    /// Longest wait of a throttled request, the request is refused when its turn is later. Keep
+    /// it below the polling interval.
+    pub max_wait_secs: u64,
+}
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            limits: HashMap::new(),
+            throttle_at: 0.8,
+            pause_at: 0.98,
+            max_wait_secs: 30,
+        }
+    }
+}
+
+/// Raw provider responses written to disk for debugging, see `captures.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CapturesConfig {
+    pub enabled: bool,
+    pub dir: String,
+    /// Captures kept, the oldest ones are removed beyond.
+    pub max_files: usize,
+    /// Total size of the captures kept, in bytes.
+    pub max_bytes: u64,
+}
+impl Default for CapturesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: "captures".to_string(),
+            max_files: 1_000,
+            max_bytes: 100_000_000,
+        }
+    }
+}
+
+/// Back-fetch of the window missed during a downtime, see `recovery.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RecoveryConfig {
+    pub enabled: bool,
+    /// Shortest downtime recovered, since the last stored article.
+    pub min_downtime_secs: u64,
+    /// Longest window recovered, the older part of a longer downtime is left out.
+    pub max_window_secs: u64,
+    /// Window of each recovery request.
+    pub slice_secs: u64,
+    /// Pause between two recovery requests to the same provider.
+    pub pause_ms: u64,
+    /// Recovery requests sent to each provider at most.
+    pub max_requests: usize,
+    pub providers: Vec<String>,
+}
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_downtime_secs: 1_800,
+            max_window_secs: 604_800,
+            slice_secs: 3_600,
+            pause_ms: 1_000,
+            max_requests: 24,
+            providers: vec!["marketaux".to_string(), "alphavantage".to_string()],
+        }
+    }
+}
+
+/// Symbols fetched on their own every polling cycle, see `watchlist.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WatchlistConfig {
+    pub symbols: Vec<String>,
+    /// Providers queried for each symbol.
+    pub providers: Vec<String>,
+    /// Symbol queries sent at the same time.
+    pub max_concurrent_fetches: usize,
+}
+impl Default for WatchlistConfig {
+    fn default() -> Self {
+        Self {
+            symbols: Vec::new(),
+            providers: vec!["marketaux".to_string(), "alphavantage".to_string(), "fmp".to_string()],
+            max_concurrent_fetches: 4,
+        }
+    }
+}
+
+/// `report` command, see `report.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    /// Watchlist of the reports.
+    pub tickers: Vec<String>,
+    /// Stories listed per ticker.
+    pub top_stories: usize,
+    /// Change of the mean sentiment score listed as a shift.
+    pub shift_threshold: f64,
+    /// Ratio between the article counts of two periods flagged as a spike or a drop.
+    pub anomaly_ratio: f64,
+    /// Article count under which a period is too quiet to be compared.
+    pub min_articles: usize,
+}
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            tickers: Vec::new(),
+            top_stories: 5,
+            shift_threshold: 0.15,
+            anomaly_ratio: 2.0,
+            min_articles: 5,
+        }
+    }
+}
+
+/// Per-ticker event timelines, see `timeline.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TimelineConfig {
+    /// Longest gap between two articles of a story.
+    pub window_hours: u64,
+    /// Least title similarity (Jaccard index) for an article to join a story.
+    pub similarity: f64,
+    /// Window of a timeline requested without `from`.
+    pub default_days: u64,
+    /// Latest events kept per timeline, 0 for all.
+    pub max_events: usize,
+}
+impl Default for TimelineConfig {
+    fn default() -> Self {
+        Self {
+            window_hours: 48,
+            similarity: 0.3,
+            default_days: 30,
+            max_events: 100,
+        }
+    }
+}
+
+/// Ticker co-mentions, see `comentions.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CoMentionsConfig {
+    /// Rolling window of a graph requested without `from`.
+    pub window_hours: u64,
+    /// Articles mentioning a pair for it to be an edge.
+    pub min_articles: u64,
+    /// Articles tagging more tickers are skipped.
+    pub max_tickers_per_article: usize,
+    /// Edges returned, 0 for all.
+    pub max_edges: usize,
+}
+impl Default for CoMentionsConfig {
+    fn default() -> Self {
+        Self {
+            window_hours: 168,
+            min_articles: 2,
+            max_tickers_per_article: 8,
+            max_edges: 200,
+        }
+    }
+}
+
+/// Portfolio-aware aggregation, see `portfolio.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PortfolioConfig {
+    /// Window of a query without `from`.
+    pub window_hours: u64,
+    /// Positions of a portfolio, 0 for no limit.
+    pub max_positions: usize,
+    /// Ranked articles returned, 0 for all.
+    pub max_articles: usize,
+}
+impl Default for PortfolioConfig {
+    fn default() -> Self {
+        Self {
+            window_hours: 24,
+            max_positions: 100,
+            max_articles: 50,
+        }
+    }
+}
+
+/// Topic taxonomy, see `taxonomy.rs`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TaxonomyConfig {
+    /// Provider topic to internal topic, completing the built-in table. `""` drops the topic.
+    pub topics: HashMap<String, String>,
+    /// Keeps the topics missing from the table as internal topics.
+    pub keep_unmapped: bool,
+}
+
+/// Keyword extraction, see `keywords.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct KeywordsConfig {
+    pub enabled: bool,
+    /// Keywords kept per article.
+    pub max_keywords: usize,
+    /// Longest phrase kept as a keyword, in words.
+    pub max_phrase_words: usize,
+    /// Stop words added to the built-in English list.
+    pub stopwords: Vec<String>,
+}
+impl Default for KeywordsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_keywords: 5,
+            max_phrase_words: 4,
+            stopwords: Vec::new(),
+        }
+    }
+}
+
+/// Named entity recognition, see `entities.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct EntitiesConfig {
+    pub enabled: bool,
+    /// Entities kept per article.
+    pub max_entities: usize,
+    /// Canonical name to aliases, completing the built-in gazetteer.
+    pub people: HashMap<String, Vec<String>>,
+    pub organizations: HashMap<String, Vec<String>>,
+    pub locations: HashMap<String, Vec<String>>,
+}
+impl Default for EntitiesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entities: 10,
+            people: HashMap::new(),
+            organizations: HashMap::new(),
+            locations: HashMap::new(),
+        }
+    }
+}
+
+/// Article translation, see `translate.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TranslationConfig {
+    pub enabled: bool,
+    /// `libretranslate` or `deepl`.
+    pub api: String,
+    pub endpoint: String,
+    pub api_key: String,
+    pub target_language: String,
+    /// Translates articles without a language, detected by the API.
+    pub translate_unknown_language: bool,
+    pub timeout_secs: u64,
+}
+impl Default for TranslationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api: "libretranslate".to_string(),
+            endpoint: "http://localhost:5000/translate".to_string(),
+            api_key: String::new(),
+            target_language: "en".to_string(),
+            translate_unknown_language: false,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Sentiment label harmonization, see `sentiment.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SentimentConfig {
+    /// Provider label to harmonized label (`bearish`, `somewhat_bearish`, `neutral`, `somewhat_bullish`
+    /// or `bullish`), completing the built-in table.
+    pub labels: HashMap<String, String>,
+    /// Inclusive upper bounds of the `bearish`, `somewhat_bearish`, `neutral` and `somewhat_bullish`
+    /// scores, for articles without a known label.
+    pub thresholds: [f64; 4],
+}
+impl Default for SentimentConfig {
+    fn default() -> Self {
+        Self {
+            labels: HashMap::new(),
+            thresholds: [-0.35, -0.15, 0.15, 0.35],
+        }
+    }
+}
+
+/// Clock skew detection, see `clock.rs`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    /// Skew tolerated before warning, in seconds.
+    pub max_skew_secs: u64,
+    /// Computes polling windows from the provider time when the skew exceeds `max_skew_secs`.
+    pub auto_correct: bool,
+}
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            max_skew_secs: 30,
+            auto_correct: false,
+        }
+    }
+}
+
+/// Fault injection, see `chaos.rs`. Never enable in production.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    pub enabled: bool,
+    /// Providers whose requests faults are injected in, all when empty.
+    pub providers: Vec<String>,
+    /// Probability of an upstream request timing out.
+    pub timeout_rate: f64,
+    /// Time an injected timeout takes to fail.
+    pub timeout_delay_ms: u64,
+    /// Probability of an upstream request being rate limited (429).
+    pub rate_limit_rate: f64,
+    /// Probability of a response body being replaced with a malformed one.
+    pub malformed_body_rate: f64,
+    /// Probability of a MongoDB write failing.
+    pub write_failure_rate: f64,
+}
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            providers: Vec::new(),
+            timeout_rate: 0.05,
+            timeout_delay_ms: 5000,
+            rate_limit_rate: 0.05,
+            malformed_body_rate: 0.05,
+            write_failure_rate: 0.05,
+        }
+    }
+}
+
+/// Single-instance guard, see `instance.rs`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct InstanceConfig {
+    /// Lock file preventing two daemons from running with the same config. Disabled when unset.
+    pub lock_file: Option<String>,
 }
 
 #[derive(Clone, Hash, Debug, Deserialize)]
 pub struct LoggingConfig {
     pub level: String,
+    /// Levels of some modules or crates, e.g. `marketaux=debug,db=warn`, see `logging.rs`.
+    #[serde(default)]
+    pub filters: String,
+    /// OTLP/gRPC endpoint receiving the spans, e.g. `http://localhost:4317`. Not exported when unset.
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+    /// `service.name` of the exported spans.
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+fn default_service_name() -> String {
+    "news_data".to_string()
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ApiConfig {
     pub alphavantage: String,
     pub marketaux: String,
-    pub fmp: String
+    pub fmp: String,
+    #[serde(default)]
+    pub finnhub: String,
+    #[serde(default)]
+    pub polygon: String,
 }
 
 #[derive(Debug, Clone, Hash, Deserialize)]
 pub struct RequestArgs {
-    pub delay_secs: i64
+    pub delay_secs: i64,
+    /// Providers fetched at the same time during a polling cycle.
+    #[serde(default = "default_max_concurrent_providers")]
+    pub max_concurrent_providers: usize,
+}
+
+fn default_max_concurrent_providers() -> usize {
+    4
 }
 #[derive(Clone, Debug, Deserialize)]
 pub struct TaskArgs {
@@ -42,6 +1277,13 @@ pub struct TaskArgs {
     pub max_delay_ms: u32,
     pub max_retries: u32,
     pub cache_ttl: u32,
+    /// Provider fetches running at the same time across all the sources, see `limiter.rs`.
+    #[serde(default = "default_max_concurrent_fetches")]
+    pub max_concurrent_fetches: usize,
+}
+
+fn default_max_concurrent_fetches() -> usize {
+    8
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -52,6 +1294,83 @@ pub struct ValueConfig {
     pub api: ApiConfig,
     pub request: RequestArgs,
     pub task: TaskArgs,
+    #[serde(default)]
+    pub instance: InstanceConfig,
+    #[serde(default)]
+    pub clock: ClockConfig,
+    #[serde(default)]
+    pub chaos: ChaosConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub exports: ExportsConfig,
+    #[serde(default)]
+    pub coverage: CoverageConfig,
+    #[serde(default)]
+    pub rss: RssConfig,
+    #[serde(default)]
+    pub reddit: RedditConfig,
+    #[serde(default)]
+    pub purge: PurgeConfig,
+    #[serde(default)]
+    pub fmp: FmpConfig,
+    #[serde(default)]
+    pub gdelt: GdeltConfig,
+    #[serde(default)]
+    pub sentiment: SentimentConfig,
+    #[serde(default)]
+    pub keywords: KeywordsConfig,
+    #[serde(default)]
+    pub entities: EntitiesConfig,
+    #[serde(default)]
+    pub taxonomy: TaxonomyConfig,
+    #[serde(default)]
+    pub watermark: WatermarkConfig,
+    #[serde(default)]
+    pub watchlist: WatchlistConfig,
+    #[serde(default)]
+    pub recovery: RecoveryConfig,
+    #[serde(default)]
+    pub autoscale: AutoscaleConfig,
+    #[serde(default)]
+    pub costs: CostsConfig,
+    #[serde(default)]
+    pub freshness: FreshnessConfig,
+    #[serde(default)]
+    pub latency: LatencyConfig,
+    #[serde(default)]
+    pub enumerations: EnumerationsConfig,
+    #[serde(default)]
+    pub api_calls: ApiCallsConfig,
+    #[serde(default)]
+    pub quota: QuotaConfig,
+    #[serde(default)]
+    pub captures: CapturesConfig,
+    #[serde(default)]
+    pub translation: TranslationConfig,
+    #[serde(default)]
+    pub backfill: BackfillConfig,
+    /// Schedule of each polling source by name, `request.delay_secs` for the others.
+    #[serde(default)]
+    pub schedule: HashMap<String, SourceSchedule>,
+    #[serde(default)]
+    pub report: ReportConfig,
+    #[serde(default)]
+    pub timeline: TimelineConfig,
+    #[serde(default)]
+    pub comentions: CoMentionsConfig,
+    #[serde(default)]
+    pub portfolio: PortfolioConfig,
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub graphql: GraphqlConfig,
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub public: PublicConfig,
 }
 impl ValueConfig {
     pub fn new() -> Result<Self, ConfigError> {
@@ -73,6 +1392,14 @@ impl ValueConfig {
     config.try_deserialize()
 
     }
+
+    /// Configuration given as a TOML document rather than read from the `config` file.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        ConfigBuilder::<DefaultState>::default()
+            .add_source(File::from_str(text, FileFormat::Toml))
+            .build()?
+            .try_deserialize()
+    }
 }
 
 impl fmt::Display for ValueConfig {
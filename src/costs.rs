@@ -0,0 +1,150 @@
+//! Estimated cost of the provider APIs.
+//!
+//! Every request sent to a provider API is counted per UTC day. Given the price of a request of
+//! each provider in `[costs.prices]`, the counts give the estimated cost of each feed, in
+//! `costs.unit` (dollars, credits...):
+//!
+//! ```toml
+//! [costs]
+//! unit = "USD"
+//! [costs.prices]
+//! marketaux = 0.002
+//! fmp = 0.0005
+//! ```
+//!
+//! The `status` admin command returns the health of every provider along with the calls and
+//! costs of the last `costs.retention_days` days, and their totals. A provider without a price
+//! has its calls counted, without a cost. Counts are those of the running process.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::clock;
+use crate::config::CostsConfig;
+
+/// Calls to a provider over some period, and their estimated cost.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq)]
+pub struct ProviderCost {
+    pub calls: u64,
+    /// `None` without a price for the provider.
+    pub cost: Option<f64>,
+}
+
+/// Costs returned by the `status` admin command.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CostReport {
+    pub unit: String,
+    /// Cost of each provider by day (`YYYY-MM-DD`).
+    pub days: BTreeMap<String, BTreeMap<String, ProviderCost>>,
+    /// Cost of each provider over the days reported.
+    pub totals: BTreeMap<String, ProviderCost>,
+}
+
+/// Calls of every provider by day, given a `CostsConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct Costs {
+    config: CostsConfig,
+    calls: BTreeMap<NaiveDate, BTreeMap<String, u64>>,
+}
+impl Costs {
+    pub fn new(config: &CostsConfig) -> Self {
+        Self { config: config.clone(), ..Self::default() }
+    }
+
+    /// Counts a call to `provider` on `day`, forgetting the days past `costs.retention_days`.
+    pub fn record_call(&mut self, provider: &str, day: NaiveDate) {
+        *self.calls.entry(day).or_default().entry(provider.to_string()).or_default() += 1;
+        while self.calls.len() > self.config.retention_days.max(1) {
+            self.calls.pop_first();
+        }
+    }
+
+    fn cost(&self, provider: &str, calls: u64) -> ProviderCost {
+        let cost = self.config.prices.get(provider).map(|price| price * calls as f64);
+        ProviderCost { calls, cost }
+    }
+
+    pub fn report(&self) -> CostReport {
+        let mut totals: BTreeMap<String, u64> = BTreeMap::new();
+        let days = self
+            .calls
+            .iter()
+            .map(|(day, providers)| {
+                let costs = providers
+                    .iter()
+                    .map(|(provider, calls)| {
+                        *totals.entry(provider.clone()).or_default() += calls;
+                        (provider.clone(), self.cost(provider, *calls))
+                    })
+                    .collect();
+                (day.to_string(), costs)
+            })
+            .collect();
+        CostReport {
+            unit: self.config.unit.clone(),
+            days,
+            totals: totals.iter().map(|(provider, calls)| (provider.clone(), self.cost(provider, *calls))).collect(),
+        }
+    }
+}
+
+fn state() -> &'static Mutex<Costs> {
+    static STATE: OnceLock<Mutex<Costs>> = OnceLock::new();
+    STATE.get_or_init(Mutex::default)
+}
+
+fn with_state<T>(f: impl FnOnce(&mut Costs) -> T) -> T {
+    f(&mut state().lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Applies the `[costs]` section of the config.
+pub fn configure(config: &CostsConfig) {
+    with_state(|costs| costs.config = config.clone());
+}
+
+/// Counts a request sent to `provider` today.
+pub fn record_call(provider: &str) {
+    let today = clock::now().date_naive();
+    with_state(|costs| costs.record_call(provider, today));
+}
+
+/// Calls and costs of the days kept.
+pub fn report() -> CostReport {
+    with_state(|costs| costs.report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn prices_the_calls_of_each_day() {
+        let config = CostsConfig {
+            unit: "credits".to_string(),
+            prices: HashMap::from([("marketaux".to_string(), 0.5)]),
+            retention_days: 2,
+        };
+        let mut costs = Costs::new(&config);
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        costs.record_call("marketaux", day(1));
+        costs.record_call("marketaux", day(2));
+        costs.record_call("marketaux", day(2));
+        costs.record_call("gdelt", day(2));
+
+        let report = costs.report();
+        assert_eq!(report.unit, "credits");
+        assert_eq!(report.days["2024-05-02"]["marketaux"], ProviderCost { calls: 2, cost: Some(1.0) });
+        assert_eq!(report.days["2024-05-02"]["gdelt"], ProviderCost { calls: 1, cost: None });
+        assert_eq!(report.totals["marketaux"], ProviderCost { calls: 3, cost: Some(1.5) });
+
+        // Only the last `retention_days` days are kept.
+        costs.record_call("marketaux", day(3));
+        let report = costs.report();
+        assert_eq!(report.days.keys().collect::<Vec<_>>(), vec!["2024-05-02", "2024-05-03"]);
+        assert_eq!(report.totals["marketaux"], ProviderCost { calls: 3, cost: Some(1.5) });
+    }
+}
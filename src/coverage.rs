@@ -0,0 +1,216 @@
+//! Gap detection in the ingested coverage.
+//!
+//! A provider outage, an expired key or a crash between two polling cycles leaves a hole in the
+//! archive that nothing else notices. The polling loop records every successful poll of each
+//! provider (see `Storage::record_poll`), and every `coverage.check_interval_secs` the
+//! `GapDetector` looks at the polls of the last `coverage.lookback_secs`. Any interval without a
+//! successful poll longer than `coverage.max_gap_secs` (twice the interval of the provider in
+//! `[schedule]` by default) raises an alert and, with `coverage.catch_up`, the missing window is
+//! fetched again with `NewsProvider::fetch_window`. Articles already stored are skipped by the dedup
+//! collection, so a catch-up never duplicates anything.
+//!
+//! Polls are measured rather than publication times: a quiet night for a provider is not an outage.
+//! The detector remembers up to when the gaps of each provider were handled, so a gap is only
+//! alerted and fetched once, and a gap still open is only fetched for its new part.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration as UtcDuration, SecondsFormat, Utc};
+use tokio::time::{sleep, Duration};
+use tracing::{debug, error, info, Instrument};
+
+use crate::alerts::{Alert, Alerter};
+use crate::{clock, limiter, logging};
+use crate::config::ValueConfig;
+use crate::db::OpError;
+use crate::normalize::parse_timestamp;
+use crate::provider::NewsProvider;
+use crate::scheduler::Schedule;
+use crate::storage::Storage;
+
+const GAP_RULE: &str = "coverage_gap";
+
+/// An interval without any stored article.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+impl Gap {
+    pub fn duration(&self) -> UtcDuration {
+        self.end - self.start
+    }
+
+    /// The part of the gap after `handled_until`, `None` when it was all handled.
+    pub fn after(self, handled_until: Option<DateTime<Utc>>) -> Option<Gap> {
+        match handled_until {
+            Some(until) if until >= self.end => None,
+            Some(until) => Some(Gap { start: self.start.max(until), end: self.end }),
+            None => Some(self),
+        }
+    }
+}
+
+/// Intervals of `[from, to]` longer than `max_gap` without any time in `polled`.
+/// `polled` must be sorted.
+pub fn find_gaps(polled: &[DateTime<Utc>], from: DateTime<Utc>, to: DateTime<Utc>, max_gap: UtcDuration) -> Vec<Gap> {
+    let inner = polled.iter().copied().filter(|t| *t >= from && *t <= to);
+    let bounds: Vec<DateTime<Utc>> = std::iter::once(from).chain(inner).chain(std::iter::once(to)).collect();
+    bounds
+        .windows(2)
+        .map(|pair| Gap { start: pair[0], end: pair[1] })
+        .filter(|gap| gap.duration() > max_gap)
+        .collect()
+}
+
+/// Periodic coverage checker.
+pub struct GapDetector {
+    providers: Arc<Vec<Arc<dyn NewsProvider>>>,
+    db_ops: Arc<dyn Storage>,
+    alerter: Arc<Alerter>,
+    config: Arc<ValueConfig>,
+    /// End of the last gap handled per provider.
+    handled: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+impl GapDetector {
+    pub fn new(
+        providers: Arc<Vec<Arc<dyn NewsProvider>>>,
+        db_ops: Arc<dyn Storage>,
+        alerter: Arc<Alerter>,
+        config: Arc<ValueConfig>,
+    ) -> Self {
+        Self { providers, db_ops, alerter, config, handled: Mutex::default() }
+    }
+
+    /// Longest tolerated interval between two polls of `source`: `coverage.max_gap_secs`, or twice
+    /// the longest interval of its schedule.
+    fn max_gap(&self, source: &str) -> UtcDuration {
+        let secs = self.config.coverage.max_gap_secs.unwrap_or_else(|| {
+            let polling = Schedule::Polling(self.config.request.delay_secs.max(1) as u64);
+            let schedule = Schedule::of(source, &self.config).unwrap_or(polling);
+            schedule.max_interval(clock::now()).as_secs().saturating_mul(2)
+        });
+        UtcDuration::seconds(secs.min(i64::MAX as u64 / 1000) as i64)
+    }
+
+    /// Checks every provider once, returning the gaps found per provider.
+    pub async fn check(&self) -> Vec<(String, Vec<Gap>)> {
+        let to = clock::now();
+        let from = to - UtcDuration::seconds(self.config.coverage.lookback_secs as i64);
+        let mut found = Vec::new();
+        for provider in self.providers.iter() {
+            match self.check_provider(provider.as_ref(), from, to).await {
+                Ok(gaps) => found.push((provider.name().to_string(), gaps)),
+                Err(e) => error!("Coverage check of `{}` failed: {}", provider.name(), e),
+            }
+        }
+        found
+    }
+
+    async fn check_provider(&self, provider: &dyn NewsProvider, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Gap>, OpError> {
+        let polled: Vec<DateTime<Utc>> = self.db_ops
+            .poll_times(
+                provider.name(),
+                &from.to_rfc3339_opts(SecondsFormat::Secs, true),
+                &to.to_rfc3339_opts(SecondsFormat::Secs, true),
+            )
+            .await?
+            .iter()
+            .filter_map(|t| parse_timestamp(t))
+            .collect();
+
+        let gaps = find_gaps(&polled, from, to, self.max_gap(provider.name()));
+        debug!("Coverage of `{}`: {} polls, {} gaps.", provider.name(), polled.len(), gaps.len());
+        if gaps.is_empty() {
+            self.alerter.resolve(GAP_RULE, provider.name()).await;
+        }
+        let handled_until = self.handled.lock().unwrap().get(provider.name()).copied();
+        for gap in gaps.iter().filter_map(|gap| gap.after(handled_until)) {
+            let message = format!(
+                "No successful poll between {} and {} ({} minutes).",
+                gap.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                gap.end.to_rfc3339_opts(SecondsFormat::Secs, true),
+                gap.duration().num_minutes()
+            );
+            self.alerter.raise(&Alert::new(GAP_RULE, provider.name(), message)).await;
+            // A failed catch-up is tried again at the next check.
+            if self.config.coverage.catch_up && !self.catch_up(provider, &gap).await {
+                break;
+            }
+            self.handled.lock().unwrap().insert(provider.name().to_string(), gap.end);
+        }
+        Ok(gaps)
+    }
+
+    /// Fetches the articles of `gap` again and stores the ones missing. Returns whether it succeeded.
+    async fn catch_up(&self, provider: &dyn NewsProvider, gap: &Gap) -> bool {
+        match limiter::run(provider.name(), provider.fetch_window(gap.start, gap.end)).await {
+            Ok(articles) if articles.is_empty() => {
+                info!("Catch-up of `{}` found no article to fill the gap.", provider.name());
+                true
+            }
+            Ok(articles) => match self.db_ops.store_articles(&articles).await {
+                Ok(stored) => {
+                    info!("Catch-up of `{}` stored {} of {} articles.", provider.name(), stored, articles.len());
+                    true
+                }
+                Err(e) => {
+                    error!("Catch-up of `{}` failed to store articles: {}", provider.name(), e);
+                    false
+                }
+            },
+            Err(e) => {
+                error!("Catch-up fetch of `{}` failed: {}", provider.name(), e);
+                false
+            }
+        }
+    }
+
+    /// Runs `check` every `coverage.check_interval_secs` in a background task.
+    pub fn spawn(self) {
+        let interval = Duration::from_secs(self.config.coverage.check_interval_secs.max(1));
+        tokio::spawn(async move {
+            loop {
+                sleep(interval).await;
+                async {
+                    info!("Checking coverage gaps...");
+                    self.check().await;
+                }
+                .instrument(logging::cycle_span("coverage"))
+                .await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap() + UtcDuration::minutes(minutes)
+    }
+
+    #[test]
+    fn finds_gaps_between_articles_and_at_the_edges() {
+        let published = [at(10), at(20), at(100)];
+        let gaps = find_gaps(&published, at(0), at(180), UtcDuration::minutes(60));
+        assert_eq!(gaps, vec![Gap { start: at(20), end: at(100) }, Gap { start: at(100), end: at(180) }]);
+    }
+
+    #[test]
+    fn an_empty_window_is_one_gap() {
+        let gaps = find_gaps(&[], at(0), at(120), UtcDuration::minutes(60));
+        assert_eq!(gaps, vec![Gap { start: at(0), end: at(120) }]);
+    }
+
+    #[test]
+    fn skips_the_handled_part_of_a_gap() {
+        let gap = Gap { start: at(20), end: at(180) };
+        assert_eq!(gap.after(None), Some(gap));
+        assert_eq!(gap.after(Some(at(100))), Some(Gap { start: at(100), end: at(180) }));
+        assert_eq!(gap.after(Some(at(180))), None);
+        assert_eq!(gap.after(Some(at(0))), Some(gap));
+    }
+}
@@ -1,15 +1,41 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
 
 use futures::TryStreamExt;
 use mongodb::{
-    bson::{doc, Document},
-    options::{ClientOptions, UpdateOptions, ServerApi, ServerApiVersion},
-    Client, Collection,
+    bson::{doc, Bson, Document},
+    error::{Error as MongoError, ErrorKind, WriteFailure, TRANSIENT_TRANSACTION_ERROR},
+    options::{ClientOptions, FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReplaceOptions, ReturnDocument, UpdateOptions, ServerApi, ServerApiVersion},
+    Client, ClientSession, Collection, Cursor, Database,
 };
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
-use tracing::info;
+use tracing::{info, instrument, warn, Instrument};
 
-use crate::config::ValueConfig;
+use crate::alerts::{AlertMute, AlertRecord, AlertState};
+use crate::alphavantage::TickerSentiment;
+use crate::api_calls::ApiCall;
+use crate::captures;
+use crate::chaos;
+use crate::config::{CollectionsConfig, DatabaseConfig, EntitiesConfig, KeywordsConfig, TranslationConfig, ValueConfig};
+use crate::diagnostics::{ExplainedQuery, QueryExplainer};
+use crate::digest::ArticleNotification;
+use crate::export::{ExportRun, ExportWatermark};
+use crate::entities::{EntityExtractor, EntityKind};
+use crate::freshness;
+use crate::latency::{self, Stage};
+use crate::keywords::KeywordExtractor;
+use crate::normalize::{NormalizedArticle, ALPHAVANTAGE_PROVIDER, MARKETAUX_PROVIDER};
+use crate::sentiment::SentimentLabel;
+use crate::storage::{ArticleFilter, SequencedArticle};
+use crate::translate::Translator;
+use crate::server_types::FMPMarketSentiment;
+use crate::snapshots::SentimentSnapshot;
+use crate::timeline::Timeline;
+use crate::utils::now;
+use crate::NewsResult;
 
 #[derive(Debug)]
 pub enum OpError {
@@ -33,6 +59,9 @@ pub enum OpError {
     },
     ConversionError {
         message: String,
+    },
+    TransactionError {
+        message: String,
     }
 }
 
@@ -59,6 +88,9 @@ impl fmt::Display for OpError {
             },
             OpError::ConversionError { message } => {
                 write!(f, "Value conversion to bson::Document failed | Error: {}", message)
+            },
+            OpError::TransactionError { message } => {
+                write!(f, "Transaction failed and was aborted | Error: {}", message)
             }
         }
     }
@@ -67,6 +99,7 @@ impl fmt::Display for OpError {
 /// Manages MongoDB Client
 pub struct ClientManager {
     client: Client,
+    supports_transactions: bool,
 }
 
 impl ClientManager {
@@ -108,31 +141,1201 @@ impl ClientManager {
             })?;
 
         info!("Pinged your deployment. You successfully connected to MongoDB cluster!");
-        
-        Ok(Self{client})
+
+        // Multi-document transactions require a replica set or a sharded cluster (mongos).
+        let supports_transactions = client
+            .database("admin")
+            .run_command(doc! {"hello": 1}, None)
+            .await
+            .map(|hello| hello.contains_key("setName") || hello.get_str("msg") == Ok("isdbgrid"))
+            .unwrap_or(false);
+        if !supports_transactions {
+            warn!("MongoDB deployment is standalone. Multi-document writes will not be transactional.");
+        }
+
+        Ok(Self{client, supports_transactions})
 }
 
+    /// Whether the deployment supports multi-document transactions.
+    pub fn supports_transactions(&self) -> bool {
+        self.supports_transactions
+    }
+
     /// Returns a reference to the MongoDB client
     pub fn get_client(&self) -> &Client {
         &self.client
     }
 }
 
+/// Kinds of data stored by the service, each one in its own collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataKind {
+    /// Raw aggregated `NewsResult` documents.
+    Results,
+    /// Articles in the common `NormalizedArticle` schema.
+    Articles,
+    /// Historical social sentiment records.
+    SocialSentiment,
+    /// Trending social sentiment snapshots.
+    Trending,
+    /// Audit log entries.
+    Audit,
+    /// Events describing stored articles, for downstream consumers.
+    Outbox,
+    /// One entry per stored article, keyed by `dedup_key`.
+    Dedup,
+    /// Newest publication time fetched per provider, see `watermark.rs`.
+    Watermarks,
+    /// Event timelines per ticker, see `timeline.rs`.
+    Timelines,
+    /// Lifecycle of the raised alerts, see `alerts.rs`.
+    Alerts,
+    /// Alert mutes set by operators, see `alerts.rs`.
+    AlertMutes,
+    /// Channels each article was notified to, see `digest.rs`.
+    Notifications,
+    /// Last run of each export job, see `export.rs`.
+    ExportRuns,
+    /// Position of the last `export` to each destination, see `export.rs`.
+    ExportWatermarks,
+    /// Latest social sentiment of each symbol, see `snapshots.rs`.
+    SentimentSnapshots,
+    /// Last sequence number of the stored articles, see `DatabaseOps::last_seq`.
+    Counters,
+    /// Requests sent to the provider APIs, see `api_calls.rs`.
+    ApiCalls,
+    /// Calls of each provider per day, see `quota.rs`.
+    Quota,
+    /// Times of the latest successful polls of each provider, see `coverage.rs`.
+    Polls,
+    /// Slow or unindexed queries, see `diagnostics.rs`.
+    Diagnostics,
+}
+impl DataKind {
+    pub fn to_str(&self) -> &str {
+        match self {
+            DataKind::Results => "results",
+            DataKind::Articles => "articles",
+            DataKind::SocialSentiment => "social_sentiment",
+            DataKind::Trending => "trending",
+            DataKind::Audit => "audit",
+            DataKind::Outbox => "outbox",
+            DataKind::Dedup => "dedup",
+            DataKind::Watermarks => "watermarks",
+            DataKind::Timelines => "timelines",
+            DataKind::Alerts => "alerts",
+            DataKind::AlertMutes => "alert_mutes",
+            DataKind::Notifications => "notifications",
+            DataKind::ExportRuns => "export_runs",
+            DataKind::ExportWatermarks => "export_watermarks",
+            DataKind::SentimentSnapshots => "sentiment_snapshots",
+            DataKind::Counters => "counters",
+            DataKind::ApiCalls => "api_calls",
+            DataKind::Quota => "quota",
+            DataKind::Polls => "polls",
+            DataKind::Diagnostics => "diagnostics",
+        }
+    }
+
+    /// Name of the collection holding this kind of data.
+    pub fn collection_name<'a>(&self, config: &'a DatabaseConfig) -> &'a str {
+        match self {
+            DataKind::Results => &config.collection_name,
+            DataKind::Articles => &config.collections.articles,
+            DataKind::SocialSentiment => &config.collections.social_sentiment,
+            DataKind::Trending => &config.collections.trending,
+            DataKind::Audit => &config.collections.audit,
+            DataKind::Outbox => &config.collections.outbox,
+            DataKind::Dedup => &config.collections.dedup,
+            DataKind::Watermarks => &config.collections.watermarks,
+            DataKind::Timelines => &config.collections.timelines,
+            DataKind::Alerts => &config.collections.alerts,
+            DataKind::AlertMutes => &config.collections.alert_mutes,
+            DataKind::Notifications => &config.collections.notifications,
+            DataKind::ExportRuns => &config.collections.export_runs,
+            DataKind::ExportWatermarks => &config.collections.export_watermarks,
+            DataKind::SentimentSnapshots => &config.collections.sentiment_snapshots,
+            DataKind::Counters => &config.collections.counters,
+            DataKind::ApiCalls => &config.collections.api_calls,
+            DataKind::Quota => &config.collections.quota,
+            DataKind::Polls => &config.collections.polls,
+            DataKind::Diagnostics => &config.collections.diagnostics,
+        }
+    }
+}
+impl std::str::FromStr for DataKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "results" => Ok(DataKind::Results),
+            "articles" => Ok(DataKind::Articles),
+            "social_sentiment" => Ok(DataKind::SocialSentiment),
+            "trending" => Ok(DataKind::Trending),
+            "audit" => Ok(DataKind::Audit),
+            "outbox" => Ok(DataKind::Outbox),
+            "dedup" => Ok(DataKind::Dedup),
+            "watermarks" => Ok(DataKind::Watermarks),
+            "timelines" => Ok(DataKind::Timelines),
+            "alerts" => Ok(DataKind::Alerts),
+            "alert_mutes" => Ok(DataKind::AlertMutes),
+            "notifications" => Ok(DataKind::Notifications),
+            "export_runs" => Ok(DataKind::ExportRuns),
+            "export_watermarks" => Ok(DataKind::ExportWatermarks),
+            "sentiment_snapshots" => Ok(DataKind::SentimentSnapshots),
+            "counters" => Ok(DataKind::Counters),
+            "api_calls" => Ok(DataKind::ApiCalls),
+            "quota" => Ok(DataKind::Quota),
+            "polls" => Ok(DataKind::Polls),
+            "diagnostics" => Ok(DataKind::Diagnostics),
+            _ => Err(format!("Unknown data kind `{}`", s)),
+        }
+    }
+}
+
+/// Polls kept per source by `DatabaseOps::record_poll`: a week of polls every minute.
+const POLLS_KEPT: i32 = 10_000;
+
 /// Handles Database Operations
 pub struct DatabaseOps {
+    client: Client,
+    transactions: bool,
+    db: Database,
+    /// Explains the `find` queries, when `database.diagnostics.explain` is set.
+    explainer: Option<QueryExplainer>,
+    /// Sets the `keywords` of stored articles, when enabled.
+    keywords: Option<KeywordExtractor>,
+    /// Sets the `entities` of stored articles, when enabled.
+    entities: Option<EntityExtractor>,
+    /// Translates the stored articles in another language, when enabled.
+    translator: Option<Translator>,
     collection: Collection<Document>,
+    articles: Collection<NormalizedArticle>,
+    social_sentiment: Collection<FMPMarketSentiment>,
+    trending: Collection<FMPMarketSentiment>,
+    audit: Collection<Document>,
+    outbox: Collection<OutboxEvent>,
+    dedup: Collection<Document>,
+    watermarks: Collection<Document>,
+    timelines: Collection<Timeline>,
+    alerts: Collection<AlertRecord>,
+    alert_mutes: Collection<AlertMute>,
+    notifications: Collection<ArticleNotification>,
+    export_runs: Collection<ExportRun>,
+    export_watermarks: Collection<ExportWatermark>,
+    sentiment_snapshots: Collection<SentimentSnapshot>,
+    counters: Collection<Document>,
+    api_calls: Collection<ApiCall>,
+    quota: Collection<Document>,
+    polls: Collection<Document>,
+    diagnostics: Collection<Document>,
 }
 
 impl DatabaseOps {
-    /// Creates a new `DatabaseOps` instance
+    /// Creates a new `DatabaseOps` instance.
+    /// `collection` receives the raw results, the other kinds use their default collection names.
     pub fn new(client: &Client, database: &str, collection: &str) -> Self {
         let db = client.database(database);
-        let collection = db.collection::<Document>(collection);
-        Self { collection }
+        let names = CollectionsConfig::default();
+        Self {
+            client: client.clone(),
+            transactions: false,
+            db: db.clone(),
+            explainer: None,
+            keywords: None,
+            entities: None,
+            translator: None,
+            collection: db.collection::<Document>(collection),
+            articles: db.collection(&names.articles),
+            social_sentiment: db.collection(&names.social_sentiment),
+            trending: db.collection(&names.trending),
+            audit: db.collection(&names.audit),
+            outbox: db.collection(&names.outbox),
+            dedup: db.collection(&names.dedup),
+            watermarks: db.collection(&names.watermarks),
+            timelines: db.collection(&names.timelines),
+            alerts: db.collection(&names.alerts),
+            alert_mutes: db.collection(&names.alert_mutes),
+            notifications: db.collection(&names.notifications),
+            export_runs: db.collection(&names.export_runs),
+            export_watermarks: db.collection(&names.export_watermarks),
+            sentiment_snapshots: db.collection(&names.sentiment_snapshots),
+            counters: db.collection(&names.counters),
+            api_calls: db.collection(&names.api_calls),
+            quota: db.collection(&names.quota),
+            polls: db.collection(&names.polls),
+            diagnostics: db.collection(&names.diagnostics),
+        }
+    }
+
+    /// Creates a new `DatabaseOps` instance using the collection names from the config.
+    pub fn from_config(client: &Client, config: &DatabaseConfig) -> Self {
+        let db = client.database(&config.database_name);
+        Self {
+            client: client.clone(),
+            transactions: false,
+            db: db.clone(),
+            explainer: config.diagnostics.explain.then(|| QueryExplainer::new(db.clone(), config)),
+            keywords: None,
+            entities: None,
+            translator: None,
+            collection: db.collection::<Document>(DataKind::Results.collection_name(config)),
+            articles: db.collection(DataKind::Articles.collection_name(config)),
+            social_sentiment: db.collection(DataKind::SocialSentiment.collection_name(config)),
+            trending: db.collection(DataKind::Trending.collection_name(config)),
+            audit: db.collection(DataKind::Audit.collection_name(config)),
+            outbox: db.collection(DataKind::Outbox.collection_name(config)),
+            dedup: db.collection(DataKind::Dedup.collection_name(config)),
+            watermarks: db.collection(DataKind::Watermarks.collection_name(config)),
+            timelines: db.collection(DataKind::Timelines.collection_name(config)),
+            alerts: db.collection(DataKind::Alerts.collection_name(config)),
+            alert_mutes: db.collection(DataKind::AlertMutes.collection_name(config)),
+            notifications: db.collection(DataKind::Notifications.collection_name(config)),
+            export_runs: db.collection(DataKind::ExportRuns.collection_name(config)),
+            export_watermarks: db.collection(DataKind::ExportWatermarks.collection_name(config)),
+            sentiment_snapshots: db.collection(DataKind::SentimentSnapshots.collection_name(config)),
+            counters: db.collection(DataKind::Counters.collection_name(config)),
+            api_calls: db.collection(DataKind::ApiCalls.collection_name(config)),
+            quota: db.collection(DataKind::Quota.collection_name(config)),
+            polls: db.collection(DataKind::Polls.collection_name(config)),
+            diagnostics: db.collection(DataKind::Diagnostics.collection_name(config)),
+        }
+    }
+
+    /// Enables multi-document transactions for composite writes such as `store_article`.
+    /// Only enable it when `ClientManager::supports_transactions` reports a replica set or sharded cluster.
+    pub fn with_transactions(mut self, enabled: bool) -> Self {
+        self.transactions = enabled;
+        self
+    }
+
+    /// Extracts the keywords of the stored articles lacking them, see `keywords.rs`.
+    pub fn with_keywords(mut self, config: &KeywordsConfig) -> Self {
+        self.keywords = config.enabled.then(|| KeywordExtractor::new(config));
+        self
+    }
+
+    /// Extracts the named entities of the stored articles lacking them, see `entities.rs`.
+    pub fn with_entities(mut self, config: &EntitiesConfig) -> Self {
+        self.entities = config.enabled.then(|| EntityExtractor::new(config));
+        self
+    }
+
+    /// Translates the stored articles in another language, see `translate.rs`.
+    pub fn with_translator(mut self, client: Arc<reqwest::Client>, config: &TranslationConfig) -> Self {
+        self.translator = config.enabled.then(|| Translator::new(client, config));
+        self
+    }
+
+    /// Typed handle on the articles collection.
+    pub fn articles(&self) -> &Collection<NormalizedArticle> {
+        &self.articles
+    }
+
+    /// Typed handle on the social sentiment collection.
+    pub fn social_sentiment(&self) -> &Collection<FMPMarketSentiment> {
+        &self.social_sentiment
+    }
+
+    /// Typed handle on the trending sentiment collection.
+    pub fn trending(&self) -> &Collection<FMPMarketSentiment> {
+        &self.trending
+    }
+
+    /// Handle on the audit log collection.
+    pub fn audit(&self) -> &Collection<Document> {
+        &self.audit
+    }
+
+    /// Untyped handle on the collection of the given kind, for kind-agnostic operations.
+    pub fn collection(&self, kind: DataKind) -> Collection<Document> {
+        match kind {
+            DataKind::Results => self.collection.clone(),
+            DataKind::Articles => self.articles.clone_with_type(),
+            DataKind::SocialSentiment => self.social_sentiment.clone_with_type(),
+            DataKind::Trending => self.trending.clone_with_type(),
+            DataKind::Audit => self.audit.clone(),
+            DataKind::Outbox => self.outbox.clone_with_type(),
+            DataKind::Dedup => self.dedup.clone(),
+            DataKind::Watermarks => self.watermarks.clone(),
+            DataKind::Timelines => self.timelines.clone_with_type(),
+            DataKind::Alerts => self.alerts.clone_with_type(),
+            DataKind::AlertMutes => self.alert_mutes.clone_with_type(),
+            DataKind::Notifications => self.notifications.clone_with_type(),
+            DataKind::ExportRuns => self.export_runs.clone_with_type(),
+            DataKind::ExportWatermarks => self.export_watermarks.clone_with_type(),
+            DataKind::SentimentSnapshots => self.sentiment_snapshots.clone_with_type(),
+            DataKind::Counters => self.counters.clone(),
+            DataKind::ApiCalls => self.api_calls.clone_with_type(),
+            DataKind::Quota => self.quota.clone(),
+            DataKind::Polls => self.polls.clone(),
+            DataKind::Diagnostics => self.diagnostics.clone(),
+        }
+    }
+
+    /// Adds the Reddit mentions of `records` to the record of their symbol and hour in the social
+    /// sentiment collection, created by the first poll of the hour.
+    #[instrument(skip_all)]
+    pub async fn add_reddit_mentions(&self, records: &[FMPMarketSentiment]) -> Result<(), OpError> {
+        if let Some(message) = chaos::write_failure("social sentiment") {
+            return Err(OpError::UpdateError { message });
+        }
+        let options = UpdateOptions::builder().upsert(true).build();
+        for record in records {
+            let filter = doc! { "name": record.name.as_deref(), "symbol": record.symbol.as_deref(), "date": record.date.as_deref() };
+            let update = doc! { "$inc": {
+                "reddit_posts": record.reddit_posts.unwrap_or(0) as i64,
+                "reddit_comments": record.reddit_comments.unwrap_or(0) as i64,
+                "reddit_score": record.reddit_score.unwrap_or(0),
+            } };
+            self.social_sentiment.update_one(filter, update, options.clone()).await
+                .map_err(|e| OpError::UpdateError { message: format!("Failed to add Reddit mentions: {}", e) })?;
+        }
+        Ok(())
+    }
+
+    /// Inserts trending sentiment snapshots into the trending collection.
+    #[instrument(skip_all)]
+    pub async fn insert_trending(&self, records: &[FMPMarketSentiment]) -> Result<(), OpError> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.trending.insert_many(records, None).await
+            .map(|_| ())
+            .map_err(|e| OpError::InsertionError {
+                message: format!("Failed to insert trending sentiment: {}", e),
+            })
+    }
+
+    /// Stores an article together with its outbox event and dedup entry.
+    ///
+    /// The three writes run in one transaction when enabled, so a crash cannot leave an article
+    /// without its event (or a dedup entry without its article). An article already stored is
+    /// handed to `update_article`, which keeps its previous version if the content changed.
+    ///
+    /// The dedup entry is written with the sequence number of the article, taken in the same
+    /// transaction: the counter stays locked until the commit, so the numbers become visible in
+    /// order. Without transactions, two concurrent stores can still make them visible out of order,
+    /// and the article is written before its dedup entry: a store interrupted in between is
+    /// completed by the next delivery.
+    #[instrument(skip_all)]
+    pub async fn store_article(&self, article: &NormalizedArticle) -> Result<StoreOutcome, OpError> {
+        // All the articles without an id would share the `provider:` dedup key, keeping only the first.
+        if article.id.trim().is_empty() {
+            warn!("Article without an id not stored. | Provider: {} | Title: {:?}", article.provider, article.title);
+            return Ok(StoreOutcome::Rejected);
+        }
+        if let Some(message) = chaos::write_failure("articles") {
+            return Err(OpError::InsertionError { message });
+        }
+        // Translated before the keywords are extracted, and only once: not on re-deliveries.
+        let translated;
+        let article = match &self.translator {
+            Some(translator) if translator.needs_translation(article) && !self.is_stored(article).await? => {
+                translated = translator.translate(article).await;
+                &translated
+            }
+            _ => article,
+        };
+        let with_keywords;
+        let article = match &self.keywords {
+            Some(extractor) if article.keywords.is_empty() => {
+                with_keywords = NormalizedArticle { keywords: extractor.keywords_of(article), ..article.clone() };
+                &with_keywords
+            }
+            _ => article,
+        };
+        let with_entities;
+        let article = match &self.entities {
+            Some(extractor) if article.entities.is_empty() => {
+                with_entities = NormalizedArticle { entities: extractor.entities_of(article), ..article.clone() };
+                &with_entities
+            }
+            _ => article,
+        };
+        let key = dedup_key(article);
+        let event = OutboxEvent::article_stored(&key, article);
+
+        if !self.transactions {
+            // Re-deliveries do not take a number. One stored concurrently skips one, a harmless gap.
+            if self.is_stored(article).await? {
+                return self.update_article(article).await;
+            }
+            let seq = self.next_seq(None).await
+                .map_err(|e| OpError::InsertionError { message: format!("Failed to take the sequence number of article {}: {}", key, e) })?;
+            // Article first, upserted so a store retried after a crash in between writes it once.
+            // The dedup entry then decides which of concurrent stores inserted the article.
+            self.upsert_article(article).await?;
+            match self.dedup.insert_one(dedup_entry(&key, article, seq), None).await {
+                Ok(_) => {},
+                Err(e) if is_duplicate_key(&e) => return self.update_article(article).await,
+                Err(e) => return Err(OpError::InsertionError { message: e.to_string() }),
+            }
+            self.outbox.insert_one(event, None).await
+                .map_err(|e| OpError::InsertionError { message: e.to_string() })?;
+            return Ok(StoreOutcome::Inserted);
+        }
+
+        let mut attempt = 1;
+        loop {
+            let mut session = self.client.start_session(None).await
+                .map_err(|e| OpError::TransactionError { message: e.to_string() })?;
+            session.start_transaction(None).await
+                .map_err(|e| OpError::TransactionError { message: e.to_string() })?;
+
+            let result = match self.store_article_in(&mut session, &key, article, event.clone()).await {
+                Ok(()) => session.commit_transaction().await,
+                Err(e) => {
+                    if let Err(abort) = session.abort_transaction().await {
+                        warn!("Failed to abort transaction: {}", abort);
+                    }
+                    Err(e)
+                }
+            };
+            match result {
+                Ok(()) => return Ok(StoreOutcome::Inserted),
+                Err(e) if is_duplicate_key(&e) => return self.update_article(article).await,
+                // Concurrent stores conflict on the counter until the first one commits.
+                Err(e) if e.contains_label(TRANSIENT_TRANSACTION_ERROR) && attempt < MAX_TRANSACTION_ATTEMPTS => attempt += 1,
+                Err(e) => return Err(OpError::TransactionError { message: e.to_string() }),
+            }
+        }
+    }
+
+    /// Inserts `article` unless an article with its provider and id is stored.
+    async fn upsert_article(&self, article: &NormalizedArticle) -> Result<(), OpError> {
+        let filter = doc! { "provider": &article.provider, "id": &article.id };
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.articles.update_one(filter, doc! { "$setOnInsert": to_document(article)? }, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::InsertionError { message: format!("Failed to insert article {}: {}", dedup_key(article), e) })
+    }
+
+    /// Stores again an `article` whose dedup entry was written but not the article itself, by a
+    /// crash in between. Removed articles, soft-deleted, redacted or purged, are left alone.
+    async fn restore_article(&self, article: &NormalizedArticle) -> Result<StoreOutcome, OpError> {
+        let key = dedup_key(article);
+        let removed = self.find_one(&self.articles, doc! { "provider": &article.provider, "id": &article.id }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to retrieve article: {}", e) })?;
+        let entry = self.find_one(&self.dedup, doc! { "_id": &key }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search dedup entries: {}", e) })?;
+        let purged = entry.as_ref().is_none_or(|entry| entry.get_bool("purged").unwrap_or(false));
+        if removed.is_some() || purged {
+            return Ok(StoreOutcome::Unchanged);
+        }
+        warn!("Article {} has a dedup entry but was not stored. Storing it again.", key);
+        self.upsert_article(article).await?;
+        self.outbox.insert_one(OutboxEvent::article_stored(&key, article), None).await
+            .map_err(|e| OpError::InsertionError { message: e.to_string() })?;
+        Ok(StoreOutcome::Inserted)
+    }
+
+    /// Takes the next sequence number of the articles, within `session` when given.
+    async fn next_seq(&self, session: Option<&mut ClientSession>) -> Result<i64, MongoError> {
+        let options = FindOneAndUpdateOptions::builder().upsert(true).return_document(ReturnDocument::After).build();
+        let (filter, update) = (doc! { "_id": ARTICLE_SEQ }, doc! { "$inc": { "seq": 1_i64 } });
+        let counter = match session {
+            Some(session) => self.counters.find_one_and_update_with_session(filter, update, options, session).await?,
+            None => self.counters.find_one_and_update(filter, update, options).await?,
+        };
+        Ok(counter.and_then(|c| c.get_i64("seq").ok()).unwrap_or_default())
+    }
+
+    /// Sequence number of the last article stored, 0 when none was.
+    #[instrument(skip_all)]
+    pub async fn last_seq(&self) -> Result<u64, OpError> {
+        let counter = self.find_one(&self.counters, doc! { "_id": ARTICLE_SEQ }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to read the article sequence: {}", e) })?;
+        Ok(counter.and_then(|c| c.get_i64("seq").ok()).unwrap_or_default() as u64)
+    }
+
+    /// Sequence number of the last article stored before `since` (RFC 3339), 0 when none was.
+    #[instrument(skip_all)]
+    pub async fn seq_before(&self, since: &str) -> Result<u64, OpError> {
+        let options = FindOneOptions::builder().sort(doc! { "seq": -1 }).build();
+        let entry = self.find_one(&self.dedup, doc! { "stored_at": { "$lt": since }, "seq": { "$exists": true } }, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search dedup entries: {}", e) })?;
+        Ok(entry.and_then(|entry| entry.get_i64("seq").ok()).unwrap_or_default() as u64)
+    }
+
+    async fn store_article_in(
+        &self,
+        session: &mut ClientSession,
+        key: &str,
+        article: &NormalizedArticle,
+        event: OutboxEvent,
+    ) -> Result<(), MongoError> {
+        let seq = self.next_seq(Some(&mut *session)).await?;
+        self.dedup.insert_one_with_session(dedup_entry(key, article, seq), None, session).await?;
+        self.articles.insert_one_with_session(article, None, session).await?;
+        self.outbox.insert_one_with_session(event, None, session).await?;
+        Ok(())
+    }
+
+    /// Stores a re-delivered `article` as a new version when its content or sentiment changed.
+    ///
+    /// The replaced version is pushed to the `history` array of the stored article with its
+    /// `replaced_at` time, `updated_at` is set and `revision` incremented. An `article_updated`
+    /// event is written to the outbox.
+    ///
+    /// The update only applies to the revision that was compared: when a concurrent re-delivery
+    /// updates the article first, it is read and compared again, so each version is pushed once.
+    #[instrument(skip_all)]
+    pub async fn update_article(&self, article: &NormalizedArticle) -> Result<StoreOutcome, OpError> {
+        // Deleted and redacted articles are never brought back by a re-delivery.
+        let filter = doc! {
+            "provider": &article.provider,
+            "id": &article.id,
+            "deleted": { "$ne": true },
+            "redacted": { "$ne": true },
+        };
+        let articles = self.articles.clone_with_type::<Document>();
+        // Watchlist tags accumulate across deliveries.
+        let tags = doc! { "watchlist": { "$each": article.watchlist.clone() } };
+        for _ in 0..MAX_TRANSACTION_ATTEMPTS {
+            let stored = self.find_one(&articles, filter.clone(), None).await
+                .map_err(|e| OpError::SearchError { message: format!("Failed to retrieve article: {}", e) })?;
+            let Some(stored) = stored else {
+                // Removed article, or dedup entry without its article written before a crash.
+                return self.restore_article(article).await;
+            };
+            // `null` also matches the articles never updated, which have no `revision`.
+            let revision = stored.get("revision").cloned().unwrap_or(Bson::Null);
+            let stored: NormalizedArticle = from_document(stored)?;
+            if stored.same_content(article) {
+                if article.watchlist.iter().any(|symbol| !stored.watchlist.contains(symbol)) {
+                    articles.update_one(filter, doc! { "$addToSet": tags }, None).await
+                        .map_err(|e| OpError::UpdateError { message: format!("Failed to update article: {}", e) })?;
+                }
+                return Ok(StoreOutcome::Unchanged);
+            }
+
+            let translated;
+            let article = match &self.translator {
+                Some(translator) => {
+                    translated = translator.translate(article).await;
+                    &translated
+                }
+                None => article,
+            };
+            let updated_at = now();
+            let mut current = to_document(article)?;
+            current.insert("updated_at", &updated_at);
+            current.remove("watchlist");
+            let update = doc! {
+                "$set": current,
+                "$addToSet": tags.clone(),
+                "$push": { "history": to_document(&ArticleVersion::of(&stored, &updated_at))? },
+                "$inc": { "revision": 1 },
+            };
+            let mut compared = filter.clone();
+            compared.insert("revision", revision);
+            let result = articles.update_one(compared, update, None).await
+                .map_err(|e| OpError::UpdateError { message: format!("Failed to update article: {}", e) })?;
+            if result.matched_count == 0 {
+                continue;
+            }
+            self.outbox.insert_one(OutboxEvent::article_updated(&dedup_key(article), article), None).await
+                .map_err(|e| OpError::InsertionError { message: e.to_string() })?;
+            return Ok(StoreOutcome::Updated);
+        }
+        Err(OpError::UpdateError {
+            message: format!("Failed to update article {}: updated concurrently", dedup_key(article)),
+        })
+    }
+
+    /// Soft-deletes an article (e.g. a retracted story): it is flagged `deleted` but kept.
+    ///
+    /// An `article_deleted` event is recorded in the outbox, like the stored and updated articles.
+    /// Returns the article as now stored, `None` when no such article is stored.
+    #[instrument(skip_all)]
+    pub async fn soft_delete_article(&self, provider: &str, id: &str, reason: &str) -> Result<Option<NormalizedArticle>, OpError> {
+        let update = doc! { "$set": { "deleted": true, "deleted_at": now(), "deletion_reason": reason } };
+        self.remove_article(provider, id, update, "article_deleted").await
+    }
+
+    /// Redacts an article: its content and version history are erased, only the identifiers remain.
+    /// Its item is removed from the raw provider results, and the captured responses containing it
+    /// are deleted (see `captures::purge`).
+    ///
+    /// An `article_redacted` event is recorded in the outbox, like the stored and updated articles.
+    /// Returns what remains of the article, `None` when no such article is stored.
+    #[instrument(skip_all)]
+    pub async fn redact_article(&self, provider: &str, id: &str, reason: &str) -> Result<Option<NormalizedArticle>, OpError> {
+        let update = doc! {
+            "$set": {
+                "title": null,
+                "summary": null,
+                "authors": [],
+                "keywords": [],
+                "translation": null,
+                "entities": [],
+                "redacted": true,
+                "redacted_at": now(),
+                "redaction_reason": reason,
+            },
+            "$unset": { "history": "" },
+        };
+        let Some(article) = self.remove_article(provider, id, update, "article_redacted").await? else {
+            return Ok(None);
+        };
+        self.pull_raw_article(provider, id).await?;
+        let removed = captures::purge(id);
+        if removed > 0 {
+            info!("Removed {} captured responses of the redacted article.", removed);
+        }
+        Ok(Some(article))
+    }
+
+    /// Removes the item of an article from the raw provider results. Only the MarketAux and
+    /// Alpha Vantage responses are kept raw.
+    async fn pull_raw_article(&self, provider: &str, id: &str) -> Result<(), OpError> {
+        let (filter, update) = match provider {
+            MARKETAUX_PROVIDER => (
+                doc! { "$or": [{ "marketaux.data.uuid": id }, { "marketaux.data.url": id }] },
+                doc! { "$pull": { "marketaux.data": { "$or": [{ "uuid": id }, { "url": id }] } } },
+            ),
+            ALPHAVANTAGE_PROVIDER => (
+                doc! { "alphavantage.feed.url": id },
+                doc! { "$pull": { "alphavantage.feed": { "url": id } } },
+            ),
+            _ => return Ok(()),
+        };
+        self.collection.update_many(filter, update, None).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to redact the raw results: {}", e) })
+    }
+
+    async fn remove_article(&self, provider: &str, id: &str, update: Document, event: &str) -> Result<Option<NormalizedArticle>, OpError> {
+        let filter = doc! { "provider": provider, "id": id };
+        let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        let article = self.articles.find_one_and_update(filter, update, options).await
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to update article: {}", e) })?;
+        let Some(article) = article else {
+            return Ok(None);
+        };
+        let key = format!("{}:{}", provider, id);
+        self.outbox.insert_one(OutboxEvent::new(event, &key, provider), None).await
+            .map_err(|e| OpError::InsertionError { message: e.to_string() })?;
+        info!("Article `{}` removed. | Event: {}", key, event);
+        Ok(Some(article))
+    }
+
+    /// Stores articles one by one with `store_article`, returning how many were new.
+    #[instrument(skip_all)]
+    pub async fn store_articles(&self, articles: &[NormalizedArticle]) -> Result<usize, OpError> {
+        let mut stored = 0;
+        let mut updated = 0;
+        for article in articles {
+            match self.store_article(article).await? {
+                StoreOutcome::Inserted => {
+                    stored += 1;
+                    if let Some(published_at) = &article.published_at {
+                        freshness::record(&article.provider, published_at);
+                    }
+                    latency::record(Stage::Stored, [article]);
+                }
+                StoreOutcome::Updated => updated += 1,
+                StoreOutcome::Unchanged | StoreOutcome::Rejected => {},
+            }
+        }
+        if updated > 0 {
+            info!("Stored a new version of {} articles.", updated);
+        }
+        Ok(stored)
+    }
+
+    /// Records a successful poll of `source` at `polled_at` (RFC 3339). The latest `POLLS_KEPT` are kept,
+    /// in one document per source.
+    #[instrument(skip_all)]
+    pub async fn record_poll(&self, source: &str, polled_at: &str) -> Result<(), OpError> {
+        let update = doc! { "$push": { "times": { "$each": [polled_at], "$slice": -POLLS_KEPT } } };
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.polls.update_one(doc! { "_id": source }, update, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to record the poll of {}: {}", source, e) })
+    }
+
+    /// Times `source` was polled successfully between `from` and `to` (RFC 3339), oldest first.
+    #[instrument(skip_all)]
+    pub async fn poll_times(&self, source: &str, from: &str, to: &str) -> Result<Vec<String>, OpError> {
+        let record = self.find_one(&self.polls, doc! { "_id": source }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to read the polls of {}: {}", source, e) })?;
+        let mut times: Vec<String> = record
+            .iter()
+            .flat_map(|doc| doc.get_array("times").into_iter().flatten())
+            .filter_map(|t| t.as_str())
+            .filter(|t| *t >= from && *t <= to)
+            .map(str::to_string)
+            .collect();
+        times.sort();
+        Ok(times)
+    }
+
+    /// Tickers of each article published between `from` and `to` (RFC 3339) with at least two.
+    #[instrument(skip_all)]
+    pub async fn ticker_sets(&self, from: &str, to: &str) -> Result<Vec<Vec<String>>, OpError> {
+        let filter = doc! {
+            "published_at": { "$gte": from, "$lte": to },
+            "tickers.1": { "$exists": true },
+            "deleted": { "$ne": true },
+        };
+        let options = FindOptions::builder().projection(doc! { "_id": 0, "tickers": 1 }).build();
+        let docs = self.find(&self.articles.clone_with_type::<Document>(), filter, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search articles: {}", e) })?;
+
+        let mut sets = Vec::new();
+        for doc in docs {
+            if let Ok(tickers) = doc.get_array("tickers") {
+                sets.push(tickers.iter().filter_map(|t| t.as_str().map(str::to_string)).collect());
+            }
+        }
+        Ok(sets)
+    }
+
+    /// Articles published between `from` and `to` (RFC 3339) with one of the harmonized `labels`, newest first.
+    #[instrument(skip_all)]
+    pub async fn articles_with_sentiment(
+        &self,
+        labels: &[SentimentLabel],
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<NormalizedArticle>, OpError> {
+        let labels: Vec<&str> = labels.iter().map(SentimentLabel::to_str).collect();
+        let filter = doc! {
+            "sentiment": { "$in": labels },
+            "published_at": { "$gte": from, "$lte": to },
+            "deleted": { "$ne": true },
+        };
+        let options = FindOptions::builder().sort(doc! { "published_at": -1 }).build();
+        self.find(&self.articles, filter, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search articles: {}", e) })
+    }
+
+    /// Number of articles per harmonized sentiment among those published between `from` and `to` (RFC 3339).
+    ///
+    /// Articles without sentiment are not counted.
+    #[instrument(skip_all)]
+    pub async fn sentiment_counts(&self, from: &str, to: &str) -> Result<BTreeMap<SentimentLabel, u64>, OpError> {
+        let pipeline = [
+            doc! { "$match": {
+                "published_at": { "$gte": from, "$lte": to },
+                "sentiment": { "$ne": null },
+                "deleted": { "$ne": true },
+            } },
+            doc! { "$group": { "_id": "$sentiment", "count": { "$sum": 1 } } },
+        ];
+        let docs = self.aggregate(&self.articles, pipeline).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to aggregate articles: {}", e) })?;
+
+        let mut counts = BTreeMap::new();
+        for doc in docs {
+            let label = doc.get_str("_id").ok().and_then(|label| label.parse::<SentimentLabel>().ok());
+            let count = doc.get_i32("count").map(i64::from).or_else(|_| doc.get_i64("count"));
+            if let (Some(label), Ok(count)) = (label, count) {
+                counts.insert(label, count as u64);
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Watermark of each provider (RFC 3339): the newest publication time saved with
+    /// `save_watermark` or among its stored articles, whichever is later.
+    #[instrument(skip_all)]
+    pub async fn watermarks(&self) -> Result<HashMap<String, String>, OpError> {
+        let pipeline = [
+            doc! { "$match": { "published_at": { "$type": "string" } } },
+            doc! { "$group": { "_id": "$provider", "published_at": { "$max": "$published_at" } } },
+        ];
+        let mut docs: Vec<Document> = self.aggregate(&self.articles, pipeline).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to aggregate articles: {}", e) })?;
+        let saved: Vec<Document> = self.find(&self.watermarks, None, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search watermarks: {}", e) })?;
+        docs.extend(saved);
+
+        let mut marks: HashMap<String, String> = HashMap::new();
+        for doc in docs {
+            if let (Ok(provider), Ok(published_at)) = (doc.get_str("_id"), doc.get_str("published_at")) {
+                let mark = marks.entry(provider.to_string()).or_default();
+                if published_at > mark.as_str() {
+                    *mark = published_at.to_string();
+                }
+            }
+        }
+        Ok(marks)
+    }
+
+    /// Whether `article` has a dedup entry, i.e. was already stored.
+    async fn is_stored(&self, article: &NormalizedArticle) -> Result<bool, OpError> {
+        self.find_one(&self.dedup, doc! { "_id": dedup_key(article) }, None).await
+            .map(|entry| entry.is_some())
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search dedup entries: {}", e) })
+    }
+
+    /// When the last article was stored (RFC 3339), `None` when none was.
+    #[instrument(skip_all)]
+    pub async fn last_stored_at(&self) -> Result<Option<String>, OpError> {
+        let options = FindOneOptions::builder().sort(doc! { "stored_at": -1 }).build();
+        let last = self.find_one(&self.dedup, None, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search dedup entries: {}", e) })?;
+        Ok(last.and_then(|doc| doc.get_str("stored_at").ok().map(str::to_string)))
+    }
+
+    /// Saves the watermark of `provider` (RFC 3339). A watermark never moves back.
+    #[instrument(skip_all)]
+    pub async fn save_watermark(&self, provider: &str, published_at: &str) -> Result<(), OpError> {
+        let update = doc! { "$max": { "published_at": published_at }, "$set": { "updated_at": now() } };
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.watermarks.update_one(doc! { "_id": provider }, update, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save watermark: {}", e) })
+    }
+
+    /// Adds `calls` to the calls of `provider` on `day` (`YYYY-MM-DD`), see `quota.rs`.
+    #[instrument(skip_all)]
+    pub async fn add_quota_calls(&self, provider: &str, day: &str, calls: u64) -> Result<(), OpError> {
+        let update = doc! { "$set": { "provider": provider, "day": day }, "$inc": { "calls": calls as i64 } };
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.quota.update_one(doc! { "_id": format!("{}|{}", provider, day) }, update, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save quota calls: {}", e) })
+    }
+
+    /// Calls of every provider on `day` (`YYYY-MM-DD`).
+    #[instrument(skip_all)]
+    pub async fn quota_calls(&self, day: &str) -> Result<HashMap<String, u64>, OpError> {
+        let docs: Vec<Document> = self.find(&self.quota, doc! { "day": day }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search quota calls: {}", e) })?;
+        Ok(docs
+            .iter()
+            .filter_map(|doc| Some((doc.get_str("provider").ok()?.to_string(), doc.get_i64("calls").ok()? as u64)))
+            .collect())
+    }
+
+    /// Stores `timeline`, replacing the one of the same ticker and window.
+    #[instrument(skip_all)]
+    pub async fn save_timeline(&self, timeline: &Timeline) -> Result<(), OpError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.timelines.replace_one(doc! { "_id": &timeline.id }, timeline, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save timeline: {}", e) })
+    }
+
+    /// The alert of `id` (`rule:subject`), whatever its state.
+    #[instrument(skip_all)]
+    pub async fn alert(&self, id: &str) -> Result<Option<AlertRecord>, OpError> {
+        self.find_one(&self.alerts, doc! { "_id": id }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search alerts: {}", e) })
+    }
+
+    /// Alerts in `state`, all of them when `None`, last fired first.
+    #[instrument(skip_all)]
+    pub async fn alerts(&self, state: Option<AlertState>) -> Result<Vec<AlertRecord>, OpError> {
+        let filter = state.map(|state| doc! { "state": state.to_str() });
+        let options = FindOptions::builder().sort(doc! { "last_fired_at": -1 }).build();
+        self.find(&self.alerts, filter, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search alerts: {}", e) })
+    }
+
+    /// Stores `alert`, replacing the one of the same rule and subject.
+    #[instrument(skip_all)]
+    pub async fn save_alert(&self, alert: &AlertRecord) -> Result<(), OpError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.alerts.replace_one(doc! { "_id": &alert.id }, alert, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save alert: {}", e) })
+    }
+
+    /// Alert mutes, expired ones included.
+    #[instrument(skip_all)]
+    pub async fn alert_mutes(&self) -> Result<Vec<AlertMute>, OpError> {
+        self.find(&self.alert_mutes, None, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search alert mutes: {}", e) })
+    }
+
+    /// Stores `mute`, replacing the one of the same rule and subject.
+    #[instrument(skip_all)]
+    pub async fn save_alert_mute(&self, mute: &AlertMute) -> Result<(), OpError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.alert_mutes.replace_one(doc! { "_id": &mute.id }, mute, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save alert mute: {}", e) })
+    }
+
+    /// Notification records of the articles of `keys`, see `digest.rs`.
+    #[instrument(skip_all)]
+    pub async fn article_notifications(&self, keys: &[String]) -> Result<Vec<ArticleNotification>, OpError> {
+        self.find(&self.notifications, doc! { "_id": { "$in": keys } }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search notifications: {}", e) })
+    }
+
+    /// Stores `notification`, replacing the one of the same article.
+    #[instrument(skip_all)]
+    pub async fn save_article_notification(&self, notification: &ArticleNotification) -> Result<(), OpError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.notifications.replace_one(doc! { "_id": &notification.key }, notification, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save notification: {}", e) })
+    }
+
+    /// Last run of each export job, see `export.rs`.
+    #[instrument(skip_all)]
+    pub async fn export_runs(&self) -> Result<Vec<ExportRun>, OpError> {
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        self.find(&self.export_runs, None, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search export runs: {}", e) })
+    }
+
+    /// Stores `run`, replacing the previous run of its job.
+    #[instrument(skip_all)]
+    pub async fn save_export_run(&self, run: &ExportRun) -> Result<(), OpError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.export_runs.replace_one(doc! { "_id": &run.job }, run, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save export run: {}", e) })
+    }
+
+    /// Position of the last export to `destination`, `None` before the first one.
+    #[instrument(skip_all)]
+    pub async fn export_watermark(&self, destination: &str) -> Result<Option<ExportWatermark>, OpError> {
+        self.find_one(&self.export_watermarks, doc! { "_id": destination }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search export watermarks: {}", e) })
+    }
+
+    /// Stores `watermark`, replacing the previous one of its destination.
+    #[instrument(skip_all)]
+    pub async fn save_export_watermark(&self, watermark: &ExportWatermark) -> Result<(), OpError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.export_watermarks.replace_one(doc! { "_id": &watermark.destination }, watermark, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save export watermark: {}", e) })
+    }
+
+    /// Snapshots of `symbols`, of every symbol when empty.
+    #[instrument(skip_all)]
+    pub async fn sentiment_snapshots(&self, symbols: &[String]) -> Result<Vec<SentimentSnapshot>, OpError> {
+        let filter = (!symbols.is_empty()).then(|| doc! { "_id": { "$in": symbols } });
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        self.find(&self.sentiment_snapshots, filter, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search sentiment snapshots: {}", e) })
+    }
+
+    /// Stores `snapshot`, replacing the previous one of its symbol.
+    #[instrument(skip_all)]
+    pub async fn save_sentiment_snapshot(&self, snapshot: &SentimentSnapshot) -> Result<(), OpError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.sentiment_snapshots.replace_one(doc! { "_id": &snapshot.symbol }, snapshot, options).await
+            .map(|_| ())
+            .map_err(|e| OpError::UpdateError { message: format!("Failed to save sentiment snapshot: {}", e) })
+    }
+
+    /// Removes the mute of `id`. Returns `false` when there is none.
+    #[instrument(skip_all)]
+    pub async fn remove_alert_mute(&self, id: &str) -> Result<bool, OpError> {
+        self.alert_mutes.delete_one(doc! { "_id": id }, None).await
+            .map(|result| result.deleted_count > 0)
+            .map_err(|e| OpError::DeletionError { message: format!("Failed to remove alert mute: {}", e) })
+    }
+
+    /// Articles published between `from` and `to` (RFC 3339) about one of `tickers`, newest first.
+    #[instrument(skip_all)]
+    pub async fn articles_with_tickers(&self, tickers: &[String], from: &str, to: &str) -> Result<Vec<NormalizedArticle>, OpError> {
+        let tickers: Vec<String> = tickers.iter().map(|t| t.to_uppercase()).collect();
+        let filter = doc! {
+            "tickers": { "$in": tickers },
+            "published_at": { "$gte": from, "$lte": to },
+            "deleted": { "$ne": true },
+        };
+        let options = FindOptions::builder().sort(doc! { "published_at": -1 }).build();
+        self.find(&self.articles, filter, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search articles: {}", e) })
+    }
+
+    /// Articles published since `since` (RFC 3339) with one of `keywords`, newest first.
+    #[instrument(skip_all)]
+    pub async fn articles_with_keywords(&self, keywords: &[String], since: &str, limit: i64) -> Result<Vec<NormalizedArticle>, OpError> {
+        let keywords: Vec<String> = keywords.iter().map(|k| k.to_lowercase()).collect();
+        self.articles_with_any("keywords", &keywords, since, limit).await
+    }
+
+    /// Articles published since `since` (RFC 3339) in one of the internal taxonomy `categories`, newest first.
+    #[instrument(skip_all)]
+    pub async fn articles_with_categories(&self, categories: &[String], since: &str, limit: i64) -> Result<Vec<NormalizedArticle>, OpError> {
+        self.articles_with_any("categories", categories, since, limit).await
+    }
+
+    /// Articles published since `since` (RFC 3339) naming one of the entities `names`, of `kind` if
+    /// given, newest first.
+    #[instrument(skip_all)]
+    pub async fn articles_with_entities(&self, names: &[String], kind: Option<EntityKind>, since: &str, limit: i64) -> Result<Vec<NormalizedArticle>, OpError> {
+        let entities = match kind {
+            Some(kind) => doc! { "$elemMatch": { "name": { "$in": names }, "kind": kind.to_str() } },
+            None => doc! { "$elemMatch": { "name": { "$in": names } } },
+        };
+        let filter = doc! {
+            "entities": entities,
+            "published_at": { "$gte": since },
+            "deleted": { "$ne": true },
+        };
+        let options = FindOptions::builder().sort(doc! { "published_at": -1 }).limit(limit).build();
+        self.find(&self.articles, filter, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search articles: {}", e) })
+    }
+
+    /// Articles published since `since` (RFC 3339), newest first.
+    #[instrument(skip_all)]
+    pub async fn latest_articles(&self, since: &str, limit: i64) -> Result<Vec<NormalizedArticle>, OpError> {
+        self.find_articles(doc! { "published_at": { "$gte": since }, "deleted": { "$ne": true } }, limit).await
+    }
+
+    /// Articles matching `filter`, newest first.
+    #[instrument(skip_all)]
+    pub async fn filtered_articles(&self, filter: &ArticleFilter, limit: i64) -> Result<Vec<NormalizedArticle>, OpError> {
+        let mut query = doc! { "deleted": { "$ne": true } };
+        if !filter.tickers.is_empty() {
+            let tickers: Vec<String> = filter.tickers.iter().map(|t| t.to_uppercase()).collect();
+            query.insert("tickers", doc! { "$in": tickers });
+        }
+        if !filter.providers.is_empty() {
+            query.insert("provider", doc! { "$in": &filter.providers });
+        }
+        if !filter.sources.is_empty() {
+            query.insert("source", doc! { "$in": &filter.sources });
+        }
+        let mut published_at = Document::new();
+        if let Some(from) = &filter.from {
+            published_at.insert("$gte", from);
+        }
+        if let Some(to) = &filter.to {
+            published_at.insert("$lte", to);
+        }
+        if !published_at.is_empty() {
+            query.insert("published_at", published_at);
+        }
+        self.find_articles(query, limit).await
+    }
+
+    /// Articles stored with a sequence number above `seq` with their storage time, lowest first.
+    /// Entries of deleted articles are skipped, however many there are before the next article.
+    #[instrument(skip_all)]
+    pub async fn stored_after(&self, mut seq: u64, limit: i64) -> Result<Vec<SequencedArticle>, OpError> {
+        loop {
+            let options = FindOptions::builder().sort(doc! { "seq": 1 }).limit(limit).build();
+            let entries: Vec<Document> = self.find(&self.dedup, doc! { "seq": { "$gt": seq as i64 } }, options).await
+                .map_err(|e| OpError::SearchError { message: format!("Failed to search dedup entries: {}", e) })?;
+            let Some(last) = entries.last().and_then(|entry| entry.get_i64("seq").ok()) else {
+                return Ok(Vec::new());
+            };
+            let stored = self.sequenced(&entries).await?;
+            if !stored.is_empty() {
+                return Ok(stored);
+            }
+            seq = last as u64;
+        }
+    }
+
+    /// Articles of the dedup `entries` with their sequence number and storage time, lowest first.
+    async fn sequenced(&self, entries: &[Document]) -> Result<Vec<SequencedArticle>, OpError> {
+        let positions: HashMap<String, (u64, String)> = entries
+            .iter()
+            .filter_map(|entry| {
+                let position = (entry.get_i64("seq").ok()? as u64, entry.get_str("stored_at").ok()?.to_string());
+                Some((entry.get_str("_id").ok()?.to_string(), position))
+            })
+            .collect();
+        let mut stored: Vec<SequencedArticle> = self.articles_of_entries(entries).await?
+            .into_iter()
+            .filter_map(|article| {
+                let (seq, stored_at) = positions.get(&dedup_key(&article))?.clone();
+                Some(SequencedArticle { seq, stored_at, article })
+            })
+            .collect();
+        stored.sort_by_key(|stored| stored.seq);
+        Ok(stored)
+    }
+
+    /// Articles of the dedup `entries` that are not deleted, in no particular order.
+    async fn articles_of_entries(&self, entries: &[Document]) -> Result<Vec<NormalizedArticle>, OpError> {
+        let keys: Vec<Document> = entries
+            .iter()
+            .filter_map(|entry| {
+                let provider = entry.get_str("provider").ok()?;
+                let id = entry.get_str("_id").ok()?.strip_prefix(provider)?.strip_prefix(':')?;
+                Some(doc! { "provider": provider, "id": id })
+            })
+            .collect();
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.find(&self.articles, doc! { "$or": keys, "deleted": { "$ne": true } }, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search articles: {}", e) })
+    }
+
+    async fn articles_with_any(&self, field: &str, values: &[String], since: &str, limit: i64) -> Result<Vec<NormalizedArticle>, OpError> {
+        let filter = doc! {
+            field: { "$in": values },
+            "published_at": { "$gte": since },
+            "deleted": { "$ne": true },
+        };
+        self.find_articles(filter, limit).await
+    }
+
+    /// Articles matching `filter`, newest first.
+    async fn find_articles(&self, filter: Document, limit: i64) -> Result<Vec<NormalizedArticle>, OpError> {
+        let options = FindOptions::builder().sort(doc! { "published_at": -1 }).limit(limit).build();
+        self.find(&self.articles, filter, options).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search articles: {}", e) })
+    }
+
+    /// Most frequent keywords of the articles published between `from` and `to` (RFC 3339), with their article count.
+    #[instrument(skip_all)]
+    pub async fn trending_keywords(&self, from: &str, to: &str, limit: i64) -> Result<Vec<(String, u64)>, OpError> {
+        let pipeline = [
+            doc! { "$match": { "published_at": { "$gte": from, "$lte": to }, "deleted": { "$ne": true } } },
+            doc! { "$unwind": "$keywords" },
+            doc! { "$group": { "_id": "$keywords", "count": { "$sum": 1 } } },
+            doc! { "$sort": { "count": -1, "_id": 1 } },
+            doc! { "$limit": limit },
+        ];
+        let docs = self.aggregate(&self.articles, pipeline).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to aggregate articles: {}", e) })?;
+
+        let mut trending = Vec::new();
+        for doc in docs {
+            let count = doc.get_i32("count").map(i64::from).or_else(|_| doc.get_i64("count"));
+            if let (Ok(keyword), Ok(count)) = (doc.get_str("_id"), count) {
+                trending.push((keyword.to_string(), count as u64));
+            }
+        }
+        Ok(trending)
+    }
+
+    /// Appends an entry to the audit log collection.
+    #[instrument(skip_all)]
+    pub async fn insert_audit(&self, entry: Document) -> Result<(), OpError> {
+        self.audit.insert_one(entry, None).await
+            .map(|_| ())
+            .map_err(|e| OpError::InsertionError {
+                message: format!("Failed to insert audit entry: {}", e),
+            })
+    }
+
+    /// Appends records of the requests sent to the provider APIs, see `api_calls.rs`.
+    #[instrument(skip_all)]
+    pub async fn insert_api_calls(&self, calls: &[ApiCall]) -> Result<(), OpError> {
+        self.api_calls.insert_many(calls, None).await
+            .map(|_| ())
+            .map_err(|e| OpError::InsertionError {
+                message: format!("Failed to insert API call records: {}", e),
+            })
     }
 
     /// Inserts a single document into the collection
+    #[instrument(skip_all)]
     pub async fn insert_one(&self, doc: Document) -> Result<(), OpError> {
+        if let Some(message) = chaos::write_failure("news") {
+            return Err(OpError::InsertionError { message });
+        }
         match self.collection.insert_one(doc, None).await {
             Ok(_) => Ok(()),
             Err(e) => Err(OpError::InsertionError {
@@ -142,6 +1345,7 @@ impl DatabaseOps {
     }
 
     /// Inserts multiple documents into the collection
+    #[instrument(skip_all)]
     pub async fn insert_many(&self, docs: Vec<Document>) -> Result<(), OpError> {
         match self.collection.insert_many(docs, None).await {
             Ok(_) => Ok(()),
@@ -152,6 +1356,7 @@ impl DatabaseOps {
     }
 
     /// Updates multiple documents based on a filter
+    #[instrument(skip_all)]
     pub async fn update_many(&self, filter: Document, update: Document) -> Result<(), OpError> {
         let update_doc = doc! { "$set": update };
         match self.collection.update_many(filter, update_doc, UpdateOptions::default()).await {
@@ -163,6 +1368,7 @@ impl DatabaseOps {
     }
 
     /// Deletes multiple documents based on a filter
+    #[instrument(skip_all)]
     pub async fn delete_many(&self, filter: Document) -> Result<(), OpError> {
         match self.collection.delete_many(filter, None).await {
             Ok(_) => Ok(()),
@@ -172,23 +1378,62 @@ impl DatabaseOps {
         }
     }
 
-    /// Searches for documents matching a filter
-    pub async fn search(&self, filter: Document) -> Result<Vec<Document>, OpError> {
-        match self.collection.find(filter, None).await {
-            Ok(mut cursor) => {
-                let mut results = Vec::new();
-                while let Some(doc) = cursor.try_next().await
-                .map_err(|e| OpError::SearchError { message: 
-                    format!("Failed to retrieve document: {}", e)
-                })? {
-                    results.push(doc);
-                }
-                Ok(results)
-            }
-            Err(e) => Err(OpError::SearchError {
-                message: format!("Failed to search documents: {}", e),
-            }),
+    /// Runs `find` on `collection` and reads every document, explaining the query when diagnostics
+    /// are enabled, see `diagnostics.rs`.
+    async fn find<T: DeserializeOwned + Unpin + Send + Sync>(
+        &self,
+        collection: &Collection<T>,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<FindOptions>>,
+    ) -> Result<Vec<T>, MongoError> {
+        let (filter, options) = (filter.into(), options.into());
+        let started = Instant::now();
+        let found = match collection.find(filter.clone(), options.clone()).await {
+            Ok(cursor) => cursor.try_collect().await,
+            Err(e) => Err(e),
+        };
+        if let Some(explainer) = &self.explainer {
+            explainer.explain(collection.name(), ExplainedQuery::find(filter.unwrap_or_default(), options.as_ref()), started.elapsed());
         }
+        found
+    }
+
+    /// Runs `find_one` on `collection`, explaining the query like `find`.
+    async fn find_one<T: DeserializeOwned + Unpin + Send + Sync>(
+        &self,
+        collection: &Collection<T>,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<FindOneOptions>>,
+    ) -> Result<Option<T>, MongoError> {
+        let (filter, options) = (filter.into(), options.into());
+        let started = Instant::now();
+        let found = collection.find_one(filter.clone(), options.clone()).await;
+        if let Some(explainer) = &self.explainer {
+            explainer.explain(collection.name(), ExplainedQuery::find_one(filter.unwrap_or_default(), options.as_ref()), started.elapsed());
+        }
+        found
+    }
+
+    /// Runs the aggregation `pipeline` on `collection` and reads every result, explaining it like
+    /// `find`.
+    async fn aggregate<T>(&self, collection: &Collection<T>, pipeline: impl IntoIterator<Item = Document>) -> Result<Vec<Document>, MongoError> {
+        let pipeline: Vec<Document> = pipeline.into_iter().collect();
+        let started = Instant::now();
+        let found = match collection.aggregate(pipeline.clone(), None).await {
+            Ok(cursor) => cursor.try_collect().await,
+            Err(e) => Err(e),
+        };
+        if let Some(explainer) = &self.explainer {
+            explainer.explain(collection.name(), ExplainedQuery::Aggregate { pipeline }, started.elapsed());
+        }
+        found
+    }
+
+    /// Searches for the news results matching a filter
+    #[instrument(skip_all)]
+    pub async fn search(&self, filter: Document) -> Result<Vec<NewsResult>, OpError> {
+        self.find(&self.collection.clone_with_type::<NewsResult>(), filter, None).await
+            .map_err(|e| OpError::SearchError { message: format!("Failed to search documents: {}", e) })
     }
 
     pub fn convert_to_document(&self, value: Value) -> Result<Document, OpError> {
@@ -196,4 +1441,170 @@ impl DatabaseOps {
             OpError::ConversionError { message: e.to_string() }
         })
     }
-}
\ No newline at end of file
+}
+
+/// Outcome of `DatabaseOps::store_article`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreOutcome {
+    /// First delivery of the article.
+    Inserted,
+    /// Re-delivery with a new content or sentiment, stored as a new version.
+    Updated,
+    /// Re-delivery of the stored version.
+    Unchanged,
+    /// Article without an id, not stored.
+    Rejected,
+}
+
+/// A replaced version of an article, kept in its `history`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ArticleVersion {
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub tickers: Vec<String>,
+    pub topics: Vec<String>,
+    pub sentiment_score: Option<f64>,
+    pub sentiment_label: Option<String>,
+    pub replaced_at: String,
+}
+impl ArticleVersion {
+    pub fn of(article: &NormalizedArticle, replaced_at: &str) -> Self {
+        Self {
+            title: article.title.clone(),
+            summary: article.summary.clone(),
+            tickers: article.tickers.clone(),
+            topics: article.topics.clone(),
+            sentiment_score: article.sentiment_score,
+            sentiment_label: article.sentiment_label.clone(),
+            replaced_at: replaced_at.to_string(),
+        }
+    }
+}
+
+/// Event written to the outbox collection alongside each stored article.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEvent {
+    pub event: String,
+    pub key: String,
+    pub provider: String,
+    pub created_at: String,
+    pub dispatched: bool,
+}
+impl OutboxEvent {
+    pub fn article_stored(key: &str, article: &NormalizedArticle) -> Self {
+        Self::new("article_stored", key, &article.provider)
+    }
+
+    pub fn article_updated(key: &str, article: &NormalizedArticle) -> Self {
+        Self::new("article_updated", key, &article.provider)
+    }
+
+    pub fn new(event: &str, key: &str, provider: &str) -> Self {
+        Self {
+            event: event.to_string(),
+            key: key.to_string(),
+            provider: provider.to_string(),
+            created_at: now(),
+            dispatched: false,
+        }
+    }
+}
+
+/// `_id` of the counter of the stored articles in the counters collection.
+const ARTICLE_SEQ: &str = "articles";
+
+/// Attempts of a transaction aborted by a conflicting one, see `store_article`, and of an update
+/// preempted by a concurrent one, see `update_article`.
+const MAX_TRANSACTION_ATTEMPTS: u32 = 5;
+
+/// Dedup entry of the article stored under `key` with the sequence number `seq`.
+fn dedup_entry(key: &str, article: &NormalizedArticle, seq: i64) -> Document {
+    doc! { "_id": key, "provider": &article.provider, "stored_at": now(), "seq": seq }
+}
+
+/// Key identifying an article across deliveries: `provider:id`.
+pub fn dedup_key(article: &NormalizedArticle) -> String {
+    format!("{}:{}", article.provider, article.id)
+}
+
+fn is_duplicate_key(error: &MongoError) -> bool {
+    const DUPLICATE_KEY: i32 = 11000;
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(e)) => e.code == DUPLICATE_KEY,
+        ErrorKind::Command(e) => e.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+/// Serializes a typed model into a `bson::Document`.
+pub fn to_document<T: Serialize>(value: &T) -> Result<Document, OpError> {
+    mongodb::bson::to_document(value).map_err(|e| {
+        OpError::ConversionError { message: e.to_string() }
+    })
+}
+
+/// Deserializes a `bson::Document` (typically a search result) into a typed model.
+/// Fields unknown to the model, such as `_id`, are ignored.
+pub fn from_document<T: DeserializeOwned>(doc: Document) -> Result<T, OpError> {
+    mongodb::bson::from_document(doc).map_err(|e| {
+        OpError::ConversionError { message: e.to_string() }
+    })
+}
+
+/// Implements `TryFrom<Document>` and `TryFrom<T> for Document` for serde models.
+macro_rules! document_conversions {
+    ($($model:ty),* $(,)?) => {
+        $(
+            impl TryFrom<Document> for $model {
+                type Error = OpError;
+                fn try_from(doc: Document) -> Result<Self, Self::Error> {
+                    from_document(doc)
+                }
+            }
+
+            impl TryFrom<$model> for Document {
+                type Error = OpError;
+                fn try_from(model: $model) -> Result<Self, Self::Error> {
+                    to_document(&model)
+                }
+            }
+        )*
+    };
+}
+
+document_conversions!(NewsResult, NormalizedArticle, FMPMarketSentiment, TickerSentiment);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alphavantage::AlphaVantageApiResponse;
+    use crate::marketaux::MarketAuxResponse;
+
+    const MARKETAUX_ALL: &str = include_str!("../fixtures/marketaux/all.json");
+    const ALPHAVANTAGE_NEWS_SENTIMENT: &str = include_str!("../fixtures/alphavantage/news_sentiment.json");
+    const FMP_SOCIAL_TRENDING: &str = include_str!("../fixtures/fmp/social-sentiments_trending.json");
+
+    #[test]
+    fn normalized_articles_round_trip_through_bson() {
+        let marketaux: MarketAuxResponse = serde_json::from_str(MARKETAUX_ALL).unwrap();
+        let alphavantage: AlphaVantageApiResponse = serde_json::from_str(ALPHAVANTAGE_NEWS_SENTIMENT).unwrap();
+        let articles: Vec<NormalizedArticle> = marketaux.data.iter().map(NormalizedArticle::from)
+            .chain(alphavantage.feed.iter().map(NormalizedArticle::from))
+            .collect();
+
+        assert_eq!(articles[0].published_at.as_deref(), Some("2024-08-07T12:31:00Z"));
+        assert_eq!(articles[2].published_at.as_deref(), Some("2024-08-07T13:30:00Z"));
+        for article in articles {
+            let doc = Document::try_from(article.clone()).unwrap();
+            assert_eq!(NormalizedArticle::try_from(doc).unwrap(), article);
+        }
+    }
+
+    #[test]
+    fn market_sentiment_round_trips_through_bson() {
+        let sentiment: Vec<FMPMarketSentiment> = serde_json::from_str(FMP_SOCIAL_TRENDING).unwrap();
+        let doc = Document::try_from(sentiment[0].clone()).unwrap();
+        assert_eq!(doc.get_str("symbol").unwrap(), "SMCI");
+        assert!(FMPMarketSentiment::try_from(doc).is_ok());
+    }
+}
@@ -0,0 +1,162 @@
+//! Deduplication of the task requests of a client.
+//!
+//! A double click, or a reconnecting client replaying its requests, sends the same task again
+//! within moments. A task request identical to a previous one of the same client (same function and
+//! params, once completed by the session) gets the result of that request, rather than another
+//! upstream fetch, while it is in flight and for `server.dedup_window_ms` after it is answered.
+//!
+//! A client is known by its token name (see `auth.rs`), or else by its address: requests of Unix
+//! socket connections without a token are not deduplicated. The fetch goes on when the connection
+//! that sent it closes, for the replays to get its result. Failures (results reported as a string)
+//! are only shared while in flight.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use serde_json::Value;
+use tracing::{debug, Instrument};
+
+/// A request in flight or answered.
+#[derive(Clone)]
+struct Request {
+    result: Shared<BoxFuture<'static, Value>>,
+    /// When it was answered and whether it failed, set by the fetch task itself so a request
+    /// nobody waits for anymore still expires.
+    answered: Arc<OnceLock<(Instant, bool)>>,
+}
+
+/// Marks a request answered, as failed if its fetch ended without a result (e.g. it panicked).
+struct Answer(Arc<OnceLock<(Instant, bool)>>);
+impl Drop for Answer {
+    fn drop(&mut self) {
+        let _ = self.0.set((Instant::now(), true));
+    }
+}
+
+/// Task requests in flight or recently answered, by client and content.
+pub struct RequestDedup {
+    window: Duration,
+    requests: Mutex<HashMap<String, Request>>,
+}
+impl RequestDedup {
+    /// Deduplicates within `window_ms`, never when 0.
+    pub fn new(window_ms: u64) -> Self {
+        Self { window: Duration::from_millis(window_ms), requests: Mutex::new(HashMap::new()) }
+    }
+
+    /// Key of a request of `function` with `args`, from the `client` name or else its `peer`
+    /// address. `None` for anonymous clients.
+    pub fn key(client: Option<&str>, peer: Option<IpAddr>, function: &str, args: &Value) -> Option<String> {
+        let client = client.map(str::to_string).or_else(|| peer.map(|peer| peer.to_string()))?;
+        Some(format!("{}|{}|{}", client, function, args))
+    }
+
+    /// Result of the request `key`: the one of the identical request in flight or answered within
+    /// the window, or else of `fetch`.
+    pub async fn run(&self, key: String, fetch: impl Future<Output = Value> + Send + 'static) -> Value {
+        if self.window.is_zero() {
+            return fetch.await;
+        }
+        let request = {
+            let mut requests = self.requests.lock().unwrap();
+            requests.retain(|_, request| self.reusable(request));
+            match requests.get(&key) {
+                Some(request) => {
+                    debug!("Answering the duplicate request {} with the previous result.", key);
+                    request.clone()
+                }
+                None => {
+                    let answered = Arc::new(OnceLock::new());
+                    let answer = Answer(answered.clone());
+                    let fetch = tokio::spawn(async move {
+                        let result = fetch.await;
+                        let _ = answer.0.set((Instant::now(), result.is_string()));
+                        result
+                    }.in_current_span());
+                    let result = async move {
+                        fetch.await.unwrap_or_else(|e| Value::String(format!("Request failed: {}", e)))
+                    }
+                    .boxed()
+                    .shared();
+                    let request = Request { result, answered };
+                    requests.insert(key, request.clone());
+                    request
+                }
+            }
+        };
+        request.result.await
+    }
+
+    /// Whether `request` is in flight, or succeeded within the window.
+    fn reusable(&self, request: &Request) -> bool {
+        match request.answered.get() {
+            None => true,
+            Some((answered_at, failed)) => !failed && answered_at.elapsed() < self.window,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+
+    /// Counts its calls, answering `result` after 50 ms.
+    fn fetch(calls: &Arc<AtomicUsize>, result: Value) -> impl Future<Output = Value> + Send + 'static {
+        let calls = calls.clone();
+        async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            result
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_the_duplicates_with_the_previous_result() {
+        let dedup = RequestDedup::new(200);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let key = RequestDedup::key(None, Some([127, 0, 0, 1].into()), "mock_news_polling", &json!({ "symbol": "AAPL" })).unwrap();
+        assert!(RequestDedup::key(None, None, "mock_news_polling", &json!({})).is_none());
+
+        // In flight.
+        let (first, second) = tokio::join!(
+            dedup.run(key.clone(), fetch(&calls, json!(1))),
+            dedup.run(key.clone(), fetch(&calls, json!(2))),
+        );
+        assert_eq!((first, second, calls.load(Ordering::SeqCst)), (json!(1), json!(1), 1));
+        // Answered within the window.
+        assert_eq!(dedup.run(key.clone(), fetch(&calls, json!(3))).await, json!(1));
+        assert_eq!(dedup.run("other".to_string(), fetch(&calls, json!(4))).await, json!(4));
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(dedup.run(key.clone(), fetch(&calls, json!(5))).await, json!(5));
+
+        // Failures are fetched again.
+        let failure = json!("mock provider polling failed");
+        assert_eq!(dedup.run("failing".to_string(), fetch(&calls, failure.clone())).await, failure);
+        assert_eq!(dedup.run("failing".to_string(), fetch(&calls, json!(6))).await, json!(6));
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn expires_the_requests_nobody_waits_for() {
+        let dedup = RequestDedup::new(50);
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        // The connection closed mid-fetch: nobody polls the result, which expires all the same.
+        let dropped = tokio::time::timeout(Duration::from_millis(10), dedup.run("key".to_string(), fetch(&calls, json!(1)))).await;
+        assert!(dropped.is_err());
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(dedup.run("other".to_string(), fetch(&calls, json!(2))).await, json!(2));
+        assert_eq!(dedup.requests.lock().unwrap().len(), 1);
+        assert_eq!(dedup.run("key".to_string(), fetch(&calls, json!(3))).await, json!(3));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
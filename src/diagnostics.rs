@@ -0,0 +1,370 @@
+//! Query diagnostics based on MongoDB `explain`.
+//!
+//! When `database.diagnostics.explain` is enabled, every `find`, `find_one` and `aggregate` run by
+//! `DatabaseOps` goes through `QueryExplainer`, which explains it in the background (so the query
+//! itself is not slowed down) and records the ones that scan the whole collection or took longer
+//! than `slow_query_ms` in the diagnostics collection (`database.collections.diagnostics`). Each
+//! record carries the *shape* of the filter or pipeline (field names and operators, without values)
+//! so that operators can see which query patterns need an index.
+//!
+//! Queries are explained as they ran, with their sort, limit and projection, and timed until their
+//! last document was read.
+//!
+//! The plan is read with `queryPlanner` verbosity, which does not run the query. Only the queries
+//! measured slower than `slow_query_ms` are explained with `executionStats`, which runs them again.
+//! At most `max_concurrent` explains run at once; the queries past that are not explained.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::{FindOneOptions, FindOptions};
+use mongodb::{Collection, Database};
+use tokio::sync::Semaphore;
+use tracing::{debug, warn, Instrument};
+
+use crate::config::{DatabaseConfig, DiagnosticsConfig};
+use crate::db::DataKind;
+use crate::utils::now;
+
+const COLLECTION_SCAN_STAGE: &str = "COLLSCAN";
+
+/// Summary of an `explain` result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlanSummary {
+    /// Stages of the winning plan, outermost first (e.g. `FETCH`, `IXSCAN`).
+    pub stages: Vec<String>,
+    pub execution_time_ms: i64,
+    pub docs_examined: i64,
+    pub keys_examined: i64,
+    pub returned: i64,
+}
+impl QueryPlanSummary {
+    /// Parses the output of `explain`. The execution statistics are zero with `queryPlanner` verbosity.
+    pub fn from_explain(explain: &Document) -> Self {
+        // Pipelines run by the classic engine explain their first stage, reading the collection.
+        let explain = explain
+            .get_array("stages")
+            .ok()
+            .and_then(|stages| stages.first()?.as_document()?.get_document("$cursor").ok())
+            .unwrap_or(explain);
+        let mut stages = Vec::new();
+        if let Ok(plan) = explain.get_document("queryPlanner").and_then(|qp| qp.get_document("winningPlan")) {
+            collect_stages(plan, &mut stages);
+        }
+        let stats = explain.get_document("executionStats").ok();
+        let number = |key: &str| stats.and_then(|s| s.get(key)).and_then(as_i64).unwrap_or(0);
+
+        Self {
+            stages,
+            execution_time_ms: number("executionTimeMillis"),
+            docs_examined: number("totalDocsExamined"),
+            keys_examined: number("totalKeysExamined"),
+            returned: number("nReturned"),
+        }
+    }
+
+    /// Whether the winning plan scans the whole collection.
+    pub fn is_unindexed(&self) -> bool {
+        self.stages.iter().any(|s| s == COLLECTION_SCAN_STAGE)
+    }
+}
+
+fn collect_stages(plan: &Document, stages: &mut Vec<String>) {
+    // Newer servers wrap the classic plan in `queryPlan`.
+    let plan = plan.get_document("queryPlan").unwrap_or(plan);
+    if let Ok(stage) = plan.get_str("stage") {
+        stages.push(stage.to_string());
+    }
+    if let Ok(input) = plan.get_document("inputStage") {
+        collect_stages(input, stages);
+    }
+    if let Ok(inputs) = plan.get_array("inputStages") {
+        for input in inputs.iter().filter_map(Bson::as_document) {
+            collect_stages(input, stages);
+        }
+    }
+}
+
+fn as_i64(value: &Bson) -> Option<i64> {
+    match value {
+        Bson::Int32(v) => Some(*v as i64),
+        Bson::Int64(v) => Some(*v),
+        Bson::Double(v) => Some(*v as i64),
+        _ => None,
+    }
+}
+
+/// Replaces every value of `filter` with a placeholder, keeping field names and operators.
+pub fn query_shape(filter: &Document) -> Document {
+    filter
+        .iter()
+        .map(|(key, value)| (key.clone(), value_shape(value)))
+        .collect()
+}
+
+fn value_shape(value: &Bson) -> Bson {
+    match value {
+        Bson::Document(doc) => Bson::Document(query_shape(doc)),
+        Bson::Array(items) => Bson::Array(items.iter().take(1).map(value_shape).collect()),
+        _ => Bson::Int32(1),
+    }
+}
+
+/// A query run by `DatabaseOps`, as explained.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExplainedQuery {
+    /// `options` are the `sort`, `limit` and `projection` fields of the `find` command, if set.
+    Find { filter: Document, options: Document },
+    Aggregate { pipeline: Vec<Document> },
+}
+impl ExplainedQuery {
+    pub fn find(filter: Document, options: Option<&FindOptions>) -> Self {
+        let options = options.map_or_else(Document::new, |o| find_options(o.sort.as_ref(), o.limit, o.projection.as_ref()));
+        ExplainedQuery::Find { filter, options }
+    }
+
+    /// A `find_one`, run as a `find` limited to one document.
+    pub fn find_one(filter: Document, options: Option<&FindOneOptions>) -> Self {
+        let options = find_options(options.and_then(|o| o.sort.as_ref()), Some(1), options.and_then(|o| o.projection.as_ref()));
+        ExplainedQuery::Find { filter, options }
+    }
+
+    /// The command explained, on `collection`.
+    pub fn command(&self, collection: &str) -> Document {
+        match self {
+            ExplainedQuery::Find { filter, options } => {
+                let mut command = doc! { "find": collection, "filter": filter.clone() };
+                command.extend(options.clone());
+                command
+            }
+            ExplainedQuery::Aggregate { pipeline } => doc! { "aggregate": collection, "pipeline": pipeline.clone(), "cursor": {} },
+        }
+    }
+
+    /// Shape of the filter, or of each stage of the pipeline, see `query_shape`.
+    pub fn shape(&self) -> Bson {
+        match self {
+            ExplainedQuery::Find { filter, .. } => Bson::Document(query_shape(filter)),
+            ExplainedQuery::Aggregate { pipeline } => Bson::Array(pipeline.iter().map(|stage| Bson::Document(query_shape(stage))).collect()),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ExplainedQuery::Find { .. } => "find",
+            ExplainedQuery::Aggregate { .. } => "aggregate",
+        }
+    }
+}
+
+fn find_options(sort: Option<&Document>, limit: Option<i64>, projection: Option<&Document>) -> Document {
+    let mut options = Document::new();
+    if let Some(sort) = sort {
+        options.insert("sort", sort.clone());
+    }
+    if let Some(limit) = limit {
+        options.insert("limit", limit);
+    }
+    if let Some(projection) = projection {
+        options.insert("projection", projection.clone());
+    }
+    options
+}
+
+/// Explains the queries of `DatabaseOps` in the background, see the module doc.
+#[derive(Clone)]
+pub struct QueryExplainer {
+    db: Database,
+    config: DiagnosticsConfig,
+    /// Collection of the records.
+    collection: String,
+    permits: Arc<Semaphore>,
+}
+impl QueryExplainer {
+    pub fn new(db: Database, config: &DatabaseConfig) -> Self {
+        let permits = Arc::new(Semaphore::new(config.diagnostics.max_concurrent.max(1)));
+        let collection = DataKind::Diagnostics.collection_name(config).to_string();
+        Self { db, config: config.diagnostics.clone(), collection, permits }
+    }
+
+    /// Explains `query` on `collection`, which took `elapsed`, unless `max_concurrent` explains are
+    /// already running.
+    pub fn explain(&self, collection: &str, query: ExplainedQuery, elapsed: Duration) {
+        let Ok(permit) = self.permits.clone().try_acquire_owned() else {
+            debug!("Skipping the explain of a query on {}: {} explains running.", collection, self.config.max_concurrent);
+            return;
+        };
+        let slow = elapsed >= Duration::from_millis(self.config.slow_query_ms);
+        let diagnostics = self.db.collection(&self.collection);
+        let (db, collection) = (self.db.clone(), collection.to_string());
+        tokio::spawn(
+            async move {
+                explain_query(db, collection, query, elapsed, slow, diagnostics).await;
+                drop(permit);
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+/// Runs `explain` on `query`, which took `elapsed`, and records it in `diagnostics` when it is
+/// unindexed or `slow`. Slow queries are explained with `executionStats`, the others with
+/// `queryPlanner`.
+async fn explain_query(
+    db: Database,
+    collection: String,
+    query: ExplainedQuery,
+    elapsed: Duration,
+    slow: bool,
+    diagnostics: Collection<Document>,
+) {
+    let command = doc! {
+        "explain": query.command(&collection),
+        "verbosity": if slow { "executionStats" } else { "queryPlanner" },
+    };
+    let explain = match db.run_command(command, None).await {
+        Ok(explain) => explain,
+        Err(e) => {
+            debug!("Explain failed for query on {}: {}", &collection, e);
+            return;
+        }
+    };
+
+    let summary = QueryPlanSummary::from_explain(&explain);
+    if !summary.is_unindexed() && !slow {
+        return;
+    }
+
+    let shape = query.shape();
+    let query_time_ms = elapsed.as_millis() as i64;
+    warn!(
+        "Query on `{}` {} | Shape: {} | Plan: {:?} | {} ms | {} docs examined for {} returned",
+        &collection,
+        if summary.is_unindexed() { "is not using an index" } else { "is slow" },
+        shape,
+        summary.stages,
+        query_time_ms,
+        summary.docs_examined,
+        summary.returned,
+    );
+
+    let mut record = doc! {
+        "collection": &collection,
+        "kind": query.kind(),
+        "shape": shape,
+        "stages": summary.stages.clone(),
+        "unindexed": summary.is_unindexed(),
+        "slow": slow,
+        "query_time_ms": query_time_ms,
+        "execution_time_ms": summary.execution_time_ms,
+        "docs_examined": summary.docs_examined,
+        "keys_examined": summary.keys_examined,
+        "returned": summary.returned,
+        "recorded_at": now(),
+    };
+    if let ExplainedQuery::Find { options, .. } = &query {
+        if let Ok(sort) = options.get_document("sort") {
+            record.insert("sort", sort.clone());
+        }
+    }
+    if let Err(e) = diagnostics.insert_one(record, None).await {
+        warn!("Failed to record query diagnostics: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_a_classic_explain() {
+        let explain = doc! {
+            "queryPlanner": {
+                "winningPlan": {
+                    "stage": "FETCH",
+                    "inputStage": { "stage": "IXSCAN", "indexName": "tickers_1" },
+                },
+            },
+            "executionStats": {
+                "executionTimeMillis": 12,
+                "totalDocsExamined": 40_i64,
+                "totalKeysExamined": 42,
+                "nReturned": 40.0,
+            },
+        };
+        let summary = QueryPlanSummary::from_explain(&explain);
+        assert_eq!(summary.stages, vec!["FETCH", "IXSCAN"]);
+        assert_eq!((summary.execution_time_ms, summary.docs_examined, summary.keys_examined, summary.returned), (12, 40, 42, 40));
+        assert!(!summary.is_unindexed());
+    }
+
+    #[test]
+    fn summarizes_a_wrapped_plan_with_several_inputs() {
+        // Newer servers wrap the plan in `queryPlan`, an `$or` gives one input per branch.
+        let explain = doc! {
+            "queryPlanner": {
+                "winningPlan": {
+                    "queryPlan": {
+                        "stage": "SUBPLAN",
+                        "inputStage": {
+                            "stage": "OR",
+                            "inputStages": [
+                                { "stage": "IXSCAN" },
+                                { "stage": "COLLSCAN" },
+                            ],
+                        },
+                    },
+                },
+            },
+        };
+        let summary = QueryPlanSummary::from_explain(&explain);
+        assert_eq!(summary.stages, vec!["SUBPLAN", "OR", "IXSCAN", "COLLSCAN"]);
+        assert!(summary.is_unindexed());
+        assert_eq!(summary.execution_time_ms, 0);
+    }
+
+    #[test]
+    fn summarizes_the_first_stage_of_a_pipeline() {
+        let explain = doc! {
+            "stages": [
+                { "$cursor": { "queryPlanner": { "winningPlan": { "stage": "COLLSCAN" } } } },
+                { "$group": { "_id": "$sentiment" } },
+            ],
+        };
+        assert_eq!(QueryPlanSummary::from_explain(&explain).stages, vec!["COLLSCAN"]);
+    }
+
+    #[test]
+    fn explains_queries_with_their_options() {
+        let options = FindOptions::builder().sort(doc! { "seq": 1 }).limit(100).projection(doc! { "_id": 1 }).build();
+        let query = ExplainedQuery::find(doc! { "seq": { "$gt": 42_i64 } }, Some(&options));
+        assert_eq!(
+            query.command("article_dedup"),
+            doc! { "find": "article_dedup", "filter": { "seq": { "$gt": 42_i64 } }, "sort": { "seq": 1 }, "limit": 100_i64, "projection": { "_id": 1 } },
+        );
+        let query = ExplainedQuery::find_one(doc! { "_id": "articles" }, None);
+        assert_eq!(query.command("counters"), doc! { "find": "counters", "filter": { "_id": "articles" }, "limit": 1_i64 });
+
+        let pipeline = vec![doc! { "$match": { "deleted": { "$ne": true } } }, doc! { "$limit": 10 }];
+        let query = ExplainedQuery::Aggregate { pipeline: pipeline.clone() };
+        assert_eq!(query.command("articles"), doc! { "aggregate": "articles", "pipeline": pipeline, "cursor": {} });
+        assert_eq!(query.shape(), Bson::Array(vec![Bson::Document(doc! { "$match": { "deleted": { "$ne": 1 } } }), Bson::Document(doc! { "$limit": 1 })]));
+    }
+
+    #[test]
+    fn shapes_keep_the_fields_and_operators() {
+        let filter = doc! {
+            "tickers": { "$in": ["AAPL", "MSFT"] },
+            "published_at": { "$gte": "2024-05-01", "$lt": "2024-05-02" },
+            "$or": [{ "source": "example.com" }, { "url": { "$regex": "example" } }],
+            "deleted": { "$ne": true },
+        };
+        assert_eq!(query_shape(&filter), doc! {
+            "tickers": { "$in": [1] },
+            "published_at": { "$gte": 1, "$lt": 1 },
+            "$or": [{ "source": 1 }],
+            "deleted": { "$ne": 1 },
+        });
+    }
+}
@@ -0,0 +1,358 @@
+//! Digests of the stored articles matching alert rules.
+//!
+//! Each `[[alerts.rules]]` selects articles by tickers, keywords and sentiment, and names the
+//! channels to notify. Every `alerts.digest.interval_secs`, the articles stored since the last
+//! run are matched against the rules and each channel receives one digest listing its new
+//! articles, through the channel policies and templates of `alerts.rs`.
+//!
+//! The same article is never notified twice to a channel, however many rules match it: the
+//! channels each article was notified to are recorded in the `article_notifications` collection.
+//! An article is identified by its URL, so the same story fetched from several providers counts
+//! once. With `alerts.digest.repeat_after_hours`, an article may be notified again to a channel
+//! after that many hours.
+//!
+//! A digest held back by its channel policy (quiet hours, rate limit, dedupe) is not lost: its
+//! articles are kept and listed in the next digest of the channel.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration as UtcDuration, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, Duration};
+use tracing::{error, info};
+
+use crate::alerts::{Alert, Alerter};
+use crate::clock;
+use crate::config::{AlertsConfig, ArticleRuleConfig, DigestConfig};
+use crate::db::{dedup_key, OpError};
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+use crate::sentiment::SentimentLabel;
+use crate::storage::{Storage, StoredFeed};
+use crate::utils::now;
+
+const DIGEST_RULE: &str = "article_digest";
+/// Stored articles read per run.
+const BATCH_SIZE: i64 = 1000;
+
+/// An `[[alerts.rules]]` entry, checked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArticleRule {
+    pub name: String,
+    tickers: Vec<String>,
+    keywords: Vec<String>,
+    sentiment: Vec<SentimentLabel>,
+    /// Channels notified, all of them when empty.
+    channels: Vec<String>,
+}
+impl ArticleRule {
+    /// Checks `config` against the names of the alert `channels`.
+    pub fn new(config: &ArticleRuleConfig, channels: &[String]) -> Result<Self, String> {
+        if config.tickers.is_empty() && config.keywords.is_empty() && config.sentiment.is_empty() {
+            return Err("no tickers, keywords nor sentiment to match".to_string());
+        }
+        let sentiment = config
+            .sentiment
+            .iter()
+            .map(|label| label.parse())
+            .collect::<Result<Vec<SentimentLabel>, String>>()?;
+        if let Some(unknown) = config.channels.iter().find(|c| !channels.contains(c)) {
+            return Err(format!("unknown channel `{}`", unknown));
+        }
+        Ok(Self {
+            name: config.name.clone(),
+            tickers: config.tickers.iter().map(|t| t.to_uppercase()).collect(),
+            keywords: config.keywords.iter().map(|k| k.to_lowercase()).collect(),
+            sentiment,
+            channels: config.channels.clone(),
+        })
+    }
+
+    pub fn matches(&self, article: &NormalizedArticle) -> bool {
+        let title = article.title.as_deref().unwrap_or_default().to_lowercase();
+        (self.tickers.is_empty() || article.tickers.iter().any(|t| self.tickers.contains(&t.to_uppercase())))
+            && (self.keywords.is_empty()
+                || self.keywords.iter().any(|k| article.keywords.iter().any(|a| a.eq_ignore_ascii_case(k)) || title.contains(k.as_str())))
+            && (self.sentiment.is_empty() || article.sentiment.is_some_and(|s| self.sentiment.contains(&s)))
+    }
+
+    fn notifies(&self, channel: &str) -> bool {
+        self.channels.is_empty() || self.channels.iter().any(|c| c == channel)
+    }
+}
+
+/// Channels an article was notified to.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ArticleNotification {
+    /// See `notification_key`.
+    #[serde(rename = "_id")]
+    pub key: String,
+    /// Channel name to when the article was last notified to it.
+    pub channels: BTreeMap<String, String>,
+    /// Rules the article matched.
+    pub rules: Vec<String>,
+}
+impl ArticleNotification {
+    /// Whether the article was notified to `channel` and may not be again `at`.
+    fn blocks(&self, channel: &str, at: DateTime<Utc>, repeat_after_hours: Option<u64>) -> bool {
+        let Some(notified_at) = self.channels.get(channel).and_then(|t| parse_timestamp(t)) else {
+            return false;
+        };
+        repeat_after_hours.is_none_or(|hours| at - notified_at < UtcDuration::hours(hours as i64))
+    }
+}
+
+/// Key of the notification record of an article: its URL without query nor trailing slash, so
+/// the same story syndicated by several providers is notified once, or its dedup key.
+pub fn notification_key(article: &NormalizedArticle) -> String {
+    match article.url.as_deref().map(|url| url.split(['?', '#']).next().unwrap_or(url).trim_end_matches('/')) {
+        Some(url) if !url.is_empty() => url.to_lowercase(),
+        _ => dedup_key(article),
+    }
+}
+
+/// An article waiting for the next digest of a channel.
+#[derive(Debug, Clone, PartialEq)]
+struct Queued {
+    key: String,
+    line: String,
+    rules: Vec<String>,
+}
+
+/// One line per article: tickers, title and URL.
+fn line(article: &NormalizedArticle) -> String {
+    let line = format!(
+        "[{}] {} {}",
+        article.tickers.join(","),
+        article.title.as_deref().unwrap_or("(no title)"),
+        article.url.as_deref().unwrap_or_default()
+    );
+    line.trim_end().to_string()
+}
+
+/// Periodic digests of the articles matching the rules.
+pub struct Digest {
+    storage: Arc<dyn Storage>,
+    alerter: Arc<Alerter>,
+    rules: Vec<ArticleRule>,
+    channels: Vec<String>,
+    config: DigestConfig,
+    feed: StoredFeed,
+    /// Articles not posted yet per channel, e.g. during quiet hours.
+    queued: BTreeMap<String, Vec<Queued>>,
+}
+impl Digest {
+    /// Digests of the articles stored from now on. Invalid rules are logged and ignored.
+    pub fn new(storage: Arc<dyn Storage>, alerter: Arc<Alerter>, config: &AlertsConfig) -> Self {
+        let channels = alerter.channels();
+        let rules = config
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                ArticleRule::new(rule, &channels)
+                    .inspect_err(|e| error!("Alert rule `{}`: {}. Rule ignored.", rule.name, e))
+                    .ok()
+            })
+            .collect();
+        Self { storage, alerter, rules, channels, config: config.digest.clone(), feed: StoredFeed::since(now()), queued: BTreeMap::new() }
+    }
+
+    /// Queues the articles stored since the last run and posts the digests. Returns the number of
+    /// articles posted.
+    pub async fn run_once(&mut self) -> Result<usize, OpError> {
+        let batch = self.feed.next_batch(self.storage.as_ref(), BATCH_SIZE).await?;
+        self.queue(batch.into_iter().map(|stored| stored.article).collect()).await?;
+        Ok(self.post().await)
+    }
+
+    /// Queues the `articles` matching a rule for each of their channels, unless already notified
+    /// or queued.
+    async fn queue(&mut self, articles: Vec<NormalizedArticle>) -> Result<(), OpError> {
+        let matched: Vec<(String, NormalizedArticle, Vec<&ArticleRule>)> = articles
+            .into_iter()
+            .filter_map(|article| {
+                let rules: Vec<&ArticleRule> = self.rules.iter().filter(|rule| rule.matches(&article)).collect();
+                (!rules.is_empty()).then(|| (notification_key(&article), article, rules))
+            })
+            .collect();
+        if matched.is_empty() {
+            return Ok(());
+        }
+        let keys: Vec<String> = matched.iter().map(|(key, _, _)| key.clone()).collect();
+        let notified: HashMap<String, ArticleNotification> =
+            self.storage.article_notifications(&keys).await?.into_iter().map(|n| (n.key.clone(), n)).collect();
+
+        let at = clock::now();
+        for (key, article, rules) in &matched {
+            for channel in &self.channels {
+                let rules: Vec<String> = rules.iter().filter(|rule| rule.notifies(channel)).map(|rule| rule.name.clone()).collect();
+                if rules.is_empty() || notified.get(key).is_some_and(|n| n.blocks(channel, at, self.config.repeat_after_hours)) {
+                    continue;
+                }
+                let queue = self.queued.entry(channel.clone()).or_default();
+                match queue.iter_mut().find(|queued| queued.key == *key) {
+                    Some(queued) => {
+                        for rule in rules {
+                            if !queued.rules.contains(&rule) {
+                                queued.rules.push(rule);
+                            }
+                        }
+                    }
+                    None => queue.push(Queued { key: key.clone(), line: line(article), rules }),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Posts the digest of each channel with queued articles. Returns the number of articles posted.
+    async fn post(&mut self) -> usize {
+        let mut posted = 0;
+        for (channel, queue) in std::mem::take(&mut self.queued) {
+            if queue.is_empty() {
+                continue;
+            }
+            if !self.alerter.notify(&self.digest_alert(&queue), &channel).await {
+                self.queued.insert(channel, queue);
+                continue;
+            }
+            posted += queue.len();
+            if let Err(e) = self.record(&channel, &queue).await {
+                error!("Failed to record the digest posted to channel `{}`: {}", channel, e);
+            }
+        }
+        posted
+    }
+
+    fn digest_alert(&self, queue: &[Queued]) -> Alert {
+        let rules: BTreeSet<&str> = queue.iter().flat_map(|queued| queued.rules.iter().map(String::as_str)).collect();
+        let shown = if self.config.max_articles > 0 { self.config.max_articles.min(queue.len()) } else { queue.len() };
+        let mut message = format!("{} new articles:", queue.len());
+        for queued in &queue[..shown] {
+            message.push_str(&format!("\n- {}", queued.line));
+        }
+        if shown < queue.len() {
+            message.push_str(&format!("\n(and {} more)", queue.len() - shown));
+        }
+        Alert::new(DIGEST_RULE, &rules.into_iter().collect::<Vec<_>>().join(", "), message)
+    }
+
+    /// Records that the articles of `queue` were notified to `channel`.
+    async fn record(&self, channel: &str, queue: &[Queued]) -> Result<(), OpError> {
+        let keys: Vec<String> = queue.iter().map(|queued| queued.key.clone()).collect();
+        let mut notified: HashMap<String, ArticleNotification> =
+            self.storage.article_notifications(&keys).await?.into_iter().map(|n| (n.key.clone(), n)).collect();
+        let at = now();
+        for queued in queue {
+            let notification = notified.entry(queued.key.clone()).or_insert_with(|| ArticleNotification {
+                key: queued.key.clone(),
+                channels: BTreeMap::new(),
+                rules: Vec::new(),
+            });
+            notification.channels.insert(channel.to_string(), at.clone());
+            for rule in &queued.rules {
+                if !notification.rules.contains(rule) {
+                    notification.rules.push(rule.clone());
+                }
+            }
+            self.storage.save_article_notification(notification).await?;
+        }
+        Ok(())
+    }
+
+    /// Runs `run_once` every `alerts.digest.interval_secs` in a background task.
+    pub fn spawn(mut self) {
+        let interval = Duration::from_secs(self.config.interval_secs.max(1));
+        tokio::spawn(async move {
+            loop {
+                sleep(interval).await;
+                match self.run_once().await {
+                    Ok(0) => {}
+                    Ok(posted) => info!("Article digests posted. | {} articles", posted),
+                    Err(e) => error!("Article digest failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+
+    use crate::config::AlertChannelConfig;
+    use crate::storage::MemoryStorage;
+
+    fn channel(name: &str, dedupe_secs: u64) -> AlertChannelConfig {
+        AlertChannelConfig {
+            name: name.to_string(),
+            // Nothing listens: deliveries fail and are logged.
+            webhook_url: "http://127.0.0.1:9/".to_string(),
+            quiet_hours: None,
+            max_per_hour: 0,
+            dedupe_secs,
+            template: None,
+            template_file: None,
+            content_type: "application/json".to_string(),
+        }
+    }
+
+    fn rule(name: &str, tickers: &[&str], sentiment: &[&str], channels: &[&str]) -> ArticleRuleConfig {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        ArticleRuleConfig { name: name.to_string(), tickers: strings(tickers), keywords: Vec::new(), sentiment: strings(sentiment), channels: strings(channels) }
+    }
+
+    #[tokio::test]
+    async fn notifies_each_article_once_per_channel() {
+        let storage = Arc::new(MemoryStorage::new());
+        let article = |provider: &str, id: &str, url: &str, sentiment: SentimentLabel| {
+            NormalizedArticle::test(id).provider(provider).url(url).title(&format!("Story {}", id)).tickers(&["NVDA"]).sentiment(sentiment)
+        };
+        let config = AlertsConfig {
+            channels: vec![channel("ops", 0), channel("desk", 0), channel("night", 3600)],
+            rules: vec![
+                rule("nvda", &["nvda"], &[], &[]),
+                rule("nvda_bearish", &["NVDA"], &["bearish"], &["ops"]),
+                rule("broken", &[], &[], &[]),
+            ],
+            digest: DigestConfig::default(),
+        };
+        let alerter = Arc::new(Alerter::new(Arc::new(Client::new()), config.clone()));
+        let mut digest = Digest::new(storage.clone(), alerter.clone(), &config);
+        assert_eq!(digest.rules.len(), 2);
+        // The digests of the night channel are held back as duplicates of this one.
+        assert!(alerter.notify(&Alert::new(DIGEST_RULE, "nvda", "earlier digest"), "night").await);
+
+        storage.store_articles(&[
+            article("marketaux", "1", "https://example.com/story-1?utm=x", SentimentLabel::Bearish),
+            article("finnhub", "2", "https://example.com/story-1/", SentimentLabel::Bearish),
+            article("finnhub", "3", "https://example.com/story-3", SentimentLabel::Bullish),
+        ]).await.unwrap();
+        // Two stories to ops and desk, none to night.
+        assert_eq!(digest.run_once().await.unwrap(), 4);
+        assert_eq!(digest.queued["night"].len(), 2);
+        let ops = storage.article_notifications(&["https://example.com/story-1".to_string()]).await.unwrap();
+        assert_eq!(ops[0].channels.keys().collect::<Vec<_>>(), vec!["desk", "ops"]);
+        assert_eq!(ops[0].rules, vec!["nvda", "nvda_bearish"]);
+
+        // Re-delivered by another provider: not notified again.
+        storage.store_articles(&[article("polygon", "4", "https://EXAMPLE.com/story-3", SentimentLabel::Bullish)]).await.unwrap();
+        assert_eq!(digest.run_once().await.unwrap(), 0);
+        assert_eq!(digest.queued["night"].len(), 2);
+    }
+
+    #[test]
+    fn repeats_after_the_configured_hours() {
+        let at = |t: &str| parse_timestamp(t).unwrap();
+        let notification = ArticleNotification {
+            key: "k".to_string(),
+            channels: BTreeMap::from([("ops".to_string(), "2024-05-01T10:00:00Z".to_string())]),
+            rules: Vec::new(),
+        };
+        assert!(notification.blocks("ops", at("2024-05-03T10:00:00Z"), None));
+        assert!(notification.blocks("ops", at("2024-05-01T20:00:00Z"), Some(24)));
+        assert!(!notification.blocks("ops", at("2024-05-02T10:00:00Z"), Some(24)));
+        assert!(!notification.blocks("desk", at("2024-05-01T10:00:00Z"), None));
+    }
+}
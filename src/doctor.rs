@@ -0,0 +1,150 @@
+//! Setup checks for the `doctor` command.
+//!
+//! Before deploying, or when the daemon misbehaves, `news_data doctor` checks what it needs
+//! without fetching anything: the config file, the schedules, the API keys of the providers,
+//! the alert templates, the MongoDB connection and the instance lock. Each check is reported as
+//! `ok`, `warning` (the service runs, degraded) or `failed` (it does not), and the command exits
+//! with an error when any check failed.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use reqwest::Client;
+use tokio::sync::Mutex;
+
+use crate::cache::SharedLockedCache;
+use crate::config::ValueConfig;
+use crate::db::ClientManager;
+use crate::instance::{InstanceError, InstanceLock};
+use crate::provider::{default_providers, NewsProvider, ProviderHealth};
+use crate::request::HTTPClient;
+use crate::scheduler::Schedule;
+use crate::templates::PayloadTemplate;
+
+/// Outcome of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+}
+impl CheckStatus {
+    pub fn to_str(&self) -> &str {
+        match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warning => "warning",
+            CheckStatus::Failed => "failed",
+        }
+    }
+}
+
+/// A check and its outcome.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status, detail: detail.into() }
+    }
+}
+
+/// Checks of the schedules and of the providers, which need no network.
+pub fn check_providers(providers: &[Arc<dyn NewsProvider>], config: &ValueConfig) -> Vec<Check> {
+    let mut checks = Vec::new();
+    for provider in providers {
+        let name = provider.name();
+        if let Err(e) = Schedule::of(name, config) {
+            checks.push(Check::new(format!("schedule.{}", name), CheckStatus::Failed, e));
+        }
+        let check = match provider.health() {
+            ProviderHealth::Unavailable(reason) => Check::new(format!("provider.{}", name), CheckStatus::Warning, reason),
+            _ => Check::new(format!("provider.{}", name), CheckStatus::Ok, "configured"),
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+/// Whether the body templates of the alert channels load, see `templates.rs`.
+pub fn check_alert_templates(config: &ValueConfig) -> Vec<Check> {
+    config
+        .alerts
+        .channels
+        .iter()
+        .filter_map(|channel| match PayloadTemplate::of_channel(channel) {
+            Ok(None) => None,
+            Ok(Some(_)) => Some(Check::new(format!("alerts.{}.template", channel.name), CheckStatus::Ok, "loaded")),
+            Err(e) => Some(Check::new(format!("alerts.{}.template", channel.name), CheckStatus::Warning, format!("{}, default body posted", e))),
+        })
+        .collect()
+}
+
+/// Whether MongoDB answers at `database.uri`.
+pub async fn check_database(config: &ValueConfig) -> Check {
+    match ClientManager::new(config).await {
+        Ok(client) => {
+            let transactions = if client.supports_transactions() { "with" } else { "without" };
+            Check::new("database", CheckStatus::Ok, format!("connected, {} transactions", transactions))
+        }
+        Err(e) => Check::new("database", CheckStatus::Failed, e.to_string()),
+    }
+}
+
+/// Whether another instance holds `instance.lock_file`.
+pub fn check_instance(config: &ValueConfig) -> Check {
+    let Some(path) = config.instance.lock_file.as_deref() else {
+        return Check::new("instance", CheckStatus::Ok, "no lock file configured");
+    };
+    match InstanceLock::acquire(Path::new(path)) {
+        Ok(_) => Check::new("instance", CheckStatus::Ok, "no other instance running"),
+        Err(e @ InstanceError::AlreadyRunning { .. }) => Check::new("instance", CheckStatus::Warning, e.to_string()),
+        Err(e) => Check::new("instance", CheckStatus::Failed, e.to_string()),
+    }
+}
+
+/// Runs every check with `config`.
+pub async fn run(config: Arc<ValueConfig>) -> Vec<Check> {
+    let req_client = Arc::new(Client::new());
+    let http_client = Arc::new(HTTPClient::new().expect("Failed to initialize HTTP client."));
+    let cache = Arc::new(Mutex::new(SharedLockedCache::new(100)));
+    let providers = default_providers(http_client, req_client, cache, config.clone());
+
+    let mut checks = vec![Check::new("config", CheckStatus::Ok, "loaded")];
+    checks.extend(check_providers(&providers, &config));
+    if let Err(e) = Schedule::of("watchlist", &config) {
+        checks.push(Check::new("schedule.watchlist", CheckStatus::Failed, e));
+    }
+    checks.extend(check_alert_templates(&config));
+    checks.push(check_database(&config).await);
+    checks.push(check_instance(&config));
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SourceSchedule;
+    use crate::{AlphaVantageApiClient, MarketAuxApiClient};
+
+    #[test]
+    fn reports_missing_keys_and_bad_schedules() {
+        let mut config = ValueConfig::from_toml(include_str!("../config.toml.example")).unwrap();
+        config.api.marketaux = String::new();
+        config.schedule.insert("alphavantage".to_string(), SourceSchedule { interval_secs: None, cron: Some("every 5 minutes".to_string()) });
+        let config = Arc::new(config);
+        let (client, cache) = (Arc::new(Client::new()), Arc::new(Mutex::new(SharedLockedCache::new(10))));
+        let providers: Vec<Arc<dyn NewsProvider>> = vec![
+            Arc::new(MarketAuxApiClient::new(client.clone(), cache.clone(), config.clone())),
+            Arc::new(AlphaVantageApiClient::new(client, cache, config.clone())),
+        ];
+        let checks = check_providers(&providers, &config);
+        let status = |name: &str| checks.iter().find(|c| c.name == name).map(|c| c.status);
+        assert_eq!(status("schedule.alphavantage"), Some(CheckStatus::Failed));
+        assert_eq!(status("schedule.marketaux"), None);
+        assert_eq!(status("provider.marketaux"), Some(CheckStatus::Warning));
+        assert_eq!(status("provider.alphavantage"), Some(CheckStatus::Ok));
+    }
+}
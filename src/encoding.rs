@@ -0,0 +1,209 @@
+//! Decoding of raw provider response bodies.
+//!
+//! Providers occasionally answer with HTML error pages (maintenance banners, CDN challenges),
+//! bodies prefixed with a byte order mark, or bodies that are not UTF-8 at all.
+//! `reqwest::Response::json` reports all of those as an opaque JSON parse failure.
+//! The helpers below validate the `Content-Type`, normalize the body to UTF-8 and surface
+//! a typed `EncodingError` so callers can tell a broken payload from a broken model.
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Response;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::captures;
+use crate::errors::{ApiError, EncodingError};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+const SNIPPET_LENGTH: usize = 200;
+
+/// Characters of the bytes 0x80 to 0x9F in windows-1252, where ISO-8859-1 has C1 controls. The
+/// five bytes windows-1252 leaves undefined keep their C1 control, as in the WHATWG encoding.
+const WINDOWS_1252_C1: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// Reads the body of `response` from `provider` and parses it as JSON.
+///
+/// The body is decoded according to its byte order mark or declared charset, then rejected
+/// with an `EncodingError` if it is empty or looks like markup rather than JSON. The raw body is
+/// captured first, see `captures.rs`.
+pub async fn decode_json_response(provider: &str, response: Response) -> Result<Value, ApiError> {
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_ascii_lowercase());
+    let (url, status) = (response.url().clone(), response.status());
+
+    let bytes = response.bytes().await.map_err(|e| ApiError::NetworkError {
+        message: format!("Failed to read body: {}", e),
+        status: None,
+        headers: None,
+        body: None,
+    })?;
+    captures::record(provider, &url, status, content_type.as_deref(), &bytes);
+
+    decode_json_bytes(&bytes, content_type.as_deref()).map_err(|e| *e)
+}
+
+/// Same as `decode_json_response`, for a body that was already read into memory. The error is
+/// boxed to keep the result small.
+pub fn decode_json_bytes(bytes: &[u8], content_type: Option<&str>) -> Result<Value, Box<ApiError>> {
+    let text = decode_text(bytes, content_type).map_err(ApiError::from)?;
+    let trimmed = text.trim_start();
+
+    if trimmed.is_empty() {
+        return Err(Box::new(EncodingError::EmptyBody.into()));
+    }
+
+    if trimmed.starts_with('<') || content_type.is_some_and(is_markup) {
+        return Err(Box::new(EncodingError::UnexpectedContentType {
+            content_type: content_type.unwrap_or("unknown").to_string(),
+            snippet: snippet(trimmed),
+        }
+        .into()));
+    }
+
+    serde_json::from_str(trimmed).map_err(|e| Box::new(match content_type {
+        // Providers that label their payloads as JSON get the usual parse error,
+        // anything else is most likely not the payload we asked for.
+        Some(ct) if !is_json(ct) => EncodingError::UnexpectedContentType {
+            content_type: ct.to_string(),
+            snippet: snippet(trimmed),
+        }
+        .into(),
+        _ => ApiError::JsonParseError { message: e.to_string() },
+    }))
+}
+
+/// Decodes `bytes` into a `String`, honouring byte order marks and the declared charset.
+pub fn decode_text(bytes: &[u8], content_type: Option<&str>) -> Result<String, EncodingError> {
+    if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        return Ok(utf8_lossy(rest));
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF16_LE_BOM) {
+        return decode_utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF16_BE_BOM) {
+        return decode_utf16(rest, u16::from_be_bytes);
+    }
+
+    match content_type.and_then(charset) {
+        None | Some("utf-8") | Some("utf8") | Some("us-ascii") => Ok(utf8_lossy(bytes)),
+        // Latin-1 maps every byte to the code point of the same value.
+        Some("iso-8859-1") | Some("latin1") => Ok(bytes.iter().map(|&b| b as char).collect()),
+        Some("windows-1252") | Some("cp1252") => Ok(bytes.iter().map(|&b| windows_1252(b)).collect()),
+        Some(other) => Err(EncodingError::UnsupportedCharset(other.to_string())),
+    }
+}
+
+fn utf8_lossy(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(e) => {
+            warn!("Response body is not valid UTF-8 ({}). Replacing invalid sequences.", e);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
+
+/// Character of `byte` in windows-1252: Latin-1 but for 0x80 to 0x9F.
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_C1[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_u16: fn([u8; 2]) -> u16) -> Result<String, EncodingError> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(EncodingError::InvalidUtf16(format!("odd number of bytes ({}), the body is truncated", bytes.len())));
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| to_u16([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|e| EncodingError::InvalidUtf16(e.to_string()))
+}
+
+fn charset(content_type: &str) -> Option<&str> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.trim().strip_prefix("charset="))
+        .map(|cs| cs.trim_matches('"'))
+        .next()
+}
+
+fn is_json(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    mime == "application/json" || mime == "text/json" || mime.ends_with("+json")
+}
+
+fn is_markup(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    mime.contains("html") || mime.contains("xml")
+}
+
+fn snippet(text: &str) -> String {
+    text.chars().take(SNIPPET_LENGTH).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `text` in UTF-16 after its byte order mark.
+    fn utf16(text: &str, bom: &[u8], to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        let mut bytes = bom.to_vec();
+        bytes.extend(text.encode_utf16().flat_map(to_bytes));
+        bytes
+    }
+
+    #[test]
+    fn decodes_the_bodies_by_byte_order_mark() {
+        let value = decode_json_bytes(b"\xEF\xBB\xBF{\"a\": 1}", Some("application/json")).unwrap();
+        assert_eq!(value["a"], 1);
+
+        let le = utf16("{\"title\": \"caf\u{e9}\"}", UTF16_LE_BOM, u16::to_le_bytes);
+        assert_eq!(decode_json_bytes(&le, None).unwrap()["title"], "caf\u{e9}");
+        let be = utf16("{\"title\": \"caf\u{e9}\"}", UTF16_BE_BOM, u16::to_be_bytes);
+        assert_eq!(decode_json_bytes(&be, None).unwrap()["title"], "caf\u{e9}");
+
+        // A truncated body is reported, not cut to the last whole unit.
+        let mut truncated = le.clone();
+        truncated.push(b'}');
+        assert!(matches!(decode_text(&truncated, None), Err(EncodingError::InvalidUtf16(_))));
+    }
+
+    #[test]
+    fn decodes_windows_1252_apart_from_latin_1() {
+        let bytes = b"\x93Q1\x94 \x80 10 \x96 caf\xE9";
+        assert_eq!(decode_text(bytes, Some("text/plain; charset=windows-1252")).unwrap(), "\u{201C}Q1\u{201D} \u{20AC} 10 \u{2013} caf\u{e9}");
+        assert_eq!(decode_text(b"\x80 caf\xE9", Some("text/plain; charset=iso-8859-1")).unwrap(), "\u{80} caf\u{e9}");
+        assert!(matches!(decode_text(b"{}", Some("application/json; charset=koi8-r")), Err(EncodingError::UnsupportedCharset(_))));
+    }
+
+    #[test]
+    fn rejects_the_bodies_that_are_not_json() {
+        let page = b"<!DOCTYPE html><html><body>Service Unavailable</body></html>";
+        match decode_json_bytes(page, Some("application/json")).map_err(|e| *e) {
+            Err(ApiError::EncodingError(EncodingError::UnexpectedContentType { snippet, .. })) => assert!(snippet.starts_with("<!DOCTYPE html>")),
+            other => panic!("expected an unexpected content type, got {:?}", other),
+        }
+        // Not JSON under a content type that is not JSON either.
+        assert!(matches!(
+            decode_json_bytes(b"Rate limit exceeded", Some("text/plain")).map_err(|e| *e),
+            Err(ApiError::EncodingError(EncodingError::UnexpectedContentType { .. }))
+        ));
+        // JSON is parsed whatever it is labelled.
+        assert_eq!(decode_json_bytes(b"{\"a\": 1}", Some("text/plain")).unwrap()["a"], 1);
+        assert!(matches!(decode_json_bytes(b"  ", None).map_err(|e| *e), Err(ApiError::EncodingError(EncodingError::EmptyBody))));
+    }
+}
@@ -0,0 +1,381 @@
+//! Named entity recognition for people, organizations and locations.
+//!
+//! Tickers only cover listed companies. Before an article is stored, its `entities` are set from
+//! its title and summary (or their translation), rule-based:
+//!
+//! - names and aliases of a gazetteer, built in for central banks, regulators and major
+//!   countries and cities, completed by `entities.people`, `entities.organizations` and
+//!   `entities.locations`: an alias is stored under its canonical name, so with
+//!   `"Jerome Powell" = ["Fed chair", "Powell"]` every mention of the Fed chair is found under
+//!   `Jerome Powell`,
+//! - runs of capitalized words ending with a company suffix (`Goldman Sachs Group`, `Apple Inc.`)
+//!   as organizations,
+//! - up to three capitalized words after a title (`CEO Tim Cook`, `analyst Jane Doe`) as people.
+//!
+//! Locations are only found through the gazetteer: capitalized words alone do not tell a place
+//! from a month or a brand. The stored entities back the `entity_news` queries of the websocket
+//! server, which resolve aliases the same way.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::EntitiesConfig;
+use crate::normalize::NormalizedArticle;
+
+/// Built-in organizations, with their aliases.
+const ORGANIZATIONS: &[(&str, &[&str])] = &[
+    ("Federal Reserve", &["Fed", "FOMC", "Federal Open Market Committee", "U.S. Federal Reserve"]),
+    ("European Central Bank", &["ECB"]),
+    ("Bank of England", &["BoE"]),
+    ("Bank of Japan", &["BoJ"]),
+    ("People's Bank of China", &["PBOC", "PBoC"]),
+    ("International Monetary Fund", &["IMF"]),
+    ("World Bank", &[]),
+    ("Securities and Exchange Commission", &["SEC", "U.S. Securities and Exchange Commission"]),
+    ("U.S. Treasury", &["Treasury Department", "U.S. Treasury Department"]),
+    ("OPEC", &["Organization of the Petroleum Exporting Countries", "OPEC+"]),
+    ("European Union", &["EU", "E.U."]),
+];
+
+/// Built-in locations, with their aliases.
+const LOCATIONS: &[(&str, &[&str])] = &[
+    ("United States", &["US", "U.S.", "USA", "U.S.A."]),
+    ("United Kingdom", &["UK", "U.K.", "Britain", "Great Britain"]),
+    ("China", &["mainland China"]),
+    ("Japan", &[]),
+    ("Germany", &[]),
+    ("France", &[]),
+    ("India", &[]),
+    ("Russia", &[]),
+    ("Canada", &[]),
+    ("Brazil", &[]),
+    ("Europe", &["euro zone", "eurozone"]),
+    ("New York", &[]),
+    ("London", &[]),
+    ("Tokyo", &[]),
+    ("Beijing", &[]),
+    ("Shanghai", &[]),
+    ("Hong Kong", &[]),
+    ("Frankfurt", &[]),
+];
+
+/// Last words of organization names.
+const ORGANIZATION_SUFFIXES: &[&str] = &[
+    "Inc", "Inc.", "Corp", "Corp.", "Corporation", "Co", "Co.", "Company", "Ltd", "Ltd.", "LLC", "Plc", "PLC",
+    "AG", "SA", "NV", "Group", "Holdings", "Bank", "Partners", "Capital", "Fund", "Commission", "Agency",
+    "Ministry", "Department", "Exchange", "University", "Association", "Authority", "Institute",
+];
+
+/// Words before the name of a person, lowercased.
+const PERSON_TITLES: &[&str] = &[
+    "ceo", "cfo", "coo", "cto", "executive", "chairman", "chairwoman", "chair", "president", "founder",
+    "co-founder", "director", "officer", "governor", "minister", "secretary", "senator", "treasurer",
+    "analyst", "economist", "strategist", "investor", "billionaire", "spokesperson", "spokesman",
+    "spokeswoman", "mr", "mrs", "ms", "dr",
+];
+
+/// Lowercase words allowed inside a capitalized run, e.g. `Bank of America`.
+const CONNECTORS: &[&str] = &["of", "&", "for", "de"];
+
+/// Abbreviations keeping their final dot, which then ends no sentence.
+const ABBREVIATIONS: &[&str] = &["Inc", "Corp", "Co", "Ltd", "Mr", "Mrs", "Ms", "Dr", "Jr", "St"];
+
+/// Kind of a named entity.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Person,
+    Organization,
+    Location,
+}
+impl EntityKind {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            EntityKind::Person => "person",
+            EntityKind::Organization => "organization",
+            EntityKind::Location => "location",
+        }
+    }
+}
+impl std::str::FromStr for EntityKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "person" => Ok(EntityKind::Person),
+            "organization" => Ok(EntityKind::Organization),
+            "location" => Ok(EntityKind::Location),
+            _ => Err(format!("Unknown entity kind `{}`", kind)),
+        }
+    }
+}
+
+/// A person, organization or location mentioned by an article, under its canonical name.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Entity {
+    pub name: String,
+    pub kind: EntityKind,
+}
+
+/// A word of the text, without its surrounding punctuation.
+#[derive(Debug)]
+struct Token<'a> {
+    word: &'a str,
+    /// Followed by punctuation: no name spans it.
+    breaks: bool,
+    /// Followed by the end of a sentence.
+    ends_sentence: bool,
+}
+
+fn tokens(text: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    for raw in text.split_whitespace() {
+        let start = raw.trim_start_matches(|c: char| !c.is_alphanumeric());
+        if start.len() < raw.len() {
+            // Opening quote or parenthesis.
+            if let Some(previous) = tokens.last_mut() {
+                previous.breaks = true;
+            }
+        }
+        let mut word = start.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '&' && c != '+');
+        let mut tail = &start[word.len()..];
+        if tail.starts_with('.') && (word.contains('.') || ABBREVIATIONS.contains(&word)) {
+            word = &start[..word.len() + 1];
+            tail = &tail[1..];
+        }
+        let word = word.strip_suffix("'s").or_else(|| word.strip_suffix("’s")).unwrap_or(word);
+        if word.is_empty() {
+            continue;
+        }
+        let ends_sentence = tail.contains(['.', '!', '?']);
+        tokens.push(Token { word, breaks: !tail.is_empty(), ends_sentence });
+    }
+    tokens
+}
+
+fn capitalized(word: &str) -> bool {
+    word.chars().next().is_some_and(char::is_uppercase)
+}
+
+fn is_title(word: &str) -> bool {
+    PERSON_TITLES.contains(&word.trim_end_matches('.').to_lowercase().as_str())
+}
+
+/// Extracts named entities, rule-based.
+pub struct EntityExtractor {
+    /// Lowercased names and aliases, to their entity.
+    gazetteer: HashMap<String, Entity>,
+    /// Longest name or alias, in words.
+    longest: usize,
+    max_entities: usize,
+}
+impl EntityExtractor {
+    pub fn new(config: &EntitiesConfig) -> Self {
+        let mut gazetteer = HashMap::new();
+        let mut add = |kind: EntityKind, name: &str, aliases: &[String]| {
+            let entity = Entity { name: name.to_string(), kind };
+            for alias in aliases.iter().map(String::as_str).chain([name]) {
+                gazetteer.insert(Self::key(alias.split_whitespace()), entity.clone());
+            }
+        };
+        for (kind, entries) in [(EntityKind::Organization, ORGANIZATIONS), (EntityKind::Location, LOCATIONS)] {
+            for (name, aliases) in entries {
+                add(kind, name, &aliases.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+            }
+        }
+        for (kind, entries) in [
+            (EntityKind::Person, &config.people),
+            (EntityKind::Organization, &config.organizations),
+            (EntityKind::Location, &config.locations),
+        ] {
+            for (name, aliases) in entries {
+                add(kind, name, aliases);
+            }
+        }
+        let longest = gazetteer.keys().map(|k| k.split(' ').count()).max().unwrap_or(1);
+        Self { gazetteer, longest, max_entities: config.max_entities }
+    }
+
+    fn key<'a>(words: impl Iterator<Item = &'a str>) -> String {
+        words.map(str::to_lowercase).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Canonical name of `name`, when it is a name or alias of the gazetteer.
+    pub fn resolve(&self, name: &str) -> String {
+        match self.gazetteer.get(&Self::key(name.split_whitespace())) {
+            Some(entity) => entity.name.clone(),
+            None => name.trim().to_string(),
+        }
+    }
+
+    /// Longest gazetteer entry starting `tokens`, with its length in words.
+    fn lookup(&self, tokens: &[Token]) -> Option<(Entity, usize)> {
+        if !capitalized(tokens.first()?.word) {
+            return None;
+        }
+        (1..=self.longest.min(tokens.len())).rev().find_map(|len| {
+            if tokens[..len - 1].iter().any(|t| t.breaks) {
+                return None;
+            }
+            let entity = self.gazetteer.get(&Self::key(tokens[..len].iter().map(|t| t.word)))?;
+            Some((entity.clone(), len))
+        })
+    }
+
+    /// Length of the run of capitalized words starting at `start`.
+    fn run_length(tokens: &[Token], start: usize) -> usize {
+        let sentence_start = start == 0 || tokens[start - 1].ends_sentence;
+        let mut end = start + 1;
+        while end < tokens.len() && !tokens[end - 1].breaks {
+            if capitalized(tokens[end].word) {
+                end += 1;
+            } else if CONNECTORS.contains(&tokens[end].word)
+                && !tokens[end].breaks
+                && end + 1 < tokens.len()
+                && capitalized(tokens[end + 1].word)
+                // `Shares of Apple Inc.`: the first word of a sentence is capitalized anyway.
+                && !(sentence_start && end == start + 1)
+            {
+                end += 2;
+            } else {
+                break;
+            }
+        }
+        end - start
+    }
+
+    /// Entity named by the capitalized run `tokens[start..start + len]`, if any.
+    fn classify(tokens: &[Token], start: usize, len: usize) -> Option<Entity> {
+        let run = &tokens[start..start + len];
+        // A title inside the run (`Apple CEO Tim Cook`) or right before it (`analyst Jane Doe`).
+        let after_title = match run.iter().rposition(|t| is_title(t.word)) {
+            Some(title) => Some(title + 1),
+            None if start > 0 && is_title(tokens[start - 1].word) && !tokens[start - 1].breaks => Some(0),
+            None => None,
+        };
+        let name = |words: &[Token]| words.iter().map(|t| t.word).collect::<Vec<_>>().join(" ");
+        if let Some(after_title) = after_title {
+            let person = &run[after_title..];
+            if (1..=3).contains(&person.len()) && !person.iter().any(|t| ORGANIZATION_SUFFIXES.contains(&t.word)) {
+                return Some(Entity { name: name(person), kind: EntityKind::Person });
+            }
+        }
+        if len >= 2 && ORGANIZATION_SUFFIXES.contains(&run[len - 1].word) {
+            return Some(Entity { name: name(run), kind: EntityKind::Organization });
+        }
+        None
+    }
+
+    /// Up to `max_entities` entities of `text`, in order of appearance.
+    pub fn extract(&self, text: &str) -> Vec<Entity> {
+        let tokens = tokens(text);
+        let mut seen = HashSet::new();
+        let mut entities = Vec::new();
+        let mut start = 0;
+        while start < tokens.len() && entities.len() < self.max_entities {
+            let (entity, len) = match self.lookup(&tokens[start..]) {
+                Some((entity, len)) => (Some(entity), len),
+                None if capitalized(tokens[start].word) => {
+                    let len = Self::run_length(&tokens, start);
+                    match Self::classify(&tokens, start, len) {
+                        Some(entity) => (Some(entity), len),
+                        // The rest of the run may hold a name: `The ECB`.
+                        None => (None, 1),
+                    }
+                }
+                None => (None, 1),
+            };
+            if let Some(entity) = entity {
+                // A name found by the rules may be an alias too, e.g. `Powell` after a title.
+                let entity = match self.gazetteer.get(&Self::key(entity.name.split(' '))) {
+                    Some(known) => known.clone(),
+                    None => entity,
+                };
+                if seen.insert(entity.clone()) {
+                    entities.push(entity);
+                }
+            }
+            start += len;
+        }
+        entities
+    }
+
+    /// Entities of `article`, from its title and summary.
+    pub fn entities_of(&self, article: &NormalizedArticle) -> Vec<Entity> {
+        // The translation when there is one: the rules are for English.
+        let (title, summary) = match &article.translation {
+            Some(translation) => (translation.title.as_deref(), translation.summary.as_deref()),
+            None => (article.title.as_deref(), article.summary.as_deref()),
+        };
+        let text = [title, summary]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(". ");
+        self.extract(&text)
+    }
+}
+impl Default for EntityExtractor {
+    fn default() -> Self {
+        Self::new(&EntitiesConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(name: &str, kind: EntityKind) -> Entity {
+        Entity { name: name.to_string(), kind }
+    }
+
+    fn extractor() -> EntityExtractor {
+        let config = EntitiesConfig {
+            people: HashMap::from([("Jerome Powell".to_string(), vec!["Fed chair".to_string(), "Powell".to_string()])]),
+            ..EntitiesConfig::default()
+        };
+        EntityExtractor::new(&config)
+    }
+
+    #[test]
+    fn finds_gazetteer_names_and_aliases() {
+        let entities = extractor().extract("Fed chair signals patience as the ECB weighs cuts. U.S. stocks rally; Powell speaks in Tokyo.");
+        assert_eq!(
+            entities,
+            vec![
+                entity("Jerome Powell", EntityKind::Person),
+                entity("European Central Bank", EntityKind::Organization),
+                entity("United States", EntityKind::Location),
+                entity("Tokyo", EntityKind::Location),
+            ]
+        );
+        assert_eq!(extractor().resolve("fed CHAIR"), "Jerome Powell");
+        assert_eq!(extractor().resolve("Tim Cook"), "Tim Cook");
+    }
+
+    #[test]
+    fn finds_organizations_and_people_by_rule() {
+        let entities = EntityExtractor::default().extract(
+            "Shares of Goldman Sachs Group fell after Apple CEO Tim Cook met analyst Jane Doe. Monday was calm for Nvidia Corp.",
+        );
+        assert_eq!(
+            entities,
+            vec![
+                entity("Goldman Sachs Group", EntityKind::Organization),
+                entity("Tim Cook", EntityKind::Person),
+                entity("Jane Doe", EntityKind::Person),
+                entity("Nvidia Corp.", EntityKind::Organization),
+            ]
+        );
+    }
+
+    #[test]
+    fn extracts_from_the_translation() {
+        let article = NormalizedArticle::test("b").title("La BCE relève ses taux directeurs").language("fr").translated("The ECB raises its key rates in Frankfurt");
+        assert_eq!(
+            EntityExtractor::default().entities_of(&article),
+            vec![entity("European Central Bank", EntityKind::Organization), entity("Frankfurt", EntityKind::Location)]
+        );
+    }
+}
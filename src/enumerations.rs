@@ -0,0 +1,169 @@
+//! Valid values of the provider filters.
+//!
+//! A filter value a provider does not know (`industries=Tech` rather than `Technology`) is no error
+//! to it: the request is spent and returns nothing. With `enumerations.enabled`, the values accepted
+//! by the filters of each provider are fetched every `enumerations.refresh_secs` (see
+//! `NewsProvider::fetch_enumerations`) and cached here:
+//!
+//! - MarketAux: `entity_types` and `industries`, from its `entity/type/list` and
+//!   `entity/industry/list` endpoints,
+//! - Alpha Vantage: `topics`, the documented ones, as the API does not list them.
+//!
+//! Before a request is sent, each of its comma-separated filters is checked against the cached list
+//! and the request rejected on an unknown value, with the expected ones. A filter without a list (not
+//! fetched yet, or not offered, like the MarketAux `countries`) is not checked. A failed refresh keeps
+//! the previous lists.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::EnumerationsConfig;
+use crate::provider::NewsProvider;
+use crate::scheduler::{self, Schedule};
+
+/// Name of the refresh in the logs.
+pub const SOURCE: &str = "enumerations";
+
+/// Valid values of the filters of a provider, by filter name.
+pub type Enumerations = BTreeMap<String, Vec<String>>;
+
+fn lists() -> &'static RwLock<HashMap<String, Enumerations>> {
+    static LISTS: OnceLock<RwLock<HashMap<String, Enumerations>>> = OnceLock::new();
+    LISTS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Replaces the lists of `provider` given in `enumerations`, an empty one being ignored.
+pub fn update(provider: &str, enumerations: Enumerations) {
+    let mut lists = lists().write().unwrap();
+    let cached = lists.entry(provider.to_string()).or_default();
+    for (filter, values) in enumerations.into_iter().filter(|(_, values)| !values.is_empty()) {
+        cached.insert(filter, values);
+    }
+}
+
+/// Cached lists of `provider`.
+pub fn of(provider: &str) -> Enumerations {
+    lists().read().unwrap().get(provider).cloned().unwrap_or_default()
+}
+
+/// Checks the filters of the request `args` of `provider` against its cached lists.
+pub fn check(provider: &str, args: &Value) -> Result<(), String> {
+    let lists = lists().read().unwrap();
+    let Some(enumerations) = lists.get(provider) else {
+        return Ok(());
+    };
+    for (filter, valid) in enumerations {
+        let Some(values) = args.get(filter).and_then(Value::as_str) else {
+            continue;
+        };
+        let unknown = values.split(',').map(str::trim).find(|v| !v.is_empty() && !valid.iter().any(|known| known == v));
+        if let Some(value) = unknown {
+            return Err(match valid.iter().find(|known| known.eq_ignore_ascii_case(value)) {
+                Some(known) => format!("Unknown `{}` value `{}` for {}, did you mean `{}`?", filter, value, provider, known),
+                None => format!("Unknown `{}` value `{}` for {}, expected one of: {}", filter, value, provider, valid.join(", ")),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Fetches the lists of every provider offering some. Returns the number of lists cached.
+pub async fn refresh(providers: &[Arc<dyn NewsProvider>]) -> usize {
+    let mut cached = 0;
+    for provider in providers {
+        match provider.fetch_enumerations().await {
+            Ok(enumerations) => {
+                cached += enumerations.values().filter(|values| !values.is_empty()).count();
+                update(provider.name(), enumerations);
+            }
+            Err(e) => warn!("Failed to fetch the filter values of {}: {}", provider.name(), e),
+        }
+    }
+    cached
+}
+
+/// Refreshes the lists of `providers` every `enumerations.refresh_secs`, starting right away.
+pub fn spawn(providers: Vec<Arc<dyn NewsProvider>>, config: &EnumerationsConfig) -> JoinHandle<()> {
+    let providers = Arc::new(providers);
+    let schedule = Schedule::Every(Duration::from_secs(config.refresh_secs.max(1)));
+    scheduler::spawn(SOURCE, schedule, move || {
+        let providers = providers.clone();
+        async move {
+            info!("Cached {} lists of filter values.", refresh(&providers).await);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::errors::{ApiError, ProviderError};
+    use crate::options::FetchType;
+    use crate::provider::{ProviderFuture, ProviderHealth};
+
+    /// Lists the `industries` of `values`, or fails without any.
+    struct Lists {
+        values: Vec<&'static str>,
+    }
+    impl NewsProvider for Lists {
+        fn name(&self) -> &str {
+            "lists"
+        }
+
+        fn supports(&self, _fetch_type: &FetchType) -> bool {
+            true
+        }
+
+        fn fetch(&self, _args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async { Ok(Value::Null) })
+        }
+
+        fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async { Ok(Value::Null) })
+        }
+
+        fn health(&self) -> ProviderHealth {
+            ProviderHealth::Healthy
+        }
+
+        fn fetch_enumerations(&self) -> ProviderFuture<'_, Result<Enumerations, ProviderError>> {
+            Box::pin(async move {
+                if self.values.is_empty() {
+                    return Err(ApiError::NoEndpointProvided.into());
+                }
+                let values = self.values.iter().map(|v| v.to_string()).collect();
+                Ok(Enumerations::from([("industries".to_string(), values), ("entity_types".to_string(), Vec::new())]))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_the_values_missing_from_the_lists() {
+        // Nothing is checked before the lists are fetched.
+        assert!(check("lists", &json!({ "industries": "Tech" })).is_ok());
+
+        let providers: Vec<Arc<dyn NewsProvider>> = vec![Arc::new(Lists { values: vec!["Technology", "Energy"] })];
+        assert_eq!(refresh(&providers).await, 1);
+        assert!(check("lists", &json!({ "industries": "Energy, Technology", "entity_types": "anything" })).is_ok());
+        assert_eq!(
+            check("lists", &json!({ "industries": "Energy,technology" })).unwrap_err(),
+            "Unknown `industries` value `technology` for lists, did you mean `Technology`?"
+        );
+        assert_eq!(
+            check("lists", &json!({ "industries": "Tech" })).unwrap_err(),
+            "Unknown `industries` value `Tech` for lists, expected one of: Technology, Energy"
+        );
+
+        // A failed refresh keeps the previous lists.
+        let failing: Vec<Arc<dyn NewsProvider>> = vec![Arc::new(Lists { values: Vec::new() })];
+        assert_eq!(refresh(&failing).await, 0);
+        assert_eq!(of("lists")["industries"], ["Technology", "Energy"]);
+    }
+}
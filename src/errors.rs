@@ -65,6 +65,12 @@ pub enum ApiError {
     },
     /// When no endpoint was provided.
     NoEndpointProvided,
+    /// Represents a provider configuration that does not allow the request.
+    ConfigError {
+        message: String,
+    },
+    /// Represents a body that could not be decoded as a JSON payload.
+    EncodingError(EncodingError),
     /// Represents an unhandled error with optional `status`, `headers` and `body` details.
     UnhandledError {
         message: String,
@@ -100,6 +106,12 @@ impl fmt::Display for ApiError {
             ApiError::NoEndpointProvided => {
                 write!(f, "No endpoint provided")
             }
+            ApiError::ConfigError { message } => {
+                write!(f, "Config Error: {}", message)
+            }
+            ApiError::EncodingError(err) => {
+                write!(f, "Encoding Error: {}", err)
+            }
             ApiError::UnhandledError { message, status, headers, body } => {
                 write!(f, "Unhandled Error: {} | Status: {:?} | Headers: {:?} | Body: {}", 
                        message, status, headers, body.as_ref().unwrap_or(&"".to_string()))
@@ -111,6 +123,51 @@ impl fmt::Display for ApiError {
 // Implement std::error::Error for ApiError.
 impl std::error::Error for ApiError {}
 
+impl From<EncodingError> for ApiError {
+    fn from(err: EncodingError) -> Self {
+        ApiError::EncodingError(err)
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() || e.is_connect() {
+            ApiError::NetworkError {
+                message: e.to_string(),
+                status: Some(StatusCode::REQUEST_TIMEOUT),
+                headers: None,
+                body: None,
+            }
+        } else {
+            ApiError::RequestError {
+                message: e.to_string(),
+                status: e.status().or(Some(StatusCode::BAD_REQUEST)),
+                headers: None,
+                body: None,
+            }
+        }
+    }
+}
+
+/// Errors raised while decoding a provider response body.
+#[derive(Debug, Error)]
+pub enum EncodingError {
+    #[error("Expected a JSON body but got `{content_type}`. Body starts with: {snippet}")]
+    UnexpectedContentType {
+        content_type: String,
+        snippet: String,
+    },
+
+    #[error("Response body is empty")]
+    EmptyBody,
+
+    #[error("Unsupported charset: {0}")]
+    UnsupportedCharset(String),
+
+    #[error("Response body is not valid UTF-16: {0}")]
+    InvalidUtf16(String),
+}
+
 
 #[derive(Debug, Error)]
 pub enum FMPApiError {
@@ -122,4 +179,19 @@ pub enum FMPApiError {
     
     #[error("Failed to parse data: {0}")]
     ParseError(String),
-}
\ No newline at end of file
+}
+/// Error of a `NewsProvider`, wrapping the error type of the underlying client.
+#[derive(Debug, Error)]
+pub enum ProviderError {
+    #[error(transparent)]
+    Api(#[from] ApiError),
+
+    #[error(transparent)]
+    FMP(#[from] FMPApiError),
+
+    #[error(transparent)]
+    Rss(#[from] crate::rss::RssError),
+
+    #[error(transparent)]
+    Reddit(#[from] crate::reddit::RedditError),
+}
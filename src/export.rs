@@ -0,0 +1,724 @@
+//! Scheduled exports of the stored articles.
+//!
+//! Each `[[exports.jobs]]` runs on its `cron` or `interval_secs` schedule (see `scheduler.rs`),
+//! as a task of the polling loop, and writes the articles published during the previous `hour` or
+//! `day` (UTC) under `exports.directory`:
+//!
+//! ```text
+//! exports/<job>/2024-05-01.parquet        # one file per period
+//! exports/<job>/2024-05-01/NVDA.parquet   # one file per ticker with `per_ticker`
+//! exports/<job>/2024-05-01T13.jsonl       # hourly periods
+//! ```
+//!
+//! Formats are `parquet` (Snappy compressed, one row per article) and `jsonl` (one
+//! `NormalizedArticle` per line). Files are written under a temporary name and renamed once
+//! complete, so a reader never sees a partial export. A period without articles writes no file.
+//!
+//! Parquet jobs can use the `hive` layout instead, partitioned by provider and publication day for
+//! DuckDB or Spark to prune, with `published_at` typed as a UTC timestamp and the partition columns
+//! left out of the files:
+//!
+//! ```text
+//! exports/<job>/provider=finnhub/date=2024-05-01/2024-05-01.parquet
+//! exports/<job>/_schema.json    # columns, partitions and a DuckDB query
+//! ```
+//!
+//! The last run of each job is recorded in the `export_runs` collection and listed by the `exports`
+//! admin command. A failed run raises an `export_failed` alert for the job, resolved by its next
+//! successful run.
+//!
+//! The `export <directory>` command exports the stored articles on demand instead, one file per
+//! run named after its time (`<directory>/20240502T003000Z.parquet`). Each run records how far it
+//! read (the sequence number of the last article) in the `export_watermarks` collection, keyed by
+//! the directory; with `--since-last`, only the articles stored (rather than published) since the
+//! last export to the same directory are written, so a downstream job can load each new file as it
+//! comes.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::{Array, ArrayRef, Float64Array, RecordBatch, StringArray, TimestampMicrosecondArray};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Duration as UtcDuration, DurationRound, SecondsFormat, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tracing::{error, info};
+
+use crate::alerts::{Alert, Alerter};
+use crate::clock;
+use crate::config::{ExportJobConfig, ExportsConfig, SourceSchedule};
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+use crate::scheduler::{self, Schedule};
+use crate::storage::{ArticleFilter, Storage, StoredFeed};
+use crate::utils::now;
+
+pub const FAILURE_RULE: &str = "export_failed";
+/// Partition of the articles without a publication time, as Hive names it.
+const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+const SCHEMA_MANIFEST: &str = "_schema.json";
+/// Articles read from the storage at once by the `export` command.
+const STORED_BATCH: i64 = 1000;
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Parquet error: {0}")]
+    Parquet(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Parquet,
+    JsonLines,
+}
+impl ExportFormat {
+    pub fn to_str(&self) -> &str {
+        match self {
+            ExportFormat::Parquet => "parquet",
+            ExportFormat::JsonLines => "jsonl",
+        }
+    }
+}
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parquet" => Ok(ExportFormat::Parquet),
+            "jsonl" => Ok(ExportFormat::JsonLines),
+            _ => Err(format!("unknown export format `{}`", s)),
+        }
+    }
+}
+
+/// Directory layout of the files of a job.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportLayout {
+    Flat,
+    /// `provider=<provider>/date=<YYYY-MM-DD>/` partitions with typed columns.
+    Hive,
+}
+impl ExportLayout {
+    pub fn to_str(&self) -> &str {
+        match self {
+            ExportLayout::Flat => "flat",
+            ExportLayout::Hive => "hive",
+        }
+    }
+}
+impl std::str::FromStr for ExportLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(ExportLayout::Flat),
+            "hive" => Ok(ExportLayout::Hive),
+            _ => Err(format!("unknown export layout `{}`", s)),
+        }
+    }
+}
+
+/// Publication period of the articles of an export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportPeriod {
+    Hour,
+    Day,
+}
+impl ExportPeriod {
+    pub fn to_str(&self) -> &str {
+        match self {
+            ExportPeriod::Hour => "hour",
+            ExportPeriod::Day => "day",
+        }
+    }
+
+    /// Start, end and label of the last complete period before `at`.
+    pub fn previous(&self, at: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>, String) {
+        let (length, format) = match self {
+            ExportPeriod::Hour => (UtcDuration::hours(1), "%Y-%m-%dT%H"),
+            ExportPeriod::Day => (UtcDuration::days(1), "%Y-%m-%d"),
+        };
+        let end = at.duration_trunc(length).unwrap_or(at);
+        let start = end - length;
+        (start, end, start.format(format).to_string())
+    }
+}
+impl std::str::FromStr for ExportPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hour" => Ok(ExportPeriod::Hour),
+            "day" => Ok(ExportPeriod::Day),
+            _ => Err(format!("unknown export period `{}`", s)),
+        }
+    }
+}
+
+/// Outcome of a run.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportStatus {
+    Succeeded,
+    Failed,
+}
+
+/// Last run of a job.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportRun {
+    #[serde(rename = "_id")]
+    pub job: String,
+    /// Label of the period exported, e.g. `2024-05-01`.
+    pub period: String,
+    pub status: ExportStatus,
+    pub started_at: String,
+    pub finished_at: String,
+    pub error: Option<String>,
+    /// Files written, relative to `exports.directory`.
+    pub files: Vec<String>,
+    pub articles: usize,
+    pub last_success_at: Option<String>,
+    /// Failed runs since the last successful one.
+    pub consecutive_failures: u32,
+}
+
+/// Position of the last `export` to a destination.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportWatermark {
+    #[serde(rename = "_id")]
+    pub destination: String,
+    /// Storage time of the last article read.
+    pub stored_at: String,
+    /// Sequence number of the last article read, see `StoredFeed`. `None` in the watermarks saved
+    /// before it was recorded, which resume from `stored_at` instead.
+    #[serde(default)]
+    pub seq: Option<u64>,
+    pub exported_at: String,
+    /// File written by the last export, `None` when it found no new article.
+    pub file: Option<String>,
+    pub articles: usize,
+}
+
+/// A run of the `export` command.
+#[derive(Debug, Clone)]
+pub struct StoredExport {
+    pub directory: PathBuf,
+    pub format: ExportFormat,
+    /// Uppercased, every ticker when empty.
+    pub tickers: Vec<String>,
+    /// Only the articles stored since the last export to `directory`.
+    pub since_last: bool,
+}
+impl StoredExport {
+    /// Writes the stored articles, or those stored since the last export, to a file named after
+    /// `at`, then saves the new watermark of the directory.
+    pub async fn run(&self, storage: &dyn Storage, at: DateTime<Utc>) -> Result<ExportWatermark, ExportError> {
+        let destination = self.directory.to_string_lossy().to_string();
+        let previous = if self.since_last {
+            storage.export_watermark(&destination).await.map_err(|e| ExportError::Database(e.to_string()))?
+        } else {
+            None
+        };
+        let (mut feed, mut stored_at) = match previous {
+            Some(ExportWatermark { seq: Some(seq), stored_at, .. }) => (StoredFeed::after(seq), stored_at),
+            // Watermark of an older version: the articles of its last second are exported again.
+            Some(watermark) => (StoredFeed::since(watermark.stored_at.clone()), watermark.stored_at),
+            None => (StoredFeed::after(0), String::new()),
+        };
+
+        let mut articles = Vec::new();
+        loop {
+            let batch = feed.next_batch(storage, STORED_BATCH).await.map_err(|e| ExportError::Database(e.to_string()))?;
+            let Some(last) = batch.last() else {
+                break;
+            };
+            stored_at = last.stored_at.clone();
+            articles.extend(batch.into_iter().map(|stored| stored.article).filter(|article| {
+                self.tickers.is_empty() || article.tickers.iter().any(|t| self.tickers.contains(&t.to_uppercase()))
+            }));
+        }
+
+        let file = if articles.is_empty() {
+            None
+        } else {
+            let name = format!("{}.{}", at.format("%Y%m%dT%H%M%SZ"), self.format.to_str());
+            write(self.format, ExportLayout::Flat, &self.directory.join(&name), &articles.iter().collect::<Vec<_>>())?;
+            Some(name)
+        };
+        let watermark = ExportWatermark {
+            destination,
+            stored_at,
+            seq: feed.position(),
+            exported_at: now(),
+            file,
+            articles: articles.len(),
+        };
+        // Not saved when the file could not be written: the next export writes these articles again.
+        storage.save_export_watermark(&watermark).await.map_err(|e| ExportError::Database(e.to_string()))?;
+        Ok(watermark)
+    }
+}
+
+/// A checked `[[exports.jobs]]` entry.
+#[derive(Debug, Clone)]
+pub struct ExportJob {
+    pub name: String,
+    pub format: ExportFormat,
+    pub period: ExportPeriod,
+    pub layout: ExportLayout,
+    pub schedule: Schedule,
+    per_ticker: bool,
+    /// Uppercased, every ticker when empty.
+    tickers: Vec<String>,
+    directory: PathBuf,
+}
+impl ExportJob {
+    /// The job of `config`, writing under `directory`.
+    pub fn new(config: &ExportJobConfig, directory: &Path) -> Result<Self, String> {
+        if config.name.is_empty() || config.name.contains(['/', '\\']) || config.name.starts_with('.') {
+            return Err(format!("invalid job name `{}`", config.name));
+        }
+        let format: ExportFormat = config.format.parse()?;
+        let period: ExportPeriod = config.period.parse()?;
+        let layout: ExportLayout = config.layout.parse()?;
+        if layout == ExportLayout::Hive && (format != ExportFormat::Parquet || config.per_ticker) {
+            return Err("the `hive` layout needs the `parquet` format, without `per_ticker`".to_string());
+        }
+        let schedule = Schedule::parse(&SourceSchedule { interval_secs: config.interval_secs, cron: config.cron.clone() })?
+            .ok_or_else(|| "neither `cron` nor `interval_secs` is set".to_string())?;
+        Ok(Self {
+            name: config.name.clone(),
+            format,
+            period,
+            layout,
+            schedule,
+            per_ticker: config.per_ticker,
+            tickers: config.tickers.iter().map(|t| t.to_uppercase()).collect(),
+            directory: directory.to_path_buf(),
+        })
+    }
+
+    /// Exports the period before `at`. Returns the label of the period, the files written relative
+    /// to the export directory, and the number of articles exported.
+    pub async fn export(&self, storage: &dyn Storage, at: DateTime<Utc>) -> Result<(String, Vec<String>, usize), ExportError> {
+        let (start, end, label) = self.period.previous(at);
+        let end = end.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let filter = ArticleFilter {
+            tickers: self.tickers.clone(),
+            from: Some(start.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            to: Some(end.clone()),
+            ..ArticleFilter::default()
+        };
+        let mut articles = storage.filtered_articles(&filter, 0).await.map_err(|e| ExportError::Database(e.to_string()))?;
+        // The bound is inclusive, the next period starts at `end`.
+        articles.retain(|a| a.published_at.as_deref().is_some_and(|t| t < end.as_str()));
+        articles.reverse();
+
+        let files: BTreeMap<String, Vec<&NormalizedArticle>> = if self.layout == ExportLayout::Hive {
+            let mut files: BTreeMap<String, Vec<&NormalizedArticle>> = BTreeMap::new();
+            for article in &articles {
+                files.entry(format!("{}/{}/{}.parquet", self.name, hive_partition(article), label)).or_default().push(article);
+            }
+            files
+        } else if self.per_ticker {
+            let mut files: BTreeMap<String, Vec<&NormalizedArticle>> = BTreeMap::new();
+            for article in &articles {
+                for ticker in article.tickers.iter().map(|t| t.to_uppercase()) {
+                    if self.tickers.is_empty() || self.tickers.contains(&ticker) {
+                        let path = format!("{}/{}/{}.{}", self.name, label, ticker.replace(['/', '\\'], "_"), self.format.to_str());
+                        files.entry(path).or_default().push(article);
+                    }
+                }
+            }
+            files
+        } else if articles.is_empty() {
+            BTreeMap::new()
+        } else {
+            BTreeMap::from([(format!("{}/{}.{}", self.name, label, self.format.to_str()), articles.iter().collect())])
+        };
+
+        for (path, articles) in &files {
+            write(self.format, self.layout, &self.directory.join(path), articles)?;
+        }
+        if self.layout == ExportLayout::Hive && !files.is_empty() {
+            let manifest = serde_json::to_vec_pretty(&schema_manifest(&self.name)).map_err(std::io::Error::other)?;
+            fs::write(self.directory.join(&self.name).join(SCHEMA_MANIFEST), manifest)?;
+        }
+        Ok((label, files.into_keys().collect(), articles.len()))
+    }
+
+    /// Runs the export of the period before `at` and records its outcome. A failure raises an
+    /// alert, a success resolves it.
+    pub async fn run(&self, storage: &dyn Storage, alerter: &Alerter, at: DateTime<Utc>) -> ExportRun {
+        let started_at = now();
+        let previous = match storage.export_runs().await {
+            Ok(runs) => runs.into_iter().find(|run| run.job == self.name),
+            Err(e) => {
+                error!("Failed to read the last run of export `{}`: {}", self.name, e);
+                None
+            }
+        };
+        let (_, _, period) = self.period.previous(at);
+        let mut run = ExportRun {
+            job: self.name.clone(),
+            period,
+            status: ExportStatus::Succeeded,
+            started_at,
+            finished_at: String::new(),
+            error: None,
+            files: Vec::new(),
+            articles: 0,
+            last_success_at: previous.as_ref().and_then(|run| run.last_success_at.clone()),
+            consecutive_failures: 0,
+        };
+        match self.export(storage, at).await {
+            Ok((_, files, articles)) => {
+                info!("Export `{}` of {} done. | {} articles, {} files", self.name, run.period, articles, files.len());
+                run.files = files;
+                run.articles = articles;
+                run.last_success_at = Some(now());
+                alerter.resolve(FAILURE_RULE, &self.name).await;
+            }
+            Err(e) => {
+                run.status = ExportStatus::Failed;
+                run.error = Some(e.to_string());
+                run.consecutive_failures = previous.map_or(0, |run| run.consecutive_failures) + 1;
+                alerter.raise(&Alert::new(FAILURE_RULE, &self.name, format!("export of {} failed: {}", run.period, e))).await;
+            }
+        }
+        run.finished_at = now();
+        if let Err(e) = storage.save_export_run(&run).await {
+            error!("Failed to record the run of export `{}`: {}", self.name, e);
+        }
+        run
+    }
+}
+
+/// `provider=<provider>/date=<YYYY-MM-DD>` directories of `article`.
+fn hive_partition(article: &NormalizedArticle) -> String {
+    let date = article.published_at.as_deref().and_then(parse_timestamp).map(|t| t.format("%Y-%m-%d").to_string());
+    format!("provider={}/date={}", article.provider.replace(['/', '\\', '='], "_"), date.as_deref().unwrap_or(HIVE_DEFAULT_PARTITION))
+}
+
+/// Writes `articles` to `path` in `format`, through a temporary file.
+fn write(format: ExportFormat, layout: ExportLayout, path: &Path, articles: &[&NormalizedArticle]) -> Result<(), ExportError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension(format!("{}.partial", format.to_str()));
+    let mut file = BufWriter::new(File::create(&partial)?);
+    match format {
+        ExportFormat::JsonLines => {
+            for article in articles {
+                serde_json::to_writer(&mut file, article).map_err(std::io::Error::other)?;
+                file.write_all(b"\n")?;
+            }
+            file.flush()?;
+        }
+        ExportFormat::Parquet => {
+            let batch = match layout {
+                ExportLayout::Flat => record_batch(articles),
+                ExportLayout::Hive => hive_record_batch(articles),
+            };
+            let batch = batch.map_err(|e| ExportError::Parquet(e.to_string()))?;
+            let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+            let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties)).map_err(|e| ExportError::Parquet(e.to_string()))?;
+            writer.write(&batch).map_err(|e| ExportError::Parquet(e.to_string()))?;
+            writer.close().map_err(|e| ExportError::Parquet(e.to_string()))?;
+        }
+    }
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// One row per article.
+pub fn record_batch(articles: &[&NormalizedArticle]) -> Result<RecordBatch, arrow_schema::ArrowError> {
+    let text = |name: &str, value: fn(&NormalizedArticle) -> Option<&str>| -> (Field, ArrayRef) {
+        let values: StringArray = articles.iter().map(|a| value(a)).collect();
+        (Field::new(name, DataType::Utf8, true), Arc::new(values))
+    };
+    let list = |name: &str, values: fn(&NormalizedArticle) -> &[String]| -> (Field, ArrayRef) {
+        let mut builder = ListBuilder::new(StringBuilder::new());
+        for article in articles {
+            for value in values(article) {
+                builder.values().append_value(value);
+            }
+            builder.append(true);
+        }
+        let array = builder.finish();
+        (Field::new(name, array.data_type().clone(), false), Arc::new(array))
+    };
+    let scores: Float64Array = articles.iter().map(|a| a.sentiment_score).collect();
+    let columns = vec![
+        text("provider", |a| Some(&a.provider)),
+        text("id", |a| Some(&a.id)),
+        text("published_at", |a| a.published_at.as_deref()),
+        text("url", |a| a.url.as_deref()),
+        text("title", |a| a.title.as_deref()),
+        text("summary", |a| a.summary.as_deref()),
+        text("source", |a| a.source.as_deref()),
+        text("language", |a| a.language.as_deref()),
+        list("authors", |a| &a.authors),
+        list("tickers", |a| &a.tickers),
+        list("topics", |a| &a.topics),
+        list("categories", |a| &a.categories),
+        list("keywords", |a| &a.keywords),
+        (Field::new("sentiment_score", DataType::Float64, true), Arc::new(scores) as ArrayRef),
+        text("sentiment", |a| a.sentiment.map(|label| label.to_str())),
+    ];
+    let (fields, arrays): (Vec<Field>, Vec<ArrayRef>) = columns.into_iter().unzip();
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+}
+
+/// One row per article with typed columns: `provider` and `id` are required, `published_at` is a
+/// UTC timestamp.
+pub fn typed_record_batch(articles: &[&NormalizedArticle]) -> Result<RecordBatch, arrow_schema::ArrowError> {
+    let flat = record_batch(articles)?;
+    let published_at: TimestampMicrosecondArray = articles
+        .iter()
+        .map(|a| a.published_at.as_deref().and_then(parse_timestamp).map(|t| t.timestamp_micros()))
+        .collect::<TimestampMicrosecondArray>()
+        .with_timezone("UTC");
+    let (fields, arrays): (Vec<Field>, Vec<ArrayRef>) = flat
+        .schema()
+        .fields()
+        .iter()
+        .zip(flat.columns())
+        .map(|(field, array)| match field.name().as_str() {
+            "published_at" => (Field::new("published_at", published_at.data_type().clone(), true), Arc::new(published_at.clone()) as ArrayRef),
+            "provider" | "id" => (field.as_ref().clone().with_nullable(false), array.clone()),
+            _ => (field.as_ref().clone(), array.clone()),
+        })
+        .unzip();
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+}
+
+/// One row per article of the `hive` layout: the typed columns without the `provider` partition.
+pub fn hive_record_batch(articles: &[&NormalizedArticle]) -> Result<RecordBatch, arrow_schema::ArrowError> {
+    let mut batch = typed_record_batch(articles)?;
+    let provider = batch.schema().index_of("provider")?;
+    batch.remove_column(provider);
+    Ok(batch)
+}
+
+/// Description of the `hive` layout files of `job`, written next to them as `_schema.json`.
+pub fn schema_manifest(job: &str) -> Value {
+    fn sql_type(data_type: &DataType) -> String {
+        match data_type {
+            DataType::Utf8 => "VARCHAR".to_string(),
+            DataType::Float64 => "DOUBLE".to_string(),
+            DataType::Timestamp(TimeUnit::Microsecond, Some(_)) => "TIMESTAMP WITH TIME ZONE".to_string(),
+            DataType::List(item) => format!("{}[]", sql_type(item.data_type())),
+            other => other.to_string(),
+        }
+    }
+    let columns: Vec<Value> = hive_record_batch(&[])
+        .map(|batch| batch.schema().fields().iter().map(|f| json!({ "name": f.name(), "type": sql_type(f.data_type()), "nullable": f.is_nullable() })).collect())
+        .unwrap_or_default();
+    json!({
+        "job": job,
+        "format": ExportFormat::Parquet.to_str(),
+        "layout": ExportLayout::Hive.to_str(),
+        "compression": "snappy",
+        "partitions": [
+            { "name": "provider", "type": "VARCHAR" },
+            { "name": "date", "type": "DATE", "default": HIVE_DEFAULT_PARTITION },
+        ],
+        "columns": columns,
+        "duckdb": format!("SELECT * FROM read_parquet('{}/**/*.parquet', hive_partitioning = true)", job),
+    })
+}
+
+/// Runs every valid job of `config` on its schedule. Invalid jobs are logged and skipped.
+pub fn spawn(config: &ExportsConfig, storage: Arc<dyn Storage>, alerter: Arc<Alerter>) {
+    for job in &config.jobs {
+        let job = match ExportJob::new(job, Path::new(&config.directory)) {
+            Ok(job) => Arc::new(job),
+            Err(e) => {
+                error!("Export job `{}`: {}. Job skipped.", job.name, e);
+                continue;
+            }
+        };
+        info!("Export `{}` scheduled. | {} of each {}, {} layout", job.name, job.format.to_str(), job.period.to_str(), job.layout.to_str());
+        let (storage, alerter) = (storage.clone(), alerter.clone());
+        scheduler::spawn(&format!("export.{}", job.name), job.schedule.clone(), move || {
+            let (job, storage, alerter) = (job.clone(), storage.clone(), alerter.clone());
+            async move {
+                job.run(storage.as_ref(), &alerter, clock::now()).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use reqwest::Client;
+
+    use crate::alerts::AlertState;
+    use crate::config::AlertsConfig;
+    use crate::storage::MemoryStorage;
+
+    fn at(t: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(t).unwrap().with_timezone(&Utc)
+    }
+
+    fn job(name: &str, format: &str, per_ticker: bool, directory: &Path) -> ExportJob {
+        let config = ExportJobConfig {
+            name: name.to_string(),
+            format: format.to_string(),
+            period: "day".to_string(),
+            cron: Some("0 30 0 * * *".to_string()),
+            interval_secs: None,
+            per_ticker,
+            tickers: Vec::new(),
+            layout: "flat".to_string(),
+        };
+        ExportJob::new(&config, directory).unwrap()
+    }
+
+    async fn storage() -> Arc<MemoryStorage> {
+        let storage = Arc::new(MemoryStorage::new());
+        let article = |id: &str, published_at: &str, tickers: &[&str]| {
+            NormalizedArticle::test(id).title(&format!("Story {}", id)).published_at(published_at).tickers(tickers).sentiment_score(0.4)
+        };
+        storage
+            .store_articles(&[
+                article("1", "2024-05-01T09:00:00Z", &["NVDA", "AMD"]),
+                article("2", "2024-05-01T23:59:59.500Z", &["NVDA"]),
+                article("3", "2024-05-02T00:00:00Z", &["NVDA"]),
+                article("4", "2024-04-30T23:00:00Z", &["AMD"]),
+            ])
+            .await
+            .unwrap();
+        storage
+    }
+
+    #[test]
+    fn previous_periods_are_complete() {
+        let (start, end, label) = ExportPeriod::Day.previous(at("2024-05-02T00:30:00Z"));
+        assert_eq!((start, end, label.as_str()), (at("2024-05-01T00:00:00Z"), at("2024-05-02T00:00:00Z"), "2024-05-01"));
+        assert_eq!(ExportPeriod::Hour.previous(at("2024-05-02T13:05:00Z")).2, "2024-05-02T12");
+    }
+
+    #[tokio::test]
+    async fn exports_the_previous_day_per_ticker() {
+        let directory = std::env::temp_dir().join(format!("news_data_export_{}", std::process::id()));
+        let storage = storage().await;
+
+        let (label, files, articles) = job("nightly", "parquet", true, &directory).export(storage.as_ref(), at("2024-05-02T00:30:00Z")).await.unwrap();
+        assert_eq!(label, "2024-05-01");
+        assert_eq!(files, vec!["nightly/2024-05-01/AMD.parquet", "nightly/2024-05-01/NVDA.parquet"]);
+        assert_eq!(articles, 2);
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(directory.join(&files[1])).unwrap()).unwrap().build().unwrap();
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, 2);
+
+        let (_, files, _) = job("lines", "jsonl", false, &directory).export(storage.as_ref(), at("2024-05-02T00:30:00Z")).await.unwrap();
+        let lines = fs::read_to_string(directory.join(&files[0])).unwrap();
+        let ids: Vec<String> = lines.lines().map(|line| serde_json::from_str::<Value>(line).unwrap()["id"].as_str().unwrap().to_string()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn records_runs_and_alerts_on_failure() {
+        // A file where the export directory should be: every write fails.
+        let blocked = std::env::temp_dir().join(format!("news_data_export_blocked_{}", std::process::id()));
+        fs::write(&blocked, "").unwrap();
+        let storage = storage().await;
+        let alerter = Alerter::new(Arc::new(Client::new()), AlertsConfig::default()).with_storage(storage.clone());
+
+        let run = job("nightly", "jsonl", false, &blocked).run(storage.as_ref(), &alerter, at("2024-05-02T00:30:00Z")).await;
+        assert_eq!(run.status, ExportStatus::Failed);
+        let run = job("nightly", "jsonl", false, &blocked).run(storage.as_ref(), &alerter, at("2024-05-02T00:30:00Z")).await;
+        assert_eq!(run.consecutive_failures, 2);
+        let alerts = storage.alerts(Some(AlertState::Fired)).await.unwrap();
+        assert_eq!((alerts[0].rule.as_str(), alerts[0].subject.as_str(), alerts[0].count), (FAILURE_RULE, "nightly", 2));
+        fs::remove_file(&blocked).unwrap();
+
+        let directory = std::env::temp_dir().join(format!("news_data_export_runs_{}", std::process::id()));
+        let run = job("nightly", "jsonl", false, &directory).run(storage.as_ref(), &alerter, at("2024-05-02T00:30:00Z")).await;
+        assert_eq!((run.status, run.consecutive_failures, run.articles), (ExportStatus::Succeeded, 0, 2));
+        assert_eq!(storage.export_runs().await.unwrap(), vec![run]);
+        assert!(storage.alerts(Some(AlertState::Fired)).await.unwrap().is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn exports_the_articles_stored_since_the_last_export() {
+        let directory = std::env::temp_dir().join(format!("news_data_export_since_last_{}", std::process::id()));
+        let storage = storage().await;
+        let export = StoredExport { directory: directory.clone(), format: ExportFormat::JsonLines, tickers: vec!["NVDA".to_string()], since_last: true };
+        let ids = |file: &str| -> Vec<String> {
+            let lines = fs::read_to_string(directory.join(file)).unwrap();
+            lines.lines().map(|line| serde_json::from_str::<Value>(line).unwrap()["id"].as_str().unwrap().to_string()).collect()
+        };
+
+        let watermark = export.run(storage.as_ref(), at("2024-05-02T00:30:00Z")).await.unwrap();
+        assert_eq!(watermark.file.as_deref(), Some("20240502T003000Z.jsonl"));
+        assert_eq!(ids("20240502T003000Z.jsonl"), vec!["1", "2", "3"]);
+        assert_eq!(storage.export_watermark(&watermark.destination).await.unwrap(), Some(watermark));
+
+        // Nothing new: no file.
+        let watermark = export.run(storage.as_ref(), at("2024-05-02T01:30:00Z")).await.unwrap();
+        assert_eq!((watermark.file, watermark.articles), (None, 0));
+
+        let mut article = storage.filtered_articles(&ArticleFilter::default(), 0).await.unwrap()[0].clone();
+        article.id = "5".to_string();
+        storage.store_articles(&[article]).await.unwrap();
+        let watermark = export.run(storage.as_ref(), at("2024-05-02T02:30:00Z")).await.unwrap();
+        assert_eq!(ids(watermark.file.as_deref().unwrap()), vec!["5"]);
+        assert_eq!(watermark.seq, Some(storage.last_seq().await.unwrap()));
+
+        // Without `since_last`, every stored article.
+        let export = StoredExport { since_last: false, ..export };
+        assert_eq!(export.run(storage.as_ref(), at("2024-05-02T03:30:00Z")).await.unwrap().articles, 4);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn exports_hive_partitions_with_a_manifest() {
+        let directory = std::env::temp_dir().join(format!("news_data_export_hive_{}", std::process::id()));
+        let storage = storage().await;
+        let mut config = ExportJobConfig {
+            name: "lake".to_string(),
+            format: "jsonl".to_string(),
+            period: "day".to_string(),
+            cron: Some("0 30 0 * * *".to_string()),
+            interval_secs: None,
+            per_ticker: false,
+            tickers: Vec::new(),
+            layout: "hive".to_string(),
+        };
+        assert!(ExportJob::new(&config, &directory).is_err());
+        config.format = "parquet".to_string();
+
+        let (_, files, _) = ExportJob::new(&config, &directory).unwrap().export(storage.as_ref(), at("2024-05-02T00:30:00Z")).await.unwrap();
+        assert_eq!(files, vec!["lake/provider=finnhub/date=2024-05-01/2024-05-01.parquet"]);
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(directory.join(&files[0])).unwrap()).unwrap().build().unwrap();
+        let batch = reader.map(|batch| batch.unwrap()).next().unwrap();
+        assert!(batch.schema().field_with_name("provider").is_err());
+        let published_at = batch.column_by_name("published_at").unwrap().as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
+        assert_eq!(published_at.value(0), at("2024-05-01T09:00:00Z").timestamp_micros());
+
+        let manifest: Value = serde_json::from_slice(&fs::read(directory.join("lake/_schema.json")).unwrap()).unwrap();
+        assert_eq!(manifest["columns"][1], json!({ "name": "published_at", "type": "TIMESTAMP WITH TIME ZONE", "nullable": true }));
+        assert_eq!(manifest["partitions"][1]["name"], "date");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
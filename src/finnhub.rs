@@ -0,0 +1,184 @@
+//! ## A Rust wrapper of the [Finnhub](https://finnhub.io) news API.
+//!
+//! Two endpoints are supported:
+//! - `company-news`: latest news of a company, by symbol and date range (North American companies).
+//! - `news`: latest market news, by category (`general`, `forex`, `crypto`, `merger`).
+//!
+//! ## Reference:
+//! [Official Finnhub Documentation](https://finnhub.io/docs/api/company-news).
+
+use std::sync::Arc;
+
+use chrono::Duration as UtcDuration;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{to_value, Value};
+use tokio::sync::Mutex;
+use tracing::{error, info, instrument, warn};
+
+use crate::api_calls;
+use crate::cache::SharedLockedCache;
+use crate::chaos;
+use crate::clock;
+use crate::config::ValueConfig;
+use crate::encoding::decode_json_response;
+use crate::errors::{ApiError, ProviderError};
+use crate::fixtures;
+use crate::lenient;
+use crate::normalize::FINNHUB_PROVIDER;
+use crate::options::FHQueryParams as QueryParams;
+use crate::options::FetchType;
+use crate::provider::{HealthTracker, NewsProvider, ProviderFuture, ProviderHealth};
+use crate::utils::{get_resp_value_from_cache_or_fetch, retry};
+
+const BASE_URL: &str = "https://finnhub.io/api/v1";
+pub const COMPANY_NEWS_ENDPOINT: &str = "company-news";
+pub const MARKET_NEWS_ENDPOINT: &str = "news";
+pub const PROVIDER_NAME: &str = FINNHUB_PROVIDER;
+const DEFAULT_CATEGORY: &str = "general";
+const ENDPOINT_MAP_KEY: &str = "endpoint";
+const TOKEN_MAP_KEY: &str = "token";
+
+/// An article of the `company-news` and `news` endpoints.
+///
+/// [See example here](https://finnhub.io/docs/api/market-news).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FinnhubArticle {
+    pub category: Option<String>,
+    /// Publication time, as a UNIX timestamp.
+    #[serde(default, deserialize_with = "lenient::opt_number")]
+    pub datetime: Option<i64>,
+    pub headline: Option<String>,
+    #[serde(default, deserialize_with = "lenient::opt_number")]
+    pub id: Option<i64>,
+    pub image: Option<String>,
+    /// Comma separated symbols the article relates to.
+    pub related: Option<String>,
+    pub source: Option<String>,
+    pub summary: Option<String>,
+    pub url: Option<String>,
+}
+
+pub struct FinnhubApiClient {
+    client: Arc<Client>,
+    cache: Arc<Mutex<SharedLockedCache>>,
+    config: Arc<ValueConfig>,
+    health: HealthTracker,
+}
+impl FinnhubApiClient {
+    pub fn new(client: Arc<Client>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Self {
+        Self { client, cache, config, health: HealthTracker::default() }
+    }
+
+    async fn get(&self, endpoint: &str, query_params: QueryParams) -> Result<Value, ApiError> {
+        let key = format!("finnhub_{}_{:?}", endpoint, &query_params);
+        get_resp_value_from_cache_or_fetch(
+            &self.cache,
+            &key,
+            || async { self.get_(endpoint, query_params).await },
+            self.config.task.cache_ttl,
+        ).await
+        .inspect_err(|_| warn!("Finnhub client encountered an error during GET request."))
+    }
+
+    async fn get_(&self, endpoint: &str, query_params: QueryParams) -> Result<Value, ApiError> {
+        let response = api_calls::send(PROVIDER_NAME, self.client.get(format!("{}/{}", BASE_URL, endpoint)).query(&query_params)).await?;
+
+        let status = response.status();
+        if status != StatusCode::OK {
+            return Err(api_calls::status_error(response).await);
+        }
+
+        let body = decode_json_response(PROVIDER_NAME, response).await
+            .inspect_err(|e| error!("Failed to read body: {}", e))?;
+        fixtures::record(PROVIDER_NAME, endpoint, &body);
+        let body = chaos::response_body(PROVIDER_NAME, body);
+        let articles: Vec<FinnhubArticle> = serde_json::from_value(body).map_err(|e| {
+            error!("Failed to parse body: {:?}", e);
+            ApiError::JsonParseError { message: e.to_string() }
+        })?;
+
+        to_value(articles).map_err(|e| ApiError::JsonParseError { message: e.to_string() })
+    }
+
+    /// Inserts the API token and pops the endpoint from the request `args`.
+    fn prepare_args(&self, args: Arc<Value>) -> (String, Value) {
+        let mut value = Arc::try_unwrap(args).unwrap_or_else(|v| (*v).clone());
+        let mut endpoint = MARKET_NEWS_ENDPOINT.to_string();
+        if let Value::Object(ref mut map) = value {
+            if let Some(Value::String(e)) = map.remove(ENDPOINT_MAP_KEY) {
+                endpoint = e;
+            }
+            map.insert(TOKEN_MAP_KEY.to_string(), Value::String(self.config.api.finnhub.clone()));
+        }
+        (endpoint, value)
+    }
+
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME, function = args["function"].as_str()))]
+    pub async fn poll(&self, args: Arc<Value>) -> Result<Value, ApiError> {
+        let (endpoint, args) = self.prepare_args(args);
+        if endpoint != COMPANY_NEWS_ENDPOINT && endpoint != MARKET_NEWS_ENDPOINT {
+            error!("Unsupported Finnhub endpoint: {}", endpoint);
+            return Err(ApiError::NoEndpointProvided);
+        }
+        let query_params = QueryParams::try_from(args)?;
+        let response = retry(PROVIDER_NAME, &self.config, || async {
+            self.get(&endpoint, query_params.clone()).await
+        }).await?;
+        info!("API GET Response was successful? : {:?}", !response.is_null());
+        Ok(response)
+    }
+
+    /// Fetches the latest `general` market news.
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME))]
+    pub async fn latest(&self) -> Result<Value, ApiError> {
+        let query = QueryParams::new(&self.config.api.finnhub, None, None, None, Some(DEFAULT_CATEGORY), None);
+        self.get_(MARKET_NEWS_ENDPOINT, query).await
+            .inspect_err(|e| error!("Error during GET request: {}", e))
+    }
+
+    /// Fetches the news of `symbol` published during the last `days` days.
+    pub async fn company_news(&self, symbol: &str, days: i64) -> Result<Value, ApiError> {
+        let to = clock::now();
+        let from = to - UtcDuration::days(days);
+        let query = QueryParams::new(
+            &self.config.api.finnhub,
+            Some(symbol),
+            Some(&from.format("%Y-%m-%d").to_string()),
+            Some(&to.format("%Y-%m-%d").to_string()),
+            None,
+            None,
+        );
+        self.get(COMPANY_NEWS_ENDPOINT, query).await
+    }
+}
+
+impl NewsProvider for FinnhubApiClient {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn supports(&self, fetch_type: &FetchType) -> bool {
+        matches!(fetch_type, FetchType::Finnhub)
+    }
+
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.poll(args).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.latest().await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn health(&self) -> ProviderHealth {
+        self.health.health(&self.config.api.finnhub)
+    }
+}
@@ -0,0 +1,642 @@
+//! Golden provider response fixtures.
+//!
+//! Representative responses for each provider and endpoint live under `fixtures/<provider>/<endpoint>.json`
+//! and are checked against the typed models by the contract tests below, so a model change that silently
+//! drops a field (or a provider renaming one) shows up as a failing test rather than as missing data.
+//!
+//! ## Refreshing fixtures
+//!
+//! `news_data record-fixtures [--dir fixtures] [--providers a,b]` sends the requests of `REFRESH_REQUESTS`
+//! with the configured API keys and writes every decoded response to `<dir>/<provider>/<endpoint>.json`,
+//! see `fixtures/README.md`. Setting `NEWS_DATA_RECORD_FIXTURES` to a directory records the responses
+//! of a running service the same way. API keys and tokens are scrubbed from the recorded bodies; review
+//! the diff before committing, recorded bodies are real provider data.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::provider::NewsProvider;
+
+/// Environment variable enabling record mode. Its value is the target directory.
+pub const RECORD_FIXTURES_ENV: &str = "NEWS_DATA_RECORD_FIXTURES";
+
+/// Keys (and query parameters) whose values are credentials.
+const SECRET_KEYS: [&str; 5] = ["apikey", "api_key", "api_token", "token", "access_token"];
+
+/// Replaces the scrubbed values.
+const REDACTED: &str = "REDACTED";
+
+/// Placeholder of the `args` of a `RefreshRequest` replaced by the uuid of the first recorded MarketAux article.
+const UUID_PLACEHOLDER: &str = "{uuid}";
+
+/// Directory set by `refresh`, taking precedence over `RECORD_FIXTURES_ENV`.
+static RECORD_DIR: OnceLock<String> = OnceLock::new();
+
+/// A request whose response is recorded as a fixture.
+pub struct RefreshRequest {
+    /// Fixture written, relative to the fixtures directory.
+    pub fixture: &'static str,
+    pub provider: &'static str,
+    /// Request args, as sent by websocket clients.
+    pub args: &'static str,
+    /// Sent to the legacy FMP endpoints (`fmp.prefer_stable` off) rather than the `stable` ones.
+    pub legacy: bool,
+}
+
+const fn request(fixture: &'static str, provider: &'static str, args: &'static str) -> RefreshRequest {
+    RefreshRequest { fixture, provider, args, legacy: false }
+}
+
+const fn legacy(fixture: &'static str, provider: &'static str, args: &'static str) -> RefreshRequest {
+    RefreshRequest { fixture, provider, args, legacy: true }
+}
+
+/// The requests producing the fixtures checked by the contract tests, in order.
+pub const REFRESH_REQUESTS: &[RefreshRequest] = &[
+    request("marketaux/all.json", "marketaux", r#"{"fetch_type": "marketaux", "endpoint": "all", "symbols": "TSLA", "limit": 3}"#),
+    request("marketaux/similar.json", "marketaux", r#"{"fetch_type": "marketaux", "endpoint": "similar", "uuid": "{uuid}", "limit": 3}"#),
+    request("marketaux/uuid.json", "marketaux", r#"{"fetch_type": "marketaux", "endpoint": "uuid", "uuid": "{uuid}"}"#),
+    request("marketaux/entity_stats_aggregation.json", "marketaux", r#"{"fetch_type": "marketaux_entity_stats", "endpoint": "aggregation", "symbols": "TSLA,AAPL"}"#),
+    request("marketaux/entity_stats_intraday.json", "marketaux", r#"{"fetch_type": "marketaux_entity_stats", "endpoint": "intraday", "symbols": "TSLA"}"#),
+    request("marketaux/news_sources.json", "marketaux", r#"{"fetch_type": "marketaux_sources", "language": "en"}"#),
+    request("alphavantage/news_sentiment.json", "alphavantage", r#"{"fetch_type": "alphavantage", "tickers": "NVDA", "limit": "2"}"#),
+    request("alphavantage/top_gainers_losers.json", "alphavantage", r#"{"fetch_type": "alphavantage_top_movers"}"#),
+    request("alphavantage/insider_transactions.json", "alphavantage", r#"{"fetch_type": "alphavantage_insider_transactions", "symbol": "IBM"}"#),
+    legacy("fmp/fmp_articles.json", "fmp", r#"{"function": "fmp articles", "page": 0, "size": 1}"#),
+    legacy("fmp/stock_news.json", "fmp", r#"{"function": "stock news", "tickers": "AAPL", "size": 2}"#),
+    request("fmp/news_stock.json", "fmp", r#"{"function": "stock news", "tickers": "AAPL", "size": 2}"#),
+    legacy("fmp/stock-news-sentiments-rss-feed.json", "fmp", r#"{"function": "stock rss", "page": 0}"#),
+    legacy("fmp/mergers-acquisitions-rss-feed.json", "fmp", r#"{"function": "mergers acquisitions", "page": 0}"#),
+    request("fmp/mergers-acquisitions-latest.json", "fmp", r#"{"function": "mergers acquisitions", "page": 0}"#),
+    legacy("fmp/earning_call_transcript_AAPL.json", "fmp", r#"{"function": "earnings transcript", "symbol": "AAPL", "year": 2024, "quarter": 3}"#),
+    legacy("fmp/historical_social-sentiment.json", "fmp", r#"{"function": "social sentiment history", "symbol": "AAPL", "page": 0}"#),
+    legacy("fmp/social-sentiments_trending.json", "fmp", r#"{"function": "social sentiment trending"}"#),
+    legacy("fmp/social-sentiments_change.json", "fmp", r#"{"function": "social sentiment changes", "type_name": "bullish", "source": "stocktwits"}"#),
+    request("finnhub/news.json", "finnhub", r#"{"endpoint": "news", "category": "general"}"#),
+    request("polygon/v2_reference_news.json", "polygon", r#"{"ticker": "AAPL", "limit": 2}"#),
+    request("gdelt/doc.json", "gdelt", r#"{"query": "inflation sourcelang:english", "maxrecords": 3, "timespan": "1d"}"#),
+];
+
+/// Writes `body` as the fixture of `provider`/`endpoint` when record mode is enabled.
+///
+/// Failures are logged and otherwise ignored: recording must never break a fetch.
+pub fn record(provider: &str, endpoint: &str, body: &Value) {
+    let Some(dir) = RECORD_DIR.get().cloned().or_else(|| std::env::var(RECORD_FIXTURES_ENV).ok()) else {
+        return;
+    };
+    let path = fixture_path(&dir, provider, endpoint);
+    let mut body = body.clone();
+    scrub(&mut body);
+    let result = path
+        .parent()
+        .map(fs::create_dir_all)
+        .transpose()
+        .and_then(|_| {
+            serde_json::to_string_pretty(&body)
+                .map_err(std::io::Error::other)
+                .and_then(|json| fs::write(&path, json + "\n"))
+        });
+    match result {
+        Ok(_) => debug!("Recorded fixture {}", path.display()),
+        Err(e) => warn!("Failed to record fixture {}: {}", path.display(), e),
+    }
+}
+
+/// Location of a fixture: endpoint path separators are flattened into the file name.
+pub fn fixture_path(dir: &str, provider: &str, endpoint: &str) -> PathBuf {
+    let name = endpoint.trim_matches('/').replace('/', "_");
+    let name = if name.is_empty() { "index".to_string() } else { name };
+    PathBuf::from(dir).join(provider).join(format!("{}.json", name))
+}
+
+fn is_secret(key: &str) -> bool {
+    SECRET_KEYS.iter().any(|secret| secret.eq_ignore_ascii_case(key))
+}
+
+/// Redacts the credentials of `value`: the values of secret keys and the secret query parameters of
+/// the strings (providers echo request URLs in `next_url` and error messages).
+pub fn scrub(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if is_secret(key) {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    scrub(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(scrub),
+        Value::String(text) if text.contains('=') => *text = scrub_query(text),
+        _ => {}
+    }
+}
+
+fn scrub_query(text: &str) -> String {
+    text.split_inclusive(['?', '&'])
+        .enumerate()
+        .map(|(i, piece)| {
+            let (param, separator) = match piece.char_indices().last() {
+                Some((at, '?' | '&')) => piece.split_at(at),
+                _ => (piece, ""),
+            };
+            match param.split_once('=') {
+                Some((name, _)) if i > 0 && is_secret(name) => format!("{}={}{}", name, REDACTED, separator),
+                _ => piece.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Sends the `REFRESH_REQUESTS` of `only` (all of them when empty) and records their responses under `dir`.
+///
+/// `providers` serve the requests to the `stable` FMP endpoints, `legacy_providers` the other ones.
+/// Returns the outcome of each request, keyed by fixture.
+pub async fn refresh(
+    dir: &str,
+    providers: &[Arc<dyn NewsProvider>],
+    legacy_providers: &[Arc<dyn NewsProvider>],
+    only: &[String],
+) -> Vec<(&'static str, Result<(), String>)> {
+    if RECORD_DIR.set(dir.to_string()).is_err() {
+        warn!("Fixtures are already recorded to {:?}", RECORD_DIR.get());
+    }
+    let mut uuid = None;
+    let mut outcomes = Vec::new();
+    for request in REFRESH_REQUESTS.iter().filter(|r| only.is_empty() || only.iter().any(|p| p == r.provider)) {
+        let providers = if request.legacy { legacy_providers } else { providers };
+        let outcome = send(request, providers, uuid.as_deref()).await;
+        if let Ok(response) = &outcome {
+            if request.fixture == "marketaux/all.json" {
+                uuid = response["data"][0]["uuid"].as_str().map(str::to_string);
+            }
+        }
+        info!("Refreshed {}: {}", request.fixture, if outcome.is_ok() { "ok" } else { "failed" });
+        outcomes.push((request.fixture, outcome.map(|_| ())));
+    }
+    outcomes
+}
+
+async fn send(request: &RefreshRequest, providers: &[Arc<dyn NewsProvider>], uuid: Option<&str>) -> Result<Value, String> {
+    let provider = providers
+        .iter()
+        .find(|p| p.name() == request.provider)
+        .ok_or_else(|| format!("Unknown provider `{}`", request.provider))?;
+    let args = match (request.args.contains(UUID_PLACEHOLDER), uuid) {
+        (false, _) => request.args.to_string(),
+        (true, Some(uuid)) => request.args.replace(UUID_PLACEHOLDER, uuid),
+        (true, None) => return Err("No MarketAux article uuid, refresh `marketaux/all.json` first".to_string()),
+    };
+    let args: Value = serde_json::from_str(&args).map_err(|e| e.to_string())?;
+    provider.fetch(Arc::new(args)).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use serde_json::Value;
+
+    use crate::alphavantage::AlphaVantageApiResponse;
+    use crate::fmp::Content;
+    use crate::marketaux::MarketAuxResponse;
+    use crate::server_types::{FMPArticle, FMPMarketSentiment};
+
+    const MARKETAUX_ALL: &str = include_str!("../fixtures/marketaux/all.json");
+    const MARKETAUX_SIMILAR: &str = include_str!("../fixtures/marketaux/similar.json");
+    const MARKETAUX_UUID: &str = include_str!("../fixtures/marketaux/uuid.json");
+    const MARKETAUX_STATS: &str = include_str!("../fixtures/marketaux/entity_stats_aggregation.json");
+    const MARKETAUX_STATS_INTRADAY: &str = include_str!("../fixtures/marketaux/entity_stats_intraday.json");
+    const MARKETAUX_SOURCES: &str = include_str!("../fixtures/marketaux/news_sources.json");
+    const ALPHAVANTAGE_NEWS_SENTIMENT: &str = include_str!("../fixtures/alphavantage/news_sentiment.json");
+    const ALPHAVANTAGE_TOP_MOVERS: &str = include_str!("../fixtures/alphavantage/top_gainers_losers.json");
+    const ALPHAVANTAGE_INSIDERS: &str = include_str!("../fixtures/alphavantage/insider_transactions.json");
+    const FMP_ARTICLES: &str = include_str!("../fixtures/fmp/fmp_articles.json");
+    const FMP_STOCK_NEWS: &str = include_str!("../fixtures/fmp/stock_news.json");
+    const FMP_STABLE_STOCK_NEWS: &str = include_str!("../fixtures/fmp/news_stock.json");
+    const FMP_STOCK_RSS: &str = include_str!("../fixtures/fmp/stock-news-sentiments-rss-feed.json");
+    const FMP_MERGERS: &str = include_str!("../fixtures/fmp/mergers-acquisitions-rss-feed.json");
+    const FMP_STABLE_MERGERS: &str = include_str!("../fixtures/fmp/mergers-acquisitions-latest.json");
+    const FMP_TRANSCRIPT: &str = include_str!("../fixtures/fmp/earning_call_transcript_AAPL.json");
+    const FMP_SOCIAL_HISTORY: &str = include_str!("../fixtures/fmp/historical_social-sentiment.json");
+    const FMP_SOCIAL_TRENDING: &str = include_str!("../fixtures/fmp/social-sentiments_trending.json");
+    const FMP_SOCIAL_CHANGES: &str = include_str!("../fixtures/fmp/social-sentiments_change.json");
+    const FINNHUB_NEWS: &str = include_str!("../fixtures/finnhub/news.json");
+    const POLYGON_NEWS: &str = include_str!("../fixtures/polygon/v2_reference_news.json");
+    const GDELT_DOC: &str = include_str!("../fixtures/gdelt/doc.json");
+
+    fn fixture(raw: &str) -> Value {
+        serde_json::from_str(raw).expect("fixture is not valid JSON")
+    }
+
+    /// Number of top-level keys carrying a non-null value.
+    fn populated_keys(value: &Value) -> usize {
+        value
+            .as_object()
+            .map(|map| map.values().filter(|v| !v.is_null()).count())
+            .unwrap_or(0)
+    }
+
+    /// Parses `raw` into `T`, serializes it back and parses that again, asserting both
+    /// serializations are identical and that no populated field was lost on the way in.
+    fn assert_round_trip<T: DeserializeOwned + Serialize>(raw: &Value) -> T {
+        let model: T = serde_json::from_value(raw.clone()).expect("fixture does not match the model");
+        let once = serde_json::to_value(&model).unwrap();
+        let twice = serde_json::to_value(serde_json::from_value::<T>(once.clone()).unwrap()).unwrap();
+        assert_eq!(once, twice, "model does not round-trip");
+        assert_eq!(
+            populated_keys(raw),
+            populated_keys(&once),
+            "model drops fields present in the fixture: {} vs {}",
+            raw,
+            once
+        );
+        model
+    }
+
+    fn assert_items_round_trip<T: DeserializeOwned + Serialize>(raw: &Value) -> Vec<T> {
+        raw.as_array()
+            .expect("fixture is not an array")
+            .iter()
+            .map(assert_round_trip::<T>)
+            .collect()
+    }
+
+    #[test]
+    fn marketaux_all_matches_model() {
+        let raw = fixture(MARKETAUX_ALL);
+        let response: MarketAuxResponse = serde_json::from_value(raw.clone()).unwrap();
+        assert_eq!(response.meta.returned as usize, response.data.len());
+        assert_eq!(response.meta.pages_after(), 24_106);
+        assert_eq!(response.to_json().unwrap(), serde_json::to_value(&response).unwrap());
+        assert_items_round_trip::<crate::marketaux::NewsItem>(&raw["data"]);
+        for item in raw["data"].as_array().unwrap() {
+            assert_items_round_trip::<crate::marketaux::Entity>(&item["entities"]);
+        }
+
+        // An industry shared by several entities is a single topic.
+        let mut item = response.data[0].clone();
+        item.entities.extend(response.data[1].entities.iter().chain(&response.data[0].entities).cloned());
+        let topics = crate::normalize::NormalizedArticle::from(&item).topics;
+        assert_eq!(topics, ["Communication Services", "Consumer Cyclical"]);
+    }
+
+    #[test]
+    fn marketaux_similar_and_uuid_match_model() {
+        let raw = fixture(MARKETAUX_SIMILAR);
+        let response: MarketAuxResponse = assert_round_trip(&raw);
+        assert_eq!(response.meta.returned as usize, response.data.len());
+
+        let item: crate::marketaux::NewsItem = assert_round_trip(&fixture(MARKETAUX_UUID));
+        assert_eq!(item.uuid.as_deref(), Some("2a4c1a3e-58c4-4b8e-9b7b-6c1d4d2f0a11"));
+    }
+
+    #[test]
+    fn marketaux_entity_stats_and_sources_match_model() {
+        use crate::marketaux::{EntityStatsResponse, IntradayEntityStatsResponse, SourcesResponse};
+
+        let stats: EntityStatsResponse = assert_round_trip(&fixture(MARKETAUX_STATS));
+        assert_eq!(stats.meta.returned as usize, stats.data.len());
+        let intraday: IntradayEntityStatsResponse = assert_round_trip(&fixture(MARKETAUX_STATS_INTRADAY));
+        assert_eq!(intraday.data[0].data[0].key, "TSLA");
+        let sources: SourcesResponse = assert_round_trip(&fixture(MARKETAUX_SOURCES));
+        assert_eq!(sources.meta.returned as usize, sources.data.len());
+    }
+
+    #[test]
+    fn marketaux_endpoints_carry_the_uuid() {
+        use crate::marketaux::MarketAuxEndpoint;
+
+        let uuid = Some("2a4c1a3e");
+        assert_eq!(MarketAuxEndpoint::from_args("", None).unwrap().path(), "all");
+        assert_eq!(MarketAuxEndpoint::from_args("similar", uuid).unwrap().path(), "similar/2a4c1a3e");
+        assert_eq!(MarketAuxEndpoint::from_args("uuid", uuid).unwrap().path(), "uuid/2a4c1a3e");
+        assert!(MarketAuxEndpoint::from_args("similar", None).is_err());
+        assert!(MarketAuxEndpoint::from_args("sources", None).is_err());
+    }
+
+    #[test]
+    fn pagination_follow_is_opt_in() {
+        use crate::options::{FMPQueryParams, MAQueryParams, DEFAULT_MAX_PAGES};
+
+        let fmp = FMPQueryParams::from(serde_json::json!({ "page": 0 }));
+        assert_eq!(fmp.pages_to_fetch(), 1);
+        let fmp = FMPQueryParams::from(serde_json::json!({ "fetch_all_pages": true }));
+        assert_eq!(fmp.pages_to_fetch(), DEFAULT_MAX_PAGES);
+        let params: Option<Vec<(String, String)>> = fmp.with_page(3).into();
+        assert_eq!(params, Some(vec![("page".to_string(), "3".to_string())]));
+
+        let ma = MAQueryParams::try_from(serde_json::json!({ "api_token": "t", "fetch_all_pages": true, "max_pages": 3 })).unwrap();
+        assert_eq!(ma.pages_to_fetch(), 3);
+        // Not sent as query parameters.
+        let query = serde_json::to_value(&ma).unwrap();
+        assert!(query.get("fetch_all_pages").is_none() && query.get("max_pages").is_none());
+    }
+
+    #[test]
+    fn alphavantage_news_sentiment_matches_model() {
+        let raw = fixture(ALPHAVANTAGE_NEWS_SENTIMENT);
+        let response: AlphaVantageApiResponse = assert_round_trip(&raw);
+        assert_eq!(response.items.as_deref(), Some("2"));
+        let feed = assert_items_round_trip::<crate::alphavantage::FeedItem>(&raw["feed"]);
+        assert_eq!(feed.len(), 2);
+        assert!(feed.iter().all(|item| item.time_published.is_some()));
+    }
+
+    #[test]
+    fn fmp_articles_match_model() {
+        let raw = fixture(FMP_ARTICLES);
+        match Content::from(raw["content"].clone()) {
+            Content::News(articles) => assert_eq!(articles.len(), 1),
+            other => panic!("expected news content, got {:?}", other),
+        }
+        assert_items_round_trip::<FMPArticle>(&raw["content"]);
+    }
+
+    #[test]
+    fn fmp_news_feeds_match_model() {
+        use crate::normalize::NormalizedArticle;
+
+        let legacy = assert_items_round_trip::<FMPArticle>(&fixture(FMP_STOCK_NEWS));
+        assert_items_round_trip::<FMPArticle>(&fixture(FMP_STOCK_RSS));
+        let stable = assert_items_round_trip::<FMPArticle>(&fixture(FMP_STABLE_STOCK_NEWS));
+        assert_eq!(stable[0].author.as_deref(), Some("CNBC"));
+        // Publication times are New York time: EDT in August, EST in February.
+        assert_eq!(NormalizedArticle::from(&legacy[0]).published_at.as_deref(), Some("2024-08-07T17:30:00Z"));
+        assert_eq!(NormalizedArticle::from(&stable[0]).published_at.as_deref(), Some("2025-02-04T04:51:37Z"));
+    }
+
+    #[test]
+    fn alphavantage_intelligence_matches_model() {
+        let raw = fixture(ALPHAVANTAGE_TOP_MOVERS);
+        let movers = assert_round_trip::<crate::alphavantage::TopMoversResponse>(&raw);
+        assert_eq!(movers.top_gainers[0].ticker, "NVNI");
+        assert_items_round_trip::<crate::alphavantage::Mover>(&raw["most_actively_traded"]);
+
+        let raw = fixture(ALPHAVANTAGE_INSIDERS);
+        assert_round_trip::<crate::alphavantage::InsiderTransactionsResponse>(&raw);
+        assert_items_round_trip::<crate::alphavantage::InsiderTransaction>(&raw["data"]);
+
+        let csv = "symbol,name,reportDate,fiscalDateEnding,estimate,currency\r\n\
+            IBM,International Business Machines Corp,2024-07-24,2024-06-30,2.17,USD\r\n\
+            IBMD,iShares iBonds Dec 2024 Term Muni Bond ETF,2024-07-25,2024-06-30,,USD\r\n";
+        let calendar = crate::alphavantage::parse_earnings_calendar(csv).unwrap();
+        assert_eq!(calendar.len(), 2);
+        assert_eq!(calendar[0].report_date.as_deref(), Some("2024-07-24"));
+        assert_eq!(calendar[1].estimate, None);
+        assert!(crate::alphavantage::parse_earnings_calendar(r#"{"Information": "rate limit"}"#).is_err());
+    }
+
+    #[test]
+    fn fmp_mergers_acquisitions_match_model() {
+        let deals = assert_items_round_trip::<crate::server_types::FMPMergerAcquisition>(&fixture(FMP_MERGERS));
+        assert_eq!(deals[0].targeted_symbol.as_deref(), Some("ANSS"));
+        let stable = assert_items_round_trip::<crate::server_types::FMPMergerAcquisition>(&fixture(FMP_STABLE_MERGERS));
+        assert!(stable[0].acceptance_time.is_some() && stable[0].url.is_some());
+    }
+
+    #[test]
+    fn fmp_earnings_transcript_matches_model() {
+        let transcripts = assert_items_round_trip::<crate::server_types::Transcript>(&fixture(FMP_TRANSCRIPT));
+        assert_eq!(transcripts[0].quarter, Some(3));
+        assert!(transcripts[0].content.as_deref().is_some_and(|c| c.lines().count() == 2));
+    }
+
+    #[test]
+    fn fmp_social_sentiment_matches_model() {
+        assert_items_round_trip::<FMPMarketSentiment>(&fixture(FMP_SOCIAL_HISTORY));
+        assert_items_round_trip::<FMPMarketSentiment>(&fixture(FMP_SOCIAL_TRENDING));
+        assert_items_round_trip::<FMPMarketSentiment>(&fixture(FMP_SOCIAL_CHANGES));
+    }
+
+    #[test]
+    fn finnhub_news_matches_model() {
+        use crate::finnhub::FinnhubArticle;
+        use crate::normalize::NormalizedArticle;
+
+        let articles = assert_items_round_trip::<FinnhubArticle>(&fixture(FINNHUB_NEWS));
+        let normalized: Vec<NormalizedArticle> = articles.iter().map(NormalizedArticle::from).collect();
+        assert_eq!(normalized[0].published_at.as_deref(), Some("2024-08-07T13:31:00Z"));
+        assert!(normalized[0].tickers.is_empty());
+        assert_eq!(normalized[1].tickers, vec!["AAPL", "TSM"]);
+        assert_eq!(normalized[1].id, "129344517");
+    }
+
+    #[test]
+    fn polygon_news_matches_model() {
+        use crate::normalize::NormalizedArticle;
+        use crate::polygon::PolygonNewsResponse;
+
+        let raw = fixture(POLYGON_NEWS);
+        let page: PolygonNewsResponse = assert_round_trip(&raw);
+        assert_eq!(page.next_cursor().as_deref(), Some("YXA9MjAyNC0wOC0wN1QxMiUzQTAwJTNBMDBa"));
+        assert_items_round_trip::<crate::polygon::PolygonArticle>(&raw["results"]);
+
+        let article = NormalizedArticle::from(&page.results[0]);
+        assert_eq!(article.published_at.as_deref(), Some("2024-08-07T14:05:00Z"));
+        assert_eq!(article.source.as_deref(), Some("The Motley Fool"));
+        assert_eq!(article.sentiment_score, Some(0.5));
+    }
+
+    #[test]
+    fn gdelt_doc_matches_model() {
+        use crate::gdelt::{GdeltArticle, GdeltResponse};
+        use crate::normalize::NormalizedArticle;
+
+        let raw = fixture(GDELT_DOC);
+        let response: GdeltResponse = assert_round_trip(&raw);
+        assert_items_round_trip::<GdeltArticle>(&raw["articles"]);
+
+        let article = NormalizedArticle::from(&response.articles[1]);
+        assert_eq!(article.published_at.as_deref(), Some("2024-08-07T13:30:00Z"));
+        assert_eq!(article.source.as_deref(), Some("lemonde.fr"));
+        assert_eq!(article.language.as_deref(), Some("fr"));
+    }
+
+    mod lenient_payloads {
+        //! Provider payloads are messier than their documentation: fields go missing or come as
+        //! `null`, numbers come as strings and undocumented fields appear. These generators derive
+        //! such permutations from the fixtures and check the models (and the normalization run on
+        //! them before storage) take them in stride.
+
+        use proptest::prelude::*;
+        use serde::de::DeserializeOwned;
+        use serde::Serialize;
+        use serde_json::{Map, Number, Value};
+
+        use super::{fixture, FMPArticle, FMPMarketSentiment, ALPHAVANTAGE_NEWS_SENTIMENT, FINNHUB_NEWS, FMP_SOCIAL_HISTORY,
+            FMP_SOCIAL_TRENDING, FMP_STOCK_RSS, MARKETAUX_ALL};
+        use crate::alphavantage::FeedItem;
+        use crate::finnhub::FinnhubArticle;
+        use crate::marketaux::NewsItem;
+        use crate::normalize::{parse_timestamp, NormalizedArticle};
+
+        /// Renderings of `n` as a string a provider may send.
+        fn number_text(n: &Number) -> BoxedStrategy<Value> {
+            let plain = n.to_string();
+            let mut texts = vec![plain.clone(), format!(" {} ", plain)];
+            if let Some(f) = n.as_f64() {
+                texts.push(format!("{:e}", f));
+            }
+            if n.is_i64() || n.is_u64() {
+                texts.push(format!("{}.0", plain));
+            }
+            prop::sample::select(texts).prop_map(Value::String).boxed()
+        }
+
+        /// Unknown fields, which the models must ignore.
+        fn extra_fields() -> impl Strategy<Value = Vec<(String, Value)>> {
+            let value = prop_oneof![
+                Just(Value::Null),
+                any::<bool>().prop_map(Value::Bool),
+                any::<i64>().prop_map(Value::from),
+                "[a-z ]{0,12}".prop_map(Value::String),
+                Just(serde_json::json!({ "nested": [1, "two"] })),
+            ];
+            prop::collection::vec(("extra_[a-z]{1,8}", value), 0..3)
+        }
+
+        /// Permutations of `value` with its numbers as strings and, if `lossy`, fields dropped,
+        /// set to `null` or added, all the way down.
+        fn messy(value: &Value, lossy: bool) -> BoxedStrategy<Value> {
+            match value {
+                Value::Object(map) => {
+                    let fields: Vec<BoxedStrategy<Option<(String, Value)>>> = map
+                        .iter()
+                        .map(|(key, field)| {
+                            let (kept, nulled) = (key.clone(), key.clone());
+                            let kept = messy(field, lossy).prop_map(move |v| Some((kept.clone(), v)));
+                            if lossy {
+                                prop_oneof![
+                                    6 => kept,
+                                    1 => Just(None),
+                                    1 => Just(Some((nulled, Value::Null))),
+                                ]
+                                .boxed()
+                            } else {
+                                kept.boxed()
+                            }
+                        })
+                        .collect();
+                    let extra = if lossy { extra_fields().boxed() } else { Just(Vec::new()).boxed() };
+                    (fields, extra)
+                        .prop_map(|(fields, extra)| {
+                            Value::Object(fields.into_iter().flatten().chain(extra).collect::<Map<_, _>>())
+                        })
+                        .boxed()
+                }
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| messy(item, lossy))
+                    .collect::<Vec<_>>()
+                    .prop_map(Value::Array)
+                    .boxed(),
+                Value::Number(n) => prop_oneof![1 => Just(value.clone()), 2 => number_text(n)].boxed(),
+                other => Just(other.clone()).boxed(),
+            }
+        }
+
+        /// An item of the array `items` along with a messed up permutation of it.
+        fn messy_items(items: Value, lossy: bool) -> BoxedStrategy<(Value, Value)> {
+            let items = items.as_array().cloned().expect("fixture is not an array");
+            prop::sample::select(items)
+                .prop_flat_map(move |item| (Just(item.clone()), messy(&item, lossy)))
+                .boxed()
+        }
+
+        fn parse<T: DeserializeOwned>(value: &Value) -> Result<T, TestCaseError> {
+            serde_json::from_value(value.clone())
+                .map_err(|e| TestCaseError::fail(format!("{} does not parse: {}", value, e)))
+        }
+
+        /// `messy` reads the same as the `original` it was derived from.
+        fn assert_reads_the_same<T: DeserializeOwned + Serialize>((original, messy): &(Value, Value)) -> Result<(), TestCaseError> {
+            let expected = serde_json::to_value(parse::<T>(original)?).unwrap();
+            prop_assert_eq!(serde_json::to_value(parse::<T>(messy)?).unwrap(), expected);
+            Ok(())
+        }
+
+        /// Normalization never fails on what parsed and keeps `published_at` a valid timestamp.
+        fn assert_normalizes(article: NormalizedArticle) -> Result<(), TestCaseError> {
+            if let Some(published_at) = &article.published_at {
+                prop_assert!(parse_timestamp(published_at).is_some(), "bad timestamp {}", published_at);
+            }
+            Ok(())
+        }
+
+        proptest! {
+            #[test]
+            fn marketaux_items_survive_messy_payloads((_, item) in messy_items(fixture(MARKETAUX_ALL)["data"].clone(), true)) {
+                assert_normalizes(NormalizedArticle::from(&parse::<NewsItem>(&item)?))?;
+            }
+
+            #[test]
+            fn marketaux_meta_survives_messy_payloads(meta in messy(&fixture(MARKETAUX_ALL)["meta"], true)) {
+                parse::<crate::marketaux::Meta>(&meta)?;
+            }
+
+            #[test]
+            fn alphavantage_items_survive_messy_payloads((_, item) in messy_items(fixture(ALPHAVANTAGE_NEWS_SENTIMENT)["feed"].clone(), true)) {
+                assert_normalizes(NormalizedArticle::from(&parse::<FeedItem>(&item)?))?;
+            }
+
+            #[test]
+            fn fmp_items_survive_messy_payloads((_, item) in messy_items(fixture(FMP_STOCK_RSS), true)) {
+                assert_normalizes(NormalizedArticle::from(&parse::<FMPArticle>(&item)?))?;
+            }
+
+            #[test]
+            fn fmp_social_sentiment_survives_messy_payloads((_, item) in messy_items(fixture(FMP_SOCIAL_HISTORY), true)) {
+                parse::<FMPMarketSentiment>(&item)?;
+            }
+
+            #[test]
+            fn finnhub_items_survive_messy_payloads((_, item) in messy_items(fixture(FINNHUB_NEWS), true)) {
+                assert_normalizes(NormalizedArticle::from(&parse::<FinnhubArticle>(&item)?))?;
+            }
+
+            #[test]
+            fn numbers_as_strings_read_as_numbers(
+                marketaux in messy_items(fixture(MARKETAUX_ALL)["data"].clone(), false),
+                fmp in messy_items(fixture(FMP_SOCIAL_TRENDING), false),
+                finnhub in messy_items(fixture(FINNHUB_NEWS), false),
+            ) {
+                assert_reads_the_same::<NewsItem>(&marketaux)?;
+                assert_reads_the_same::<FMPMarketSentiment>(&fmp)?;
+                assert_reads_the_same::<FinnhubArticle>(&finnhub)?;
+            }
+        }
+    }
+
+    #[test]
+    fn recorded_bodies_are_scrubbed() {
+        let mut body = serde_json::json!({
+            "meta": { "api_token": "t0k3n" },
+            "results": [{ "apiKey": "k3y", "title": "a=b" }],
+            "next_url": "https://api.polygon.io/v2/reference/news?cursor=YXA9&apiKey=k3y&limit=2",
+        });
+        super::scrub(&mut body);
+        assert_eq!(body["meta"]["api_token"], "REDACTED");
+        assert_eq!(body["results"][0]["apiKey"], "REDACTED");
+        assert_eq!(body["results"][0]["title"], "a=b");
+        assert_eq!(body["next_url"], "https://api.polygon.io/v2/reference/news?cursor=YXA9&apiKey=REDACTED&limit=2");
+    }
+
+    #[test]
+    fn refresh_requests_cover_the_fixtures() {
+        for request in super::REFRESH_REQUESTS {
+            assert!(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join(request.fixture).exists(), "{}", request.fixture);
+            serde_json::from_str::<Value>(request.args).expect("args are not valid JSON");
+        }
+    }
+
+    #[test]
+    fn fixture_paths_flatten_endpoints() {
+        let path = super::fixture_path("fixtures", "fmp", "historical/social-sentiment");
+        assert_eq!(path, std::path::PathBuf::from("fixtures/fmp/historical_social-sentiment.json"));
+    }
+}
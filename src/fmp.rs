@@ -1,4 +1,5 @@
 
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::sync::Arc;
 use std::time::Duration;
@@ -8,16 +9,18 @@ use serde_json::{Value, from_str, to_value};
 use serde::{Serialize, Deserialize};
 use tokio::sync::Mutex;
 use tracing_subscriber::field::debug; 
-use tracing::info;
+use tracing::{debug, info, instrument, warn};
 
-use crate::config::ValueConfig;
+use crate::config::{FmpConfig, ValueConfig};
 use crate::cache::SharedLockedCache;
 use crate::request::HTTPClient;
 use crate::options::FetchType;
-use crate::server_types::{FMPArticle, FMPMarketSentiment};
+use crate::server_types::{FMPArticle, FMPMarketSentiment, FMPMergerAcquisition, Transcript};
+use crate::normalize::NormalizedArticle;
 use crate::utils::{retry, get_from_cache_or_fetch};
-use crate::errors::FMPApiError;
+use crate::errors::{FMPApiError, ProviderError};
 use crate::options::FMPQueryParams as QueryParams;
+use crate::provider::{HealthTracker, NewsProvider, ProviderFuture, ProviderHealth};
 
 const FMP_ARTICLES_V3: &str = "fmp/articles";
 const GENERAL_NEWS_V4: &str = "general_news";
@@ -26,11 +29,114 @@ const STOCK_RSS_V4: &str = "stock-news-sentiments-rss-feed";
 const FOREX_NEWS_V4: &str = "forex_news";
 const CRYPTO_NEWS_V4: &str = "crypto_news";
 const PRESS_RELEASES_V3: &str = "press_releases";
+const EARNING_CALL_TRANSCRIPT_V3: &str = "earning_call_transcript";
+const MERGERS_ACQUISITIONS_RSS_V4: &str = "mergers-acquisitions-rss-feed";
 const HISTORICAL_SOCIAL_SENTIMENT_V4: &str = "historical/social-sentiment";
 const TRENDING_SOCIAL_SENTIMENT_V4: &str = "social-sentiments/trending";
 const SOCIAL_SENTIMENT_CHANGES_V4: &str = "social-sentiments/change";
+const FMP_ARTICLES_STABLE: &str = "fmp-articles";
+const GENERAL_NEWS_STABLE: &str = "news/general-latest";
+const STOCK_NEWS_LATEST_STABLE: &str = "news/stock-latest";
+const STOCK_NEWS_STABLE: &str = "news/stock";
+const FOREX_NEWS_LATEST_STABLE: &str = "news/forex-latest";
+const FOREX_NEWS_STABLE: &str = "news/forex";
+const CRYPTO_NEWS_LATEST_STABLE: &str = "news/crypto-latest";
+const CRYPTO_NEWS_STABLE: &str = "news/crypto";
+const PRESS_RELEASES_LATEST_STABLE: &str = "news/press-releases-latest";
+const PRESS_RELEASES_STABLE: &str = "news/press-releases";
+const MERGERS_ACQUISITIONS_STABLE: &str = "mergers-acquisitions-latest";
+const EARNING_CALL_TRANSCRIPT_STABLE: &str = "earning-call-transcript";
+pub const PROVIDER_NAME: &str = "fmp";
+/// Function polled by `fetch_latest`.
+const LATEST_NEWS_FUNCTION: &str = "fmp articles";
 
 
+/// Generation of the FMP API. `stable` replaces the legacy `v3` and `v4` endpoints, with the same
+/// news under other paths and parameters (see `FMPQueryParams::stable_params`), and no social
+/// sentiment yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Api {
+    V3,
+    V4,
+    Stable,
+}
+impl Display for Api {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Api::V3 => write!(f, "v3"),
+            Api::V4 => write!(f, "v4"),
+            Api::Stable => write!(f, "stable"),
+        }
+    }
+}
+
+/// Endpoint of the FMP API and its query.
+#[derive(Debug, Clone, PartialEq)]
+struct Endpoint {
+    api: Api,
+    path: String,
+    params: Option<Vec<(String, String)>>,
+}
+impl Endpoint {
+    fn v3(path: &str, query_params: &QueryParams) -> Self {
+        Self { api: Api::V3, path: path.to_string(), params: query_params.clone().into() }
+    }
+
+    fn v4(path: &str, query_params: &QueryParams) -> Self {
+        Self { api: Api::V4, path: path.to_string(), params: query_params.clone().into() }
+    }
+
+    fn stable(path: &str, query_params: &QueryParams) -> Self {
+        Self { api: Api::Stable, path: path.to_string(), params: query_params.stable_params() }
+    }
+
+    /// Stable news: the `latest` ones, or those of the `tickers` when given.
+    fn stable_news(latest: &str, by_symbol: &str, query_params: &QueryParams) -> Self {
+        let path = if query_params.tickers().is_some() { by_symbol } else { latest };
+        Self::stable(path, query_params)
+    }
+}
+
+/// Endpoints to fetch in turn: the `stable` one first with `fmp.prefer_stable`, the other one only
+/// with `fmp.fallback`.
+fn attempts(config: &FmpConfig, legacy: Endpoint, stable: Option<Endpoint>) -> Vec<Endpoint> {
+    let mut endpoints = match stable {
+        Some(stable) if config.prefer_stable => vec![stable, legacy],
+        Some(stable) => vec![legacy, stable],
+        None => vec![legacy],
+    };
+    if !config.fallback {
+        endpoints.truncate(1);
+    }
+    endpoints
+}
+
+/// One response of the `pages` of a history, without the records repeated across pages. `last`
+/// tells whether the history is `complete` rather than cut at the maximum of pages.
+fn merge_history(pages: Vec<Vec<FMPMarketSentiment>>, complete: bool) -> FMPApiResponse {
+    let fetched = pages.len() as u64;
+    let mut seen = HashSet::new();
+    let records: Vec<FMPMarketSentiment> = pages
+        .into_iter()
+        .flatten()
+        .filter(|record| record.date.is_none() || seen.insert((record.symbol.clone(), record.date.clone())))
+        .collect();
+    let count = records.len() as u64;
+    FMPApiResponse {
+        content: Some(Content::MarketSentiment(records)),
+        pageable: None,
+        total_pages: Some(fetched),
+        total_elements: Some(count),
+        last: Some(complete),
+        number: None,
+        size: None,
+        number_of_elements: Some(count),
+        sort: None,
+        first: Some(true),
+        empty: Some(count == 0),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Content {
     News(Vec<FMPArticle>),
@@ -48,6 +154,7 @@ impl From<Value> for Content {
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum AbstactContent {
     News,
     MarketSentiment,
@@ -89,232 +196,283 @@ impl FMPApiResponse {
         // TODO: Implement to_json method
         to_value(self).map_err(|err| FMPApiError::ParseError(err.to_string()))
     }
-    
+
+    /// Social sentiment records of the response, none for news.
+    pub fn market_sentiment(self) -> Vec<FMPMarketSentiment> {
+        match self.content {
+            Some(Content::MarketSentiment(records)) => records,
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether a page follows this one.
+    fn has_next(&self) -> bool {
+        match (self.last, self.number, self.total_pages) {
+            (Some(last), _, _) => !last,
+            (None, Some(number), Some(total_pages)) => number + 1 < total_pages,
+            _ => false,
+        }
+    }
+
+    /// Appends the content of the next `page`.
+    fn merge(&mut self, page: FMPApiResponse) {
+        match (&mut self.content, page.content) {
+            (Some(Content::News(news)), Some(Content::News(more))) => news.extend(more),
+            (Some(Content::MarketSentiment(records)), Some(Content::MarketSentiment(more))) => records.extend(more),
+            (content @ None, more) => *content = more,
+            _ => {}
+        }
+        self.number_of_elements = Some(self.number_of_elements.unwrap_or(0) + page.number_of_elements.unwrap_or(0));
+        self.last = page.last;
+        self.empty = Some(self.empty.unwrap_or(true) && page.empty.unwrap_or(true));
+    }
 }
 
 pub struct FMPClient{
     http_client: Arc<HTTPClient>,
     cache: Arc<Mutex<SharedLockedCache>>,
     config: Arc<ValueConfig>,
+    health: HealthTracker,
 }
 impl FMPClient {
     pub fn new(http_client: Arc<HTTPClient>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Self {
         FMPClient {
             http_client,
             cache,
-            config
+            config,
+            health: HealthTracker::default(),
         }
     }
 
-    async fn get_fmp_articles(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
-        let key = format!("fmp_articles_{}", &query_params);
+    /// Body of an `endpoint`, failing on the error messages FMP answers with.
+    async fn get_endpoint(&self, endpoint: &Endpoint) -> Result<Value, FMPApiError> {
+        let params = endpoint.params.clone();
+        let body = match endpoint.api {
+            Api::V3 => self.http_client.get_v3(&endpoint.path, params).await,
+            Api::V4 => self.http_client.get_v4(&endpoint.path, params).await,
+            Api::Stable => self.http_client.get_stable(&endpoint.path, params).await,
+        }
+        .map_err(|e| FMPApiError::FetchError(e.to_string()))?;
+        // E.g. a legacy endpoint with a key issued after the move to `stable`.
+        match body.get("Error Message").and_then(Value::as_str) {
+            Some(message) => Err(FMPApiError::FetchError(message.to_string())),
+            None => Ok(body),
+        }
+    }
+
+    /// Body of the `legacy` endpoint or of its `stable` counterpart, in the order of
+    /// `fmp.prefer_stable`, cached under `key`.
+    async fn get(&self, key: &str, legacy: Endpoint, stable: Option<Endpoint>) -> Result<Value, FMPApiError> {
+        let endpoints = attempts(&self.config.fmp, legacy, stable);
         get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
+            &self.cache,
+            key,
             || async {
-                self.http_client.get_v3(FMP_ARTICLES_V3,query_params.into()).await
-            }, 
+                let mut error = None;
+                for (index, endpoint) in endpoints.iter().enumerate() {
+                    match self.get_endpoint(endpoint).await {
+                        Ok(body) => return Ok(body),
+                        Err(e) => {
+                            if let Some(next) = endpoints.get(index + 1) {
+                                warn!("FMP {} `{}` failed, falling back to {} `{}`: {}", endpoint.api, endpoint.path, next.api, next.path, e);
+                            }
+                            error = Some(e);
+                        }
+                    }
+                }
+                Err(error.unwrap_or_else(|| FMPApiError::TaskError("No endpoint to fetch.".to_string())))
+            },
             self.config.task.cache_ttl
         ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+    }
+
+    async fn get_fmp_articles(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
+        let key = format!("fmp_articles_{}", &query_params);
+        let stable = Endpoint::stable(FMP_ARTICLES_STABLE, &query_params);
+        self.get(&key, Endpoint::v3(FMP_ARTICLES_V3, &query_params), Some(stable)).await
     }
 
     async fn get_general_news(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key  = format!("general_news_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v4(GENERAL_NEWS_V4, query_params.into()).await
-            },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        let stable = Endpoint::stable(GENERAL_NEWS_STABLE, &query_params);
+        self.get(&key, Endpoint::v4(GENERAL_NEWS_V4, &query_params), Some(stable)).await
     }
 
     async fn get_stock_news(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key = format!("stock_news_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v3(STOCK_NEWS_V3, query_params.into()).await
-            },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        let stable = Endpoint::stable_news(STOCK_NEWS_LATEST_STABLE, STOCK_NEWS_STABLE, &query_params);
+        self.get(&key, Endpoint::v3(STOCK_NEWS_V3, &query_params), Some(stable)).await
     }
 
     async  fn get_stock_rss(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key = format!("stock_rss_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v4(STOCK_RSS_V4, query_params.into()).await
-            },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        // Sentiment is not served on `stable`.
+        self.get(&key, Endpoint::v4(STOCK_RSS_V4, &query_params), None).await
     }
 
     async fn get_forex_news(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key = format!("forex_news_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v4(FOREX_NEWS_V4, query_params.into()).await
-            },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        let stable = Endpoint::stable_news(FOREX_NEWS_LATEST_STABLE, FOREX_NEWS_STABLE, &query_params);
+        self.get(&key, Endpoint::v4(FOREX_NEWS_V4, &query_params), Some(stable)).await
     }
 
     async fn get_crypto_news(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key = format!("crypto_news_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v4(CRYPTO_NEWS_V4, query_params.into()).await
-                },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        let stable = Endpoint::stable_news(CRYPTO_NEWS_LATEST_STABLE, CRYPTO_NEWS_STABLE, &query_params);
+        self.get(&key, Endpoint::v4(CRYPTO_NEWS_V4, &query_params), Some(stable)).await
     }
 
     async fn get_press_releases(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key = format!("press_releases_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v3(PRESS_RELEASES_V3, query_params.into()).await
-            },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        let stable = Endpoint::stable_news(PRESS_RELEASES_LATEST_STABLE, PRESS_RELEASES_STABLE, &query_params);
+        self.get(&key, Endpoint::v3(PRESS_RELEASES_V3, &query_params), Some(stable)).await
+    }
+
+    async fn get_mergers_acquisitions(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
+        let key = format!("mergers_acquisitions_{}", &query_params);
+        let stable = Endpoint::stable(MERGERS_ACQUISITIONS_STABLE, &query_params);
+        self.get(&key, Endpoint::v4(MERGERS_ACQUISITIONS_RSS_V4, &query_params), Some(stable)).await
+    }
+
+    /// Transcripts of the earnings calls of `symbol`, of `year` and `quarter` when given.
+    async fn get_earnings_transcript(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
+        let symbol = query_params.symbol()
+            .ok_or_else(|| FMPApiError::TaskError("`symbol` is required for earnings call transcripts.".to_string()))?;
+        let key = format!("earnings_transcript_{}", &query_params);
+        // The symbol is part of the legacy path, and a parameter of the stable one.
+        let mut legacy = Endpoint::v3(&format!("{}/{}", EARNING_CALL_TRANSCRIPT_V3, symbol), &query_params);
+        legacy.params = legacy.params.map(|params| params.into_iter().filter(|(name, _)| name != "symbol").collect());
+        let stable = Endpoint::stable(EARNING_CALL_TRANSCRIPT_STABLE, &query_params);
+        self.get(&key, legacy, Some(stable)).await
     }
 
     async fn get_historical_social_sentiment(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key = format!("historical_social_sentiment_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v4(HISTORICAL_SOCIAL_SENTIMENT_V4, query_params.into()).await
-            },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        self.get(&key, Endpoint::v4(HISTORICAL_SOCIAL_SENTIMENT_V4, &query_params), None).await
     }
 
     async fn get_trending_social_sentiment(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key = format!("trending_social_sentiment_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v4(TRENDING_SOCIAL_SENTIMENT_V4, query_params.into()).await
-            },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        self.get(&key, Endpoint::v4(TRENDING_SOCIAL_SENTIMENT_V4, &query_params), None).await
     }
 
     async fn get_social_sentiment_changes(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
         let key = format!("social_sentiment_changes_{}", &query_params);
-        get_from_cache_or_fetch(
-            &self.cache, 
-            &key, 
-            || async {
-                self.http_client.get_v4(SOCIAL_SENTIMENT_CHANGES_V4, query_params.into()).await
-            },
-            self.config.task.cache_ttl
-        ).await
-        .map_err(|e| FMPApiError::FetchError(e.to_string()))
+        self.get(&key, Endpoint::v4(SOCIAL_SENTIMENT_CHANGES_V4, &query_params), None).await
+    }
+
+    /// Raw body of one page of a paged endpoint.
+    async fn get_page(&self, fetch_type: &FetchType, query_params: QueryParams) -> Result<Value, FMPApiError> {
+        match fetch_type {
+            FetchType::FMPArticle => self.get_fmp_articles(query_params).await,
+            FetchType::GeneralNews => self.get_general_news(query_params).await,
+            FetchType::StockNews => self.get_stock_news(query_params).await,
+            FetchType::StockRSS => self.get_stock_rss(query_params).await,
+            FetchType::ForexNews => self.get_forex_news(query_params).await,
+            FetchType::CryptoNews => self.get_crypto_news(query_params).await,
+            FetchType::PressReleases => self.get_press_releases(query_params).await,
+            FetchType::SocialSentimentHistory => self.get_historical_social_sentiment(query_params).await,
+            FetchType::SocialSentimentTrending => self.get_trending_social_sentiment(query_params).await,
+            FetchType::SocialSentimentChanges => self.get_social_sentiment_changes(query_params).await,
+            _ => Err(FMPApiError::TaskError(format!("Fetch type `{}` is not paged.", fetch_type))),
+        }
+    }
+
+    /// Fetches a paged endpoint. With `fetch_all_pages`, the following pages are fetched until the
+    /// last one (or `max_pages`) and merged into the first.
+    async fn fetch_pages(&self, fetch_type: &FetchType, query_params: QueryParams, content: AbstactContent) -> Result<Value, FMPApiError> {
+        let result = self.get_page(fetch_type, query_params.clone()).await?;
+        let mut response = self.response_from_value(result, content)
+            .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
+        let first_page = response.number.or(query_params.page()).unwrap_or(0);
+        let mut fetched = 1;
+        while fetched < query_params.pages_to_fetch() && response.has_next() {
+            let params = query_params.clone().with_page(first_page + fetched);
+            let result = self.get_page(fetch_type, params).await?;
+            let page = self.response_from_value(result, content)
+                .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
+            response.merge(page);
+            fetched += 1;
+        }
+        if fetched > 1 {
+            debug!("Merged {} pages of `{}`.", fetched, fetch_type);
+        }
+        response.to_json()
     }
 
     async fn fetch(&self, fetch_type: FetchType, query_params: QueryParams) -> Result<Value, FMPApiError> {
         match fetch_type {
-            FetchType::FMPArticle => {
-                let result = self.get_fmp_articles(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::News)
-                    .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
-            },
-            FetchType::GeneralNews => {
-                let result = self.get_general_news(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::News)
-                    .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
-            }
-            FetchType::StockNews => {
-                let result = self.get_stock_news(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::News)
-                    .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
-            },
-            FetchType::StockRSS => {
-                let result = self.get_stock_rss(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::News)
-                    .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
-            }
-            FetchType::ForexNews => {
-                let result = self.get_forex_news(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::News)
+            FetchType::FMPArticle
+            | FetchType::GeneralNews
+            | FetchType::StockNews
+            | FetchType::StockRSS
+            | FetchType::ForexNews
+            | FetchType::CryptoNews
+            | FetchType::PressReleases => self.fetch_pages(&fetch_type, query_params, AbstactContent::News).await,
+            FetchType::MergersAcquisitions => {
+                let result = self.get_mergers_acquisitions(query_params).await?;
+                let deals: Vec<FMPMergerAcquisition> = serde_json::from_value(result)
                     .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
+                to_value(deals).map_err(|e| FMPApiError::ParseError(e.to_string()))
             }
-            FetchType::CryptoNews => {
-                let result = self.get_crypto_news(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::News)
+            FetchType::EarningsTranscript => {
+                let result = self.get_earnings_transcript(query_params).await?;
+                let transcripts: Vec<Transcript> = serde_json::from_value(result)
                     .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
-            }
-            FetchType::PressReleases => {
-                let result = self.get_press_releases(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::News)
-                    .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
+                to_value(transcripts).map_err(|e| FMPApiError::ParseError(e.to_string()))
             }
 
-            FetchType::SocialSentimentHistory => {
-                let result = self.get_historical_social_sentiment(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::MarketSentiment)
-                    .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
-            }
-            FetchType::SocialSentimentTrending => {
-                let result = self.get_trending_social_sentiment(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::MarketSentiment)
-                    .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
-            }
-            FetchType::SocialSentimentChanges => {
-                let result = self.get_social_sentiment_changes(query_params).await?;
-                let articles: FMPApiResponse = self.response_from_value(result, AbstactContent::MarketSentiment)
-                    .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
-                Ok(articles.to_json()?)
+            FetchType::SocialSentimentHistory => self.fetch_social_sentiment_history(query_params).await,
+            FetchType::SocialSentimentTrending
+            | FetchType::SocialSentimentChanges => {
+                self.fetch_pages(&fetch_type, query_params, AbstactContent::MarketSentiment).await
             }
 
             _ => Err(FMPApiError::TaskError(format!("Fetch type `{}` is not supported.", fetch_type))),
         }
     }
 
+    /// Whole social sentiment history of the `symbol` param: the pages from `page` (0 by default)
+    /// on, until an empty one or `max_pages` (`fmp.max_history_pages` by default), merged. Only
+    /// the `page` asked for without `fetch_all_pages`.
+    async fn fetch_social_sentiment_history(&self, query_params: QueryParams) -> Result<Value, FMPApiError> {
+        let symbol = query_params.symbol()
+            .ok_or_else(|| FMPApiError::TaskError("`symbol` is required for the social sentiment history.".to_string()))?
+            .to_string();
+        if query_params.page().is_some() && !query_params.fetch_all_pages() {
+            return self.fetch_pages(&FetchType::SocialSentimentHistory, query_params, AbstactContent::MarketSentiment).await;
+        }
+        let first_page = query_params.page().unwrap_or(0);
+        let max_pages = query_params.max_pages().unwrap_or(self.config.fmp.max_history_pages).max(1);
+        let mut pages = Vec::new();
+        let mut complete = false;
+        for page in first_page..first_page + max_pages {
+            let result = self.get_historical_social_sentiment(query_params.clone().with_page(page)).await?;
+            let records: Vec<FMPMarketSentiment> = serde_json::from_value(result)
+                .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
+            if records.is_empty() {
+                complete = true;
+                break;
+            }
+            pages.push(records);
+        }
+        if !complete {
+            warn!("Stopped the social sentiment history of `{}` after {} pages.", symbol, max_pages);
+        }
+        merge_history(pages, complete).to_json()
+    }
+
     fn response_from_value(&self, value: Value, abstract_type: AbstactContent) -> Result<FMPApiResponse, FMPApiError> {
         let content = match abstract_type {
             AbstactContent::News => {
-                let content_value = value.get("content");
+                let content_value = value.get("content").or(value.is_array().then_some(&value));
                 content_value.and_then(|v| {
                     let result: Result<Vec<FMPArticle>, _> = serde_json::from_value(v.clone());
                     result.map(Content::News).ok()
                 })
             }
             AbstactContent::MarketSentiment => {
-                let content_value = value.get("content");
+                let content_value = value.get("content").or(value.is_array().then_some(&value));
                 content_value.and_then(|v| {
                     let result: Result<Vec<FMPMarketSentiment>, _> = serde_json::from_value(v.clone());
                     result.map(Content::MarketSentiment).ok()
@@ -348,13 +506,116 @@ impl FMPClient {
         })
     }
 
+    /// Fetches the stock news mentioning `symbol`.
+    pub async fn symbol_news(&self, symbol: &str) -> Result<Vec<NormalizedArticle>, FMPApiError> {
+        let query_params = QueryParams::from(serde_json::json!({ "tickers": symbol }));
+        let result = self.get_stock_news(query_params).await?;
+        let articles: Vec<FMPArticle> = serde_json::from_value(result)
+            .map_err(|e| FMPApiError::ParseError(e.to_string()))?;
+        Ok(articles.iter().map(NormalizedArticle::from).collect())
+    }
+
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME, function = args["function"].as_str()))]
     pub async fn poll(&self, args: Arc<Value>) -> Result<Value, FMPApiError> {
         let query_params = QueryParams::from(args.clone());
         let fetch_type = FetchType::from(args);
         retry(
+            PROVIDER_NAME,
             &self.config.clone(), 
             || async {
                 self.fetch(fetch_type.clone(), query_params.clone()).await
             }).await
     }
-}
\ No newline at end of file
+}
+
+impl NewsProvider for FMPClient {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn supports(&self, fetch_type: &FetchType) -> bool {
+        !matches!(
+            fetch_type,
+            FetchType::MarketAux
+                | FetchType::MarketAuxEntityStats
+                | FetchType::MarketAuxSources
+                | FetchType::AlphaVantage
+                | FetchType::AlphaVantageTopMovers
+                | FetchType::AlphaVantageInsiderTransactions
+                | FetchType::AlphaVantageEarningsCalendar
+                | FetchType::Unknown
+        )
+    }
+
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.poll(args).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        NewsProvider::fetch(self, Arc::new(serde_json::json!({ "function": LATEST_NEWS_FUNCTION })))
+    }
+
+    fn health(&self) -> ProviderHealth {
+        self.health.health(&self.config.api.fmp)
+    }
+
+    fn fetch_symbol<'a>(&'a self, symbol: &'a str) -> ProviderFuture<'a, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async move {
+            let result = self.symbol_news(symbol).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn tries_the_stable_endpoints_first_and_falls_back() {
+        let query_params = QueryParams::from(json!({ "tickers": "AAPL,MSFT", "size": 20, "page": 1 }));
+        let legacy = Endpoint::v3(STOCK_NEWS_V3, &query_params);
+        let stable = Endpoint::stable_news(STOCK_NEWS_LATEST_STABLE, STOCK_NEWS_STABLE, &query_params);
+        assert_eq!(stable.path, "news/stock");
+        let params = |pairs: &[(&str, &str)]| Some(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<_>>());
+        assert_eq!(stable.params, params(&[("symbols", "AAPL,MSFT"), ("page", "1"), ("limit", "20")]));
+        assert_eq!(legacy.params, params(&[("tickers", "AAPL,MSFT"), ("page", "1"), ("size", "20")]));
+        let latest = Endpoint::stable_news(STOCK_NEWS_LATEST_STABLE, STOCK_NEWS_STABLE, &QueryParams::from(json!({})));
+        assert_eq!((latest.path.as_str(), latest.params), ("news/stock-latest", None));
+
+        let apis = |config: &FmpConfig, stable: Option<Endpoint>| -> Vec<Api> {
+            attempts(config, legacy.clone(), stable).iter().map(|endpoint| endpoint.api).collect()
+        };
+        let mut config = FmpConfig::default();
+        assert_eq!(apis(&config, Some(stable.clone())), vec![Api::Stable, Api::V3]);
+        assert_eq!(apis(&config, None), vec![Api::V3]);
+        config.prefer_stable = false;
+        assert_eq!(apis(&config, Some(stable.clone())), vec![Api::V3, Api::Stable]);
+        config.fallback = false;
+        assert_eq!(apis(&config, Some(stable)), vec![Api::V3]);
+    }
+
+    #[test]
+    fn merges_the_pages_of_a_history() {
+        let record = |date: &str| -> FMPMarketSentiment {
+            serde_json::from_value(json!({ "symbol": "AAPL", "date": date, "stocktwitsPosts": 1 })).unwrap()
+        };
+        let pages = vec![
+            vec![record("2024-08-07 13:00:00"), record("2024-08-07 12:00:00")],
+            // Shifted by a record published in the meantime.
+            vec![record("2024-08-07 12:00:00"), record("2024-08-07 11:00:00")],
+        ];
+        let merged = merge_history(pages, false).to_json().unwrap();
+        let dates: Vec<&str> = merged["content"]["MarketSentiment"].as_array().unwrap().iter().filter_map(|r| r["date"].as_str()).collect();
+        assert_eq!(dates, vec!["2024-08-07 13:00:00", "2024-08-07 12:00:00", "2024-08-07 11:00:00"]);
+        assert_eq!((merged["total_pages"].as_u64(), merged["total_elements"].as_u64()), (Some(2), Some(3)));
+        assert_eq!(merged["last"], false);
+        assert_eq!(merge_history(Vec::new(), true).to_json().unwrap()["empty"], true);
+    }
+}
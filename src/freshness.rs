@@ -0,0 +1,137 @@
+//! Latency to publication of each provider.
+//!
+//! When an article is first stored, the time between its `published_at` and its ingestion is
+//! recorded for its provider: how stale each source is in practice, as opposed to how often it is
+//! polled. The last `freshness.window` latencies of each provider are kept, and their percentiles
+//! returned by the `status` admin command. Each latency is also recorded in the
+//! `news_publication_latency_seconds` histogram of the `metrics` facade, labelled by provider, for
+//! applications installing a recorder.
+//!
+//! Articles published more than `freshness.max_age_secs` before their ingestion, as stored by a
+//! backfill or a coverage catch-up, are left out.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::clock;
+use crate::config::FreshnessConfig;
+use crate::normalize::parse_timestamp;
+
+const LATENCY_HISTOGRAM: &str = "news_publication_latency_seconds";
+
+/// Nearest-rank percentiles of some durations, in seconds.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct Percentiles {
+    pub p50: Option<i64>,
+    pub p90: Option<i64>,
+    pub max: Option<i64>,
+}
+impl Percentiles {
+    pub fn of(mut values: Vec<i64>) -> Self {
+        values.sort_unstable();
+        let rank = |p: f64| -> Option<i64> {
+            let index = ((p * values.len() as f64).ceil() as usize).max(1) - 1;
+            values.get(index).copied()
+        };
+        Self { p50: rank(0.5), p90: rank(0.9), max: values.last().copied() }
+    }
+}
+
+/// Latency to publication of a provider, over its last `samples` articles.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct ProviderFreshness {
+    pub samples: usize,
+    pub latency_secs: Percentiles,
+}
+
+/// Recent latencies of every provider, given a `FreshnessConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct Freshness {
+    config: FreshnessConfig,
+    latencies: HashMap<String, VecDeque<i64>>,
+}
+impl Freshness {
+    pub fn new(config: &FreshnessConfig) -> Self {
+        Self { config: config.clone(), ..Self::default() }
+    }
+
+    /// Records an article of `provider` published at `published_at` (RFC 3339) and ingested at
+    /// `ingested_at`. Returns the latency in seconds, `None` when left out.
+    pub fn record(&mut self, provider: &str, published_at: &str, ingested_at: DateTime<Utc>) -> Option<i64> {
+        let latency = (ingested_at - parse_timestamp(published_at)?).num_seconds().max(0);
+        if latency as u64 > self.config.max_age_secs {
+            return None;
+        }
+        let latencies = self.latencies.entry(provider.to_string()).or_default();
+        latencies.push_back(latency);
+        while latencies.len() > self.config.window.max(1) {
+            latencies.pop_front();
+        }
+        Some(latency)
+    }
+
+    pub fn report(&self) -> BTreeMap<String, ProviderFreshness> {
+        self.latencies
+            .iter()
+            .map(|(provider, latencies)| {
+                let freshness = ProviderFreshness {
+                    samples: latencies.len(),
+                    latency_secs: Percentiles::of(latencies.iter().copied().collect()),
+                };
+                (provider.clone(), freshness)
+            })
+            .collect()
+    }
+}
+
+fn state() -> &'static Mutex<Freshness> {
+    static STATE: OnceLock<Mutex<Freshness>> = OnceLock::new();
+    STATE.get_or_init(Mutex::default)
+}
+
+fn with_state<T>(f: impl FnOnce(&mut Freshness) -> T) -> T {
+    f(&mut state().lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Applies the `[freshness]` section of the config.
+pub fn configure(config: &FreshnessConfig) {
+    with_state(|freshness| freshness.config = config.clone());
+}
+
+/// Records an article of `provider` published at `published_at` and ingested now.
+pub fn record(provider: &str, published_at: &str) {
+    let ingested_at = clock::now();
+    if let Some(latency) = with_state(|freshness| freshness.record(provider, published_at, ingested_at)) {
+        metrics::histogram!(LATENCY_HISTOGRAM, "provider" => provider.to_string()).record(latency as f64);
+    }
+}
+
+/// Latency percentiles of every provider.
+pub fn report() -> BTreeMap<String, ProviderFreshness> {
+    with_state(|freshness| freshness.report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_recent_latencies_of_each_provider() {
+        let mut freshness = Freshness::new(&FreshnessConfig { window: 3, max_age_secs: 3_600 });
+        let ingested_at = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        for published_at in ["2024-05-01T09:00:00Z", "2024-05-01T09:59:00Z", "2024-05-01T09:58:00Z", "2024-05-01T09:50:00Z"] {
+            assert!(freshness.record("finnhub", published_at, ingested_at).is_some());
+        }
+        // Backfilled, or without a publication time.
+        assert_eq!(freshness.record("finnhub", "2024-04-01T10:00:00Z", ingested_at), None);
+        assert_eq!(freshness.record("gdelt", "yesterday", ingested_at), None);
+
+        let report = freshness.report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report["finnhub"].samples, 3);
+        assert_eq!(report["finnhub"].latency_secs, Percentiles { p50: Some(120), p90: Some(600), max: Some(600) });
+    }
+}
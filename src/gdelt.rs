@@ -0,0 +1,200 @@
+//! ## A Rust wrapper of the [GDELT 2.0 DOC API](https://blog.gdeltproject.org/gdelt-doc-2-0-api-debuts/).
+//!
+//! GDELT monitors news worldwide in 65 languages and tags every article with themes (GKG taxonomy)
+//! and the country of its source. It covers macro and geopolitical events that company-centric
+//! providers miss. No API key is needed.
+//!
+//! Queries combine free keywords, `theme:` and `sourcecountry:` filters. The polling cycle uses the
+//! `gdelt.themes` and `gdelt.countries` of the config; websocket clients can send their own
+//! `query`, or `keywords`, `themes` and `countries` to have it built.
+//!
+//! ## Reference:
+//! [GDELT DOC 2.0 API](https://blog.gdeltproject.org/gdelt-doc-2-0-api-debuts/).
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{to_value, Value};
+use tokio::sync::Mutex;
+use tracing::{error, info, instrument, warn};
+
+use crate::api_calls;
+use crate::cache::SharedLockedCache;
+use crate::chaos;
+use crate::clock;
+use crate::config::ValueConfig;
+use crate::encoding::decode_json_response;
+use crate::errors::{ApiError, ProviderError};
+use crate::fixtures;
+use crate::normalize::{NormalizedArticle, GDELT_PROVIDER};
+use crate::options::FetchType;
+use crate::options::GDQueryParams as QueryParams;
+use crate::provider::{HealthTracker, NewsProvider, ProviderFuture, ProviderHealth};
+use crate::utils::{get_resp_value_from_cache_or_fetch, retry};
+
+const BASE_URL: &str = "https://api.gdeltproject.org/api/v2/doc/doc";
+pub const PROVIDER_NAME: &str = GDELT_PROVIDER;
+const ENDPOINT: &str = "doc";
+/// Most articles GDELT returns per request.
+const MAX_RECORDS: u32 = 250;
+/// Date format of `startdatetime` and `enddatetime`.
+const DATETIME_FORMAT: &str = "%Y%m%d%H%M%S";
+
+/// Response of the DOC API in `ArtList` mode.
+///
+/// [See example here](https://api.gdeltproject.org/api/v2/doc/doc?query=theme:ECON_INFLATION&mode=ArtList&format=json).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GdeltResponse {
+    #[serde(default)]
+    pub articles: Vec<GdeltArticle>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GdeltArticle {
+    pub url: Option<String>,
+    pub url_mobile: Option<String>,
+    pub title: Option<String>,
+    /// Time GDELT first saw the article, as `YYYYMMDDTHHMMSSZ`.
+    pub seendate: Option<String>,
+    pub socialimage: Option<String>,
+    pub domain: Option<String>,
+    /// Language name, e.g. `English`.
+    pub language: Option<String>,
+    /// Country name of the source, e.g. `United States`.
+    pub sourcecountry: Option<String>,
+}
+
+pub struct GdeltApiClient {
+    client: Arc<Client>,
+    cache: Arc<Mutex<SharedLockedCache>>,
+    config: Arc<ValueConfig>,
+    health: HealthTracker,
+}
+impl GdeltApiClient {
+    pub fn new(client: Arc<Client>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Self {
+        Self { client, cache, config, health: HealthTracker::default() }
+    }
+
+    async fn get(&self, query_params: QueryParams) -> Result<Value, ApiError> {
+        let key = format!("gdelt_{:?}", &query_params);
+        get_resp_value_from_cache_or_fetch(
+            &self.cache,
+            &key,
+            || async { self.get_(query_params).await },
+            self.config.task.cache_ttl,
+        ).await
+        .inspect_err(|_| warn!("GDELT client encountered an error during GET request."))
+    }
+
+    async fn get_(&self, query_params: QueryParams) -> Result<Value, ApiError> {
+        let response = api_calls::send(PROVIDER_NAME, self.client.get(BASE_URL).query(&query_params)).await?;
+        if response.status() != StatusCode::OK {
+            return Err(api_calls::status_error(response).await);
+        }
+
+        // Invalid queries are answered with `200 OK` and a plain text message, which surfaces here
+        // as an `EncodingError`. No match is answered with an empty object.
+        let body = decode_json_response(PROVIDER_NAME, response).await
+            .inspect_err(|e| error!("Failed to read body: {}", e))?;
+        fixtures::record(PROVIDER_NAME, ENDPOINT, &body);
+        let body = chaos::response_body(PROVIDER_NAME, body);
+        let response: GdeltResponse = serde_json::from_value(body).map_err(|e| {
+            error!("Failed to parse body: {:?}", e);
+            ApiError::JsonParseError { message: e.to_string() }
+        })?;
+
+        to_value(response).map_err(|e| ApiError::JsonParseError { message: e.to_string() })
+    }
+
+    /// Query built from the `gdelt` section of the config.
+    fn configured_query(&self) -> String {
+        let gdelt = &self.config.gdelt;
+        QueryParams::build_query(gdelt.keywords.as_deref(), &gdelt.themes, &gdelt.countries)
+    }
+
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME, function = args["function"].as_str()))]
+    pub async fn poll(&self, args: Arc<Value>) -> Result<Value, ApiError> {
+        let args = Arc::try_unwrap(args).unwrap_or_else(|v| (*v).clone());
+        let query_params = QueryParams::try_from(args)?;
+        let response = retry(PROVIDER_NAME, &self.config, || async {
+            self.get(query_params.clone()).await
+        }).await?;
+        info!("API GET Response was successful? : {:?}", !response.is_null());
+        Ok(response)
+    }
+
+    /// Fetches the articles matching the configured query seen since the last polling cycle.
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME))]
+    pub async fn latest(&self) -> Result<Value, ApiError> {
+        let to = clock::now();
+        let from = to - chrono::Duration::seconds(self.config.request.delay_secs);
+        let query_params = self.window_query(from, to).ok_or_else(Self::not_configured)?;
+        self.get_(query_params).await
+            .inspect_err(|e| error!("Error during GET request: {}", e))
+    }
+
+    /// Fetches the articles matching the configured query seen between `from` and `to`.
+    pub async fn window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<NormalizedArticle>, ApiError> {
+        let query_params = self.window_query(from, to).ok_or_else(Self::not_configured)?;
+        let value = self.get(query_params).await?;
+        let response: GdeltResponse = serde_json::from_value(value)
+            .map_err(|e| ApiError::JsonParseError { message: e.to_string() })?;
+        Ok(response.articles.iter().map(NormalizedArticle::from).collect())
+    }
+
+    /// Query of the articles seen between `from` and `to`, `None` when nothing is configured to match.
+    fn window_query(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Option<QueryParams> {
+        let query = self.configured_query();
+        if query.is_empty() {
+            return None;
+        }
+        Some(QueryParams::new(
+            &query,
+            Some(self.config.gdelt.max_records.min(MAX_RECORDS)),
+            None,
+            Some(&from.format(DATETIME_FORMAT).to_string()),
+            Some(&to.format(DATETIME_FORMAT).to_string()),
+            Some("DateDesc"),
+        ))
+    }
+
+    fn not_configured() -> ApiError {
+        ApiError::ConfigError { message: "No `gdelt` keywords, themes or countries configured".to_string() }
+    }
+}
+
+impl NewsProvider for GdeltApiClient {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn supports(&self, fetch_type: &FetchType) -> bool {
+        matches!(fetch_type, FetchType::Gdelt)
+    }
+
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.poll(args).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.latest().await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn health(&self) -> ProviderHealth {
+        self.health.status()
+    }
+
+    fn fetch_window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async move { Ok(self.window(from, to).await?) })
+    }
+}
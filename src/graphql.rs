@@ -0,0 +1,281 @@
+//! GraphQL queries of the stored news.
+//!
+//! When `graphql.enabled` is set, the REST API (see `http.rs`) also serves `POST /graphql`, taking
+//! the usual `{"query": ..., "variables": ...}` body. Every query takes an optional `filter`:
+//!
+//! ```graphql
+//! {
+//!   articles(filter: {tickers: ["NVDA"], from: "2024-05-01T00:00:00Z", sources: ["Reuters"]}, limit: 20) {
+//!     title publishedAt provider sentiment sentimentScore entities { name kind }
+//!   }
+//!   entities(filter: {tickers: ["NVDA"]}, limit: 10) { name kind articles }
+//!   topics(filter: {providers: ["marketaux"]}) { topic articles }
+//!   sentiment(filter: {tickers: ["NVDA", "AMD"]}) { articles meanScore labels { label articles } }
+//! }
+//! ```
+//!
+//! Without `from`, a query covers the last `graphql.window_hours`. Aggregates are computed over at
+//! most `graphql.max_articles` articles, the newest ones.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Error, InputObject, Object, Result, Schema, SimpleObject};
+use chrono::{Duration as UtcDuration, SecondsFormat};
+
+use crate::clock;
+use crate::config::GraphqlConfig;
+use crate::entities::EntityKind;
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+use crate::sentiment::SentimentLabel;
+use crate::storage::{ArticleFilter, Storage};
+
+pub type NewsSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Schema answering from `storage`.
+pub fn schema(storage: Arc<dyn Storage>, config: GraphqlConfig) -> NewsSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(config.max_depth)
+        .data(storage)
+        .data(config)
+        .finish()
+}
+
+/// Articles a query is about. Lists match any of their values.
+#[derive(Debug, Default, InputObject)]
+pub struct FilterInput {
+    tickers: Option<Vec<String>>,
+    /// Providers the articles were fetched from, e.g. `finnhub`.
+    providers: Option<Vec<String>>,
+    /// Publishers, e.g. `Reuters`.
+    sources: Option<Vec<String>>,
+    /// RFC 3339, `graphql.window_hours` before `to` by default.
+    from: Option<String>,
+    /// RFC 3339, now by default.
+    to: Option<String>,
+}
+impl FilterInput {
+    fn to_filter(&self, config: &GraphqlConfig) -> Result<ArticleFilter> {
+        let time = |key: &str, value: &Option<String>| match value {
+            Some(t) => parse_timestamp(t).map(Some).ok_or_else(|| Error::new(format!("`{}` is not an RFC 3339 time: {}", key, t))),
+            None => Ok(None),
+        };
+        let to = time("to", &self.to)?.unwrap_or_else(clock::now);
+        let from = time("from", &self.from)?.unwrap_or(to - UtcDuration::hours(config.window_hours as i64));
+        Ok(ArticleFilter {
+            tickers: self.tickers.clone().unwrap_or_default(),
+            providers: self.providers.clone().unwrap_or_default(),
+            sources: self.sources.clone().unwrap_or_default(),
+            from: Some(from.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            to: Some(to.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        })
+    }
+}
+
+/// A person, organization or location named by an article.
+#[derive(Debug, SimpleObject)]
+pub struct EntityObject {
+    name: String,
+    kind: String,
+}
+
+/// A stored article.
+#[derive(Debug, SimpleObject)]
+pub struct Article {
+    provider: String,
+    id: String,
+    url: Option<String>,
+    title: Option<String>,
+    summary: Option<String>,
+    source: Option<String>,
+    authors: Vec<String>,
+    language: Option<String>,
+    published_at: Option<String>,
+    tickers: Vec<String>,
+    topics: Vec<String>,
+    categories: Vec<String>,
+    keywords: Vec<String>,
+    sentiment_score: Option<f64>,
+    /// Harmonized label, e.g. `somewhat_bullish`.
+    sentiment: Option<String>,
+    entities: Vec<EntityObject>,
+}
+impl From<NormalizedArticle> for Article {
+    fn from(article: NormalizedArticle) -> Self {
+        Self {
+            provider: article.provider,
+            id: article.id,
+            url: article.url,
+            title: article.title,
+            summary: article.summary,
+            source: article.source,
+            authors: article.authors,
+            language: article.language,
+            published_at: article.published_at,
+            tickers: article.tickers,
+            topics: article.topics,
+            categories: article.categories,
+            keywords: article.keywords,
+            sentiment_score: article.sentiment_score,
+            sentiment: article.sentiment.map(|label| label.to_str().to_string()),
+            entities: article.entities.into_iter().map(|e| EntityObject { name: e.name, kind: e.kind.to_str().to_string() }).collect(),
+        }
+    }
+}
+
+/// An entity and the number of articles naming it.
+#[derive(Debug, SimpleObject)]
+pub struct EntityCount {
+    name: String,
+    kind: String,
+    articles: u64,
+}
+
+/// A topic and the number of articles about it.
+#[derive(Debug, SimpleObject)]
+pub struct TopicCount {
+    topic: String,
+    articles: u64,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct LabelCount {
+    label: String,
+    articles: u64,
+}
+
+/// Sentiment of the matching articles.
+#[derive(Debug, SimpleObject)]
+pub struct SentimentAggregate {
+    articles: u64,
+    /// Mean sentiment score, null without scored articles.
+    mean_score: Option<f64>,
+    /// Every harmonized label, most bearish first.
+    labels: Vec<LabelCount>,
+}
+
+pub struct QueryRoot;
+
+/// Newest articles matching `filter`, at most `limit`.
+async fn find(ctx: &Context<'_>, filter: Option<FilterInput>, limit: usize) -> Result<Vec<NormalizedArticle>> {
+    let storage = ctx.data::<Arc<dyn Storage>>()?;
+    let config = ctx.data::<GraphqlConfig>()?;
+    let filter = filter.unwrap_or_default().to_filter(config)?;
+    let limit = limit.clamp(1, config.max_articles.max(1)) as i64;
+    storage.filtered_articles(&filter, limit).await.map_err(|e| Error::new(e.to_string()))
+}
+
+/// `counts` sorted by decreasing count, then key, at most `limit`.
+fn ranked<K: Ord>(counts: HashMap<K, u64>, limit: usize) -> Vec<(K, u64)> {
+    let mut ranked: Vec<(K, u64)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(limit);
+    ranked
+}
+
+#[Object]
+impl QueryRoot {
+    /// Matching articles, newest first.
+    async fn articles(&self, ctx: &Context<'_>, filter: Option<FilterInput>, #[graphql(default = 50)] limit: usize) -> Result<Vec<Article>> {
+        Ok(find(ctx, filter, limit).await?.into_iter().map(Article::from).collect())
+    }
+
+    /// Entities named by the matching articles, most named first.
+    async fn entities(&self, ctx: &Context<'_>, filter: Option<FilterInput>, kind: Option<String>, #[graphql(default = 20)] limit: usize) -> Result<Vec<EntityCount>> {
+        let kind = match kind.as_deref() {
+            Some(k) => Some(k.parse::<EntityKind>().map_err(Error::new)?),
+            None => None,
+        };
+        let mut counts: HashMap<(String, &'static str), u64> = HashMap::new();
+        for article in find(ctx, filter, usize::MAX).await? {
+            for entity in article.entities.into_iter().filter(|e| kind.is_none_or(|kind| e.kind == kind)) {
+                *counts.entry((entity.name, entity.kind.to_str())).or_default() += 1;
+            }
+        }
+        Ok(ranked(counts, limit).into_iter().map(|((name, kind), articles)| EntityCount { name, kind: kind.to_string(), articles }).collect())
+    }
+
+    /// Topics of the matching articles, most frequent first.
+    async fn topics(&self, ctx: &Context<'_>, filter: Option<FilterInput>, #[graphql(default = 20)] limit: usize) -> Result<Vec<TopicCount>> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for article in find(ctx, filter, usize::MAX).await? {
+            for topic in article.topics {
+                *counts.entry(topic).or_default() += 1;
+            }
+        }
+        Ok(ranked(counts, limit).into_iter().map(|(topic, articles)| TopicCount { topic, articles }).collect())
+    }
+
+    /// Sentiment of the matching articles.
+    async fn sentiment(&self, ctx: &Context<'_>, filter: Option<FilterInput>) -> Result<SentimentAggregate> {
+        let articles = find(ctx, filter, usize::MAX).await?;
+        let mut labels: BTreeMap<SentimentLabel, u64> = SentimentLabel::ALL.iter().map(|label| (*label, 0)).collect();
+        for label in articles.iter().filter_map(|a| a.sentiment) {
+            *labels.entry(label).or_default() += 1;
+        }
+        let scores: Vec<f64> = articles.iter().filter_map(|a| a.sentiment_score).collect();
+        Ok(SentimentAggregate {
+            articles: articles.len() as u64,
+            mean_score: (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64),
+            labels: labels.into_iter().map(|(label, articles)| LabelCount { label: label.to_str().to_string(), articles }).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn filters_and_aggregates_articles() {
+        let storage = Arc::new(MemoryStorage::new());
+        let article = |id: &str, hours_ago: i64, ticker: &str, source: &str, score: f64| -> NormalizedArticle {
+            let published_at = (clock::now() - UtcDuration::hours(hours_ago)).to_rfc3339_opts(SecondsFormat::Secs, true);
+            NormalizedArticle::test(id)
+                .provider("marketaux")
+                .source(source)
+                .published_at(&published_at)
+                .tickers(&[ticker])
+                .topics(&["Earnings"])
+                .sentiment_score(score)
+                .sentiment(if score > 0.0 { SentimentLabel::Bullish } else { SentimentLabel::Bearish })
+                .entity("Jensen Huang", EntityKind::Person)
+        };
+        storage
+            .store_articles(&[
+                article("1", 1, "NVDA", "Reuters", 0.6),
+                article("2", 2, "NVDA", "Bloomberg", -0.2),
+                article("3", 3, "AMD", "Reuters", 0.4),
+                article("4", 30, "NVDA", "Reuters", 0.9),
+            ])
+            .await
+            .unwrap();
+        let schema = schema(storage, GraphqlConfig::default());
+
+        let query = r#"{
+            articles(filter: {tickers: ["nvda"]}) { id source publishedAt }
+            reuters: articles(filter: {sources: ["Reuters"]}, limit: 1) { id }
+            entities(filter: {tickers: ["NVDA"]}, kind: "person") { name kind articles }
+            topics { topic articles }
+            sentiment(filter: {tickers: ["NVDA"]}) { articles meanScore labels { label articles } }
+        }"#;
+        let response = schema.execute(query).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["articles"], json!([
+            {"id": "1", "source": "Reuters", "publishedAt": data["articles"][0]["publishedAt"]},
+            {"id": "2", "source": "Bloomberg", "publishedAt": data["articles"][1]["publishedAt"]},
+        ]));
+        assert_eq!(data["reuters"], json!([{"id": "1"}]));
+        assert_eq!(data["entities"], json!([{"name": "Jensen Huang", "kind": "person", "articles": 2}]));
+        assert_eq!(data["topics"], json!([{"topic": "Earnings", "articles": 3}]));
+        assert_eq!(data["sentiment"]["articles"], 2);
+        assert!((data["sentiment"]["meanScore"].as_f64().unwrap() - 0.2).abs() < 1e-9);
+
+        let response = schema.execute(r#"{ articles(filter: {from: "yesterday"}) { id } }"#).await;
+        assert!(response.errors[0].message.contains("RFC 3339"));
+    }
+}
@@ -0,0 +1,321 @@
+//! gRPC service alongside the websocket server.
+//!
+//! For backends that want typed messages rather than JSON over a websocket, the functions of the
+//! websocket server are also served over gRPC from the same `PollState` when `grpc.enabled` is
+//! set. The service is defined in `proto/news.proto`:
+//!
+//! ```text
+//! PollProvider(PollRequest) -> PollResponse      # like `<provider>_news_polling`
+//! Search(SearchRequest) -> Articles              # by tickers, keywords, topics, entities or sentiment
+//! Subscribe(SubscribeRequest) -> stream StoredArticle
+//! ```
+//!
+//! Providers do not share a response format, so `PollProvider` takes and returns JSON strings;
+//! stored articles are typed. `Subscribe` sends each article stored from then on, whichever
+//! process stored it, like `/news/stream` of the REST API (see `http.rs`): a client resumes with
+//! the `seq` of the last article it received as `after`.
+//!
+//! When `[grpc.tokens]` is not empty, calls must present one of its tokens as
+//! `authorization: Bearer <token>` metadata.
+
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::Stream;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use tracing::{error, info, warn};
+
+use crate::autoscale;
+use crate::config::GrpcConfig;
+use crate::http::ArticleFeed;
+use crate::normalize::NormalizedArticle;
+use crate::query::{self as article_query, QueryFilter};
+use crate::sentiment::SentimentLabel;
+use crate::storage::{Storage, StoredFeed};
+use crate::utils::now;
+use crate::webhook::authorize_token;
+use crate::websocket::PollState;
+
+/// Messages and service generated from `proto/news.proto`.
+pub mod proto {
+    tonic::include_proto!("news_data.v1");
+}
+
+use proto::news_server::{News, NewsServer};
+use proto::{Articles, PollRequest, PollResponse, SearchRequest, StoredArticle, SubscribeRequest};
+
+const DEFAULT_HOURS: u32 = 24;
+const DEFAULT_LIMIT: u32 = 50;
+
+impl From<NormalizedArticle> for proto::Article {
+    fn from(article: NormalizedArticle) -> Self {
+        Self {
+            provider: article.provider,
+            id: article.id,
+            url: article.url,
+            title: article.title,
+            summary: article.summary,
+            source: article.source,
+            authors: article.authors,
+            language: article.language,
+            published_at: article.published_at,
+            tickers: article.tickers,
+            topics: article.topics,
+            categories: article.categories,
+            keywords: article.keywords,
+            sentiment_score: article.sentiment_score,
+            sentiment: article.sentiment.map(|label| label.to_str().to_string()),
+            entities: article.entities.into_iter().map(|e| proto::Entity { name: e.name, kind: e.kind.to_str().to_string() }).collect(),
+        }
+    }
+}
+
+/// Rejects the calls without a known bearer token, when tokens are configured.
+#[derive(Clone)]
+pub struct TokenCheck {
+    tokens: Arc<HashMap<String, String>>,
+}
+impl Interceptor for TokenCheck {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        if self.tokens.is_empty() {
+            return Ok(request);
+        }
+        let presented = request.metadata().get("authorization").and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer "));
+        match presented.and_then(|token| authorize_token(token, &self.tokens)) {
+            Some(_) => Ok(request),
+            None => {
+                warn!("Rejected a gRPC call with a missing or unknown token.");
+                Err(Status::unauthenticated("Missing or unknown bearer token"))
+            }
+        }
+    }
+}
+
+pub struct NewsService {
+    poll: Arc<PollState>,
+    config: Arc<GrpcConfig>,
+}
+impl NewsService {
+    fn storage(&self) -> Option<&Arc<dyn Storage>> {
+        self.poll.db.as_ref()
+    }
+}
+
+fn no_database() -> Status {
+    Status::unavailable("Database is not available")
+}
+
+/// The query of `request`, its `limit` capped at `max_limit`.
+fn search_query(request: &SearchRequest, max_limit: usize) -> Result<article_query::Query, String> {
+    let filter = if !request.tickers.is_empty() {
+        QueryFilter::Tickers(request.tickers.clone())
+    } else if !request.keywords.is_empty() {
+        QueryFilter::Keywords(request.keywords.clone())
+    } else if !request.topics.is_empty() {
+        QueryFilter::Categories(request.topics.clone())
+    } else if !request.entities.is_empty() {
+        QueryFilter::Entities(request.entities.clone())
+    } else if !request.sentiment.is_empty() {
+        let labels = request
+            .sentiment
+            .iter()
+            .map(|label| label.parse())
+            .collect::<Result<Vec<SentimentLabel>, String>>()?;
+        QueryFilter::Sentiment(labels)
+    } else {
+        return Err("One of `tickers`, `keywords`, `topics`, `entities` or `sentiment` is required".to_string());
+    };
+    Ok(article_query::Query {
+        hours: request.hours.unwrap_or(DEFAULT_HOURS) as i64,
+        limit: (request.limit.unwrap_or(DEFAULT_LIMIT) as usize).min(max_limit),
+        ..article_query::Query::new(filter)
+    })
+}
+
+#[tonic::async_trait]
+impl News for NewsService {
+    async fn poll_provider(&self, request: Request<PollRequest>) -> Result<Response<PollResponse>, Status> {
+        let request = request.into_inner();
+        let Some(provider) = self.poll.providers.iter().find(|p| p.name() == request.provider) else {
+            return Err(Status::not_found(format!("Unknown provider `{}`", request.provider)));
+        };
+        let args: Value = match request.params_json.trim() {
+            "" => json!({}),
+            params => serde_json::from_str(params).map_err(|e| Status::invalid_argument(format!("`params_json` is not JSON: {}", e)))?,
+        };
+        match autoscale::track(provider.name(), provider.fetch(Arc::new(args))).await {
+            Ok(news) => Ok(Response::new(PollResponse { news_json: news.to_string() })),
+            Err(e) => Err(Status::unavailable(format!("{} provider polling failed: {}", request.provider, e))),
+        }
+    }
+
+    async fn search(&self, request: Request<SearchRequest>) -> Result<Response<Articles>, Status> {
+        let db = self.storage().ok_or_else(no_database)?;
+        let query = search_query(request.get_ref(), self.config.max_limit).map_err(Status::invalid_argument)?;
+        match article_query::run(db.as_ref(), &query).await {
+            Ok(articles) => Ok(Response::new(Articles { articles: articles.into_iter().map(proto::Article::from).collect() })),
+            Err(e) => Err(Status::internal(format!("Search failed: {}", e))),
+        }
+    }
+
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<StoredArticle, Status>> + Send>>;
+
+    async fn subscribe(&self, request: Request<SubscribeRequest>) -> Result<Response<Self::SubscribeStream>, Status> {
+        let request = request.into_inner();
+        let feed = match (request.after, request.since) {
+            (Some(seq), _) => StoredFeed::after(seq),
+            (None, since) => StoredFeed::since(since.unwrap_or_else(now)),
+        };
+        let feed = ArticleFeed::new(self.storage().ok_or_else(no_database)?.clone(), &request.tickers, self.config.max_limit, feed);
+        let interval = Duration::from_secs(self.config.stream_interval_secs.max(1));
+
+        let articles = futures::stream::unfold((feed, VecDeque::new()), move |(mut feed, mut pending)| async move {
+            while pending.is_empty() {
+                tokio::time::sleep(interval).await;
+                match feed.next_batch().await {
+                    Ok(batch) => pending.extend(batch),
+                    Err(e) => warn!("gRPC subscription failed to read the storage: {}", e),
+                }
+            }
+            let stored = pending.pop_front()?;
+            Some((Ok(StoredArticle { stored_at: stored.stored_at, article: Some(stored.article.into()), seq: stored.seq }), (feed, pending)))
+        });
+        Ok(Response::new(Box::pin(articles)))
+    }
+}
+
+/// The service of `poll`, behind the token check.
+pub fn service(poll: Arc<PollState>, config: GrpcConfig) -> InterceptedService<NewsServer<NewsService>, TokenCheck> {
+    let check = TokenCheck { tokens: Arc::new(config.tokens.clone()) };
+    NewsServer::with_interceptor(NewsService { poll, config: Arc::new(config) }, check)
+}
+
+/// Serves the gRPC service on `grpc.host`:`grpc.port` in a background task.
+pub async fn spawn(poll: Arc<PollState>, config: GrpcConfig) -> std::io::Result<()> {
+    let address = format!("{}:{}", config.host, config.port);
+    let listener = TcpListener::bind(&address).await?;
+    info!("gRPC service listening on: {}", address);
+    let incoming = TcpIncoming::from_listener(listener, true, None).map_err(std::io::Error::other)?;
+    let service = service(poll, config);
+    tokio::spawn(async move {
+        if let Err(e) = Server::builder().add_service(service).serve_with_incoming(incoming).await {
+            error!("gRPC server failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use tonic::transport::Channel;
+    use tonic::Code;
+
+    use crate::config::ValueConfig;
+    use crate::entities::EntityKind;
+    use crate::errors::{ApiError, ProviderError};
+    use crate::options::FetchType;
+    use crate::provider::{NewsProvider, ProviderFuture, ProviderHealth};
+    use crate::storage::MemoryStorage;
+    use proto::news_client::NewsClient;
+
+    struct EchoProvider;
+    impl NewsProvider for EchoProvider {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn supports(&self, _fetch_type: &FetchType) -> bool {
+            true
+        }
+
+        fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async move {
+                if args.get("fail").is_some() {
+                    return Err(ApiError::NoEndpointProvided.into());
+                }
+                Ok(json!({ "args": *args }))
+            })
+        }
+
+        fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            self.fetch(Arc::new(json!({})))
+        }
+
+        fn health(&self) -> ProviderHealth {
+            ProviderHealth::Healthy
+        }
+    }
+
+    async fn serve(tokens: &[(&str, &str)]) -> (String, Arc<MemoryStorage>) {
+        let config = Arc::new(ValueConfig::from_toml(include_str!("../config.toml.example")).unwrap());
+        let storage = Arc::new(MemoryStorage::new());
+        let article = |id: &str, ticker: &str| {
+            NormalizedArticle::test(id).published_at(&now()).tickers(&[ticker]).sentiment(SentimentLabel::Bullish).entity("Jensen Huang", EntityKind::Person)
+        };
+        storage.store_articles(&[article("1", "NVDA"), article("2", "AMD")]).await.unwrap();
+        let poll = Arc::new(PollState::with_providers(config, vec![Arc::new(EchoProvider)]).with_database(storage.clone()));
+        let grpc_config = GrpcConfig {
+            tokens: tokens.iter().map(|(name, token)| (name.to_string(), token.to_string())).collect(),
+            stream_interval_secs: 1,
+            ..GrpcConfig::default()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(Server::builder().add_service(service(poll, grpc_config)).serve_with_incoming(incoming));
+        (format!("http://{}", address), storage)
+    }
+
+    #[tokio::test]
+    async fn polls_searches_and_streams_articles() {
+        let (address, storage) = serve(&[]).await;
+        let mut client = NewsClient::connect(address).await.unwrap();
+
+        let polled = client.poll_provider(PollRequest { provider: "echo".to_string(), params_json: r#"{"symbols": "NVDA"}"#.to_string() }).await.unwrap();
+        let polled: Value = serde_json::from_str(&polled.into_inner().news_json).unwrap();
+        assert_eq!(polled["args"]["symbols"], "NVDA");
+        let failed = client.poll_provider(PollRequest { provider: "echo".to_string(), params_json: r#"{"fail": true}"#.to_string() }).await;
+        assert_eq!(failed.unwrap_err().code(), Code::Unavailable);
+        let unknown = client.poll_provider(PollRequest { provider: "unknown".to_string(), params_json: String::new() }).await;
+        assert_eq!(unknown.unwrap_err().code(), Code::NotFound);
+
+        let found = client.search(SearchRequest { tickers: vec!["nvda".to_string()], ..SearchRequest::default() }).await.unwrap().into_inner();
+        assert_eq!(found.articles.len(), 1);
+        assert_eq!(found.articles[0].id, "1");
+        assert_eq!(found.articles[0].sentiment.as_deref(), Some("bullish"));
+        assert_eq!(found.articles[0].entities[0].kind, "person");
+        let invalid = client.search(SearchRequest { sentiment: vec!["euphoric".to_string()], ..SearchRequest::default() }).await;
+        assert_eq!(invalid.unwrap_err().code(), Code::InvalidArgument);
+
+        let mut stream = client.subscribe(SubscribeRequest { tickers: vec!["AAPL".to_string()], since: None, after: None }).await.unwrap().into_inner();
+        storage.store_articles(&[NormalizedArticle::test("3").published_at(&now()).tickers(&["NVDA"]), NormalizedArticle::test("4").published_at(&now()).tickers(&["AAPL"])]).await.unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), stream.next()).await.unwrap().unwrap().unwrap();
+        assert_eq!(received.article.map(|a| a.id).as_deref(), Some("4"));
+        assert!(!received.stored_at.is_empty());
+    }
+
+    #[tokio::test]
+    async fn requires_a_known_token_when_configured() {
+        let (address, _) = serve(&[("backend", "s3cret")]).await;
+        let channel = Channel::from_shared(address).unwrap().connect().await.unwrap();
+        let search = SearchRequest { tickers: vec!["NVDA".to_string()], ..SearchRequest::default() };
+
+        let mut client = NewsClient::new(channel);
+        assert_eq!(client.search(search.clone()).await.unwrap_err().code(), Code::Unauthenticated);
+
+        let mut request = Request::new(search);
+        request.metadata_mut().insert("authorization", "Bearer s3cret".parse().unwrap());
+        assert_eq!(client.search(request).await.unwrap().into_inner().articles.len(), 1);
+    }
+}
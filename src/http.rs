@@ -0,0 +1,425 @@
+//! REST API alongside the websocket server.
+//!
+//! For clients that cannot hold a websocket connection (cron jobs, serverless functions,
+//! dashboards), the websocket server also serves a few HTTP endpoints from the same `PollState`
+//! when `http.enabled` is set:
+//!
+//! ```text
+//! GET  /news/latest?hours=24&limit=50                  # latest stored articles
+//! GET  /news/search?tickers=NVDA,AMD&hours=24&limit=50 # or keywords=, topics=, entities=, sentiment=
+//! GET  /sentiment/{ticker}?hours=24                    # sentiment of a ticker's articles
+//! POST /providers/{name}/poll                          # body: the params of `<name>_news_polling`
+//! GET  /news/stream?tickers=NVDA,AMD                   # Server-Sent Events, see below
+//! POST /graphql                                        # see `graphql.rs`
+//! ```
+//!
+//! Responses are JSON, errors `{"error": ...}` with a matching status. When `[http.tokens]` is
+//! not empty, requests must present one of its tokens in an `Authorization: Bearer` header.
+//!
+//! `/news/stream` sends an `article` event for each article stored from then on, whichever
+//! process stored it: the storage is looked up every `http.stream_interval_secs`. The id of an
+//! event is the sequence number of its article (see `StoredFeed`), so a reconnecting
+//! `EventSource` resumes where it stopped with its `Last-Event-ID` header. As `EventSource` cannot set headers, the token may
+//! also be given as the `access_token` param of this endpoint.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{Duration as UtcDuration, SecondsFormat};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tracing::{error, info, warn};
+
+use crate::autoscale;
+use crate::clock;
+use crate::config::HttpConfig;
+use crate::db::OpError;
+use crate::graphql::{self, NewsSchema};
+use crate::normalize::NormalizedArticle;
+use crate::query::{self as article_query, QueryFilter};
+use crate::sentiment::SentimentLabel;
+use crate::storage::{SequencedArticle, Storage, StoredFeed};
+use crate::utils::now;
+use crate::webhook::{authorize, authorize_token};
+use crate::websocket::PollState;
+
+const DEFAULT_HOURS: i64 = 24;
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Clone)]
+struct HttpState {
+    poll: Arc<PollState>,
+    config: Arc<HttpConfig>,
+    /// Built when `graphql.enabled` is set and the database is available.
+    graphql: Option<NewsSchema>,
+}
+
+type Params = Query<HashMap<String, String>>;
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+/// Status and message of a failed request.
+type Failure = (StatusCode, String);
+
+fn storage(state: &HttpState) -> Result<&Arc<dyn Storage>, Failure> {
+    state.poll.db.as_ref().ok_or_else(|| (StatusCode::SERVICE_UNAVAILABLE, "Database is not available".to_string()))
+}
+
+/// The `key` param, comma separated.
+fn list_param(params: &HashMap<String, String>, key: &str) -> Vec<String> {
+    params
+        .get(key)
+        .map(|values| values.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// The `hours` and `limit` params, `limit` capped at `http.max_limit`.
+fn window_params(params: &HashMap<String, String>, config: &HttpConfig) -> Result<(i64, usize), Failure> {
+    let number = |key: &str, default: usize| match params.get(key) {
+        Some(value) => value.parse::<usize>().map_err(|_| (StatusCode::BAD_REQUEST, format!("`{}` must be a positive integer", key))),
+        None => Ok(default),
+    };
+    let hours = number("hours", DEFAULT_HOURS as usize)? as i64;
+    let limit = number("limit", DEFAULT_LIMIT)?.min(config.max_limit);
+    Ok((hours, limit))
+}
+
+/// The `access_token` param of `/news/stream`, if it is a known token.
+fn stream_token<'a>(request: &Request, tokens: &'a HashMap<String, String>) -> Option<&'a str> {
+    if request.uri().path() != "/news/stream" {
+        return None;
+    }
+    let Query(params) = Query::<HashMap<String, String>>::try_from_uri(request.uri()).ok()?;
+    authorize_token(params.get("access_token")?, tokens)
+}
+
+async fn require_token(State(state): State<HttpState>, request: Request, next: Next) -> Response {
+    let tokens = &state.config.tokens;
+    if !tokens.is_empty() && authorize(request.headers(), tokens).or_else(|| stream_token(&request, tokens)).is_none() {
+        warn!("Rejected an HTTP request with a missing or unknown token.");
+        return error(StatusCode::UNAUTHORIZED, "Missing or unknown bearer token");
+    }
+    next.run(request).await
+}
+
+async fn latest_news(State(state): State<HttpState>, Query(params): Params) -> Response {
+    let db = match storage(&state) {
+        Ok(db) => db,
+        Err((status, message)) => return error(status, message),
+    };
+    let (hours, limit) = match window_params(&params, &state.config) {
+        Ok(window) => window,
+        Err((status, message)) => return error(status, message),
+    };
+    let since = (clock::now() - UtcDuration::hours(hours)).to_rfc3339_opts(SecondsFormat::Secs, true);
+    match db.latest_articles(&since, limit as i64).await {
+        Ok(articles) => Json(articles).into_response(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Latest news failed: {}", e)),
+    }
+}
+
+async fn search_news(State(state): State<HttpState>, Query(params): Params) -> Response {
+    let db = match storage(&state) {
+        Ok(db) => db,
+        Err((status, message)) => return error(status, message),
+    };
+    let (hours, limit) = match window_params(&params, &state.config) {
+        Ok(window) => window,
+        Err((status, message)) => return error(status, message),
+    };
+    let filter = if params.contains_key("tickers") {
+        QueryFilter::Tickers(list_param(&params, "tickers"))
+    } else if params.contains_key("keywords") {
+        QueryFilter::Keywords(list_param(&params, "keywords"))
+    } else if params.contains_key("topics") {
+        QueryFilter::Categories(list_param(&params, "topics"))
+    } else if params.contains_key("entities") {
+        QueryFilter::Entities(list_param(&params, "entities"))
+    } else if params.contains_key("sentiment") {
+        let labels: Result<Vec<SentimentLabel>, String> = list_param(&params, "sentiment")
+            .iter()
+            .map(|label| label.parse())
+            .collect();
+        match labels {
+            Ok(labels) => QueryFilter::Sentiment(labels),
+            Err(e) => return error(StatusCode::BAD_REQUEST, e),
+        }
+    } else {
+        return error(StatusCode::BAD_REQUEST, "One of `tickers`, `keywords`, `topics`, `entities` or `sentiment` is required");
+    };
+    match article_query::run(db.as_ref(), &article_query::Query { hours, limit, ..article_query::Query::new(filter) }).await {
+        Ok(articles) => Json(articles).into_response(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Search failed: {}", e)),
+    }
+}
+
+async fn ticker_sentiment(State(state): State<HttpState>, Path(ticker): Path<String>, Query(params): Params) -> Response {
+    let db = match storage(&state) {
+        Ok(db) => db,
+        Err((status, message)) => return error(status, message),
+    };
+    let (hours, _) = match window_params(&params, &state.config) {
+        Ok(window) => window,
+        Err((status, message)) => return error(status, message),
+    };
+    let ticker = ticker.trim().to_uppercase();
+    let now = clock::now();
+    let from = (now - UtcDuration::hours(hours)).to_rfc3339_opts(SecondsFormat::Secs, true);
+    let to = now.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let articles = match db.articles_with_tickers(std::slice::from_ref(&ticker), &from, &to).await {
+        Ok(articles) => articles,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Sentiment failed: {}", e)),
+    };
+
+    let mut labels: BTreeMap<&str, u64> = SentimentLabel::ALL.iter().map(|label| (label.to_str(), 0)).collect();
+    for label in articles.iter().filter_map(|a| a.sentiment) {
+        *labels.entry(label.to_str()).or_default() += 1;
+    }
+    let scores: Vec<f64> = articles.iter().filter_map(|a| a.sentiment_score).collect();
+    let mean_score = (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64);
+    Json(json!({
+        "ticker": ticker,
+        "from": from,
+        "to": to,
+        "articles": articles.len(),
+        "mean_score": mean_score,
+        "labels": labels,
+    }))
+    .into_response()
+}
+
+async fn poll_provider(State(state): State<HttpState>, Path(name): Path<String>, body: Option<Json<Value>>) -> Response {
+    let Some(provider) = state.poll.providers.iter().find(|p| p.name() == name) else {
+        return error(StatusCode::NOT_FOUND, format!("Unknown provider `{}`", name));
+    };
+    let args = body.map(|Json(args)| args).unwrap_or_else(|| json!({}));
+    match autoscale::track(provider.name(), provider.fetch(Arc::new(args))).await {
+        Ok(news) => Json(news).into_response(),
+        Err(e) => error(StatusCode::BAD_GATEWAY, format!("{} provider polling failed: {}", name, e)),
+    }
+}
+
+/// Articles stored after a cursor about some tickers, read from the storage batch by batch.
+pub(crate) struct ArticleFeed {
+    db: Arc<dyn Storage>,
+    /// Uppercased, every article when empty.
+    tickers: Vec<String>,
+    limit: i64,
+    feed: StoredFeed,
+}
+impl ArticleFeed {
+    /// Articles about `tickers` read from `feed`, `limit` at most at once.
+    pub(crate) fn new(db: Arc<dyn Storage>, tickers: &[String], limit: usize, feed: StoredFeed) -> Self {
+        Self { db, tickers: tickers.iter().map(|t| t.to_uppercase()).collect(), limit: limit as i64, feed }
+    }
+
+    /// Articles stored since the last batch, with their sequence number and storage time.
+    pub(crate) async fn next_batch(&mut self) -> Result<Vec<SequencedArticle>, OpError> {
+        let mut batch = self.feed.next_batch(self.db.as_ref(), self.limit).await?;
+        batch.retain(|stored| self.tickers.is_empty() || stored.article.tickers.iter().any(|t| self.tickers.contains(&t.to_uppercase())));
+        Ok(batch)
+    }
+}
+
+async fn stream_news(State(state): State<HttpState>, headers: HeaderMap, Query(params): Params) -> Response {
+    let db = match storage(&state) {
+        Ok(db) => db.clone(),
+        Err((status, message)) => return error(status, message),
+    };
+    // Event ids are sequence numbers, see `StoredFeed`.
+    let last_event = headers.get("last-event-id").and_then(|id| id.to_str().ok()?.parse().ok());
+    let feed = last_event.map_or_else(|| StoredFeed::since(now()), StoredFeed::after);
+    let feed = ArticleFeed::new(db, &list_param(&params, "tickers"), state.config.max_limit, feed);
+    let interval = Duration::from_secs(state.config.stream_interval_secs.max(1));
+
+    let events = futures::stream::unfold((feed, VecDeque::new()), move |(mut feed, mut pending)| async move {
+        while pending.is_empty() {
+            tokio::time::sleep(interval).await;
+            match feed.next_batch().await {
+                Ok(batch) => pending.extend(batch),
+                Err(e) => warn!("News stream failed to read the storage: {}", e),
+            }
+        }
+        let stored = pending.pop_front()?;
+        let event = Event::default().event("article").id(stored.seq.to_string()).data(serde_json::to_string(&stored.article).unwrap_or_default());
+        Some((Ok::<Event, Infallible>(event), (feed, pending)))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+async fn graphql_query(State(state): State<HttpState>, Json(request): Json<async_graphql::Request>) -> Response {
+    let Some(schema) = &state.graphql else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Database is not available");
+    };
+    Json(schema.execute(request).await).into_response()
+}
+
+pub fn router(poll: Arc<PollState>, config: HttpConfig) -> Router {
+    let graphql = match &poll.db {
+        Some(db) if poll.config.graphql.enabled => Some(graphql::schema(db.clone(), poll.config.graphql.clone())),
+        _ => None,
+    };
+    let routes = if poll.config.graphql.enabled { Router::new().route("/graphql", post(graphql_query)) } else { Router::new() };
+    let state = HttpState { poll, config: Arc::new(config), graphql };
+    routes
+        .route("/news/latest", get(latest_news))
+        .route("/news/search", get(search_news))
+        .route("/news/stream", get(stream_news))
+        .route("/sentiment/:ticker", get(ticker_sentiment))
+        .route("/providers/:name/poll", post(poll_provider))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// Serves the REST API on `http.host`:`http.port` in a background task.
+pub async fn spawn(poll: Arc<PollState>, config: HttpConfig) -> std::io::Result<()> {
+    let address = format!("{}:{}", config.host, config.port);
+    let listener = TcpListener::bind(&address).await?;
+    info!("REST API listening on: {}", address);
+    let app = router(poll, config);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("REST API server failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::ValueConfig;
+    use crate::errors::{ApiError, ProviderError};
+    use crate::normalize::NormalizedArticle;
+    use crate::options::FetchType;
+    use crate::provider::{NewsProvider, ProviderFuture, ProviderHealth};
+    use crate::storage::MemoryStorage;
+
+    struct EchoProvider;
+    impl NewsProvider for EchoProvider {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn supports(&self, _fetch_type: &FetchType) -> bool {
+            true
+        }
+
+        fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async move {
+                if args.get("fail").is_some() {
+                    return Err(ApiError::NoEndpointProvided.into());
+                }
+                Ok(json!({ "args": *args }))
+            })
+        }
+
+        fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            self.fetch(Arc::new(json!({})))
+        }
+
+        fn health(&self) -> ProviderHealth {
+            ProviderHealth::Healthy
+        }
+    }
+
+    async fn serve(tokens: &[(&str, &str)]) -> (String, Arc<MemoryStorage>) {
+        let config = Arc::new(ValueConfig::from_toml(include_str!("../config.toml.example")).unwrap());
+        let storage = Arc::new(MemoryStorage::new());
+        let article = |id: &str, hours_ago: i64, score: f64| -> NormalizedArticle {
+            let published_at = (clock::now() - UtcDuration::hours(hours_ago)).to_rfc3339_opts(SecondsFormat::Secs, true);
+            NormalizedArticle::test(id)
+                .published_at(&published_at)
+                .tickers(&["NVDA"])
+                .sentiment_score(score)
+                .sentiment(if score > 0.0 { SentimentLabel::Bullish } else { SentimentLabel::Bearish })
+        };
+        storage.store_articles(&[article("1", 2, 0.5), article("2", 1, -0.3), article("3", 48, 0.9)]).await.unwrap();
+        let poll = Arc::new(PollState::with_providers(config, vec![Arc::new(EchoProvider)]).with_database(storage.clone()));
+        let http_config = HttpConfig {
+            tokens: tokens.iter().map(|(name, token)| (name.to_string(), token.to_string())).collect(),
+            stream_interval_secs: 1,
+            ..HttpConfig::default()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(poll, http_config)).await });
+        (format!("http://{}", address), storage)
+    }
+
+    #[tokio::test]
+    async fn serves_news_sentiment_and_polling() {
+        let (base, _) = serve(&[]).await;
+        let client = reqwest::Client::new();
+        let get = |path: &str| client.get(format!("{}{}", base, path)).send();
+
+        let latest: Value = get("/news/latest?limit=1").await.unwrap().json().await.unwrap();
+        assert_eq!(latest.as_array().map(|a| a.len()), Some(1));
+        assert_eq!(latest[0]["id"], "2");
+
+        let found: Value = get("/news/search?tickers=nvda,amd").await.unwrap().json().await.unwrap();
+        assert_eq!(found.as_array().map(|a| a.len()), Some(2));
+        assert_eq!(get("/news/search?limit=5").await.unwrap().status().as_u16(), 400);
+
+        let sentiment: Value = get("/sentiment/nvda").await.unwrap().json().await.unwrap();
+        assert_eq!(sentiment["ticker"], "NVDA");
+        assert_eq!(sentiment["articles"], 2);
+        assert_eq!(sentiment["labels"]["bullish"], 1);
+        assert_eq!(sentiment["labels"]["bearish"], 1);
+
+        let poll = |name: &str, body: Value| client.post(format!("{}/providers/{}/poll", base, name)).json(&body).send();
+        let graphql: Value = client.post(format!("{}/graphql", base)).json(&json!({ "query": "{ sentiment { articles } }" }))
+            .send().await.unwrap().json().await.unwrap();
+        assert_eq!(graphql["data"]["sentiment"]["articles"], 2);
+
+        let polled: Value = poll("echo", json!({ "symbols": "NVDA" })).await.unwrap().json().await.unwrap();
+        assert_eq!(polled["args"]["symbols"], "NVDA");
+        assert_eq!(poll("echo", json!({ "fail": true })).await.unwrap().status().as_u16(), 502);
+        assert_eq!(poll("unknown", json!({})).await.unwrap().status().as_u16(), 404);
+    }
+
+    #[tokio::test]
+    async fn requires_a_known_token_when_configured() {
+        let (base, _) = serve(&[("dashboard", "s3cret")]).await;
+        let client = reqwest::Client::new();
+        let url = format!("{}/news/latest", base);
+        assert_eq!(client.get(&url).send().await.unwrap().status().as_u16(), 401);
+        assert_eq!(client.get(&url).bearer_auth("s3cret").send().await.unwrap().status().as_u16(), 200);
+        assert_eq!(client.get(format!("{}?access_token=s3cret", url)).send().await.unwrap().status().as_u16(), 401);
+        let stream = format!("{}/news/stream?access_token=s3cret", base);
+        assert_eq!(client.get(&stream).send().await.unwrap().status().as_u16(), 200);
+    }
+
+    #[tokio::test]
+    async fn streams_the_newly_stored_articles() {
+        let (base, storage) = serve(&[]).await;
+        let mut response = reqwest::Client::new().get(format!("{}/news/stream?tickers=aapl", base)).send().await.unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        let article = |id: &str, ticker: &str| NormalizedArticle::test(id).tickers(&[ticker]);
+        storage.store_articles(&[article("4", "NVDA"), article("5", "AAPL")]).await.unwrap();
+
+        let mut received = String::new();
+        while !received.contains("\n\n") {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk()).await.unwrap().unwrap().unwrap();
+            received.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        assert!(received.starts_with("event: article\n"));
+        assert!(received.contains(r#""id":"5""#));
+        assert!(!received.contains(r#""id":"1""#) && !received.contains(r#""id":"4""#));
+        assert!(received.contains(&format!("\nid: {}\n", storage.last_seq().await.unwrap())));
+    }
+}
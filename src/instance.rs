@@ -0,0 +1,98 @@
+//! Single-instance guard.
+//!
+//! When `instance.lock_file` is set, the daemon takes an exclusive lock on that file at startup and
+//! writes its PID into it. A second copy started with the same config finds the lock held and exits
+//! instead of ingesting the same data twice. The lock is released by the OS when the process exits,
+//! so a crash never leaves a stale lock behind.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use tracing::info;
+
+use crate::config::ValueConfig;
+
+#[derive(Debug, Error)]
+pub enum InstanceError {
+    #[error("Another instance is already running (lock file: {}, pid: {})", path.display(), pid.as_deref().unwrap_or("unknown"))]
+    AlreadyRunning { path: PathBuf, pid: Option<String> },
+
+    #[error("Failed to acquire lock file {}: {source}", path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+}
+
+/// Exclusive lock held for the lifetime of the process.
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+    // Keeps the lock: it is released when the file is closed.
+    _file: File,
+}
+impl InstanceLock {
+    /// Locks `path`, creating it if needed, and records the current PID in it.
+    pub fn acquire(path: &Path) -> Result<Self, InstanceError> {
+        let io_error = |source| InstanceError::Io { path: path.to_path_buf(), source };
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(io_error)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut pid = String::new();
+                let _ = file.read_to_string(&mut pid);
+                let pid = Some(pid.trim().to_string()).filter(|p| !p.is_empty());
+                return Err(InstanceError::AlreadyRunning { path: path.to_path_buf(), pid });
+            }
+            Err(TryLockError::Error(e)) => return Err(io_error(e)),
+        }
+
+        file.set_len(0).map_err(io_error)?;
+        file.seek(SeekFrom::Start(0)).map_err(io_error)?;
+        writeln!(file, "{}", std::process::id()).map_err(io_error)?;
+        file.flush().map_err(io_error)?;
+        info!("Acquired instance lock {} | PID: {}", path.display(), std::process::id());
+
+        Ok(Self { path: path.to_path_buf(), _file: file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Acquires the lock configured in `instance.lock_file`, if any.
+pub fn lock_from_config(config: &ValueConfig) -> Result<Option<InstanceLock>, InstanceError> {
+    config
+        .instance
+        .lock_file
+        .as_deref()
+        .map(|path| InstanceLock::acquire(Path::new(path)))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_second_lock_finds_the_first_one() {
+        let path = std::env::temp_dir().join(format!("news_data_instance_{}.lock", std::process::id()));
+        let lock = InstanceLock::acquire(&path).unwrap();
+        match InstanceLock::acquire(&path) {
+            Err(InstanceError::AlreadyRunning { pid, .. }) => assert_eq!(pid, Some(std::process::id().to_string())),
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+
+        // Released with the first lock.
+        drop(lock);
+        assert_eq!(InstanceLock::acquire(&path).unwrap().path(), path);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+//! Keyword extraction for articles without provider topics.
+//!
+//! Alpha Vantage and Polygon.io tag articles with topics, but Finnhub, GDELT and most feeds do not.
+//! Before an article is stored, its `keywords` are set: its topics lowercased when it has some,
+//! otherwise the best phrases of its title and summary ranked with RAKE (Rapid Automatic Keyword
+//! Extraction). RAKE splits the text into candidate phrases at punctuation and stop words, then
+//! scores each word by its degree (the length of the phrases it appears in) over its frequency.
+//! It needs no corpus, so one article is enough.
+//!
+//! The stored keywords back the `keyword_news` subscriptions and the `trending_keywords` queries
+//! of the websocket server.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::config::KeywordsConfig;
+use crate::normalize::NormalizedArticle;
+
+/// English stop words, completed by `keywords.stopwords`.
+pub const STOPWORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "also", "am", "an", "and", "any", "are",
+    "as", "at", "be", "because", "been", "before", "being", "below", "between", "both", "but", "by",
+    "can", "could", "did", "do", "does", "doing", "down", "during", "each", "few", "for", "from",
+    "further", "had", "has", "have", "having", "he", "her", "here", "hers", "him", "his", "how", "i",
+    "if", "in", "into", "is", "it", "its", "itself", "just", "more", "most", "new", "no", "nor", "not",
+    "now", "of", "off", "on", "once", "only", "or", "other", "our", "ours", "out", "over", "own",
+    "said", "same", "says", "she", "should", "so", "some", "such", "than", "that", "the", "their",
+    "theirs", "them", "then", "there", "these", "they", "this", "those", "through", "to", "too",
+    "under", "until", "up", "very", "was", "we", "were", "what", "when", "where", "which", "while",
+    "who", "whom", "why", "will", "with", "would", "you", "your", "yours",
+];
+
+/// Extracts keywords with RAKE.
+pub struct KeywordExtractor {
+    stopwords: HashSet<String>,
+    max_keywords: usize,
+    max_phrase_words: usize,
+}
+impl KeywordExtractor {
+    pub fn new(config: &KeywordsConfig) -> Self {
+        let stopwords = STOPWORDS
+            .iter()
+            .map(|w| w.to_string())
+            .chain(config.stopwords.iter().map(|w| w.to_lowercase()))
+            .collect();
+        Self { stopwords, max_keywords: config.max_keywords, max_phrase_words: config.max_phrase_words.max(1) }
+    }
+
+    /// Candidate phrases of `text`, lowercased, in order of appearance.
+    fn phrases(&self, text: &str) -> Vec<Vec<String>> {
+        let mut phrases = Vec::new();
+        for fragment in text.split(|c: char| !(c.is_alphanumeric() || c.is_whitespace() || c == '-' || c == '\'')) {
+            let mut phrase: Vec<String> = Vec::new();
+            for word in fragment.split_whitespace() {
+                let word = word.trim_matches(|c: char| c == '-' || c == '\'').to_lowercase();
+                let keep = word.chars().count() > 1
+                    && !word.chars().all(|c| c.is_numeric())
+                    && !self.stopwords.contains(&word);
+                if keep {
+                    phrase.push(word);
+                } else if !phrase.is_empty() {
+                    phrases.push(std::mem::take(&mut phrase));
+                }
+            }
+            if !phrase.is_empty() {
+                phrases.push(phrase);
+            }
+        }
+        // Long phrases are usually sentences without stop words rather than keywords.
+        phrases.retain(|p| p.len() <= self.max_phrase_words);
+        phrases
+    }
+
+    /// Up to `max_keywords` keywords of `text`, best first.
+    pub fn extract(&self, text: &str) -> Vec<String> {
+        let phrases = self.phrases(text);
+        let mut frequency: HashMap<&str, f64> = HashMap::new();
+        let mut degree: HashMap<&str, f64> = HashMap::new();
+        for phrase in &phrases {
+            for word in phrase {
+                *frequency.entry(word).or_default() += 1.0;
+                *degree.entry(word).or_default() += phrase.len() as f64;
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut scored: Vec<(String, f64)> = Vec::new();
+        for phrase in &phrases {
+            let keyword = phrase.join(" ");
+            if !seen.insert(keyword.clone()) {
+                continue;
+            }
+            let score = phrase.iter().map(|w| degree[w.as_str()] / frequency[w.as_str()]).sum();
+            scored.push((keyword, score));
+        }
+        // Stable sort: ties keep their order of appearance.
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(self.max_keywords).map(|(keyword, _)| keyword).collect()
+    }
+
+    /// Keywords of `article`: its topics when it has some, extracted from its title and summary otherwise.
+    pub fn keywords_of(&self, article: &NormalizedArticle) -> Vec<String> {
+        if !article.topics.is_empty() {
+            let mut topics: Vec<String> = article.topics.iter().map(|t| t.to_lowercase()).collect();
+            topics.dedup();
+            return topics;
+        }
+        // The translation when there is one: the stop words are English.
+        let (title, summary) = match &article.translation {
+            Some(translation) => (translation.title.as_deref(), translation.summary.as_deref()),
+            None => (article.title.as_deref(), article.summary.as_deref()),
+        };
+        let text = [title, summary]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(". ");
+        self.extract(&text)
+    }
+}
+impl Default for KeywordExtractor {
+    fn default() -> Self {
+        Self::new(&KeywordsConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_multi_word_phrases_first() {
+        let extractor = KeywordExtractor::default();
+        let keywords = extractor.extract(
+            "Federal Reserve raises rates as inflation stays elevated. The Federal Reserve said markets were calm in 2024.",
+        );
+        assert_eq!(keywords, vec!["federal reserve raises rates", "inflation stays elevated", "federal reserve", "markets", "calm"]);
+    }
+
+    #[test]
+    fn keeps_provider_topics() {
+        let extractor = KeywordExtractor::default();
+        let article = NormalizedArticle::test("a").provider("alphavantage").title("Earnings beat").topics(&["Earnings", "Technology"]);
+        assert_eq!(extractor.keywords_of(&article), vec!["earnings", "technology"]);
+    }
+
+    #[test]
+    fn extracts_from_the_translation() {
+        let extractor = KeywordExtractor::default();
+        let article = NormalizedArticle::test("b").title("La BCE relève ses taux directeurs").language("fr").translated("ECB raises its key rates");
+        assert_eq!(extractor.keywords_of(&article), vec!["ecb raises", "key rates"]);
+    }
+}
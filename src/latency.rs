@@ -0,0 +1,215 @@
+//! Latency of the articles from publication to subscriber delivery.
+//!
+//! A `latency.sample_rate` share of the articles, picked by a hash of their provider and id so the
+//! same ones are followed at every stage, is timed from its `published_at` at each stage of the
+//! path: `ingested` once fetched by the polling loop, `stored` once first written to the database,
+//! `delivered` once pushed to a websocket subscriber. The last `latency.window` latencies of each
+//! stage are kept, and their percentiles returned by the `status` admin command under `latency`.
+//! Each latency is also recorded in the `news_article_latency_seconds` histogram of the `metrics`
+//! facade, labelled by stage. Articles published more than `latency.max_age_secs` before a stage
+//! (backfills, replays of old articles) are left out of it.
+//!
+//! With `latency.slo_secs`, the p90 latency to delivery is checked every
+//! `latency.check_interval_secs` once `latency.min_samples` articles were delivered: a
+//! `latency_slo` alert is raised while it exceeds the SLO, and resolved once back under.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use tracing::debug;
+
+use crate::alerts::{Alert, Alerter};
+use crate::clock;
+use crate::config::LatencyConfig;
+use crate::freshness::Percentiles;
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+
+const LATENCY_HISTOGRAM: &str = "news_article_latency_seconds";
+const SLO_RULE: &str = "latency_slo";
+
+/// Stage of the path of an article.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Ingested,
+    Stored,
+    Delivered,
+}
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Ingested => "ingested",
+            Stage::Stored => "stored",
+            Stage::Delivered => "delivered",
+        }
+    }
+}
+
+/// Latency to a stage since publication, over its last `samples` articles.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct StageLatency {
+    pub samples: usize,
+    pub latency_secs: Percentiles,
+}
+
+/// Latency to delivery against `latency.slo_secs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slo {
+    /// No SLO, or too few articles delivered to tell.
+    Unknown,
+    Met,
+    /// The p90 latency to delivery, in seconds.
+    Breached(i64),
+}
+
+/// Recent latencies of every stage, given a `LatencyConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct Latency {
+    config: LatencyConfig,
+    latencies: HashMap<Stage, VecDeque<i64>>,
+}
+impl Latency {
+    pub fn new(config: &LatencyConfig) -> Self {
+        Self { config: config.clone(), ..Self::default() }
+    }
+
+    /// Whether `article` is one of the sampled ones.
+    pub fn sampled(&self, article: &NormalizedArticle) -> bool {
+        let mut hasher = DefaultHasher::new();
+        (&article.provider, &article.id).hash(&mut hasher);
+        (hasher.finish() % 10_000) < (self.config.sample_rate.clamp(0.0, 1.0) * 10_000.0) as u64
+    }
+
+    /// Records `article` reaching `stage` at `at`. Returns the latency in seconds, `None` when left
+    /// out.
+    pub fn record(&mut self, stage: Stage, article: &NormalizedArticle, at: DateTime<Utc>) -> Option<i64> {
+        if !self.sampled(article) {
+            return None;
+        }
+        let latency = (at - parse_timestamp(article.published_at.as_deref()?)?).num_seconds().max(0);
+        if latency as u64 > self.config.max_age_secs {
+            return None;
+        }
+        let latencies = self.latencies.entry(stage).or_default();
+        latencies.push_back(latency);
+        while latencies.len() > self.config.window.max(1) {
+            latencies.pop_front();
+        }
+        Some(latency)
+    }
+
+    pub fn slo(&self) -> Slo {
+        let delivered = self.latencies.get(&Stage::Delivered);
+        let samples = delivered.map(VecDeque::len).unwrap_or_default();
+        if self.config.slo_secs == 0 || samples == 0 || samples < self.config.min_samples {
+            return Slo::Unknown;
+        }
+        match Percentiles::of(delivered.into_iter().flatten().copied().collect()).p90 {
+            Some(p90) if p90 as u64 > self.config.slo_secs => Slo::Breached(p90),
+            _ => Slo::Met,
+        }
+    }
+
+    pub fn report(&self) -> BTreeMap<String, StageLatency> {
+        self.latencies
+            .iter()
+            .map(|(stage, latencies)| {
+                let latency = StageLatency {
+                    samples: latencies.len(),
+                    latency_secs: Percentiles::of(latencies.iter().copied().collect()),
+                };
+                (stage.as_str().to_string(), latency)
+            })
+            .collect()
+    }
+}
+
+fn state() -> &'static Mutex<Latency> {
+    static STATE: OnceLock<Mutex<Latency>> = OnceLock::new();
+    STATE.get_or_init(Mutex::default)
+}
+
+fn with_state<T>(f: impl FnOnce(&mut Latency) -> T) -> T {
+    f(&mut state().lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Applies the `[latency]` section of the config.
+pub fn configure(config: &LatencyConfig) {
+    with_state(|latency| latency.config = config.clone());
+}
+
+/// Records `articles` reaching `stage` now.
+pub fn record<'a>(stage: Stage, articles: impl IntoIterator<Item = &'a NormalizedArticle>) {
+    let at = clock::now();
+    let latencies: Vec<i64> = with_state(|latency| articles.into_iter().filter_map(|article| latency.record(stage, article, at)).collect());
+    for latency in latencies {
+        metrics::histogram!(LATENCY_HISTOGRAM, "stage" => stage.as_str()).record(latency as f64);
+    }
+}
+
+/// Checks the latency to delivery against `latency.slo_secs` every `latency.check_interval_secs`,
+/// raising and resolving the `latency_slo` alert with `alerter`, in a background task.
+pub fn spawn(alerter: Arc<Alerter>, config: &LatencyConfig) -> Option<JoinHandle<()>> {
+    if config.slo_secs == 0 {
+        return None;
+    }
+    let (interval, slo_secs) = (Duration::from_secs(config.check_interval_secs.max(1)), config.slo_secs);
+    Some(tokio::spawn(async move {
+        loop {
+            sleep(interval).await;
+            match with_state(|latency| latency.slo()) {
+                Slo::Unknown => debug!("Too few articles delivered to check the latency SLO."),
+                Slo::Met => alerter.resolve(SLO_RULE, Stage::Delivered.as_str()).await,
+                Slo::Breached(p90) => {
+                    let message = format!("Articles delivered {} s after their publication (p90), over the SLO of {} s.", p90, slo_secs);
+                    alerter.raise(&Alert::new(SLO_RULE, Stage::Delivered.as_str(), message)).await;
+                }
+            }
+        }
+    }))
+}
+
+/// Latency percentiles of every stage.
+pub fn report() -> BTreeMap<String, StageLatency> {
+    with_state(|latency| latency.report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_the_stages_and_checks_the_slo() {
+        let config = LatencyConfig { sample_rate: 1.0, window: 3, max_age_secs: 3_600, slo_secs: 300, min_samples: 2, check_interval_secs: 60 };
+        let mut latency = Latency::new(&config);
+        let at = parse_timestamp("2024-05-01T10:00:00Z").unwrap();
+        assert_eq!(latency.record(Stage::Stored, &NormalizedArticle::test("1").published_at("2024-05-01T09:59:00Z"), at), Some(60));
+        assert_eq!(latency.record(Stage::Delivered, &NormalizedArticle::test("1").published_at("2024-05-01T09:59:00Z"), at), Some(60));
+        assert_eq!(latency.slo(), Slo::Unknown);
+
+        assert_eq!(latency.record(Stage::Delivered, &NormalizedArticle::test("2").published_at("2024-05-01T09:58:00Z"), at), Some(120));
+        assert_eq!(latency.slo(), Slo::Met);
+        for id in ["3", "4"] {
+            latency.record(Stage::Delivered, &NormalizedArticle::test(id).published_at("2024-05-01T09:50:00Z"), at);
+        }
+        assert_eq!(latency.slo(), Slo::Breached(600));
+        // Replayed long after, or without a publication time.
+        assert_eq!(latency.record(Stage::Delivered, &NormalizedArticle::test("5").published_at("2024-04-01T10:00:00Z"), at), None);
+        assert_eq!(latency.record(Stage::Delivered, &NormalizedArticle::test("6"), at), None);
+
+        let report = latency.report();
+        assert_eq!(report["stored"].samples, 1);
+        assert_eq!(report["delivered"].latency_secs, Percentiles { p50: Some(600), p90: Some(600), max: Some(600) });
+
+        // The same articles are sampled at every stage.
+        let half = Latency::new(&LatencyConfig { sample_rate: 0.5, ..config });
+        let sampled: Vec<bool> = (0..100).map(|id| half.sampled(&NormalizedArticle::test(&id.to_string()))).collect();
+        assert!(sampled.iter().any(|s| *s) && !sampled.iter().all(|s| *s));
+        assert_eq!(sampled, (0..100).map(|id| half.sampled(&NormalizedArticle::test(&id.to_string()))).collect::<Vec<_>>());
+        assert!(Latency::new(&LatencyConfig { sample_rate: 0.0, ..LatencyConfig::default() }).record(Stage::Stored, &NormalizedArticle::test("1").published_at("2024-05-01T09:59:00Z"), at).is_none());
+    }
+}
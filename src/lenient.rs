@@ -0,0 +1,167 @@
+//! Lenient deserialization of provider payloads.
+//!
+//! Providers do not always send the types they document: numbers come as strings (`"0.52"`),
+//! figures documented as strings come as numbers, and missing values show up as `null`, `""` or
+//! `"N/A"` instead of an absent key. The helpers below are used with `deserialize_with` on the
+//! fields of the typed models, so one odd value does not fail a whole response. Values that are
+//! present but meaningless (e.g. `"abc"` for a score) are still rejected.
+
+use std::str::FromStr;
+
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use serde_json::{Number, Value};
+
+/// Strings standing for a missing value, compared case-insensitively.
+const MISSING: [&str; 6] = ["", "null", "none", "n/a", "na", "-"];
+
+fn is_missing(text: &str) -> bool {
+    let text = text.trim();
+    MISSING.iter().any(|m| text.eq_ignore_ascii_case(m))
+}
+
+/// Number type a value can be leniently read as.
+pub trait LenientNumber: Sized + FromStr {
+    fn from_number(number: &Number) -> Option<Self>;
+
+    fn from_text(text: &str) -> Option<Self> {
+        text.trim().parse().ok()
+    }
+}
+
+impl LenientNumber for f64 {
+    fn from_number(number: &Number) -> Option<Self> {
+        number.as_f64()
+    }
+
+    /// Only finite values: `NaN` and `inf` parse, but cannot be serialized back to JSON.
+    fn from_text(text: &str) -> Option<Self> {
+        text.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+    }
+}
+
+impl LenientNumber for i64 {
+    fn from_number(number: &Number) -> Option<Self> {
+        number.as_i64().or_else(|| number.as_f64().and_then(integral).map(|n| n as i64))
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        let text = text.trim();
+        text.parse().ok().or_else(|| text.parse::<f64>().ok().and_then(integral).map(|n| n as i64))
+    }
+}
+
+impl LenientNumber for u64 {
+    fn from_number(number: &Number) -> Option<Self> {
+        number.as_u64().or_else(|| number.as_f64().and_then(integral).filter(|n| *n >= 0.0).map(|n| n as u64))
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        let text = text.trim();
+        text.parse().ok().or_else(|| text.parse::<f64>().ok().and_then(integral).filter(|n| *n >= 0.0).map(|n| n as u64))
+    }
+}
+
+/// `n` if it is a whole number within the range of 64-bit integers (e.g. `12.0`, `1e3`).
+fn integral(n: f64) -> Option<f64> {
+    (n.is_finite() && n.fract() == 0.0 && n.abs() < 9.2e18).then_some(n)
+}
+
+/// A number given as a number or a numeric string, `None` when missing.
+/// Use with `#[serde(default, deserialize_with = "lenient::opt_number")]`.
+pub fn opt_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: LenientNumber,
+{
+    let value = Value::deserialize(deserializer)?;
+    let parsed = match &value {
+        Value::Null => return Ok(None),
+        Value::String(text) if is_missing(text) => return Ok(None),
+        Value::String(text) => T::from_text(text),
+        Value::Number(number) => T::from_number(number),
+        _ => None,
+    };
+    parsed
+        .map(Some)
+        .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Other(&value.to_string()), &"a number"))
+}
+
+/// Same as `opt_number` for a required number, defaulting to zero when missing.
+/// Use with `#[serde(default, deserialize_with = "lenient::number")]`.
+pub fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: LenientNumber + Default,
+{
+    opt_number(deserializer).map(Option::unwrap_or_default)
+}
+
+/// A string given as a string or a number, `None` when missing.
+/// Use with `#[serde(default, deserialize_with = "lenient::opt_string")]`.
+pub fn opt_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        Value::String(text) if is_missing(&text) => Ok(None),
+        Value::String(text) => Ok(Some(text)),
+        Value::Number(number) => Ok(Some(number.to_string())),
+        Value::Bool(flag) => Ok(Some(flag.to_string())),
+        other => Err(de::Error::invalid_type(de::Unexpected::Other(&other.to_string()), &"a string")),
+    }
+}
+
+/// A list, empty when missing or `null`.
+/// Use with `#[serde(default, deserialize_with = "lenient::list")]`.
+pub fn list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<Vec<T>>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Model {
+        #[serde(default, deserialize_with = "opt_number")]
+        score: Option<f64>,
+        #[serde(default, deserialize_with = "number")]
+        count: u64,
+        #[serde(default, deserialize_with = "opt_string")]
+        label: Option<String>,
+        #[serde(default, deserialize_with = "list")]
+        tags: Vec<String>,
+    }
+
+    fn parse(value: Value) -> Result<Model, serde_json::Error> {
+        serde_json::from_value(value)
+    }
+
+    #[test]
+    fn reads_numbers_given_as_strings() {
+        let model = parse(json!({ "score": " 0.52", "count": "1e3", "label": 7, "tags": null })).unwrap();
+        assert_eq!(model, Model { score: Some(0.52), count: 1000, label: Some("7".to_string()), tags: Vec::new() });
+    }
+
+    #[test]
+    fn reads_missing_markers_as_missing() {
+        let model = parse(json!({ "score": "N/A", "count": "", "label": "none" })).unwrap();
+        assert_eq!(model, Model { score: None, count: 0, label: None, tags: Vec::new() });
+    }
+
+    #[test]
+    fn rejects_meaningless_values() {
+        assert!(parse(json!({ "score": "abc" })).is_err());
+        assert!(parse(json!({ "score": "NaN" })).is_err());
+        assert!(parse(json!({ "count": -3 })).is_err());
+        assert!(parse(json!({ "count": 1.5 })).is_err());
+        assert!(parse(json!({ "label": ["a"] })).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! News data from MarketAux, Alpha Vantage, FMP and other providers.
+//!
+//! The crate backs the `news_data` service (polling daemon and websocket server), and exposes the
+//! pieces it is built from to services embedding them:
+//!
+//! - provider clients: [`MarketAuxApiClient`], [`AlphaVantageApiClient`], [`FMPClient`] and the
+//!   modules of the other providers, all behind the [`NewsProvider`] trait,
+//! - their models, and [`NormalizedArticle`], the provider-agnostic article schema,
+//! - [`SharedLockedCache`], the response cache the clients share,
+//! - [`DatabaseOps`] and the [`Storage`] trait, to store articles in MongoDB,
+//! - the error types of [`errors`] and the query parameters of [`options`],
+//! - [`ValueConfig`], read from `config.toml` (see `config.toml.example`),
+//! - with the `arrow-ipc` feature, `columnar`: stored articles as Arrow record batches and IPC
+//!   buffers, to hand large result sets to analytics tooling without JSON.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use news_data::{MarketAuxApiClient, SharedLockedCache, ValueConfig};
+//! use serde_json::json;
+//! use tokio::sync::Mutex;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = Arc::new(ValueConfig::new()?);
+//! let cache = Arc::new(Mutex::new(SharedLockedCache::new(100)));
+//! let marketaux = MarketAuxApiClient::new(Arc::new(reqwest::Client::new()), cache, config);
+//! let news = marketaux.poll(Arc::new(json!({ "endpoint": "all", "symbols": "NVDA" }))).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The modules hidden from the documentation run the service itself (scheduling, websocket
+//! server, maintenance commands). They are public for the `news_data` binary, not as a stable API.
+
+#![allow(dead_code)]
+#![allow(unused_imports)]
+
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use alphavantage::AlphaVantageApiResponse;
+use marketaux::MarketAuxResponse;
+use normalize::NormalizedArticle;
+
+// Provider clients and their models.
+pub mod provider;
+pub mod marketaux;
+pub mod alphavantage;
+pub mod fmp;
+pub mod finnhub;
+pub mod polygon;
+pub mod gdelt;
+pub mod rss;
+pub mod reddit;
+pub mod request;
+pub mod server_types;
+pub mod normalize;
+
+// Caching, storage, configuration and errors.
+pub mod cache;
+pub mod db;
+pub mod storage;
+pub mod config;
+pub mod errors;
+pub mod options;
+#[cfg(feature = "arrow-ipc")]
+pub mod columnar;
+
+// Article enrichment and analysis.
+pub mod sentiment;
+pub mod taxonomy;
+pub mod keywords;
+pub mod entities;
+pub mod translate;
+pub mod timeline;
+pub mod comentions;
+pub mod portfolio;
+
+// The service.
+#[doc(hidden)] pub mod encoding;
+#[doc(hidden)] pub mod lenient;
+#[doc(hidden)] pub mod fixtures;
+#[doc(hidden)] pub mod diagnostics;
+#[doc(hidden)] pub mod utils;
+#[doc(hidden)] pub mod logging;
+#[doc(hidden)] pub mod polling;
+#[doc(hidden)] pub mod websocket;
+#[doc(hidden)] pub mod request_parser;
+#[doc(hidden)] pub mod public;
+#[doc(hidden)] pub mod maintenance;
+#[doc(hidden)] pub mod systemd;
+#[doc(hidden)] pub mod instance;
+#[doc(hidden)] pub mod clock;
+#[doc(hidden)] pub mod chaos;
+#[doc(hidden)] pub mod autoscale;
+#[doc(hidden)] pub mod costs;
+#[doc(hidden)] pub mod freshness;
+#[doc(hidden)] pub mod retries;
+#[doc(hidden)] pub mod enumerations;
+#[doc(hidden)] pub mod api_calls;
+#[doc(hidden)] pub mod limiter;
+#[doc(hidden)] pub mod quota;
+#[doc(hidden)] pub mod latency;
+#[doc(hidden)] pub mod captures;
+#[doc(hidden)] pub mod alerts;
+#[doc(hidden)] pub mod templates;
+#[doc(hidden)] pub mod digest;
+#[doc(hidden)] pub mod export;
+#[doc(hidden)] pub mod compare;
+#[doc(hidden)] pub mod coverage;
+#[doc(hidden)] pub mod watchlist;
+#[doc(hidden)] pub mod watermark;
+#[doc(hidden)] pub mod recovery;
+#[doc(hidden)] pub mod backfill;
+#[doc(hidden)] pub mod scheduler;
+#[doc(hidden)] pub mod backup;
+#[doc(hidden)] pub mod purge;
+#[doc(hidden)] pub mod report;
+#[doc(hidden)] pub mod query;
+#[doc(hidden)] pub mod doctor;
+#[doc(hidden)] pub mod webhook;
+#[doc(hidden)] pub mod http;
+#[doc(hidden)] pub mod graphql;
+#[doc(hidden)] pub mod grpc;
+#[doc(hidden)] pub mod subscriptions;
+#[doc(hidden)] pub mod session;
+#[doc(hidden)] pub mod resume;
+#[doc(hidden)] pub mod acks;
+#[doc(hidden)] pub mod dedup;
+#[doc(hidden)] pub mod auth;
+#[doc(hidden)] pub mod protocol;
+#[doc(hidden)] pub mod admin;
+#[doc(hidden)] pub mod snapshots;
+#[doc(hidden)] pub mod repl;
+
+pub use alphavantage::AlphaVantageApiClient;
+pub use cache::SharedLockedCache;
+pub use config::ValueConfig;
+pub use db::DatabaseOps;
+pub use errors::{ApiError, FMPApiError, ProviderError};
+pub use fmp::FMPClient;
+pub use marketaux::MarketAuxApiClient;
+pub use provider::{default_providers, NewsProvider};
+pub use request::HTTPClient;
+pub use storage::Storage;
+
+/// Custom error type for fetching news data.
+#[derive(Debug, Clone)]
+pub struct FetchNewsError {
+    pub message: String,
+}
+
+impl std::error::Error for FetchNewsError {}
+
+impl fmt::Display for FetchNewsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Failure of one provider during a polling cycle.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProviderFailure {
+    pub provider: String,
+    pub message: String,
+    pub at: String,
+}
+
+/// Struct representing the result of fetching news data.
+///
+/// A provider section is `None` when that provider failed; the failure is then listed in `errors`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct NewsResult {
+    hash_key: String,
+    marketaux: Option<MarketAuxResponse>,
+    alphavantage: Option<AlphaVantageApiResponse>,
+    from: String,
+    to: String,
+    time_range: u64,
+    marketaux_data_len: u64,
+    alphavantage_data_len: u64,
+    #[serde(default)]
+    errors: Vec<ProviderFailure>,
+}
+impl NewsResult {
+    /// Checks if two NewsResult instances are equal based on hash_key, from, and to fields.
+    pub fn eq(&self, other: &Self) -> bool {
+        self.hash_key == other.hash_key && 
+        self.from == other.from &&
+        self.to == other.to
+    }
+
+    /// Converts the NewsResult instance to a JSON value.
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("Failed to convert to JSON value") 
+    }
+
+    /// Returns the articles of every provider section in the common schema.
+    pub fn articles(&self) -> Vec<NormalizedArticle> {
+        self.iter_articles().collect()
+    }
+
+    /// Articles of every provider section in the common schema, converted as they are read.
+    pub fn iter_articles(&self) -> impl Iterator<Item = NormalizedArticle> + '_ {
+        self.marketaux.iter().flat_map(|m| m.data.iter().map(NormalizedArticle::from))
+            .chain(self.alphavantage.iter().flat_map(|a| a.feed.iter().map(NormalizedArticle::from)))
+    }
+
+    /// Whether some provider failed during the cycle.
+    pub fn is_partial(&self) -> bool {
+        !self.errors.is_empty()
+    }
+}
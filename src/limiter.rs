@@ -0,0 +1,99 @@
+//! Shared limit of the concurrent provider fetches.
+//!
+//! The sources run on their own schedules (see `scheduler.rs`), each fanning out its fetches. All
+//! of them draw from one pool of `task.max_concurrent_fetches` permits, so together they never send
+//! more fetches at once: the provider fetches of the polling cycles, the watchlist symbol queries,
+//! the downtime recovery and coverage catch-ups, and the backfill days. A fetch waits for a permit
+//! before it starts. 0 lifts the limit.
+//!
+//! `request.max_concurrent_providers` and `watchlist.max_concurrent_fetches` still bound each
+//! fan-out on its own. A new limit, on a config reload, applies to the fetches started afterwards.
+//!
+//! A fetch from a provider throttled by its daily quota waits for its pace before taking a permit
+//! (see `quota::ready`), so the wait does not hold one from the other providers.
+
+use std::future::Future;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use tokio::sync::Semaphore;
+
+use crate::config::TaskArgs;
+use crate::quota;
+
+/// Pool of `limit` permits, unlimited when 0.
+#[derive(Debug, Default)]
+pub struct Limiter {
+    limit: usize,
+    permits: Option<Arc<Semaphore>>,
+}
+impl Limiter {
+    pub fn new(limit: usize) -> Self {
+        Self { limit, permits: (limit > 0).then(|| Arc::new(Semaphore::new(limit))) }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Runs `fetch` once a permit is available.
+    pub fn run<F: Future>(&self, fetch: F) -> impl Future<Output = F::Output> {
+        let permits = self.permits.clone();
+        async move {
+            let _permit = match permits {
+                Some(permits) => permits.acquire_owned().await.ok(),
+                None => None,
+            };
+            fetch.await
+        }
+    }
+}
+
+fn limiter() -> &'static RwLock<Limiter> {
+    static LIMITER: OnceLock<RwLock<Limiter>> = OnceLock::new();
+    LIMITER.get_or_init(RwLock::default)
+}
+
+/// Applies `task.max_concurrent_fetches`.
+pub fn configure(config: &TaskArgs) {
+    let mut limiter = limiter().write().unwrap();
+    if limiter.limit() != config.max_concurrent_fetches {
+        *limiter = Limiter::new(config.max_concurrent_fetches);
+    }
+}
+
+/// Runs the `fetch` from `provider` once its quota allows it and a permit of the shared pool is
+/// available.
+pub fn run<F: Future>(provider: &str, fetch: F) -> impl Future<Output = F::Output> {
+    let provider = provider.to_string();
+    let fetch = limiter().read().unwrap().run(fetch);
+    async move {
+        quota::ready(&provider).await;
+        fetch.await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test]
+    async fn runs_at_most_the_limit_at_once() {
+        for (limit, expected) in [(2, 2), (0, 5)] {
+            let limiter = Limiter::new(limit);
+            let (running, max) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+            let fetches = (0..5).map(|_| {
+                let (running, max) = (running.clone(), max.clone());
+                limiter.run(async move {
+                    max.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    sleep(Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            });
+            futures::future::join_all(fetches).await;
+            assert_eq!(max.load(Ordering::SeqCst), expected);
+        }
+    }
+}
@@ -1,6 +1,35 @@
-use tracing::{span, info, debug, error, warn, trace};
+//! Logs of the service, and the export of its spans.
+//!
+//! With `logging.otlp_endpoint`, the spans (the polling cycles of `fetch_news_data`, the requests
+//! of each provider client and the database operations) are exported over OTLP/gRPC, e.g. to
+//! Jaeger or Tempo, as `logging.service_name`. Only the spans enabled by the level of the logs are.
+//!
+//! `logging.filters` sets the level of some modules or crates apart from `logging.level`, e.g.
+//! `marketaux=debug,db=warn,mongodb=error`. A bare name stands for the module of the service
+//! (`news_data::marketaux`) as well as the crate of that name; any `EnvFilter` directive is
+//! accepted. Both can be changed at runtime with `set_level` and `set_filters`.
+//!
+//! Every polling cycle (`cycle_span`) and websocket request (`request_span`) runs in a span with a
+//! new `correlation_id`. The logs of the provider clients, the cache, `utils::retry` and the
+//! database operations it leads to are nested in it, so they all carry the ID: grepping it follows
+//! one article from its fetch to its storage.
+
+use std::sync::{Mutex, OnceLock};
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use serde_json::Value;
+use tracing::{span, info, debug, error, warn, trace, Level, Span};
 use tracing_subscriber;
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, FmtSubscriber};
+
+use crate::config::LoggingConfig;
+use crate::utils::generate_random_key;
 
 pub enum LogLevel {
     Trace, Info, Debug, Warn, Error
@@ -26,6 +55,24 @@ impl LogLevel {
             _ => LogLevel::Trace,
         }
     }
+
+    /// Level named `s`, `None` if unknown.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "trace" | "info" | "debug" | "warn" | "error" => Some(Self::from_str(s)),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
 }
 impl Default for LogLevel {
     fn default() -> Self {
@@ -34,13 +81,110 @@ impl Default for LogLevel {
 }
 
 const SPAN_NAME: &str = "News data";
+
+type ReloadFilter = Box<dyn Fn(EnvFilter) -> Result<(), String> + Send + Sync>;
+
+/// Replaces the filter of the logger installed by `Logger::init`.
+static RELOAD: OnceLock<ReloadFilter> = OnceLock::new();
+
+/// Exporter of the spans, flushed by `shutdown`.
+static TRACER_PROVIDER: OnceLock<TracerProvider> = OnceLock::new();
+
+/// Level and per-module filters of the installed filter.
+static DIRECTIVES: Mutex<(String, String)> = Mutex::new((String::new(), String::new()));
+
+/// Filter of the logs at `level`, with the per-module `filters` (comma-separated directives).
+pub fn env_filter(level: &str, filters: &str) -> Result<EnvFilter, String> {
+    let mut directives = vec![level.to_string()];
+    for directive in filters.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        if let Some((target, level)) = directive.split_once('=') {
+            if !target.is_empty() && target.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                directives.push(format!("{}::{}={}", env!("CARGO_CRATE_NAME"), target, level));
+            }
+        }
+        directives.push(directive.to_string());
+    }
+    EnvFilter::try_new(directives.join(",")).map_err(|e| format!("Invalid log filters `{}`: {}", filters, e))
+}
+
+/// Sets the level of the logs at runtime, e.g. from the `set_log_level` admin command. The
+/// per-module filters are kept.
+pub fn set_level(level: LogLevel) -> Result<(), String> {
+    let filters = DIRECTIVES.lock().unwrap().1.clone();
+    set_directives(level, &filters)
+}
+
+/// Sets the per-module filters at runtime, keeping the level of the other logs.
+pub fn set_filters(filters: &str) -> Result<(), String> {
+    let level = LogLevel::parse(&DIRECTIVES.lock().unwrap().0).unwrap_or_default();
+    set_directives(level, filters)
+}
+
+/// Sets the level of the logs and the per-module filters at runtime.
+pub fn set_directives(level: LogLevel, filters: &str) -> Result<(), String> {
+    let reload = RELOAD.get().ok_or_else(|| "The logger is not initialized".to_string())?;
+    reload(env_filter(level.as_str(), filters)?)?;
+    *DIRECTIVES.lock().unwrap() = (level.as_str().to_string(), filters.to_string());
+    Ok(())
+}
+
+/// Level of the logs and per-module filters in effect.
+pub fn directives() -> (String, String) {
+    DIRECTIVES.lock().unwrap().clone()
+}
+
+/// Length of the correlation IDs.
+const CORRELATION_ID_LEN: usize = 12;
+
+/// New ID of a polling cycle or a websocket request.
+pub fn correlation_id() -> String {
+    generate_random_key(CORRELATION_ID_LEN)
+}
+
+// The spans below are at the `error` level for the ID to be on the logs of every level.
+
+/// Span of a run of the background `source`, with a new correlation ID.
+pub fn cycle_span(source: &str) -> Span {
+    span!(Level::ERROR, "cycle", source = %source, correlation_id = %correlation_id())
+}
+
+/// Span of a websocket request, with a new correlation ID and the `id` given by the client.
+pub fn request_span(id: Option<&Value>) -> Span {
+    span!(Level::ERROR, "request", correlation_id = %correlation_id(), id = id.map(tracing::field::display))
+}
+
 pub struct Logger;
 
 impl Logger {
-    /// Initialize the logger
+    /// Initialize the logger, its level can be changed with `set_level`.
     pub fn init(level: LogLevel) {
-        tracing_subscriber::fmt()
-            .with_max_level(level.to_log_level()) // Set the maximum log level
+        Self::init_with(level, None)
+    }
+
+    /// Like `init`, with the per-module `filters` of `config`, exporting the spans when it has an
+    /// `otlp_endpoint`.
+    pub fn init_with(level: LogLevel, config: Option<&LoggingConfig>) {
+        let filters = config.map(|config| config.filters.as_str()).unwrap_or_default();
+        let (filters, env_filter) = match env_filter(level.as_str(), filters) {
+            Ok(env_filter) => (filters, env_filter),
+            Err(e) => {
+                eprintln!("{}, only `{}` is applied", e, level.as_str());
+                ("", EnvFilter::new(level.as_str()))
+            }
+        };
+        *DIRECTIVES.lock().unwrap() = (level.as_str().to_string(), filters.to_string());
+        let (filter, handle) = reload::Layer::new(env_filter); // Set the maximum log level
+        let _ = RELOAD.set(Box::new(move |filter| handle.reload(filter).map_err(|e| e.to_string())));
+        let tracer = config.and_then(|config| {
+            let endpoint = config.otlp_endpoint.as_deref()?;
+            otlp_tracer(endpoint, &config.service_name)
+                .inspect_err(|e| eprintln!("Spans are not exported, failed to create the OTLP exporter: {}", e))
+                .ok()
+        });
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer())
+            .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)))
             .init();
     }
 
@@ -82,6 +226,36 @@ impl Logger {
     }
 }
 
+/// Tracer exporting the spans to the OTLP `endpoint`, in batches.
+fn otlp_tracer(endpoint: &str, service_name: &str) -> Result<Tracer, String> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", service_name.to_string())]))
+        .build();
+    let tracer = provider.tracer(SPAN_NAME);
+    let _ = TRACER_PROVIDER.set(provider);
+    Ok(tracer)
+}
+
+/// Exports the spans not exported yet, before the process exits.
+pub fn shutdown() {
+    if let Some(provider) = TRACER_PROVIDER.get() {
+        if let Err(e) = tokio::task::block_in_place(|| provider.shutdown()) {
+            eprintln!("Failed to export the last spans: {}", e);
+        }
+    }
+}
+
+/// Initializes the logger at `level`, exporting the spans as set in `config`.
+pub fn setup_tracing(level: &str, config: &LoggingConfig) {
+    Logger::init_with(LogLevel::from_str(level), Some(config));
+}
+
 pub fn setup_logger(level: &str) {
     match level {
         "error" => Logger::init(LogLevel::Error),
@@ -104,3 +278,50 @@ pub fn test_() {
     Logger::warn("This is a warning message.");
     Logger::error("This is an error message.");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_the_module_filters() {
+        let filter = env_filter("info", "marketaux=debug, mongodb::connection=warn,,db=error").unwrap().to_string();
+        for directive in ["news_data::marketaux=debug", "marketaux=debug", "mongodb::connection=warn", "news_data::db=error"] {
+            assert!(filter.contains(directive), "`{}` missing from `{}`", directive, filter);
+        }
+        assert!(!filter.contains("news_data::mongodb"));
+        assert!(env_filter("info", "marketaux=loud").is_err());
+    }
+
+    /// Logs written to a shared buffer.
+    #[derive(Clone, Default)]
+    struct Buffer(std::sync::Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tags_the_logs_with_the_correlation_id() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = fmt::Subscriber::builder().with_max_level(Level::WARN).with_ansi(false).with_writer(move || writer.clone()).finish();
+        tracing::subscriber::with_default(subscriber, || {
+            cycle_span("marketaux").in_scope(|| warn!("Attempt 1/3 failed."));
+            request_span(Some(&Value::from(7))).in_scope(|| error!("Request failed."));
+        });
+
+        let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let ids: Vec<&str> = logs.split("correlation_id=").skip(1).map(|rest| &rest[..CORRELATION_ID_LEN]).collect();
+        assert_eq!(ids.len(), 2, "{}", logs);
+        assert_ne!(ids[0], ids[1]);
+        assert!(logs.contains("cycle{source=marketaux correlation_id="), "{}", logs);
+        assert!(logs.contains(&format!("request{{correlation_id={} id=7}}", ids[1])), "{}", logs);
+    }
+}
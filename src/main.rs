@@ -1,250 +1,556 @@
-//! This module handles fetching news data from MarketAux and AlphaVantage APIs,
-//! caching the results, and inserting them into a MongoDB database.
-
-#![allow(dead_code)]
-#![allow(unused_imports)]
-
-
-use std::fmt;
+//! The `news_data` binary: the websocket server, one-off fetches and queries, and the maintenance
+//! commands.
+//!
+//! ```text
+//! news_data [serve]                           # websocket server
+//! news_data fetch-once [--sources a,b]        # one polling cycle, see `polling.rs`
+//! news_data backfill --from YYYY-MM-DD [flags] # see `backfill.rs`
+//! news_data query --ticker NVDA [flags]       # see `query.rs`
+//! news_data doctor                            # see `doctor.rs`
+//! news_data backup|restore <file> [flags]     # see `backup.rs`
+//! news_data purge [flags]                     # see `purge.rs`
+//! news_data report [flags]                    # see `report.rs`
+//! news_data export <directory> [--since-last]  # see `export.rs`
+//! news_data compare <provider> <provider>     # see `compare.rs`
+//! news_data repl [--url ws://host:port]       # see `repl.rs`
+//! news_data record-fixtures [--providers a,b] # see `fixtures.rs`
+//! ```
+//!
+//! `news_data help <command>` lists the flags of a command.
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use cache::SharedLockedCache;
-use cached::TimedCache;
-use cached::proc_macro::cached;
-use request::HTTPClient;
+use chrono::{DateTime, Duration as UtcDuration, NaiveDate, SecondsFormat, Utc};
+use clap::{ArgGroup, Args, Parser, Subcommand};
 use reqwest::Client;
-use serde::{Serialize, Deserialize};
-use serde_json::{json, Value};
-use tokio::time::{sleep, Duration};
 use tokio::sync::Mutex;
-use tracing::{trace, info, error, warn, debug};
-
-use alphavantage::AlphaVantageApiResponse;
-use marketaux::MarketAuxResponse;
-use tracing_subscriber::fmt::format::json;
-
-use crate::utils::{time_rfc3339_opts, now, generate_random_key};
-use crate::logging::setup_logger;
-use crate::fmp::FMPClient;
-use crate::config::ValueConfig;
-use alphavantage::{AlphaVantageApiClient, BASE_FUNCTION};
-use marketaux::{MarketAuxApiClient, ALL_NEWS_ENDPOINT, SIMILAR_NEWS_ENDPOINT, NEWS_BY_UUID};
-
-pub mod errors;
-pub mod fmp;
-pub mod marketaux;
-pub mod alphavantage;
-pub mod db;
-pub mod config;
-pub mod utils;
-pub mod logging;
-pub mod options;
-pub mod request;
-pub mod server_types;
-pub mod cache;
-pub mod websocket;
-pub mod request_parser;
-
-/// Custom error type for fetching news data.
-#[derive(Debug, Clone)]
-pub struct FetchNewsError {
-    pub message: String,
-}
-
-impl std::error::Error for FetchNewsError {}
-
-impl fmt::Display for FetchNewsError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
-    }
+use tracing::{error, info, warn};
+
+use news_data::logging::{self, setup_logger, setup_tracing};
+use news_data::sentiment::SentimentLabel;
+use news_data::{backfill, backup, captures, chaos, clock, compare, config, db, repl, doctor, export, fixtures, limiter, normalize, polling, purge, query, quota, report, sentiment, taxonomy, websocket};
+use news_data::{default_providers, HTTPClient, SharedLockedCache};
+
+#[derive(Debug, Parser)]
+#[command(name = "news_data", version, about = "Collects financial news and serves them over websockets.")]
+struct Cli {
+    /// The websocket server when omitted.
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-/// Struct representing the result of fetching news data.
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
-pub struct NewsResult {
-    hash_key: String,
-    marketaux: MarketAuxResponse,
-    alphavantage: AlphaVantageApiResponse,
-    from: String,
-    to: String,
-    time_range: u64,
-    marketaux_data_len: u64,
-    alphavantage_data_len: u64
-}
-impl NewsResult {
-    /// Checks if two NewsResult instances are equal based on hash_key, from, and to fields.
-    pub fn eq(&self, other: &Self) -> bool {
-        self.hash_key == other.hash_key && 
-        self.from == other.from &&
-        self.to == other.to
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Runs the websocket server.
+    Serve,
+    /// Fetches the polled sources once, stores the articles and exits.
+    FetchOnce {
+        /// Sources to fetch, e.g. `marketaux,watchlist`. All of them by default.
+        #[arg(long, value_delimiter = ',')]
+        sources: Vec<String>,
+    },
+    /// Fetches and stores a past date range, day by day.
+    Backfill {
+        /// First day (YYYY-MM-DD).
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last day (YYYY-MM-DD), today by default.
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// Providers to backfill, `backfill.providers` by default.
+        #[arg(long, value_delimiter = ',')]
+        providers: Option<Vec<String>>,
+    },
+    /// Prints the stored articles matching the filters.
+    Query(QueryArgs),
+    /// Checks the config, the providers and the database.
+    Doctor,
+    /// Dumps the database into an archive.
+    Backup(BackupArgs),
+    /// Loads an archive made by `backup`.
+    Restore {
+        /// Archive to load.
+        file: PathBuf,
+    },
+    /// Deletes or redacts stored articles.
+    Purge(PurgeArgs),
+    /// Renders a news report.
+    Report(ReportArgs),
+    /// Writes the stored articles to a Parquet or JSON Lines file.
+    Export(ExportArgs),
+    /// Fetches the same window from two providers and compares their coverage.
+    Compare(CompareArgs),
+    /// Sends requests to a running server from a prompt.
+    Repl(ReplArgs),
+    /// Refreshes the provider response fixtures of the contract tests.
+    RecordFixtures {
+        /// Fixtures directory.
+        #[arg(long, default_value = "fixtures")]
+        dir: String,
+        /// Providers to refresh, e.g. `marketaux,fmp`. All of them by default.
+        #[arg(long, value_delimiter = ',')]
+        providers: Vec<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("filter").args(["tickers", "keywords", "categories", "entities", "sentiment"])))]
+struct QueryArgs {
+    /// Articles tagged with these tickers.
+    #[arg(long, visible_alias = "ticker", value_delimiter = ',')]
+    tickers: Vec<String>,
+    /// Articles with these keywords.
+    #[arg(long, value_delimiter = ',')]
+    keywords: Vec<String>,
+    /// Articles in these categories of the taxonomy.
+    #[arg(long, value_delimiter = ',')]
+    categories: Vec<String>,
+    /// Articles mentioning these people, organizations or locations.
+    #[arg(long, value_delimiter = ',')]
+    entities: Vec<String>,
+    /// Articles with these sentiment labels, e.g. `bullish,somewhat_bullish`.
+    #[arg(long, value_delimiter = ',')]
+    sentiment: Vec<SentimentLabel>,
+    /// Articles fetched from these providers, e.g. `finnhub`.
+    #[arg(long, visible_alias = "providers", value_delimiter = ',')]
+    provider: Vec<String>,
+    /// Articles with at least this sentiment score, from -1 to 1.
+    #[arg(long, allow_hyphen_values = true)]
+    min_sentiment: Option<f64>,
+    /// Articles published in the last hours.
+    #[arg(long, default_value_t = 24)]
+    hours: i64,
+    /// Articles published since this time (RFC 3339), instead of `--hours`.
+    #[arg(long, value_parser = parse_time, conflicts_with = "hours")]
+    since: Option<DateTime<Utc>>,
+    #[arg(long, default_value_t = 20)]
+    limit: usize,
+    /// `table`, `json` or `csv`.
+    #[arg(long, default_value = "table")]
+    format: query::OutputFormat,
+    /// Same as `--format json`.
+    #[arg(long, conflicts_with = "format")]
+    json: bool,
+}
+impl QueryArgs {
+    fn query(self) -> query::Query {
+        let filter = if !self.tickers.is_empty() {
+            query::QueryFilter::Tickers(self.tickers)
+        } else if !self.keywords.is_empty() {
+            query::QueryFilter::Keywords(self.keywords)
+        } else if !self.categories.is_empty() {
+            query::QueryFilter::Categories(self.categories)
+        } else if !self.entities.is_empty() {
+            query::QueryFilter::Entities(self.entities)
+        } else if !self.sentiment.is_empty() {
+            query::QueryFilter::Sentiment(self.sentiment)
+        } else {
+            query::QueryFilter::Any
+        };
+        query::Query {
+            filter,
+            hours: self.hours,
+            since: self.since.map(|since| since.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            providers: self.provider,
+            min_sentiment: self.min_sentiment,
+            limit: self.limit,
+        }
     }
+}
 
-    /// Converts the NewsResult instance to a JSON value.
-    pub fn to_json(&self) -> Value {
-        serde_json::to_value(self).expect("Failed to convert to JSON value") 
+#[derive(Debug, Args)]
+struct BackupArgs {
+    /// Archive to write.
+    file: PathBuf,
+    /// Kinds to dump, `articles,social_sentiment,trending` by default.
+    #[arg(long, value_delimiter = ',')]
+    kinds: Vec<db::DataKind>,
+    /// Documents dated from this day or time (inclusive).
+    #[arg(long)]
+    since: Option<String>,
+    /// Documents dated before this day or time.
+    #[arg(long)]
+    until: Option<String>,
+}
+impl BackupArgs {
+    fn options(&self) -> backup::BackupOptions {
+        backup::BackupOptions {
+            kinds: if self.kinds.is_empty() { backup::DEFAULT_KINDS.to_vec() } else { self.kinds.clone() },
+            since: self.since.clone(),
+            until: self.until.clone(),
+        }
     }
 }
 
-/// Fetches news data from MarketAux and AlphaVantage APIs, with caching.
-#[cached(
-    type = "TimedCache<String, Result<NewsResult, FetchNewsError>>",
-    create = "{ TimedCache::with_lifespan(600) }", // Cache lifespan of 10 minutes
-    convert = r#"{ format!("{:?}", config) }"#
-)]
-async fn fetch_news_data(req_client: Arc<Client>, config: Arc<ValueConfig>) -> Result<NewsResult, FetchNewsError> {
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("target").args(["source", "author"]).required(true)))]
+struct PurgeArgs {
+    /// Source domain to purge, subdomains included.
+    #[arg(long)]
+    source: Option<String>,
+    /// Author to purge, matched exactly.
+    #[arg(long)]
+    author: Option<String>,
+    /// `delete` or `anonymize`.
+    #[arg(long, default_value = "delete")]
+    mode: purge::PurgeMode,
+    /// Backups to rewrite without the purged content.
+    #[arg(long = "archive")]
+    archives: Vec<PathBuf>,
+}
 
-    let cache = Arc::new(Mutex::new(SharedLockedCache::new(100)));
+#[derive(Debug, Args)]
+struct ReportArgs {
+    /// `daily` or `weekly`.
+    #[arg(long, default_value = "daily")]
+    period: report::ReportPeriod,
+    /// `markdown` or `html`.
+    #[arg(long, default_value = "markdown")]
+    format: report::ReportFormat,
+    /// Tickers to report on, `report.tickers` by default.
+    #[arg(long = "ticker")]
+    tickers: Vec<String>,
+    /// File receiving the report, stdout by default.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
 
-    let marketaux_data = marketaux::run(
-            ALL_NEWS_ENDPOINT, 
-            req_client.clone(),
-            cache.clone(), 
-            config.clone()
-        ).await
-        .map(serde_json::from_value::<MarketAuxResponse>)
-        .unwrap()
-        .inspect(|data| info!("Successfully fetched from marketaux. | Meta :{:?}", data.meta))
-        .map_err(|e| FetchNewsError { message: format!("MarketAux error: {}", e)})?;
-    
-    let alphavantage_data = alphavantage::run(
-            req_client.clone(),
-            cache.clone(),  
-            config.clone()
-        ).await
-        .map(serde_json::from_value::<AlphaVantageApiResponse>)
-        .unwrap()
-        .inspect(|data| info!("Successfully fetched data from Alphavantage. | Meta: {:?}", data.items))
-        .map_err(|e| FetchNewsError { message: format!("AlphaVantage error: {}", e)})?;
-
-    Ok(NewsResult {
-        hash_key: generate_random_key(8),
-        marketaux: marketaux_data.clone(),
-        alphavantage: alphavantage_data.clone(),
-        from: time_rfc3339_opts(config.request.delay_secs),
-        to: now(),
-        time_range: config.request.delay_secs as u64,
-        marketaux_data_len: marketaux_data.data.len() as u64,
-        alphavantage_data_len: alphavantage_data.feed.len() as u64,
-    })
+#[derive(Debug, Args)]
+struct ExportArgs {
+    /// Directory receiving one file per export.
+    directory: PathBuf,
+    /// `parquet` or `jsonl`.
+    #[arg(long, default_value = "parquet")]
+    format: export::ExportFormat,
+    /// Only the articles stored since the last export to the same directory.
+    #[arg(long)]
+    since_last: bool,
+    /// Articles tagged with these tickers, every article by default.
+    #[arg(long, value_delimiter = ',')]
+    tickers: Vec<String>,
 }
 
-/// Main function that reads the config, initializes the database client, 
-/// fetches news data in a loop, and inserts it into the database.
-#[tokio::main]
-async fn main_2() -> Result<(), FetchNewsError> {
-    // Initialize tracing
-    setup_logger("trace");
+#[derive(Debug, Args)]
+struct CompareArgs {
+    first: String,
+    second: String,
+    /// Start of the window (RFC 3339), `--hours` before `--to` by default.
+    #[arg(long, value_parser = parse_time)]
+    from: Option<DateTime<Utc>>,
+    /// End of the window (RFC 3339), now by default.
+    #[arg(long, value_parser = parse_time)]
+    to: Option<DateTime<Utc>>,
+    #[arg(long, default_value_t = 1)]
+    hours: i64,
+}
 
-    info!("Reading config file & Preparing components...");
-    let value_config = Arc::new(config::ValueConfig::new().expect("Failed to read config file"));
-    let req_client = Arc::new(Client::new());
+#[derive(Debug, Args)]
+struct ReplArgs {
+    /// Websocket URL of the server, `ws://<server.host>:<server.port>` by default.
+    #[arg(long)]
+    url: Option<String>,
+    /// Access token, when `server.auth` is enabled.
+    #[arg(long)]
+    token: Option<String>,
+}
 
-    info!("Creating databse client...");
-    let db_client = db::ClientManager::new(&value_config).await.map_err(
-        |e| {e}
-    ).unwrap();
-
-    info!("Getting ready...");
-    let db_ops = db::DatabaseOps::new(
-        db_client.get_client(), 
-        &value_config.database.database_name, 
-        &value_config.database.collection_name);
-
-    info!("Fetching data....");
-    loop {
-        match fetch_news_data(req_client.clone(), value_config.clone()).await {
-            Ok(data) => {
-                trace!(
-                "GET request yielded: {} results | Hash key: {} \n",
-                data.marketaux_data_len + data.alphavantage_data_len,
-                data.hash_key );
-
-                info!("Inserting into database...");
-                let doc = db_ops.convert_to_document(data.to_json())
-                    .map_err(|e| error!("Error converting NewsResult to bson::Document: {}", e))
-                    .unwrap();
-
-                let _ = db_ops.insert_one(doc).await
-                    .map_err(|e| error!("Error inserting document: {}", e))
-                    .unwrap();
-
-                info!("Done.");
-            },
-            Err(e) => error!("Error fetching news data: {}", e),
-        }
+fn parse_time(time: &str) -> Result<DateTime<Utc>, String> {
+    normalize::parse_timestamp(time).ok_or_else(|| format!("invalid time `{}`", time))
+}
 
-        // Sleep to throttle requests
-        info!("Next fetch in {} seconds", value_config.request.delay_secs);
-        sleep(Duration::from_secs(value_config.request.delay_secs as u64)).await;
-    }
+/// Runs the `backup <file> [flags]` command.
+async fn run_backup_command(args: BackupArgs) -> Result<(), backup::BackupError> {
+    let db_ops = backup_db_ops().await?;
+    let summary = backup::backup(&db_ops, &args.file, &args.options()).await?;
+    log_backup_summary("backup", &summary, &args.file);
+    Ok(())
 }
 
+/// Runs the `restore <file>` command.
+async fn run_restore_command(file: PathBuf) -> Result<(), backup::BackupError> {
+    let db_ops = backup_db_ops().await?;
+    let summary = backup::restore(&db_ops, &file).await?;
+    log_backup_summary("restore", &summary, &file);
+    Ok(())
+}
 
-#[tokio::main]
-async fn main_() {
-    // Initialize tracing
-    setup_logger("trace");
+async fn backup_db_ops() -> Result<db::DatabaseOps, backup::BackupError> {
+    let value_config = load_config();
+    let db_client = db::ClientManager::new(&value_config).await
+        .map_err(|e| backup::BackupError::Database(e.to_string()))?;
+    Ok(db::DatabaseOps::from_config(db_client.get_client(), &value_config.database))
+}
 
-    // Fetch news data
-    info!("Fetching news...");
-    let args = json!({
-        "function": "stock news"
-    });
+fn log_backup_summary(command: &str, summary: &backup::BackupSummary, path: &Path) {
+    for (kind, count) in &summary.counts {
+        info!("{}: {} documents", kind.to_str(), count);
+    }
+    info!("{} complete. | {} documents | File: {}", command, summary.total(), path.display());
+}
 
-    info!("Initializing cache...");
-    let cache = Arc::new(Mutex::new(SharedLockedCache::new(100 as usize)));
+/// Runs the `report [flags]` command.
+async fn run_report_command(args: ReportArgs) -> Result<(), report::ReportError> {
+    let value_config = load_config();
+    let request = report::ReportRequest::new(args.period, args.format, &args.tickers, args.output, &value_config.report)?;
+    clock::configure(&value_config.clock);
+
+    let db_client = db::ClientManager::new(&value_config).await
+        .map_err(|e| report::ReportError::Database(e.to_string()))?;
+    let db_ops = db::DatabaseOps::from_config(db_client.get_client(), &value_config.database);
+
+    let rendered = report::generate(&db_ops, &request, &value_config.report).await?;
+    match &request.output {
+        Some(path) => {
+            std::fs::write(path, rendered)?;
+            info!("report complete. | {} {} | File: {}", request.period.to_str(), request.format.to_str(), path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Runs the `export <directory> [flags]` command.
+async fn run_export_command(args: ExportArgs) -> Result<(), export::ExportError> {
+    let value_config = load_config();
+    clock::configure(&value_config.clock);
+    let db_client = db::ClientManager::new(&value_config).await
+        .map_err(|e| export::ExportError::Database(e.to_string()))?;
+    let db_ops = db::DatabaseOps::from_config(db_client.get_client(), &value_config.database);
+
+    let request = export::StoredExport {
+        directory: args.directory,
+        format: args.format,
+        tickers: args.tickers.iter().map(|t| t.to_uppercase()).collect(),
+        since_last: args.since_last,
+    };
+    let watermark = request.run(&db_ops, clock::now()).await?;
+    match &watermark.file {
+        Some(file) => info!("export complete. | {} articles | File: {}", watermark.articles, request.directory.join(file).display()),
+        None => info!("export complete. | No new articles"),
+    }
+    Ok(())
+}
 
-    info!("Initializing HTTP client...");
+/// Runs the `compare <provider> <provider> [flags]` command, printing the comparison as JSON.
+async fn run_compare_command(args: CompareArgs) -> Result<(), compare::CompareError> {
+    let value_config = Arc::new(load_config());
+    clock::configure(&value_config.clock);
+    sentiment::configure(&value_config.sentiment);
+    taxonomy::configure(&value_config.taxonomy);
+
+    let req_client = Arc::new(Client::new());
     let http_client = Arc::new(HTTPClient::new().expect("Failed to initialize HTTP client."));
+    let cache = Arc::new(Mutex::new(SharedLockedCache::new(100)));
+    let providers = default_providers(http_client, req_client, cache, value_config);
 
-    info!("Reading configurations...");
-    let config = Arc::new(ValueConfig::new().expect("Configurations were not properly parsed."));
+    let to = args.to.unwrap_or_else(clock::now);
+    let from = args.from.unwrap_or(to - UtcDuration::hours(args.hours));
+    let comparison = compare::compare(&providers, &args.first, &args.second, from, to).await?;
+    println!("{}", serde_json::to_string_pretty(&comparison).unwrap_or_default());
+    Ok(())
+}
 
-    info!("Creating FMP client...");
-    let fmp_client = FMPClient::new(http_client, cache, config);
+/// Runs the `record-fixtures [--dir fixtures] [--providers a,b]` command.
+async fn run_record_fixtures_command(dir: String, only: Vec<String>) -> Result<(), String> {
+    let value_config = load_config();
+    clock::configure(&value_config.clock);
 
-    info!("Now fetching news data...");
-    let response = fmp_client.poll(Arc::new(args)).await;
-    debug!("Request yielded a Response {:?}: ", response.is_ok());
+    let req_client = Arc::new(Client::new());
+    let http_client = Arc::new(HTTPClient::new().expect("Failed to initialize HTTP client."));
+    let cache = Arc::new(Mutex::new(SharedLockedCache::new(100)));
+    let mut stable = value_config.clone();
+    stable.fmp.prefer_stable = true;
+    let mut legacy = value_config;
+    legacy.fmp.prefer_stable = false;
+    let providers = default_providers(http_client.clone(), req_client.clone(), cache.clone(), Arc::new(stable));
+    let legacy_providers = default_providers(http_client, req_client, cache, Arc::new(legacy));
+
+    let outcomes = fixtures::refresh(&dir, &providers, &legacy_providers, &only).await;
+    let failed: Vec<String> = outcomes
+        .into_iter()
+        .filter_map(|(fixture, outcome)| outcome.err().map(|e| format!("{}: {}", fixture, e)))
+        .collect();
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(format!("{} fixtures not refreshed:\n{}", failed.len(), failed.join("\n"))),
+    }
 }
 
-#[tokio::main]
-async fn main_3() {
-    // Initialize tracing
-    setup_logger("debug");
+/// Runs the `backfill --from YYYY-MM-DD [flags]` command.
+async fn run_backfill_command(from: NaiveDate, to: Option<NaiveDate>, providers: Option<Vec<String>>) -> Result<(), backfill::BackfillError> {
+    let value_config = Arc::new(load_config());
+    let request = backfill::BackfillRequest::new(from, to, providers, &value_config.backfill)?;
+    clock::configure(&value_config.clock);
+    chaos::configure(&value_config.chaos);
+    sentiment::configure(&value_config.sentiment);
+    taxonomy::configure(&value_config.taxonomy);
+    limiter::configure(&value_config.task);
+    quota::configure(&value_config.quota);
+    captures::configure(&value_config.captures);
 
-    info!("Reading config file & Preparing components...");
-    let value_config = Arc::new(config::ValueConfig::new().expect("Failed to read config file"));
     let req_client = Arc::new(Client::new());
+    let http_client = Arc::new(HTTPClient::new().expect("Failed to initialize HTTP client."));
     let cache = Arc::new(Mutex::new(SharedLockedCache::new(100)));
+    let providers = default_providers(http_client, req_client.clone(), cache, value_config.clone());
+
+    let db_client = db::ClientManager::new(&value_config).await
+        .map_err(|e| backfill::BackfillError::Database(e.to_string()))?;
+    let db_ops = Arc::new(db::DatabaseOps::from_config(db_client.get_client(), &value_config.database)
+        .with_transactions(db_client.supports_transactions())
+        .with_keywords(&value_config.keywords)
+        .with_entities(&value_config.entities)
+        .with_translator(req_client, &value_config.translation));
+
+    quota::spawn(db_ops.clone(), &value_config.quota);
+
+    let backfill = backfill::Backfill::new(&providers, db_ops.clone(), &request, &value_config.backfill, Arc::default())?;
+    let progress = backfill.run().await;
+    quota::flush(&db_ops).await;
+    for failure in &progress.failures {
+        warn!("Not backfilled: {}", failure);
+    }
+    info!(
+        "backfill complete. | {} to {} | {} requests | {} articles fetched, {} new | {} failures",
+        progress.from, progress.to, progress.requests, progress.fetched, progress.stored, progress.failures.len()
+    );
+    Ok(())
+}
+
+/// Runs the `purge (--source DOMAIN | --author NAME) [flags]` command.
+async fn run_purge_command(args: PurgeArgs) -> Result<(), purge::PurgeError> {
+    let request = purge::PurgeRequest::new(args.source, args.author, args.mode, args.archives, "cli")?;
+
+    let value_config = load_config();
+    // The captures are on this host, the caches in the server process.
+    captures::configure(&value_config.captures);
+    let db_client = db::ClientManager::new(&value_config).await
+        .map_err(|e| purge::PurgeError::Database(e.to_string()))?;
+    let db_ops = db::DatabaseOps::from_config(db_client.get_client(), &value_config.database);
+
+    let record = purge::purge(&db_ops, &request, None, &value_config.purge.signing_key).await?;
+    info!("purge complete. | {:?} | Signature: {}", record.report, record.signature);
+    Ok(())
+}
 
-    // Fetch news data
-    let marketaux_client = MarketAuxApiClient::new(req_client.clone(), cache.clone(),  value_config.clone());
-    let _alphavantage_client = AlphaVantageApiClient::new(req_client.clone(), cache.clone(),  value_config.clone());
+/// Runs the `query` command, printing the articles to stdout.
+async fn run_query_command(args: QueryArgs) -> Result<(), db::OpError> {
+    let format = if args.json { query::OutputFormat::Json } else { args.format };
+    let value_config = load_config();
+    clock::configure(&value_config.clock);
+    let db_client = db::ClientManager::new(&value_config).await?;
+    let db_ops = db::DatabaseOps::from_config(db_client.get_client(), &value_config.database);
+
+    let articles = query::run(&db_ops, &args.query()).await?;
+    let rendered = query::render(&articles, format)
+        .map_err(|message| db::OpError::SearchError { message })?;
+    print!("{}", rendered);
+    Ok(())
+}
 
-    //query_params
-    let query_params = json!({
-        "endpoint": ALL_NEWS_ENDPOINT,
-        "symbols": "DIS",
-        "hola": "hello world"
-    });
+/// Runs the `doctor` command, printing one line per check.
+async fn run_doctor_command() -> Result<(), String> {
+    let value_config = config::ValueConfig::new().map_err(|e| format!("config: {}", e))?;
+    let checks = doctor::run(Arc::new(value_config)).await;
+    for check in &checks {
+        println!("{:<8} {:<24} {}", check.status.to_str(), check.name, check.detail);
+    }
+    match checks.iter().filter(|c| c.status == doctor::CheckStatus::Failed).count() {
+        0 => Ok(()),
+        failed => Err(format!("{} checks failed", failed)),
+    }
+}
 
-    info!("Fetching data from MarketAux...");
-    let m_data = marketaux_client.poll(Arc::new(query_params))
-        .await
-        .map(serde_json::from_value::<MarketAuxResponse>)
-        .unwrap()
-        .inspect(|data| info!("GET request yielded: {:?}", data.meta))
-        .map_err(|err| error!("Error fetching data. | Error: {:?}", err));
-    debug!("Request yielded a Response: {:?} ", m_data.is_ok());
+/// Reads the config file, exiting with an error when it cannot be read.
+fn load_config() -> config::ValueConfig {
+    config::ValueConfig::new().unwrap_or_else(|e| {
+        error!("Failed to read the config file: {}", e);
+        logging::shutdown();
+        std::process::exit(1);
+    })
+}
 
+/// Exits with an error when `command` failed.
+fn exit_on_error<E: Display>(command: &str, result: Result<(), E>) {
+    if let Err(e) = result {
+        error!("{} failed: {}", command, e);
+        logging::shutdown();
+        std::process::exit(1);
+    }
+}
 
+/// Runs the `repl [flags]` command.
+async fn run_repl_command(args: ReplArgs) -> Result<(), repl::ReplError> {
+    let url = match args.url {
+        Some(url) => url,
+        None => {
+            let value_config = load_config();
+            format!("ws://{}:{}", value_config.server.host, value_config.server.port)
+        }
+    };
+    repl::run(&url, args.token.as_deref()).await
 }
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    setup_logger("debug");
-    // Run websocket server
-    let _ = websocket::run().await;
-}
\ No newline at end of file
+    let cli = Cli::parse();
+
+    // Initialize tracing. The commands printing results keep stdout for them.
+    let level = match cli.command {
+        Some(Command::Query(_) | Command::Doctor | Command::Compare(_) | Command::Repl(_)) => "warn",
+        _ => "debug",
+    };
+    // The spans are exported as set in `[logging]`, see `logging.rs`.
+    match config::ValueConfig::new() {
+        Ok(value_config) => setup_tracing(level, &value_config.logging),
+        Err(_) => setup_logger(level),
+    }
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => exit_on_error("Server", websocket::run().await),
+        Command::FetchOnce { sources } => match polling::fetch_once(&sources).await {
+            Ok(stored) => info!("fetch-once complete. | {} new articles", stored),
+            Err(e) => exit_on_error("fetch-once", Err(e)),
+        },
+        Command::Backfill { from, to, providers } => exit_on_error("backfill", run_backfill_command(from, to, providers).await),
+        Command::Query(args) => exit_on_error("query", run_query_command(args).await),
+        Command::Doctor => exit_on_error("doctor", run_doctor_command().await),
+        Command::Backup(args) => exit_on_error("backup", run_backup_command(args).await),
+        Command::Restore { file } => exit_on_error("restore", run_restore_command(file).await),
+        Command::Purge(args) => exit_on_error("purge", run_purge_command(args).await),
+        Command::Report(args) => exit_on_error("report", run_report_command(args).await),
+        Command::Export(args) => exit_on_error("export", run_export_command(args).await),
+        Command::Compare(args) => exit_on_error("compare", run_compare_command(args).await),
+        Command::Repl(args) => exit_on_error("repl", run_repl_command(args).await),
+        Command::RecordFixtures { dir, providers } => exit_on_error("record-fixtures", run_record_fixtures_command(dir, providers).await),
+    }
+    logging::shutdown();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup_args(args: &[&str]) -> Result<BackupArgs, clap::Error> {
+        let cli = Cli::try_parse_from(["news_data", "backup"].iter().chain(args))?;
+        match cli.command {
+            Some(Command::Backup(args)) => Ok(args),
+            other => panic!("expected the backup command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_the_backup_flags() {
+        let options = backup_args(&["dump.jsonl.gz", "--kinds", "articles,trending", "--since", "2024-08-01"]).unwrap().options();
+        assert_eq!(options.kinds, vec![db::DataKind::Articles, db::DataKind::Trending]);
+        assert_eq!(options.since.as_deref(), Some("2024-08-01"));
+        assert_eq!(options.until, None);
+        assert_eq!(backup_args(&["dump.jsonl.gz"]).unwrap().options().kinds, backup::DEFAULT_KINDS.to_vec());
+
+        assert!(backup_args(&["dump.jsonl.gz", "--kinds", "articles,unknown"]).is_err());
+        assert!(backup_args(&["dump.jsonl.gz", "--format", "json"]).is_err());
+        assert!(backup_args(&["dump.jsonl.gz", "--since"]).is_err());
+        assert!(backup_args(&[]).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Connection draining for maintenance.
+//!
+//! Once maintenance mode is entered, the server stops serving new connections (they receive a
+//! "retry later" response and are closed), every open connection receives a drain notice, and the
+//! server exits as soon as the last connection is closed or the drain timeout passes.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, Notify};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+pub struct Maintenance {
+    draining: AtomicBool,
+    connections: AtomicUsize,
+    /// Drain notices sent to open connections.
+    notices: broadcast::Sender<Value>,
+    /// Signaled when draining starts and whenever a connection closes.
+    changed: Notify,
+    timeout_secs: AtomicUsize,
+}
+impl Default for Maintenance {
+    fn default() -> Self {
+        let (notices, _) = broadcast::channel(16);
+        Self {
+            draining: AtomicBool::new(false),
+            connections: AtomicUsize::new(0),
+            notices,
+            changed: Notify::new(),
+            timeout_secs: AtomicUsize::new(0),
+        }
+    }
+}
+impl Maintenance {
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Enters maintenance mode and notifies the open connections.
+    /// Returns `false` if the server was already draining.
+    pub fn start(&self, timeout: Duration) -> bool {
+        if self.draining.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        self.timeout_secs.store(timeout.as_secs() as usize, Ordering::SeqCst);
+        info!("Entering maintenance mode. | {} open connections | Timeout: {}s", self.connections(), timeout.as_secs());
+        let _ = self.notices.send(json!({
+            "event": "drain",
+            "timeout_secs": timeout.as_secs(),
+        }));
+        self.changed.notify_waiters();
+        true
+    }
+
+    /// Receiver of the drain notices, one per connection.
+    pub fn notices(&self) -> broadcast::Receiver<Value> {
+        self.notices.subscribe()
+    }
+
+    /// Counts a connection as open until the returned guard is dropped.
+    pub fn track_connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.connections.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard { maintenance: self.clone() }
+    }
+
+    /// Resolves once maintenance mode was entered and every connection is closed, or the drain timeout passed.
+    pub async fn drained(&self) {
+        loop {
+            let changed = self.changed.notified();
+            if self.is_draining() {
+                break;
+            }
+            changed.await;
+        }
+
+        let timeout = Duration::from_secs(self.timeout_secs.load(Ordering::SeqCst) as u64);
+        let deadline = Instant::now() + timeout;
+        while self.connections() > 0 {
+            let changed = self.changed.notified();
+            if self.connections() == 0 {
+                break;
+            }
+            if tokio::time::timeout_at(deadline, changed).await.is_err() {
+                warn!("Drain timeout reached with {} open connections.", self.connections());
+                return;
+            }
+        }
+        info!("All connections drained.");
+    }
+}
+
+/// Marks a connection as closed when dropped.
+pub struct ConnectionGuard {
+    maintenance: Arc<Maintenance>,
+}
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.maintenance.connections.fetch_sub(1, Ordering::SeqCst);
+        self.maintenance.changed.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn notifies_the_open_connections_once() {
+        let maintenance = Arc::new(Maintenance::default());
+        let _connection = maintenance.track_connection();
+        let mut notices = maintenance.notices();
+        assert!(!maintenance.is_draining());
+
+        assert!(maintenance.start(Duration::from_secs(30)));
+        assert!(maintenance.is_draining());
+        assert_eq!(notices.try_recv().unwrap(), json!({ "event": "drain", "timeout_secs": 30 }));
+
+        assert!(!maintenance.start(Duration::from_secs(60)));
+        assert!(notices.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drains_once_the_last_connection_closes() {
+        let maintenance = Arc::new(Maintenance::default());
+        let (first, second) = (maintenance.track_connection(), maintenance.track_connection());
+        let started = Instant::now();
+        let drained = tokio::spawn({
+            let maintenance = maintenance.clone();
+            async move { maintenance.drained().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!drained.is_finished());
+
+        maintenance.start(Duration::from_secs(60));
+        drop(first);
+        tokio::task::yield_now().await;
+        assert!(!drained.is_finished());
+
+        drop(second);
+        drained.await.unwrap();
+        assert_eq!(maintenance.connections(), 0);
+        assert!(started.elapsed() < Duration::from_secs(60));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_waiting_at_the_drain_timeout() {
+        let maintenance = Arc::new(Maintenance::default());
+        let _connection = maintenance.track_connection();
+        let started = Instant::now();
+        maintenance.start(Duration::from_secs(60));
+
+        maintenance.drained().await;
+        assert_eq!(started.elapsed(), Duration::from_secs(60));
+        assert_eq!(maintenance.connections(), 1);
+    }
+}
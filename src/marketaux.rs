@@ -5,6 +5,17 @@
 //! To retrieve all news for articles with identified entities, use the parameter must_have_entities, 
 //! or specify any of the entity params such as symbols or exchanges as defined below to produce more concise results.
 //! 
+//! Three news endpoints are supported, selected with the `endpoint` arg of `poll`: `all` (default),
+//! `similar` (news similar to the article of the `uuid` arg) and `uuid` (the article of the `uuid` arg).
+//!
+//! With `"fetch_type": "marketaux_entity_stats"`, `poll` returns the article count and average
+//! sentiment per entity instead (`endpoint` being `aggregation`, the default, or `intraday` for one
+//! series per `interval`). With `"fetch_type": "marketaux_sources"`, it returns the sources that can
+//! be passed as `source_ids` or `domains`.
+//!
+//! The `entity_types` and `industries` filters are checked against the values listed by the API
+//! before sending a request, once fetched (see `enumerations.rs`).
+//!
 //! ## Reference:
 //! [Official Marketaux Documentation](https://www.marketaux.com/documentation).
 //! 
@@ -14,27 +25,49 @@ use std::sync::Arc;
 use std::time::Duration;
 use std::hash::{Hash, Hasher};
 
+use chrono::{DateTime, NaiveDate, Utc};
 use reqwest::{Client, Response, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, from_str, to_value};
-use tracing::{warn, debug, info, error};
+use tracing::{warn, debug, info, error, instrument};
 use tokio::sync::Mutex;
 
 use crate::cache::SharedLockedCache;
+use crate::chaos;
+use crate::api_calls;
+use crate::enumerations::{self, Enumerations};
 use crate::config::ValueConfig;
-use crate::utils::{get_resp_value_from_cache_or_fetch, time_rfc3339_opts};
+use crate::utils::{get_resp_value_from_cache_or_fetch, retry, time_rfc3339_opts};
 use twitter_v2::oauth2::helpers::variant_name;
 use crate::options::FetchType;
-use crate::errors::{AbstractApiError, ApiError};
+use crate::encoding::decode_json_response;
+use crate::fixtures;
+use crate::errors::{AbstractApiError, ApiError, ProviderError};
 use crate::options::MAQueryParams as QueryParams;
+use crate::options::{MASourcesQueryParams, MAStatsQueryParams};
+use crate::lenient;
+use crate::watermark;
+use crate::normalize::NormalizedArticle;
+use crate::provider::{HealthTracker, NewsProvider, ProviderFuture, ProviderHealth};
 
+const API_URL: &str = "https://api.marketaux.com/v1";
 const BASE_URL: &str = "https://api.marketaux.com/v1/news";
+pub const ENTITY_STATS_AGGREGATION_ENDPOINT: &str = "entity/stats/aggregation";
+pub const ENTITY_STATS_INTRADAY_ENDPOINT: &str = "entity/stats/intraday";
+pub const SOURCES_ENDPOINT: &str = "news/sources";
+pub const ENTITY_TYPES_ENDPOINT: &str = "entity/type/list";
+pub const INDUSTRIES_ENDPOINT: &str = "entity/industry/list";
+const STATS_AGGREGATION: &str = "aggregation";
+const STATS_INTRADAY: &str = "intraday";
 pub const ALL_NEWS_ENDPOINT: &str = "all";
 pub const SIMILAR_NEWS_ENDPOINT: &str = "similar";
 pub const NEWS_BY_UUID: &str = "uuid";
 const ENDPONT_MAP_KEY: &str = "endpoint";
+const UUID_MAP_KEY: &str = "uuid";
 const API_TOKEN_MAP_KEY: &str = "api_token";
 const FETCH_TYPE_KEY_MAP: &str = "fetch_type";
+pub const PROVIDER_NAME: &str = "marketaux";
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -71,16 +104,36 @@ impl MarketAuxResponse {
         let json = serde_json::to_string(&map)?;
         Self::from_json(&json)
     }
+
+    /// Appends the articles of the next `page`.
+    fn merge(&mut self, page: MarketAuxResponse) {
+        self.data.extend(page.data);
+        self.meta.returned = self.data.len() as i64;
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Meta {
+    #[serde(default, deserialize_with = "lenient::number")]
     pub found: i64,
+    #[serde(default, deserialize_with = "lenient::number")]
     pub returned: i64,
+    #[serde(default, deserialize_with = "lenient::number")]
     pub limit: i64,
+    #[serde(default, deserialize_with = "lenient::number")]
     pub page: i64,
 }
 
+impl Meta {
+    /// Pages of `found` articles following this one.
+    pub fn pages_after(&self) -> i64 {
+        if self.limit <= 0 {
+            return 0;
+        }
+        ((self.found + self.limit - 1) / self.limit - self.page).max(0)
+    }
+}
+
 impl PartialEq for Meta {
     fn eq(&self, other: &Self) -> bool {
         self.found == other.found &&
@@ -103,8 +156,11 @@ pub struct NewsItem {
     #[serde(rename = "published_at")]
     pub published_at: Option<String>, // you can change this to DateTime if needed
     pub source: Option<String>,
+    #[serde(default, deserialize_with = "lenient::opt_number")]
     pub relevance_score: Option<f64>,
+    #[serde(default, deserialize_with = "lenient::list")]
     pub entities: Vec<Entity>,
+    #[serde(default, deserialize_with = "lenient::list")]
     pub similar: Vec<Value>, // Assuming similar items can vary in structure
 }
 
@@ -130,29 +186,137 @@ pub struct Entity {
     pub country: Option<String>,
     pub r#type: Option<String>, // Using `r#type` to avoid conflicting with the `type` keyword
     pub industry: Option<String>,
+    #[serde(default, deserialize_with = "lenient::number")]
     pub match_score: f64,
+    #[serde(default, deserialize_with = "lenient::number")]
     pub sentiment_score: f64,
+    #[serde(default, deserialize_with = "lenient::list")]
     pub highlights: Vec<Highlight>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Highlight {
     pub highlight: Option<String>,
+    #[serde(default, deserialize_with = "lenient::opt_number")]
     pub sentiment: Option<f64>,
     #[serde(rename = "highlighted_in")]
     pub highlighted_in: Option<String>,
 }
 
+/// Article count and average sentiment of an entity (or of a group of entities, see `group_by`).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct EntityStats {
+    /// Symbol, or exchange, country, type or industry depending on `group_by`.
+    pub key: String,
+    #[serde(default, deserialize_with = "lenient::number")]
+    pub total_documents: i64,
+    #[serde(default, deserialize_with = "lenient::opt_number")]
+    pub sentiment_avg: Option<f64>,
+}
+
+/// Response of the `/entity/stats/aggregation` endpoint.
+///
+/// [See example here](https://www.marketaux.com/documentation#entitystats).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct EntityStatsResponse {
+    pub meta: Meta,
+    pub data: Vec<EntityStats>,
+}
+
+/// Entity stats of one interval.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct IntradayEntityStats {
+    /// Start of the interval.
+    pub date: String,
+    pub data: Vec<EntityStats>,
+}
+
+/// Response of the `/entity/stats/intraday` endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct IntradayEntityStatsResponse {
+    pub meta: Option<Value>,
+    pub data: Vec<IntradayEntityStats>,
+}
+
+/// A news source, as listed by the `/news/sources` endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Source {
+    /// Identifier to pass as `source_ids`.
+    pub source_id: String,
+    pub domain: String,
+    pub language: Option<String>,
+}
+
+/// Response of the `/news/sources` endpoint.
+///
+/// [See example here](https://www.marketaux.com/documentation#sources).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SourcesResponse {
+    pub meta: Meta,
+    pub data: Vec<Source>,
+}
+
+/// Values accepted by a filter, as listed by the `entity/type/list` and `entity/industry/list` endpoints.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ListResponse {
+    pub data: Vec<String>,
+}
+
+/// A news endpoint with its path parameter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MarketAuxEndpoint {
+    /// `/news/all`: every article matching the filters.
+    All,
+    /// `/news/similar/{uuid}`: articles similar to the article `uuid`.
+    Similar(String),
+    /// `/news/uuid/{uuid}`: the article `uuid` alone.
+    ByUuid(String),
+}
+impl MarketAuxEndpoint {
+    /// Endpoint named `endpoint` (`all` when empty), `uuid` being required by `similar` and `uuid`.
+    pub fn from_args(endpoint: &str, uuid: Option<&str>) -> Result<Self, String> {
+        let uuid = uuid.filter(|uuid| !uuid.is_empty()).map(str::to_string);
+        let parsed = match endpoint {
+            "" | ALL_NEWS_ENDPOINT => Some(MarketAuxEndpoint::All),
+            SIMILAR_NEWS_ENDPOINT => uuid.map(MarketAuxEndpoint::Similar),
+            NEWS_BY_UUID => uuid.map(MarketAuxEndpoint::ByUuid),
+            other => return Err(format!("Unknown MarketAux endpoint `{}`", other)),
+        };
+        parsed.ok_or_else(|| format!("Endpoint `{}` requires a `{}` arg", endpoint, UUID_MAP_KEY))
+    }
+
+    fn invalid_args(message: String) -> ApiError {
+        ApiError::RequestError { message, status: None, headers: None, body: None }
+    }
+
+    /// Endpoint name, without the path parameter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MarketAuxEndpoint::All => ALL_NEWS_ENDPOINT,
+            MarketAuxEndpoint::Similar(_) => SIMILAR_NEWS_ENDPOINT,
+            MarketAuxEndpoint::ByUuid(_) => NEWS_BY_UUID,
+        }
+    }
+
+    /// Path of the endpoint relative to the news base url.
+    pub fn path(&self) -> String {
+        match self {
+            MarketAuxEndpoint::All => ALL_NEWS_ENDPOINT.to_string(),
+            MarketAuxEndpoint::Similar(uuid) | MarketAuxEndpoint::ByUuid(uuid) => format!("{}/{}", self.name(), uuid),
+        }
+    }
+}
 
 pub struct MarketAuxApiClient {
     client: Arc<Client>,
     cache: Arc<Mutex<SharedLockedCache>>,
     config: Arc<ValueConfig>,
+    health: HealthTracker,
 }
 impl MarketAuxApiClient {
 
     pub fn new(client: Arc<Client>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Self {
-        Self {client, cache, config}
+        Self {client, cache, config, health: HealthTracker::default()}
     }
 
     fn append_to_base_url(&self, endpoint: &str) -> String {
@@ -162,12 +326,12 @@ impl MarketAuxApiClient {
     async fn get(
         &self,
         fetch_type: &FetchType,
-        endpoint: &str,
+        endpoint: &MarketAuxEndpoint,
         query_params: Option<QueryParams>   
     ) -> Result<Value, ApiError> {
         match fetch_type {
             FetchType::MarketAux => {
-                let key = format!("{}_{}_{:?}", variant_name(&fetch_type), endpoint, &query_params);
+                let key = format!("{}_{}_{:?}", variant_name(&fetch_type), endpoint.path(), &query_params);
                 get_resp_value_from_cache_or_fetch(
                     &self.cache, 
                     &key, 
@@ -186,36 +350,17 @@ impl MarketAuxApiClient {
         }
     }
 
-    async fn get_(
+    /// Sends a GET request to `url` and returns the decoded body, recorded as the `name` fixture.
+    async fn get_body<Q: Serialize>(
         &self,
-        endpoint: &str,
-        query_params: Option<QueryParams>
+        url: &str,
+        name: &str,
+        query_params: &Q
     ) -> Result<Value, ApiError> {
             // Send GET request
-            let response = self
-            .client
-            .get(&self.append_to_base_url(endpoint))
-            .query(&query_params)
-            .send()
-            .await.map_err(|e| {
-                warn!("MarketAux client encountered an error during GET request.");
-                // Check if the error is a network error
-                if e.is_timeout() || e.is_connect() {
-                    ApiError::NetworkError {
-                        message: e.to_string(),
-                        status: Some(StatusCode::REQUEST_TIMEOUT), //Error: 408 - substitutes to `None`: normaly error is not received here, as the rea did not even go through,
-                        headers: None,
-                        body: None,
-                    }
-                } else {
-                    ApiError::RequestError{
-                        message: e.to_string(),
-                        status: Some(StatusCode::BAD_REQUEST),  // Error 400
-                        headers: None,
-                        body: None
-                    }
-                }
-            })?; // Handle request error
+            let response = api_calls::send(PROVIDER_NAME, self.client.get(url).query(&query_params))
+            .await
+            .inspect_err(|_| warn!("MarketAux client encountered an error during GET request."))?;
 
         // Check for rate limit error in response
         if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
@@ -256,11 +401,52 @@ impl MarketAuxApiClient {
         // Attempt to parse the JSON response directly
         // Also the only place the Response super-struct `MarketAuxResponse` is Actually used.
         // For data integrity reasons.
-        let response_json: MarketAuxResponse = response.json().await.map_err(|e| {
-            error!("Failed to read body: {:?}", e);
+        // The body is decoded first so HTML error pages and odd encodings surface as `EncodingError`.
+        let body = decode_json_response(PROVIDER_NAME, response).await.map_err(|e| {
+            error!("Failed to read body: {}", e);
+            e
+        })?;
+        fixtures::record("marketaux", name, &body);
+        Ok(chaos::response_body(PROVIDER_NAME, body))
+    }
+
+    async fn get_(
+        &self,
+        endpoint: &MarketAuxEndpoint,
+        query_params: Option<QueryParams>
+    ) -> Result<Value, ApiError> {
+        let body = self.get_body(&self.append_to_base_url(&endpoint.path()), endpoint.name(), &query_params).await?;
+        // The `uuid` endpoint answers with the article alone.
+        if let MarketAuxEndpoint::ByUuid(_) = endpoint {
+            let item: NewsItem = serde_json::from_value(body).map_err(|e| {
+                error!("Failed to parse body: {:?}", e);
+                ApiError::JsonParseError { message: e.to_string() }
+            })?;
+            return to_value(item).map_err(|e| ApiError::JsonParseError { message: e.to_string() });
+        }
+        let mut response_json: MarketAuxResponse = serde_json::from_value(body).map_err(|e| {
+            error!("Failed to parse body: {:?}", e);
             ApiError::JsonParseError { message: e.to_string() }
         })?; // Handle JSON parsing error
 
+        // With `fetch_all_pages`, the following pages are merged into the first one.
+        if let Some(query_params) = query_params.filter(|q| q.pages_to_fetch() > 1) {
+            let remaining = (query_params.pages_to_fetch() - 1).min(response_json.meta.pages_after() as u64);
+            let first_page = response_json.meta.page;
+            for offset in 1..=remaining as i64 {
+                let params = query_params.clone().with_page((first_page + offset) as i32);
+                let body = self.get_body(&self.append_to_base_url(&endpoint.path()), endpoint.name(), &params).await?;
+                let page: MarketAuxResponse = serde_json::from_value(body).map_err(|e| {
+                    error!("Failed to parse body: {:?}", e);
+                    ApiError::JsonParseError { message: e.to_string() }
+                })?;
+                if page.data.is_empty() {
+                    break;
+                }
+                response_json.merge(page);
+            }
+        }
+
         response_json.to_json()
     }
 
@@ -318,36 +504,105 @@ impl MarketAuxApiClient {
         Arc::new(value)
     }
 
-    fn pop_endpoint(&self, value: Arc<Value>) -> Option<((String, Value), Arc<Value>)> {
+    /// Pops the `endpoint` and `uuid` args, which are not query parameters.
+    fn pop_endpoint(&self, value: Arc<Value>) -> Option<(Result<MarketAuxEndpoint, ApiError>, Arc<Value>)> {
         let mut value = Arc::try_unwrap(value).unwrap_or_else(|v| (*v).clone());
         if let Value::Object(ref mut map) = value {
-            Some((map
-                    .remove_entry(ENDPONT_MAP_KEY)
-                    .unwrap_or((ENDPONT_MAP_KEY.to_string(), Value::String("".to_string()))), Arc::new(value))
+            let endpoint = map.remove(ENDPONT_MAP_KEY);
+            let uuid = map.remove(UUID_MAP_KEY);
+            let endpoint = MarketAuxEndpoint::from_args(
+                endpoint.as_ref().and_then(Value::as_str).unwrap_or(ALL_NEWS_ENDPOINT),
+                uuid.as_ref().and_then(Value::as_str),
             )
+            .map_err(MarketAuxEndpoint::invalid_args);
+            Some((endpoint, Arc::new(value)))
         } else {
             None
         }
     }
 
+    /// Fetches `path` (relative to the API url) with a cache and retries, checking the body against `T`.
+    async fn get_typed<T, Q>(&self, path: &str, query_params: Q) -> Result<Value, ApiError>
+    where
+        T: DeserializeOwned + Serialize,
+        Q: Serialize + fmt::Debug,
+    {
+        let key = format!("marketaux_{}_{:?}", path, &query_params);
+        let url = format!("{}/{}", API_URL, path);
+        retry(PROVIDER_NAME, &self.config, || async {
+            get_resp_value_from_cache_or_fetch(
+                &self.cache,
+                &key,
+                || async {
+                    let body = self.get_body(&url, path, &query_params).await?;
+                    let typed: T = serde_json::from_value(body).map_err(|e| {
+                        error!("Failed to parse body: {:?}", e);
+                        ApiError::JsonParseError { message: e.to_string() }
+                    })?;
+                    to_value(typed).map_err(|e| ApiError::JsonParseError { message: e.to_string() })
+                },
+                self.config.task.cache_ttl,
+            ).await
+        }).await
+    }
+
+    /// Article count and average sentiment per entity, over the whole period (`aggregation` endpoint)
+    /// or per `interval` (`intraday` endpoint).
+    pub async fn entity_stats(&self, endpoint: &str, query_params: MAStatsQueryParams) -> Result<Value, ApiError> {
+        match endpoint {
+            "" | STATS_AGGREGATION => self.get_typed::<EntityStatsResponse, _>(ENTITY_STATS_AGGREGATION_ENDPOINT, query_params).await,
+            STATS_INTRADAY => self.get_typed::<IntradayEntityStatsResponse, _>(ENTITY_STATS_INTRADAY_ENDPOINT, query_params).await,
+            other => Err(MarketAuxEndpoint::invalid_args(format!("Unknown entity stats endpoint `{}`", other))),
+        }
+    }
+
+    /// Sources that can be passed as `source_ids` or `domains`.
+    pub async fn sources(&self, query_params: MASourcesQueryParams) -> Result<Value, ApiError> {
+        self.get_typed::<SourcesResponse, _>(SOURCES_ENDPOINT, query_params).await
+    }
+
+    /// Values accepted by the `entity_types` and `industries` filters.
+    pub async fn enumerations(&self) -> Result<Enumerations, ApiError> {
+        let mut enumerations = Enumerations::new();
+        for (filter, path) in [("entity_types", ENTITY_TYPES_ENDPOINT), ("industries", INDUSTRIES_ENDPOINT)] {
+            let value = self.get_typed::<ListResponse, _>(path, [(API_TOKEN_MAP_KEY, &self.config.api.marketaux)]).await?;
+            let list: ListResponse = serde_json::from_value(value)
+                .map_err(|e| ApiError::JsonParseError { message: e.to_string() })?;
+            enumerations.insert(filter.to_string(), list.data);
+        }
+        Ok(enumerations)
+    }
+
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME, function = args["function"].as_str()))]
     pub async fn poll(&self, args: Arc<Value>) -> Result<Value, ApiError> {
+        enumerations::check(PROVIDER_NAME, &args).map_err(MarketAuxEndpoint::invalid_args)?;
         // Insert API token into the provided args value.
         let args = self.insert_api_token(args);
+        let fetch_type = args.get(FETCH_TYPE_KEY_MAP)
+            .and_then(|s| s.as_str())
+            .map(FetchType::from_str)
+            .unwrap_or(FetchType::Unknown);
+        match fetch_type {
+            FetchType::MarketAuxEntityStats => {
+                let endpoint = args.get(ENDPONT_MAP_KEY).and_then(Value::as_str).unwrap_or_default().to_string();
+                let query_params = MAStatsQueryParams::try_from((*args).clone())?;
+                return self.entity_stats(&endpoint, query_params).await;
+            }
+            FetchType::MarketAuxSources => {
+                return self.sources(MASourcesQueryParams::try_from((*args).clone())?).await;
+            }
+            _ => {}
+        }
         // Extract the endpoint from the provided args value.
-        if let Some(((_key, endpoint), args)) = self.pop_endpoint(args) {
-            let endpoint = endpoint.as_str()
-                .unwrap_or_else(|| ALL_NEWS_ENDPOINT);
+        if let Some((endpoint, args)) = self.pop_endpoint(args) {
+            let endpoint = endpoint?;
             // Perform GET request with retry mechanism.
             let mut retry_count = 0;
             let max_retries = self.config.task.max_retries;
             let delay_ms = self.config.task.base_delay_ms as u64;
             let delay = Duration::from_millis(delay_ms);
-            let fetch_type = args.get(FETCH_TYPE_KEY_MAP) // which does not get popped out of the query params
-                .and_then(|s| s.as_str())
-                .map(FetchType::from_str)
-                .unwrap_or(FetchType::Unknown);
             loop {
-                match self.get(&fetch_type, endpoint, Some(QueryParams::try_from(args.clone())?)).await {
+                match self.get(&fetch_type, &endpoint, Some(QueryParams::try_from(args.clone())?)).await {
                     Ok(response) => {
                         info!("API GET Response was successful? : {:?}", bool::from(!response.is_null()));
                         return Ok(response);
@@ -369,47 +624,159 @@ impl MarketAuxApiClient {
             Err(ApiError::NoEndpointProvided)
         }
     }
+
+    /// Fetches the news published since the last polling cycle from `endpoint`.
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME))]
+    pub async fn latest(&self, endpoint: &MarketAuxEndpoint) -> Result<Value, ApiError> {
+        // Construct query parameters for the API request, currently set to None for all optional fields.
+        let query = QueryParams::new(
+            &self.config.api.marketaux, 
+            None, // Symbols, 
+            None, // entity_types, 
+            None, // industries, 
+            None, // countries, 
+            None, // sentiment_gte, 
+            None, // sentiment_lte, 
+            None, // min_match_score, 
+            None, // filter_entities, 
+            None, // must_have_entities, 
+            None, // group_similar, 
+            None, // search, 
+            None, // domains, 
+            None, // exclude_domains, 
+            None, // source_ids, 
+            None, // exclude_source_ids, 
+            None, // language, 
+            None, // published_before, 
+            Some(&window_bound(watermark::since(PROVIDER_NAME, self.config.request.delay_secs))), // published_after, 
+            None, // published_on, 
+            None, // sort, 
+            None, // sort_order, 
+            None, // limit, 
+            None); // page
+
+        // Send a GET request to the Marketaux API and await the result.
+        self.get_(endpoint, Some(query)).await
+            .map_err(|e|  {
+                error!("Error during GET request: {}", e); // Log error
+                e // Repropagate error
+            })
+    }
+
+    /// Fetches the news published between `from` and `to`.
+    pub async fn window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<NormalizedArticle>, ApiError> {
+        let published_before = window_bound(to);
+        let published_after = window_bound(from);
+        let query = QueryParams::new(
+            &self.config.api.marketaux,
+            None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+            Some(&published_before), // published_before,
+            Some(&published_after), // published_after,
+            None, None, None, None, None);
+
+        let value = self.get_(&MarketAuxEndpoint::All, Some(query)).await?;
+        let response: MarketAuxResponse = serde_json::from_value(value)
+            .map_err(|e| ApiError::JsonParseError { message: e.to_string() })?;
+        Ok(response.data.iter().map(NormalizedArticle::from).collect())
+    }
+
+    /// Fetches the news published on `day`, following the pagination up to
+    /// `backfill.max_pages_per_day` pages.
+    pub async fn day(&self, day: NaiveDate) -> Result<Vec<NormalizedArticle>, ApiError> {
+        let published_on = day.format("%Y-%m-%d").to_string();
+        let query = QueryParams::new(
+            &self.config.api.marketaux,
+            None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+            None, // published_before
+            None, // published_after
+            Some(&published_on), // published_on
+            None, None, None, None)
+            .with_all_pages(self.config.backfill.max_pages_per_day);
+
+        let value = self.get_(&MarketAuxEndpoint::All, Some(query)).await?;
+        let response: MarketAuxResponse = serde_json::from_value(value)
+            .map_err(|e| ApiError::JsonParseError { message: e.to_string() })?;
+        Ok(response.data.iter().map(NormalizedArticle::from).collect())
+    }
+
+    /// Fetches the news published since the last cycle mentioning `symbol`.
+    pub async fn symbol_news(&self, symbol: &str) -> Result<Vec<NormalizedArticle>, ApiError> {
+        let published_after = time_rfc3339_opts(self.config.request.delay_secs);
+        let query = QueryParams::new(
+            &self.config.api.marketaux,
+            Some(symbol), // symbols
+            None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+            None, // published_before
+            Some(&published_after), // published_after
+            None, None, None, None, None);
+
+        let value = self.get_(&MarketAuxEndpoint::All, Some(query)).await?;
+        let response: MarketAuxResponse = serde_json::from_value(value)
+            .map_err(|e| ApiError::JsonParseError { message: e.to_string() })?;
+        Ok(response.data.iter().map(NormalizedArticle::from).collect())
+    }
+}
+
+/// Formats a window bound the way `published_after` and `published_before` expect it.
+fn window_bound(bound: DateTime<Utc>) -> String {
+    bound.format("%Y-%m-%dT%H:%M:%S").to_string()
 }
 
-pub async fn run(endpoint: &str, client: Arc<Client>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Result<Value, ApiError> {
-    // Construct query parameters for the API request, currently set to None for all optional fields.
-    let query = QueryParams::new(
-        &config.api.marketaux, 
-        None, // Symbols, 
-        None, // entity_types, 
-        None, // industries, 
-        None, // countries, 
-        None, // sentiment_gte, 
-        None, // sentiment_lte, 
-        None, // min_match_score, 
-        None, // filter_entities, 
-        None, // must_have_entities, 
-        None, // group_similar, 
-        None, // search, 
-        None, // domains, 
-        None, // exclude_domains, 
-        None, // source_ids, 
-        None, // exclude_source_ids, 
-        None, // language, 
-        None, // published_before, 
-        Some(&time_rfc3339_opts(config.request.delay_secs).as_str()), // published_after, 
-        None, // published_on, 
-        None, // sort, 
-        None, // sort_order, 
-        None, // limit, 
-        None); // page
+impl NewsProvider for MarketAuxApiClient {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn supports(&self, fetch_type: &FetchType) -> bool {
+        matches!(fetch_type, FetchType::MarketAux | FetchType::MarketAuxEntityStats | FetchType::MarketAuxSources)
+    }
+
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.poll(args).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.latest(&MarketAuxEndpoint::All).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn health(&self) -> ProviderHealth {
+        self.health.health(&self.config.api.marketaux)
+    }
+
+    fn fetch_window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async move { Ok(self.window(from, to).await?) })
+    }
+
+    fn fetch_day(&self, day: NaiveDate) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async move { Ok(self.day(day).await?) })
+    }
+
+    fn fetch_symbol<'a>(&'a self, symbol: &'a str) -> ProviderFuture<'a, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async move {
+            let result = self.symbol_news(symbol).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_enumerations(&self) -> ProviderFuture<'_, Result<Enumerations, ProviderError>> {
+        Box::pin(async move { Ok(self.enumerations().await?) })
+    }
+}
 
+pub async fn run(endpoint: &MarketAuxEndpoint, client: Arc<Client>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Result<Value, ApiError> {
     // Initialize the request manager with the created client.
     let req_manager = MarketAuxApiClient::new(client, cache, config);
 
-    // Send a GET request to the Marketaux API and await the result.
-    let result = req_manager.get_(endpoint, Some(query)).await
-        .map_err(|e|  {
-            error!("Error during GET request: {}", e); // Log error
-            e // Repropagate error
-        })?;
-
-    // Return the result of the API request.
-    Ok(result)
+    // Send a GET request to the Marketaux API and return the result.
+    req_manager.latest(endpoint).await
 }
 
@@ -0,0 +1,480 @@
+//! Provider-agnostic article schema.
+//!
+//! Each provider models articles differently (MarketAux `NewsItem`, Alpha Vantage `FeedItem`, FMP `FMPArticle`,
+//! Finnhub `FinnhubArticle`, Polygon.io `PolygonArticle`, GDELT `GdeltArticle`, feed entries in `rss.rs`).
+//! `NormalizedArticle` is the common shape stored in MongoDB and pushed to clients, so that
+//! filters and aggregations do not need to know where an article came from.
+
+use chrono::{DateTime, Duration, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use serde::{Deserialize, Serialize};
+
+use crate::alphavantage::FeedItem;
+use crate::entities::Entity;
+use crate::finnhub::FinnhubArticle;
+use crate::gdelt::GdeltArticle;
+use crate::polygon::PolygonArticle;
+use crate::marketaux::NewsItem;
+use crate::sentiment::{harmonize, SentimentLabel};
+use crate::taxonomy;
+use crate::server_types::FMPArticle;
+
+pub const MARKETAUX_PROVIDER: &str = "marketaux";
+pub const ALPHAVANTAGE_PROVIDER: &str = "alphavantage";
+pub const FMP_PROVIDER: &str = "fmp";
+pub const FINNHUB_PROVIDER: &str = "finnhub";
+pub const POLYGON_PROVIDER: &str = "polygon";
+pub const RSS_PROVIDER: &str = "rss";
+pub const GDELT_PROVIDER: &str = "gdelt";
+
+/// An article in the common schema.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct NormalizedArticle {
+    /// Name of the provider the article was fetched from.
+    pub provider: String,
+    /// Provider identifier of the article (uuid when available, url otherwise).
+    pub id: String,
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub source: Option<String>,
+    pub authors: Vec<String>,
+    pub language: Option<String>,
+    /// Publication time in RFC 3339 format (UTC).
+    pub published_at: Option<String>,
+    pub tickers: Vec<String>,
+    pub topics: Vec<String>,
+    pub sentiment_score: Option<f64>,
+    /// Label as given by the provider.
+    pub sentiment_label: Option<String>,
+    /// Label harmonized across providers, see `sentiment.rs`.
+    #[serde(default)]
+    pub sentiment: Option<SentimentLabel>,
+    /// Topics, or keywords extracted when the provider gives none, see `keywords.rs`.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// `topics` mapped onto the internal taxonomy, see `taxonomy.rs`.
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Watchlist symbols the article was fetched for, see `watchlist.rs`.
+    #[serde(default)]
+    pub watchlist: Vec<String>,
+    /// People, organizations and locations named, see `entities.rs`.
+    #[serde(default)]
+    pub entities: Vec<Entity>,
+    /// `title` and `summary` translated, when in another language, see `translate.rs`.
+    #[serde(default)]
+    pub translation: Option<Translation>,
+}
+
+/// Title and summary of an article translated into `language`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Translation {
+    pub language: String,
+    /// Language the translation API detected, when the provider gave none.
+    pub detected_language: Option<String>,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+}
+
+impl NormalizedArticle {
+    /// Sets the `categories` from the `topics`.
+    pub fn with_categories(mut self) -> Self {
+        self.categories = taxonomy::categorize(&self.topics);
+        self
+    }
+
+    /// Tags the article with the watchlist `symbol` it was fetched for.
+    pub fn tag_symbol(&mut self, symbol: &str) {
+        if !self.watchlist.iter().any(|s| s == symbol) {
+            self.watchlist.push(symbol.to_string());
+        }
+    }
+
+    /// Whether `other` carries the same content and sentiment, ignoring the delivery details.
+    pub fn same_content(&self, other: &Self) -> bool {
+        self.title == other.title
+            && self.summary == other.summary
+            && self.tickers == other.tickers
+            && self.topics == other.topics
+            && self.sentiment_score == other.sentiment_score
+            && self.sentiment_label == other.sentiment_label
+    }
+}
+
+/// Articles of the tests: `NormalizedArticle::test(id)`, then the fields a test needs.
+#[cfg(test)]
+impl NormalizedArticle {
+    /// Article `id` of `finnhub`, every other field empty.
+    pub fn test(id: &str) -> Self {
+        Self {
+            provider: FINNHUB_PROVIDER.to_string(),
+            id: id.to_string(),
+            url: None,
+            title: None,
+            summary: None,
+            source: None,
+            authors: Vec::new(),
+            language: None,
+            published_at: None,
+            tickers: Vec::new(),
+            topics: Vec::new(),
+            sentiment_score: None,
+            sentiment_label: None,
+            sentiment: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
+            watchlist: Vec::new(),
+            entities: Vec::new(),
+            translation: None,
+        }
+    }
+
+    pub fn provider(mut self, provider: &str) -> Self {
+        self.provider = provider.to_string();
+        self
+    }
+
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    pub fn language<'a>(mut self, language: impl Into<Option<&'a str>>) -> Self {
+        self.language = language.into().map(str::to_string);
+        self
+    }
+
+    pub fn published_at(mut self, published_at: &str) -> Self {
+        self.published_at = Some(published_at.to_string());
+        self
+    }
+
+    pub fn tickers(mut self, tickers: &[&str]) -> Self {
+        self.tickers = tickers.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn topics(mut self, topics: &[&str]) -> Self {
+        self.topics = topics.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn sentiment_score(mut self, score: impl Into<Option<f64>>) -> Self {
+        self.sentiment_score = score.into();
+        self
+    }
+
+    pub fn sentiment(mut self, label: impl Into<Option<SentimentLabel>>) -> Self {
+        self.sentiment = label.into();
+        self
+    }
+
+    pub fn keywords(mut self, keywords: &[&str]) -> Self {
+        self.keywords = keywords.iter().map(|k| k.to_string()).collect();
+        self
+    }
+
+    pub fn categories(mut self, categories: &[&str]) -> Self {
+        self.categories = categories.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn entity(mut self, name: &str, kind: crate::entities::EntityKind) -> Self {
+        self.entities.push(Entity { name: name.to_string(), kind });
+        self
+    }
+
+    /// Sets the English `title` of a translation.
+    pub fn translated(mut self, title: &str) -> Self {
+        self.translation = Some(Translation { language: "en".to_string(), detected_language: None, title: Some(title.to_string()), summary: None });
+        self
+    }
+}
+
+impl From<&NewsItem> for NormalizedArticle {
+    fn from(item: &NewsItem) -> Self {
+        let entities_sentiment: Vec<f64> = item.entities.iter().map(|e| e.sentiment_score).collect();
+        let sentiment_score = match entities_sentiment.len() {
+            0 => None,
+            n => Some(entities_sentiment.iter().sum::<f64>() / n as f64),
+        };
+        let mut topics: Vec<String> = Vec::new();
+        for industry in item.entities.iter().filter_map(|e| e.industry.as_ref()) {
+            if !topics.contains(industry) {
+                topics.push(industry.clone());
+            }
+        }
+
+        Self {
+            provider: MARKETAUX_PROVIDER.to_string(),
+            id: item.uuid.clone().or_else(|| item.url.clone()).unwrap_or_default(),
+            url: item.url.clone(),
+            title: item.title.clone(),
+            summary: item.description.clone().or_else(|| item.snippet.clone()),
+            source: item.source.clone(),
+            authors: Vec::new(),
+            language: item.language.clone(),
+            published_at: item.published_at.as_deref().and_then(normalize_timestamp),
+            tickers: item.entities.iter().filter_map(|e| e.symbol.clone()).collect(),
+            topics,
+            sentiment_score,
+            sentiment_label: None,
+            sentiment: harmonize(None, sentiment_score),
+            keywords: Vec::new(),
+            categories: Vec::new(),
+            watchlist: Vec::new(),
+            entities: Vec::new(),
+            translation: None,
+        }
+        .with_categories()
+    }
+}
+
+impl From<&FeedItem> for NormalizedArticle {
+    fn from(item: &FeedItem) -> Self {
+        Self {
+            provider: ALPHAVANTAGE_PROVIDER.to_string(),
+            id: item.url.clone().unwrap_or_default(),
+            url: item.url.clone(),
+            title: item.title.clone(),
+            summary: item.summary.clone(),
+            source: item.source.clone(),
+            authors: item.authors.clone(),
+            language: None,
+            published_at: item.time_published.as_deref().and_then(normalize_timestamp),
+            tickers: item.ticker_sentiment.iter().filter_map(|t| t.ticker.clone()).collect(),
+            topics: item.topics.iter().filter_map(|t| t.topic.clone()).collect(),
+            sentiment_score: Some(item.overall_sentiment_score),
+            sentiment_label: item.overall_sentiment_label.clone(),
+            sentiment: harmonize(item.overall_sentiment_label.as_deref(), Some(item.overall_sentiment_score)),
+            keywords: Vec::new(),
+            categories: Vec::new(),
+            watchlist: Vec::new(),
+            entities: Vec::new(),
+            translation: None,
+        }
+        .with_categories()
+    }
+}
+
+impl From<&FMPArticle> for NormalizedArticle {
+    fn from(item: &FMPArticle) -> Self {
+        let url = item.url.clone().or_else(|| item.link.clone());
+        let tickers = match (&item.symbol, &item.tickers) {
+            (Some(symbol), _) => vec![symbol.clone()],
+            // `tickers` is formatted as `EXCHANGE:SYMBOL,EXCHANGE:SYMBOL`.
+            (None, Some(tickers)) => tickers
+                .split(',')
+                .map(|t| t.rsplit(':').next().unwrap_or(t).trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            (None, None) => Vec::new(),
+        };
+
+        Self {
+            provider: FMP_PROVIDER.to_string(),
+            id: url.clone().unwrap_or_default(),
+            url,
+            title: item.title.clone(),
+            summary: item.text.clone().or_else(|| item.content.clone()),
+            source: item.site.clone(),
+            authors: item.author.clone().into_iter().collect(),
+            language: None,
+            published_at: item
+                .published_date
+                .as_deref()
+                .or(item.date.as_deref())
+                .and_then(normalize_fmp_timestamp),
+            tickers,
+            topics: item.type_name.iter().map(|t| t.to_str().to_string()).collect(),
+            sentiment_score: item.sentiment_score,
+            sentiment_label: item.sentiment.clone(),
+            sentiment: harmonize(item.sentiment.as_deref(), item.sentiment_score),
+            keywords: Vec::new(),
+            categories: Vec::new(),
+            watchlist: Vec::new(),
+            entities: Vec::new(),
+            translation: None,
+        }
+        .with_categories()
+    }
+}
+
+impl From<&FinnhubArticle> for NormalizedArticle {
+    fn from(item: &FinnhubArticle) -> Self {
+        Self {
+            provider: FINNHUB_PROVIDER.to_string(),
+            id: item.id.map(|id| id.to_string()).or_else(|| item.url.clone()).unwrap_or_default(),
+            url: item.url.clone(),
+            title: item.headline.clone(),
+            summary: item.summary.clone().filter(|s| !s.is_empty()),
+            source: item.source.clone(),
+            authors: Vec::new(),
+            language: None,
+            // Finnhub publishes UNIX timestamps.
+            published_at: item
+                .datetime
+                .and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0))
+                .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            // `related` is a comma separated list of symbols.
+            tickers: item
+                .related
+                .iter()
+                .flat_map(|r| r.split(','))
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            topics: item.category.clone().into_iter().collect(),
+            sentiment_score: None,
+            sentiment_label: None,
+            sentiment: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
+            watchlist: Vec::new(),
+            entities: Vec::new(),
+            translation: None,
+        }
+        .with_categories()
+    }
+}
+
+impl From<&PolygonArticle> for NormalizedArticle {
+    fn from(item: &PolygonArticle) -> Self {
+        // Insights only carry a label: positive = 1, neutral = 0, negative = -1, averaged over the tickers.
+        let scores: Vec<f64> = item
+            .insights
+            .iter()
+            .filter_map(|i| match i.sentiment.as_deref() {
+                Some("positive") => Some(1.0),
+                Some("neutral") => Some(0.0),
+                Some("negative") => Some(-1.0),
+                _ => None,
+            })
+            .collect();
+        let sentiment_score = match scores.len() {
+            0 => None,
+            n => Some(scores.iter().sum::<f64>() / n as f64),
+        };
+
+        Self {
+            provider: POLYGON_PROVIDER.to_string(),
+            id: item.id.clone().or_else(|| item.article_url.clone()).unwrap_or_default(),
+            url: item.article_url.clone(),
+            title: item.title.clone(),
+            summary: item.description.clone(),
+            source: item.publisher.as_ref().and_then(|p| p.name.clone()),
+            authors: item.author.clone().into_iter().collect(),
+            language: None,
+            published_at: item.published_utc.as_deref().and_then(normalize_timestamp),
+            tickers: item.tickers.clone(),
+            topics: item.keywords.clone(),
+            sentiment_score,
+            sentiment_label: None,
+            sentiment: harmonize(None, sentiment_score),
+            keywords: Vec::new(),
+            categories: Vec::new(),
+            watchlist: Vec::new(),
+            entities: Vec::new(),
+            translation: None,
+        }
+        .with_categories()
+    }
+}
+
+impl From<&GdeltArticle> for NormalizedArticle {
+    fn from(item: &GdeltArticle) -> Self {
+        Self {
+            provider: GDELT_PROVIDER.to_string(),
+            id: item.url.clone().unwrap_or_default(),
+            url: item.url.clone(),
+            title: item.title.clone(),
+            summary: None,
+            source: item.domain.clone(),
+            authors: Vec::new(),
+            language: item.language.as_deref().map(language_code),
+            // `seendate` is `YYYYMMDDTHHMMSSZ`.
+            published_at: item.seendate.as_deref().and_then(|s| normalize_timestamp(s.trim_end_matches('Z'))),
+            tickers: Vec::new(),
+            topics: Vec::new(),
+            sentiment_score: None,
+            sentiment_label: None,
+            sentiment: None,
+            keywords: Vec::new(),
+            categories: Vec::new(),
+            watchlist: Vec::new(),
+            entities: Vec::new(),
+            translation: None,
+        }
+        .with_categories()
+    }
+}
+
+/// ISO 639-1 code of a language name (GDELT reports `English`, the other providers `en`).
+/// Unknown names are returned lowercased.
+pub fn language_code(name: &str) -> String {
+    let code = match name.trim().to_lowercase().as_str() {
+        "english" => "en",
+        "french" => "fr",
+        "german" => "de",
+        "spanish" => "es",
+        "italian" => "it",
+        "portuguese" => "pt",
+        "dutch" => "nl",
+        "russian" => "ru",
+        "chinese" => "zh",
+        "japanese" => "ja",
+        "korean" => "ko",
+        "arabic" => "ar",
+        other => return other.to_string(),
+    };
+    code.to_string()
+}
+
+/// Parses the timestamp formats used by the providers into an RFC 3339 UTC string.
+///
+/// Supported: RFC 3339 (MarketAux, FMP RSS), `YYYYMMDDTHHMMSS` (Alpha Vantage)
+/// and `YYYY-MM-DD HH:MM:SS`. Times without an offset are read as UTC.
+pub fn normalize_timestamp(raw: &str) -> Option<String> {
+    parse_timestamp(raw).map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Like `normalize_timestamp`, but FMP gives its times without an offset in New York time.
+pub fn normalize_fmp_timestamp(raw: &str) -> Option<String> {
+    let dt = match DateTime::parse_from_rfc3339(raw.trim()) {
+        Ok(dt) => dt.with_timezone(&Utc),
+        Err(_) => {
+            let naive = parse_naive(raw)?;
+            // Times skipped when the clocks move forward are read an hour later.
+            let local = New_York.from_local_datetime(&naive).earliest()
+                .or_else(|| New_York.from_local_datetime(&(naive + Duration::hours(1))).earliest())?;
+            local.with_timezone(&Utc)
+        }
+    };
+    Some(dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Parses the timestamp formats used by the providers into a UTC `DateTime`.
+pub fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    match DateTime::parse_from_rfc3339(raw.trim()) {
+        Ok(dt) => Some(dt.with_timezone(&Utc)),
+        Err(_) => parse_naive(raw).map(|naive| naive.and_utc()),
+    }
+}
+
+/// Parses the timestamp formats without an offset.
+fn parse_naive(raw: &str) -> Option<NaiveDateTime> {
+    ["%Y%m%dT%H%M%S", "%Y%m%dT%H%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(raw.trim(), fmt).ok())
+}
@@ -12,38 +12,64 @@ use tokio::sync::Mutex;
 
 use crate::errors::ApiError;
 
+/// Pages fetched by the clients following the pagination when `max_pages` is not set.
+pub const DEFAULT_MAX_PAGES: u64 = 10;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FetchType {
     MarketAux,
+    MarketAuxEntityStats,
+    MarketAuxSources,
     AlphaVantage,
+    AlphaVantageTopMovers,
+    AlphaVantageInsiderTransactions,
+    AlphaVantageEarningsCalendar,
     FMPArticle,
+    EarningsTranscript,
     GeneralNews,
     StockNews,
     StockRSS,
     CryptoNews,
     ForexNews,
     PressReleases,
+    MergersAcquisitions,
     SocialSentimentHistory,
     SocialSentimentTrending,
     SocialSentimentChanges,
+    Finnhub,
+    Polygon,
+    Rss,
+    Reddit,
+    Gdelt,
     Unknown
 }
 impl Display for  FetchType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let name = match self {
             FetchType::MarketAux => "Market Auxiliary",
+            FetchType::MarketAuxEntityStats => "Market Auxiliary Entity Stats",
+            FetchType::MarketAuxSources => "Market Auxiliary Sources",
             FetchType::AlphaVantage => "Alpha Vantage",
+            FetchType::AlphaVantageTopMovers => "Alpha Vantage Top Gainers & Losers",
+            FetchType::AlphaVantageInsiderTransactions => "Alpha Vantage Insider Transactions",
+            FetchType::AlphaVantageEarningsCalendar => "Alpha Vantage Earnings Calendar",
             FetchType::FMPArticle => "FMP Article",
+            FetchType::EarningsTranscript => "Earnings Call Transcript",
             FetchType::GeneralNews => "General News",
             FetchType::StockNews => "Stock News",
             FetchType::StockRSS => "Stock RSS",
             FetchType::CryptoNews => "Crypto News",
             FetchType::ForexNews => "Forex News",
             FetchType::PressReleases => "Press Releases",
+            FetchType::MergersAcquisitions => "Mergers & Acquisitions",
             FetchType::SocialSentimentHistory => "Social Sentiment History",
             FetchType::SocialSentimentTrending => "Social Sentiment Trending",
             FetchType::SocialSentimentChanges => "Social Sentiment Changes",
+            FetchType::Finnhub => "Finnhub",
+            FetchType::Polygon => "Polygon",
+            FetchType::Rss => "RSS",
+            FetchType::Reddit => "Reddit",
+            FetchType::Gdelt => "GDELT",
             _ => "Unknown",
         };
         write!(f, "{}", name)
@@ -56,17 +82,29 @@ impl FetchType {
         let value = Arc::try_unwrap(value).unwrap_or_else(|v| (*v).clone());
         match value["function"].as_str() {
             Some("marketaux") => FetchType::MarketAux,
+            Some("marketaux entity stats") => FetchType::MarketAuxEntityStats,
+            Some("marketaux sources") => FetchType::MarketAuxSources,
             Some("alphavantage") => FetchType::AlphaVantage,
+            Some("alphavantage top movers") => FetchType::AlphaVantageTopMovers,
+            Some("alphavantage insider transactions") => FetchType::AlphaVantageInsiderTransactions,
+            Some("alphavantage earnings calendar") => FetchType::AlphaVantageEarningsCalendar,
             Some("fmp articles") => FetchType::FMPArticle,
+            Some("earnings transcript") => FetchType::EarningsTranscript,
             Some("general news") => FetchType::GeneralNews,
             Some("stock news") => FetchType::StockNews,
             Some("stock rss") => FetchType::StockRSS,
             Some("crypto news") => FetchType::CryptoNews,
             Some("forex news") => FetchType::ForexNews,
             Some("press releases") => FetchType::PressReleases,
+            Some("mergers acquisitions") => FetchType::MergersAcquisitions,
             Some("social sentiment history") => FetchType::SocialSentimentHistory,
             Some("social sentiment trending") => FetchType::SocialSentimentTrending,
             Some("social sentiment changes") => FetchType::SocialSentimentChanges,
+            Some("finnhub") => FetchType::Finnhub,
+            Some("polygon") => FetchType::Polygon,
+            Some("rss") => FetchType::Rss,
+            Some("reddit") => FetchType::Reddit,
+            Some("gdelt") => FetchType::Gdelt,
             _ => FetchType::Unknown,
         }
     
@@ -75,17 +113,29 @@ impl FetchType {
     pub fn from_str(s: &str) -> FetchType {
         match s {
             "marketaux" => FetchType::MarketAux,
+            "marketaux_entity_stats" => FetchType::MarketAuxEntityStats,
+            "marketaux_sources" => FetchType::MarketAuxSources,
             "alphavantage" => FetchType::AlphaVantage,
+            "alphavantage_top_movers" => FetchType::AlphaVantageTopMovers,
+            "alphavantage_insider_transactions" => FetchType::AlphaVantageInsiderTransactions,
+            "alphavantage_earnings_calendar" => FetchType::AlphaVantageEarningsCalendar,
             "fmp_articles" => FetchType::FMPArticle,
+            "earnings_transcript" => FetchType::EarningsTranscript,
             "general_news" => FetchType::GeneralNews,
             "stock_news" => FetchType::StockNews,
             "stock_rss" => FetchType::StockRSS,
             "crypto_news" => FetchType::CryptoNews,
             "forex_news" => FetchType::ForexNews,
             "press_releases" => FetchType::PressReleases,
+            "mergers_acquisitions" => FetchType::MergersAcquisitions,
             "social_sentiment_history" => FetchType::SocialSentimentHistory,
             "social_sentiment_trending" => FetchType::SocialSentimentTrending,
             "social_sentiment_changes" => FetchType::SocialSentimentChanges,
+            "finnhub" => FetchType::Finnhub,
+            "polygon" => FetchType::Polygon,
+            "rss" => FetchType::Rss,
+            "reddit" => FetchType::Reddit,
+            "gdelt" => FetchType::Gdelt,
             _ => FetchType::Unknown,
         }
     }
@@ -176,6 +226,40 @@ impl TryFrom<Value> for AVQueryParams {
     }    
 }
 
+/// Query parameters of the Alpha Vantage intelligence functions other than `NEWS_SENTIMENT`
+/// (`TOP_GAINERS_LOSERS`, `INSIDER_TRANSACTIONS` and `EARNINGS_CALENDAR`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AVFunctionQueryParams {
+    /// The function, set from the fetch type.
+    pub function: String,
+
+    /// Ticker of the company. Required by `INSIDER_TRANSACTIONS`, optional for `EARNINGS_CALENDAR`
+    /// (all companies by default), ignored by `TOP_GAINERS_LOSERS`.
+    pub symbol: Option<String>,
+
+    /// `EARNINGS_CALENDAR` only: `3month` (default), `6month` or `12month`.
+    pub horizon: Option<String>,
+
+    /// Your API key.
+    pub apikey: String,
+}
+impl AVFunctionQueryParams {
+    pub fn new(apikey: &str, function: &str, symbol: Option<&str>, horizon: Option<&str>) -> Self {
+        Self {
+            function: function.to_string(),
+            symbol: symbol.map(|s| s.to_string()),
+            horizon: horizon.map(|h| h.to_string()),
+            apikey: apikey.to_string(),
+        }
+    }
+}
+impl TryFrom<Value> for AVFunctionQueryParams {
+    type Error = ApiError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value).map_err(|err| ApiError::JsonParseError { message: err.to_string() })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 /// Represents the HTTP request parameters for the Marketaux API.
 ///
@@ -277,6 +361,16 @@ pub struct MAQueryParams {
     /// Use for pagination to navigate through the result set. Default is 1.
     /// Example: page=2
     page: Option<i32>,
+
+    /// Follow the pagination from `page` on and merge the pages into one response.
+    /// Not a query parameter.
+    #[serde(default, skip_serializing)]
+    fetch_all_pages: bool,
+
+    /// Pages fetched at most when `fetch_all_pages` is set. Default is `DEFAULT_MAX_PAGES`.
+    /// Not a query parameter.
+    #[serde(default, skip_serializing)]
+    max_pages: Option<u64>,
 }
 
 impl MAQueryParams {
@@ -332,6 +426,32 @@ impl MAQueryParams {
             sort_order: sort_order.map(|s| s.to_string()),
             limit,
             page,
+            fetch_all_pages: false,
+            max_pages: None,
+        }
+    }
+
+    pub fn page(&self) -> Option<i32> {
+        self.page
+    }
+
+    pub fn with_page(mut self, page: i32) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// Follows the pagination, up to `max_pages`.
+    pub fn with_all_pages(mut self, max_pages: u64) -> Self {
+        self.fetch_all_pages = true;
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    /// Pages to fetch: `max_pages` when following the pagination, 1 otherwise.
+    pub fn pages_to_fetch(&self) -> u64 {
+        match self.fetch_all_pages {
+            true => self.max_pages.unwrap_or(DEFAULT_MAX_PAGES).max(1),
+            false => 1,
         }
     }
 }
@@ -351,6 +471,124 @@ impl TryFrom<Arc<Value>> for MAQueryParams {
 }
 
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Represents the HTTP request parameters of the Marketaux entity stats endpoints
+/// (`/entity/stats/aggregation` and `/entity/stats/intraday`).
+///
+/// The entity and article filters are the ones of `MAQueryParams`.
+pub struct MAStatsQueryParams {
+    /// Your Marketaux API key.
+    api_token: String,
+
+    /// Specify entity symbol(s). Example: symbols=TSLA,AMZN,MSFT
+    symbols: Option<String>,
+
+    /// Specify the type of entities. Example: entity_types=index,equity
+    entity_types: Option<String>,
+
+    /// Specify the industries of entities. Example: industries=Technology,Industrials
+    industries: Option<String>,
+
+    /// Specify the country of the exchange of entities. Example: countries=us,ca
+    countries: Option<String>,
+
+    /// Use to search for specific terms or phrases in articles.
+    search: Option<String>,
+
+    /// Specify a comma-separated list of languages to include. Example: language=en,es
+    language: Option<String>,
+
+    /// Only count articles published before the specified date. Example: published_before=2024-12-05T08:25:06
+    published_before: Option<String>,
+
+    /// Only count articles published after the specified date. Example: published_after=2024-12-05T08:25:06
+    published_after: Option<String>,
+
+    /// Only count articles published on the specified date. Example: published_on=2024-12-05
+    published_on: Option<String>,
+
+    /// Group the stats by `symbol` (default), `exchange`, `country`, `type` or `industry`.
+    group_by: Option<String>,
+
+    /// Intraday endpoint only: `minute`, `hour`, `day` (default), `week`, `month`, `quarter` or `year`.
+    interval: Option<String>,
+
+    /// Sort by `total_documents` (default) or `sentiment_avg`.
+    sort: Option<String>,
+
+    /// Specify the sort order. Options: "desc" | "asc". Default is "desc".
+    sort_order: Option<String>,
+
+    /// Specify the number of entities to return.
+    limit: Option<i32>,
+
+    /// Use for pagination. Default is 1.
+    page: Option<i32>,
+}
+
+impl MAStatsQueryParams {
+    /// Creates the query of the stats of `symbols` grouped by `group_by`, all other filters unset.
+    pub fn new(apikey: &str, symbols: Option<&str>, group_by: Option<&str>, interval: Option<&str>) -> Self {
+        Self {
+            api_token: apikey.to_string(),
+            symbols: symbols.map(|s| s.to_string()),
+            entity_types: None,
+            industries: None,
+            countries: None,
+            search: None,
+            language: None,
+            published_before: None,
+            published_after: None,
+            published_on: None,
+            group_by: group_by.map(|s| s.to_string()),
+            interval: interval.map(|s| s.to_string()),
+            sort: None,
+            sort_order: None,
+            limit: None,
+            page: None,
+        }
+    }
+}
+impl TryFrom<Value> for MAStatsQueryParams {
+    type Error = ApiError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value).map_err(|err| ApiError::JsonParseError { message: err.to_string() })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Represents the HTTP request parameters of the Marketaux `/news/sources` endpoint.
+pub struct MASourcesQueryParams {
+    /// Your Marketaux API key.
+    api_token: String,
+
+    /// Return one source per domain.
+    distinct_domain: Option<bool>,
+
+    /// Specify a comma-separated list of languages. Example: language=en,es
+    language: Option<String>,
+
+    /// Use for pagination. Default is 1.
+    page: Option<i32>,
+}
+
+impl MASourcesQueryParams {
+    pub fn new(apikey: &str, distinct_domain: Option<bool>, language: Option<&str>, page: Option<i32>) -> Self {
+        Self {
+            api_token: apikey.to_string(),
+            distinct_domain,
+            language: language.map(|s| s.to_string()),
+            page,
+        }
+    }
+}
+impl TryFrom<Value> for MASourcesQueryParams {
+    type Error = ApiError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value).map_err(|err| ApiError::JsonParseError { message: err.to_string() })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FMPQueryParams {
     /// Symbol. E.g: AAPL.
@@ -376,6 +614,73 @@ pub struct FMPQueryParams {
 
     /// `stockwits`
     source: Option<String>,
+
+    /// Fiscal year of an earnings call. E.g: 2024.
+    year: Option<u64>,
+
+    /// Fiscal quarter of an earnings call, 1 to 4.
+    quarter: Option<u64>,
+
+    /// Follow the pagination from `page` on and merge the pages into one response.
+    #[serde(default)]
+    fetch_all_pages: bool,
+
+    /// Pages fetched at most when `fetch_all_pages` is set. Default is `DEFAULT_MAX_PAGES`.
+    #[serde(default)]
+    max_pages: Option<u64>,
+}
+impl FMPQueryParams {
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    pub fn page(&self) -> Option<u64> {
+        self.page
+    }
+
+    pub fn tickers(&self) -> Option<&str> {
+        self.tickers.as_deref()
+    }
+
+    pub fn fetch_all_pages(&self) -> bool {
+        self.fetch_all_pages
+    }
+
+    pub fn max_pages(&self) -> Option<u64> {
+        self.max_pages
+    }
+
+    /// Query of the `stable` endpoints, which take `symbols` and `limit` rather than `tickers` and
+    /// `size`, and no sentiment filters.
+    pub fn stable_params(&self) -> Option<Vec<(String, String)>> {
+        let params: Vec<(String, String)> = [
+            ("symbol", self.symbol.clone()),
+            ("symbols", self.tickers.clone()),
+            ("from", self.from.clone()),
+            ("to", self.to.clone()),
+            ("page", self.page.map(|page| page.to_string())),
+            ("limit", self.size.map(|size| size.to_string())),
+            ("year", self.year.map(|year| year.to_string())),
+            ("quarter", self.quarter.map(|quarter| quarter.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value?)))
+        .collect();
+        (!params.is_empty()).then_some(params)
+    }
+
+    pub fn with_page(mut self, page: u64) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// Pages to fetch: `max_pages` when following the pagination, 1 otherwise.
+    pub fn pages_to_fetch(&self) -> u64 {
+        match self.fetch_all_pages {
+            true => self.max_pages.unwrap_or(DEFAULT_MAX_PAGES).max(1),
+            false => 1,
+        }
+    }
 }
 impl Into<Option<Vec<(String, String)>>> for FMPQueryParams {
     fn into(self) -> Option<Vec<(String, String)>> {
@@ -404,6 +709,12 @@ impl Into<Option<Vec<(String, String)>>> for FMPQueryParams {
         if let Some(source) = &self.source {
             query_params.push(("source".to_string(), source.to_string()));
         }
+        if let Some(year) = &self.year {
+            query_params.push(("year".to_string(), year.to_string()));
+        }
+        if let Some(quarter) = &self.quarter {
+            query_params.push(("quarter".to_string(), quarter.to_string()));
+        }
         match query_params.len() {
             0 => None,
             _ => Some(query_params),
@@ -423,6 +734,10 @@ impl From<Value> for FMPQueryParams {
             size: value.get("size").and_then(|v| v.as_u64()),
             type_name: value.get("type_name").and_then(|v| v.as_str().map(|s| s.to_string())),
             source: value.get("source").and_then(|v| v.as_str().map(|s| s.to_string())),
+            year: value.get("year").and_then(|v| v.as_u64()),
+            quarter: value.get("quarter").and_then(|v| v.as_u64()),
+            fetch_all_pages: value.get("fetch_all_pages").and_then(|v| v.as_bool()).unwrap_or(false),
+            max_pages: value.get("max_pages").and_then(|v| v.as_u64()),
         }
     }
 }
@@ -439,3 +754,202 @@ impl Display for  FMPQueryParams {
         write!(f, "{:?}", self)
     }   
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Represents the HTTP request parameters for the Finnhub news endpoints.
+pub struct FHQueryParams {
+    /// Your Finnhub API key.
+    pub token: String,
+
+    /// Company symbol, required by `company-news`. E.g: AAPL.
+    pub symbol: Option<String>,
+
+    /// Date in YYYY-MM-DD format, required by `company-news`.
+    pub from: Option<String>,
+
+    /// Date in YYYY-MM-DD format, required by `company-news`.
+    pub to: Option<String>,
+
+    /// News category of `news`: `general`, `forex`, `crypto` or `merger`.
+    pub category: Option<String>,
+
+    /// Only return `news` articles with an id greater than this one.
+    #[serde(rename = "minId")]
+    pub min_id: Option<u64>,
+}
+impl FHQueryParams {
+    pub fn new(
+        token: &str,
+        symbol: Option<&str>,
+        from: Option<&str>,
+        to: Option<&str>,
+        category: Option<&str>,
+        min_id: Option<u64>,
+    ) -> Self {
+        Self {
+            token: token.to_string(),
+            symbol: symbol.map(|s| s.to_string()),
+            from: from.map(|s| s.to_string()),
+            to: to.map(|s| s.to_string()),
+            category: category.map(|s| s.to_string()),
+            min_id,
+        }
+    }
+}
+impl TryFrom<Value> for FHQueryParams {
+    type Error = ApiError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value).map_err(|err| ApiError::JsonParseError { message: err.to_string() })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Represents the HTTP request parameters for the Polygon.io `v2/reference/news` endpoint.
+pub struct PGQueryParams {
+    /// Your Polygon.io API key.
+    #[serde(rename = "apiKey")]
+    pub api_key: String,
+
+    /// Return results that contain this ticker. E.g: AAPL.
+    pub ticker: Option<String>,
+
+    /// Return results published on, before, or after this date (`YYYY-MM-DD` or RFC 3339).
+    #[serde(rename = "published_utc.gte")]
+    pub published_utc_gte: Option<String>,
+
+    #[serde(rename = "published_utc.lte")]
+    pub published_utc_lte: Option<String>,
+
+    /// `asc` or `desc`.
+    pub order: Option<String>,
+
+    /// Number of results per page. Default is 10, maximum is 1000.
+    pub limit: Option<u32>,
+
+    /// Field used for ordering. Default is `published_utc`.
+    pub sort: Option<String>,
+
+    /// Cursor of the page to fetch, taken from a previous response `next_url`.
+    pub cursor: Option<String>,
+}
+impl PGQueryParams {
+    /// News published between `published_utc_gte` and `published_utc_lte`, newest first, in pages of
+    /// the maximum size.
+    pub fn new(api_key: &str, published_utc_gte: Option<&str>, published_utc_lte: Option<&str>) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            ticker: None,
+            published_utc_gte: published_utc_gte.map(|s| s.to_string()),
+            published_utc_lte: published_utc_lte.map(|s| s.to_string()),
+            order: Some("desc".to_string()),
+            limit: Some(1000),
+            sort: Some("published_utc".to_string()),
+            cursor: None,
+        }
+    }
+}
+impl TryFrom<Value> for PGQueryParams {
+    type Error = ApiError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value).map_err(|err| ApiError::JsonParseError { message: err.to_string() })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Represents the HTTP request parameters for the GDELT 2.0 DOC API.
+pub struct GDQueryParams {
+    /// Search query, e.g. `(theme:ECON_INFLATION OR theme:WB_2444_INFLATION) sourcecountry:US`.
+    pub query: String,
+
+    /// Output mode. Only `ArtList` (list of articles) is supported.
+    pub mode: String,
+
+    /// Output format. Only `json` is supported.
+    pub format: String,
+
+    /// Number of articles to return, at most 250.
+    pub maxrecords: Option<u32>,
+
+    /// Relative window ending now, e.g. `1h`, `1d`, `2w`. Ignored when a date range is given.
+    pub timespan: Option<String>,
+
+    /// Start of the date range, as `YYYYMMDDHHMMSS` (UTC).
+    pub startdatetime: Option<String>,
+
+    /// End of the date range, as `YYYYMMDDHHMMSS` (UTC).
+    pub enddatetime: Option<String>,
+
+    /// `DateDesc`, `DateAsc`, `ToneDesc`, `ToneAsc` or `HybridRel` (default).
+    pub sort: Option<String>,
+}
+impl GDQueryParams {
+    pub fn new(
+        query: &str,
+        maxrecords: Option<u32>,
+        timespan: Option<&str>,
+        startdatetime: Option<&str>,
+        enddatetime: Option<&str>,
+        sort: Option<&str>,
+    ) -> Self {
+        Self {
+            query: query.to_string(),
+            mode: "ArtList".to_string(),
+            format: "json".to_string(),
+            maxrecords,
+            timespan: timespan.map(|s| s.to_string()),
+            startdatetime: startdatetime.map(|s| s.to_string()),
+            enddatetime: enddatetime.map(|s| s.to_string()),
+            sort: sort.map(|s| s.to_string()),
+        }
+    }
+
+    /// Builds a query from free `keywords`, GKG `themes` and FIPS `countries`.
+    /// Alternatives are OR-ed inside each group and the groups AND-ed, as GDELT expects.
+    pub fn build_query(keywords: Option<&str>, themes: &[String], countries: &[String]) -> String {
+        let group = |prefix: &str, values: &[String]| match values.len() {
+            0 => None,
+            1 => Some(format!("{}:{}", prefix, values[0])),
+            _ => Some(format!("({})", values.iter().map(|v| format!("{}:{}", prefix, v)).collect::<Vec<_>>().join(" OR "))),
+        };
+        keywords
+            .map(str::to_string)
+            .filter(|k| !k.trim().is_empty())
+            .into_iter()
+            .chain(group("theme", themes))
+            .chain(group("sourcecountry", countries))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+impl TryFrom<Value> for GDQueryParams {
+    type Error = ApiError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        #[derive(Deserialize)]
+        struct Args {
+            query: Option<String>,
+            keywords: Option<String>,
+            #[serde(default)]
+            themes: Vec<String>,
+            #[serde(default)]
+            countries: Vec<String>,
+            maxrecords: Option<u32>,
+            timespan: Option<String>,
+            startdatetime: Option<String>,
+            enddatetime: Option<String>,
+            sort: Option<String>,
+        }
+        let args: Args = serde_json::from_value(value).map_err(|err| ApiError::JsonParseError { message: err.to_string() })?;
+        let query = args.query.unwrap_or_else(|| Self::build_query(args.keywords.as_deref(), &args.themes, &args.countries));
+        if query.is_empty() {
+            return Err(ApiError::JsonParseError { message: "A `query`, `keywords`, `themes` or `countries` is required".to_string() });
+        }
+        Ok(Self::new(
+            &query,
+            args.maxrecords,
+            args.timespan.as_deref(),
+            args.startdatetime.as_deref(),
+            args.enddatetime.as_deref(),
+            args.sort.as_deref(),
+        ))
+    }
+}
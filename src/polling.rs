@@ -0,0 +1,367 @@
+//! Polling daemon.
+//!
+//! Every polled provider, and the watchlist, runs as its own task on its schedule (see
+//! `scheduler.rs`): the latest news are fetched, stored as a raw `NewsResult` and as articles, and
+//! the watermarks advanced. RSS and Reddit polling, downtime recovery, coverage checks and the
+//! sentiment snapshots (see `snapshots.rs`) and the refresh of the filter values (see
+//! `enumerations.rs`) run in the background when enabled.
+//!
+//! The websocket server can run the same loop in its process (`server.poll`): every polling
+//! result is then published to its `NewsHub` as well, and forwarded to the connected clients.
+//!
+//! `fetch_once` runs a single cycle of the same sources instead, for the `fetch-once` command.
+
+use std::sync::Arc;
+
+use cached::proc_macro::cached;
+use cached::TimedCache;
+use chrono::SecondsFormat;
+use futures::stream::{self, StreamExt};
+use reqwest::Client;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{error, info, instrument, trace, warn, Instrument};
+
+use crate::alphavantage::{self, AlphaVantageApiResponse};
+use crate::cache::SharedLockedCache;
+use crate::config::{self, ValueConfig};
+use crate::logging::{self, setup_logger};
+use crate::marketaux::{self, MarketAuxResponse};
+use crate::fmp;
+use crate::provider::{self, default_providers, NewsProvider};
+use crate::request::HTTPClient;
+use crate::utils::{generate_random_key, now, time_rfc3339_opts};
+use crate::{alerts, api_calls, autoscale, captures, chaos, clock, costs, coverage, db, digest, enumerations, export, freshness, instance, latency, limiter, quota, recovery, reddit, rss, scheduler, sentiment, snapshots, systemd, taxonomy, watchlist, watermark};
+use crate::{FetchNewsError, NewsResult, ProviderFailure};
+
+/// Publishes the polling results to the websocket connections, see `PollState`.
+pub type NewsHub = broadcast::Sender<Arc<NewsResult>>;
+
+/// Fetches news data from the polled providers, with caching.
+#[cached(
+    type = "TimedCache<String, Result<NewsResult, FetchNewsError>>",
+    create = "{ TimedCache::with_lifespan(60) }", // Shorter than the schedules, see `scheduler.rs`
+    convert = r#"{ format!("{:?} {:?}", providers.iter().map(|p| p.name()).collect::<Vec<_>>(), config) }"#
+)]
+async fn fetch_news_data(providers: Arc<Vec<Arc<dyn NewsProvider>>>, config: Arc<ValueConfig>) -> Result<NewsResult, FetchNewsError> {
+    fetch_providers(providers, config).await
+}
+
+/// Polling cycle of `fetch_news_data`, in its span: only uncached cycles are traced.
+#[instrument(name = "fetch_news_data", skip_all, fields(providers = providers.len()))]
+async fn fetch_providers(providers: Arc<Vec<Arc<dyn NewsProvider>>>, config: Arc<ValueConfig>) -> Result<NewsResult, FetchNewsError> {
+    // Fetch every provider concurrently, so a cycle takes as long as the slowest provider.
+    // Futures built ahead of the stream: the scheduler spawns this, and a stream mapping a
+    // `dyn NewsProvider` with a closure is not proven `Send`.
+    let fetches: Vec<_> = providers
+        .iter()
+        .cloned()
+        .map(|provider| async move {
+            let result = limiter::run(provider.name(), autoscale::track(provider.name(), provider.fetch_latest())).await;
+            (provider.name().to_string(), result)
+        })
+        .collect();
+    let results: Vec<_> = stream::iter(fetches)
+        .buffer_unordered(config.request.max_concurrent_providers.max(1))
+        .collect()
+        .await;
+
+    let mut marketaux_data = None;
+    let mut alphavantage_data = None;
+    let mut errors = Vec::new();
+    for (name, result) in results {
+        let parsed = match result {
+            Ok(data) => match name.as_str() {
+                marketaux::PROVIDER_NAME => serde_json::from_value::<MarketAuxResponse>(data)
+                    .inspect(|data| info!("Successfully fetched from marketaux. | Meta :{:?}", data.meta))
+                    .map(|data| marketaux_data = Some(data))
+                    .map_err(|e| e.to_string()),
+                alphavantage::PROVIDER_NAME => serde_json::from_value::<AlphaVantageApiResponse>(data)
+                    .inspect(|data| info!("Successfully fetched data from Alphavantage. | Meta: {:?}", data.items))
+                    .map(|data| alphavantage_data = Some(data))
+                    .map_err(|e| e.to_string()),
+                other => {
+                    warn!("`NewsResult` has no section for provider `{}`. Data ignored.", other);
+                    Ok(())
+                }
+            },
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(message) = parsed {
+            error!("Provider `{}` failed: {}", name, message);
+            errors.push(ProviderFailure { provider: name.to_string(), message, at: now() });
+        }
+    }
+
+    // Only a cycle where every provider failed is an error.
+    if marketaux_data.is_none() && alphavantage_data.is_none() {
+        let messages: Vec<String> = errors.iter().map(|e| format!("{} error: {}", e.provider, e.message)).collect();
+        return Err(FetchNewsError { message: messages.join(" | ") });
+    }
+
+    Ok(NewsResult {
+        hash_key: generate_random_key(8),
+        marketaux_data_len: marketaux_data.as_ref().map(|m| m.data.len() as u64).unwrap_or(0),
+        alphavantage_data_len: alphavantage_data.as_ref().map(|a| a.feed.len() as u64).unwrap_or(0),
+        marketaux: marketaux_data,
+        alphavantage: alphavantage_data,
+        from: time_rfc3339_opts(config.request.delay_secs),
+        to: now(),
+        time_range: config.request.delay_secs as u64,
+        errors,
+    })
+}
+
+/// Reads the config, initializes the database client, then fetches news data on the schedule of
+/// each source and inserts it into the database.
+pub async fn run() -> Result<(), FetchNewsError> {
+    // Initialize tracing
+    setup_logger("trace");
+
+    info!("Reading config file & Preparing components...");
+    let value_config = Arc::new(config::ValueConfig::new().expect("Failed to read config file"));
+    let _lock = instance::lock_from_config(&value_config)
+        .map_err(|e| FetchNewsError { message: e.to_string() })?;
+    poll(value_config, None).await
+}
+
+/// Fetches news data on the schedule of each source and inserts it into the database, publishing
+/// every polling result to `hub` when given.
+pub async fn poll(value_config: Arc<ValueConfig>, hub: Option<NewsHub>) -> Result<(), FetchNewsError> {
+    let Components { req_client, all_providers, providers, db_ops } = Components::prepare(value_config.clone()).await?;
+    let alerter = Arc::new(alerts::Alerter::new(req_client.clone(), value_config.alerts.clone()).with_storage(db_ops.clone()));
+
+    info!("Checking providers...");
+    let mut available = 0;
+    for provider in providers.iter() {
+        match provider.health() {
+            provider::ProviderHealth::Unavailable(reason) => warn!("Provider `{}` is unavailable: {}", provider.name(), reason),
+            _ => available += 1,
+        }
+    }
+    if available == 0 {
+        error!("No provider is available.");
+        return Err(FetchNewsError { message: "No provider is available".to_string() });
+    }
+    systemd::notify_ready(&format!("Polling {} providers", available));
+    api_calls::spawn(db_ops.clone(), &value_config.api_calls);
+    quota::spawn(db_ops.clone(), &value_config.quota);
+    latency::spawn(alerter.clone(), &value_config.latency);
+    let watchdog = systemd::Watchdog::from_env();
+
+    if value_config.rss.enabled {
+        Arc::new(rss::RssClient::new(req_client.clone(), value_config.clone())).spawn_polling(db_ops.clone());
+    }
+    if value_config.reddit.enabled {
+        Arc::new(reddit::RedditClient::new(req_client.clone(), value_config.clone())).spawn_polling(db_ops.clone());
+    }
+    if value_config.recovery.enabled {
+        recovery::Recovery::new(&all_providers, db_ops.clone(), &value_config.recovery).spawn().await;
+    }
+    if !value_config.alerts.rules.is_empty() {
+        digest::Digest::new(db_ops.clone(), alerter.clone(), &value_config.alerts).spawn();
+    }
+    export::spawn(&value_config.exports, db_ops.clone(), alerter.clone());
+    if value_config.coverage.enabled {
+        coverage::GapDetector::new(providers.clone(), db_ops.clone(), alerter, value_config.clone()).spawn();
+    }
+
+    info!("Fetching data....");
+    let mut tasks = Vec::new();
+    for provider in providers.iter() {
+        let schedule = scheduler::Schedule::of(provider.name(), &value_config)
+            .map_err(|message| FetchNewsError { message })?;
+        let (source, config, db_ops, hub) = (Arc::new(vec![provider.clone()]), value_config.clone(), db_ops.clone(), hub.clone());
+        tasks.push(scheduler::spawn(provider.name(), schedule, move || poll_sources(source.clone(), config.clone(), db_ops.clone(), hub.clone())));
+    }
+    if value_config.fmp.sentiment_snapshots {
+        match all_providers.iter().find(|p| p.name() == fmp::PROVIDER_NAME) {
+            Some(provider) => {
+                let schedule = scheduler::Schedule::of(snapshots::SOURCE, &value_config)
+                    .map_err(|message| FetchNewsError { message })?;
+                let (provider, db_ops) = (provider.clone(), db_ops.clone());
+                tasks.push(scheduler::spawn(snapshots::SOURCE, schedule, move || poll_snapshots(provider.clone(), db_ops.clone())));
+            }
+            None => warn!("`fmp.sentiment_snapshots` is enabled without the FMP provider."),
+        }
+    }
+    if value_config.enumerations.enabled {
+        tasks.push(enumerations::spawn(all_providers.clone(), &value_config.enumerations));
+    }
+    if !value_config.watchlist.symbols.is_empty() {
+        let schedule = scheduler::Schedule::of(WATCHLIST_SOURCE, &value_config)
+            .map_err(|message| FetchNewsError { message })?;
+        let (providers, config, db_ops) = (Arc::new(all_providers), value_config.clone(), db_ops.clone());
+        tasks.push(scheduler::spawn(WATCHLIST_SOURCE, schedule, move || poll_watchlist(providers.clone(), config.clone(), db_ops.clone())));
+    }
+    watchdog.spawn_keepalive();
+    futures::future::join_all(tasks).await;
+    Ok(())
+}
+
+/// Runs one cycle of `sources` (polled provider names, or `watchlist`; all of them when empty)
+/// and stores what was fetched. Returns the number of new articles.
+pub async fn fetch_once(sources: &[String]) -> Result<usize, FetchNewsError> {
+    let value_config = Arc::new(config::ValueConfig::new().map_err(|e| FetchNewsError { message: e.to_string() })?);
+    let Components { all_providers, providers, db_ops, .. } = Components::prepare(value_config.clone()).await?;
+
+    let known: Vec<&str> = providers.iter().map(|p| p.name()).chain([WATCHLIST_SOURCE]).collect();
+    if let Some(unknown) = sources.iter().find(|s| !known.contains(&s.as_str())) {
+        return Err(FetchNewsError { message: format!("Unknown source `{}`, expected one of: {}", unknown, known.join(", ")) });
+    }
+    let selected = |name: &str| sources.is_empty() || sources.iter().any(|s| s == name);
+
+    async {
+        let mut stored = 0;
+        let polled: Vec<Arc<dyn NewsProvider>> = providers.iter().filter(|p| selected(p.name())).cloned().collect();
+        if !polled.is_empty() {
+            stored += store_sources(Arc::new(polled), value_config.clone(), db_ops.clone(), None).await?;
+        }
+        if selected(WATCHLIST_SOURCE) && !value_config.watchlist.symbols.is_empty() {
+            stored += store_watchlist(Arc::new(all_providers), value_config, db_ops).await?;
+        }
+        Ok(stored)
+    }
+    .instrument(logging::cycle_span("fetch-once"))
+    .await
+}
+
+/// Clients shared by the sources.
+struct Components {
+    req_client: Arc<Client>,
+    /// Every provider, for the watchlist and the background pollers.
+    all_providers: Vec<Arc<dyn NewsProvider>>,
+    /// Sources aggregated into `NewsResult`.
+    providers: Arc<Vec<Arc<dyn NewsProvider>>>,
+    db_ops: Arc<db::DatabaseOps>,
+}
+impl Components {
+    /// Configures the global settings, builds the providers and connects to the database.
+    async fn prepare(value_config: Arc<ValueConfig>) -> Result<Self, FetchNewsError> {
+        clock::configure(&value_config.clock);
+        chaos::configure(&value_config.chaos);
+        autoscale::configure(&value_config.autoscale);
+        costs::configure(&value_config.costs);
+        freshness::configure(&value_config.freshness);
+        latency::configure(&value_config.latency);
+        watermark::configure(&value_config.watermark);
+        sentiment::configure(&value_config.sentiment);
+        taxonomy::configure(&value_config.taxonomy);
+        api_calls::configure(&value_config.api_calls);
+        limiter::configure(&value_config.task);
+        quota::configure(&value_config.quota);
+        captures::configure(&value_config.captures);
+        let req_client = Arc::new(Client::new());
+        let http_client = Arc::new(HTTPClient::new().expect("Failed to initialize HTTP client."));
+        let cache = Arc::new(Mutex::new(SharedLockedCache::new(100)));
+
+        let all_providers = default_providers(http_client, req_client.clone(), cache, value_config.clone());
+        let providers: Arc<Vec<Arc<dyn NewsProvider>>> = Arc::new(
+            all_providers
+                .iter()
+                .filter(|p| matches!(p.name(), marketaux::PROVIDER_NAME | alphavantage::PROVIDER_NAME))
+                .cloned()
+                .collect()
+        );
+
+        info!("Creating databse client...");
+        let db_client = db::ClientManager::new(&value_config).await
+            .map_err(|e| FetchNewsError { message: e.to_string() })?;
+
+        info!("Getting ready...");
+        let db_ops = Arc::new(db::DatabaseOps::from_config(db_client.get_client(), &value_config.database)
+            .with_transactions(db_client.supports_transactions())
+            .with_keywords(&value_config.keywords)
+            .with_entities(&value_config.entities)
+            .with_translator(req_client.clone(), &value_config.translation));
+        match db_ops.watermarks().await {
+            Ok(marks) => watermark::restore(&marks),
+            Err(e) => warn!("Failed to load the watermarks, polling from `request.delay_secs` ago: {}", e),
+        }
+        Ok(Self { req_client, all_providers, providers, db_ops })
+    }
+}
+
+/// Name of the watchlist fan-out in `[schedule]`.
+const WATCHLIST_SOURCE: &str = "watchlist";
+
+/// Fetches the latest news of `providers` and stores them: the raw `NewsResult` and its articles.
+async fn poll_sources(providers: Arc<Vec<Arc<dyn NewsProvider>>>, value_config: Arc<ValueConfig>, db_ops: Arc<db::DatabaseOps>, hub: Option<NewsHub>) {
+    if let Err(e) = store_sources(providers, value_config, db_ops, hub.as_ref()).await {
+        error!("{}", e);
+    }
+}
+
+/// See `poll_sources`. Returns the number of new articles.
+async fn store_sources(providers: Arc<Vec<Arc<dyn NewsProvider>>>, value_config: Arc<ValueConfig>, db_ops: Arc<db::DatabaseOps>, hub: Option<&NewsHub>) -> Result<usize, FetchNewsError> {
+    let polled = providers.clone();
+    let data = fetch_news_data(providers, value_config).await
+        .map_err(|e| FetchNewsError { message: format!("Error fetching news data: {}", e) })?;
+    trace!(
+    "GET request yielded: {} results | Hash key: {} \n",
+    data.marketaux_data_len + data.alphavantage_data_len,
+    data.hash_key );
+    if data.is_partial() {
+        warn!("Partial result: {} provider(s) failed. Storing what succeeded.", data.errors.len());
+    }
+
+    info!("Inserting into database...");
+    let doc = db_ops.convert_to_document(data.to_json())
+        .map_err(|e| error!("Error converting NewsResult to bson::Document: {}", e))
+        .unwrap();
+
+    // A failed write must not stop the loop: the articles are stored below regardless.
+    if let Err(e) = db_ops.insert_one(doc).await {
+        error!("Error inserting document: {}", e);
+    }
+
+    let articles = data.articles();
+    latency::record(latency::Stage::Ingested, &articles);
+    let stored = db_ops.store_articles(&articles).await
+        .map_err(|e| FetchNewsError { message: format!("Error storing articles: {}", e) })?;
+    info!("Stored {} new articles.", stored);
+    // The successful polls, for the gap detection of `coverage.rs`.
+    let polled_at = clock::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    for provider in polled.iter().filter(|p| !data.errors.iter().any(|e| e.provider == p.name())) {
+        if let Err(e) = db_ops.record_poll(provider.name(), &polled_at).await {
+            error!("Error recording the poll of {}: {}", provider.name(), e);
+        }
+    }
+    // Only once stored: a failed write is fetched again next cycle.
+    for (provider, published_at) in watermark::advance(&articles) {
+        if let Err(e) = db_ops.save_watermark(&provider, &published_at).await {
+            error!("Error saving the watermark of {}: {}", provider, e);
+        }
+    }
+    // Sending only fails without connected clients.
+    if let Some(hub) = hub {
+        let _ = hub.send(Arc::new(data));
+    }
+
+    info!("Done.");
+    Ok(stored)
+}
+
+/// Fetches the social sentiment rankings from `provider` (FMP) and upserts the snapshot of each symbol.
+async fn poll_snapshots(provider: Arc<dyn NewsProvider>, db_ops: Arc<db::DatabaseOps>) {
+    match snapshots::refresh(provider.as_ref(), db_ops.as_ref()).await {
+        Ok(stored) => info!("Stored {} sentiment snapshots.", stored),
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// Fetches the watchlist symbols from `providers` and stores the articles found.
+async fn poll_watchlist(providers: Arc<Vec<Arc<dyn NewsProvider>>>, value_config: Arc<ValueConfig>, db_ops: Arc<db::DatabaseOps>) {
+    if let Err(e) = store_watchlist(providers, value_config, db_ops).await {
+        error!("{}", e);
+    }
+}
+
+/// See `poll_watchlist`. Returns the number of new articles.
+async fn store_watchlist(providers: Arc<Vec<Arc<dyn NewsProvider>>>, value_config: Arc<ValueConfig>, db_ops: Arc<db::DatabaseOps>) -> Result<usize, FetchNewsError> {
+    info!("Fetching watchlist...");
+    let articles = watchlist::fetch(&providers, &value_config.watchlist).await;
+    let stored = db_ops.store_articles(&articles).await
+        .map_err(|e| FetchNewsError { message: format!("Error storing watchlist articles: {}", e) })?;
+    info!("Stored {} new watchlist articles.", stored);
+    Ok(stored)
+}
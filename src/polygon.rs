@@ -0,0 +1,239 @@
+//! ## A Rust wrapper of the [Polygon.io](https://polygon.io) ticker news API (`v2/reference/news`).
+//!
+//! Results are paginated with a cursor: each page carries a `next_url` holding the cursor of the
+//! next one. `poll` follows up to `max_pages` pages (1 by default) and returns the merged results
+//! along with the last `next_url`, so clients can resume from there by passing its `cursor`.
+//!
+//! ## Reference:
+//! [Official Polygon.io Documentation](https://polygon.io/docs/stocks/get_v2_reference_news).
+
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{to_value, Value};
+use tokio::sync::Mutex;
+use tracing::{error, info, instrument, warn};
+
+use crate::api_calls;
+use crate::cache::SharedLockedCache;
+use crate::chaos;
+use crate::config::ValueConfig;
+use crate::encoding::decode_json_response;
+use crate::errors::{ApiError, ProviderError};
+use crate::fixtures;
+use crate::normalize::{NormalizedArticle, POLYGON_PROVIDER};
+use crate::options::FetchType;
+use crate::options::PGQueryParams as QueryParams;
+use crate::provider::{HealthTracker, NewsProvider, ProviderFuture, ProviderHealth};
+use crate::utils::{get_resp_value_from_cache_or_fetch, retry, time_rfc3339_opts};
+
+const BASE_URL: &str = "https://api.polygon.io/v2/reference/news";
+pub const PROVIDER_NAME: &str = POLYGON_PROVIDER;
+const ENDPOINT: &str = "v2_reference_news";
+const API_KEY_MAP_KEY: &str = "apiKey";
+const MAX_PAGES_MAP_KEY: &str = "max_pages";
+const CURSOR_QUERY_KEY: &str = "cursor";
+/// Pages followed when fetching a past window.
+const WINDOW_MAX_PAGES: usize = 10;
+
+/// A page of the `v2/reference/news` endpoint.
+///
+/// [See example here](https://polygon.io/docs/stocks/get_v2_reference_news).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PolygonNewsResponse {
+    pub status: Option<String>,
+    pub request_id: Option<String>,
+    pub count: Option<u64>,
+    /// URL of the next page, absent on the last one.
+    pub next_url: Option<String>,
+    #[serde(default)]
+    pub results: Vec<PolygonArticle>,
+}
+impl PolygonNewsResponse {
+    /// Cursor of the next page, extracted from `next_url`.
+    pub fn next_cursor(&self) -> Option<String> {
+        let next_url = reqwest::Url::parse(self.next_url.as_deref()?).ok()?;
+        next_url
+            .query_pairs()
+            .find(|(key, _)| key == CURSOR_QUERY_KEY)
+            .map(|(_, value)| value.into_owned())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PolygonArticle {
+    pub id: Option<String>,
+    pub publisher: Option<Publisher>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Publication time in RFC 3339 format.
+    pub published_utc: Option<String>,
+    pub article_url: Option<String>,
+    #[serde(default)]
+    pub tickers: Vec<String>,
+    pub amp_url: Option<String>,
+    pub image_url: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub insights: Vec<Insight>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Publisher {
+    pub name: Option<String>,
+    pub homepage_url: Option<String>,
+    pub logo_url: Option<String>,
+    pub favicon_url: Option<String>,
+}
+
+/// Per-ticker sentiment of an article.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Insight {
+    pub ticker: Option<String>,
+    /// `positive`, `neutral` or `negative`.
+    pub sentiment: Option<String>,
+    pub sentiment_reasoning: Option<String>,
+}
+
+pub struct PolygonApiClient {
+    client: Arc<Client>,
+    cache: Arc<Mutex<SharedLockedCache>>,
+    config: Arc<ValueConfig>,
+    health: HealthTracker,
+}
+impl PolygonApiClient {
+    pub fn new(client: Arc<Client>, cache: Arc<Mutex<SharedLockedCache>>, config: Arc<ValueConfig>) -> Self {
+        Self { client, cache, config, health: HealthTracker::default() }
+    }
+
+    async fn get(&self, query_params: QueryParams) -> Result<Value, ApiError> {
+        let key = format!("polygon_{:?}", &query_params);
+        get_resp_value_from_cache_or_fetch(
+            &self.cache,
+            &key,
+            || async { self.get_(query_params).await },
+            self.config.task.cache_ttl,
+        ).await
+        .inspect_err(|_| warn!("Polygon client encountered an error during GET request."))
+    }
+
+    async fn get_(&self, query_params: QueryParams) -> Result<Value, ApiError> {
+        let response = api_calls::send(PROVIDER_NAME, self.client.get(BASE_URL).query(&query_params)).await?;
+        if response.status() != StatusCode::OK {
+            return Err(api_calls::status_error(response).await);
+        }
+
+        let body = decode_json_response(PROVIDER_NAME, response).await
+            .inspect_err(|e| error!("Failed to read body: {}", e))?;
+        fixtures::record(PROVIDER_NAME, ENDPOINT, &body);
+        let body = chaos::response_body(PROVIDER_NAME, body);
+        let page: PolygonNewsResponse = serde_json::from_value(body).map_err(|e| {
+            error!("Failed to parse body: {:?}", e);
+            ApiError::JsonParseError { message: e.to_string() }
+        })?;
+
+        to_value(page).map_err(|e| ApiError::JsonParseError { message: e.to_string() })
+    }
+
+    /// Fetches up to `max_pages` pages starting at `query_params`, following the cursors.
+    pub async fn fetch_pages(&self, mut query_params: QueryParams, max_pages: usize) -> Result<PolygonNewsResponse, ApiError> {
+        let mut merged: Option<PolygonNewsResponse> = None;
+        for page_number in 1..=max_pages.max(1) {
+            let value = retry(PROVIDER_NAME, &self.config, || async {
+                self.get(query_params.clone()).await
+            }).await?;
+            let page: PolygonNewsResponse = serde_json::from_value(value)
+                .map_err(|e| ApiError::JsonParseError { message: e.to_string() })?;
+            let cursor = page.next_cursor();
+
+            merged = Some(match merged {
+                None => page,
+                Some(mut merged) => {
+                    merged.results.extend(page.results);
+                    merged.count = Some(merged.results.len() as u64);
+                    merged.next_url = page.next_url;
+                    merged.request_id = page.request_id;
+                    merged
+                }
+            });
+
+            match cursor {
+                Some(cursor) if page_number < max_pages => query_params.cursor = Some(cursor),
+                _ => break,
+            }
+        }
+        Ok(merged.expect("at least one page is fetched"))
+    }
+
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME, function = args["function"].as_str()))]
+    pub async fn poll(&self, args: Arc<Value>) -> Result<Value, ApiError> {
+        let mut args = Arc::try_unwrap(args).unwrap_or_else(|v| (*v).clone());
+        let mut max_pages = 1;
+        if let Value::Object(ref mut map) = args {
+            max_pages = map.remove(MAX_PAGES_MAP_KEY).and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+            map.insert(API_KEY_MAP_KEY.to_string(), Value::String(self.config.api.polygon.clone()));
+        }
+        let response = self.fetch_pages(QueryParams::try_from(args)?, max_pages).await?;
+        info!("Polygon returned {} articles. | More: {}", response.results.len(), response.next_url.is_some());
+        to_value(response).map_err(|e| ApiError::JsonParseError { message: e.to_string() })
+    }
+
+    /// Fetches the news published since the last polling cycle.
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME))]
+    pub async fn latest(&self) -> Result<Value, ApiError> {
+        let since = time_rfc3339_opts(self.config.request.delay_secs);
+        let query = QueryParams::new(&self.config.api.polygon, Some(&format!("{}Z", since)), None);
+        let response = self.fetch_pages(query, 1).await
+            .inspect_err(|e| error!("Error during GET request: {}", e))?;
+        to_value(response).map_err(|e| ApiError::JsonParseError { message: e.to_string() })
+    }
+
+    /// Fetches the news published between `from` and `to`, following up to `WINDOW_MAX_PAGES` pages.
+    pub async fn window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<NormalizedArticle>, ApiError> {
+        let query = QueryParams::new(
+            &self.config.api.polygon,
+            Some(&from.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            Some(&to.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        );
+        let response = self.fetch_pages(query, WINDOW_MAX_PAGES).await?;
+        Ok(response.results.iter().map(NormalizedArticle::from).collect())
+    }
+}
+
+impl NewsProvider for PolygonApiClient {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn supports(&self, fetch_type: &FetchType) -> bool {
+        matches!(fetch_type, FetchType::Polygon)
+    }
+
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.poll(args).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.latest().await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn health(&self) -> ProviderHealth {
+        self.health.health(&self.config.api.polygon)
+    }
+
+    fn fetch_window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async move { Ok(self.window(from, to).await?) })
+    }
+}
@@ -0,0 +1,199 @@
+//! Portfolio-aware aggregation.
+//!
+//! A portfolio owner cares more about news on a 40% position than on a 2% one. Given positions
+//! (tickers and weights), the `portfolio_news` function of the websocket server returns:
+//!
+//! - per position, its exposure (weight over the sum of the absolute weights), its article count
+//!   and its mean sentiment score over the window;
+//! - the sentiment of the portfolio: the mean sentiment of the positions weighted by their
+//!   exposure. A negative weight is a short position, whose sentiment counts reversed: good news
+//!   on a shorted stock are bad news for the portfolio;
+//! - the news ranked by the exposure they touch (the exposures of the positions an article is
+//!   tagged with), most recent first among equals.
+//!
+//! Positions are given as the `portfolio` param, an object of ticker to weight, e.g.
+//! `{"NVDA": 0.4, "AAPL": 0.35, "TSLA": -0.25}`. Weights need not add up to 1.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::PortfolioConfig;
+use crate::db::OpError;
+use crate::normalize::NormalizedArticle;
+use crate::storage::Storage;
+
+/// Tickers and their weights, uppercased.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Portfolio {
+    pub weights: BTreeMap<String, f64>,
+}
+impl Portfolio {
+    /// Parses an object of ticker to weight, see the module docs.
+    pub fn from_value(value: &Value, config: &PortfolioConfig) -> Result<Self, String> {
+        let Some(positions) = value.as_object() else {
+            return Err("`portfolio` must be an object of ticker to weight".to_string());
+        };
+        let mut weights = BTreeMap::new();
+        for (ticker, weight) in positions {
+            let ticker = ticker.trim().to_uppercase();
+            let weight = weight.as_f64().filter(|w| w.is_finite()).ok_or_else(|| format!("Weight of `{}` is not a number", ticker))?;
+            if !ticker.is_empty() && weight != 0.0 {
+                *weights.entry(ticker).or_insert(0.0) += weight;
+            }
+        }
+        if weights.is_empty() {
+            return Err("`portfolio` has no position".to_string());
+        }
+        if config.max_positions > 0 && weights.len() > config.max_positions {
+            return Err(format!("`portfolio` has {} positions, at most {} are allowed", weights.len(), config.max_positions));
+        }
+        Ok(Self { weights })
+    }
+
+    pub fn tickers(&self) -> Vec<String> {
+        self.weights.keys().cloned().collect()
+    }
+
+    /// Weight of `ticker` over the sum of the absolute weights, 0 outside the portfolio.
+    pub fn exposure(&self, ticker: &str) -> f64 {
+        let gross: f64 = self.weights.values().map(|w| w.abs()).sum();
+        self.weights.get(&ticker.to_uppercase()).map(|w| w.abs() / gross).unwrap_or(0.0)
+    }
+}
+
+/// A position and its news over the window.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PositionSummary {
+    pub ticker: String,
+    pub weight: f64,
+    pub exposure: f64,
+    pub articles: usize,
+    /// Mean sentiment score, `None` without scored articles.
+    pub sentiment: Option<f64>,
+}
+
+/// An article and the exposure it touches.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RankedArticle {
+    pub exposure: f64,
+    /// Tickers of the portfolio the article is tagged with.
+    pub positions: Vec<String>,
+    pub article: NormalizedArticle,
+}
+
+/// Positions, sentiment and ranked news of a portfolio between `from` and `to`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PortfolioNews {
+    pub from: String,
+    pub to: String,
+    pub positions: Vec<PositionSummary>,
+    /// Exposure-weighted sentiment, short positions reversed. `None` without scored articles.
+    pub sentiment: Option<f64>,
+    /// Largest exposure first.
+    pub news: Vec<RankedArticle>,
+}
+impl PortfolioNews {
+    /// Aggregates the `articles` published between `from` and `to` for `portfolio`.
+    pub fn build(portfolio: &Portfolio, from: &str, to: &str, articles: Vec<NormalizedArticle>, config: &PortfolioConfig) -> Self {
+        let positions: Vec<PositionSummary> = portfolio
+            .weights
+            .iter()
+            .map(|(ticker, weight)| {
+                let tagged: Vec<&NormalizedArticle> = articles.iter().filter(|a| is_tagged(a, ticker)).collect();
+                let scores: Vec<f64> = tagged.iter().filter_map(|a| a.sentiment_score).collect();
+                PositionSummary {
+                    ticker: ticker.clone(),
+                    weight: *weight,
+                    exposure: portfolio.exposure(ticker),
+                    articles: tagged.len(),
+                    sentiment: (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64),
+                }
+            })
+            .collect();
+
+        // Weighted over the positions having a sentiment only, so quiet positions do not dilute it.
+        let scored: Vec<(f64, f64)> = positions
+            .iter()
+            .filter_map(|p| p.sentiment.map(|s| (p.exposure, s * p.weight.signum())))
+            .collect();
+        let scored_exposure: f64 = scored.iter().map(|(exposure, _)| exposure).sum();
+        let sentiment = (scored_exposure > 0.0).then(|| scored.iter().map(|(e, s)| e * s).sum::<f64>() / scored_exposure);
+
+        let mut news: Vec<RankedArticle> = articles
+            .into_iter()
+            .map(|article| {
+                let positions: Vec<String> = portfolio.weights.keys().filter(|t| is_tagged(&article, t)).cloned().collect();
+                RankedArticle { exposure: positions.iter().map(|t| portfolio.exposure(t)).sum(), positions, article }
+            })
+            .filter(|ranked| !ranked.positions.is_empty())
+            .collect();
+        news.sort_by(|a, b| {
+            b.exposure.total_cmp(&a.exposure).then_with(|| b.article.published_at.cmp(&a.article.published_at))
+        });
+        if config.max_articles > 0 {
+            news.truncate(config.max_articles);
+        }
+
+        Self { from: from.to_string(), to: to.to_string(), positions, sentiment, news }
+    }
+}
+
+fn is_tagged(article: &NormalizedArticle, ticker: &str) -> bool {
+    article.tickers.iter().any(|t| t.eq_ignore_ascii_case(ticker))
+}
+
+/// Aggregates the stored articles published between `from` and `to` for `portfolio`.
+pub async fn news(storage: &dyn Storage, portfolio: &Portfolio, from: DateTime<Utc>, to: DateTime<Utc>, config: &PortfolioConfig) -> Result<PortfolioNews, OpError> {
+    let (from, to) = (from.to_rfc3339_opts(SecondsFormat::Secs, true), to.to_rfc3339_opts(SecondsFormat::Secs, true));
+    let articles = storage.articles_with_tickers(&portfolio.tickers(), &from, &to).await?;
+    Ok(PortfolioNews::build(portfolio, &from, &to, articles, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_positions() {
+        let config = PortfolioConfig::default();
+        let portfolio = Portfolio::from_value(&json!({"nvda": 3, "AAPL": 1.0, "MSFT": 0}), &config).unwrap();
+        assert_eq!(portfolio.tickers(), vec!["AAPL", "NVDA"]);
+        assert_eq!(portfolio.exposure("NVDA"), 0.75);
+        assert_eq!(portfolio.exposure("MSFT"), 0.0);
+
+        assert!(Portfolio::from_value(&json!(["NVDA"]), &config).is_err());
+        assert!(Portfolio::from_value(&json!({"NVDA": "a lot"}), &config).is_err());
+        assert!(Portfolio::from_value(&json!({}), &config).is_err());
+        let small = PortfolioConfig { max_positions: 1, ..config };
+        assert!(Portfolio::from_value(&json!({"NVDA": 1, "AAPL": 1}), &small).is_err());
+    }
+
+    #[test]
+    fn ranks_news_by_exposure() {
+        let portfolio = Portfolio::from_value(&json!({"NVDA": 0.6, "AAPL": 0.2, "TSLA": -0.2}), &PortfolioConfig::default()).unwrap();
+        let article = |id: &str, published_at: &str, tickers: &[&str], score: Option<f64>| {
+            NormalizedArticle::test(id).published_at(published_at).tickers(tickers).sentiment_score(score)
+        };
+        let articles = vec![
+            article("1", "2024-05-01T10:00:00Z", &["AAPL"], Some(0.4)),
+            article("2", "2024-05-01T09:00:00Z", &["NVDA"], Some(0.2)),
+            article("3", "2024-05-01T11:00:00Z", &["TSLA", "AAPL"], Some(0.5)),
+            article("4", "2024-05-01T12:00:00Z", &["NVDA"], None),
+        ];
+        let news = PortfolioNews::build(&portfolio, "from", "to", articles, &PortfolioConfig::default());
+
+        let ranked: Vec<&str> = news.news.iter().map(|r| r.article.id.as_str()).collect();
+        assert_eq!(ranked, vec!["4", "2", "3", "1"]);
+        assert_eq!(news.news[2].positions, vec!["AAPL", "TSLA"]);
+
+        let nvda = &news.positions[1];
+        assert_eq!((nvda.ticker.as_str(), nvda.articles, nvda.sentiment), ("NVDA", 2, Some(0.2)));
+        // NVDA 0.6 * 0.2, AAPL 0.2 * 0.45, TSLA short 0.2 * -0.5.
+        let expected = (0.6 * 0.2 + 0.2 * 0.45 - 0.2 * 0.5) / 1.0;
+        assert!((news.sentiment.unwrap() - expected).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,420 @@
+//! Versions of the websocket message schema.
+//!
+//! A connection picks its version in the handshake, with the `Sec-WebSocket-Protocol` header
+//! (`news_data.v1`, `news_data.v2`; the server selects the latest one it supports) or, for
+//! clients that cannot set it, a `version` query parameter. Connections without either speak v1,
+//! so both versions are served at once and existing clients keep working while others move on.
+//! Handshakes asking only for unknown versions are refused with a `400`.
+//!
+//! Requests are the same in every version. Responses and events differ:
+//!
+//! ```text
+//! v1: {"status": 200, "message": {...}, "reason": null}
+//!     {"status": 200, "message": {"event": "news", "news": {...}}, "reason": null}
+//! v2: {"version": 2, "status": 200, "ok": true, "data": {...}, "error": null}
+//!     {"version": 2, "status": 200, "ok": true, "event": "news", "data": {"news": {...}}, "error": null}
+//! ```
+//!
+//! The `id` of a request is echoed at the top level in both.
+//!
+//! ## MessagePack
+//!
+//! With the `msgpack` feature, a connection may exchange MessagePack `Binary` frames rather than
+//! JSON text, much smaller for large article batches: a `+msgpack` suffix to the subprotocol
+//! (`news_data.v2+msgpack`) or an `encoding=msgpack` query parameter. Messages have the same
+//! shape in both encodings. Text frames are still accepted from such clients.
+//!
+//! ## Chunks
+//!
+//! A response with more than `server.chunk_size` items, in its `message` or in an array field of
+//! it (the `data` or `feed` of a provider answer), is streamed as a sequence of `chunk` events
+//! rather than one message. Each carries `seq` (from 0), the `field` chunked (`null` for the
+//! `message` itself) and its `items`; the last one has `final: true`, the `total` of items and,
+//! for an object, the `rest` of its fields. Chunks echo the `id` of their request, those of
+//! concurrent requests may interleave. The chunks of a task result are built from its items as
+//! they are read, so no other copy of the result is held while it is sent.
+//!
+//! ```text
+//! v1: {"status": 200, "message": {"event": "chunk", "seq": 0, "field": "data", "items": [...], "final": false}, "reason": null}
+//!     {"status": 200, "message": {"event": "chunk", "seq": 1, "field": "data", "items": [...], "final": true, "total": 180, "rest": {"meta": {...}}}, "reason": null}
+//! ```
+
+use async_tungstenite::tungstenite::protocol::Message;
+use serde_json::{Map, Value};
+
+const SUBPROTOCOL_PREFIX: &str = "news_data.v";
+const MSGPACK_SUFFIX: &str = "+msgpack";
+const OK_STATUS: u64 = 200;
+const INVALID_STATUS: u64 = 400;
+
+/// Version of the messages of a connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protocol {
+    #[default]
+    V1,
+    V2,
+}
+impl Protocol {
+    pub fn from_version(version: u64) -> Option<Self> {
+        match version {
+            1 => Some(Protocol::V1),
+            2 => Some(Protocol::V2),
+            _ => None,
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        match self {
+            Protocol::V1 => 1,
+            Protocol::V2 => 2,
+        }
+    }
+
+    /// `Sec-WebSocket-Protocol` value of the version in `encoding`.
+    pub fn subprotocol(&self, encoding: Encoding) -> String {
+        let suffix = if encoding == Encoding::Json { "" } else { MSGPACK_SUFFIX };
+        format!("{}{}{}", SUBPROTOCOL_PREFIX, self.version(), suffix)
+    }
+
+    /// Version and encoding of a subprotocol, if supported.
+    fn parse_subprotocol(subprotocol: &str) -> Option<(Self, Encoding)> {
+        let version = subprotocol.trim().strip_prefix(SUBPROTOCOL_PREFIX)?;
+        let (version, encoding) = match version.strip_suffix(MSGPACK_SUFFIX) {
+            Some(version) => (version, Encoding::from_name("msgpack")?),
+            None => (version, Encoding::Json),
+        };
+        Some((Self::from_version(version.parse().ok()?)?, encoding))
+    }
+
+    /// Version and encoding asked for in a handshake: the latest supported of the `offered`
+    /// subprotocols, or else the `version` and `encoding` query parameters, v1 in JSON without
+    /// them. `Err` when only unsupported ones are asked for.
+    pub fn negotiate(offered: &[&str], version: Option<&str>, encoding: Option<&str>) -> Result<(Self, Encoding), String> {
+        if !offered.is_empty() {
+            return offered
+                .iter()
+                .filter_map(|p| Self::parse_subprotocol(p))
+                .max_by_key(|(protocol, encoding)| (*protocol, *encoding != Encoding::Json))
+                .ok_or_else(|| format!("Unsupported protocols: {}", offered.join(", ")));
+        }
+        let protocol = match version {
+            Some(version) => version
+                .parse()
+                .ok()
+                .and_then(Self::from_version)
+                .ok_or_else(|| format!("Unsupported protocol version `{}`", version))?,
+            None => Protocol::V1,
+        };
+        let encoding = match encoding {
+            Some(name) => Encoding::from_name(name).ok_or_else(|| format!("Unsupported encoding `{}`", name))?,
+            None => Encoding::Json,
+        };
+        Ok((protocol, encoding))
+    }
+
+    /// `message`, written as a v1 response, in this version.
+    pub fn render(&self, message: Value) -> Value {
+        match self {
+            Protocol::V1 => message,
+            Protocol::V2 => to_v2(message),
+        }
+    }
+}
+
+/// Encoding of the frames of a connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+impl Encoding {
+    /// `json`, or `msgpack` with the `msgpack` feature.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Encoding::Json),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Some(Encoding::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// Frame carrying `message`. A string is plain text, always sent as such.
+    pub fn frame(&self, message: Value) -> Message {
+        match (self, message) {
+            (_, Value::String(text)) => Message::Text(text),
+            (Encoding::Json, message) => Message::Text(message.to_string()),
+            #[cfg(feature = "msgpack")]
+            (Encoding::MessagePack, message) => match rmp_serde::to_vec_named(&message) {
+                Ok(bytes) => Message::Binary(bytes),
+                Err(_) => Message::Text(message.to_string()),
+            },
+        }
+    }
+
+    /// JSON text of the message of a `Binary` frame, `None` when it is not one in this encoding.
+    #[cfg_attr(not(feature = "msgpack"), allow(unused_variables))]
+    pub fn decode(&self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Encoding::Json => None,
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => rmp_serde::from_slice::<Value>(bytes).ok().map(|value| value.to_string()),
+        }
+    }
+}
+
+/// `response` as the `chunk` events streaming it, in chunks of `size` items (never when 0), or
+/// as is when it is not a success with more items than that. Each event is built when it is read.
+pub fn chunk(mut response: Value, size: usize) -> Box<dyn Iterator<Item = Value> + Send> {
+    let ok = response.get("status").and_then(Value::as_u64) == Some(OK_STATUS);
+    let taken = match response.get_mut("message") {
+        Some(message) if ok && size > 0 => take_items(message, size),
+        _ => None,
+    };
+    let Some((field, items)) = taken else {
+        return Box::new(std::iter::once(response));
+    };
+    let mut chunker = Chunker::new(size, response.get_mut("id").map(Value::take));
+    if let (Some(field), Some(Value::Object(rest))) = (field, response.get_mut("message").map(Value::take)) {
+        chunker = chunker.in_field(field, rest);
+    }
+
+    let mut items = items.into_iter();
+    let mut chunker = Some(chunker);
+    Box::new(std::iter::from_fn(move || {
+        let pending = chunker.as_mut()?;
+        for item in items.by_ref() {
+            if let Some(chunk) = pending.push(item) {
+                return Some(chunk);
+            }
+        }
+        chunker.take().map(Chunker::finish)
+    }))
+}
+
+/// Builds the `chunk` events of a success response from its items, as they come. At most `size`
+/// items plus one are held at once.
+pub struct Chunker {
+    size: usize,
+    id: Option<Value>,
+    /// Field of the message holding the items, `None` when the message is the list itself.
+    field: Option<String>,
+    /// Other fields of the message, sent with the last chunk.
+    rest: Option<Map<String, Value>>,
+    seq: usize,
+    total: usize,
+    pending: Vec<Value>,
+}
+impl Chunker {
+    /// Chunker of a response listing items in chunks of `size` (never when 0), echoing `id`.
+    pub fn new(size: usize, id: Option<Value>) -> Self {
+        Self { size, id, field: None, rest: None, seq: 0, total: 0, pending: Vec::new() }
+    }
+
+    /// The items are in `field` of a message with the other fields `rest`.
+    pub fn in_field(self, field: String, rest: Map<String, Value>) -> Self {
+        Self { field: Some(field), rest: Some(rest), ..self }
+    }
+
+    /// Adds `item`, returning the chunk it completes, if any. A chunk is complete once an item
+    /// follows it, the last one being returned by `finish`.
+    pub fn push(&mut self, item: Value) -> Option<Value> {
+        self.total += 1;
+        self.pending.push(item);
+        if self.size == 0 || self.pending.len() <= self.size {
+            return None;
+        }
+        let next = self.pending.split_off(self.size);
+        let items = std::mem::replace(&mut self.pending, next);
+        Some(self.event(items, false))
+    }
+
+    /// The last chunk, with the remaining items, their `total` and the `rest` of the message, or
+    /// the whole response when no chunk was completed.
+    pub fn finish(mut self) -> Value {
+        let items = std::mem::take(&mut self.pending);
+        if self.seq > 0 {
+            return self.event(items, true);
+        }
+        let message = match (self.field.take(), self.rest.take()) {
+            (Some(field), Some(mut rest)) => {
+                rest.insert(field, Value::Array(items));
+                Value::Object(rest)
+            }
+            _ => Value::Array(items),
+        };
+        self.response(message)
+    }
+
+    fn event(&mut self, items: Vec<Value>, last: bool) -> Value {
+        let mut message = Map::new();
+        message.insert("event".to_string(), Value::from("chunk"));
+        message.insert("seq".to_string(), Value::from(self.seq));
+        message.insert("field".to_string(), self.field.clone().map_or(Value::Null, Value::String));
+        message.insert("items".to_string(), Value::Array(items));
+        message.insert("final".to_string(), Value::Bool(last));
+        if last {
+            message.insert("total".to_string(), Value::from(self.total));
+            if let Some(rest) = self.rest.take() {
+                message.insert("rest".to_string(), Value::Object(rest));
+            }
+        }
+        self.seq += 1;
+        self.response(Value::Object(message))
+    }
+
+    fn response(&self, message: Value) -> Value {
+        let mut response = Map::new();
+        response.insert("status".to_string(), Value::from(OK_STATUS));
+        response.insert("message".to_string(), message);
+        response.insert("reason".to_string(), Value::Null);
+        if let Some(id) = &self.id {
+            response.insert("id".to_string(), id.clone());
+        }
+        Value::Object(response)
+    }
+}
+
+/// Takes the items of `message` to chunk, if more than `size`: the message itself when an array,
+/// or else its first array field, returned with them.
+fn take_items(message: &mut Value, size: usize) -> Option<(Option<String>, Vec<Value>)> {
+    match message {
+        Value::Array(items) if items.len() > size => Some((None, std::mem::take(items))),
+        Value::Object(object) => {
+            let field = object.iter().find(|(_, value)| value.as_array().is_some_and(|items| items.len() > size))?.0.clone();
+            let Some(Value::Array(items)) = object.remove(&field) else {
+                return None;
+            };
+            Some((Some(field), items))
+        }
+        _ => None,
+    }
+}
+
+fn to_v2(message: Value) -> Value {
+    let mut v1 = match message {
+        Value::Object(v1) => v1,
+        // Plain text answers, such as `Invalid JSON`, are errors.
+        message => Map::from_iter([
+            ("status".to_string(), Value::from(INVALID_STATUS)),
+            ("reason".to_string(), message),
+        ]),
+    };
+    let status = v1.get("status").and_then(Value::as_u64).unwrap_or(INVALID_STATUS);
+    let mut data = v1.remove("message").unwrap_or(Value::Null);
+    let event = data.as_object_mut().and_then(|data| data.remove("event"));
+
+    let mut v2 = Map::new();
+    v2.insert("version".to_string(), Value::from(Protocol::V2.version()));
+    if let Some(id) = v1.remove("id") {
+        v2.insert("id".to_string(), id);
+    }
+    v2.insert("status".to_string(), Value::from(status));
+    v2.insert("ok".to_string(), Value::Bool(status == OK_STATUS));
+    if let Some(event) = event {
+        v2.insert("event".to_string(), event);
+    }
+    v2.insert("data".to_string(), data);
+    v2.insert("error".to_string(), v1.remove("reason").unwrap_or(Value::Null));
+    Value::Object(v2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn negotiates_and_renders_versions() {
+        let json = Encoding::Json;
+        assert_eq!(Protocol::negotiate(&[], None, None), Ok((Protocol::V1, json)));
+        assert_eq!(Protocol::negotiate(&["news_data.v1", "news_data.v2", "news_data.v9"], None, None), Ok((Protocol::V2, json)));
+        assert_eq!(Protocol::negotiate(&[], Some("2"), Some("json")), Ok((Protocol::V2, json)));
+        assert!(Protocol::negotiate(&["graphql-ws"], None, None).is_err());
+        assert!(Protocol::negotiate(&[], Some("3"), None).is_err());
+        assert!(Protocol::negotiate(&[], None, Some("cbor")).is_err());
+
+        let response = json!({ "status": 200, "message": { "event": "news", "news": {} }, "reason": null, "id": 7 });
+        assert_eq!(Protocol::V1.render(response.clone()), response);
+        assert_eq!(
+            Protocol::V2.render(response),
+            json!({ "version": 2, "id": 7, "status": 200, "ok": true, "event": "news", "data": { "news": {} }, "error": null }),
+        );
+
+        let invalid = Value::from("Invalid JSON");
+        assert_eq!(json.frame(Protocol::V1.render(invalid.clone())), Message::Text("Invalid JSON".to_string()));
+        assert_eq!(
+            Protocol::V2.render(invalid),
+            json!({ "version": 2, "status": 400, "ok": false, "data": null, "error": "Invalid JSON" }),
+        );
+    }
+
+    #[test]
+    fn chunks_large_responses() {
+        let response = json!({ "status": 200, "message": { "data": [1, 2, 3, 4, 5], "meta": { "found": 5 } }, "reason": null, "id": "a" });
+        let chunks: Vec<Value> = chunk(response.clone(), 2).collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0], json!({
+            "status": 200,
+            "message": { "event": "chunk", "seq": 0, "field": "data", "items": [1, 2], "final": false },
+            "reason": null,
+            "id": "a",
+        }));
+        assert_eq!(chunks[2]["message"], json!({
+            "event": "chunk", "seq": 2, "field": "data", "items": [5], "final": true, "total": 5, "rest": { "meta": { "found": 5 } },
+        }));
+        let v2 = Protocol::V2.render(chunks[1].clone());
+        assert_eq!((v2["event"].clone(), v2["data"]["items"].clone()), (json!("chunk"), json!([3, 4])));
+
+        let chunks: Vec<Value> = chunk(json!({ "status": 200, "message": [1, 2, 3], "reason": null }), 2).collect();
+        assert_eq!(chunks[1]["message"], json!({ "event": "chunk", "seq": 1, "field": null, "items": [3], "final": true, "total": 3 }));
+
+        // Small or failed responses, or without chunks, are left alone.
+        assert_eq!(chunk(response.clone(), 5).collect::<Vec<_>>(), vec![response.clone()]);
+        assert_eq!(chunk(response.clone(), 0).collect::<Vec<_>>(), vec![response]);
+        let failed = json!({ "status": 400, "message": [1, 2, 3], "reason": "no" });
+        assert_eq!(chunk(failed.clone(), 2).collect::<Vec<_>>(), vec![failed]);
+    }
+
+    #[test]
+    fn chunks_items_as_they_come() {
+        let mut chunker = Chunker::new(2, Some(json!(4)));
+        assert_eq!((chunker.push(json!(1)), chunker.push(json!(2))), (None, None));
+        // A chunk is sent once an item follows it.
+        let first = chunker.push(json!(3)).unwrap();
+        assert_eq!(first, json!({
+            "status": 200,
+            "message": { "event": "chunk", "seq": 0, "field": null, "items": [1, 2], "final": false },
+            "reason": null,
+            "id": 4,
+        }));
+        assert_eq!(chunker.push(json!(4)), None);
+        assert_eq!(chunker.finish()["message"], json!({ "event": "chunk", "seq": 1, "field": null, "items": [3, 4], "final": true, "total": 4 }));
+
+        // Too few items for a chunk make the whole response.
+        let rest = json!({ "meta": { "found": 2 } }).as_object().unwrap().clone();
+        let mut chunker = Chunker::new(2, None).in_field("data".to_string(), rest);
+        assert_eq!((chunker.push(json!(1)), chunker.push(json!(2))), (None, None));
+        assert_eq!(chunker.finish(), json!({ "status": 200, "message": { "data": [1, 2], "meta": { "found": 2 } }, "reason": null }));
+        assert_eq!(Chunker::new(2, None).finish(), json!({ "status": 200, "message": [], "reason": null }));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn frames_messages_in_msgpack() {
+        let msgpack = Encoding::MessagePack;
+        let offered = ["news_data.v2", "news_data.v2+msgpack"];
+        assert_eq!(Protocol::negotiate(&offered, None, None), Ok((Protocol::V2, msgpack)));
+        assert_eq!(Protocol::V2.subprotocol(msgpack), "news_data.v2+msgpack");
+        assert_eq!(Protocol::negotiate(&[], None, Some("msgpack")), Ok((Protocol::V1, msgpack)));
+
+        let message = json!({ "status": 200, "message": { "articles": [{ "id": "1", "score": 0.5 }] } });
+        let Message::Binary(bytes) = msgpack.frame(message.clone()) else {
+            panic!("not a binary frame");
+        };
+        assert!(bytes.len() < message.to_string().len());
+        assert_eq!(serde_json::from_str::<Value>(&msgpack.decode(&bytes).unwrap()).unwrap(), message);
+        assert_eq!(msgpack.frame(Value::from("Invalid JSON")), Message::Text("Invalid JSON".to_string()));
+    }
+}
@@ -0,0 +1,162 @@
+//! Common interface of the news sources.
+//!
+//! Every client (`MarketAuxApiClient`, `AlphaVantageApiClient`, `FMPClient`, `FinnhubApiClient`, `PolygonApiClient`, `GdeltApiClient`, `RssClient`, `RedditClient`, ...) implements
+//! `NewsProvider`, so the polling loop and the websocket server can work with a list of providers
+//! instead of hard-coding each client. Adding a source means implementing the trait and adding it
+//! to `default_providers`.
+
+use std::fmt::Display;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use futures_util::Future;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::alphavantage::AlphaVantageApiClient;
+use crate::cache::SharedLockedCache;
+use crate::config::ValueConfig;
+use crate::enumerations::Enumerations;
+use crate::errors::ProviderError;
+use crate::finnhub::FinnhubApiClient;
+use crate::gdelt::GdeltApiClient;
+use crate::polygon::PolygonApiClient;
+use crate::reddit::RedditClient;
+use crate::rss::RssClient;
+use crate::fmp::FMPClient;
+use crate::marketaux::MarketAuxApiClient;
+use crate::normalize::NormalizedArticle;
+use crate::options::FetchType;
+use crate::request::HTTPClient;
+use crate::utils::now;
+
+/// Boxed future returned by `NewsProvider` methods, so the trait stays object safe.
+pub type ProviderFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Consecutive failures after which a provider is reported as unavailable.
+const UNAVAILABLE_AFTER_FAILURES: u32 = 3;
+
+/// A source of news.
+pub trait NewsProvider: Send + Sync {
+    /// Short, unique name of the provider (e.g. `marketaux`).
+    fn name(&self) -> &str;
+
+    /// Whether the provider can serve requests of the given `FetchType`.
+    fn supports(&self, fetch_type: &FetchType) -> bool;
+
+    /// Fetches data for request `args`, as sent by websocket clients.
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>>;
+
+    /// Fetches the latest news, as used by the polling loop.
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>>;
+
+    /// Current health, derived from recent fetch outcomes.
+    fn health(&self) -> ProviderHealth;
+
+    /// Fetches the articles published between `from` and `to`, used to fill coverage gaps.
+    /// Providers that cannot filter by publication time return no article.
+    fn fetch_window(&self, _from: DateTime<Utc>, _to: DateTime<Utc>) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    /// Fetches the articles published on `day` (UTC), used by the backfill. Defaults to
+    /// `fetch_window` over the day.
+    fn fetch_day(&self, day: NaiveDate) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+        let from = day.and_time(NaiveTime::MIN).and_utc();
+        let to = day.and_hms_opt(23, 59, 59).unwrap_or_default().and_utc();
+        self.fetch_window(from, to)
+    }
+
+    /// Fetches the latest news mentioning `symbol`, used by the watchlist fan-out.
+    /// Providers that cannot filter by symbol return no article.
+    fn fetch_symbol<'a>(&'a self, _symbol: &'a str) -> ProviderFuture<'a, Result<Vec<NormalizedArticle>, ProviderError>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    /// Fetches the values accepted by the filters of the provider, checked before sending a request
+    /// (see `enumerations.rs`). Providers without such lists return none.
+    fn fetch_enumerations(&self) -> ProviderFuture<'_, Result<Enumerations, ProviderError>> {
+        Box::pin(async { Ok(Enumerations::new()) })
+    }
+}
+
+/// Health of a provider.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum ProviderHealth {
+    Healthy,
+    Degraded(String),
+    Unavailable(String),
+}
+
+#[derive(Debug, Default)]
+struct HealthState {
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    last_success: Option<String>,
+}
+
+/// Records fetch outcomes of a provider to derive its `ProviderHealth`.
+#[derive(Debug, Default)]
+pub struct HealthTracker {
+    state: StdMutex<HealthState>,
+}
+impl HealthTracker {
+    /// Records the outcome of a fetch.
+    pub fn record<T, E: Display>(&self, result: &Result<T, E>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(_) => {
+                state.consecutive_failures = 0;
+                state.last_success = Some(now());
+            }
+            Err(e) => {
+                state.consecutive_failures += 1;
+                state.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Health given the configured `api_key`: a provider without a key is never healthy.
+    pub fn health(&self, api_key: &str) -> ProviderHealth {
+        if api_key.trim().is_empty() {
+            return ProviderHealth::Unavailable("API key is not configured".to_string());
+        }
+        self.status()
+    }
+
+    /// Health derived from the recorded outcomes only, for providers without an API key.
+    pub fn status(&self) -> ProviderHealth {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let last_error = state.last_error.clone().unwrap_or_default();
+        match state.consecutive_failures {
+            0 => ProviderHealth::Healthy,
+            n if n < UNAVAILABLE_AFTER_FAILURES => {
+                ProviderHealth::Degraded(format!("{} consecutive failures. Last error: {}", n, last_error))
+            }
+            n => ProviderHealth::Unavailable(format!("{} consecutive failures. Last error: {}", n, last_error)),
+        }
+    }
+}
+
+/// Builds every available provider sharing the given clients, cache and config.
+pub fn default_providers(
+    http_client: Arc<HTTPClient>,
+    client: Arc<Client>,
+    cache: Arc<Mutex<SharedLockedCache>>,
+    config: Arc<ValueConfig>,
+) -> Vec<Arc<dyn NewsProvider>> {
+    vec![
+        Arc::new(AlphaVantageApiClient::new(client.clone(), cache.clone(), config.clone())),
+        Arc::new(MarketAuxApiClient::new(client.clone(), cache.clone(), config.clone())),
+        Arc::new(FinnhubApiClient::new(client.clone(), cache.clone(), config.clone())),
+        Arc::new(PolygonApiClient::new(client.clone(), cache.clone(), config.clone())),
+        Arc::new(GdeltApiClient::new(client.clone(), cache.clone(), config.clone())),
+        Arc::new(RssClient::new(client.clone(), config.clone())),
+        Arc::new(RedditClient::new(client, config.clone())),
+        Arc::new(FMPClient::new(http_client, cache, config)),
+    ]
+}
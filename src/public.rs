@@ -0,0 +1,211 @@
+//! Public read-only mode.
+//!
+//! Listeners flagged `public` (`server.public` for the main one) share the stored dataset without
+//! risking the upstream quotas or the data: their connections may only call the query functions
+//! of `public.functions` (stored articles by keyword or topic, trending keywords), never the
+//! provider polling functions nor admin commands. Each client IP (IPv6 /64 network) gets a token
+//! bucket of `public.burst` requests refilled at `public.requests_per_minute`, the `limit` param is
+//! capped at `public.max_limit` and responses are cached for `public.cache_ttl_secs`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::cache::{Cache, SharedLockedCache};
+use crate::config::PublicConfig;
+
+/// Buckets kept before the least recently refilled ones are forgotten.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// Key of the bucket in `Buckets::by_refill`.
+    order: (Instant, u64),
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    by_client: HashMap<IpAddr, Bucket>,
+    /// Clients by last refill, oldest first, the sequence number telling apart the ones of the same instant.
+    by_refill: BTreeMap<(Instant, u64), IpAddr>,
+    refills: u64,
+}
+
+/// Per-client token bucket, for at most `MAX_TRACKED_CLIENTS` clients.
+pub struct RateLimiter {
+    per_sec: f64,
+    burst: f64,
+    max_clients: usize,
+    buckets: Mutex<Buckets>,
+}
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32, burst: u32) -> Self {
+        Self {
+            per_sec: f64::from(requests_per_minute.max(1)) / 60.0,
+            burst: f64::from(burst.max(1)),
+            max_clients: MAX_TRACKED_CLIENTS,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Takes a token from the bucket of `ip`, or returns the time until one is available.
+    ///
+    /// Past `MAX_TRACKED_CLIENTS` clients, the bucket of the client seen least recently is
+    /// forgotten: it is the likeliest to be full again.
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let client = client_key(ip);
+        let mut guard = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let buckets = &mut *guard;
+        let order = (now, buckets.refills);
+        buckets.refills += 1;
+        match buckets.by_client.get(&client) {
+            Some(bucket) => {
+                buckets.by_refill.remove(&bucket.order);
+            }
+            None if buckets.by_client.len() >= self.max_clients => {
+                if let Some((_, oldest)) = buckets.by_refill.pop_first() {
+                    buckets.by_client.remove(&oldest);
+                }
+            }
+            None => {}
+        }
+        buckets.by_refill.insert(order, client);
+        let bucket = buckets.by_client.entry(client).or_insert(Bucket { tokens: self.burst, refilled_at: now, order });
+        let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_sec).min(self.burst);
+        bucket.refilled_at = now;
+        bucket.order = order;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_sec))
+        }
+    }
+}
+
+/// Key of the bucket of `ip`: the address itself, or the /64 network of an IPv6 address, a single
+/// host commonly holding a whole /64.
+fn client_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(v6) & (u128::MAX << 64))),
+        },
+        v4 => v4,
+    }
+}
+
+/// What public connections go through: function allowlist, rate limiting and response cache.
+pub struct PublicGate {
+    config: PublicConfig,
+    limiter: RateLimiter,
+    cache: SharedLockedCache,
+}
+impl PublicGate {
+    pub fn new(config: &PublicConfig) -> Self {
+        Self {
+            config: config.clone(),
+            limiter: RateLimiter::new(config.requests_per_minute, config.burst),
+            cache: SharedLockedCache::new(config.cache_size.max(1)),
+        }
+    }
+
+    pub fn allows(&self, function: &str) -> bool {
+        self.config.functions.iter().any(|f| f == function)
+    }
+
+    /// Takes a request from the budget of `ip`, or returns the seconds to wait.
+    pub fn check_rate(&self, ip: IpAddr) -> Result<(), u64> {
+        self.limiter.check(ip, Instant::now()).map_err(|wait| wait.as_secs().max(1))
+    }
+
+    /// `args` with their `limit` capped at `public.max_limit`.
+    pub fn cap_limit(&self, mut args: Value) -> Value {
+        if let Value::Object(map) = &mut args {
+            let limit = map.get("limit").and_then(Value::as_i64).unwrap_or(self.config.max_limit);
+            map.insert("limit".to_string(), Value::from(limit.clamp(1, self.config.max_limit)));
+        }
+        args
+    }
+
+    pub async fn cached(&self, key: &str) -> Option<Value> {
+        let (value, stored_at) = self.cache.get(key).await?;
+        (stored_at.elapsed() < Duration::from_secs(self.config.cache_ttl_secs)).then_some(value)
+    }
+
+    pub async fn store(&self, key: &str, value: &Value) {
+        self.cache.put(key.to_string(), (value.clone(), Instant::now())).await;
+    }
+
+    /// Empties the response cache, returning the entries removed.
+    pub async fn flush(&self) -> usize {
+        self.cache.remove_where(|_| true).await
+    }
+
+    pub async fn entries(&self) -> usize {
+        self.cache.read().await.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_client_to_its_bucket() {
+        let limiter = RateLimiter::new(60, 2);
+        let (a, b): (IpAddr, IpAddr) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        let start = Instant::now();
+        assert!(limiter.check(a, start).is_ok());
+        assert!(limiter.check(a, start).is_ok());
+        let wait = limiter.check(a, start).unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+        assert!(limiter.check(b, start).is_ok());
+        // One request per second is refilled.
+        assert!(limiter.check(a, start + Duration::from_secs(1)).is_ok());
+        assert!(limiter.check(a, start + Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn tracks_a_bounded_number_of_clients() {
+        let limiter = RateLimiter { max_clients: 3, ..RateLimiter::new(60, 1) };
+        let start = Instant::now();
+        for n in 1..=10 {
+            let ip = IpAddr::from([10, 0, 0, n]);
+            assert!(limiter.check(ip, start + Duration::from_millis(u64::from(n))).is_ok());
+        }
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!((buckets.by_client.len(), buckets.by_refill.len()), (3, 3));
+        // The least recently seen were forgotten.
+        assert!(buckets.by_client.contains_key(&IpAddr::from([10, 0, 0, 10])));
+        assert!(!buckets.by_client.contains_key(&IpAddr::from([10, 0, 0, 1])));
+    }
+
+    #[test]
+    fn shares_a_bucket_per_ipv6_network() {
+        let limiter = RateLimiter::new(60, 1);
+        let start = Instant::now();
+        let (a, b, other): (IpAddr, IpAddr, IpAddr) =
+            ("2001:db8::1".parse().unwrap(), "2001:db8::ffff:2".parse().unwrap(), "2001:db8:0:1::1".parse().unwrap());
+        assert!(limiter.check(a, start).is_ok());
+        assert!(limiter.check(b, start).is_err());
+        assert!(limiter.check(other, start).is_ok());
+    }
+
+    #[test]
+    fn caps_the_limit_param() {
+        let gate = PublicGate::new(&PublicConfig::default());
+        let max = PublicConfig::default().max_limit;
+        assert_eq!(gate.cap_limit(serde_json::json!({ "limit": 100_000 }))["limit"], max);
+        assert_eq!(gate.cap_limit(serde_json::json!({}))["limit"], max);
+        assert_eq!(gate.cap_limit(serde_json::json!({ "limit": 5 }))["limit"], 5);
+        assert!(gate.allows("keyword_news"));
+        assert!(!gate.allows("marketaux_news_polling"));
+    }
+}
@@ -0,0 +1,427 @@
+//! Right-to-forget purge of the content of a source domain or an author.
+//!
+//! A purge reaches every place the content may be kept:
+//! - hot storage: matching articles are deleted or anonymized, and the matching items of the raw
+//!   provider results are removed;
+//! - archives: the backups given with `--archive` are rewritten without (or with anonymized copies
+//!   of) the matching articles;
+//! - captures: the captured provider responses mentioning the target are deleted (see
+//!   `captures.rs`);
+//! - caches: cached provider responses mentioning the target are dropped and the response cache
+//!   of the public listeners is flushed (admin command only, the caches live in the server
+//!   process).
+//!
+//! The layers a purge could not reach, such as the caches of a server when purging from the
+//! command line, are listed in the `skipped` field of its report.
+//!
+//! Each purge appends a `PurgeRecord` to the audit collection, signed with HMAC-SHA256 using
+//! `purge.signing_key`, so the record can later be shown to be unaltered.
+//!
+//! ## Usage
+//!
+//! ```text
+//! news_data purge (--source DOMAIN | --author NAME) [--mode delete|anonymize] [--archive FILE]...
+//! ```
+
+use std::path::PathBuf;
+
+use futures::TryStreamExt;
+use hmac::{Hmac, Mac};
+use mongodb::bson::{doc, Document};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::backup::{self, BackupError};
+use crate::cache::SharedLockedCache;
+use crate::captures;
+use crate::db::{to_document, DataKind, DatabaseOps};
+use crate::public::PublicGate;
+use crate::utils::now;
+
+const ANONYMIZED: &str = "[anonymized]";
+const SIGNATURE_ALGORITHM: &str = "HMAC-SHA256";
+
+#[derive(Debug, Error)]
+pub enum PurgeError {
+    #[error("Invalid arguments: {0}")]
+    Usage(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Archive error: {0}")]
+    Archive(#[from] BackupError),
+}
+
+/// Content to purge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum PurgeTarget {
+    /// A source domain, e.g. `example.com` (subdomains included).
+    Source(String),
+    /// An author name, matched exactly.
+    Author(String),
+}
+impl PurgeTarget {
+    /// Filter on the articles collection.
+    fn article_filter(&self) -> Document {
+        match self {
+            PurgeTarget::Source(domain) => doc! {
+                "$or": [
+                    { "source": domain },
+                    { "url": { "$regex": domain_pattern(domain), "$options": "i" } },
+                ]
+            },
+            PurgeTarget::Author(name) => doc! { "authors": name },
+        }
+    }
+
+    /// Whether an archived article document matches.
+    fn matches_article(&self, document: &Value) -> bool {
+        match self {
+            PurgeTarget::Source(domain) => {
+                document.get("source").and_then(Value::as_str) == Some(domain.as_str())
+                    || document.get("url").and_then(Value::as_str).is_some_and(|url| url_has_domain(url, domain))
+            }
+            PurgeTarget::Author(name) => document
+                .get("authors")
+                .and_then(Value::as_array)
+                .is_some_and(|authors| authors.iter().any(|a| a.as_str() == Some(name.as_str()))),
+        }
+    }
+
+    /// Whether a cached value mentions the target at all.
+    fn mentioned_in(&self, value: &Value) -> bool {
+        value.to_string().to_lowercase().contains(&self.value().to_lowercase())
+    }
+
+    /// The domain or author name.
+    fn value(&self) -> &str {
+        match self {
+            PurgeTarget::Source(value) | PurgeTarget::Author(value) => value,
+        }
+    }
+}
+
+/// What happens to the matching articles.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PurgeMode {
+    /// Articles are removed.
+    Delete,
+    /// Articles are kept without the purged source or author.
+    Anonymize,
+}
+impl PurgeMode {
+    pub fn to_str(&self) -> &str {
+        match self {
+            PurgeMode::Delete => "delete",
+            PurgeMode::Anonymize => "anonymize",
+        }
+    }
+}
+impl std::str::FromStr for PurgeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(PurgeMode::Delete),
+            "anonymize" => Ok(PurgeMode::Anonymize),
+            _ => Err(format!("Unknown mode `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PurgeRequest {
+    pub target: PurgeTarget,
+    pub mode: PurgeMode,
+    pub archives: Vec<PathBuf>,
+    /// Who asked for the purge, recorded in the audit log.
+    pub requested_by: String,
+}
+impl PurgeRequest {
+    /// Request purging the content of `source` or `author`, exactly one of which is given.
+    pub fn new(
+        source: Option<String>,
+        author: Option<String>,
+        mode: PurgeMode,
+        archives: Vec<PathBuf>,
+        requested_by: &str,
+    ) -> Result<Self, PurgeError> {
+        let target = match (source, author) {
+            (Some(source), None) => PurgeTarget::Source(source.to_lowercase()),
+            (None, Some(author)) => PurgeTarget::Author(author),
+            _ => return Err(PurgeError::Usage("Either a source or an author is required".to_string())),
+        };
+        Ok(Self { target, mode, archives, requested_by: requested_by.to_string() })
+    }
+}
+
+/// What a purge changed, as recorded in the audit log.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PurgeReport {
+    pub articles: u64,
+    pub raw_results: u64,
+    pub archived_documents: u64,
+    pub archives: Vec<String>,
+    pub cache_entries: u64,
+    // Left out when zero or empty, so the records of older versions still verify.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub captures: u64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub public_cache_entries: u64,
+    /// Layers the purge could not reach, e.g. `provider_cache`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// Caches of the server process, reached by the `purge` admin command only.
+pub struct ServerCaches<'a> {
+    pub providers: &'a Mutex<SharedLockedCache>,
+    pub public: &'a PublicGate,
+}
+
+/// Audit record of a purge.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeRecord {
+    pub event: String,
+    pub target: PurgeTarget,
+    pub mode: PurgeMode,
+    pub requested_by: String,
+    pub at: String,
+    pub report: PurgeReport,
+    pub algorithm: String,
+    /// Hex HMAC of the record without `signature`. Empty when no signing key is configured.
+    pub signature: String,
+}
+impl PurgeRecord {
+    fn new(request: &PurgeRequest, report: PurgeReport) -> Self {
+        Self {
+            event: "purge".to_string(),
+            target: request.target.clone(),
+            mode: request.mode,
+            requested_by: request.requested_by.clone(),
+            at: now(),
+            report,
+            algorithm: SIGNATURE_ALGORITHM.to_string(),
+            signature: String::new(),
+        }
+    }
+
+    /// Bytes covered by the signature.
+    fn signed_payload(&self) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        unsigned.signature = String::new();
+        serde_json::to_vec(&unsigned).expect("Failed to serialize purge record")
+    }
+
+    pub fn sign(&mut self, key: &str) {
+        self.signature = hex::encode(mac(key, &self.signed_payload()).finalize().into_bytes());
+    }
+
+    /// Whether `signature` matches the record content for `key`.
+    pub fn verify(&self, key: &str) -> bool {
+        hex::decode(&self.signature)
+            .map(|signature| mac(key, &self.signed_payload()).verify_slice(&signature).is_ok())
+            .unwrap_or(false)
+    }
+}
+
+fn mac(key: &str, payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac
+}
+
+/// Runs a purge and records it in the audit log. `caches` are those of the server, if any.
+pub async fn purge(
+    db_ops: &DatabaseOps,
+    request: &PurgeRequest,
+    caches: Option<ServerCaches<'_>>,
+    signing_key: &str,
+) -> Result<PurgeRecord, PurgeError> {
+    let mut report = PurgeReport {
+        articles: purge_articles(db_ops, request).await?,
+        raw_results: purge_raw_results(db_ops, &request.target).await?,
+        ..PurgeReport::default()
+    };
+
+    for archive in &request.archives {
+        report.archived_documents += purge_archive(request, archive)?;
+        report.archives.push(archive.display().to_string());
+    }
+    report.captures = captures::purge(request.target.value()) as u64;
+    match caches {
+        Some(caches) => {
+            let target = &request.target;
+            report.cache_entries = caches.providers.lock().await.remove_where(|value| target.mentioned_in(value)).await as u64;
+            // Public responses are keyed by their request, not by what they contain.
+            report.public_cache_entries = caches.public.flush().await as u64;
+        }
+        None => report.skipped.extend(["provider_cache".to_string(), "public_cache".to_string()]),
+    }
+    if !report.skipped.is_empty() {
+        warn!("The purge did not reach: {}.", report.skipped.join(", "));
+    }
+
+    let mut record = PurgeRecord::new(request, report);
+    if signing_key.is_empty() {
+        warn!("`purge.signing_key` is not set. The purge audit record is not signed.");
+    } else {
+        record.sign(signing_key);
+    }
+    let entry = to_document(&record).map_err(|e| PurgeError::Database(e.to_string()))?;
+    db_ops.insert_audit(entry).await.map_err(|e| PurgeError::Database(e.to_string()))?;
+    info!("Purge of {:?} complete. | {:?}", record.target, record.report);
+    Ok(record)
+}
+
+async fn purge_articles(db_ops: &DatabaseOps, request: &PurgeRequest) -> Result<u64, PurgeError> {
+    let articles = db_ops.collection(DataKind::Articles);
+    let filter = request.target.article_filter();
+    let count = match request.mode {
+        PurgeMode::Delete => {
+            // Their dedup entries are kept and flagged, so a re-delivery does not store them again.
+            let purged = purged_keys(db_ops, filter.clone()).await?;
+            db_ops.collection(DataKind::Dedup)
+                .update_many(doc! { "_id": { "$in": purged } }, doc! { "$set": { "purged": true } }, None)
+                .await
+                .map_err(|e| PurgeError::Database(e.to_string()))?;
+            articles.delete_many(filter, None).await.map(|r| r.deleted_count)
+        }
+        PurgeMode::Anonymize => {
+            let update = match &request.target {
+                PurgeTarget::Source(_) => doc! {
+                    "$set": { "source": ANONYMIZED, "url": null },
+                    "$unset": { "history": "" },
+                },
+                PurgeTarget::Author(name) => doc! { "$pull": { "authors": name } },
+            };
+            articles.update_many(filter, update, None).await.map(|r| r.modified_count)
+        }
+    };
+    count.map_err(|e| PurgeError::Database(e.to_string()))
+}
+
+/// Dedup keys of the articles matching `filter`.
+async fn purged_keys(db_ops: &DatabaseOps, filter: Document) -> Result<Vec<String>, PurgeError> {
+    let options = FindOptions::builder().projection(doc! { "provider": 1, "id": 1 }).build();
+    let cursor = db_ops.collection(DataKind::Articles).find(filter, options).await
+        .map_err(|e| PurgeError::Database(e.to_string()))?;
+    let articles: Vec<Document> = cursor.try_collect().await.map_err(|e| PurgeError::Database(e.to_string()))?;
+    Ok(articles
+        .iter()
+        .filter_map(|article| Some(format!("{}:{}", article.get_str("provider").ok()?, article.get_str("id").ok()?)))
+        .collect())
+}
+
+/// Removes the matching items from the raw provider results. Raw payloads cannot be anonymized
+/// field by field reliably, so items are always removed.
+async fn purge_raw_results(db_ops: &DatabaseOps, target: &PurgeTarget) -> Result<u64, PurgeError> {
+    let (filter, update) = match target {
+        PurgeTarget::Source(domain) => (
+            doc! { "$or": [{ "marketaux.data.source": domain }, { "alphavantage.feed.source_domain": domain }] },
+            doc! { "$pull": { "marketaux.data": { "source": domain }, "alphavantage.feed": { "source_domain": domain } } },
+        ),
+        PurgeTarget::Author(name) => (
+            doc! { "alphavantage.feed.authors": name },
+            doc! { "$pull": { "alphavantage.feed": { "authors": name } } },
+        ),
+    };
+    db_ops
+        .collection(DataKind::Results)
+        .update_many(filter, update, None)
+        .await
+        .map(|r| r.modified_count)
+        .map_err(|e| PurgeError::Database(e.to_string()))
+}
+
+fn purge_archive(request: &PurgeRequest, path: &std::path::Path) -> Result<u64, PurgeError> {
+    let changed = backup::rewrite_archive(path, |kind, document| {
+        if kind != DataKind::Articles.to_str() || !request.target.matches_article(document) {
+            return true;
+        }
+        match (request.mode, &request.target) {
+            (PurgeMode::Delete, _) => return false,
+            (PurgeMode::Anonymize, PurgeTarget::Source(_)) => {
+                document["source"] = Value::String(ANONYMIZED.to_string());
+                document["url"] = Value::Null;
+                if let Some(map) = document.as_object_mut() {
+                    map.remove("history");
+                }
+            }
+            (PurgeMode::Anonymize, PurgeTarget::Author(name)) => {
+                if let Some(authors) = document.get_mut("authors").and_then(Value::as_array_mut) {
+                    authors.retain(|a| a.as_str() != Some(name.as_str()));
+                }
+            }
+        }
+        true
+    })?;
+    Ok(changed)
+}
+
+/// Regex matching URLs on `domain` or one of its subdomains.
+fn domain_pattern(domain: &str) -> String {
+    let escaped: String = domain
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c.to_string() } else { format!("\\{}", c) })
+        .collect();
+    format!("^[a-z]+://([^/]*\\.)?{}([:/?#]|$)", escaped)
+}
+
+fn url_has_domain(url: &str, domain: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+        .is_some_and(|host| host == domain || host.ends_with(&format!(".{}", domain)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn matches_source_by_name_or_url_domain() {
+        let target = PurgeTarget::Source("example.com".to_string());
+        assert!(target.matches_article(&json!({ "source": "example.com" })));
+        assert!(target.matches_article(&json!({ "url": "https://news.example.com/a" })));
+        assert!(!target.matches_article(&json!({ "url": "https://notexample.com/a" })));
+    }
+
+    #[test]
+    fn signature_covers_the_record() {
+        let request = PurgeRequest::new(None, Some("Jane Doe".to_string()), PurgeMode::Delete, Vec::new(), "cli").unwrap();
+        let mut record = PurgeRecord::new(&request, PurgeReport { articles: 3, ..PurgeReport::default() });
+        record.sign("secret");
+        assert!(record.verify("secret"));
+        assert!(!record.verify("other"));
+
+        record.report.articles = 2;
+        assert!(!record.verify("secret"));
+    }
+
+    #[test]
+    fn records_of_older_versions_still_verify() {
+        let request = PurgeRequest::new(Some("example.com".to_string()), None, PurgeMode::Delete, Vec::new(), "cli").unwrap();
+        let mut record = PurgeRecord::new(&request, PurgeReport { articles: 3, ..PurgeReport::default() });
+        record.sign("secret");
+        let older = serde_json::to_value(&record).unwrap();
+        assert!(older["report"].get("skipped").is_none() && older["report"].get("captures").is_none());
+        assert!(serde_json::from_value::<PurgeRecord>(older).unwrap().verify("secret"));
+
+        record.report.skipped = vec!["provider_cache".to_string()];
+        assert!(!record.verify("secret"));
+    }
+}
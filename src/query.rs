@@ -0,0 +1,215 @@
+//! Queries of the stored articles from the command line.
+//!
+//! The `query` command runs the same storage queries as the websocket functions (`ticker_news`,
+//! `keyword_news`, `category_news`, `entity_news`, `sentiment_news`) without starting the server,
+//! so operators inspect the dataset without a Mongo shell:
+//!
+//! ```text
+//! news_data query --ticker NVDA,AMD [--hours 24 | --since 2024-05-01T00:00:00Z] [--limit 20] [--format table|json|csv]
+//! news_data query --keywords "rate cut" | --categories earnings | --entities "Jensen Huang" | --sentiment bullish
+//! news_data query --provider finnhub --min-sentiment 0.3
+//! ```
+//!
+//! `--provider` and `--min-sentiment` narrow any of the queries, or every article published in the
+//! window without one. Articles are printed newest first, as a table by default.
+
+use chrono::{Duration as UtcDuration, SecondsFormat};
+
+use crate::clock;
+use crate::db::OpError;
+use crate::normalize::NormalizedArticle;
+use crate::sentiment::SentimentLabel;
+use crate::storage::{ArticleFilter, Storage};
+
+/// Articles read at most to apply the `providers` and `min_sentiment` filters of a query.
+const SCAN_LIMIT: i64 = 10_000;
+
+/// Articles to look for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryFilter {
+    /// Every article of the window.
+    Any,
+    Tickers(Vec<String>),
+    Keywords(Vec<String>),
+    Categories(Vec<String>),
+    Entities(Vec<String>),
+    Sentiment(Vec<SentimentLabel>),
+}
+
+/// A query of the articles published in the last `hours`, or `since` some time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub filter: QueryFilter,
+    pub hours: i64,
+    /// Earliest publication time (RFC 3339), replacing `hours`.
+    pub since: Option<String>,
+    /// Providers the articles were fetched from, any when empty.
+    pub providers: Vec<String>,
+    /// Lowest `sentiment_score`, unscored articles being left out.
+    pub min_sentiment: Option<f64>,
+    pub limit: usize,
+}
+impl Query {
+    /// Query of the articles of the last 24 hours matching `filter`.
+    pub fn new(filter: QueryFilter) -> Self {
+        Self { filter, hours: 24, since: None, providers: Vec::new(), min_sentiment: None, limit: 20 }
+    }
+
+    /// Whether `article` passes the `providers` and `min_sentiment` filters.
+    fn keeps(&self, article: &NormalizedArticle) -> bool {
+        (self.providers.is_empty() || self.providers.iter().any(|p| p.eq_ignore_ascii_case(&article.provider)))
+            && self.min_sentiment.is_none_or(|min| article.sentiment_score.is_some_and(|score| score >= min))
+    }
+}
+
+/// Output of the `query` command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// One aligned line per article, under a header.
+    Table,
+    /// A JSON array of the articles.
+    Json,
+    /// One row per article, under a header.
+    Csv,
+}
+impl OutputFormat {
+    pub fn to_str(&self) -> &str {
+        match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("unknown output format `{}`", s)),
+        }
+    }
+}
+
+/// Runs `query` against `storage`, newest articles first.
+pub async fn run(storage: &dyn Storage, query: &Query) -> Result<Vec<NormalizedArticle>, OpError> {
+    let now = clock::now();
+    let since = match &query.since {
+        Some(since) => since.clone(),
+        None => (now - UtcDuration::hours(query.hours)).to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let until = now.to_rfc3339_opts(SecondsFormat::Secs, true);
+    // The storage queries do not filter on providers and sentiment: more articles are read then.
+    let limit = match query.providers.is_empty() && query.min_sentiment.is_none() {
+        true => query.limit as i64,
+        false => SCAN_LIMIT,
+    };
+    let mut articles = match &query.filter {
+        QueryFilter::Any => {
+            let filter = ArticleFilter { from: Some(since), to: Some(until), ..ArticleFilter::default() };
+            storage.filtered_articles(&filter, limit).await?
+        }
+        QueryFilter::Tickers(tickers) => storage.articles_with_tickers(tickers, &since, &until).await?,
+        QueryFilter::Keywords(keywords) => storage.articles_with_keywords(keywords, &since, limit).await?,
+        QueryFilter::Categories(categories) => storage.articles_with_categories(categories, &since, limit).await?,
+        QueryFilter::Entities(names) => storage.articles_with_entities(names, None, &since, limit).await?,
+        QueryFilter::Sentiment(labels) => storage.articles_with_sentiment(labels, &since, &until).await?,
+    };
+    articles.retain(|article| query.keeps(article));
+    articles.sort_by(|a, b| b.published_at.cmp(&a.published_at));
+    articles.truncate(query.limit);
+    Ok(articles)
+}
+
+/// `articles` in `format`.
+pub fn render(articles: &[NormalizedArticle], format: OutputFormat) -> Result<String, String> {
+    match format {
+        OutputFormat::Table => {
+            let header = format!("{:<20}  {:<12} {:<16} {}", "PUBLISHED", "PROVIDER", "TICKERS", "TITLE");
+            Ok(std::iter::once(header).chain(articles.iter().map(line)).map(|l| l + "\n").collect())
+        }
+        OutputFormat::Json => serde_json::to_string_pretty(articles).map(|json| json + "\n").map_err(|e| e.to_string()),
+        OutputFormat::Csv => csv(articles).map_err(|e| e.to_string()),
+    }
+}
+
+fn csv(articles: &[NormalizedArticle]) -> Result<String, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["published_at", "provider", "id", "source", "tickers", "language", "sentiment_score", "sentiment", "title", "url"])?;
+    for article in articles {
+        writer.write_record([
+            article.published_at.as_deref().unwrap_or_default(),
+            &article.provider,
+            &article.id,
+            article.source.as_deref().unwrap_or_default(),
+            &article.tickers.join(","),
+            article.language.as_deref().unwrap_or_default(),
+            &article.sentiment_score.map(|score| score.to_string()).unwrap_or_default(),
+            article.sentiment.map(|label| label.to_str()).unwrap_or_default(),
+            article.title.as_deref().unwrap_or_default(),
+            article.url.as_deref().unwrap_or_default(),
+        ])?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// One line per article: publication time, provider, tickers and title.
+pub fn line(article: &NormalizedArticle) -> String {
+    format!(
+        "{}  {:<12} {:<16} {}",
+        article.published_at.as_deref().unwrap_or("-"),
+        article.provider,
+        article.tickers.join(","),
+        article.title.as_deref().unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn lists_the_latest_matches_first() {
+        let storage = MemoryStorage::new();
+        let article = |id: &str, hours_ago: i64, ticker: &str| -> NormalizedArticle {
+            let published_at = (clock::now() - UtcDuration::hours(hours_ago)).to_rfc3339_opts(SecondsFormat::Secs, true);
+            NormalizedArticle::test(id).title(&format!("Story {}", id)).published_at(&published_at).tickers(&[ticker])
+        };
+        storage.store_articles(&[article("1", 30, "NVDA"), article("2", 5, "NVDA"), article("3", 2, "NVDA"), article("4", 1, "AMD")]).await.unwrap();
+
+        let query = Query { limit: 10, ..Query::new(QueryFilter::Tickers(vec!["NVDA".to_string()])) };
+        let ids: Vec<String> = run(&storage, &query).await.unwrap().into_iter().map(|a| a.id).collect();
+        assert_eq!(ids, vec!["3", "2"]);
+
+        let articles = run(&storage, &Query { limit: 1, ..query }).await.unwrap();
+        assert_eq!(articles.len(), 1);
+        assert!(line(&articles[0]).ends_with("finnhub      NVDA             Story 3"));
+    }
+
+    #[tokio::test]
+    async fn narrows_any_query_by_provider_and_sentiment() {
+        let storage = MemoryStorage::new();
+        let published_at = (clock::now() - UtcDuration::hours(1)).to_rfc3339_opts(SecondsFormat::Secs, true);
+        let article = |id: &str, provider: &str, score: Option<f64>| {
+            NormalizedArticle::test(id).provider(provider).title(&format!("Story, {}", id)).published_at(&published_at).tickers(&["NVDA", "AMD"]).sentiment_score(score)
+        };
+        storage.store_articles(&[article("1", "finnhub", Some(0.6)), article("2", "finnhub", Some(0.1)), article("3", "polygon", Some(0.8)), article("4", "finnhub", None)]).await.unwrap();
+
+        let query = Query { providers: vec!["Finnhub".to_string()], min_sentiment: Some(0.5), ..Query::new(QueryFilter::Any) };
+        let articles = run(&storage, &query).await.unwrap();
+        assert_eq!(articles.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["1"]);
+        let since = (clock::now() + UtcDuration::hours(1)).to_rfc3339_opts(SecondsFormat::Secs, true);
+        assert!(run(&storage, &Query { since: Some(since), ..query }).await.unwrap().is_empty());
+
+        let csv = render(&articles, OutputFormat::Csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("published_at,provider,id,source,tickers,language,sentiment_score,sentiment,title,url"));
+        assert_eq!(lines.next(), Some(format!("{},finnhub,1,,\"NVDA,AMD\",,0.6,,\"Story, 1\",", published_at).as_str()));
+        assert!(render(&articles, OutputFormat::Table).unwrap().starts_with("PUBLISHED "));
+    }
+}
@@ -0,0 +1,346 @@
+//! Daily quotas of the provider APIs.
+//!
+//! With `quota.enabled`, the requests sent to each provider are counted per UTC day and compared to
+//! the daily limit of its plan in `[quota.limits]`:
+//!
+//! ```toml
+//! [quota]
+//! enabled = true
+//! [quota.limits]
+//! marketaux = 100
+//! alphavantage = 25
+//! ```
+//!
+//! Past `quota.throttle_at` of its limit (a share), the requests to a provider are spaced for the
+//! rest of the budget to last until the end of the day. Past `quota.pause_at`, they are refused
+//! until then with a `RateLimitError`, rather than sent to be answered with 429s. The providers
+//! without a limit are only counted.
+//!
+//! A throttled fetch waits for its turn before taking a permit of the shared pool of fetches (see
+//! `limiter.rs`), then each of its requests waits `quota.max_wait_secs` at most: a request whose
+//! turn is later is refused with a `RateLimitError`, left to the next polling cycle.
+//!
+//! The counts are saved to the `database.collections.quota` collection, one document per provider
+//! and day, every minute and at the end of a backfill, and loaded at start: a restart does not
+//! reset the budget. The `status` admin command returns the calls, limit and state of each provider.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use tracing::{debug, info, warn};
+
+use crate::clock;
+use crate::config::QuotaConfig;
+use crate::db::DatabaseOps;
+use crate::errors::ApiError;
+
+/// Pause between two saves of the counts.
+const FLUSH: Duration = Duration::from_secs(60);
+
+/// Whether a request can be sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    Go,
+    /// After this delay, the budget being close to exhausted.
+    Wait(Duration),
+    /// Not before this time, too far for the request to wait.
+    Deferred(DateTime<Utc>),
+    /// Not before the next day, the budget being exhausted.
+    Paused(String),
+}
+
+/// State of the quota of a provider.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaState {
+    Ok,
+    Throttled,
+    Paused,
+}
+
+/// Calls of a provider today, against its limit.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ProviderQuota {
+    pub calls: u64,
+    pub limit: Option<u64>,
+    pub state: QuotaState,
+}
+
+/// Calls of every provider today, given a `QuotaConfig`.
+#[derive(Debug, Default)]
+pub struct Quotas {
+    config: QuotaConfig,
+    day: Option<NaiveDate>,
+    calls: HashMap<String, u64>,
+    /// Calls not saved yet, by day and provider.
+    unsaved: HashMap<(NaiveDate, String), u64>,
+    /// Earliest time of the next request of the throttled providers.
+    next_call: HashMap<String, DateTime<Utc>>,
+}
+impl Quotas {
+    pub fn new(config: &QuotaConfig) -> Self {
+        Self { config: config.clone(), ..Self::default() }
+    }
+
+    /// Starts the counts of `now`'s day, when it is a new one.
+    fn roll(&mut self, now: DateTime<Utc>) -> NaiveDate {
+        let today = now.date_naive();
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.calls.clear();
+            self.next_call.clear();
+        }
+        today
+    }
+
+    /// Adds the calls of `day` saved by a previous run.
+    pub fn load(&mut self, day: NaiveDate, saved: HashMap<String, u64>, now: DateTime<Utc>) {
+        if self.roll(now) == day {
+            for (provider, calls) in saved {
+                *self.calls.entry(provider).or_default() += calls;
+            }
+        }
+    }
+
+    fn state(&self, provider: &str) -> QuotaState {
+        let Some(limit) = self.config.limits.get(provider).map(|limit| *limit as f64) else {
+            return QuotaState::Ok;
+        };
+        let calls = self.calls.get(provider).copied().unwrap_or_default() as f64;
+        if calls >= limit * self.config.pause_at {
+            QuotaState::Paused
+        } else if calls >= limit * self.config.throttle_at {
+            QuotaState::Throttled
+        } else {
+            QuotaState::Ok
+        }
+    }
+
+    /// Time left before the turn of the next request to `provider`, `quota.max_wait_secs` at most.
+    pub fn delay(&mut self, provider: &str, now: DateTime<Utc>) -> Duration {
+        self.roll(now);
+        let max_wait = Duration::from_secs(self.config.max_wait_secs);
+        match self.next_call.get(provider) {
+            Some(next_call) if self.state(provider) == QuotaState::Throttled => (*next_call - now).to_std().unwrap_or_default().min(max_wait),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Whether a request to `provider` can be sent at `now`, counting it when it can.
+    pub fn admit(&mut self, provider: &str, now: DateTime<Utc>) -> Admission {
+        let today = self.roll(now);
+        let state = self.state(provider);
+        let calls = self.calls.get(provider).copied().unwrap_or_default();
+        let limit = self.config.limits.get(provider).copied().unwrap_or_default();
+        let end_of_day = today.succ_opt().and_then(|day| day.and_hms_opt(0, 0, 0)).map(|t| t.and_utc()).unwrap_or(now);
+        let admission = match state {
+            QuotaState::Ok => Admission::Go,
+            QuotaState::Paused => {
+                return Admission::Paused(format!(
+                    "Daily quota of {} nearly exhausted ({}/{} calls), paused until {}",
+                    provider,
+                    calls,
+                    limit,
+                    end_of_day.to_rfc3339()
+                ));
+            }
+            QuotaState::Throttled => {
+                let left = ((limit as f64 * self.config.pause_at) as u64).saturating_sub(calls).max(1);
+                let pace = (end_of_day - now) / left as i32;
+                let next_call = self.next_call.get(provider).copied().unwrap_or(now).max(now);
+                let wait = (next_call - now).to_std().unwrap_or_default();
+                if wait > Duration::from_secs(self.config.max_wait_secs) {
+                    return Admission::Deferred(next_call);
+                }
+                self.next_call.insert(provider.to_string(), next_call + pace);
+                Admission::Wait(wait)
+            }
+        };
+        *self.calls.entry(provider.to_string()).or_default() += 1;
+        *self.unsaved.entry((today, provider.to_string())).or_default() += 1;
+        admission
+    }
+
+    /// Takes the calls not saved yet.
+    pub fn take_unsaved(&mut self) -> HashMap<(NaiveDate, String), u64> {
+        std::mem::take(&mut self.unsaved)
+    }
+
+    /// Gives back calls that failed to be saved.
+    pub fn restore(&mut self, unsaved: HashMap<(NaiveDate, String), u64>) {
+        for (key, calls) in unsaved {
+            *self.unsaved.entry(key).or_default() += calls;
+        }
+    }
+
+    pub fn report(&mut self, now: DateTime<Utc>) -> BTreeMap<String, ProviderQuota> {
+        self.roll(now);
+        let providers: Vec<String> = self.calls.keys().chain(self.config.limits.keys()).cloned().collect();
+        providers
+            .into_iter()
+            .map(|provider| {
+                let quota = ProviderQuota {
+                    calls: self.calls.get(&provider).copied().unwrap_or_default(),
+                    limit: self.config.limits.get(&provider).copied(),
+                    state: self.state(&provider),
+                };
+                (provider, quota)
+            })
+            .collect()
+    }
+}
+
+fn state() -> &'static Mutex<Quotas> {
+    static STATE: OnceLock<Mutex<Quotas>> = OnceLock::new();
+    STATE.get_or_init(Mutex::default)
+}
+
+fn with_state<T>(f: impl FnOnce(&mut Quotas) -> T) -> T {
+    f(&mut state().lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Applies the `[quota]` section of the config.
+pub fn configure(config: &QuotaConfig) {
+    with_state(|quotas| quotas.config = config.clone());
+}
+
+/// Waits for the quota of `provider` to allow a request, counting it. A `RateLimitError` when its
+/// budget is exhausted.
+pub async fn before_request(provider: &str) -> Result<(), ApiError> {
+    let admission = with_state(|quotas| quotas.config.enabled.then(|| quotas.admit(provider, clock::now())));
+    match admission {
+        None | Some(Admission::Go) => Ok(()),
+        Some(Admission::Wait(delay)) => {
+            debug!("Quota of {} nearly exhausted, waiting {:?} before the request.", provider, delay);
+            sleep(delay).await;
+            Ok(())
+        }
+        Some(Admission::Deferred(next_call)) => {
+            let message = format!("Quota of {} nearly exhausted, next request not before {}", provider, next_call.to_rfc3339());
+            warn!("{}", message);
+            Err(ApiError::RateLimitError { message, status: None, headers: None, body: None })
+        }
+        Some(Admission::Paused(message)) => {
+            warn!("{}", message);
+            Err(ApiError::RateLimitError { message, status: None, headers: None, body: None })
+        }
+    }
+}
+
+/// Waits for the turn of the next request to `provider` when throttled, `quota.max_wait_secs` at
+/// most, without counting it: before a fetch takes a permit of the shared pool.
+pub async fn ready(provider: &str) {
+    let delay = with_state(|quotas| if quotas.config.enabled { quotas.delay(provider, clock::now()) } else { Duration::ZERO });
+    if !delay.is_zero() {
+        debug!("Quota of {} nearly exhausted, waiting {:?} before the fetch.", provider, delay);
+        sleep(delay).await;
+    }
+}
+
+/// Saves the calls not saved yet to `db_ops`.
+pub async fn flush(db_ops: &DatabaseOps) {
+    let unsaved = with_state(|quotas| quotas.take_unsaved());
+    let mut failed = HashMap::new();
+    for ((day, provider), calls) in unsaved {
+        if let Err(e) = db_ops.add_quota_calls(&provider, &day.to_string(), calls).await {
+            warn!("Failed to save the quota calls of {}: {}", provider, e);
+            failed.insert((day, provider), calls);
+        }
+    }
+    with_state(|quotas| quotas.restore(failed));
+}
+
+/// Loads the calls of today saved to `db_ops` then saves the new ones every minute, in a background
+/// task, with `quota.enabled`.
+pub fn spawn(db_ops: Arc<DatabaseOps>, config: &QuotaConfig) -> Option<JoinHandle<()>> {
+    if !config.enabled {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let now = clock::now();
+        match db_ops.quota_calls(&now.date_naive().to_string()).await {
+            Ok(saved) => {
+                info!("Loaded the quota calls of {} providers.", saved.len());
+                with_state(|quotas| quotas.load(now.date_naive(), saved, now));
+            }
+            Err(e) => warn!("Failed to load the quota calls, counting from 0: {}", e),
+        }
+        loop {
+            sleep(FLUSH).await;
+            flush(&db_ops).await;
+        }
+    }))
+}
+
+/// Calls, limit and state of the providers today.
+pub fn report() -> BTreeMap<String, ProviderQuota> {
+    with_state(|quotas| quotas.report(clock::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn throttles_then_pauses_close_to_the_limit() {
+        let config = QuotaConfig {
+            enabled: true,
+            limits: HashMap::from([("marketaux".to_string(), 10)]),
+            throttle_at: 0.5,
+            pause_at: 0.9,
+            max_wait_secs: 24 * 3600,
+        };
+        let mut quotas = Quotas::new(&config);
+        let now = at("2024-05-01T12:00:00Z");
+        quotas.load(now.date_naive(), HashMap::from([("marketaux".to_string(), 4)]), now);
+        assert_eq!(quotas.admit("marketaux", now), Admission::Go);
+        assert_eq!(quotas.admit("gdelt", now), Admission::Go);
+
+        // 5 calls: the 4 left before the pause are spread over the 12 hours left.
+        assert_eq!(quotas.admit("marketaux", now), Admission::Wait(Duration::ZERO));
+        assert_eq!(quotas.admit("marketaux", now), Admission::Wait(Duration::from_secs(3 * 3600)));
+        assert_eq!(quotas.delay("marketaux", now), Duration::from_secs(7 * 3600));
+        assert_eq!(quotas.delay("gdelt", now), Duration::ZERO);
+        assert_eq!(quotas.report(now)["marketaux"], ProviderQuota { calls: 7, limit: Some(10), state: QuotaState::Throttled });
+        quotas.admit("marketaux", now);
+        quotas.admit("marketaux", now);
+        assert!(matches!(quotas.admit("marketaux", now), Admission::Paused(message) if message.contains("9/10")));
+        assert_eq!(quotas.report(now)["gdelt"], ProviderQuota { calls: 1, limit: None, state: QuotaState::Ok });
+
+        let unsaved = quotas.take_unsaved();
+        assert_eq!(unsaved[&(now.date_naive(), "marketaux".to_string())], 5);
+        assert!(quotas.take_unsaved().is_empty());
+
+        // A new day, a new budget.
+        assert_eq!(quotas.admit("marketaux", at("2024-05-02T00:00:01Z")), Admission::Go);
+    }
+
+    #[test]
+    fn refuses_the_requests_whose_turn_is_too_far() {
+        let config = QuotaConfig {
+            enabled: true,
+            limits: HashMap::from([("marketaux".to_string(), 10)]),
+            throttle_at: 0.5,
+            pause_at: 0.9,
+            max_wait_secs: 60,
+        };
+        let mut quotas = Quotas::new(&config);
+        let now = at("2024-05-01T12:00:00Z");
+        quotas.load(now.date_naive(), HashMap::from([("marketaux".to_string(), 5)]), now);
+        assert_eq!(quotas.admit("marketaux", now), Admission::Wait(Duration::ZERO));
+        // Not counted: the next turn stays 3 hours away.
+        assert_eq!(quotas.admit("marketaux", now), Admission::Deferred(at("2024-05-01T15:00:00Z")));
+        assert_eq!(quotas.admit("marketaux", now), Admission::Deferred(at("2024-05-01T15:00:00Z")));
+        assert_eq!(quotas.report(now)["marketaux"].calls, 6);
+        assert_eq!(quotas.delay("marketaux", now), Duration::from_secs(60));
+        assert_eq!(quotas.admit("marketaux", at("2024-05-01T15:00:00Z")), Admission::Wait(Duration::ZERO));
+    }
+}
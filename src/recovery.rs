@@ -0,0 +1,230 @@
+//! Downtime recovery on startup.
+//!
+//! While the service is down nothing is fetched, and the first cycle after a restart only looks
+//! back `request.delay_secs` (or from the watermarks, within `watermark.max_lookback_secs`). On
+//! startup, the time elapsed since the last stored article is taken as the downtime; when it
+//! exceeds `recovery.min_downtime_secs`, the missed window (at most `recovery.max_window_secs`) is
+//! fetched again with `NewsProvider::fetch_window` in slices of `recovery.slice_secs`, newest
+//! first, in a background task. Provider limits are respected by pausing `recovery.pause_ms`
+//! between two requests and sending at most `recovery.max_requests` per provider: the oldest
+//! slices left over are reported by the coverage checks like any other gap.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration as UtcDuration, SecondsFormat, Utc};
+use tokio::time::{sleep, Duration};
+use tracing::{error, info, warn, Instrument};
+
+use crate::{clock, limiter, logging};
+use crate::config::RecoveryConfig;
+use crate::coverage::Gap;
+use crate::normalize::parse_timestamp;
+use crate::provider::NewsProvider;
+use crate::storage::Storage;
+
+/// Window missed by a service last storing an article at `last_stored`, if long enough to recover.
+pub fn missed_window(last_stored: Option<DateTime<Utc>>, now: DateTime<Utc>, config: &RecoveryConfig) -> Option<Gap> {
+    let last_stored = last_stored?;
+    if now - last_stored <= UtcDuration::seconds(config.min_downtime_secs as i64) {
+        return None;
+    }
+    let earliest = now - UtcDuration::seconds(config.max_window_secs as i64);
+    Some(Gap { start: last_stored.max(earliest), end: now })
+}
+
+/// `window` cut in slices of `slice`, newest first.
+pub fn slices(window: Gap, slice: UtcDuration) -> Vec<Gap> {
+    let slice = slice.max(UtcDuration::seconds(1));
+    let mut slices = Vec::new();
+    let mut end = window.end;
+    while end > window.start {
+        let start = (end - slice).max(window.start);
+        slices.push(Gap { start, end });
+        end = start;
+    }
+    slices
+}
+
+/// Back-fetches the window missed during a downtime.
+pub struct Recovery {
+    providers: Vec<Arc<dyn NewsProvider>>,
+    db_ops: Arc<dyn Storage>,
+    config: RecoveryConfig,
+}
+impl Recovery {
+    /// Recovers the providers of `recovery.providers` among `providers`.
+    pub fn new(providers: &[Arc<dyn NewsProvider>], db_ops: Arc<dyn Storage>, config: &RecoveryConfig) -> Self {
+        let providers = providers
+            .iter()
+            .filter(|p| config.providers.iter().any(|name| name == p.name()))
+            .cloned()
+            .collect();
+        Self { providers, db_ops, config: config.clone() }
+    }
+
+    /// Window missed since the last stored article, if any.
+    pub async fn detect(&self) -> Option<Gap> {
+        let last_stored = match self.db_ops.last_stored_at().await {
+            Ok(last_stored) => last_stored.as_deref().and_then(parse_timestamp),
+            Err(e) => {
+                error!("Failed to find the last stored article: {}", e);
+                return None;
+            }
+        };
+        missed_window(last_stored, clock::now(), &self.config)
+    }
+
+    /// Fetches `window` again from every provider, returning how many articles were new.
+    pub async fn run(&self, window: Gap) -> usize {
+        let slices = slices(window, UtcDuration::seconds(self.config.slice_secs as i64));
+        let mut stored = 0;
+        for provider in &self.providers {
+            let max_requests = self.config.max_requests.max(1);
+            for (sent, slice) in slices.iter().enumerate() {
+                if sent >= max_requests {
+                    warn!(
+                        "Recovery of `{}` stopped after {} requests: no article before {}.",
+                        provider.name(),
+                        max_requests,
+                        slice.end.to_rfc3339_opts(SecondsFormat::Secs, true)
+                    );
+                    break;
+                }
+                if sent > 0 {
+                    sleep(Duration::from_millis(self.config.pause_ms)).await;
+                }
+                stored += self.recover_slice(provider.as_ref(), slice).await;
+            }
+        }
+        stored
+    }
+
+    async fn recover_slice(&self, provider: &dyn NewsProvider, slice: &Gap) -> usize {
+        let articles = match limiter::run(provider.name(), provider.fetch_window(slice.start, slice.end)).await {
+            Ok(articles) => articles,
+            Err(e) => {
+                error!("Recovery fetch of `{}` failed: {}", provider.name(), e);
+                return 0;
+            }
+        };
+        match self.db_ops.store_articles(&articles).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                error!("Recovery of `{}` failed to store articles: {}", provider.name(), e);
+                0
+            }
+        }
+    }
+
+    /// Detects the downtime now, before the polling loop stores anything, and recovers it in a
+    /// background task.
+    pub async fn spawn(self) {
+        let Some(window) = self.detect().await else {
+            return;
+        };
+        info!(
+            "Service was down from {} ({} minutes). Recovering the missed window...",
+            window.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            window.duration().num_minutes()
+        );
+        tokio::spawn(
+            async move {
+                let stored = self.run(window).await;
+                info!("Recovery stored {} new articles.", stored);
+            }
+            .instrument(logging::cycle_span("recovery")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use serde_json::Value;
+
+    use crate::errors::ProviderError;
+    use crate::normalize::NormalizedArticle;
+    use crate::options::FetchType;
+    use crate::provider::{ProviderFuture, ProviderHealth};
+    use crate::storage::MemoryStorage;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap() + UtcDuration::minutes(minutes)
+    }
+
+    /// Provider publishing one article at the end of each fetched window.
+    #[derive(Default)]
+    struct Archive {
+        windows: Mutex<Vec<Gap>>,
+    }
+    impl NewsProvider for Archive {
+        fn name(&self) -> &str {
+            "archive"
+        }
+
+        fn supports(&self, _fetch_type: &FetchType) -> bool {
+            false
+        }
+
+        fn fetch(&self, _args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async { Ok(Value::Null) })
+        }
+
+        fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async { Ok(Value::Null) })
+        }
+
+        fn health(&self) -> ProviderHealth {
+            ProviderHealth::Healthy
+        }
+
+        fn fetch_window(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> ProviderFuture<'_, Result<Vec<NormalizedArticle>, ProviderError>> {
+            self.windows.lock().unwrap().push(Gap { start: from, end: to });
+            let published_at = to.to_rfc3339_opts(SecondsFormat::Secs, true);
+            let article = NormalizedArticle::test(&published_at).provider("archive").published_at(&published_at);
+            Box::pin(async move { Ok(vec![article]) })
+        }
+    }
+
+    #[test]
+    fn only_long_downtimes_are_recovered() {
+        let config = RecoveryConfig { min_downtime_secs: 600, max_window_secs: 3 * 3600, ..RecoveryConfig::default() };
+        assert_eq!(missed_window(None, at(60), &config), None);
+        assert_eq!(missed_window(Some(at(55)), at(60), &config), None);
+        assert_eq!(missed_window(Some(at(0)), at(60), &config), Some(Gap { start: at(0), end: at(60) }));
+        assert_eq!(missed_window(Some(at(0)), at(600), &config), Some(Gap { start: at(420), end: at(600) }));
+    }
+
+    #[test]
+    fn slices_the_window_newest_first() {
+        let slices = slices(Gap { start: at(0), end: at(150) }, UtcDuration::minutes(60));
+        assert_eq!(
+            slices,
+            vec![Gap { start: at(90), end: at(150) }, Gap { start: at(30), end: at(90) }, Gap { start: at(0), end: at(30) }]
+        );
+    }
+
+    #[tokio::test]
+    async fn recovers_slices_within_the_request_budget() {
+        let archive = Arc::new(Archive::default());
+        let storage = Arc::new(MemoryStorage::new());
+        let config = RecoveryConfig {
+            providers: vec!["archive".to_string()],
+            slice_secs: 3600,
+            max_requests: 2,
+            pause_ms: 0,
+            ..RecoveryConfig::default()
+        };
+        let providers: Vec<Arc<dyn NewsProvider>> = vec![archive.clone()];
+        let recovery = Recovery::new(&providers, storage.clone(), &config);
+
+        assert_eq!(recovery.run(Gap { start: at(0), end: at(180) }).await, 2);
+        assert_eq!(
+            *archive.windows.lock().unwrap(),
+            vec![Gap { start: at(120), end: at(180) }, Gap { start: at(60), end: at(120) }]
+        );
+        assert_eq!(storage.articles().len(), 2);
+    }
+}
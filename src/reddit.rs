@@ -0,0 +1,346 @@
+//! ## Reddit financial subreddits source.
+//!
+//! New submissions of the subreddits listed in `reddit.subreddits` (r/stocks, r/wallstreetbets, ...)
+//! are fetched from the public JSON API (`/r/<subreddit>/new.json`, no credentials needed) every
+//! `reddit.poll_interval_secs`. Ticker mentions are extracted from the titles and bodies and
+//! aggregated per ticker and hour into `FMPMarketSentiment` records, stored in the social sentiment
+//! collection next to the StockTwits and Twitter figures of FMP. The mentions of each poll are added
+//! to the record of their hour, so an hour spanning several polls keeps one record per ticker.
+//!
+//! Mentions are `$TSLA`-style cashtags, plus bare uppercase words listed in `reddit.tickers`:
+//! without a list, words such as `CEO` or `YOLO` would be counted as tickers.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use chrono::DateTime;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{to_value, Value};
+use thiserror::Error;
+use tokio::time::sleep;
+use tracing::{debug, error, info, instrument, warn, Instrument};
+
+use crate::api_calls;
+use crate::captures;
+use crate::config::ValueConfig;
+use crate::errors::ProviderError;
+use crate::logging;
+use crate::options::FetchType;
+use crate::provider::{HealthTracker, NewsProvider, ProviderFuture, ProviderHealth};
+use crate::server_types::FMPMarketSentiment;
+use crate::storage::Storage;
+
+const BASE_URL: &str = "https://www.reddit.com/r";
+pub const PROVIDER_NAME: &str = "reddit";
+const SUBREDDIT_MAP_KEY: &str = "subreddit";
+const MAX_TICKER_LEN: usize = 5;
+
+#[derive(Debug, Error)]
+pub enum RedditError {
+    #[error("Failed to fetch r/{subreddit}: {source}")]
+    Request { subreddit: String, source: reqwest::Error },
+
+    #[error("r/{subreddit} responded with status {status}")]
+    Status { subreddit: String, status: StatusCode },
+
+    #[error("Failed to parse r/{subreddit}: {source}")]
+    Parse { subreddit: String, source: serde_json::Error },
+
+    #[error("Every subreddit failed: {0}")]
+    AllSubredditsFailed(String),
+}
+
+/// A listing page of the public JSON API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Listing {
+    pub data: ListingData,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListingData {
+    pub after: Option<String>,
+    #[serde(default)]
+    pub children: Vec<Thing>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Thing {
+    pub kind: String,
+    pub data: Submission,
+}
+
+/// A submission (`t3`) of a listing.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Submission {
+    pub id: String,
+    pub subreddit: String,
+    pub title: String,
+    #[serde(default)]
+    pub selftext: String,
+    pub author: Option<String>,
+    /// Creation time, as a UNIX timestamp.
+    pub created_utc: f64,
+    #[serde(default)]
+    pub score: i64,
+    #[serde(default)]
+    pub num_comments: u64,
+    pub permalink: Option<String>,
+}
+
+/// Tickers mentioned in `text`: cashtags, and the bare words found in `known`.
+pub fn extract_tickers(text: &str, known: &HashSet<String>) -> Vec<String> {
+    let mut tickers = Vec::new();
+    for word in text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '$')) {
+        let (symbol, cashtag) = match word.strip_prefix('$') {
+            Some(symbol) => (symbol, true),
+            None => (word, false),
+        };
+        let valid = !symbol.is_empty()
+            && symbol.len() <= MAX_TICKER_LEN
+            && symbol.chars().all(|c| c.is_ascii_alphabetic());
+        if !valid {
+            continue;
+        }
+        let symbol = symbol.to_ascii_uppercase();
+        let is_ticker = if cashtag { true } else { word.chars().all(|c| c.is_ascii_uppercase()) && known.contains(&symbol) };
+        if is_ticker && !tickers.contains(&symbol) {
+            tickers.push(symbol);
+        }
+    }
+    tickers
+}
+
+/// Aggregates the mentions of `submissions` per ticker and hour.
+pub fn aggregate(submissions: &[Submission], known: &HashSet<String>) -> Vec<FMPMarketSentiment> {
+    let mut buckets: BTreeMap<(String, String), FMPMarketSentiment> = BTreeMap::new();
+    for submission in submissions {
+        let Some(created) = DateTime::from_timestamp(submission.created_utc as i64, 0) else {
+            continue;
+        };
+        let hour = created.format("%Y-%m-%d %H:00:00").to_string();
+        let text = format!("{} {}", submission.title, submission.selftext);
+        for ticker in extract_tickers(&text, known) {
+            let record = buckets
+                .entry((hour.clone(), ticker.clone()))
+                .or_insert_with(|| FMPMarketSentiment::reddit(&hour, &ticker));
+            record.reddit_posts = Some(record.reddit_posts.unwrap_or(0) + 1);
+            record.reddit_comments = Some(record.reddit_comments.unwrap_or(0) + submission.num_comments);
+            record.reddit_score = Some(record.reddit_score.unwrap_or(0) + submission.score);
+        }
+    }
+    buckets.into_values().collect()
+}
+
+pub struct RedditClient {
+    client: Arc<Client>,
+    config: Arc<ValueConfig>,
+    health: HealthTracker,
+    /// Creation time of the newest submission seen per subreddit, so polls do not count a post twice.
+    newest: StdMutex<HashMap<String, f64>>,
+}
+impl RedditClient {
+    pub fn new(client: Arc<Client>, config: Arc<ValueConfig>) -> Self {
+        Self { client, config, health: HealthTracker::default(), newest: StdMutex::new(HashMap::new()) }
+    }
+
+    fn known_tickers(&self) -> HashSet<String> {
+        self.config.reddit.tickers.iter().map(|t| t.to_ascii_uppercase()).collect()
+    }
+
+    /// Fetches the newest submissions of `subreddit`.
+    pub async fn new_submissions(&self, subreddit: &str) -> Result<Vec<Submission>, RedditError> {
+        let request_error = |source| RedditError::Request { subreddit: subreddit.to_string(), source };
+        let request = self.client
+            .get(format!("{}/{}/new.json", BASE_URL, subreddit))
+            .query(&[("limit", self.config.reddit.limit.to_string()), ("raw_json", "1".to_string())])
+            .header(reqwest::header::USER_AGENT, &self.config.reddit.user_agent);
+        let response = api_calls::execute(PROVIDER_NAME, request)
+            .await
+            .map_err(request_error)?;
+        if response.status() != StatusCode::OK {
+            return Err(RedditError::Status { subreddit: subreddit.to_string(), status: response.status() });
+        }
+        let (url, status) = (response.url().clone(), response.status());
+        let content_type = response.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let body = response.bytes().await.map_err(request_error)?;
+        captures::record(PROVIDER_NAME, &url, status, content_type.as_deref(), &body);
+        let listing: Listing = serde_json::from_slice(&body)
+            .map_err(|source| RedditError::Parse { subreddit: subreddit.to_string(), source })?;
+        Ok(listing.data.children.into_iter().filter(|t| t.kind == "t3").map(|t| t.data).collect())
+    }
+
+    /// Fetches the submissions posted since the previous call, across every subreddit.
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME))]
+    pub async fn fresh_submissions(&self) -> Result<Vec<Submission>, RedditError> {
+        let mut submissions = Vec::new();
+        let mut errors = Vec::new();
+        for subreddit in &self.config.reddit.subreddits {
+            match self.new_submissions(subreddit).await {
+                Ok(fetched) => {
+                    let mut newest = self.newest.lock().unwrap_or_else(|e| e.into_inner());
+                    let since = newest.get(subreddit).copied().unwrap_or(f64::MIN);
+                    let fresh: Vec<Submission> = fetched.into_iter().filter(|s| s.created_utc > since).collect();
+                    if let Some(latest) = fresh.iter().map(|s| s.created_utc).reduce(f64::max) {
+                        newest.insert(subreddit.clone(), latest);
+                    }
+                    debug!("r/{}: {} new submissions.", subreddit, fresh.len());
+                    submissions.extend(fresh);
+                }
+                Err(e) => {
+                    warn!("{}", e);
+                    errors.push(e.to_string());
+                }
+            }
+        }
+        if !errors.is_empty() && errors.len() == self.config.reddit.subreddits.len() {
+            return Err(RedditError::AllSubredditsFailed(errors.join(" | ")));
+        }
+        Ok(submissions)
+    }
+
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME, function = args["function"].as_str()))]
+    pub async fn poll(&self, args: Arc<Value>) -> Result<Value, RedditError> {
+        let submissions = match args.get(SUBREDDIT_MAP_KEY).and_then(Value::as_str) {
+            Some(subreddit) => self.new_submissions(subreddit).await?,
+            None => {
+                let mut all = Vec::new();
+                for subreddit in &self.config.reddit.subreddits {
+                    all.extend(self.new_submissions(subreddit).await?);
+                }
+                all
+            }
+        };
+        Ok(to_json(aggregate(&submissions, &self.known_tickers())))
+    }
+
+    /// Polls every subreddit every `reddit.poll_interval_secs` and stores the mentions, in a background task.
+    pub fn spawn_polling(self: Arc<Self>, db_ops: Arc<dyn Storage>) {
+        let interval = Duration::from_secs(self.config.reddit.poll_interval_secs.max(1));
+        tokio::spawn(async move {
+            loop {
+                async {
+                    let result = self.fresh_submissions().await;
+                    self.health.record(&result);
+                    match result {
+                        Ok(submissions) => {
+                            let records = aggregate(&submissions, &self.known_tickers());
+                            if !records.is_empty() {
+                                match db_ops.add_reddit_mentions(&records).await {
+                                    Ok(()) => info!("Stored {} Reddit mention records from {} submissions.", records.len(), submissions.len()),
+                                    Err(e) => error!("Error storing Reddit mentions: {}", e),
+                                }
+                            }
+                        }
+                        Err(e) => error!("{}", e),
+                    }
+                }
+                .instrument(logging::cycle_span(PROVIDER_NAME))
+                .await;
+                sleep(interval).await;
+            }
+        });
+    }
+}
+
+fn to_json(records: Vec<FMPMarketSentiment>) -> Value {
+    to_value(records).expect("Failed to convert sentiment records to JSON value")
+}
+
+impl NewsProvider for RedditClient {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn supports(&self, fetch_type: &FetchType) -> bool {
+        matches!(fetch_type, FetchType::Reddit)
+    }
+
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.poll(args).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.fresh_submissions().await;
+            self.health.record(&result);
+            Ok(to_json(aggregate(&result?, &self.known_tickers())))
+        })
+    }
+
+    fn health(&self) -> ProviderHealth {
+        if self.config.reddit.subreddits.is_empty() {
+            return ProviderHealth::Unavailable("No subreddit is configured".to_string());
+        }
+        self.health.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known() -> HashSet<String> {
+        ["AAPL", "TSLA"].iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn extracts_cashtags_and_known_tickers() {
+        let text = "YOLO on $gme and TSLA calls, CEO says AAPL is fine. Not aapl though. $GME again";
+        assert_eq!(extract_tickers(text, &known()), vec!["GME", "TSLA", "AAPL"]);
+    }
+
+    #[test]
+    fn aggregates_per_ticker_and_hour() {
+        let submission = |id: &str, created_utc: f64, title: &str| Submission {
+            id: id.to_string(),
+            subreddit: "stocks".to_string(),
+            title: title.to_string(),
+            selftext: String::new(),
+            author: None,
+            created_utc,
+            score: 10,
+            num_comments: 3,
+            permalink: None,
+        };
+        // 2025-01-06 14:00 UTC and one hour later.
+        let submissions = [
+            submission("a", 1_736_172_000.0, "TSLA earnings"),
+            submission("b", 1_736_172_600.0, "$TSLA to the moon"),
+            submission("c", 1_736_175_600.0, "TSLA again"),
+        ];
+        let records = aggregate(&submissions, &known());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].date.as_deref(), Some("2025-01-06 14:00:00"));
+        assert_eq!(records[0].symbol.as_deref(), Some("TSLA"));
+        assert_eq!(records[0].reddit_posts, Some(2));
+        assert_eq!(records[0].reddit_comments, Some(6));
+        assert_eq!(records[0].reddit_score, Some(20));
+        assert_eq!(records[1].reddit_posts, Some(1));
+    }
+
+    #[tokio::test]
+    async fn polls_of_the_same_hour_share_a_record() {
+        let storage = crate::storage::MemoryStorage::new();
+        let mention = |posts: u64| FMPMarketSentiment {
+            reddit_posts: Some(posts),
+            reddit_comments: Some(2),
+            reddit_score: Some(5),
+            ..FMPMarketSentiment::reddit("2025-01-06 14:00:00", "TSLA")
+        };
+        storage.add_reddit_mentions(&[mention(2)]).await.unwrap();
+        storage.add_reddit_mentions(&[mention(1)]).await.unwrap();
+
+        let stored = storage.social_sentiment();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].reddit_posts, Some(3));
+        assert_eq!(stored[0].reddit_comments, Some(4));
+        assert_eq!(stored[0].reddit_score, Some(10));
+    }
+}
@@ -0,0 +1,326 @@
+//! Interactive client of the websocket API, to try requests by hand.
+//!
+//! `news_data repl [--url ws://localhost:8080] [--token <token>]` connects to a running server and
+//! reads one command per line:
+//!
+//! ```text
+//! task <function> [{params}]          # e.g. task keyword_news {"keywords": ["nvidia"]}
+//! admin <command> [{params}]          # on the admin listeners only
+//! subscription <command> [{params}]
+//! session <command> [{params}]
+//! describe
+//! {"caller": {...}, "target": ...}    # a whole request, sent as is
+//! help | quit
+//! ```
+//!
+//! The shorthands are sent with a `repl` caller. Each request gets an `id`, which the response
+//! echoes, so the messages pushed meanwhile (polling results, subscribed articles) are told apart:
+//! they are printed as they come, before the response. Every message is pretty-printed.
+//!
+//! Tab completes the targets, their commands and the task functions registered on the server,
+//! listed by a `describe` request once connected.
+
+use std::time::Duration;
+
+use async_tungstenite::tokio::{connect_async, ConnectStream};
+use async_tungstenite::tungstenite::client::IntoClientRequest;
+use async_tungstenite::tungstenite::http::HeaderValue;
+use async_tungstenite::tungstenite::Message;
+use async_tungstenite::WebSocketStream;
+use futures::{FutureExt, SinkExt, StreamExt};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::request_parser::params::{AdminCommand, SessionCommand, SubscriptionCommand};
+
+/// Time waited for the response to a request.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+const HELP: &str = "\
+task <function> [{params}]          runs a registered function, e.g. task keyword_news {\"keywords\": [\"nvidia\"]}
+admin <command> [{params}]          manages the server, on the admin listeners
+subscription <command> [{params}]   subscribe or unsubscribe
+session <command> [{params}]        set, get or clear the session filters
+describe                            lists the registered functions
+{...}                               sends a whole request
+help                                prints this help
+quit                                closes the connection";
+
+#[derive(Debug, Error)]
+pub enum ReplError {
+    #[error("Failed to connect to {0}: {1}")]
+    Connect(String, String),
+
+    #[error("Websocket error: {0}")]
+    Websocket(String),
+
+    #[error("Connection closed by the server")]
+    Closed,
+
+    #[error("Failed to read the input: {0}")]
+    Input(String),
+}
+
+/// A line typed at the prompt.
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    Empty,
+    Help,
+    Quit,
+    Request(Value),
+}
+
+/// Targets and commands of the first word.
+const TARGETS: [&str; 7] = ["task", "admin", "subscription", "session", "describe", "help", "quit"];
+
+fn request(target: &str, args: Value) -> Value {
+    json!({
+        "caller": { "id": "repl", "ipaddr": "127.0.0.1", "queue": 0, "status": 0, "mode": "sync" },
+        "target": target,
+        "args": args,
+    })
+}
+
+/// `params` given after a command, an empty object without any.
+fn params(rest: &str) -> Result<Value, String> {
+    if rest.is_empty() {
+        return Ok(json!({}));
+    }
+    match serde_json::from_str(rest) {
+        Ok(Value::Object(params)) => Ok(Value::Object(params)),
+        Ok(other) => Err(format!("The params must be an object, not `{}`", other)),
+        Err(e) => Err(format!("Invalid params: {}", e)),
+    }
+}
+
+/// Splits the first word of `line` from the rest.
+fn split_word(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+/// Reads a line typed at the prompt.
+pub fn parse(line: &str) -> Result<Input, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(Input::Empty);
+    }
+    if line.starts_with('{') {
+        return match serde_json::from_str(line) {
+            Ok(Value::Object(request)) => Ok(Input::Request(Value::Object(request))),
+            Ok(_) => Err("A request must be an object".to_string()),
+            Err(e) => Err(format!("Invalid request: {}", e)),
+        };
+    }
+    let (target, rest) = split_word(line);
+    match target {
+        "help" => Ok(Input::Help),
+        "quit" | "exit" => Ok(Input::Quit),
+        "describe" => Ok(Input::Request(request("describe", json!({})))),
+        "task" => {
+            let (function, rest) = split_word(rest);
+            if function.is_empty() {
+                return Err("Usage: task <function> [{params}]".to_string());
+            }
+            let args = json!({
+                "function": "aggregated_polling",
+                "count": "single",
+                "look_for": { "where_": function },
+                "params": params(rest)?,
+            });
+            Ok(Input::Request(request("task", args)))
+        }
+        "admin" | "subscription" | "session" => {
+            let (command, rest) = split_word(rest);
+            if command.is_empty() {
+                return Err(format!("Usage: {} <command> [{{params}}]", target));
+            }
+            Ok(Input::Request(request(target, json!({ "command": command, "params": params(rest)? }))))
+        }
+        other => Err(format!("Unknown command `{}`, see `help`", other)),
+    }
+}
+
+/// Completes the commands typed, knowing the task `functions` of the server.
+pub struct ReplHelper {
+    functions: Vec<String>,
+}
+impl ReplHelper {
+    pub fn new(functions: Vec<String>) -> Self {
+        Self { functions }
+    }
+
+    /// Start of the word before `pos` and the words it may be completed with.
+    pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let previous: Vec<&str> = before[..start].split_whitespace().collect();
+        let words: Vec<String> = match previous.as_slice() {
+            [] => TARGETS.iter().map(|t| t.to_string()).collect(),
+            ["task"] => self.functions.clone(),
+            ["admin"] => AdminCommand::ALL.iter().map(|c| c.to_str().to_string()).collect(),
+            ["subscription"] => SubscriptionCommand::ALL.iter().map(|c| c.to_str().to_string()).collect(),
+            ["session"] => SessionCommand::ALL.iter().map(|c| c.to_str().to_string()).collect(),
+            _ => Vec::new(),
+        };
+        (start, words.into_iter().filter(|w| w.starts_with(word)).collect())
+    }
+}
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+impl Highlighter for ReplHelper {}
+impl Validator for ReplHelper {}
+impl Helper for ReplHelper {}
+
+/// Connection to the server.
+struct Client {
+    ws: WebSocketStream<ConnectStream>,
+    requests: u64,
+}
+impl Client {
+    async fn connect(url: &str, token: Option<&str>) -> Result<Self, ReplError> {
+        let connect_error = |e: String| ReplError::Connect(url.to_string(), e);
+        let mut request = url.into_client_request().map_err(|e| connect_error(e.to_string()))?;
+        if let Some(token) = token {
+            let header = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|e| connect_error(e.to_string()))?;
+            request.headers_mut().insert("authorization", header);
+        }
+        let (ws, _) = connect_async(request).await.map_err(|e| connect_error(e.to_string()))?;
+        Ok(Self { ws, requests: 0 })
+    }
+
+    /// Next text message, parsed when it is JSON.
+    async fn receive(&mut self) -> Result<Value, ReplError> {
+        loop {
+            match self.ws.next().await {
+                Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text).unwrap_or(Value::String(text))),
+                Some(Ok(Message::Close(_))) | None => return Err(ReplError::Closed),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(ReplError::Websocket(e.to_string())),
+            }
+        }
+    }
+
+    /// Prints the messages pushed since the last request.
+    async fn print_pushed(&mut self) -> Result<(), ReplError> {
+        while let Some(message) = self.receive().now_or_never() {
+            print_message("pushed", &message?);
+        }
+        Ok(())
+    }
+
+    /// Sends `request` and waits for its response, printing the messages pushed meanwhile.
+    async fn call(&mut self, mut request: Value) -> Result<Option<Value>, ReplError> {
+        self.requests += 1;
+        let id = Value::String(format!("repl-{}", self.requests));
+        request["id"] = id.clone();
+        self.ws.send(Message::Text(request.to_string())).await.map_err(|e| ReplError::Websocket(e.to_string()))?;
+        let wait = async {
+            loop {
+                let message = self.receive().await?;
+                if message.get("id") == Some(&id) {
+                    return Ok(message);
+                }
+                print_message("pushed", &message);
+            }
+        };
+        match tokio::time::timeout(RESPONSE_TIMEOUT, wait).await {
+            Ok(response) => response.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+fn print_message(label: &str, message: &Value) {
+    println!("<- {}\n{}", label, serde_json::to_string_pretty(message).unwrap_or_default());
+}
+
+/// Names of the task functions listed by a `describe` response.
+fn function_names(response: &Value) -> Vec<String> {
+    response["message"]["functions"]
+        .as_array()
+        .map(|functions| functions.iter().filter_map(|f| f["name"].as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+/// Connects to `url` and runs the prompt until `quit` or the end of the input.
+pub async fn run(url: &str, token: Option<&str>) -> Result<(), ReplError> {
+    let mut client = Client::connect(url, token).await?;
+    let functions = match client.call(request("describe", json!({}))).await? {
+        Some(response) => function_names(&response),
+        None => Vec::new(),
+    };
+    println!("Connected to {}, {} functions registered. Type `help` for the commands.", url, functions.len());
+
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::new().map_err(|e| ReplError::Input(e.to_string()))?;
+    editor.set_helper(Some(ReplHelper::new(functions)));
+    loop {
+        client.print_pushed().await?;
+        let line = match tokio::task::block_in_place(|| editor.readline("news_data> ")) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(ReplError::Input(e.to_string())),
+        };
+        let _ = editor.add_history_entry(line.as_str());
+        match parse(&line) {
+            Ok(Input::Empty) => {}
+            Ok(Input::Help) => println!("{}", HELP),
+            Ok(Input::Quit) => break,
+            Ok(Input::Request(request)) => match client.call(request).await? {
+                Some(response) => print_message("response", &response),
+                None => println!("No response within {}s", RESPONSE_TIMEOUT.as_secs()),
+            },
+            Err(e) => println!("{}", e),
+        }
+    }
+    let _ = client.ws.close(None).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_shorthands_and_completes_them() {
+        let Ok(Input::Request(task)) = parse(r#"task keyword_news {"keywords": ["nvidia"]}"#) else {
+            panic!("task not read");
+        };
+        assert_eq!((task["target"].clone(), task["args"]["look_for"]["where_"].clone()), (json!("task"), json!("keyword_news")));
+        assert_eq!(task["args"]["params"], json!({ "keywords": ["nvidia"] }));
+        let Ok(Input::Request(admin)) = parse("admin stats") else {
+            panic!("admin not read");
+        };
+        assert_eq!(admin["args"], json!({ "command": "stats", "params": {} }));
+        assert_eq!(parse(r#"{"target": "describe"}"#), Ok(Input::Request(json!({ "target": "describe" }))));
+        assert_eq!(parse("  "), Ok(Input::Empty));
+        assert!(parse("task").is_err());
+        assert!(parse("session set [1]").is_err());
+        assert!(parse("delete everything").is_err());
+
+        let helper = ReplHelper::new(vec!["keyword_news".to_string(), "mock_news_polling".to_string()]);
+        assert_eq!(helper.candidates("se", 2), (0, vec!["session".to_string()]));
+        assert_eq!(helper.candidates("task k", 6), (5, vec!["keyword_news".to_string()]));
+        assert_eq!(helper.candidates("admin set_", 10), (6, vec!["set_log_level".to_string()]));
+        assert_eq!(helper.candidates("session ", 8).1, ["set", "get", "clear"]);
+        assert!(helper.candidates("task keyword_news ", 18).1.is_empty());
+    }
+}
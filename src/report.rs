@@ -0,0 +1,485 @@
+//! Daily and weekly reports of the stored news.
+//!
+//! A report covers the last day or week and compares it with the period before, for each ticker of
+//! the watchlist (`report.tickers`, or the `--ticker` flags):
+//! - top stories: the stories covered by the most articles, then the most recent;
+//! - sentiment shifts: the mean sentiment score against the previous period, listed when it moves
+//!   by more than `report.shift_threshold`;
+//! - volume anomalies: an article count `report.anomaly_ratio` times above (spike) or below (drop)
+//!   the previous period, ignored under `report.min_articles` articles.
+//!
+//! Reports are rendered in Markdown or HTML and written to stdout or to `--output`, ready to be
+//! committed to a notes repository or sent by email.
+//!
+//! ## Usage
+//!
+//! ```text
+//! news_data report [--period daily|weekly] [--format markdown|html] [--ticker SYMBOL]... [--output FILE]
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration as UtcDuration, SecondsFormat, Utc};
+use thiserror::Error;
+
+use crate::clock;
+use crate::config::ReportConfig;
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+use crate::sentiment::SentimentLabel;
+use crate::storage::Storage;
+
+#[derive(Debug, Error)]
+pub enum ReportError {
+    #[error("Invalid arguments: {0}")]
+    Usage(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportPeriod {
+    Daily,
+    Weekly,
+}
+impl ReportPeriod {
+    pub fn to_str(&self) -> &str {
+        match self {
+            ReportPeriod::Daily => "daily",
+            ReportPeriod::Weekly => "weekly",
+        }
+    }
+
+    pub fn duration(&self) -> UtcDuration {
+        match self {
+            ReportPeriod::Daily => UtcDuration::days(1),
+            ReportPeriod::Weekly => UtcDuration::weeks(1),
+        }
+    }
+}
+impl std::str::FromStr for ReportPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "daily" => Ok(ReportPeriod::Daily),
+            "weekly" => Ok(ReportPeriod::Weekly),
+            _ => Err(format!("Unknown period `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+impl ReportFormat {
+    pub fn to_str(&self) -> &str {
+        match self {
+            ReportFormat::Markdown => "markdown",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(format!("Unknown format `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportRequest {
+    pub period: ReportPeriod,
+    pub format: ReportFormat,
+    /// Watchlist, `report.tickers` when no `--ticker` is given.
+    pub tickers: Vec<String>,
+    /// Written to stdout when missing.
+    pub output: Option<PathBuf>,
+}
+impl ReportRequest {
+    /// Request a report of `tickers`, the `report.tickers` watchlist when empty.
+    pub fn new(
+        period: ReportPeriod,
+        format: ReportFormat,
+        tickers: &[String],
+        output: Option<PathBuf>,
+        config: &ReportConfig,
+    ) -> Result<Self, ReportError> {
+        let tickers = if tickers.is_empty() { &config.tickers } else { tickers };
+        let tickers: Vec<String> = tickers.iter().map(|t| t.to_uppercase()).collect();
+        if tickers.is_empty() {
+            return Err(ReportError::Usage("No ticker: set `report.tickers` or pass `--ticker`".to_string()));
+        }
+        Ok(Self { period, format, tickers, output })
+    }
+}
+
+/// A story and the number of articles covering it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Story {
+    pub title: String,
+    pub url: Option<String>,
+    pub source: Option<String>,
+    pub published_at: Option<String>,
+    pub sentiment: Option<SentimentLabel>,
+    pub coverage: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeAnomaly {
+    Spike,
+    Drop,
+}
+impl VolumeAnomaly {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            VolumeAnomaly::Spike => "spike",
+            VolumeAnomaly::Drop => "drop",
+        }
+    }
+}
+
+/// Section of a report about one ticker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickerReport {
+    pub ticker: String,
+    pub articles: usize,
+    pub previous_articles: usize,
+    /// Mean sentiment score, `None` without scored articles.
+    pub sentiment: Option<f64>,
+    pub previous_sentiment: Option<f64>,
+    pub anomaly: Option<VolumeAnomaly>,
+    pub top_stories: Vec<Story>,
+}
+impl TickerReport {
+    pub fn sentiment_shift(&self) -> Option<f64> {
+        Some(self.sentiment? - self.previous_sentiment?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub period: ReportPeriod,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub tickers: Vec<TickerReport>,
+    /// Tickers whose sentiment moved by more than `report.shift_threshold`, largest move first.
+    pub shifts: Vec<(String, f64)>,
+}
+
+fn mean_sentiment(articles: &[&NormalizedArticle]) -> Option<f64> {
+    let scores: Vec<f64> = articles.iter().filter_map(|a| a.sentiment_score).collect();
+    (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64)
+}
+
+/// Compares the article counts of two consecutive periods.
+pub fn volume_anomaly(current: usize, previous: usize, config: &ReportConfig) -> Option<VolumeAnomaly> {
+    let (current_f, previous_f) = (current as f64, previous as f64);
+    if current >= config.min_articles && current_f >= previous_f.max(1.0) * config.anomaly_ratio {
+        Some(VolumeAnomaly::Spike)
+    } else if previous >= config.min_articles && current_f * config.anomaly_ratio <= previous_f {
+        Some(VolumeAnomaly::Drop)
+    } else {
+        None
+    }
+}
+
+/// Groups `articles` by title and keeps the `limit` most covered stories, the most recent first on ties.
+pub fn top_stories(articles: &[&NormalizedArticle], limit: usize) -> Vec<Story> {
+    let mut stories: HashMap<String, Story> = HashMap::new();
+    for article in articles {
+        let Some(title) = article.title.as_deref().map(str::trim).filter(|t| !t.is_empty()) else {
+            continue;
+        };
+        let story = stories.entry(title.to_lowercase()).or_insert_with(|| Story {
+            title: title.to_string(),
+            url: article.url.clone(),
+            source: article.source.clone(),
+            published_at: article.published_at.clone(),
+            sentiment: article.sentiment,
+            coverage: 0,
+        });
+        story.coverage += 1;
+    }
+    let mut stories: Vec<Story> = stories.into_values().collect();
+    stories.sort_by(|a, b| {
+        b.coverage
+            .cmp(&a.coverage)
+            .then_with(|| b.published_at.cmp(&a.published_at))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    stories.truncate(limit);
+    stories
+}
+
+impl Report {
+    /// Builds the report of `[from, to]` from the watchlist articles published between
+    /// `from - period` and `to`.
+    pub fn build(
+        period: ReportPeriod,
+        to: DateTime<Utc>,
+        tickers: &[String],
+        articles: &[NormalizedArticle],
+        config: &ReportConfig,
+    ) -> Self {
+        let from = to - period.duration();
+        let previous_from = from - period.duration();
+        let in_window = |article: &NormalizedArticle, start: DateTime<Utc>, end: DateTime<Utc>| {
+            article
+                .published_at
+                .as_deref()
+                .and_then(parse_timestamp)
+                .is_some_and(|t| t >= start && t < end)
+        };
+
+        let mut reports = Vec::new();
+        for ticker in tickers {
+            let about: Vec<&NormalizedArticle> = articles
+                .iter()
+                .filter(|a| a.tickers.iter().any(|t| t.eq_ignore_ascii_case(ticker)))
+                .collect();
+            let current: Vec<&NormalizedArticle> = about.iter().copied().filter(|a| in_window(a, from, to)).collect();
+            let previous: Vec<&NormalizedArticle> =
+                about.iter().copied().filter(|a| in_window(a, previous_from, from)).collect();
+            reports.push(TickerReport {
+                ticker: ticker.clone(),
+                articles: current.len(),
+                previous_articles: previous.len(),
+                sentiment: mean_sentiment(&current),
+                previous_sentiment: mean_sentiment(&previous),
+                anomaly: volume_anomaly(current.len(), previous.len(), config),
+                top_stories: top_stories(&current, config.top_stories),
+            });
+        }
+
+        let mut shifts: Vec<(String, f64)> = reports
+            .iter()
+            .filter_map(|r| r.sentiment_shift().map(|shift| (r.ticker.clone(), shift)))
+            .filter(|(_, shift)| shift.abs() > config.shift_threshold)
+            .collect();
+        shifts.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+
+        Self { period, from, to, tickers: reports, shifts }
+    }
+
+    fn title(&self) -> String {
+        let title = match self.period {
+            ReportPeriod::Daily => "Daily news report",
+            ReportPeriod::Weekly => "Weekly news report",
+        };
+        format!(
+            "{}: {} to {}",
+            title,
+            self.from.to_rfc3339_opts(SecondsFormat::Secs, true),
+            self.to.to_rfc3339_opts(SecondsFormat::Secs, true)
+        )
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.to_markdown(),
+            ReportFormat::Html => self.to_html(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# {}\n", self.title());
+        let _ = writeln!(out, "| Ticker | Articles | Previous | Sentiment | Shift | Volume |");
+        let _ = writeln!(out, "|---|---:|---:|---:|---:|---|");
+        for r in &self.tickers {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                r.ticker,
+                r.articles,
+                r.previous_articles,
+                format_score(r.sentiment),
+                format_signed(r.sentiment_shift()),
+                r.anomaly.map(|a| a.to_str()).unwrap_or("-")
+            );
+        }
+
+        let _ = writeln!(out, "\n## Sentiment shifts\n");
+        if self.shifts.is_empty() {
+            let _ = writeln!(out, "No significant shift.");
+        }
+        for (ticker, shift) in &self.shifts {
+            let _ = writeln!(out, "- **{}**: {}", ticker, format_signed(Some(*shift)));
+        }
+
+        for r in &self.tickers {
+            let _ = writeln!(out, "\n## {}\n", r.ticker);
+            if r.top_stories.is_empty() {
+                let _ = writeln!(out, "No story.");
+            }
+            for story in &r.top_stories {
+                let title = markdown_escape(&story.title);
+                let line = match &story.url {
+                    Some(url) => format!("[{}]({})", title, url),
+                    None => title,
+                };
+                let _ = writeln!(out, "- {}{}", line, story_details(story));
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        let title = html_escape(&self.title());
+        let _ = writeln!(out, "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>", title);
+        let _ = writeln!(out, "<h1>{}</h1>", title);
+        let _ = writeln!(out, "<table>\n<tr><th>Ticker</th><th>Articles</th><th>Previous</th><th>Sentiment</th><th>Shift</th><th>Volume</th></tr>");
+        for r in &self.tickers {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                html_escape(&r.ticker),
+                r.articles,
+                r.previous_articles,
+                format_score(r.sentiment),
+                format_signed(r.sentiment_shift()),
+                r.anomaly.map(|a| a.to_str()).unwrap_or("-")
+            );
+        }
+        let _ = writeln!(out, "</table>\n<h2>Sentiment shifts</h2>");
+        if self.shifts.is_empty() {
+            let _ = writeln!(out, "<p>No significant shift.</p>");
+        } else {
+            let _ = writeln!(out, "<ul>");
+            for (ticker, shift) in &self.shifts {
+                let _ = writeln!(out, "<li><strong>{}</strong>: {}</li>", html_escape(ticker), format_signed(Some(*shift)));
+            }
+            let _ = writeln!(out, "</ul>");
+        }
+
+        for r in &self.tickers {
+            let _ = writeln!(out, "<h2>{}</h2>", html_escape(&r.ticker));
+            if r.top_stories.is_empty() {
+                let _ = writeln!(out, "<p>No story.</p>");
+                continue;
+            }
+            let _ = writeln!(out, "<ul>");
+            for story in &r.top_stories {
+                let title = html_escape(&story.title);
+                let line = match &story.url {
+                    Some(url) => format!("<a href=\"{}\">{}</a>", html_escape(url), title),
+                    None => title,
+                };
+                let _ = writeln!(out, "<li>{}{}</li>", line, html_escape(&story_details(story)));
+            }
+            let _ = writeln!(out, "</ul>");
+        }
+        let _ = writeln!(out, "</body>\n</html>");
+        out
+    }
+}
+
+/// Source, sentiment and coverage of a story, e.g. ` (reuters.com, bullish, 3 articles)`.
+fn story_details(story: &Story) -> String {
+    let mut details: Vec<String> = Vec::new();
+    details.extend(story.source.clone());
+    details.extend(story.sentiment.map(|s| s.to_str().to_string()));
+    if story.coverage > 1 {
+        details.push(format!("{} articles", story.coverage));
+    }
+    if details.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", details.join(", "))
+    }
+}
+
+fn format_score(score: Option<f64>) -> String {
+    score.map(|s| format!("{:.2}", s)).unwrap_or_else(|| "-".to_string())
+}
+
+fn format_signed(score: Option<f64>) -> String {
+    score.map(|s| format!("{:+.2}", s)).unwrap_or_else(|| "-".to_string())
+}
+
+fn markdown_escape(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Builds the report of the period ending now and renders it.
+pub async fn generate(db: &dyn Storage, request: &ReportRequest, config: &ReportConfig) -> Result<String, ReportError> {
+    let to = clock::now();
+    let since = to - request.period.duration() * 2;
+    let articles = db
+        .articles_with_tickers(
+            &request.tickers,
+            &since.to_rfc3339_opts(SecondsFormat::Secs, true),
+            &to.to_rfc3339_opts(SecondsFormat::Secs, true),
+        )
+        .await
+        .map_err(|e| ReportError::Database(e.to_string()))?;
+    let report = Report::build(request.period, to, &request.tickers, &articles, config);
+    Ok(report.render(request.format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_with_the_previous_period() {
+        let to = parse_timestamp("2024-05-02T00:00:00Z").unwrap();
+        let article = |id: &str, title: &str, published_at: &str, score: f64| {
+            NormalizedArticle::test(id)
+                .provider("marketaux")
+                .url(&format!("https://example.com/{}", id))
+                .title(title)
+                .source("example.com")
+                .published_at(published_at)
+                .tickers(&["AAPL"])
+                .sentiment_score(score)
+        };
+        let articles = vec![
+            article("a", "Apple beats estimates", "2024-05-01T10:00:00Z", 0.6),
+            article("b", "Apple Beats Estimates", "2024-05-01T12:00:00Z", 0.4),
+            article("c", "Apple opens store", "2024-05-01T15:00:00Z", 0.2),
+            article("d", "Apple faces probe", "2024-04-30T09:00:00Z", -0.3),
+        ];
+        let config = ReportConfig { min_articles: 3, ..ReportConfig::default() };
+        let report = Report::build(ReportPeriod::Daily, to, &["AAPL".to_string()], &articles, &config);
+
+        let aapl = &report.tickers[0];
+        assert_eq!((aapl.articles, aapl.previous_articles), (3, 1));
+        assert_eq!(aapl.anomaly, Some(VolumeAnomaly::Spike));
+        assert_eq!(aapl.top_stories[0].title, "Apple beats estimates");
+        assert_eq!(aapl.top_stories[0].coverage, 2);
+        assert_eq!(report.shifts.len(), 1);
+        assert!((report.shifts[0].1 - 0.7).abs() < 1e-9);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("| AAPL | 3 | 1 | 0.40 | +0.70 | spike |"));
+        assert!(markdown.contains("- [Apple beats estimates](https://example.com/a) (example.com, 2 articles)"));
+        assert!(report.to_html().contains("<td>AAPL</td>"));
+    }
+
+    #[test]
+    fn flags_volume_drops_above_the_minimum() {
+        let config = ReportConfig::default();
+        assert_eq!(volume_anomaly(2, 10, &config), Some(VolumeAnomaly::Drop));
+        assert_eq!(volume_anomaly(0, 2, &config), None);
+        assert_eq!(volume_anomaly(6, 5, &config), None);
+    }
+}
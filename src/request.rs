@@ -7,6 +7,11 @@ use tracing::{info, error};
 use tracing_subscriber;
 
 use crate::config::ValueConfig;
+use crate::chaos;
+use crate::api_calls;
+use crate::encoding::decode_json_response;
+use crate::fixtures;
+use crate::errors::ApiError;
 use crate::logging::{LogLevel, Logger};
 
 #[derive(Debug, Clone)]
@@ -15,24 +20,25 @@ pub struct HTTPClient {
     headers: HashMap<String, String>,
     base_url_v3: String,
     base_url_v4: String,
+    base_url_stable: String,
     config: ValueConfig,
 }
 
-const BASE_URL_V3: &str = "https://financialmodelingprep.com/api/v3/";
-const BASE_URL_V4: &str = "https://financialmodelingprep.com/api/v4/";
 const MAX_CLIENT_POOL_SIZE: usize = 1024;
 
 impl HTTPClient {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
         //Logger::init(LogLevel::Trace);
+        let config = ValueConfig::new()?;
         Ok(Self {
             client: Arc::new(Client::builder()
             .pool_max_idle_per_host(MAX_CLIENT_POOL_SIZE)
             .build()?),
             headers: HashMap::new(),
-            base_url_v3: BASE_URL_V3.to_string(),
-            base_url_v4: BASE_URL_V4.to_string(),
-            config: ValueConfig::new()?,
+            base_url_v3: config.fmp.base_url_v3.clone(),
+            base_url_v4: config.fmp.base_url_v4.clone(),
+            base_url_stable: config.fmp.base_url_stable.clone(),
+            config,
         })
     }
 
@@ -70,46 +76,50 @@ impl HTTPClient {
         self.headers.insert(key.to_string(), value.to_string());
     }
 
-    pub async fn get_v3(&self, url: &str, query_params: Option<Vec<(String, String)>>) -> Result<Value, reqwest::Error> {
+    pub async fn get_v3(&self, url: &str, query_params: Option<Vec<(String, String)>>) -> Result<Value, ApiError> {
         info!(
             name: "running",
             target: "v3 http request",
             query = format!("{:?}",query_params),
         );
-        let url = format!("{}/{}", self.base_url_v3.trim_end_matches("/"), url.trim_start_matches("/"));
-
-        if let Some(query_params) = query_params {
-            let query_params = self.build_query(query_params);
-            let response = self.client.get(&url).query(&query_params).send().await?.json().await?;
-            Ok(response)
-        }
-        else {
-            let response = self.client.get(&url)
-                .query(&vec![("apikey".to_string(), self.config.api.fmp.clone())])
-                .send().await?.json().await?;
-            Ok(response)
-        }
-        
+        self.get(&self.base_url_v3, url, query_params).await
     }
 
-    pub async fn get_v4(&self, url: &str, query_params: Option<Vec<(String, String)>>) -> Result<Value, reqwest::Error> {
+    pub async fn get_v4(&self, url: &str, query_params: Option<Vec<(String, String)>>) -> Result<Value, ApiError> {
         info!(
             name: "running",
             target: "v4 http request",
             query = format!("{:?}",query_params),
         );
-        let url = format!("{}/{}", self.base_url_v4.trim_end_matches("/"), url.trim_start_matches("/"));
+        self.get(&self.base_url_v4, url, query_params).await
+    }
+
+    pub async fn get_stable(&self, url: &str, query_params: Option<Vec<(String, String)>>) -> Result<Value, ApiError> {
+        info!(
+            name: "running",
+            target: "stable http request",
+            query = format!("{:?}",query_params),
+        );
+        self.get(&self.base_url_stable, url, query_params).await
+    }
+
+    async fn get(&self, base_url: &str, url: &str, query_params: Option<Vec<(String, String)>>) -> Result<Value, ApiError> {
+        let endpoint = url;
+        let url = format!("{}/{}", base_url.trim_end_matches("/"), url.trim_start_matches("/"));
 
         if let Some(query_params) = query_params {
             let query_params = self.build_query(query_params);
-            let response = self.client.get(&url).query(&query_params).send().await?.json().await?;
-            Ok(response)
+            let response = api_calls::send("fmp", self.client.get(&url).query(&query_params)).await?;
+            let body = decode_json_response("fmp", response).await?;
+            fixtures::record("fmp", endpoint, &body);
+            Ok(chaos::response_body("fmp", body))
         }
         else {
-            let response = self.client.get(&url)
-                .query(&vec![("apikey".to_string(), self.config.api.fmp.clone())])
-                .send().await?.json().await?;
-            Ok(response)
+            let request = self.client.get(&url).query(&vec![("apikey".to_string(), self.config.api.fmp.clone())]);
+            let response = api_calls::send("fmp", request).await?;
+            let body = decode_json_response("fmp", response).await?;
+            fixtures::record("fmp", endpoint, &body);
+            Ok(chaos::response_body("fmp", body))
         }
     }
-}
\ No newline at end of file
+}
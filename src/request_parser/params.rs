@@ -37,9 +37,19 @@
 //!
 //! - `ObjectCount`: Indicates whether the database operation involves a single object or multiple objects.
 //!
-//! - `TargetService`: Identifies the target service for the request, either `Database` or `Task`.
+//! - `AdminCommand`: Lists the operator commands, such as `Maintenance` or `RedactArticle`.
 //!
-//! - `Args`: A wrapper enumeration that can hold either `DatabaseArgs` or `TaskArgs`.
+//! - `SubscriptionCommand`: Starts, ends or resumes live article subscriptions, or acknowledges
+//!   their articles: `Subscribe`, `Unsubscribe`, `Resume`, `Ack` or `Parked`.
+//!
+//! - `SessionCommand`: Sets, reads or clears the saved filters of the connection: `Set`, `Get` or
+//!   `Clear`.
+//!
+//! - `TargetService`: Identifies the target service for the request: `Database`, `Task`, `Admin`,
+//!   `Subscription`, `Session` or `Describe`, which lists the task functions.
+//!
+//! - `Args`: A wrapper enumeration that can hold either `DatabaseArgs`, `TaskArgs`, `AdminArgs`,
+//!   `SubscriptionArgs` or `SessionArgs`.
 //!
 //! This module leverages the `serde` crate for serialization and deserialization of the defined
 //! structures and enumerations, facilitating easy conversion to and from JSON format.
@@ -253,11 +263,198 @@ impl DatabaseArgs {
 }
 // ************* Database *************** | END
 
+// ************* Admin *************** | START
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AdminCommand {
+    Maintenance,
+    DeleteArticle,
+    RedactArticle,
+    Purge,
+    Pressure,
+    Backfill,
+    Alerts,
+    AckAlert,
+    ResolveAlert,
+    MuteAlerts,
+    UnmuteAlerts,
+    Exports,
+    IssueToken,
+    Status,
+    CacheFlush,
+    Stats,
+    ReloadConfig,
+    SetLogLevel,
+    Unknown,
+}
+impl AdminCommand {
+    pub const ALL: [AdminCommand; 18] = [
+        AdminCommand::Maintenance, AdminCommand::DeleteArticle, AdminCommand::RedactArticle, AdminCommand::Purge,
+        AdminCommand::Pressure, AdminCommand::Backfill, AdminCommand::Alerts, AdminCommand::AckAlert,
+        AdminCommand::ResolveAlert, AdminCommand::MuteAlerts, AdminCommand::UnmuteAlerts, AdminCommand::Exports,
+        AdminCommand::IssueToken, AdminCommand::Status, AdminCommand::CacheFlush, AdminCommand::Stats,
+        AdminCommand::ReloadConfig, AdminCommand::SetLogLevel,
+    ];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            AdminCommand::Maintenance => "maintenance",
+            AdminCommand::DeleteArticle => "delete_article",
+            AdminCommand::RedactArticle => "redact_article",
+            AdminCommand::Purge => "purge",
+            AdminCommand::Pressure => "pressure",
+            AdminCommand::Backfill => "backfill",
+            AdminCommand::Alerts => "alerts",
+            AdminCommand::AckAlert => "ack_alert",
+            AdminCommand::ResolveAlert => "resolve_alert",
+            AdminCommand::MuteAlerts => "mute_alerts",
+            AdminCommand::UnmuteAlerts => "unmute_alerts",
+            AdminCommand::Exports => "exports",
+            AdminCommand::IssueToken => "issue_token",
+            AdminCommand::Status => "status",
+            AdminCommand::CacheFlush => "cache_flush",
+            AdminCommand::Stats => "stats",
+            AdminCommand::ReloadConfig => "reload_config",
+            AdminCommand::SetLogLevel => "set_log_level",
+            AdminCommand::Unknown => "unknown",
+        }
+    }
+}
+impl std::str::FromStr for AdminCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "maintenance" => Ok(AdminCommand::Maintenance),
+            "delete_article" => Ok(AdminCommand::DeleteArticle),
+            "redact_article" => Ok(AdminCommand::RedactArticle),
+            "purge" => Ok(AdminCommand::Purge),
+            "pressure" => Ok(AdminCommand::Pressure),
+            "backfill" => Ok(AdminCommand::Backfill),
+            "alerts" => Ok(AdminCommand::Alerts),
+            "ack_alert" => Ok(AdminCommand::AckAlert),
+            "resolve_alert" => Ok(AdminCommand::ResolveAlert),
+            "mute_alerts" => Ok(AdminCommand::MuteAlerts),
+            "unmute_alerts" => Ok(AdminCommand::UnmuteAlerts),
+            "exports" => Ok(AdminCommand::Exports),
+            "issue_token" => Ok(AdminCommand::IssueToken),
+            "status" => Ok(AdminCommand::Status),
+            "cache_flush" => Ok(AdminCommand::CacheFlush),
+            "stats" => Ok(AdminCommand::Stats),
+            "reload_config" => Ok(AdminCommand::ReloadConfig),
+            "set_log_level" => Ok(AdminCommand::SetLogLevel),
+            _ => Err(format!("Unknown admin command `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminArgs {
+    pub command: AdminCommand,
+    pub params: Option<HashMap<String, Value>>
+}
+// ************* Admin *************** | END
+
+// ************* Subscription *************** | START
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SubscriptionCommand {
+    Subscribe,
+    Unsubscribe,
+    Resume,
+    Ack,
+    Parked,
+    Unknown,
+}
+impl SubscriptionCommand {
+    pub const ALL: [SubscriptionCommand; 5] = [
+        SubscriptionCommand::Subscribe,
+        SubscriptionCommand::Unsubscribe,
+        SubscriptionCommand::Resume,
+        SubscriptionCommand::Ack,
+        SubscriptionCommand::Parked,
+    ];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            SubscriptionCommand::Subscribe => "subscribe",
+            SubscriptionCommand::Unsubscribe => "unsubscribe",
+            SubscriptionCommand::Resume => "resume",
+            SubscriptionCommand::Ack => "ack",
+            SubscriptionCommand::Parked => "parked",
+            SubscriptionCommand::Unknown => "unknown",
+        }
+    }
+}
+impl std::str::FromStr for SubscriptionCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "subscribe" => Ok(SubscriptionCommand::Subscribe),
+            "unsubscribe" => Ok(SubscriptionCommand::Unsubscribe),
+            "resume" => Ok(SubscriptionCommand::Resume),
+            "ack" => Ok(SubscriptionCommand::Ack),
+            "parked" => Ok(SubscriptionCommand::Parked),
+            _ => Err(format!("Unknown subscription command `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionArgs {
+    pub command: SubscriptionCommand,
+    pub params: Option<HashMap<String, Value>>
+}
+// ************* Subscription *************** | END
+
+// ************* Session *************** | START
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SessionCommand {
+    Set,
+    Get,
+    Clear,
+    Unknown,
+}
+impl SessionCommand {
+    pub const ALL: [SessionCommand; 3] = [SessionCommand::Set, SessionCommand::Get, SessionCommand::Clear];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            SessionCommand::Set => "set",
+            SessionCommand::Get => "get",
+            SessionCommand::Clear => "clear",
+            SessionCommand::Unknown => "unknown",
+        }
+    }
+}
+impl std::str::FromStr for SessionCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "set" => Ok(SessionCommand::Set),
+            "get" => Ok(SessionCommand::Get),
+            "clear" => Ok(SessionCommand::Clear),
+            _ => Err(format!("Unknown session command `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionArgs {
+    pub command: SessionCommand,
+    pub params: Option<HashMap<String, Value>>
+}
+// ************* Session *************** | END
+
 // ************* ReqParams *************** | START
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TargetService {
     Database,
     Task,
+    Admin,
+    Subscription,
+    Session,
+    Describe,
     Unknown,
 }
 impl TargetService {
@@ -265,6 +462,10 @@ impl TargetService {
         match s {
             "database" => TargetService::Database,
             "task" => TargetService::Task,
+            "admin" => TargetService::Admin,
+            "subscription" => TargetService::Subscription,
+            "session" => TargetService::Session,
+            "describe" => TargetService::Describe,
             _ => TargetService::Unknown,
         }
     }
@@ -273,6 +474,10 @@ impl TargetService {
         match self {
             TargetService::Database => "database",
             TargetService::Task => "task",
+            TargetService::Admin => "admin",
+            TargetService::Subscription => "subscription",
+            TargetService::Session => "session",
+            TargetService::Describe => "describe",
             TargetService::Unknown => "unknown",
         }
     }
@@ -283,6 +488,9 @@ impl TargetService {
 pub struct Args {
     pub for_database: Option<DatabaseArgs>,
     pub for_task: Option<TaskArgs>,
+    pub for_admin: Option<AdminArgs>,
+    pub for_subscription: Option<SubscriptionArgs>,
+    pub for_session: Option<SessionArgs>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,6 +66,9 @@ impl CallParser {
                         document,
                     }),
                     for_task: None,
+                    for_admin: None,
+                    for_subscription: None,
+                    for_session: None,
                 })
             }
             TargetService::Task => {
@@ -83,8 +86,67 @@ impl CallParser {
                         look_for,
                         params,
                     }),
+                    for_admin: None,
+                    for_subscription: None,
+                    for_session: None,
                 })
             }
+            TargetService::Admin => {
+                let admin_args = json_value.get("args").ok_or("Missing 'args' field")?;
+                let command = admin_args.get("command").and_then(Value::as_str).map(|command| command.parse().unwrap_or(AdminCommand::Unknown)).ok_or("Missing 'command' field")?;
+                let params = admin_args.get("params").and_then(Value::as_object).map(|p| p.clone().into_iter().collect());
+
+                Ok(Args {
+                    for_database: None,
+                    for_task: None,
+                    for_admin: Some(AdminArgs {
+                        command,
+                        params,
+                    }),
+                    for_subscription: None,
+                    for_session: None,
+                })
+            }
+            TargetService::Subscription => {
+                let subscription_args = json_value.get("args").ok_or("Missing 'args' field")?;
+                let command = subscription_args.get("command").and_then(Value::as_str).map(|command| command.parse().unwrap_or(SubscriptionCommand::Unknown)).ok_or("Missing 'command' field")?;
+                let params = subscription_args.get("params").and_then(Value::as_object).map(|p| p.clone().into_iter().collect());
+
+                Ok(Args {
+                    for_database: None,
+                    for_task: None,
+                    for_admin: None,
+                    for_subscription: Some(SubscriptionArgs {
+                        command,
+                        params,
+                    }),
+                    for_session: None,
+                })
+            }
+            TargetService::Session => {
+                let session_args = json_value.get("args").ok_or("Missing 'args' field")?;
+                let command = session_args.get("command").and_then(Value::as_str).map(|command| command.parse().unwrap_or(SessionCommand::Unknown)).ok_or("Missing 'command' field")?;
+                let params = session_args.get("params").and_then(Value::as_object).map(|p| p.clone().into_iter().collect());
+
+                Ok(Args {
+                    for_database: None,
+                    for_task: None,
+                    for_admin: None,
+                    for_subscription: None,
+                    for_session: Some(SessionArgs {
+                        command,
+                        params,
+                    }),
+                })
+            }
+            // Takes no args.
+            TargetService::Describe => Ok(Args {
+                for_database: None,
+                for_task: None,
+                for_admin: None,
+                for_subscription: None,
+                for_session: None,
+            }),
             TargetService::Unknown => Err("Unknown target service".to_string()),
         }
     }
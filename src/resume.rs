@@ -0,0 +1,101 @@
+//! Resumption of the subscriptions of a closed connection.
+//!
+//! The `subscribe` responses of a connection carry its `resume_token`. When the connection
+//! closes, its subscriptions, their cursor and its session filters (see `session.rs`) are parked
+//! for `server.resume_ttl_secs`. A client reconnecting within that time takes them over:
+//!
+//! ```text
+//! {"caller": {...}, "target": "subscription", "args": {"command": "resume", "params": {"token": "...", "resume_from": 1042}}}
+//! ```
+//!
+//! The articles stored after the last one pushed before the disconnect are pushed right away,
+//! oldest first. `resume_from` (optional) is the sequence number of the last article the client
+//! acknowledges receiving, for the ones pushed but lost with the connection to be pushed again.
+//! The subscriptions keep their ids and token, so the new connection can be resumed in turn.
+//!
+//! A token is used once, and only by the client it was given to when it authenticated (see
+//! `auth.rs`). A connection resumes before subscribing. Nothing is parked when the TTL is 0.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::session::Session;
+use crate::subscriptions::Subscriptions;
+
+/// State of a closed connection.
+#[derive(Debug)]
+struct Parked {
+    client: Option<String>,
+    subscriptions: Subscriptions,
+    session: Session,
+    at: Instant,
+}
+
+/// Subscriptions of the closed connections, by resume token.
+#[derive(Debug)]
+pub struct ParkedSessions {
+    ttl: Duration,
+    parked: Mutex<HashMap<String, Parked>>,
+}
+impl ParkedSessions {
+    pub fn new(ttl_secs: u64) -> Self {
+        Self { ttl: Duration::from_secs(ttl_secs), parked: Mutex::new(HashMap::new()) }
+    }
+
+    /// Whether closed connections are parked.
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Parks the state of the connection of `client` closing, if it has subscriptions and a token.
+    pub fn park(&self, client: Option<String>, subscriptions: Subscriptions, session: Session) {
+        let Some(token) = subscriptions.issued_token().map(str::to_string) else {
+            return;
+        };
+        if !self.enabled() || subscriptions.is_empty() {
+            return;
+        }
+        let mut parked = self.parked.lock().unwrap();
+        parked.retain(|_, state| state.at.elapsed() < self.ttl);
+        parked.insert(token, Parked { client, subscriptions, session, at: Instant::now() });
+    }
+
+    /// Takes the state parked under `token` for `client`, `None` when unknown, expired or given to
+    /// another client.
+    pub fn resume(&self, token: &str, client: Option<&str>) -> Option<(Subscriptions, Session)> {
+        let mut parked = self.parked.lock().unwrap();
+        parked.retain(|_, state| state.at.elapsed() < self.ttl);
+        if parked.get(token)?.client.as_deref().is_some_and(|owner| Some(owner) != client) {
+            return None;
+        }
+        parked.remove(token).map(|state| (state.subscriptions, state.session))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumes_the_parked_subscriptions_once() {
+        let sessions = ParkedSessions::new(60);
+        let mut subscriptions = Subscriptions::default();
+        subscriptions.subscribe(&["NVDA".to_string()], &[], 10, 3).unwrap();
+        let token = subscriptions.resume_token();
+        sessions.park(Some("desk".to_string()), subscriptions, Session::default());
+        // Without a token, nothing can be resumed.
+        sessions.park(None, Subscriptions::default(), Session::default());
+
+        assert!(sessions.resume(&token, Some("other")).is_none());
+        assert!(sessions.resume(&token, None).is_none());
+        let (mut resumed, _) = sessions.resume(&token, Some("desk")).unwrap();
+        assert_eq!(resumed.active()[0].tickers, ["NVDA"]);
+        assert_eq!(resumed.resume_token(), token);
+        assert!(sessions.resume(&token, Some("desk")).is_none());
+
+        let expired = ParkedSessions::new(0);
+        expired.park(None, resumed, Session::default());
+        assert!(expired.resume(&token, None).is_none());
+    }
+}
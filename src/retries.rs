@@ -0,0 +1,136 @@
+//! Retries of the provider requests.
+//!
+//! Every failed attempt of `utils::retry` is logged as a `warn` event with the `provider`, `attempt`,
+//! `error_class` (the variant of the error, e.g. `RateLimitError`) and `delay_ms` fields, and
+//! counted in the `news_provider_retries_total` counter of the `metrics` facade, labelled by
+//! provider and error class. Requests given up on after `task.max_retries` attempts are counted in
+//! `news_provider_retries_exhausted_total`.
+//!
+//! The `status` admin command returns the totals of each provider under `retries`, with its last
+//! retries, so the backoff can be audited without a metrics recorder.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+
+use crate::clock;
+
+const RETRIES_COUNTER: &str = "news_provider_retries_total";
+const EXHAUSTED_COUNTER: &str = "news_provider_retries_exhausted_total";
+
+/// Number of retries of each provider kept for the report.
+const RECENT: usize = 20;
+
+/// Class of `error`: the name of its variant, from its `Debug` output.
+pub fn error_class(error: &impl std::fmt::Debug) -> String {
+    let debug = format!("{:?}", error);
+    let class: String = debug.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+    if class.is_empty() { "Unknown".to_string() } else { class }
+}
+
+/// A failed attempt, retried.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Retry {
+    pub at: String,
+    pub attempt: u32,
+    pub error_class: String,
+    pub delay_ms: u64,
+}
+
+/// Retries of a provider.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ProviderRetries {
+    /// Failed attempts retried.
+    pub retries: u64,
+    /// Requests given up on.
+    pub exhausted: u64,
+    /// Failed attempts by error class, the last ones included.
+    pub errors: BTreeMap<String, u64>,
+    /// Last retries, oldest first.
+    pub recent: VecDeque<Retry>,
+}
+
+/// Retries of every provider.
+#[derive(Debug, Default)]
+pub struct Retries {
+    providers: HashMap<String, ProviderRetries>,
+}
+impl Retries {
+    /// Records the failed `attempt` of a request of `provider`, retried after `delay_ms`.
+    pub fn retried(&mut self, provider: &str, attempt: u32, error_class: &str, delay_ms: u64) {
+        let retries = self.providers.entry(provider.to_string()).or_default();
+        retries.retries += 1;
+        *retries.errors.entry(error_class.to_string()).or_default() += 1;
+        retries.recent.push_back(Retry { at: clock::now().to_rfc3339(), attempt, error_class: error_class.to_string(), delay_ms });
+        while retries.recent.len() > RECENT {
+            retries.recent.pop_front();
+        }
+    }
+
+    /// Records a request of `provider` given up on, its last attempt failing with `error_class`.
+    pub fn exhausted(&mut self, provider: &str, error_class: &str) {
+        let retries = self.providers.entry(provider.to_string()).or_default();
+        retries.exhausted += 1;
+        *retries.errors.entry(error_class.to_string()).or_default() += 1;
+    }
+
+    pub fn report(&self) -> BTreeMap<String, ProviderRetries> {
+        self.providers.iter().map(|(provider, retries)| (provider.clone(), retries.clone())).collect()
+    }
+}
+
+fn state() -> &'static Mutex<Retries> {
+    static STATE: OnceLock<Mutex<Retries>> = OnceLock::new();
+    STATE.get_or_init(Mutex::default)
+}
+
+fn with_state<T>(f: impl FnOnce(&mut Retries) -> T) -> T {
+    f(&mut state().lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Records the failed `attempt` of a request of `provider`, retried after `delay_ms`.
+pub fn retried(provider: &str, attempt: u32, error_class: &str, delay_ms: u64) {
+    with_state(|retries| retries.retried(provider, attempt, error_class, delay_ms));
+    metrics::counter!(RETRIES_COUNTER, "provider" => provider.to_string(), "error_class" => error_class.to_string()).increment(1);
+}
+
+/// Records a request of `provider` given up on.
+pub fn exhausted(provider: &str, error_class: &str) {
+    with_state(|retries| retries.exhausted(provider, error_class));
+    metrics::counter!(EXHAUSTED_COUNTER, "provider" => provider.to_string(), "error_class" => error_class.to_string()).increment(1);
+}
+
+/// Retries of every provider.
+pub fn report() -> BTreeMap<String, ProviderRetries> {
+    with_state(|retries| retries.report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ApiError;
+
+    #[test]
+    fn counts_the_retries_of_each_provider() {
+        let rate_limited = error_class(&ApiError::RateLimitError { message: "slow down".to_string(), status: None, headers: None, body: None });
+        assert_eq!(rate_limited, "RateLimitError");
+        assert_eq!(error_class(&ApiError::NoEndpointProvided), "NoEndpointProvided");
+        assert_eq!(error_class(&"timeout"), "Unknown");
+
+        let mut retries = Retries::default();
+        for attempt in 1..=(RECENT as u32 + 2) {
+            retries.retried("marketaux", attempt, &rate_limited, 100 * attempt as u64);
+        }
+        retries.exhausted("marketaux", "NetworkError");
+        retries.retried("gdelt", 1, "NetworkError", 500);
+
+        let report = retries.report();
+        let marketaux = &report["marketaux"];
+        assert_eq!((marketaux.retries, marketaux.exhausted), (RECENT as u64 + 2, 1));
+        assert_eq!(marketaux.errors, BTreeMap::from([("NetworkError".to_string(), 1), ("RateLimitError".to_string(), RECENT as u64 + 2)]));
+        assert_eq!(marketaux.recent.len(), RECENT);
+        assert_eq!((marketaux.recent[0].attempt, marketaux.recent[0].delay_ms), (3, 300));
+        assert_eq!(report["gdelt"].recent[0].error_class, "NetworkError");
+    }
+}
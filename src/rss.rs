@@ -0,0 +1,249 @@
+//! ## Generic RSS/Atom feed ingestion.
+//!
+//! Publishers without a news API (or without a paid plan) usually still publish a feed. The feeds
+//! listed in `[[rss.feeds]]` are polled every `rss.poll_interval_secs`, their entries converted to
+//! `NormalizedArticle`s and stored like API-sourced news, outbox events included. RSS 0.9x/1.0/2.0,
+//! Atom and JSON Feed are supported (parsing is done by `feed-rs`).
+//!
+//! Websocket clients can also fetch the feeds on demand with `{"function": "rss"}`, optionally
+//! restricted to one feed with `"feed": "<name>"`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode};
+use serde_json::{to_value, Value};
+use thiserror::Error;
+use tokio::time::sleep;
+use tracing::{error, info, instrument, warn, Instrument};
+
+use crate::api_calls;
+use crate::captures;
+use crate::config::{FeedConfig, ValueConfig};
+use crate::errors::ProviderError;
+use crate::logging;
+use crate::normalize::{NormalizedArticle, RSS_PROVIDER};
+use crate::options::FetchType;
+use crate::provider::{HealthTracker, NewsProvider, ProviderFuture, ProviderHealth};
+use crate::storage::Storage;
+
+pub const PROVIDER_NAME: &str = RSS_PROVIDER;
+const FEED_MAP_KEY: &str = "feed";
+
+#[derive(Debug, Error)]
+pub enum RssError {
+    #[error("Failed to fetch feed `{feed}`: {source}")]
+    Request { feed: String, source: reqwest::Error },
+
+    #[error("Feed `{feed}` responded with status {status}")]
+    Status { feed: String, status: StatusCode },
+
+    #[error("Failed to parse feed `{feed}`: {message}")]
+    Parse { feed: String, message: String },
+
+    #[error("Unknown feed `{0}`")]
+    UnknownFeed(String),
+
+    #[error("Every feed failed: {0}")]
+    AllFeedsFailed(String),
+}
+
+/// Converts a feed entry to the common schema. `feed` names the source.
+pub fn normalize_entry(feed: &FeedConfig, language: Option<&str>, entry: &feed_rs::model::Entry) -> NormalizedArticle {
+    let url = entry.links.first().map(|link| link.href.clone());
+    NormalizedArticle {
+        provider: PROVIDER_NAME.to_string(),
+        id: url.clone().unwrap_or_else(|| entry.id.clone()),
+        url,
+        title: entry.title.as_ref().map(|t| t.content.clone()),
+        summary: entry.summary.as_ref().map(|t| t.content.clone()),
+        source: Some(feed.name.clone()),
+        authors: entry.authors.iter().map(|a| a.name.clone()).collect(),
+        language: language.map(str::to_string),
+        published_at: entry.published.or(entry.updated).map(|dt| dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+        tickers: feed.tickers.clone(),
+        topics: entry.categories.iter().map(|c| c.term.clone()).collect(),
+        sentiment_score: None,
+        sentiment_label: None,
+        sentiment: None,
+        keywords: Vec::new(),
+        categories: Vec::new(),
+        watchlist: Vec::new(),
+        entities: Vec::new(),
+        translation: None,
+    }
+    .with_categories()
+}
+
+fn to_json(articles: Vec<NormalizedArticle>) -> Value {
+    to_value(articles).expect("Failed to convert articles to JSON value")
+}
+
+/// Parses a feed document into normalized articles.
+pub fn parse_feed(feed: &FeedConfig, body: &[u8]) -> Result<Vec<NormalizedArticle>, RssError> {
+    let parsed = feed_rs::parser::parse(body)
+        .map_err(|e| RssError::Parse { feed: feed.name.clone(), message: e.to_string() })?;
+    let language = parsed.language.as_deref();
+    Ok(parsed.entries.iter().map(|entry| normalize_entry(feed, language, entry)).collect())
+}
+
+pub struct RssClient {
+    client: Arc<Client>,
+    config: Arc<ValueConfig>,
+    health: HealthTracker,
+}
+impl RssClient {
+    pub fn new(client: Arc<Client>, config: Arc<ValueConfig>) -> Self {
+        Self { client, config, health: HealthTracker::default() }
+    }
+
+    /// Fetches and parses one feed.
+    pub async fn fetch_feed(&self, feed: &FeedConfig) -> Result<Vec<NormalizedArticle>, RssError> {
+        let request_error = |source| RssError::Request { feed: feed.name.clone(), source };
+        let response = api_calls::execute(PROVIDER_NAME, self.client.get(&feed.url)).await.map_err(request_error)?;
+        if response.status() != StatusCode::OK {
+            return Err(RssError::Status { feed: feed.name.clone(), status: response.status() });
+        }
+        let (url, status) = (response.url().clone(), response.status());
+        let content_type = response.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let body = response.bytes().await.map_err(request_error)?;
+        captures::record(PROVIDER_NAME, &url, status, content_type.as_deref(), &body);
+        parse_feed(feed, &body)
+    }
+
+    /// Fetches every configured feed. Failing feeds are logged and skipped, unless they all fail.
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME))]
+    pub async fn fetch_all(&self) -> Result<Vec<NormalizedArticle>, RssError> {
+        let mut articles = Vec::new();
+        let mut errors = Vec::new();
+        for feed in &self.config.rss.feeds {
+            match self.fetch_feed(feed).await {
+                Ok(entries) => articles.extend(entries),
+                Err(e) => {
+                    warn!("{}", e);
+                    errors.push(e.to_string());
+                }
+            }
+        }
+        if !errors.is_empty() && errors.len() == self.config.rss.feeds.len() {
+            return Err(RssError::AllFeedsFailed(errors.join(" | ")));
+        }
+        Ok(articles)
+    }
+
+    #[instrument(skip_all, fields(provider = PROVIDER_NAME, function = args["function"].as_str()))]
+    pub async fn poll(&self, args: Arc<Value>) -> Result<Value, RssError> {
+        let articles = match args.get(FEED_MAP_KEY).and_then(Value::as_str) {
+            Some(name) => {
+                let feed = self.config.rss.feeds.iter().find(|f| f.name == name)
+                    .ok_or_else(|| RssError::UnknownFeed(name.to_string()))?;
+                self.fetch_feed(feed).await?
+            }
+            None => self.fetch_all().await?,
+        };
+        Ok(to_json(articles))
+    }
+
+    /// Polls every feed every `rss.poll_interval_secs` and stores the new entries, in a background task.
+    pub fn spawn_polling(self: Arc<Self>, db_ops: Arc<dyn Storage>) {
+        let interval = Duration::from_secs(self.config.rss.poll_interval_secs.max(1));
+        tokio::spawn(async move {
+            loop {
+                async {
+                    let result = self.fetch_all().await;
+                    self.health.record(&result);
+                    match result {
+                        Ok(articles) => match db_ops.store_articles(&articles).await {
+                            Ok(stored) => info!("Stored {} new articles from {} feeds.", stored, self.config.rss.feeds.len()),
+                            Err(e) => error!("Error storing feed articles: {}", e),
+                        },
+                        Err(e) => error!("{}", e),
+                    }
+                }
+                .instrument(logging::cycle_span(PROVIDER_NAME))
+                .await;
+                sleep(interval).await;
+            }
+        });
+    }
+}
+
+impl NewsProvider for RssClient {
+    fn name(&self) -> &str {
+        PROVIDER_NAME
+    }
+
+    fn supports(&self, fetch_type: &FetchType) -> bool {
+        matches!(fetch_type, FetchType::Rss)
+    }
+
+    fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.poll(args).await;
+            self.health.record(&result);
+            Ok(result?)
+        })
+    }
+
+    fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+        Box::pin(async move {
+            let result = self.fetch_all().await;
+            self.health.record(&result);
+            Ok(to_json(result?))
+        })
+    }
+
+    fn health(&self) -> ProviderHealth {
+        if self.config.rss.feeds.is_empty() {
+            return ProviderHealth::Unavailable("No feed is configured".to_string());
+        }
+        self.health.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed() -> FeedConfig {
+        FeedConfig { name: "example".to_string(), url: "https://example.com/feed".to_string(), tickers: vec!["EXM".to_string()] }
+    }
+
+    #[test]
+    fn parses_rss_items() {
+        let body = br#"<?xml version="1.0"?>
+            <rss version="2.0"><channel><title>Example</title><language>en</language>
+            <item><title>Example beats estimates</title><link>https://example.com/a</link>
+            <description>Quarterly results.</description><category>earnings</category>
+            <pubDate>Mon, 06 Jan 2025 14:30:00 GMT</pubDate></item>
+            </channel></rss>"#;
+        let articles = parse_feed(&feed(), body).unwrap();
+        assert_eq!(articles.len(), 1);
+        let article = &articles[0];
+        assert_eq!(article.provider, PROVIDER_NAME);
+        assert_eq!(article.id, "https://example.com/a");
+        assert_eq!(article.title.as_deref(), Some("Example beats estimates"));
+        assert_eq!(article.source.as_deref(), Some("example"));
+        assert_eq!(article.language.as_deref(), Some("en"));
+        assert_eq!(article.published_at.as_deref(), Some("2025-01-06T14:30:00Z"));
+        assert_eq!(article.tickers, vec!["EXM"]);
+        assert_eq!(article.topics, vec!["earnings"]);
+    }
+
+    #[test]
+    fn parses_atom_entries() {
+        let body = br#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom"><title>Example</title><id>urn:example</id>
+            <updated>2025-01-06T15:00:00Z</updated>
+            <entry><title>Example files 8-K</title><id>urn:example:1</id>
+            <link href="https://example.com/b"/><updated>2025-01-06T15:00:00Z</updated>
+            <author><name>Jane Doe</name></author></entry>
+            </feed>"#;
+        let articles = parse_feed(&feed(), body).unwrap();
+        assert_eq!(articles.len(), 1);
+        assert_eq!(articles[0].url.as_deref(), Some("https://example.com/b"));
+        assert_eq!(articles[0].authors, vec!["Jane Doe"]);
+        assert_eq!(articles[0].published_at.as_deref(), Some("2025-01-06T15:00:00Z"));
+    }
+}
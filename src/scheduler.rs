@@ -0,0 +1,154 @@
+//! Per-source polling schedules.
+//!
+//! Each source of the polling loop (every polled provider, and the watchlist) runs as its own task
+//! on the schedule of its `[schedule.<name>]` section:
+//!
+//! - `cron`: a cron expression with a seconds field, e.g. `"0 */30 * * * *"` for every 30 minutes
+//!   on the half hour (UTC). The first run waits for the first match.
+//! - `interval_secs`: a fixed pause between the end of a run and the start of the next. The first
+//!   run starts right away.
+//!
+//! `cron` wins when both are set. Sources without a section run every `request.delay_secs`,
+//! adjusted by the auto-tuning of `autoscale.rs`. A slow or failing source no longer delays the
+//! others.
+//!
+//! Each run is in a `cycle` span with a new correlation ID (see `logging.rs`), and is followed by
+//! the systemd watchdog (see `systemd.rs`).
+
+use std::future::Future;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+use tracing::{info, Instrument};
+
+use crate::{autoscale, logging};
+use crate::config::{SourceSchedule, ValueConfig};
+use crate::systemd;
+use crate::utils::now;
+
+/// Upcoming runs of a cron expression compared by `Schedule::max_interval`.
+const CRON_RUNS_COMPARED: usize = 50;
+
+/// When a source runs.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// At every match of a cron expression.
+    Cron(Box<cron::Schedule>),
+    /// With a fixed pause between runs.
+    Every(Duration),
+    /// Every `request.delay_secs`, auto-tuned.
+    Polling(u64),
+}
+impl Schedule {
+    /// Schedule of `source`, from its `[schedule.<source>]` section.
+    pub fn of(source: &str, config: &ValueConfig) -> Result<Self, String> {
+        let polling = Schedule::Polling(config.request.delay_secs.max(1) as u64);
+        match config.schedule.get(source) {
+            Some(schedule) => Self::parse(schedule).map(|s| s.unwrap_or(polling)).map_err(|e| format!("`schedule.{}`: {}", source, e)),
+            None => Ok(polling),
+        }
+    }
+
+    /// Schedule of a section, `None` when it sets neither `cron` nor `interval_secs`.
+    pub fn parse(schedule: &SourceSchedule) -> Result<Option<Self>, String> {
+        if let Some(expression) = &schedule.cron {
+            let cron = cron::Schedule::from_str(expression).map_err(|e| format!("invalid cron expression `{}`: {}", expression, e))?;
+            return Ok(Some(Schedule::Cron(Box::new(cron))));
+        }
+        Ok(schedule.interval_secs.map(|secs| Schedule::Every(Duration::from_secs(secs.max(1)))))
+    }
+
+    /// Whether the first run starts right away rather than after `delay`.
+    pub fn runs_at_start(&self) -> bool {
+        !matches!(self, Schedule::Cron(_))
+    }
+
+    /// Longest time between two runs after `now`: the largest of the next intervals of a cron expression.
+    pub fn max_interval(&self, now: DateTime<Utc>) -> Duration {
+        match self {
+            Schedule::Cron(cron) => {
+                let runs: Vec<DateTime<Utc>> = cron.after(&now).take(CRON_RUNS_COMPARED).collect();
+                runs.windows(2)
+                    .filter_map(|pair| (pair[1] - pair[0]).to_std().ok())
+                    .max()
+                    .unwrap_or_else(|| self.delay(now))
+            }
+            _ => self.delay(now),
+        }
+    }
+
+    /// Time to wait before the next run, the previous one having ended at `now`.
+    pub fn delay(&self, now: DateTime<Utc>) -> Duration {
+        match self {
+            Schedule::Cron(cron) => cron
+                .after(&now)
+                .next()
+                .and_then(|next| (next - now).to_std().ok())
+                .unwrap_or(Duration::MAX),
+            Schedule::Every(interval) => *interval,
+            Schedule::Polling(delay_secs) => autoscale::tune(*delay_secs),
+        }
+    }
+}
+
+/// Runs `run` on `schedule` in its own task, for as long as the runtime lives.
+pub fn spawn<F, Fut>(source: &str, schedule: Schedule, mut run: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let source = source.to_string();
+    tokio::spawn(async move {
+        if !schedule.runs_at_start() {
+            sleep(schedule.delay(Utc::now())).await;
+        }
+        loop {
+            systemd::cycle_started(&source);
+            run().instrument(logging::cycle_span(&source)).await;
+            systemd::cycle_ended(&source);
+            systemd::notify_status(&format!("Last `{}` cycle: {}", source, now()));
+            let delay = schedule.delay(Utc::now());
+            info!("Next `{}` fetch in {} seconds", source, delay.as_secs());
+            sleep(delay).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(interval_secs: Option<u64>, cron: Option<&str>) -> Result<Option<Schedule>, String> {
+        Schedule::parse(&SourceSchedule { interval_secs, cron: cron.map(str::to_string) })
+    }
+
+    #[test]
+    fn cron_wins_over_intervals() {
+        let at = DateTime::parse_from_rfc3339("2024-05-01T10:12:30Z").unwrap().with_timezone(&Utc);
+        let cron = schedule(Some(60), Some("0 */30 * * * *")).unwrap().unwrap();
+        assert!(!cron.runs_at_start());
+        assert_eq!(cron.delay(at), Duration::from_secs(17 * 60 + 30));
+
+        let every = schedule(Some(300), None).unwrap().unwrap();
+        assert!(every.runs_at_start());
+        assert_eq!(every.delay(at), Duration::from_secs(300));
+        assert!(schedule(None, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn measures_the_longest_interval_of_a_cron_expression() {
+        let at = DateTime::parse_from_rfc3339("2024-05-01T10:12:30Z").unwrap().with_timezone(&Utc);
+        // Hourly during the day, then overnight.
+        let cron = schedule(None, Some("0 0 8-18 * * *")).unwrap().unwrap();
+        assert_eq!(cron.max_interval(at), Duration::from_secs(14 * 3600));
+        let every = schedule(Some(300), None).unwrap().unwrap();
+        assert_eq!(every.max_interval(at), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn rejects_invalid_cron_expressions() {
+        assert!(schedule(None, Some("every 5 minutes")).is_err());
+    }
+}
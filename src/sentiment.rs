@@ -0,0 +1,173 @@
+//! Sentiment label harmonization.
+//!
+//! Providers label sentiment in their own words: Alpha Vantage uses `Bearish` to `Bullish` with
+//! `Somewhat-` steps, FMP and Polygon.io `positive` / `negative`, MarketAux only scores. Every
+//! normalized article carries a `sentiment` from the fixed `SentimentLabel` scale, which filters and
+//! aggregations use instead of the provider labels.
+//!
+//! Labels are looked up in a built-in table, completed or overridden by `[sentiment.labels]`
+//! (case, spaces and dashes are ignored). Articles without a known label are classified from their
+//! score with the Alpha Vantage thresholds, also configurable.
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config::SentimentConfig;
+
+/// Harmonized sentiment, from most negative to most positive.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SentimentLabel {
+    Bearish,
+    SomewhatBearish,
+    Neutral,
+    SomewhatBullish,
+    Bullish,
+}
+impl SentimentLabel {
+    pub const ALL: [SentimentLabel; 5] = [
+        SentimentLabel::Bearish,
+        SentimentLabel::SomewhatBearish,
+        SentimentLabel::Neutral,
+        SentimentLabel::SomewhatBullish,
+        SentimentLabel::Bullish,
+    ];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            SentimentLabel::Bearish => "bearish",
+            SentimentLabel::SomewhatBearish => "somewhat_bearish",
+            SentimentLabel::Neutral => "neutral",
+            SentimentLabel::SomewhatBullish => "somewhat_bullish",
+            SentimentLabel::Bullish => "bullish",
+        }
+    }
+}
+impl std::str::FromStr for SentimentLabel {
+    type Err = String;
+
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        match label {
+            "bearish" => Ok(SentimentLabel::Bearish),
+            "somewhat_bearish" => Ok(SentimentLabel::SomewhatBearish),
+            "neutral" => Ok(SentimentLabel::Neutral),
+            "somewhat_bullish" => Ok(SentimentLabel::SomewhatBullish),
+            "bullish" => Ok(SentimentLabel::Bullish),
+            _ => Err(format!("Unknown sentiment label `{}`", label)),
+        }
+    }
+}
+
+/// Provider labels known without configuration.
+const DEFAULT_LABELS: &[(&str, SentimentLabel)] = &[
+    ("bearish", SentimentLabel::Bearish),
+    ("somewhat_bearish", SentimentLabel::SomewhatBearish),
+    ("neutral", SentimentLabel::Neutral),
+    ("somewhat_bullish", SentimentLabel::SomewhatBullish),
+    ("bullish", SentimentLabel::Bullish),
+    ("negative", SentimentLabel::Bearish),
+    ("positive", SentimentLabel::Bullish),
+];
+
+/// Lowercases `label` and replaces spaces and dashes with underscores.
+fn label_key(label: &str) -> String {
+    label.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// Label table and score thresholds.
+#[derive(Clone, Debug)]
+pub struct SentimentMap {
+    labels: HashMap<String, SentimentLabel>,
+    /// Upper bounds of `bearish`, `somewhat_bearish`, `neutral` and `somewhat_bullish` scores.
+    thresholds: [f64; 4],
+}
+impl SentimentMap {
+    pub fn new(config: &SentimentConfig) -> Self {
+        let mut labels: HashMap<String, SentimentLabel> =
+            DEFAULT_LABELS.iter().map(|(label, harmonized)| (label.to_string(), *harmonized)).collect();
+        for (label, harmonized) in &config.labels {
+            match label_key(harmonized).parse::<SentimentLabel>() {
+                Ok(harmonized) => {
+                    labels.insert(label_key(label), harmonized);
+                }
+                Err(_) => warn!("Ignoring sentiment label `{}`: unknown label `{}`.", label, harmonized),
+            }
+        }
+        Self { labels, thresholds: config.thresholds }
+    }
+
+    /// Harmonized label of a provider `label`, or of `score` when the label is missing or unknown.
+    pub fn harmonize(&self, label: Option<&str>, score: Option<f64>) -> Option<SentimentLabel> {
+        label
+            .and_then(|label| self.labels.get(&label_key(label)).copied())
+            .or_else(|| score.map(|score| self.classify(score)))
+    }
+
+    /// Label of a score in `[-1, 1]`.
+    pub fn classify(&self, score: f64) -> SentimentLabel {
+        let bucket = self.thresholds.iter().position(|bound| score <= *bound).unwrap_or(4);
+        SentimentLabel::ALL[bucket]
+    }
+}
+impl Default for SentimentMap {
+    fn default() -> Self {
+        Self::new(&SentimentConfig::default())
+    }
+}
+
+fn map() -> &'static RwLock<SentimentMap> {
+    static MAP: OnceLock<RwLock<SentimentMap>> = OnceLock::new();
+    MAP.get_or_init(|| RwLock::new(SentimentMap::default()))
+}
+
+/// Applies the `[sentiment]` section of the config.
+pub fn configure(config: &SentimentConfig) {
+    *map().write().unwrap_or_else(|e| e.into_inner()) = SentimentMap::new(config);
+}
+
+/// Harmonizes a provider label with the configured map, see `SentimentMap::harmonize`.
+pub fn harmonize(label: Option<&str>, score: Option<f64>) -> Option<SentimentLabel> {
+    map().read().unwrap_or_else(|e| e.into_inner()).harmonize(label, score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harmonizes_provider_labels() {
+        let map = SentimentMap::default();
+        assert_eq!(map.harmonize(Some("Somewhat-Bullish"), None), Some(SentimentLabel::SomewhatBullish));
+        assert_eq!(map.harmonize(Some("positive"), Some(-0.9)), Some(SentimentLabel::Bullish));
+        assert_eq!(map.harmonize(Some("Neutral"), None), Some(SentimentLabel::Neutral));
+        assert_eq!(map.harmonize(None, None), None);
+    }
+
+    #[test]
+    fn classifies_unknown_labels_by_score() {
+        let map = SentimentMap::default();
+        assert_eq!(map.harmonize(Some("mixed"), Some(0.2)), Some(SentimentLabel::SomewhatBullish));
+        assert_eq!(map.harmonize(None, Some(-0.35)), Some(SentimentLabel::Bearish));
+        assert_eq!(map.harmonize(None, Some(0.0)), Some(SentimentLabel::Neutral));
+        assert_eq!(map.harmonize(None, Some(0.8)), Some(SentimentLabel::Bullish));
+    }
+
+    #[test]
+    fn configured_labels_override_defaults() {
+        let config = SentimentConfig {
+            labels: HashMap::from([
+                ("Mixed".to_string(), "neutral".to_string()),
+                ("positive".to_string(), "somewhat-bullish".to_string()),
+                ("meh".to_string(), "unknown".to_string()),
+            ]),
+            ..SentimentConfig::default()
+        };
+        let map = SentimentMap::new(&config);
+        assert_eq!(map.harmonize(Some("mixed"), Some(0.9)), Some(SentimentLabel::Neutral));
+        assert_eq!(map.harmonize(Some("Positive"), None), Some(SentimentLabel::SomewhatBullish));
+        assert_eq!(map.harmonize(Some("meh"), None), None);
+    }
+}
@@ -1,12 +1,23 @@
 use serde::{Serialize, Deserialize};
 use serde_json::Value;
 
+use crate::lenient;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-enum FMPNewsType {
+pub enum FMPNewsType {
     Crypto,
     Forex,
     Stock,
 }
+impl FMPNewsType {
+    pub fn to_str(&self) -> &str {
+        match self {
+            FMPNewsType::Crypto => "crypto",
+            FMPNewsType::Forex => "forex",
+            FMPNewsType::Stock => "stock",
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
@@ -34,23 +45,29 @@ type DateString = String;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FMPArticle {
-    title: Option<String>,
-    date: Option<String>,
-	content: Option<HtmlLikeString>, // html-like string //<p><a href='https://financialmodelingprep.com/financial-summary/SEDG'>SolarEdge Technologies (NASDAQ:SEDG)</a> shares plunged more than 25% intra-day today following the company's preliminary Q3 financial results. Revenue for the quarter is now expected to be between $720 million and $730 million, a significant drop from the earlier projection of $880 million to $920 million,....",
-	tickers: Option<String>,
-	image: Option<UrlString>,
-	link: Option<UrlString>,
-	author: Option<String>,
-    site: Option<String>,
-    published_date: Option<DateString>,
-	url: Option<UrlString>,
-    symbol: Option<String>,
-	text: Option<String>,
-    sentiment: Option<String>,
-	sentiment_score: Option<f64>,
-	updated_at: Option<DateString>,
-	created_at: Option<DateString>,
-	type_name: Option<FMPNewsType>,
+    pub title: Option<String>,
+    pub date: Option<String>,
+	pub content: Option<HtmlLikeString>, // html-like string //<p><a href='https://financialmodelingprep.com/financial-summary/SEDG'>SolarEdge Technologies (NASDAQ:SEDG)</a> shares plunged more than 25% intra-day today following the company's preliminary Q3 financial results. Revenue for the quarter is now expected to be between $720 million and $730 million, a significant drop from the earlier projection of $880 million to $920 million,....",
+	pub tickers: Option<String>,
+	pub image: Option<UrlString>,
+	pub link: Option<UrlString>,
+	/// `publisher` on the `stable` news endpoints.
+	#[serde(alias = "publisher")]
+	pub author: Option<String>,
+    pub site: Option<String>,
+    #[serde(alias = "publishedDate")]
+    pub published_date: Option<DateString>,
+	pub url: Option<UrlString>,
+    pub symbol: Option<String>,
+	pub text: Option<String>,
+    pub sentiment: Option<String>,
+	#[serde(alias = "sentimentScore", default, deserialize_with = "lenient::opt_number")]
+	pub sentiment_score: Option<f64>,
+	#[serde(alias = "updatedAt")]
+	pub updated_at: Option<DateString>,
+	#[serde(alias = "createdAt")]
+	pub created_at: Option<DateString>,
+	pub type_name: Option<FMPNewsType>,
 
 }
 impl FMPArticle {
@@ -82,25 +99,84 @@ impl FMPArticle {
     }
 }
 
+/// M&A filing, as returned by `mergers-acquisitions-rss-feed`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FMPMergerAcquisition {
+    /// Acquirer.
+    #[serde(alias = "companyName")]
+    pub company_name: Option<String>,
+    pub cik: Option<String>,
+    pub symbol: Option<String>,
+    #[serde(alias = "targetedCompanyName")]
+    pub targeted_company_name: Option<String>,
+    #[serde(alias = "targetedCik")]
+    pub targeted_cik: Option<String>,
+    #[serde(alias = "targetedSymbol")]
+    pub targeted_symbol: Option<String>,
+    #[serde(alias = "transactionDate")]
+    pub transaction_date: Option<DateString>,
+    /// Time the filing was accepted by the SEC, `YYYY-MM-DD HH:MM:SS`.
+    #[serde(alias = "acceptanceTime", alias = "acceptedDate")]
+    pub acceptance_time: Option<DateString>,
+    /// SEC filing, `link` on the `stable` endpoint.
+    #[serde(alias = "link")]
+    pub url: Option<UrlString>,
+}
+
+/// Earnings call transcript, as returned by `earning_call_transcript`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Transcript {
+    pub symbol: Option<String>,
+    #[serde(default, deserialize_with = "lenient::opt_number")]
+    pub quarter: Option<u64>,
+    #[serde(default, deserialize_with = "lenient::opt_number")]
+    pub year: Option<u64>,
+    /// Date of the call, `YYYY-MM-DD HH:MM:SS`.
+    pub date: Option<DateString>,
+    /// Full text of the call, one speaker turn per line.
+    pub content: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FMPMarketSentiment {
-		date: Option<String>,
-		symbol: Option<String>,
-		stock_twits_posts: Option<u64>,
-		twitter_posts: Option<u64>,
-		stock_twits_comments:Option<u64>,
-		twitter_comments: Option<u64>,
-		stocktwits_likes: Option<u64>,
-		twitter_likes: Option<u64>,
-		stock_twits_impressions: Option<u64>,
-		twitter_impressions: Option<u64>,
-		stock_twits_sentiment: Option<f64>,
-		twitter_sentiment: Option<f64>,
-		name: Option<String>,
-		rank: Option<u64>,
-		sentiment: Option<u64>,
-		last_sentiment: Option<f64>,
-		sentiment_change: Option<f64>
+		pub date: Option<String>,
+		pub symbol: Option<String>,
+		#[serde(alias = "stocktwitsPosts", default, deserialize_with = "lenient::opt_number")]
+		pub stock_twits_posts: Option<u64>,
+		#[serde(alias = "twitterPosts", default, deserialize_with = "lenient::opt_number")]
+		pub twitter_posts: Option<u64>,
+		#[serde(alias = "stocktwitsComments", default, deserialize_with = "lenient::opt_number")]
+		pub stock_twits_comments:Option<u64>,
+		#[serde(alias = "twitterComments", default, deserialize_with = "lenient::opt_number")]
+		pub twitter_comments: Option<u64>,
+		#[serde(alias = "stocktwitsLikes", default, deserialize_with = "lenient::opt_number")]
+		pub stocktwits_likes: Option<u64>,
+		#[serde(alias = "twitterLikes", default, deserialize_with = "lenient::opt_number")]
+		pub twitter_likes: Option<u64>,
+		#[serde(alias = "stocktwitsImpressions", default, deserialize_with = "lenient::opt_number")]
+		pub stock_twits_impressions: Option<u64>,
+		#[serde(alias = "twitterImpressions", default, deserialize_with = "lenient::opt_number")]
+		pub twitter_impressions: Option<u64>,
+		#[serde(alias = "stocktwitsSentiment", default, deserialize_with = "lenient::opt_number")]
+		pub stock_twits_sentiment: Option<f64>,
+		#[serde(alias = "twitterSentiment", default, deserialize_with = "lenient::opt_number")]
+		pub twitter_sentiment: Option<f64>,
+		pub name: Option<String>,
+		#[serde(default, deserialize_with = "lenient::opt_number")]
+		pub rank: Option<u64>,
+		#[serde(default, deserialize_with = "lenient::opt_number")]
+		pub sentiment: Option<f64>,
+		#[serde(alias = "lastSentiment", default, deserialize_with = "lenient::opt_number")]
+		pub last_sentiment: Option<f64>,
+		#[serde(alias = "sentimentChange", default, deserialize_with = "lenient::opt_number")]
+		pub sentiment_change: Option<f64>,
+		/// Reddit submissions mentioning the symbol, see `reddit.rs`.
+		#[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient::opt_number")]
+		pub reddit_posts: Option<u64>,
+		#[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient::opt_number")]
+		pub reddit_comments: Option<u64>,
+		#[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "lenient::opt_number")]
+		pub reddit_score: Option<i64>,
 
 }
 impl FMPMarketSentiment {
@@ -120,10 +196,23 @@ impl FMPMarketSentiment {
             twitter_sentiment: value.get("twitter_sentiment").and_then(|v| v.as_f64()),
             name: value.get("name").and_then(|v| v.as_str()).map(|s| s.to_string()),
             rank: value.get("rank").and_then(|v| v.as_u64()),
-            sentiment: value.get("sentiment").and_then(|v| v.as_u64()),
+            sentiment: value.get("sentiment").and_then(|v| v.as_f64()),
             last_sentiment: value.get("last_sentiment").and_then(|v| v.as_f64()),
             sentiment_change: value.get("sentiment_change").and_then(|v| v.as_f64()),
+            reddit_posts: value.get("reddit_posts").and_then(|v| v.as_u64()),
+            reddit_comments: value.get("reddit_comments").and_then(|v| v.as_u64()),
+            reddit_score: value.get("reddit_score").and_then(|v| v.as_i64()),
         
         }
     }
+
+    /// Empty Reddit record of `symbol` for the hour starting at `date`.
+    pub fn reddit(date: &str, symbol: &str) -> FMPMarketSentiment {
+        FMPMarketSentiment {
+            date: Some(date.to_string()),
+            symbol: Some(symbol.to_string()),
+            name: Some("reddit".to_string()),
+            ..FMPMarketSentiment::from_value(serde_json::Value::Null)
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+//! Saved filters of a websocket connection.
+//!
+//! Rather than resending the same filters with every request, a client saves them in the session
+//! of its connection:
+//!
+//! ```text
+//! {"caller": {...}, "target": "session", "args": {"command": "set", "params": {"tickers": ["NVDA", "AMD"], "language": "en", "min_sentiment": 0.2}}}
+//! {"caller": {...}, "target": "session", "args": {"command": "get"}}
+//! {"caller": {...}, "target": "session", "args": {"command": "clear"}}
+//! ```
+//!
+//! `set` replaces the filters given (`null` removes one) and keeps the others. From then on:
+//! - the params of every `task` request lacking them are completed with the filters: `tickers`
+//!   (comma separated), `language` and `min_sentiment`, and the articles it returns (in the common
+//!   schema) are filtered as they are sent,
+//! - the pushed polling results carry only the matching `articles`, and are skipped without any,
+//! - the pushed subscribed articles are skipped unless they match.
+//!
+//! An article matches when it is about one of the `tickers`, in the `language` and scored at least
+//! `min_sentiment`, a missing filter matching any article. The session ends with the connection.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::normalize::NormalizedArticle;
+
+/// Filters saved by a connection.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Session {
+    /// Uppercased.
+    pub tickers: Vec<String>,
+    /// Lowercased ISO 639-1 code, e.g. `en`.
+    pub language: Option<String>,
+    /// Lowest `sentiment_score`, from -1 to 1.
+    pub min_sentiment: Option<f64>,
+}
+impl Session {
+    /// Whether no filter is saved.
+    pub fn is_empty(&self) -> bool {
+        self.tickers.is_empty() && self.language.is_none() && self.min_sentiment.is_none()
+    }
+
+    /// Replaces the filters of `params`, a `null` one being removed. Nothing changes on an invalid one.
+    pub fn set(&mut self, params: &Map<String, Value>) -> Result<(), String> {
+        let mut session = self.clone();
+        for (key, value) in params {
+            match (key.as_str(), value) {
+                ("tickers", Value::Null) => session.tickers.clear(),
+                ("tickers", Value::String(tickers)) => session.tickers = Self::tickers(tickers.split(',')),
+                ("tickers", Value::Array(tickers)) => {
+                    session.tickers = Self::tickers(tickers.iter().filter_map(Value::as_str));
+                }
+                ("language", Value::Null) => session.language = None,
+                ("language", Value::String(language)) => {
+                    session.language = Some(language.trim().to_lowercase()).filter(|l| !l.is_empty());
+                }
+                ("min_sentiment", Value::Null) => session.min_sentiment = None,
+                ("min_sentiment", Value::Number(score)) => session.min_sentiment = score.as_f64(),
+                ("tickers" | "language" | "min_sentiment", value) => return Err(format!("Invalid `{}` filter: {}", key, value)),
+                (key, _) => return Err(format!("Unknown filter `{}`", key)),
+            }
+        }
+        *self = session;
+        Ok(())
+    }
+
+    fn tickers<'a>(tickers: impl Iterator<Item = &'a str>) -> Vec<String> {
+        let mut unique: Vec<String> = Vec::new();
+        for ticker in tickers.map(|t| t.trim().to_uppercase()).filter(|t| !t.is_empty()) {
+            if !unique.contains(&ticker) {
+                unique.push(ticker);
+            }
+        }
+        unique
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn matches(&self, article: &NormalizedArticle) -> bool {
+        (self.tickers.is_empty() || article.tickers.iter().any(|t| self.tickers.contains(&t.to_uppercase())))
+            && self.language.as_ref().is_none_or(|language| article.language.as_ref().is_some_and(|l| l.eq_ignore_ascii_case(language)))
+            && self.min_sentiment.is_none_or(|min| article.sentiment_score.is_some_and(|score| score >= min))
+    }
+
+    /// Completes the task `params` with the filters they lack.
+    pub fn complete(&self, params: &mut Map<String, Value>) {
+        if !self.tickers.is_empty() {
+            params.entry("tickers").or_insert_with(|| Value::String(self.tickers.join(",")));
+        }
+        if let Some(language) = &self.language {
+            params.entry("language").or_insert_with(|| Value::String(language.clone()));
+        }
+        if let Some(min) = self.min_sentiment {
+            params.entry("min_sentiment").or_insert_with(|| min.into());
+        }
+    }
+
+    /// Whether the `item` of a result list is kept: anything but an article not matching.
+    pub fn keeps(&self, item: &Value) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        NormalizedArticle::deserialize(item).ok().is_none_or(|article| self.matches(&article))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn article(tickers: &[&str], language: &str, score: f64) -> Value {
+        json!({ "provider": "finnhub", "id": "1", "authors": [], "tickers": tickers, "topics": [], "language": language, "sentiment_score": score })
+    }
+
+    #[test]
+    fn filters_the_articles_of_a_result() {
+        let mut session = Session::default();
+        let params = json!({ "tickers": "nvda, AMD", "language": "EN", "min_sentiment": 0.2 });
+        session.set(params.as_object().unwrap()).unwrap();
+        assert_eq!(session.tickers, ["NVDA", "AMD"]);
+
+        let result = [article(&["NVDA"], "en", 0.5), article(&["AAPL"], "en", 0.5), article(&["AMD"], "fr", 0.5), article(&["AMD"], "en", 0.1)];
+        let kept: Vec<&Value> = result.iter().filter(|item| session.keeps(item)).collect();
+        assert_eq!(kept, [&article(&["NVDA"], "en", 0.5)]);
+        // Anything else than articles is kept.
+        assert!(session.keeps(&json!({ "symbol": "AAPL" })));
+
+        let mut params = json!({ "tickers": "AAPL" });
+        session.complete(params.as_object_mut().unwrap());
+        assert_eq!(params, json!({ "tickers": "AAPL", "language": "en", "min_sentiment": 0.2 }));
+
+        assert!(session.set(json!({ "language": null, "min_sentiment": "high" }).as_object().unwrap()).is_err());
+        assert_eq!(session.language.as_deref(), Some("en"));
+        session.set(json!({ "language": null }).as_object().unwrap()).unwrap();
+        assert!(session.language.is_none());
+        session.clear();
+        assert!(session.is_empty());
+    }
+}
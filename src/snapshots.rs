@@ -0,0 +1,238 @@
+//! Latest social sentiment of each symbol.
+//!
+//! The social sentiment history grows with every poll, so reading the current sentiment of a
+//! symbol from it means scanning it. With `fmp.sentiment_snapshots`, the trending and changes
+//! rankings of FMP are fetched on the `[schedule.sentiment_snapshots]` schedule instead, and the
+//! record of each symbol upserted in the `sentiment_snapshots` collection: one document per
+//! symbol (its `_id`), holding the latest record of each ranking, so dashboards read it directly.
+//!
+//! A symbol missing from the latest ranking keeps its previous record, `updated_at` telling how
+//! old it is. Each fetch of the trending ranking is also appended, as fetched, to the `trending`
+//! collection, which keeps its history.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::db::OpError;
+use crate::errors::ProviderError;
+use crate::fmp::FMPApiResponse;
+use crate::provider::NewsProvider;
+use crate::server_types::FMPMarketSentiment;
+use crate::storage::Storage;
+use crate::utils::now;
+
+/// Name of the snapshots in `[schedule]`.
+pub const SOURCE: &str = "sentiment_snapshots";
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Failed to fetch the {0} social sentiment: {1}")]
+    Fetch(SnapshotKind, ProviderError),
+
+    #[error("Failed to parse the {0} social sentiment: {1}")]
+    Parse(SnapshotKind, String),
+
+    #[error("Failed to store the sentiment snapshots: {0}")]
+    Storage(OpError),
+}
+
+/// Rankings of the social sentiment kept per symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnapshotKind {
+    Trending,
+    Changes,
+}
+impl SnapshotKind {
+    pub const ALL: [SnapshotKind; 2] = [SnapshotKind::Trending, SnapshotKind::Changes];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            SnapshotKind::Trending => "trending",
+            SnapshotKind::Changes => "changes",
+        }
+    }
+
+    /// `function` of the FMP request fetching the ranking.
+    fn function(&self) -> &'static str {
+        match self {
+            SnapshotKind::Trending => "social sentiment trending",
+            SnapshotKind::Changes => "social sentiment changes",
+        }
+    }
+}
+impl std::fmt::Display for SnapshotKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// Latest social sentiment of a symbol.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentimentSnapshot {
+    #[serde(rename = "_id")]
+    pub symbol: String,
+    /// Latest record of the symbol in the trending ranking.
+    pub trending: Option<FMPMarketSentiment>,
+    /// Latest record of the symbol in the sentiment changes ranking.
+    pub changes: Option<FMPMarketSentiment>,
+    /// When a record was last replaced (RFC 3339).
+    pub updated_at: String,
+}
+impl SentimentSnapshot {
+    pub fn new(symbol: &str) -> Self {
+        Self { symbol: symbol.to_string(), trending: None, changes: None, updated_at: String::new() }
+    }
+
+    /// Replaces the record of `kind`.
+    pub fn update(&mut self, kind: SnapshotKind, record: FMPMarketSentiment, at: &str) {
+        match kind {
+            SnapshotKind::Trending => self.trending = Some(record),
+            SnapshotKind::Changes => self.changes = Some(record),
+        }
+        self.updated_at = at.to_string();
+    }
+}
+
+/// Uppercased symbol of `record`, `None` without one.
+fn symbol_of(record: &FMPMarketSentiment) -> Option<String> {
+    record.symbol.as_deref().map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty())
+}
+
+/// Applies the `records` of a `kind` ranking to the `snapshots` of their symbols, returning the
+/// updated ones. Records without a symbol are skipped, the last one wins for a repeated symbol.
+fn apply(snapshots: &mut HashMap<String, SentimentSnapshot>, kind: SnapshotKind, records: Vec<FMPMarketSentiment>, at: &str) -> Vec<String> {
+    let mut updated: Vec<String> = Vec::new();
+    for record in records {
+        let Some(symbol) = symbol_of(&record) else {
+            continue;
+        };
+        snapshots.entry(symbol.clone()).or_insert_with(|| SentimentSnapshot::new(&symbol)).update(kind, record, at);
+        if !updated.contains(&symbol) {
+            updated.push(symbol);
+        }
+    }
+    updated
+}
+
+/// Records of the `kind` ranking, fetched from `provider` (FMP).
+pub async fn fetch(provider: &dyn NewsProvider, kind: SnapshotKind) -> Result<Vec<FMPMarketSentiment>, SnapshotError> {
+    let body = provider.fetch(Arc::new(json!({ "function": kind.function() }))).await
+        .map_err(|e| SnapshotError::Fetch(kind, e))?;
+    records(body).map_err(|e| SnapshotError::Parse(kind, e))
+}
+
+/// Records of a social sentiment response of `FMPClient`.
+fn records(body: Value) -> Result<Vec<FMPMarketSentiment>, String> {
+    let response: FMPApiResponse = serde_json::from_value(body).map_err(|e| e.to_string())?;
+    Ok(response.market_sentiment())
+}
+
+/// Fetches every ranking from `provider` and upserts the snapshots of the symbols found. Returns
+/// the number of snapshots stored.
+pub async fn refresh(provider: &dyn NewsProvider, storage: &dyn Storage) -> Result<usize, SnapshotError> {
+    let mut fetched = Vec::new();
+    for kind in SnapshotKind::ALL {
+        fetched.push((kind, fetch(provider, kind).await?));
+    }
+    for (kind, records) in &fetched {
+        if *kind == SnapshotKind::Trending {
+            storage.insert_trending(records).await.map_err(SnapshotError::Storage)?;
+        }
+    }
+    let symbols: Vec<String> = fetched
+        .iter()
+        .flat_map(|(_, records)| records.iter().filter_map(symbol_of))
+        .collect();
+    if symbols.is_empty() {
+        return Ok(0);
+    }
+    let mut snapshots: HashMap<String, SentimentSnapshot> = storage.sentiment_snapshots(&symbols).await.map_err(SnapshotError::Storage)?
+        .into_iter()
+        .map(|snapshot| (snapshot.symbol.clone(), snapshot))
+        .collect();
+    let at = now();
+    let mut updated: Vec<String> = Vec::new();
+    for (kind, records) in fetched {
+        for symbol in apply(&mut snapshots, kind, records, &at) {
+            if !updated.contains(&symbol) {
+                updated.push(symbol);
+            }
+        }
+    }
+    for symbol in &updated {
+        storage.save_sentiment_snapshot(&snapshots[symbol]).await.map_err(SnapshotError::Storage)?;
+    }
+    Ok(updated.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::FMPApiError;
+    use crate::provider::{ProviderFuture, ProviderHealth};
+    use crate::storage::MemoryStorage;
+
+    /// Answers each ranking with the records of `trending` and `changes`.
+    struct Rankings {
+        trending: Value,
+        changes: Value,
+    }
+    impl NewsProvider for Rankings {
+        fn name(&self) -> &str {
+            "fmp"
+        }
+
+        fn supports(&self, _fetch_type: &crate::options::FetchType) -> bool {
+            true
+        }
+
+        fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async move {
+                let records = match args["function"].as_str() {
+                    Some("social sentiment trending") => self.trending.clone(),
+                    Some("social sentiment changes") => self.changes.clone(),
+                    other => return Err(FMPApiError::TaskError(format!("Unexpected function {:?}", other)).into()),
+                };
+                Ok(json!({ "content": { "MarketSentiment": records } }))
+            })
+        }
+
+        fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async { Ok(Value::Null) })
+        }
+
+        fn health(&self) -> ProviderHealth {
+            ProviderHealth::Healthy
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_the_latest_record_of_each_symbol() {
+        let storage = MemoryStorage::new();
+        let first = Rankings {
+            trending: json!([{ "symbol": "NVDA", "rank": 1, "sentiment": 0.7 }, { "symbol": "amd", "rank": 2, "sentiment": 0.4 }]),
+            changes: json!([{ "symbol": "NVDA", "sentimentChange": 12.5 }, { "name": "no symbol" }]),
+        };
+        assert_eq!(refresh(&first, &storage).await.unwrap(), 2);
+
+        let second = Rankings {
+            trending: json!([{ "symbol": "NVDA", "rank": 3, "sentiment": 0.2 }]),
+            changes: json!([]),
+        };
+        assert_eq!(refresh(&second, &storage).await.unwrap(), 1);
+
+        let snapshots = storage.sentiment_snapshots(&[]).await.unwrap();
+        assert_eq!(snapshots.iter().map(|s| s.symbol.as_str()).collect::<Vec<_>>(), ["AMD", "NVDA"]);
+        let nvda = &snapshots[1];
+        assert_eq!(nvda.trending.as_ref().and_then(|r| r.rank), Some(3));
+        // Missing from the latest changes ranking: the previous record is kept.
+        assert_eq!(nvda.changes.as_ref().and_then(|r| r.sentiment_change), Some(12.5));
+        assert!(snapshots[0].changes.is_none());
+        // Every fetch of the trending ranking is kept.
+        assert_eq!(storage.trending().len(), 3);
+    }
+}
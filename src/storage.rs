@@ -0,0 +1,1074 @@
+//! Storage of the ingested data.
+//!
+//! The pipeline (polling loops, coverage catch-up, webhook ingestion, reports) and the websocket
+//! handlers only use the `Storage` trait, implemented by `DatabaseOps` on MongoDB and by
+//! `MemoryStorage`, an in-memory double with the same semantics, so they can be tested without a
+//! MongoDB instance. Operations tied to MongoDB itself (backups, purges of the raw results) still
+//! take a `DatabaseOps`, reachable with `Storage::database`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use crate::alerts::{AlertMute, AlertRecord, AlertState};
+use crate::config::{EntitiesConfig, KeywordsConfig};
+use crate::db::{dedup_key, DatabaseOps, OpError};
+use crate::digest::ArticleNotification;
+use crate::export::{ExportRun, ExportWatermark};
+use crate::entities::{EntityExtractor, EntityKind};
+use crate::keywords::KeywordExtractor;
+use crate::normalize::NormalizedArticle;
+use crate::sentiment::SentimentLabel;
+use crate::server_types::FMPMarketSentiment;
+use crate::snapshots::SentimentSnapshot;
+use crate::timeline::Timeline;
+use crate::utils::now;
+
+/// Articles to find with `Storage::filtered_articles`. Empty lists and missing bounds match any
+/// article.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArticleFilter {
+    pub tickers: Vec<String>,
+    /// Providers the articles were fetched from, e.g. `finnhub`.
+    pub providers: Vec<String>,
+    /// Publishers, e.g. `Reuters`.
+    pub sources: Vec<String>,
+    /// Bounds of the publication time.
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+impl ArticleFilter {
+    pub fn matches(&self, article: &NormalizedArticle) -> bool {
+        let published_at = article.published_at.as_deref();
+        (self.tickers.is_empty() || article.tickers.iter().any(|t| self.tickers.iter().any(|ticker| ticker.eq_ignore_ascii_case(t))))
+            && (self.providers.is_empty() || self.providers.contains(&article.provider))
+            && (self.sources.is_empty() || article.source.as_ref().is_some_and(|s| self.sources.contains(s)))
+            && self.from.as_deref().is_none_or(|from| published_at.is_some_and(|t| t >= from))
+            && self.to.as_deref().is_none_or(|to| published_at.is_some_and(|t| t <= to))
+    }
+}
+
+/// Cursor over the stored articles in storage order, see `Storage::stored_after`.
+///
+/// Positions are sequence numbers rather than storage times: times have a one second precision
+/// and do not follow the order in which the articles became readable.
+#[derive(Debug, Clone)]
+pub struct StoredFeed {
+    position: FeedPosition,
+}
+#[derive(Debug, Clone)]
+enum FeedPosition {
+    /// Storage time to start from, turned into a sequence number by the first batch.
+    Since(String),
+    /// Sequence number of the last article read.
+    After(u64),
+}
+impl StoredFeed {
+    /// Feed of the articles stored since `since` (RFC 3339).
+    pub fn since(since: String) -> Self {
+        Self { position: FeedPosition::Since(since) }
+    }
+
+    /// Feed of the articles stored after the one numbered `seq`, e.g. a `position` saved earlier.
+    pub fn after(seq: u64) -> Self {
+        Self { position: FeedPosition::After(seq) }
+    }
+
+    /// Sequence number of the last article read, `None` before the first batch.
+    pub fn position(&self) -> Option<u64> {
+        match self.position {
+            FeedPosition::Since(_) => None,
+            FeedPosition::After(seq) => Some(seq),
+        }
+    }
+
+    /// Articles stored since the last batch, at most `limit`, lowest sequence number first.
+    pub async fn next_batch(&mut self, storage: &dyn Storage, limit: i64) -> Result<Vec<SequencedArticle>, OpError> {
+        let seq = match &self.position {
+            FeedPosition::Since(since) => storage.seq_before(since).await?,
+            FeedPosition::After(seq) => *seq,
+        };
+        self.position = FeedPosition::After(seq);
+        let batch = storage.stored_after(seq, limit).await?;
+        if let Some(last) = batch.last() {
+            self.position = FeedPosition::After(last.seq);
+        }
+        Ok(batch)
+    }
+}
+
+/// An article with the sequence number and time of its storage, see `Storage::stored_after`.
+#[derive(Debug, Clone)]
+pub struct SequencedArticle {
+    pub seq: u64,
+    pub stored_at: String,
+    pub article: NormalizedArticle,
+}
+
+/// Boxed future returned by `Storage` methods, so the trait stays object safe.
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Times are RFC 3339 strings, compared as such like MongoDB does.
+pub trait Storage: Send + Sync {
+    /// Stores articles with their dedup entry, returning how many were new. Articles already stored
+    /// are replaced by a new version when their content changed.
+    fn store_articles<'a>(&'a self, articles: &'a [NormalizedArticle]) -> StorageFuture<'a, Result<usize, OpError>>;
+
+    /// Soft-deletes an article. Returns it as now stored, `None` when no such article is stored.
+    fn soft_delete_article<'a>(&'a self, provider: &'a str, id: &'a str, reason: &'a str) -> StorageFuture<'a, Result<Option<NormalizedArticle>, OpError>>;
+
+    /// Erases the content of an article. Returns what remains of it, `None` when no such article
+    /// is stored.
+    fn redact_article<'a>(&'a self, provider: &'a str, id: &'a str, reason: &'a str) -> StorageFuture<'a, Result<Option<NormalizedArticle>, OpError>>;
+
+    /// Records a successful poll of `source`, see `coverage.rs`.
+    fn record_poll<'a>(&'a self, source: &'a str, polled_at: &'a str) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// Times `source` was polled successfully between `from` and `to`, oldest first.
+    fn poll_times<'a>(&'a self, source: &'a str, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<String>, OpError>>;
+
+    /// Articles published between `from` and `to` about one of `tickers`, newest first.
+    fn articles_with_tickers<'a>(&'a self, tickers: &'a [String], from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>>;
+
+    /// Articles published between `from` and `to` with one of the harmonized `labels`, newest first.
+    fn articles_with_sentiment<'a>(&'a self, labels: &'a [SentimentLabel], from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>>;
+
+    /// Number of articles per harmonized sentiment among those published between `from` and `to`.
+    fn sentiment_counts<'a>(&'a self, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<BTreeMap<SentimentLabel, u64>, OpError>>;
+
+    /// Articles published since `since`, newest first.
+    fn latest_articles<'a>(&'a self, since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>>;
+
+    /// Articles matching `filter`, newest first.
+    fn filtered_articles<'a>(&'a self, filter: &'a ArticleFilter, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>>;
+
+    /// Articles stored with a sequence number above `seq`, lowest first. Every new article takes
+    /// the next number, so they are read in storage order.
+    fn stored_after(&self, seq: u64, limit: i64) -> StorageFuture<'_, Result<Vec<SequencedArticle>, OpError>>;
+
+    /// Sequence number of the last article stored before `since` (RFC 3339), 0 when none was.
+    fn seq_before<'a>(&'a self, since: &'a str) -> StorageFuture<'a, Result<u64, OpError>>;
+
+    /// Sequence number of the last article stored, 0 when none was.
+    fn last_seq(&self) -> StorageFuture<'_, Result<u64, OpError>>;
+
+    /// Articles published since `since` with one of `keywords`, newest first.
+    fn articles_with_keywords<'a>(&'a self, keywords: &'a [String], since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>>;
+
+    /// Articles published since `since` in one of the taxonomy `categories`, newest first.
+    fn articles_with_categories<'a>(&'a self, categories: &'a [String], since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>>;
+
+    /// Articles published since `since` naming one of the entities `names`, of `kind` if given, newest first.
+    fn articles_with_entities<'a>(&'a self, names: &'a [String], kind: Option<EntityKind>, since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>>;
+
+    /// Most frequent keywords of the articles published between `from` and `to`, with their article count.
+    fn trending_keywords<'a>(&'a self, from: &'a str, to: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<(String, u64)>, OpError>>;
+
+    /// Tickers of each article published between `from` and `to` with at least two, see `comentions.rs`.
+    fn ticker_sets<'a>(&'a self, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<Vec<String>>, OpError>>;
+
+    /// Adds Reddit mention counts to the social sentiment record of their symbol and hour, see `reddit.rs`.
+    fn add_reddit_mentions<'a>(&'a self, records: &'a [FMPMarketSentiment]) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// Appends a fetch of the trending social sentiment ranking, see `snapshots.rs`.
+    fn insert_trending<'a>(&'a self, records: &'a [FMPMarketSentiment]) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// When the last article was stored, `None` when none was.
+    fn last_stored_at(&self) -> StorageFuture<'_, Result<Option<String>, OpError>>;
+
+    /// Newest publication time fetched per provider, see `watermark.rs`.
+    fn watermarks(&self) -> StorageFuture<'_, Result<HashMap<String, String>, OpError>>;
+
+    /// Saves the watermark of `provider`. A watermark never moves back.
+    fn save_watermark<'a>(&'a self, provider: &'a str, published_at: &'a str) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// Stores a timeline, replacing the one of the same ticker and window, see `timeline.rs`.
+    fn save_timeline<'a>(&'a self, timeline: &'a Timeline) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// The alert of `id` (`rule:subject`), see `alerts.rs`.
+    fn alert<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Result<Option<AlertRecord>, OpError>>;
+
+    /// Alerts in `state`, all of them when `None`, last fired first.
+    fn alerts(&self, state: Option<AlertState>) -> StorageFuture<'_, Result<Vec<AlertRecord>, OpError>>;
+
+    /// Stores an alert, replacing the one of the same rule and subject.
+    fn save_alert<'a>(&'a self, alert: &'a AlertRecord) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// Alert mutes, expired ones included.
+    fn alert_mutes(&self) -> StorageFuture<'_, Result<Vec<AlertMute>, OpError>>;
+
+    /// Stores a mute, replacing the one of the same rule and subject.
+    fn save_alert_mute<'a>(&'a self, mute: &'a AlertMute) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// Removes the mute of `id`. Returns `false` when there is none.
+    fn remove_alert_mute<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Result<bool, OpError>>;
+
+    /// Notification records of the articles of `keys`, see `digest.rs`.
+    fn article_notifications<'a>(&'a self, keys: &'a [String]) -> StorageFuture<'a, Result<Vec<ArticleNotification>, OpError>>;
+
+    /// Stores a notification record, replacing the one of the same article.
+    fn save_article_notification<'a>(&'a self, notification: &'a ArticleNotification) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// Last run of each export job, by job name, see `export.rs`.
+    fn export_runs(&self) -> StorageFuture<'_, Result<Vec<ExportRun>, OpError>>;
+
+    /// Stores an export run, replacing the previous run of its job.
+    fn save_export_run<'a>(&'a self, run: &'a ExportRun) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// Position of the last export to `destination`, `None` before the first one.
+    fn export_watermark<'a>(&'a self, destination: &'a str) -> StorageFuture<'a, Result<Option<ExportWatermark>, OpError>>;
+
+    /// Stores an export watermark, replacing the previous one of its destination.
+    fn save_export_watermark<'a>(&'a self, watermark: &'a ExportWatermark) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// Latest social sentiment of `symbols`, of every symbol when empty, by symbol, see `snapshots.rs`.
+    fn sentiment_snapshots<'a>(&'a self, symbols: &'a [String]) -> StorageFuture<'a, Result<Vec<SentimentSnapshot>, OpError>>;
+
+    /// Stores a sentiment snapshot, replacing the previous one of its symbol.
+    fn save_sentiment_snapshot<'a>(&'a self, snapshot: &'a SentimentSnapshot) -> StorageFuture<'a, Result<(), OpError>>;
+
+    /// The MongoDB storage, for the operations not covered by this trait.
+    fn database(&self) -> Option<&DatabaseOps> {
+        None
+    }
+}
+
+impl Storage for DatabaseOps {
+    fn store_articles<'a>(&'a self, articles: &'a [NormalizedArticle]) -> StorageFuture<'a, Result<usize, OpError>> {
+        Box::pin(DatabaseOps::store_articles(self, articles))
+    }
+
+    fn soft_delete_article<'a>(&'a self, provider: &'a str, id: &'a str, reason: &'a str) -> StorageFuture<'a, Result<Option<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::soft_delete_article(self, provider, id, reason))
+    }
+
+    fn redact_article<'a>(&'a self, provider: &'a str, id: &'a str, reason: &'a str) -> StorageFuture<'a, Result<Option<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::redact_article(self, provider, id, reason))
+    }
+
+    fn record_poll<'a>(&'a self, source: &'a str, polled_at: &'a str) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::record_poll(self, source, polled_at))
+    }
+
+    fn poll_times<'a>(&'a self, source: &'a str, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<String>, OpError>> {
+        Box::pin(DatabaseOps::poll_times(self, source, from, to))
+    }
+
+    fn articles_with_tickers<'a>(&'a self, tickers: &'a [String], from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::articles_with_tickers(self, tickers, from, to))
+    }
+
+    fn articles_with_sentiment<'a>(&'a self, labels: &'a [SentimentLabel], from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::articles_with_sentiment(self, labels, from, to))
+    }
+
+    fn sentiment_counts<'a>(&'a self, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<BTreeMap<SentimentLabel, u64>, OpError>> {
+        Box::pin(DatabaseOps::sentiment_counts(self, from, to))
+    }
+
+    fn latest_articles<'a>(&'a self, since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::latest_articles(self, since, limit))
+    }
+
+    fn filtered_articles<'a>(&'a self, filter: &'a ArticleFilter, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::filtered_articles(self, filter, limit))
+    }
+
+    fn stored_after(&self, seq: u64, limit: i64) -> StorageFuture<'_, Result<Vec<SequencedArticle>, OpError>> {
+        Box::pin(DatabaseOps::stored_after(self, seq, limit))
+    }
+
+    fn seq_before<'a>(&'a self, since: &'a str) -> StorageFuture<'a, Result<u64, OpError>> {
+        Box::pin(DatabaseOps::seq_before(self, since))
+    }
+
+    fn last_seq(&self) -> StorageFuture<'_, Result<u64, OpError>> {
+        Box::pin(DatabaseOps::last_seq(self))
+    }
+
+    fn articles_with_keywords<'a>(&'a self, keywords: &'a [String], since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::articles_with_keywords(self, keywords, since, limit))
+    }
+
+    fn articles_with_categories<'a>(&'a self, categories: &'a [String], since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::articles_with_categories(self, categories, since, limit))
+    }
+
+    fn articles_with_entities<'a>(&'a self, names: &'a [String], kind: Option<EntityKind>, since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(DatabaseOps::articles_with_entities(self, names, kind, since, limit))
+    }
+
+    fn trending_keywords<'a>(&'a self, from: &'a str, to: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<(String, u64)>, OpError>> {
+        Box::pin(DatabaseOps::trending_keywords(self, from, to, limit))
+    }
+
+    fn ticker_sets<'a>(&'a self, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<Vec<String>>, OpError>> {
+        Box::pin(DatabaseOps::ticker_sets(self, from, to))
+    }
+
+    fn add_reddit_mentions<'a>(&'a self, records: &'a [FMPMarketSentiment]) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::add_reddit_mentions(self, records))
+    }
+
+    fn insert_trending<'a>(&'a self, records: &'a [FMPMarketSentiment]) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::insert_trending(self, records))
+    }
+
+    fn last_stored_at(&self) -> StorageFuture<'_, Result<Option<String>, OpError>> {
+        Box::pin(DatabaseOps::last_stored_at(self))
+    }
+
+    fn watermarks(&self) -> StorageFuture<'_, Result<HashMap<String, String>, OpError>> {
+        Box::pin(DatabaseOps::watermarks(self))
+    }
+
+    fn save_watermark<'a>(&'a self, provider: &'a str, published_at: &'a str) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::save_watermark(self, provider, published_at))
+    }
+
+    fn save_timeline<'a>(&'a self, timeline: &'a Timeline) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::save_timeline(self, timeline))
+    }
+
+    fn alert<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Result<Option<AlertRecord>, OpError>> {
+        Box::pin(DatabaseOps::alert(self, id))
+    }
+
+    fn alerts(&self, state: Option<AlertState>) -> StorageFuture<'_, Result<Vec<AlertRecord>, OpError>> {
+        Box::pin(DatabaseOps::alerts(self, state))
+    }
+
+    fn save_alert<'a>(&'a self, alert: &'a AlertRecord) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::save_alert(self, alert))
+    }
+
+    fn alert_mutes(&self) -> StorageFuture<'_, Result<Vec<AlertMute>, OpError>> {
+        Box::pin(DatabaseOps::alert_mutes(self))
+    }
+
+    fn save_alert_mute<'a>(&'a self, mute: &'a AlertMute) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::save_alert_mute(self, mute))
+    }
+
+    fn remove_alert_mute<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Result<bool, OpError>> {
+        Box::pin(DatabaseOps::remove_alert_mute(self, id))
+    }
+
+    fn article_notifications<'a>(&'a self, keys: &'a [String]) -> StorageFuture<'a, Result<Vec<ArticleNotification>, OpError>> {
+        Box::pin(DatabaseOps::article_notifications(self, keys))
+    }
+
+    fn save_article_notification<'a>(&'a self, notification: &'a ArticleNotification) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::save_article_notification(self, notification))
+    }
+
+    fn export_runs(&self) -> StorageFuture<'_, Result<Vec<ExportRun>, OpError>> {
+        Box::pin(DatabaseOps::export_runs(self))
+    }
+
+    fn save_export_run<'a>(&'a self, run: &'a ExportRun) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::save_export_run(self, run))
+    }
+
+    fn export_watermark<'a>(&'a self, destination: &'a str) -> StorageFuture<'a, Result<Option<ExportWatermark>, OpError>> {
+        Box::pin(DatabaseOps::export_watermark(self, destination))
+    }
+
+    fn save_export_watermark<'a>(&'a self, watermark: &'a ExportWatermark) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::save_export_watermark(self, watermark))
+    }
+
+    fn sentiment_snapshots<'a>(&'a self, symbols: &'a [String]) -> StorageFuture<'a, Result<Vec<SentimentSnapshot>, OpError>> {
+        Box::pin(DatabaseOps::sentiment_snapshots(self, symbols))
+    }
+
+    fn save_sentiment_snapshot<'a>(&'a self, snapshot: &'a SentimentSnapshot) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(DatabaseOps::save_sentiment_snapshot(self, snapshot))
+    }
+
+    fn database(&self) -> Option<&DatabaseOps> {
+        Some(self)
+    }
+}
+
+/// A stored article and its removal flags.
+#[derive(Debug, Clone)]
+struct MemoryArticle {
+    article: NormalizedArticle,
+    deleted: bool,
+    redacted: bool,
+    revision: u32,
+    stored_at: String,
+    seq: u64,
+}
+
+#[derive(Debug, Default)]
+struct MemoryData {
+    /// Articles by dedup key, in insertion order.
+    articles: Vec<(String, MemoryArticle)>,
+    /// Sequence number of each dedup key, kept apart from the articles like the dedup collection.
+    dedup: HashMap<String, u64>,
+    last_seq: u64,
+    social_sentiment: Vec<FMPMarketSentiment>,
+    trending: Vec<FMPMarketSentiment>,
+    watermarks: HashMap<String, String>,
+    timelines: Vec<Timeline>,
+    alerts: Vec<AlertRecord>,
+    alert_mutes: Vec<AlertMute>,
+    notifications: Vec<ArticleNotification>,
+    export_runs: Vec<ExportRun>,
+    export_watermarks: Vec<ExportWatermark>,
+    sentiment_snapshots: Vec<SentimentSnapshot>,
+    polls: HashMap<String, Vec<String>>,
+}
+
+/// In-memory `Storage`, for tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    data: Mutex<MemoryData>,
+    keywords: Option<KeywordExtractor>,
+    entities: Option<EntityExtractor>,
+}
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extracts the keywords of the stored articles lacking them, like `DatabaseOps::with_keywords`.
+    pub fn with_keywords(mut self, config: &KeywordsConfig) -> Self {
+        self.keywords = config.enabled.then(|| KeywordExtractor::new(config));
+        self
+    }
+
+    /// Extracts the named entities of the stored articles lacking them, like `DatabaseOps::with_entities`.
+    pub fn with_entities(mut self, config: &EntitiesConfig) -> Self {
+        self.entities = config.enabled.then(|| EntityExtractor::new(config));
+        self
+    }
+
+    /// Stored articles, removed ones included, in insertion order.
+    pub fn articles(&self) -> Vec<NormalizedArticle> {
+        self.lock().articles.iter().map(|(_, stored)| stored.article.clone()).collect()
+    }
+
+    /// Revision of a stored article: 0 when first stored, incremented by each new version.
+    pub fn revision(&self, provider: &str, id: &str) -> Option<u32> {
+        let key = format!("{}:{}", provider, id);
+        self.lock().articles.iter().find(|(k, _)| *k == key).map(|(_, stored)| stored.revision)
+    }
+
+    pub fn social_sentiment(&self) -> Vec<FMPMarketSentiment> {
+        self.lock().social_sentiment.clone()
+    }
+
+    pub fn trending(&self) -> Vec<FMPMarketSentiment> {
+        self.lock().trending.clone()
+    }
+
+    pub fn timelines(&self) -> Vec<Timeline> {
+        self.lock().timelines.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryData> {
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn store(&self, article: &NormalizedArticle) -> bool {
+        // Rejected like `DatabaseOps::store_article` does, the key would be `provider:`.
+        if article.id.trim().is_empty() {
+            return false;
+        }
+        let mut article = article.clone();
+        if let Some(extractor) = &self.keywords {
+            if article.keywords.is_empty() {
+                article.keywords = extractor.keywords_of(&article);
+            }
+        }
+        if let Some(extractor) = &self.entities {
+            if article.entities.is_empty() {
+                article.entities = extractor.entities_of(&article);
+            }
+        }
+        let key = dedup_key(&article);
+        let mut guard = self.lock();
+        let data = &mut *guard;
+        match data.articles.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, stored)) => {
+                // Removed articles are never brought back by a re-delivery.
+                if stored.deleted || stored.redacted {
+                    return false;
+                }
+                // Watchlist tags accumulate across deliveries, like `$addToSet` on MongoDB.
+                let mut watchlist = stored.article.watchlist.clone();
+                watchlist.extend(article.watchlist.iter().filter(|s| !stored.article.watchlist.contains(s)).cloned());
+                if !stored.article.same_content(&article) {
+                    stored.article = article;
+                    stored.revision += 1;
+                }
+                stored.article.watchlist = watchlist;
+                false
+            }
+            None => {
+                // A dedup entry left without its article keeps its number, see `DatabaseOps::restore_article`.
+                let seq = match data.dedup.get(&key) {
+                    Some(seq) => *seq,
+                    None => {
+                        data.last_seq += 1;
+                        data.dedup.insert(key.clone(), data.last_seq);
+                        data.last_seq
+                    }
+                };
+                data.articles.push((key, MemoryArticle { article, deleted: false, redacted: false, revision: 0, stored_at: now(), seq }));
+                true
+            }
+        }
+    }
+
+    fn remove(&self, provider: &str, id: &str, redact: bool) -> Option<NormalizedArticle> {
+        let mut data = self.lock();
+        let (_, stored) = data.articles.iter_mut().find(|(_, stored)| stored.article.provider == provider && stored.article.id == id)?;
+        if redact {
+            stored.redacted = true;
+            stored.article.title = None;
+            stored.article.summary = None;
+            stored.article.authors.clear();
+            stored.article.keywords.clear();
+            stored.article.translation = None;
+            stored.article.entities.clear();
+        } else {
+            stored.deleted = true;
+        }
+        Some(stored.article.clone())
+    }
+
+    /// Loses the article of `key` but not its dedup entry, like a crash between the two writes.
+    #[cfg(test)]
+    fn lose_article(&self, key: &str) {
+        self.lock().articles.retain(|(k, _)| k != key);
+    }
+
+    /// Articles not deleted matching `predicate`, newest first.
+    fn find<F: Fn(&NormalizedArticle, &str) -> bool>(&self, predicate: F) -> Vec<NormalizedArticle> {
+        let data = self.lock();
+        let mut found: Vec<NormalizedArticle> = data
+            .articles
+            .iter()
+            .filter(|(_, stored)| !stored.deleted)
+            .filter_map(|(_, stored)| {
+                let published_at = stored.article.published_at.as_deref()?;
+                predicate(&stored.article, published_at).then(|| stored.article.clone())
+            })
+            .collect();
+        found.sort_by(|a, b| b.published_at.cmp(&a.published_at));
+        found
+    }
+}
+
+fn truncate<T>(mut articles: Vec<T>, limit: i64) -> Vec<T> {
+    if limit > 0 {
+        articles.truncate(limit as usize);
+    }
+    articles
+}
+
+impl Storage for MemoryStorage {
+    fn store_articles<'a>(&'a self, articles: &'a [NormalizedArticle]) -> StorageFuture<'a, Result<usize, OpError>> {
+        Box::pin(async move { Ok(articles.iter().filter(|article| self.store(article)).count()) })
+    }
+
+    fn soft_delete_article<'a>(&'a self, provider: &'a str, id: &'a str, _reason: &'a str) -> StorageFuture<'a, Result<Option<NormalizedArticle>, OpError>> {
+        Box::pin(async move { Ok(self.remove(provider, id, false)) })
+    }
+
+    fn redact_article<'a>(&'a self, provider: &'a str, id: &'a str, _reason: &'a str) -> StorageFuture<'a, Result<Option<NormalizedArticle>, OpError>> {
+        Box::pin(async move { Ok(self.remove(provider, id, true)) })
+    }
+
+    fn record_poll<'a>(&'a self, source: &'a str, polled_at: &'a str) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            self.lock().polls.entry(source.to_string()).or_default().push(polled_at.to_string());
+            Ok(())
+        })
+    }
+
+    fn poll_times<'a>(&'a self, source: &'a str, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<String>, OpError>> {
+        Box::pin(async move {
+            let data = self.lock();
+            let mut times: Vec<String> = data.polls.get(source).into_iter().flatten()
+                .filter(|t| t.as_str() >= from && t.as_str() <= to)
+                .cloned()
+                .collect();
+            times.sort();
+            Ok(times)
+        })
+    }
+
+    fn articles_with_tickers<'a>(&'a self, tickers: &'a [String], from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(async move {
+            let tickers: Vec<String> = tickers.iter().map(|t| t.to_uppercase()).collect();
+            Ok(self.find(|article, t| t >= from && t <= to && article.tickers.iter().any(|ticker| tickers.contains(ticker))))
+        })
+    }
+
+    fn articles_with_sentiment<'a>(&'a self, labels: &'a [SentimentLabel], from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(async move {
+            Ok(self.find(|article, t| t >= from && t <= to && article.sentiment.is_some_and(|s| labels.contains(&s))))
+        })
+    }
+
+    fn sentiment_counts<'a>(&'a self, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<BTreeMap<SentimentLabel, u64>, OpError>> {
+        Box::pin(async move {
+            let mut counts = BTreeMap::new();
+            for article in self.find(|_, t| t >= from && t <= to) {
+                if let Some(label) = article.sentiment {
+                    *counts.entry(label).or_insert(0) += 1;
+                }
+            }
+            Ok(counts)
+        })
+    }
+
+    fn latest_articles<'a>(&'a self, since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(async move { Ok(truncate(self.find(|_, t| t >= since), limit)) })
+    }
+
+    fn filtered_articles<'a>(&'a self, filter: &'a ArticleFilter, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(async move {
+            // Like on MongoDB, articles without a publication time only match without bounds.
+            let data = self.lock();
+            let mut found: Vec<NormalizedArticle> = data
+                .articles
+                .iter()
+                .filter(|(_, stored)| !stored.deleted && filter.matches(&stored.article))
+                .map(|(_, stored)| stored.article.clone())
+                .collect();
+            found.sort_by(|a, b| b.published_at.cmp(&a.published_at));
+            Ok(truncate(found, limit))
+        })
+    }
+
+    fn stored_after(&self, seq: u64, limit: i64) -> StorageFuture<'_, Result<Vec<SequencedArticle>, OpError>> {
+        Box::pin(async move {
+            let data = self.lock();
+            let stored: Vec<SequencedArticle> = data
+                .articles
+                .iter()
+                .filter(|(_, stored)| !stored.deleted && stored.seq > seq)
+                .map(|(_, stored)| SequencedArticle { seq: stored.seq, stored_at: stored.stored_at.clone(), article: stored.article.clone() })
+                .collect();
+            Ok(truncate(stored, limit))
+        })
+    }
+
+    fn seq_before<'a>(&'a self, since: &'a str) -> StorageFuture<'a, Result<u64, OpError>> {
+        Box::pin(async move {
+            let data = self.lock();
+            Ok(data.articles.iter().filter(|(_, stored)| stored.stored_at.as_str() < since).map(|(_, stored)| stored.seq).max().unwrap_or(0))
+        })
+    }
+
+    fn last_seq(&self) -> StorageFuture<'_, Result<u64, OpError>> {
+        Box::pin(async move { Ok(self.lock().last_seq) })
+    }
+
+    fn articles_with_keywords<'a>(&'a self, keywords: &'a [String], since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(async move {
+            let keywords: Vec<String> = keywords.iter().map(|k| k.to_lowercase()).collect();
+            let found = self.find(|article, t| t >= since && article.keywords.iter().any(|k| keywords.contains(k)));
+            Ok(truncate(found, limit))
+        })
+    }
+
+    fn articles_with_categories<'a>(&'a self, categories: &'a [String], since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(async move {
+            let found = self.find(|article, t| t >= since && article.categories.iter().any(|c| categories.contains(c)));
+            Ok(truncate(found, limit))
+        })
+    }
+
+    fn articles_with_entities<'a>(&'a self, names: &'a [String], kind: Option<EntityKind>, since: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<NormalizedArticle>, OpError>> {
+        Box::pin(async move {
+            let named = |article: &NormalizedArticle| {
+                article.entities.iter().any(|e| names.contains(&e.name) && kind.is_none_or(|kind| e.kind == kind))
+            };
+            let found = self.find(|article, t| t >= since && named(article));
+            Ok(truncate(found, limit))
+        })
+    }
+
+    fn trending_keywords<'a>(&'a self, from: &'a str, to: &'a str, limit: i64) -> StorageFuture<'a, Result<Vec<(String, u64)>, OpError>> {
+        Box::pin(async move {
+            let mut counts: HashMap<String, u64> = HashMap::new();
+            for article in self.find(|_, t| t >= from && t <= to) {
+                for keyword in article.keywords {
+                    *counts.entry(keyword).or_insert(0) += 1;
+                }
+            }
+            let mut trending: Vec<(String, u64)> = counts.into_iter().collect();
+            trending.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            if limit > 0 {
+                trending.truncate(limit as usize);
+            }
+            Ok(trending)
+        })
+    }
+
+    fn ticker_sets<'a>(&'a self, from: &'a str, to: &'a str) -> StorageFuture<'a, Result<Vec<Vec<String>>, OpError>> {
+        Box::pin(async move {
+            let found = self.find(|article, t| t >= from && t <= to && article.tickers.len() > 1);
+            Ok(found.into_iter().map(|article| article.tickers).collect())
+        })
+    }
+
+    fn add_reddit_mentions<'a>(&'a self, records: &'a [FMPMarketSentiment]) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            for record in records {
+                let bucket = data.social_sentiment.iter_mut()
+                    .find(|stored| (&stored.name, &stored.symbol, &stored.date) == (&record.name, &record.symbol, &record.date));
+                match bucket {
+                    Some(stored) => {
+                        stored.reddit_posts = Some(stored.reddit_posts.unwrap_or(0) + record.reddit_posts.unwrap_or(0));
+                        stored.reddit_comments = Some(stored.reddit_comments.unwrap_or(0) + record.reddit_comments.unwrap_or(0));
+                        stored.reddit_score = Some(stored.reddit_score.unwrap_or(0) + record.reddit_score.unwrap_or(0));
+                    }
+                    None => data.social_sentiment.push(record.clone()),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn insert_trending<'a>(&'a self, records: &'a [FMPMarketSentiment]) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            self.lock().trending.extend_from_slice(records);
+            Ok(())
+        })
+    }
+
+    fn last_stored_at(&self) -> StorageFuture<'_, Result<Option<String>, OpError>> {
+        Box::pin(async move { Ok(self.lock().articles.iter().map(|(_, stored)| stored.stored_at.clone()).max()) })
+    }
+
+    fn watermarks(&self) -> StorageFuture<'_, Result<HashMap<String, String>, OpError>> {
+        Box::pin(async move {
+            let data = self.lock();
+            let mut marks = data.watermarks.clone();
+            for (_, stored) in &data.articles {
+                if let Some(published_at) = &stored.article.published_at {
+                    let mark = marks.entry(stored.article.provider.clone()).or_default();
+                    if published_at > mark {
+                        *mark = published_at.clone();
+                    }
+                }
+            }
+            Ok(marks)
+        })
+    }
+
+    fn save_watermark<'a>(&'a self, provider: &'a str, published_at: &'a str) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            let mark = data.watermarks.entry(provider.to_string()).or_default();
+            if published_at > mark.as_str() {
+                *mark = published_at.to_string();
+            }
+            Ok(())
+        })
+    }
+
+    fn save_timeline<'a>(&'a self, timeline: &'a Timeline) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            data.timelines.retain(|t| t.id != timeline.id);
+            data.timelines.push(timeline.clone());
+            Ok(())
+        })
+    }
+
+    fn alert<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Result<Option<AlertRecord>, OpError>> {
+        Box::pin(async move { Ok(self.lock().alerts.iter().find(|a| a.id == id).cloned()) })
+    }
+
+    fn alerts(&self, state: Option<AlertState>) -> StorageFuture<'_, Result<Vec<AlertRecord>, OpError>> {
+        Box::pin(async move {
+            let mut alerts: Vec<AlertRecord> = self.lock().alerts.iter().filter(|a| state.is_none_or(|s| a.state == s)).cloned().collect();
+            alerts.sort_by(|a, b| b.last_fired_at.cmp(&a.last_fired_at));
+            Ok(alerts)
+        })
+    }
+
+    fn save_alert<'a>(&'a self, alert: &'a AlertRecord) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            data.alerts.retain(|a| a.id != alert.id);
+            data.alerts.push(alert.clone());
+            Ok(())
+        })
+    }
+
+    fn alert_mutes(&self) -> StorageFuture<'_, Result<Vec<AlertMute>, OpError>> {
+        Box::pin(async move { Ok(self.lock().alert_mutes.clone()) })
+    }
+
+    fn save_alert_mute<'a>(&'a self, mute: &'a AlertMute) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            data.alert_mutes.retain(|m| m.id != mute.id);
+            data.alert_mutes.push(mute.clone());
+            Ok(())
+        })
+    }
+
+    fn remove_alert_mute<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Result<bool, OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            let before = data.alert_mutes.len();
+            data.alert_mutes.retain(|m| m.id != id);
+            Ok(data.alert_mutes.len() < before)
+        })
+    }
+
+    fn article_notifications<'a>(&'a self, keys: &'a [String]) -> StorageFuture<'a, Result<Vec<ArticleNotification>, OpError>> {
+        Box::pin(async move { Ok(self.lock().notifications.iter().filter(|n| keys.contains(&n.key)).cloned().collect()) })
+    }
+
+    fn save_article_notification<'a>(&'a self, notification: &'a ArticleNotification) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            data.notifications.retain(|n| n.key != notification.key);
+            data.notifications.push(notification.clone());
+            Ok(())
+        })
+    }
+
+    fn export_runs(&self) -> StorageFuture<'_, Result<Vec<ExportRun>, OpError>> {
+        Box::pin(async move {
+            let mut runs = self.lock().export_runs.clone();
+            runs.sort_by(|a, b| a.job.cmp(&b.job));
+            Ok(runs)
+        })
+    }
+
+    fn save_export_run<'a>(&'a self, run: &'a ExportRun) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            data.export_runs.retain(|r| r.job != run.job);
+            data.export_runs.push(run.clone());
+            Ok(())
+        })
+    }
+
+    fn export_watermark<'a>(&'a self, destination: &'a str) -> StorageFuture<'a, Result<Option<ExportWatermark>, OpError>> {
+        Box::pin(async move { Ok(self.lock().export_watermarks.iter().find(|w| w.destination == destination).cloned()) })
+    }
+
+    fn save_export_watermark<'a>(&'a self, watermark: &'a ExportWatermark) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            data.export_watermarks.retain(|w| w.destination != watermark.destination);
+            data.export_watermarks.push(watermark.clone());
+            Ok(())
+        })
+    }
+
+    fn sentiment_snapshots<'a>(&'a self, symbols: &'a [String]) -> StorageFuture<'a, Result<Vec<SentimentSnapshot>, OpError>> {
+        Box::pin(async move {
+            let mut snapshots: Vec<SentimentSnapshot> = self.lock().sentiment_snapshots.iter()
+                .filter(|s| symbols.is_empty() || symbols.contains(&s.symbol))
+                .cloned()
+                .collect();
+            snapshots.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+            Ok(snapshots)
+        })
+    }
+
+    fn save_sentiment_snapshot<'a>(&'a self, snapshot: &'a SentimentSnapshot) -> StorageFuture<'a, Result<(), OpError>> {
+        Box::pin(async move {
+            let mut data = self.lock();
+            data.sentiment_snapshots.retain(|s| s.symbol != snapshot.symbol);
+            data.sentiment_snapshots.push(snapshot.clone());
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn dedups_and_versions_redelivered_articles() {
+        let storage = MemoryStorage::new();
+        let first = NormalizedArticle::test("1").title("Title 1").published_at("2024-05-01T10:00:00Z").tickers(&["AAPL"]);
+        assert_eq!(storage.store_articles(&[first.clone(), first.clone()]).await.unwrap(), 1);
+
+        let edited = NormalizedArticle { title: Some("Edited".to_string()), ..first.clone() };
+        assert_eq!(storage.store_articles(&[edited]).await.unwrap(), 0);
+        assert_eq!(storage.revision("finnhub", "1"), Some(1));
+        assert_eq!(storage.articles()[0].title.as_deref(), Some("Edited"));
+
+        assert!(storage.soft_delete_article("finnhub", "1", "retracted").await.unwrap().is_some());
+        assert!(storage.soft_delete_article("finnhub", "2", "retracted").await.unwrap().is_none());
+        assert!(storage.articles_with_tickers(&["aapl".to_string()], "2024-05-01T00:00:00Z", "2024-05-02T00:00:00Z").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn stores_again_an_article_lost_after_its_dedup_entry() {
+        let storage = MemoryStorage::new();
+        let article = NormalizedArticle::test("1").title("Title 1");
+        assert_eq!(storage.store_articles(std::slice::from_ref(&article)).await.unwrap(), 1);
+        storage.lose_article("finnhub:1");
+        assert!(storage.articles().is_empty());
+
+        assert_eq!(storage.store_articles(std::slice::from_ref(&article)).await.unwrap(), 1);
+        assert_eq!(storage.articles(), vec![article]);
+        let stored = storage.stored_after(0, 10).await.unwrap();
+        assert_eq!((stored.len(), stored[0].seq, storage.last_seq().await.unwrap()), (1, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn versions_only_a_changed_content_or_sentiment() {
+        let storage = MemoryStorage::new();
+        let article = NormalizedArticle::test("1").title("Title 1").tickers(&["AAPL"]).sentiment_score(0.2);
+        storage.store_articles(std::slice::from_ref(&article)).await.unwrap();
+
+        // Delivery details are not content.
+        let redelivered = article.clone().url("https://example.com/1").source("example.com").published_at("2024-05-01T10:00:00Z");
+        assert!(article.same_content(&redelivered));
+        storage.store_articles(&[redelivered]).await.unwrap();
+        assert_eq!(storage.revision("finnhub", "1"), Some(0));
+
+        let rescored = article.clone().sentiment_score(0.6);
+        assert!(!article.same_content(&rescored));
+        storage.store_articles(&[rescored]).await.unwrap();
+        assert_eq!(storage.revision("finnhub", "1"), Some(1));
+        assert_eq!(storage.articles()[0].sentiment_score, Some(0.6));
+
+        // A redacted article is not brought back by a re-delivery.
+        assert!(storage.redact_article("finnhub", "1", "legal").await.unwrap().is_some());
+        storage.store_articles(&[article.title("Title 2")]).await.unwrap();
+        assert_eq!(storage.revision("finnhub", "1"), Some(1));
+        assert_eq!(storage.articles()[0].title, None);
+    }
+
+    #[tokio::test]
+    async fn rejects_articles_without_an_id() {
+        let storage = MemoryStorage::new();
+        let articles = [NormalizedArticle::test("").title("Title 1"), NormalizedArticle::test(" ").title("Title 2")];
+        assert_eq!(storage.store_articles(&articles).await.unwrap(), 0);
+        assert!(storage.articles().is_empty());
+    }
+
+    #[tokio::test]
+    async fn redaction_clears_the_derived_fields() {
+        let storage = MemoryStorage::new();
+        let article = NormalizedArticle::test("1").title("Titre 1").keywords(&["merger"]).translated("Title 1")
+            .entity("Jane Doe", EntityKind::Person);
+        storage.store_articles(&[article]).await.unwrap();
+
+        let removed = storage.redact_article("finnhub", "1", "legal").await.unwrap();
+        let redacted = &storage.articles()[0];
+        assert_eq!(removed.as_ref(), Some(redacted));
+        assert_eq!(redacted.title, None);
+        assert!(redacted.keywords.is_empty());
+        assert_eq!(redacted.translation, None);
+        assert!(redacted.entities.is_empty());
+    }
+
+    #[tokio::test]
+    async fn feeds_the_articles_in_storage_order() {
+        let storage = MemoryStorage::new();
+        let articles: Vec<NormalizedArticle> = (1..=5).map(|id| NormalizedArticle::test(&id.to_string())).collect();
+        storage.store_articles(&articles).await.unwrap();
+        // Storage times do not follow the storage order, e.g. clocks of several processes.
+        for (_, stored) in storage.lock().articles.iter_mut() {
+            stored.stored_at = if stored.seq == 2 { "2024-05-01T09:59:59Z" } else { "2024-05-01T10:00:00Z" }.to_string();
+        }
+        storage.soft_delete_article("finnhub", "3", "retracted").await.unwrap();
+
+        let mut feed = StoredFeed::since("2024-05-01T00:00:00Z".to_string());
+        let mut read = Vec::new();
+        loop {
+            let batch = feed.next_batch(&storage, 2).await.unwrap();
+            if batch.is_empty() {
+                break;
+            }
+            read.extend(batch.into_iter().map(|stored| stored.article.id));
+        }
+        assert_eq!(read, vec!["1", "2", "4", "5"]);
+        assert_eq!(feed.position(), Some(5));
+
+        // Resumed from a saved position, only the articles stored after it.
+        storage.store_articles(&[NormalizedArticle::test("6")]).await.unwrap();
+        let batch = StoredFeed::after(5).next_batch(&storage, 2).await.unwrap();
+        assert_eq!(batch.iter().map(|stored| stored.article.id.as_str()).collect::<Vec<_>>(), vec!["6"]);
+        assert_eq!(storage.seq_before("2024-05-01T10:00:00Z").await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn accumulates_watchlist_tags() {
+        let storage = MemoryStorage::new();
+        let mut article = NormalizedArticle::test("1").title("Title 1");
+        article.tag_symbol("AAPL");
+        storage.store_articles(&[article.clone()]).await.unwrap();
+        article.watchlist = vec!["MSFT".to_string()];
+        storage.store_articles(&[article.clone()]).await.unwrap();
+        article.title = Some("Edited".to_string());
+        article.watchlist.clear();
+        storage.store_articles(&[article]).await.unwrap();
+
+        assert_eq!(storage.revision("finnhub", "1"), Some(1));
+        assert_eq!(storage.articles()[0].watchlist, vec!["AAPL", "MSFT"]);
+    }
+
+    #[tokio::test]
+    async fn watermarks_never_move_back() {
+        let storage = MemoryStorage::new();
+        storage.store_articles(&[NormalizedArticle::test("1").published_at("2024-05-01T10:00:00Z")]).await.unwrap();
+        storage.save_watermark("finnhub", "2024-05-01T09:00:00Z").await.unwrap();
+        storage.save_watermark("marketaux", "2024-05-01T11:00:00Z").await.unwrap();
+        storage.save_watermark("marketaux", "2024-05-01T08:00:00Z").await.unwrap();
+
+        let marks = storage.watermarks().await.unwrap();
+        assert_eq!(marks.get("finnhub").map(String::as_str), Some("2024-05-01T10:00:00Z"));
+        assert_eq!(marks.get("marketaux").map(String::as_str), Some("2024-05-01T11:00:00Z"));
+    }
+
+    #[tokio::test]
+    async fn queries_match_the_mongodb_semantics() {
+        let storage = MemoryStorage::new();
+        let article = |id: &str, published_at: &str, keywords: &[&str]| {
+            NormalizedArticle::test(id).published_at(published_at).sentiment(SentimentLabel::Neutral).keywords(keywords)
+        };
+        let articles = [
+            article("1", "2024-05-01T10:00:00Z", &["chips", "ai"]),
+            article("2", "2024-05-01T12:00:00Z", &["ai"]),
+            article("3", "2024-04-01T12:00:00Z", &["ai"]),
+        ];
+        storage.store_articles(&articles).await.unwrap();
+
+        let found = storage.articles_with_keywords(&["AI".to_string()], "2024-05-01T00:00:00Z", 10).await.unwrap();
+        assert_eq!(found.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), vec!["2", "1"]);
+        let trending = storage.trending_keywords("2024-05-01T00:00:00Z", "2024-05-02T00:00:00Z", 10).await.unwrap();
+        assert_eq!(trending, vec![("ai".to_string(), 2), ("chips".to_string(), 1)]);
+        let counts = storage.sentiment_counts("2024-01-01T00:00:00Z", "2024-12-31T00:00:00Z").await.unwrap();
+        assert_eq!(counts.get(&SentimentLabel::Neutral), Some(&3));
+    }
+
+    #[tokio::test]
+    async fn finds_articles_by_entity() {
+        let storage = MemoryStorage::new().with_entities(&EntitiesConfig::default());
+        let fed = NormalizedArticle::test("1").title("The Fed holds rates as U.S. inflation cools").published_at("2024-05-01T10:00:00Z");
+        let boe = NormalizedArticle::test("2").title("Bank of England follows the Fed").published_at("2024-05-01T12:00:00Z");
+        storage.store_articles(&[fed, boe]).await.unwrap();
+
+        let names = ["Federal Reserve".to_string()];
+        let found = storage.articles_with_entities(&names, None, "2024-05-01T00:00:00Z", 10).await.unwrap();
+        assert_eq!(found.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), vec!["2", "1"]);
+        let names = ["United States".to_string()];
+        let found = storage.articles_with_entities(&names, Some(EntityKind::Person), "2024-05-01T00:00:00Z", 10).await.unwrap();
+        assert!(found.is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+//! Live article subscriptions of a websocket connection.
+//!
+//! Rather than re-polling, a client subscribes to the articles about some tickers or topics:
+//!
+//! ```text
+//! {"caller": {...}, "target": "subscription", "args": {"command": "subscribe", "params": {"tickers": ["NVDA"], "topics": ["Earnings"]}}}
+//! {"caller": {...}, "target": "subscription", "args": {"command": "unsubscribe", "params": {"subscription": "1"}}}
+//! ```
+//!
+//! `subscribe` answers with the id of the subscription. From then on, every article stored (by
+//! the polling loop or any other process) matching one of the subscriptions of the connection is
+//! pushed to it, looked up every `server.subscription_interval_secs`:
+//!
+//! ```text
+//! {"status": 200, "message": {"event": "article", "subscriptions": ["1"], "seq": 1042, "stored_at": "...", "article": {...}}}
+//! ```
+//!
+//! A subscription matches an article about one of its `tickers` and in one of its `topics` (or
+//! taxonomy categories), a missing list matching any article. `unsubscribe` without a
+//! `subscription` param ends every subscription of the connection. Subscriptions end with the
+//! connection.
+//!
+//! When an article is deleted or redacted with the `delete_article` or `redact_article` admin
+//! command of the server, the subscriptions matching it are told, for the client to drop its copy:
+//!
+//! ```text
+//! {"status": 200, "message": {"event": "article_deleted", "subscriptions": ["1"], "provider": "finnhub", "id": "42", "reason": "retracted"}}
+//! ```
+//!
+//! `seq` is the sequence number of the article, given in storage order and saved with it (see
+//! `Storage::stored_after`). A client reconnecting after a disconnect subscribes again with the
+//! last one it received, `"resume_from": 1042`, to be pushed every matching article stored since,
+//! oldest first, before the new ones. Or it resumes them with the `resume_token` of its
+//! `subscribe` responses, see `resume.rs`. Subscribing with `"ack": true`, the pushed articles are
+//! acknowledged and pushed again until they are, see `acks.rs`.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::acks::Deliveries;
+use crate::normalize::NormalizedArticle;
+use crate::utils::generate_random_key;
+
+/// Length of the resume tokens.
+const TOKEN_LENGTH: usize = 32;
+
+/// Articles a client subscribed to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Subscription {
+    pub id: String,
+    /// Uppercased.
+    pub tickers: Vec<String>,
+    /// Lowercased.
+    pub topics: Vec<String>,
+}
+impl Subscription {
+    pub fn matches(&self, article: &NormalizedArticle) -> bool {
+        (self.tickers.is_empty() || article.tickers.iter().any(|t| self.tickers.contains(&t.to_uppercase())))
+            && (self.topics.is_empty()
+                || article.topics.iter().chain(&article.categories).any(|t| self.topics.contains(&t.to_lowercase())))
+    }
+}
+
+/// An article deleted or redacted by an admin command, see `handle_removal`.
+#[derive(Debug, Clone)]
+pub struct Removal {
+    /// `article_deleted` or `article_redacted`.
+    pub event: &'static str,
+    pub reason: String,
+    /// The article as stored after its removal.
+    pub article: NormalizedArticle,
+}
+impl Removal {
+    /// Event pushed to the subscriptions `ids`.
+    pub fn to_json(&self, ids: &[String]) -> Value {
+        json!({
+            "event": self.event,
+            "subscriptions": ids,
+            "provider": self.article.provider,
+            "id": self.article.id,
+            "reason": self.reason,
+        })
+    }
+}
+
+/// Subscriptions of a connection.
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    active: Vec<Subscription>,
+    next_id: u64,
+    /// Sequence number of the last article looked up, the articles stored after it are pushed.
+    cursor: Option<u64>,
+    /// Token resuming the subscriptions from another connection, see `resume.rs`.
+    token: Option<String>,
+    /// Pushed events waiting for an acknowledgement, in acknowledgement mode, see `acks.rs`.
+    deliveries: Option<Deliveries>,
+}
+impl Subscriptions {
+    /// Adds a subscription pushing the articles stored after the sequence number `after`, `None`
+    /// when `max` are already active.
+    pub fn subscribe(&mut self, tickers: &[String], topics: &[String], after: u64, max: usize) -> Option<Subscription> {
+        if self.active.len() >= max {
+            return None;
+        }
+        self.next_id += 1;
+        let subscription = Subscription {
+            id: self.next_id.to_string(),
+            tickers: tickers.iter().map(|t| t.trim().to_uppercase()).filter(|t| !t.is_empty()).collect(),
+            topics: topics.iter().map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()).collect(),
+        };
+        self.active.push(subscription.clone());
+        // A resumed subscription moves the cursor back, never forward.
+        self.cursor = Some(self.cursor.map_or(after, |cursor| cursor.min(after)));
+        Some(subscription)
+    }
+
+    /// Ends the subscription `id`, or every one. Returns how many ended.
+    pub fn unsubscribe(&mut self, id: Option<&str>) -> usize {
+        let before = self.active.len();
+        self.active.retain(|s| id.is_some_and(|id| s.id != id));
+        if self.active.is_empty() {
+            self.cursor = None;
+        }
+        before - self.active.len()
+    }
+
+    /// Sequence number after which articles are pushed, `None` without subscriptions.
+    pub fn cursor(&self) -> Option<u64> {
+        self.cursor
+    }
+
+    /// Moves the cursor back to `after`, for the articles stored since to be pushed again.
+    pub fn rewind(&mut self, after: u64) {
+        self.cursor = self.cursor.map(|cursor| cursor.min(after));
+    }
+
+    pub fn active(&self) -> &[Subscription] {
+        &self.active
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Token resuming the subscriptions, drawn on the first call.
+    pub fn resume_token(&mut self) -> String {
+        self.token.get_or_insert_with(|| generate_random_key(TOKEN_LENGTH)).clone()
+    }
+
+    /// Switches to the acknowledgement mode, for the connection.
+    pub fn require_acks(&mut self) {
+        self.deliveries.get_or_insert_with(Deliveries::default);
+    }
+
+    /// Pushed events waiting for an acknowledgement, `None` outside the acknowledgement mode.
+    pub fn deliveries(&mut self) -> Option<&mut Deliveries> {
+        self.deliveries.as_mut()
+    }
+
+    /// Token resuming the subscriptions, if one was given out.
+    pub fn issued_token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Moves the cursor from `from` to `to` once the articles in between were pushed. Left as is
+    /// when it moved meanwhile, e.g. back to a resumed subscription.
+    pub fn advance(&mut self, from: u64, to: u64) {
+        if self.cursor == Some(from) {
+            self.cursor = Some(to);
+        }
+    }
+
+    /// Ids of the subscriptions matching `article`.
+    pub fn matching(&self, article: &NormalizedArticle) -> Vec<String> {
+        self.active.iter().filter(|s| s.matches(article)).map(|s| s.id.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn matches_tickers_and_topics() {
+        let article = NormalizedArticle::test("1").tickers(&["NVDA"]).topics(&["Earnings"]).categories(&["semiconductors"]);
+        let strings = |values: &[&str]| -> Vec<String> { values.iter().map(|v| v.to_string()).collect() };
+        let mut subscriptions = Subscriptions::default();
+        assert!(subscriptions.cursor().is_none());
+        subscriptions.subscribe(&strings(&["nvda"]), &[], 10, 3).unwrap();
+        subscriptions.subscribe(&strings(&["AMD"]), &strings(&["earnings"]), 12, 3).unwrap();
+        assert_eq!(subscriptions.cursor(), Some(10));
+        subscriptions.advance(10, 15);
+        subscriptions.subscribe(&[], &strings(&["Semiconductors"]), 4, 3).unwrap();
+        assert_eq!(subscriptions.cursor(), Some(4));
+        subscriptions.advance(15, 20);
+        assert_eq!(subscriptions.cursor(), Some(4));
+        assert!(subscriptions.subscribe(&[], &[], 20, 3).is_none());
+        assert_eq!(subscriptions.matching(&article), vec!["1", "3"]);
+
+        assert_eq!(subscriptions.unsubscribe(Some("1")), 1);
+        assert_eq!(subscriptions.unsubscribe(Some("1")), 0);
+        assert_eq!(subscriptions.matching(&article), vec!["3"]);
+        assert_eq!(subscriptions.unsubscribe(None), 2);
+        assert!(subscriptions.cursor().is_none());
+    }
+}
@@ -0,0 +1,151 @@
+//! systemd integration: readiness notification and watchdog.
+//!
+//! Run the service as `Type=notify` to have systemd wait for `notify_ready`, and set `WatchdogSec=`
+//! to have it restart the service when the watchdog is not pet in time. Every function is a no-op
+//! when the service is not started by systemd (`NOTIFY_SOCKET` unset) or on non-Unix platforms.
+//!
+//! The watchdog follows the polling loop: the scheduler marks the start and the end of every cycle,
+//! and the keepalive stops petting once a cycle has been running for longer than `WatchdogSec`.
+//! Set `WatchdogSec=` above the longest expected cycle, and only with polling enabled.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use tokio::time::{sleep, Instant};
+use tracing::{debug, error, info, warn};
+
+#[cfg(unix)]
+fn notify(state: &[sd_notify::NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, state) {
+        warn!("Failed to notify systemd: {}", e);
+    }
+}
+
+/// Tells systemd the service is ready.
+pub fn notify_ready(status: &str) {
+    #[cfg(unix)]
+    notify(&[sd_notify::NotifyState::Ready, sd_notify::NotifyState::Status(status)]);
+    info!("Ready. | {}", status);
+}
+
+/// Updates the status line shown by `systemctl status`.
+pub fn notify_status(status: &str) {
+    #[cfg(unix)]
+    notify(&[sd_notify::NotifyState::Status(status)]);
+    debug!("Status: {}", status);
+}
+
+/// Tells systemd the service is shutting down.
+pub fn notify_stopping(status: &str) {
+    #[cfg(unix)]
+    notify(&[sd_notify::NotifyState::Stopping, sd_notify::NotifyState::Status(status)]);
+}
+
+/// Start of the cycles in progress, by source.
+fn cycles() -> &'static Mutex<HashMap<String, Instant>> {
+    static CYCLES: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    CYCLES.get_or_init(Mutex::default)
+}
+
+/// Marks the start of a cycle of `source`.
+pub fn cycle_started(source: &str) {
+    cycles().lock().unwrap().insert(source.to_string(), Instant::now());
+}
+
+/// Marks the end of the cycle of `source`.
+pub fn cycle_ended(source: &str) {
+    cycles().lock().unwrap().remove(source);
+}
+
+/// The longest cycle in progress for longer than `timeout`, with how long it has been running.
+fn stalled_cycle(timeout: Duration) -> Option<(String, Duration)> {
+    cycles().lock().unwrap()
+        .iter()
+        .map(|(source, started)| (source.clone(), started.elapsed()))
+        .filter(|(_, running)| *running > timeout)
+        .max_by_key(|(_, running)| *running)
+}
+
+/// The systemd watchdog, if enabled for the service.
+#[derive(Debug, Clone, Copy)]
+pub struct Watchdog {
+    /// Half the configured `WatchdogSec`, as recommended by `sd_watchdog_enabled(3)`.
+    interval: Option<Duration>,
+}
+impl Watchdog {
+    pub fn from_env() -> Self {
+        #[cfg(unix)]
+        {
+            let mut usec = 0;
+            if sd_notify::watchdog_enabled(false, &mut usec) {
+                let interval = Duration::from_micros(usec / 2);
+                info!("systemd watchdog enabled. | Interval: {:?}", interval);
+                return Self { interval: Some(interval) };
+            }
+        }
+        Self { interval: None }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /// Signals systemd that the service is alive.
+    pub fn pet(&self) {
+        #[cfg(unix)]
+        if self.is_enabled() {
+            notify(&[sd_notify::NotifyState::Watchdog]);
+        }
+    }
+
+    /// Sleeps for `duration`, petting the watchdog in between so long waits do not trigger it.
+    pub async fn sleep(&self, duration: Duration) {
+        let Some(interval) = self.interval else {
+            return sleep(duration).await;
+        };
+        let deadline = Instant::now() + duration;
+        loop {
+            self.pet();
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            sleep(interval.min(deadline - now)).await;
+        }
+    }
+
+    /// Pets the watchdog from a background task for as long as no cycle hangs: once one has been
+    /// running for longer than `WatchdogSec`, the task stops and systemd restarts the service.
+    pub fn spawn_keepalive(self) {
+        if let Some(interval) = self.interval {
+            tokio::spawn(async move {
+                loop {
+                    if let Some((source, running)) = stalled_cycle(interval * 2) {
+                        error!("The `{}` cycle has been running for {:?}. No longer petting the watchdog.", source, running);
+                        return;
+                    }
+                    self.pet();
+                    sleep(interval).await;
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_the_cycles_running_past_the_timeout() {
+        cycle_started("stalled-test");
+        tokio::time::advance(Duration::from_secs(90)).await;
+        let (source, running) = stalled_cycle(Duration::from_secs(60)).unwrap();
+        assert_eq!(source, "stalled-test");
+        assert!(running >= Duration::from_secs(90));
+
+        cycle_ended("stalled-test");
+        assert!(stalled_cycle(Duration::from_secs(60)).is_none());
+    }
+}
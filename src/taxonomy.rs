@@ -0,0 +1,160 @@
+//! Topic taxonomy shared by all providers.
+//!
+//! Alpha Vantage tags articles with topics (`Economy - Monetary`, `Mergers & Acquisitions`, ...),
+//! MarketAux with the industries of their entities (`Financial Services`, ...) and FMP with the kind
+//! of news (`crypto`, `forex`, `stock`). These provider topics are mapped onto one internal
+//! taxonomy, stored as the `categories` of each normalized article, so a topic filter selects the
+//! same articles whatever their origin.
+//!
+//! The built-in table is completed or overridden by `[taxonomy.topics]` (case and punctuation are
+//! ignored); mapping a topic to `""` drops it. Unmapped topics are dropped too, unless
+//! `taxonomy.keep_unmapped` is set.
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use crate::config::TaxonomyConfig;
+
+/// Provider topics known without configuration, and their internal topic.
+const DEFAULT_TOPICS: &[(&str, &str)] = &[
+    // Alpha Vantage topics, as returned in the feed and as accepted by the `topics` param.
+    ("blockchain", "crypto"),
+    ("earnings", "earnings"),
+    ("ipo", "ipo"),
+    ("mergers_acquisitions", "mergers_acquisitions"),
+    ("financial_markets", "markets"),
+    ("economy_fiscal", "fiscal_policy"),
+    ("economy_fiscal_policy", "fiscal_policy"),
+    ("economy_monetary", "monetary_policy"),
+    ("economy_monetary_policy", "monetary_policy"),
+    ("economy_macro", "macroeconomy"),
+    ("economy_macro_overall", "macroeconomy"),
+    ("energy_transportation", "energy"),
+    ("finance", "financials"),
+    ("life_sciences", "healthcare"),
+    ("manufacturing", "industrials"),
+    ("real_estate_construction", "real_estate"),
+    ("retail_wholesale", "consumer"),
+    ("technology", "technology"),
+    // MarketAux industries.
+    ("basic_materials", "materials"),
+    ("communication_services", "communication"),
+    ("consumer_cyclical", "consumer"),
+    ("consumer_defensive", "consumer"),
+    ("energy", "energy"),
+    ("financial", "financials"),
+    ("financial_services", "financials"),
+    ("healthcare", "healthcare"),
+    ("industrials", "industrials"),
+    ("real_estate", "real_estate"),
+    ("utilities", "utilities"),
+    ("n_a", ""),
+    // FMP news kinds and Finnhub categories.
+    ("crypto", "crypto"),
+    ("forex", "forex"),
+    ("stock", "equities"),
+    ("merger", "mergers_acquisitions"),
+];
+
+/// Lowercases `topic` and joins its words with underscores (`Economy - Fiscal` becomes `economy_fiscal`).
+fn topic_key(topic: &str) -> String {
+    topic
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Provider topic to internal topic table.
+#[derive(Clone, Debug)]
+pub struct TopicMap {
+    topics: HashMap<String, String>,
+    keep_unmapped: bool,
+}
+impl TopicMap {
+    pub fn new(config: &TaxonomyConfig) -> Self {
+        let mut topics: HashMap<String, String> = DEFAULT_TOPICS
+            .iter()
+            .map(|(topic, internal)| (topic.to_string(), internal.to_string()))
+            .collect();
+        for (topic, internal) in &config.topics {
+            topics.insert(topic_key(topic), topic_key(internal));
+        }
+        Self { topics, keep_unmapped: config.keep_unmapped }
+    }
+
+    /// Internal topics of the provider `topics`, without duplicates, in order of appearance.
+    pub fn categorize(&self, topics: &[String]) -> Vec<String> {
+        let mut categories: Vec<String> = Vec::new();
+        for topic in topics {
+            let key = topic_key(topic);
+            let category = match self.topics.get(&key) {
+                Some(internal) => internal.clone(),
+                None if self.keep_unmapped => key,
+                None => continue,
+            };
+            if !category.is_empty() && !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+        categories
+    }
+}
+impl Default for TopicMap {
+    fn default() -> Self {
+        Self::new(&TaxonomyConfig::default())
+    }
+}
+
+fn map() -> &'static RwLock<TopicMap> {
+    static MAP: OnceLock<RwLock<TopicMap>> = OnceLock::new();
+    MAP.get_or_init(|| RwLock::new(TopicMap::default()))
+}
+
+/// Applies the `[taxonomy]` section of the config.
+pub fn configure(config: &TaxonomyConfig) {
+    *map().write().unwrap_or_else(|e| e.into_inner()) = TopicMap::new(config);
+}
+
+/// Maps provider topics with the configured table, see `TopicMap::categorize`.
+pub fn categorize(topics: &[String]) -> Vec<String> {
+    map().read().unwrap_or_else(|e| e.into_inner()).categorize(topics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topics(topics: &[&str]) -> Vec<String> {
+        topics.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn maps_provider_topics_onto_the_taxonomy() {
+        let map = TopicMap::default();
+        assert_eq!(
+            map.categorize(&topics(&["Economy - Monetary", "Mergers & Acquisitions", "Finance"])),
+            vec!["monetary_policy", "mergers_acquisitions", "financials"]
+        );
+        // MarketAux industries and FMP kinds land on the same topics.
+        assert_eq!(map.categorize(&topics(&["Financial Services", "N/A", "stock"])), vec!["financials", "equities"]);
+        assert!(map.categorize(&topics(&["tesla"])).is_empty());
+    }
+
+    #[test]
+    fn configured_topics_override_defaults() {
+        let config = TaxonomyConfig {
+            topics: HashMap::from([
+                ("Blockchain".to_string(), "digital assets".to_string()),
+                ("Technology".to_string(), String::new()),
+            ]),
+            keep_unmapped: true,
+        };
+        let map = TopicMap::new(&config);
+        assert_eq!(
+            map.categorize(&topics(&["Blockchain", "Technology", "Artificial Intelligence"])),
+            vec!["digital_assets", "artificial_intelligence"]
+        );
+    }
+}
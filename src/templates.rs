@@ -0,0 +1,69 @@
+//! Templated notification payloads.
+//!
+//! Alert channels post `{"text": ...}` by default, which Slack-compatible webhooks accept. Other
+//! receivers (Teams, Discord, PagerDuty, an internal service) expect other bodies: a channel can
+//! give a [minijinja](https://docs.rs/minijinja) `template` of its body, inline or in a
+//! `template_file`, posted with its `content_type`. Templates see:
+//!
+//! - `rule`, `subject`, `message`, `at`: the alert,
+//! - `text`: its one-line rendering, with the count of the alerts held back,
+//! - `held_back`: alerts held back by the channel policy since its last post,
+//! - `channel`: the name of the channel.
+//!
+//! Values are inserted as is: in a JSON body, use the `tojson` filter so they are quoted and
+//! escaped, e.g. `{"content": {{ text|tojson }}, "username": "news_data"}`.
+
+use minijinja::Environment;
+use serde::Serialize;
+
+use crate::config::AlertChannelConfig;
+
+/// A body template, checked when loaded.
+#[derive(Debug)]
+pub struct PayloadTemplate {
+    source: String,
+    pub content_type: String,
+    env: Environment<'static>,
+}
+impl PayloadTemplate {
+    pub fn new(source: impl Into<String>, content_type: impl Into<String>) -> Result<Self, String> {
+        let source = source.into();
+        let env = Environment::new();
+        env.template_from_str(&source).map_err(|e| format!("invalid template: {}", e))?;
+        Ok(Self { source, content_type: content_type.into(), env })
+    }
+
+    /// Template of `channel`, `None` when it posts the default body.
+    pub fn of_channel(channel: &AlertChannelConfig) -> Result<Option<Self>, String> {
+        let source = match (&channel.template, &channel.template_file) {
+            (Some(template), _) => template.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path).map_err(|e| format!("cannot read template file {}: {}", path, e))?,
+            (None, None) => return Ok(None),
+        };
+        Self::new(source, channel.content_type.clone()).map(Some)
+    }
+
+    pub fn render<C: Serialize>(&self, context: C) -> Result<String, String> {
+        self.env.render_str(&self.source, context).map_err(|e| format!("template rendering failed: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn renders_escaped_json_bodies() {
+        let template = PayloadTemplate::new(
+            r#"{"content": {{ text|tojson }}, "fields": [{% for t in tickers %}{{ t|tojson }}{% if not loop.last %},{% endif %}{% endfor %}]}"#,
+            "application/json",
+        )
+        .unwrap();
+        let body = template.render(json!({ "text": "[gap] \"finnhub\": no article", "tickers": ["NVDA", "AMD"] })).unwrap();
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body, json!({ "content": "[gap] \"finnhub\": no article", "fields": ["NVDA", "AMD"] }));
+
+        assert!(PayloadTemplate::new("{{ text", "text/plain").unwrap_err().starts_with("invalid template"));
+    }
+}
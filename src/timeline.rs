@@ -0,0 +1,221 @@
+//! Event timelines per ticker.
+//!
+//! A timeline answers "what happened to NVDA this month": the stored articles about a ticker are
+//! grouped into stories, and the stories listed chronologically. Walking the articles oldest
+//! first, an article joins the open story whose headline or latest article is the most similar to
+//! its own (Jaccard index of their title words and keywords, stop words aside), when at least
+//! `timeline.similarity` and published within `timeline.window_hours` of that latest article.
+//! Otherwise it starts a new story.
+//!
+//! Each event carries the mean sentiment score of its articles and its delta with the previous
+//! event having one, so turning points stand out. Timelines are built on request by the
+//! `ticker_timeline` function of the websocket server and stored in the `timelines` collection,
+//! keyed by ticker and window, for downstream consumers.
+
+use std::collections::{BTreeSet, HashSet};
+
+use chrono::{DateTime, Duration as UtcDuration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::TimelineConfig;
+use crate::db::{dedup_key, OpError};
+use crate::keywords::STOPWORDS;
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+use crate::storage::Storage;
+use crate::utils::now;
+
+/// A story of the timeline: articles about the same event.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimelineEvent {
+    /// Publication time of the first and last articles (RFC 3339).
+    pub started_at: String,
+    pub ended_at: String,
+    /// Title of the first article.
+    pub headline: String,
+    pub url: Option<String>,
+    /// `dedup_key` of the articles, oldest first.
+    pub articles: Vec<String>,
+    pub providers: Vec<String>,
+    pub sources: Vec<String>,
+    /// Mean sentiment score, `None` without scored articles.
+    pub sentiment: Option<f64>,
+    /// Change from the sentiment of the previous event having one.
+    pub sentiment_delta: Option<f64>,
+}
+
+/// Events of a ticker between `from` and `to`, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Timeline {
+    /// `<ticker>:<from>:<to>`
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub ticker: String,
+    pub from: String,
+    pub to: String,
+    pub built_at: String,
+    pub events: Vec<TimelineEvent>,
+}
+
+/// Lowercased words of `article` telling its story apart: title words and keywords.
+fn words_of(article: &NormalizedArticle) -> HashSet<String> {
+    let title = article.title.as_deref().unwrap_or_default();
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|w| w.chars().count() > 2 && !STOPWORDS.contains(&w.as_str()))
+        .chain(article.keywords.iter().map(|k| k.to_lowercase()))
+        .collect()
+}
+
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// A story being built.
+struct Story<'a> {
+    articles: Vec<&'a NormalizedArticle>,
+    headline_words: HashSet<String>,
+    latest_words: HashSet<String>,
+    latest_at: DateTime<Utc>,
+}
+impl Story<'_> {
+    fn into_event(self) -> TimelineEvent {
+        let first = self.articles[0];
+        let last = self.articles[self.articles.len() - 1];
+        let scores: Vec<f64> = self.articles.iter().filter_map(|a| a.sentiment_score).collect();
+        let distinct = |values: Vec<Option<&String>>| values.into_iter().flatten().cloned().collect::<BTreeSet<_>>().into_iter().collect();
+        TimelineEvent {
+            started_at: first.published_at.clone().unwrap_or_default(),
+            ended_at: last.published_at.clone().unwrap_or_default(),
+            headline: first.title.clone().unwrap_or_default(),
+            url: first.url.clone(),
+            articles: self.articles.iter().map(|a| dedup_key(a)).collect(),
+            providers: distinct(self.articles.iter().map(|a| Some(&a.provider)).collect()),
+            sources: distinct(self.articles.iter().map(|a| a.source.as_ref()).collect()),
+            sentiment: (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64),
+            sentiment_delta: None,
+        }
+    }
+}
+
+/// Groups `articles` into events, oldest first, see the module docs.
+pub fn cluster(articles: &[NormalizedArticle], config: &TimelineConfig) -> Vec<TimelineEvent> {
+    let mut dated: Vec<(DateTime<Utc>, &NormalizedArticle)> = articles
+        .iter()
+        .filter_map(|a| Some((a.published_at.as_deref().and_then(parse_timestamp)?, a)))
+        .collect();
+    dated.sort_by_key(|(at, _)| *at);
+
+    let window = UtcDuration::hours(config.window_hours as i64);
+    let mut stories: Vec<Story> = Vec::new();
+    for (at, article) in dated {
+        let words = words_of(article);
+        let best = stories
+            .iter_mut()
+            .filter(|story| at - story.latest_at <= window)
+            .map(|story| {
+                let score = similarity(&words, &story.headline_words).max(similarity(&words, &story.latest_words));
+                (score, story)
+            })
+            .filter(|(score, _)| *score >= config.similarity)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        match best {
+            Some((_, story)) => {
+                story.articles.push(article);
+                story.latest_words = words;
+                story.latest_at = at;
+            }
+            None => stories.push(Story { articles: vec![article], headline_words: words.clone(), latest_words: words, latest_at: at }),
+        }
+    }
+
+    let mut events: Vec<TimelineEvent> = stories.into_iter().map(Story::into_event).collect();
+    events.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    let mut previous = None;
+    for event in &mut events {
+        if let Some(sentiment) = event.sentiment {
+            event.sentiment_delta = previous.map(|previous| sentiment - previous);
+            previous = Some(sentiment);
+        }
+    }
+    events
+}
+
+impl Timeline {
+    /// Timeline of `ticker` from the `articles` published between `from` and `to`.
+    pub fn build(ticker: &str, from: DateTime<Utc>, to: DateTime<Utc>, articles: &[NormalizedArticle], config: &TimelineConfig) -> Self {
+        let ticker = ticker.to_uppercase();
+        let (from, to) = (from.to_rfc3339_opts(SecondsFormat::Secs, true), to.to_rfc3339_opts(SecondsFormat::Secs, true));
+        let mut events = cluster(articles, config);
+        if config.max_events > 0 && events.len() > config.max_events {
+            // The latest events: the question is usually what happened lately.
+            events.drain(..events.len() - config.max_events);
+        }
+        Self { id: format!("{}:{}:{}", ticker, from, to), ticker, from, to, built_at: now(), events }
+    }
+}
+
+/// Builds the timeline of `ticker` between `from` and `to` from the stored articles, and stores it.
+pub async fn build(storage: &dyn Storage, ticker: &str, from: DateTime<Utc>, to: DateTime<Utc>, config: &TimelineConfig) -> Result<Timeline, OpError> {
+    let (since, until) = (from.to_rfc3339_opts(SecondsFormat::Secs, true), to.to_rfc3339_opts(SecondsFormat::Secs, true));
+    let articles = storage.articles_with_tickers(&[ticker.to_string()], &since, &until).await?;
+    let timeline = Timeline::build(ticker, from, to, &articles, config);
+    storage.save_timeline(&timeline).await?;
+    Ok(timeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clusters_stories_chronologically() {
+        let article = |id: &str, published_at: &str, title: &str, score: Option<f64>| {
+            NormalizedArticle::test(id)
+                .provider("marketaux")
+                .title(title)
+                .source(&format!("source-{}", id))
+                .published_at(published_at)
+                .tickers(&["NVDA"])
+                .sentiment_score(score)
+        };
+        let articles = [
+            article("3", "2024-05-23T09:00:00Z", "Nvidia earnings beat estimates on data center demand", Some(0.6)),
+            article("1", "2024-05-01T10:00:00Z", "Nvidia faces export curbs on China chips", Some(-0.4)),
+            article("2", "2024-05-01T15:00:00Z", "China chips export curbs hit Nvidia shares", Some(-0.2)),
+            article("4", "2024-05-23T18:00:00Z", "Nvidia earnings: data center revenue triples", None),
+            // Same words, but weeks after the first story ended.
+            article("5", "2024-05-28T10:00:00Z", "Nvidia export curbs on China chips eased", Some(0.2)),
+        ];
+        let events = cluster(&articles, &TimelineConfig::default());
+        let keys: Vec<Vec<&str>> = events.iter().map(|e| e.articles.iter().map(String::as_str).collect()).collect();
+        assert_eq!(keys, vec![vec!["marketaux:1", "marketaux:2"], vec!["marketaux:3", "marketaux:4"], vec!["marketaux:5"]]);
+        assert_eq!(events[0].headline, "Nvidia faces export curbs on China chips");
+        assert_eq!(events[0].sources, vec!["source-1", "source-2"]);
+        assert_eq!(events[0].sentiment.map(|s| (s * 100.0).round()), Some(-30.0));
+        assert_eq!(events[0].sentiment_delta, None);
+        assert_eq!(events[1].sentiment_delta.map(|d| (d * 100.0).round()), Some(90.0));
+        assert_eq!(events[2].sentiment_delta.map(|d| (d * 100.0).round()), Some(-40.0));
+    }
+
+    #[tokio::test]
+    async fn stores_the_latest_events() {
+        let storage = crate::storage::MemoryStorage::new();
+        storage.store_articles(&[
+            NormalizedArticle::test("1").title("Nvidia faces export curbs").published_at("2024-05-01T10:00:00Z").tickers(&["NVDA"]),
+            NormalizedArticle::test("2").title("Nvidia earnings beat estimates").published_at("2024-05-23T09:00:00Z").tickers(&["NVDA"]),
+        ]).await.unwrap();
+        let config = TimelineConfig { max_events: 1, ..TimelineConfig::default() };
+        let (from, to) = (parse_timestamp("2024-05-01T00:00:00Z").unwrap(), parse_timestamp("2024-06-01T00:00:00Z").unwrap());
+
+        let timeline = build(&storage, "nvda", from, to, &config).await.unwrap();
+        assert_eq!(timeline.id, "NVDA:2024-05-01T00:00:00Z:2024-06-01T00:00:00Z");
+        assert_eq!(timeline.events.len(), 1);
+        assert_eq!(timeline.events[0].headline, "Nvidia earnings beat estimates");
+        assert_eq!(storage.timelines(), vec![timeline]);
+    }
+}
@@ -0,0 +1,190 @@
+//! Translation of non-English articles.
+//!
+//! With `translation.enabled`, articles in another language than `translation.target_language`
+//! get their title and summary translated by the configured API before they are first stored (or
+//! stored in a new version), in a `translation` next to the originals. Keywords are extracted
+//! from the translation, so the `keyword_news` and `trending_keywords` queries find articles
+//! whatever their language.
+//!
+//! Two APIs are supported, set with `translation.api`:
+//!
+//! - `libretranslate` (default): `POST {endpoint}` with `q`, `source`, `target` and `api_key`,
+//! - `deepl`: `POST {endpoint}` with `text` and `target_lang`, authenticated by a
+//!   `DeepL-Auth-Key` header.
+//!
+//! Articles without a language are only translated with `translation.translate_unknown_language`,
+//! the API then detects it. A failed translation is logged and the article stored untranslated.
+
+use std::sync::Arc;
+
+use reqwest::Client;
+use serde_json::{json, Value};
+use tokio::time::Duration;
+use tracing::{debug, warn};
+
+use crate::config::TranslationConfig;
+use crate::normalize::{NormalizedArticle, Translation};
+
+pub const LIBRETRANSLATE: &str = "libretranslate";
+pub const DEEPL: &str = "deepl";
+
+/// Texts translated by the API, in the order sent, with the source language it detected.
+#[derive(Debug, Clone, PartialEq)]
+struct Translated {
+    texts: Vec<String>,
+    detected_language: Option<String>,
+}
+
+/// Whether two language tags (`en`, `EN-us`, ...) have the same primary language.
+fn same_language(a: &str, b: &str) -> bool {
+    let primary = |tag: &str| tag.trim().split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    primary(a) == primary(b)
+}
+
+/// Translates articles with the API of a `TranslationConfig`.
+pub struct Translator {
+    client: Arc<Client>,
+    config: TranslationConfig,
+}
+impl Translator {
+    pub fn new(client: Arc<Client>, config: &TranslationConfig) -> Self {
+        if ![LIBRETRANSLATE, DEEPL].contains(&config.api.as_str()) {
+            warn!("Unknown translation API `{}`, using `{}`.", config.api, LIBRETRANSLATE);
+        }
+        Self { client, config: config.clone() }
+    }
+
+    /// Whether `article` has text to translate, in another language than the target.
+    pub fn needs_translation(&self, article: &NormalizedArticle) -> bool {
+        if article.translation.is_some() || (article.title.is_none() && article.summary.is_none()) {
+            return false;
+        }
+        match article.language.as_deref().filter(|l| !l.trim().is_empty()) {
+            Some(language) => !same_language(language, &self.config.target_language),
+            None => self.config.translate_unknown_language,
+        }
+    }
+
+    /// `article` with its `translation` set, or unchanged when it needs none or the API failed.
+    pub async fn translate(&self, article: &NormalizedArticle) -> NormalizedArticle {
+        if !self.needs_translation(article) {
+            return article.clone();
+        }
+        let texts: Vec<&str> = [article.title.as_deref(), article.summary.as_deref()].into_iter().flatten().collect();
+        let translated = match self.request(&texts).await {
+            Ok(translated) if translated.texts.len() == texts.len() => translated,
+            Ok(translated) => {
+                warn!("Translation API returned {} texts for {} sent.", translated.texts.len(), texts.len());
+                return article.clone();
+            }
+            Err(e) => {
+                warn!("Failed to translate article `{}` from `{}`: {}", article.id, article.provider, e);
+                return article.clone();
+            }
+        };
+        // Detected as already in the target language: nothing to keep.
+        if translated.detected_language.as_deref().is_some_and(|l| same_language(l, &self.config.target_language)) {
+            return article.clone();
+        }
+
+        let mut texts = translated.texts.into_iter();
+        let translation = Translation {
+            language: self.config.target_language.clone(),
+            detected_language: translated.detected_language.filter(|_| article.language.is_none()),
+            title: article.title.as_ref().and_then(|_| texts.next()),
+            summary: article.summary.as_ref().and_then(|_| texts.next()),
+        };
+        debug!("Translated article `{}` from `{}`.", article.id, article.provider);
+        NormalizedArticle { translation: Some(translation), ..article.clone() }
+    }
+
+    async fn request(&self, texts: &[&str]) -> Result<Translated, String> {
+        let request = self.client.post(&self.config.endpoint).timeout(Duration::from_secs(self.config.timeout_secs));
+        let request = match self.config.api.as_str() {
+            DEEPL => request
+                .header("Authorization", format!("DeepL-Auth-Key {}", self.config.api_key))
+                .json(&json!({ "text": texts, "target_lang": self.config.target_language.to_uppercase() })),
+            _ => request.json(&json!({
+                "q": texts,
+                "source": "auto",
+                "target": self.config.target_language,
+                "format": "text",
+                "api_key": self.config.api_key,
+            })),
+        };
+        let body: Value = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+        let parsed = match self.config.api.as_str() {
+            DEEPL => parse_deepl(&body),
+            _ => parse_libretranslate(&body),
+        };
+        parsed.ok_or_else(|| format!("Unexpected response: {}", body))
+    }
+}
+
+/// `{"translatedText": [...], "detectedLanguage": [{"language": ...}, ...]}`
+fn parse_libretranslate(body: &Value) -> Option<Translated> {
+    let texts = body["translatedText"].as_array()?.iter().map(|t| t.as_str().map(str::to_string)).collect::<Option<Vec<_>>>()?;
+    let detected_language = match &body["detectedLanguage"] {
+        Value::Array(detected) => detected.first().and_then(|d| d["language"].as_str()),
+        detected => detected["language"].as_str(),
+    };
+    Some(Translated { texts, detected_language: detected_language.map(str::to_lowercase) })
+}
+
+/// `{"translations": [{"detected_source_language": ..., "text": ...}, ...]}`
+fn parse_deepl(body: &Value) -> Option<Translated> {
+    let translations = body["translations"].as_array()?;
+    let texts = translations.iter().map(|t| t["text"].as_str().map(str::to_string)).collect::<Option<Vec<_>>>()?;
+    let detected_language = translations.first().and_then(|t| t["detected_source_language"].as_str());
+    Some(Translated { texts, detected_language: detected_language.map(str::to_lowercase) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translator(translate_unknown_language: bool) -> Translator {
+        let config = TranslationConfig { translate_unknown_language, ..TranslationConfig::default() };
+        Translator::new(Arc::new(Client::new()), &config)
+    }
+
+    #[test]
+    fn translates_other_languages_only() {
+        assert!(same_language("EN-us", "en") && !same_language("fr", "en"));
+        let translator = translator(false);
+        let article = |language: Option<&str>| NormalizedArticle::test("1").title("La BCE relève ses taux").language(language);
+        assert!(translator.needs_translation(&article(Some("fr"))));
+        assert!(!translator.needs_translation(&article(Some("en-GB"))));
+        assert!(!translator.needs_translation(&article(None)));
+        assert!(self::translator(true).needs_translation(&article(None)));
+
+        assert!(!translator.needs_translation(&article(Some("fr")).translated("The ECB raises its rates")));
+    }
+
+    #[test]
+    fn parses_the_api_responses() {
+        let libre = json!({
+            "translatedText": ["ECB raises rates", "Second hike this year"],
+            "detectedLanguage": [{ "confidence": 90, "language": "fr" }, { "confidence": 90, "language": "fr" }],
+        });
+        let expected = Translated {
+            texts: vec!["ECB raises rates".to_string(), "Second hike this year".to_string()],
+            detected_language: Some("fr".to_string()),
+        };
+        assert_eq!(parse_libretranslate(&libre), Some(expected.clone()));
+
+        let deepl = json!({ "translations": [
+            { "detected_source_language": "FR", "text": "ECB raises rates" },
+            { "detected_source_language": "FR", "text": "Second hike this year" },
+        ]});
+        assert_eq!(parse_deepl(&deepl), Some(expected));
+        assert_eq!(parse_deepl(&json!({ "message": "Quota exceeded" })), None);
+    }
+}
@@ -9,16 +9,17 @@ use futures_util::Future;
 use tokio::time::sleep;
 use serde_json::Value;
 use tokio::sync::{Mutex, Semaphore};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, debug_span, error, info, warn, Instrument};
 
 use crate::cache::{Cache, SharedLockedCache};
+use crate::{clock, retries};
 use crate::config::ValueConfig;
 use crate::errors::ApiError;
 
 
 pub fn time_rfc3339_opts(secs: i64) -> String {
-    // Get current UTC time
-    let now = Utc::now();
+    // Get current UTC time, corrected for clock skew if enabled
+    let now = clock::now();
     // Subtract specified seconds from the current time
     let tartget_time = now - UtcDuration::seconds(secs);
     // Format the time in RFC 3339 format with second precision
@@ -30,8 +31,8 @@ pub fn time_rfc3339_opts(secs: i64) -> String {
 }
 
 pub fn time_yyyy_mmdd_thhmm(secs: i64) -> String {
-    // Get current UTC time
-    let now = Utc::now();
+    // Get current UTC time, corrected for clock skew if enabled
+    let now = clock::now();
     // Subtract specified seconds from the current time
     let tartget_time = now - UtcDuration::seconds(secs);
     // Format the time in the custom format: yyyyMMddTHHmm
@@ -58,7 +59,11 @@ pub fn generate_random_key(length: usize) -> String {
         .collect()
 }
 
+/// Runs `operation`, a request of `provider`, until it succeeds, up to `task.max_retries` times with
+/// an exponential backoff. Each attempt runs in an `attempt` span, nested in the span of the caller
+/// (see `logging.rs`), and the failed ones are recorded (see `retries.rs`).
 pub async fn retry<F, Fut, T, E>(
+    provider: &str,
     config: &Arc<ValueConfig>,
     mut operation: F,
 ) -> Result<T, E>
@@ -71,19 +76,29 @@ where
 
     loop {
         attempts += 1;
-        match operation().await {
+        match operation().instrument(debug_span!("attempt", attempt = attempts)).await {
             Ok(value) => return Ok(value),
             Err(err) if attempts < config.task.max_retries => {
-                warn!("Attempt {}/{} failed with error: {:?}.", &attempts, &config.task.max_retries, err);
-                debug!("Attempting again...");
                 let delay = std::cmp::min(
                     config.task.base_delay_ms * (2u32.pow(attempts - 1)),
                     config.task.max_delay_ms,
                 );
+                let error_class = retries::error_class(&err);
+                warn!(
+                    provider, attempt = attempts, error_class = %error_class, delay_ms = delay,
+                    "Attempt {}/{} failed with error: {:?}.", &attempts, &config.task.max_retries, err
+                );
+                retries::retried(provider, attempts, &error_class, delay as u64);
+                debug!("Attempting again...");
                 sleep(Duration::from_millis(delay as u64)).await;
             }
             Err(err) => {
-                error!("All {} attempts have been unsuccessful. | Returning final error. | Error: {:?}", &config.task.max_retries, err);
+                let error_class = retries::error_class(&err);
+                error!(
+                    provider, attempt = attempts, error_class = %error_class,
+                    "All {} attempts have been unsuccessful. | Returning final error. | Error: {:?}", &config.task.max_retries, err
+                );
+                retries::exhausted(provider, &error_class);
                 return Err(err)
             },
         }
@@ -91,15 +106,16 @@ where
 }
 
 
-pub async fn get_from_cache_or_fetch<F, Fut>(
+pub async fn get_from_cache_or_fetch<F, Fut, E>(
     cache: &Arc<Mutex<SharedLockedCache>>,
     key: &str,
     fetch_fn: F,
     ttl: u32,
-) -> Result<Value, reqwest::Error>
+) -> Result<Value, E>
 where
     F: FnOnce() -> Fut,
-    Fut: Future<Output = Result<Value, reqwest::Error>>,
+    Fut: Future<Output = Result<Value, E>>,
+    E: std::fmt::Display,
 {
     info!("Looking in cache for {}...", &key);
     let cache = cache.lock().await;
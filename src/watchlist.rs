@@ -0,0 +1,115 @@
+//! Per-symbol fetches of the watchlist.
+//!
+//! The polling loop fetches the latest news untargeted, so the symbols that matter most compete
+//! with the whole market for the few articles of each response. Every cycle, the symbols of
+//! `watchlist.symbols` are also fetched on their own from each provider of `watchlist.providers`
+//! (MarketAux `symbols`, Alpha Vantage `tickers`, FMP `tickers`). The articles found are tagged
+//! with the symbols they were fetched for in their `watchlist` field, which `Storage` merges into
+//! the stored article when it is delivered again.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::stream::{self, StreamExt};
+use tracing::{debug, error};
+
+use crate::config::WatchlistConfig;
+use crate::db::dedup_key;
+use crate::limiter;
+use crate::normalize::NormalizedArticle;
+use crate::provider::NewsProvider;
+
+/// Symbols of the watchlist, uppercased and without duplicates.
+pub fn symbols(config: &WatchlistConfig) -> Vec<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    for symbol in config.symbols.iter().map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()) {
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
+
+/// Fetches every watchlist symbol from every watchlist provider among `providers`.
+///
+/// A failed query is logged and skipped. Articles found for several symbols are returned once,
+/// tagged with all of them.
+pub async fn fetch(providers: &[Arc<dyn NewsProvider>], config: &WatchlistConfig) -> Vec<NormalizedArticle> {
+    let providers: Vec<Arc<dyn NewsProvider>> = providers
+        .iter()
+        .filter(|p| config.providers.iter().any(|name| name == p.name()))
+        .cloned()
+        .collect();
+    let queries: Vec<(String, Arc<dyn NewsProvider>)> = symbols(config)
+        .into_iter()
+        .flat_map(|symbol| providers.iter().map(move |provider| (symbol.clone(), provider.clone())))
+        .collect();
+
+    // Futures built ahead of the stream, so `fetch` can run in a spawned task.
+    let fetches: Vec<_> = queries
+        .into_iter()
+        .map(|(symbol, provider)| async move {
+            match limiter::run(provider.name(), provider.fetch_symbol(&symbol)).await {
+                Ok(articles) => {
+                    debug!("Fetched {} articles of `{}` from `{}`.", articles.len(), symbol, provider.name());
+                    (symbol, articles)
+                }
+                Err(e) => {
+                    error!("Failed to fetch `{}` from `{}`: {}", symbol, provider.name(), e);
+                    (symbol, Vec::new())
+                }
+            }
+        })
+        .collect();
+    let fetched: Vec<(String, Vec<NormalizedArticle>)> = stream::iter(fetches)
+        .buffer_unordered(config.max_concurrent_fetches.max(1))
+        .collect()
+        .await;
+    merge(fetched)
+}
+
+/// Tags the articles fetched for each symbol, merging those fetched for several symbols.
+fn merge(fetched: Vec<(String, Vec<NormalizedArticle>)>) -> Vec<NormalizedArticle> {
+    let mut merged: Vec<NormalizedArticle> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (symbol, articles) in fetched {
+        for mut article in articles {
+            match positions.get(&dedup_key(&article)) {
+                Some(&position) => merged[position].tag_symbol(&symbol),
+                None => {
+                    article.tag_symbol(&symbol);
+                    positions.insert(dedup_key(&article), merged.len());
+                    merged.push(article);
+                }
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_the_symbols() {
+        let config = WatchlistConfig {
+            symbols: vec!["aapl".to_string(), " MSFT ".to_string(), "AAPL".to_string(), "".to_string()],
+            ..WatchlistConfig::default()
+        };
+        assert_eq!(symbols(&config), vec!["AAPL", "MSFT"]);
+    }
+
+    #[test]
+    fn tags_articles_with_every_symbol_they_were_fetched_for() {
+        let fetched = vec![
+            ("AAPL".to_string(), vec![NormalizedArticle::test("1").provider("marketaux"), NormalizedArticle::test("1").provider("fmp")]),
+            ("MSFT".to_string(), vec![NormalizedArticle::test("1").provider("marketaux"), NormalizedArticle::test("2").provider("marketaux")]),
+        ];
+        let merged = merge(fetched);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].watchlist, vec!["AAPL", "MSFT"]);
+        assert_eq!(merged[1].watchlist, vec!["AAPL"]);
+        assert_eq!(merged[2].watchlist, vec!["MSFT"]);
+    }
+}
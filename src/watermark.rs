@@ -0,0 +1,158 @@
+//! Incremental polling from the newest stored article.
+//!
+//! Polling windows computed as `request.delay_secs` ago leave a gap whenever a cycle starts late
+//! (a slow provider, the daemon being restarted or down for a while). Instead, the latest news of
+//! a provider are fetched from its watermark: the newest publication time stored for it. The
+//! watermarks are restored from the storage on startup (`Storage::watermarks`), then advanced and
+//! saved after each stored cycle, so a restart resumes where the previous run stopped.
+//!
+//! The window starts `watermark.overlap_secs` before the watermark, to catch articles indexed
+//! late (articles fetched twice are dropped by the dedup), and never further back than
+//! `watermark.max_lookback_secs`. Providers without a watermark are polled from
+//! `request.delay_secs` ago.
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use chrono::{DateTime, Duration as UtcDuration, SecondsFormat, Utc};
+use tracing::debug;
+
+use crate::clock;
+use crate::config::WatermarkConfig;
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+
+/// Watermark of each provider.
+#[derive(Debug, Clone, Default)]
+pub struct Watermarks {
+    config: WatermarkConfig,
+    marks: HashMap<String, DateTime<Utc>>,
+}
+impl Watermarks {
+    pub fn new(config: &WatermarkConfig) -> Self {
+        Self { config: config.clone(), marks: HashMap::new() }
+    }
+
+    pub fn get(&self, provider: &str) -> Option<DateTime<Utc>> {
+        self.marks.get(provider).copied()
+    }
+
+    /// Restores the watermarks (RFC 3339) loaded from the storage. Unparseable ones are skipped.
+    pub fn restore(&mut self, marks: &HashMap<String, String>) {
+        for (provider, published_at) in marks {
+            if let Some(at) = parse_timestamp(published_at) {
+                self.marks.insert(provider.clone(), at);
+            }
+        }
+    }
+
+    /// Advances the watermarks to the newest of the stored `articles` published before `now`.
+    /// Returns the watermarks that moved, to be saved.
+    pub fn advance(&mut self, articles: &[NormalizedArticle], now: DateTime<Utc>) -> Vec<(String, DateTime<Utc>)> {
+        let mut newest: HashMap<&str, DateTime<Utc>> = HashMap::new();
+        for article in articles {
+            // Articles dated in the future would stall polling until that date.
+            let Some(at) = article.published_at.as_deref().and_then(parse_timestamp).filter(|at| *at <= now) else {
+                continue;
+            };
+            let entry = newest.entry(article.provider.as_str()).or_insert(at);
+            *entry = (*entry).max(at);
+        }
+
+        let mut moved = Vec::new();
+        for (provider, at) in newest {
+            if self.get(provider).is_none_or(|mark| at > mark) {
+                self.marks.insert(provider.to_string(), at);
+                moved.push((provider.to_string(), at));
+            }
+        }
+        moved.sort();
+        moved
+    }
+
+    /// Start of the next polling window of `provider`.
+    pub fn since(&self, provider: &str, delay_secs: i64, now: DateTime<Utc>) -> DateTime<Utc> {
+        let fallback = now - UtcDuration::seconds(delay_secs);
+        let Some(mark) = self.get(provider).filter(|_| self.config.enabled) else {
+            return fallback;
+        };
+        let earliest = now - UtcDuration::seconds(self.config.max_lookback_secs as i64);
+        (mark - UtcDuration::seconds(self.config.overlap_secs as i64)).clamp(earliest, now)
+    }
+}
+
+fn state() -> &'static RwLock<Watermarks> {
+    static STATE: OnceLock<RwLock<Watermarks>> = OnceLock::new();
+    STATE.get_or_init(RwLock::default)
+}
+
+/// Applies the `[watermark]` section of the config.
+pub fn configure(config: &WatermarkConfig) {
+    state().write().unwrap_or_else(|e| e.into_inner()).config = config.clone();
+}
+
+/// Restores the watermarks loaded with `Storage::watermarks`.
+pub fn restore(marks: &HashMap<String, String>) {
+    state().write().unwrap_or_else(|e| e.into_inner()).restore(marks);
+}
+
+/// Advances the watermarks with the articles of a stored cycle, returning the ones that moved
+/// (RFC 3339), to be saved with `Storage::save_watermark`.
+pub fn advance(articles: &[NormalizedArticle]) -> Vec<(String, String)> {
+    let moved = state().write().unwrap_or_else(|e| e.into_inner()).advance(articles, clock::now());
+    moved
+        .into_iter()
+        .map(|(provider, at)| (provider, at.to_rfc3339_opts(SecondsFormat::Secs, true)))
+        .collect()
+}
+
+/// Start of the next polling window of `provider`, see the module documentation.
+pub fn since(provider: &str, delay_secs: i64) -> DateTime<Utc> {
+    let since = state().read().unwrap_or_else(|e| e.into_inner()).since(provider, delay_secs, clock::now());
+    debug!("Polling {} since {}.", provider, since);
+    since
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(raw: &str) -> DateTime<Utc> {
+        parse_timestamp(raw).unwrap()
+    }
+
+    #[test]
+    fn advances_to_the_newest_stored_article() {
+        let now = at("2024-05-01T12:00:00Z");
+        let mut marks = Watermarks::new(&WatermarkConfig::default());
+        marks.restore(&HashMap::from([("marketaux".to_string(), "2024-05-01T10:00:00Z".to_string())]));
+
+        let articles = [
+            NormalizedArticle::test("1").provider("marketaux").published_at("2024-05-01T09:00:00Z"),
+            NormalizedArticle::test("2").provider("alphavantage").published_at("2024-05-01T11:00:00Z"),
+            NormalizedArticle::test("3").provider("alphavantage").published_at("2024-05-01T11:30:00Z"),
+            NormalizedArticle::test("4").provider("alphavantage").published_at("2024-05-02T00:00:00Z"),
+        ];
+        let moved = marks.advance(&articles, now);
+        // Older than the restored watermark, or dated in the future: ignored.
+        assert_eq!(moved, vec![("alphavantage".to_string(), at("2024-05-01T11:30:00Z"))]);
+        assert_eq!(marks.get("marketaux"), Some(at("2024-05-01T10:00:00Z")));
+    }
+
+    #[test]
+    fn polls_from_the_watermark_within_the_lookback() {
+        let now = at("2024-05-01T12:00:00Z");
+        let config = WatermarkConfig { enabled: true, overlap_secs: 60, max_lookback_secs: 6 * 3600 };
+        let mut marks = Watermarks::new(&config);
+        marks.restore(&HashMap::from([
+            ("marketaux".to_string(), "2024-05-01T10:00:00Z".to_string()),
+            ("alphavantage".to_string(), "2024-04-01T00:00:00Z".to_string()),
+        ]));
+
+        assert_eq!(marks.since("marketaux", 600, now), at("2024-05-01T09:59:00Z"));
+        assert_eq!(marks.since("alphavantage", 600, now), at("2024-05-01T06:00:00Z"));
+        assert_eq!(marks.since("fmp", 600, now), at("2024-05-01T11:50:00Z"));
+
+        let disabled = Watermarks { config: WatermarkConfig { enabled: false, ..config }, ..marks };
+        assert_eq!(disabled.since("marketaux", 600, now), at("2024-05-01T11:50:00Z"));
+    }
+}
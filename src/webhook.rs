@@ -0,0 +1,223 @@
+//! Webhook ingestion of pushed articles.
+//!
+//! Internal scrapers and partner feeds push articles to `POST /webhook/articles` instead of being
+//! polled. The body is one article or an array of articles in the normalized schema (see
+//! `NormalizedArticle`, `provider` may be omitted), authenticated with one of the bearer tokens of
+//! `[webhook.tokens]`:
+//!
+//! ```text
+//! curl -X POST http://127.0.0.1:8090/webhook/articles \
+//!      -H 'Authorization: Bearer <token>' -H 'Content-Type: application/json' \
+//!      -d '[{"id": "42", "title": "...", "published_at": "2024-05-01T10:00:00Z", "authors": [], "tickers": ["AAPL"], "topics": []}]'
+//! ```
+//!
+//! Pushed articles go through the same pipeline as polled ones: their sentiment is harmonized,
+//! their topics mapped onto the taxonomy and they are stored with `Storage::store_articles`
+//! (dedup, keywords, outbox). They are tagged with the `pushed` provider and their `id` is prefixed
+//! with the name of the token (`partner:42`), so two pushers reusing an id do not dedup each other's
+//! articles. The name of the token also stands in for a missing `source`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::SecondsFormat;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tracing::{error, info, warn};
+
+use crate::config::WebhookConfig;
+use crate::normalize::{parse_timestamp, NormalizedArticle};
+use crate::sentiment;
+use crate::storage::Storage;
+
+pub const PUSHED_PROVIDER: &str = "pushed";
+pub const ARTICLES_PATH: &str = "/webhook/articles";
+
+#[derive(Clone)]
+struct WebhookState {
+    db: Arc<dyn Storage>,
+    config: Arc<WebhookConfig>,
+}
+
+/// Name of the token presented in the `Authorization: Bearer` header, if it is a known one.
+pub fn authorize<'a>(headers: &HeaderMap, tokens: &'a HashMap<String, String>) -> Option<&'a str> {
+    let presented = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?;
+    authorize_token(presented, tokens)
+}
+
+/// Name of the `presented` token, if it is a known one.
+pub fn authorize_token<'a>(presented: &str, tokens: &'a HashMap<String, String>) -> Option<&'a str> {
+    let presented = presented.trim();
+    tokens
+        .iter()
+        .find(|(_, token)| !token.is_empty() && constant_time_eq(token.as_bytes(), presented.as_bytes()))
+        .map(|(name, _)| name.as_str())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Parses a pushed body into articles tagged with the `pushed` provider, ready to be stored.
+pub fn parse_pushed(body: Value, pusher: &str) -> Result<Vec<NormalizedArticle>, String> {
+    let items = match body {
+        Value::Array(items) => items,
+        item @ Value::Object(_) => vec![item],
+        _ => return Err("Expected an article or an array of articles".to_string()),
+    };
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut item)| {
+            if let Value::Object(map) = &mut item {
+                map.insert("provider".to_string(), Value::String(PUSHED_PROVIDER.to_string()));
+            }
+            let article: NormalizedArticle =
+                serde_json::from_value(item).map_err(|e| format!("Article {}: {}", index, e))?;
+            prepare(article, pusher).map_err(|e| format!("Article {}: {}", index, e))
+        })
+        .collect()
+}
+
+/// Applies the normalization the provider conversions do to a pushed article.
+fn prepare(mut article: NormalizedArticle, pusher: &str) -> Result<NormalizedArticle, String> {
+    if article.id.trim().is_empty() {
+        return Err("`id` is empty".to_string());
+    }
+    article.id = format!("{}:{}", pusher, article.id.trim());
+    if let Some(published_at) = &article.published_at {
+        let published = parse_timestamp(published_at)
+            .ok_or_else(|| format!("`published_at` is not a timestamp: {}", published_at))?;
+        article.published_at = Some(published.to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
+    if article.source.is_none() {
+        article.source = Some(pusher.to_string());
+    }
+    article.tickers = article.tickers.iter().map(|t| t.to_uppercase()).collect();
+    if article.sentiment.is_none() {
+        article.sentiment = sentiment::harmonize(article.sentiment_label.as_deref(), article.sentiment_score);
+    }
+    if article.categories.is_empty() {
+        article = article.with_categories();
+    }
+    Ok(article)
+}
+
+async fn push_articles(
+    State(state): State<WebhookState>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> (StatusCode, Json<Value>) {
+    let Some(pusher) = authorize(&headers, &state.config.tokens) else {
+        warn!("Rejected a webhook push with a missing or unknown token.");
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "error": "Missing or unknown bearer token" })));
+    };
+    let articles = match parse_pushed(body, pusher) {
+        Ok(articles) => articles,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))),
+    };
+    if articles.len() > state.config.max_articles {
+        let message = format!("At most {} articles per push", state.config.max_articles);
+        return (StatusCode::PAYLOAD_TOO_LARGE, Json(json!({ "error": message })));
+    }
+    match state.db.store_articles(&articles).await {
+        Ok(stored) => {
+            info!("Stored {} new of {} articles pushed by `{}`.", stored, articles.len(), pusher);
+            (StatusCode::OK, Json(json!({ "received": articles.len(), "stored": stored })))
+        }
+        Err(e) => {
+            error!("Error storing pushed articles: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Failed to store articles" })))
+        }
+    }
+}
+
+pub fn router(db: Arc<dyn Storage>, config: WebhookConfig) -> Router {
+    Router::new()
+        .route(ARTICLES_PATH, post(push_articles))
+        .with_state(WebhookState { db, config: Arc::new(config) })
+}
+
+/// Serves the webhook endpoint on `webhook.host`:`webhook.port` in a background task.
+pub async fn spawn(db: Arc<dyn Storage>, config: WebhookConfig) -> std::io::Result<()> {
+    if config.tokens.is_empty() {
+        warn!("Webhook ingestion is enabled without any token: every push will be rejected.");
+    }
+    let address = format!("{}:{}", config.host, config.port);
+    let listener = TcpListener::bind(&address).await?;
+    info!("Webhook ingestion listening on: {}{}", address, ARTICLES_PATH);
+    let app = router(db, config);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("Webhook server failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn authorizes_known_bearer_tokens() {
+        let tokens = HashMap::from([("scraper".to_string(), "s3cret".to_string())]);
+        let mut headers = HeaderMap::new();
+        assert_eq!(authorize(&headers, &tokens), None);
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer wrong"));
+        assert_eq!(authorize(&headers, &tokens), None);
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer s3cret"));
+        assert_eq!(authorize(&headers, &tokens), Some("scraper"));
+    }
+
+    #[test]
+    fn tags_and_normalizes_pushed_articles() {
+        let body = json!({
+            "provider": "spoofed",
+            "id": "42",
+            "url": null,
+            "title": "Chipmaker raises guidance",
+            "summary": null,
+            "source": null,
+            "authors": [],
+            "language": "en",
+            "published_at": "2024-05-01 10:00:00",
+            "tickers": ["nvda"],
+            "topics": ["Technology"],
+            "sentiment_score": 0.5,
+            "sentiment_label": null,
+        });
+        let articles = parse_pushed(body, "partner").unwrap();
+        assert_eq!(articles[0].provider, PUSHED_PROVIDER);
+        assert_eq!(articles[0].id, "partner:42");
+        assert_eq!(articles[0].source.as_deref(), Some("partner"));
+        assert_eq!(articles[0].published_at.as_deref(), Some("2024-05-01T10:00:00Z"));
+        assert_eq!(articles[0].tickers, vec!["NVDA"]);
+        assert_eq!(articles[0].sentiment, Some(sentiment::SentimentLabel::Bullish));
+        assert_eq!(articles[0].categories, vec!["technology"]);
+
+        assert!(parse_pushed(json!([{ "id": " " }]), "partner").is_err());
+        assert!(parse_pushed(json!("article"), "partner").is_err());
+    }
+
+    #[tokio::test]
+    async fn pushers_reusing_an_id_do_not_collide() {
+        let storage = crate::storage::MemoryStorage::new();
+        let body = json!({ "id": "42", "title": "Chipmaker raises guidance", "authors": [], "tickers": [], "topics": [] });
+        for pusher in ["scraper", "partner"] {
+            let articles = parse_pushed(body.clone(), pusher).unwrap();
+            assert_eq!(storage.store_articles(&articles).await.unwrap(), 1);
+        }
+        let ids: Vec<String> = storage.articles().into_iter().map(|article| article.id).collect();
+        assert_eq!(ids, vec!["scraper:42", "partner:42"]);
+    }
+}
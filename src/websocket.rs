@@ -3,31 +3,77 @@
 #![allow(unused_variables)]
 
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use std::pin::Pin;
 
-use futures_util::{SinkExt, StreamExt, Future};
+use futures_util::{SinkExt, Stream, StreamExt, Future};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::mpsc;
-//use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
-use async_tungstenite::tokio::accept_async_with_config;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::sync::{broadcast, mpsc, Semaphore};
+use async_tungstenite::tokio::{accept_async, accept_hdr_async_with_config};
+use tungstenite::handshake::server::{ErrorResponse, Request as UpgradeRequest, Response as UpgradeResponse};
 use async_tungstenite::tungstenite::protocol::Message;
 use async_tungstenite::tungstenite::error::Error;
 use tungstenite::protocol::WebSocketConfig;
+use tungstenite::http::{HeaderValue, StatusCode};
 use tokio::net::lookup_host;
 use serde_json::{to_value, from_str, Value};
 use serde::{Serialize, Deserialize};
-use tracing::{error, info, warn};
+use tracing::{error, info, warn, Instrument};
 use reqwest::Client;
 
-use crate::logging::{LogLevel, Logger, setup_logger};
-use crate::config::ValueConfig;
+use crate::logging::{self, LogLevel, Logger, setup_logger};
+use crate::config::{ListenerConfig, ServerConfig, UnixSocketConfig, ValueConfig};
+use crate::db::{self, DatabaseOps};
+use crate::maintenance::Maintenance;
+use crate::purge::{self, PurgeMode, PurgeRequest, ServerCaches};
+use crate::backfill::{Backfill, BackfillProgress, BackfillRequest};
+use crate::systemd;
+use crate::instance;
+use crate::chaos;
+use crate::autoscale;
+use crate::costs;
+use crate::freshness;
+use crate::latency::{self, Stage};
+use crate::retries;
+use crate::enumerations;
+use crate::api_calls;
+use crate::limiter;
+use crate::quota;
+use crate::captures;
+use crate::alerts::{self, AlertRecord, AlertState};
+use crate::clock;
+use crate::sentiment;
+use crate::taxonomy;
+use crate::webhook;
+use crate::http;
+use crate::grpc;
+use crate::entities::{EntityExtractor, EntityKind};
+use crate::timeline;
+use crate::comentions;
+use crate::portfolio::{self, Portfolio};
+use crate::normalize::parse_timestamp;
+use crate::public::PublicGate;
+use crate::subscriptions::{Removal, Subscriptions};
+use crate::session::Session;
+use crate::dedup::RequestDedup;
+use crate::resume::ParkedSessions;
+use crate::auth::TokenAuth;
+use crate::admin::{AdminError, AdminHandler};
+use crate::protocol::{self, Chunker, Encoding, Protocol};
+use crate::polling::{self, NewsHub};
+use crate::storage::Storage;
 use crate::cache::SharedLockedCache;
-use crate::fmp::FMPClient;
-use crate::alphavantage::{AlphaVantageApiClient, BASE_FUNCTION};
-use crate::marketaux::{MarketAuxApiClient, ALL_NEWS_ENDPOINT, SIMILAR_NEWS_ENDPOINT, NEWS_BY_UUID};
+use crate::alphavantage::BASE_FUNCTION;
+use crate::marketaux::{ALL_NEWS_ENDPOINT, SIMILAR_NEWS_ENDPOINT, NEWS_BY_UUID};
 use crate::request::HTTPClient;
+use crate::provider::{default_providers, NewsProvider};
+use crate::NewsResult;
 use crate::request_parser::parser::CallParser;
 use crate::request_parser::params::*;
 
@@ -38,8 +84,25 @@ const REQUEST_TIMEOUT: u32 = 408;
 const REQUEST_CANCELED: u32 = 499;
 const REQUEST_INTERNAL_ERROR: u32 = 503;
 const NOT_FOUND: u32 = 404;     
+const UNAUTHORIZED: u32 = 401;
 const REQUEST_RATE_LIMITED: u32 = 429;
 const CACHE_SIZE: usize = 1000;
+const UNIX_LISTENER: &str = "unix";
+const SUBSCRIPTION_BATCH: i64 = 500;
+/// Validity of the tokens issued by `issue_token` by default.
+const TOKEN_TTL_SECS: i64 = 24 * 3600;
+const MAINTENANCE_REASON: &str = "Server is in maintenance mode. Retry later.";
+const SATURATED_REASON: &str = "Too many open connections. Retry later.";
+/// Time given to a connection turned away to receive the reason.
+const REJECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+/// Connections turned away at once. Past it, they are closed without the reason.
+const MAX_REJECTIONS: usize = 64;
+/// Pause after a failed `accept`, e.g. when out of file descriptors.
+const ACCEPT_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+/// Default lookback of the keyword functions, in hours.
+const KEYWORD_LOOKBACK_HOURS: i64 = 24;
+/// Time given to the writer of a closing connection to send the close frame.
+const CLOSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 enum Outcome {
     Failure,
@@ -49,57 +112,248 @@ enum Outcome {
     InternalError,
     NotFound,
     RateLimited,
+    Maintenance,
+    Unauthorized,
 }
 
 pub struct ServerSocket {
-    address: String,
+    listeners: Vec<ListenerConfig>,
+    unix_socket: Option<UnixSocketConfig>,
     make: MakeResponse,
     state: Arc<PollState>,
 }
 impl ServerSocket {
     pub fn new(address: &str) -> Self {
+        let (host, port) = address.rsplit_once(':').unwrap_or((address, "8080"));
         Self {
-            address: address.to_string(),
+            listeners: vec![ListenerConfig {
+                name: ServerConfig::MAIN_LISTENER.to_string(),
+                host: host.to_string(),
+                port: port.parse().unwrap_or(8080),
+                admin: false,
+                public: false,
+            }],
+            unix_socket: None,
             make: MakeResponse::new(),
             state: Arc::new(PollState::default()),
         }
     }
 
+    /// Server listening on every address of `config.server`.
+    pub fn from_config(config: Arc<ValueConfig>) -> Self {
+        Self::from_state(config.clone(), PollState::new(config))
+    }
 
-    pub async fn run(&mut self) -> Result<(), Error> {
-        info!(message="Resolving address", addr=self.address);
-        let mut addrs = lookup_host(&self.address).await
-            .map_err(|e| println!("Error resolving address: {}", e.to_string()))
-            .unwrap();
+    /// Server listening on every address of `config.server`, sharing `state` between connections.
+    pub fn from_state(config: Arc<ValueConfig>, state: PollState) -> Self {
+        Self {
+            listeners: config.server.all_listeners(),
+            unix_socket: config.server.unix_socket.clone(),
+            make: MakeResponse::new(),
+            state: Arc::new(state),
+        }
+    }
 
-        let addr = addrs.next().ok_or_else(|| {
-            error!(err="Failed to resolve address", addr=self.address);
-            Error::Url(tungstenite::error::UrlError::NoHostName)
-        })?;
+    /// State shared between the connections, e.g. with the REST API of `http.rs`.
+    pub fn state(&self) -> Arc<PollState> {
+        self.state.clone()
+    }
 
-        info!("Setting address: {}", self.address);
-        let listener = TcpListener::bind(&addr).await
-            .map_err(|e| println!("Error: {}", e.to_string()))
-            .unwrap();
+    async fn bind(name: &str, address: &str) -> Result<TcpListener, Error> {
+        info!(message="Resolving address", listener=name, addr=address);
+        let addr = lookup_host(address).await
+            .map_err(|e| {
+                error!("Error resolving address {}: {}", address, e);
+                Error::Io(e)
+            })?
+            .next()
+            .ok_or_else(|| {
+                error!(err="Failed to resolve address", addr=address);
+                Error::Url(tungstenite::error::UrlError::NoHostName)
+            })?;
+
+        TcpListener::bind(&addr).await
+            .map_err(|e| {
+                error!("Error binding {}: {}", address, e);
+                Error::Io(e)
+            })
+    }
 
+    /// Serves every listener until maintenance mode was entered and the connections are drained.
+    pub async fn run(&mut self) -> Result<(), Error> {
+        // Bind every listener first, so a bad address fails the startup.
+        let mut listeners = Vec::new();
+        for listener_config in &self.listeners {
+            let listener = Self::bind(&listener_config.name, &listener_config.address()).await?;
+            listeners.push((listener_config.clone(), listener));
+        }
+        self.serve(listeners).await
+    }
+
+    /// Serves already bound `listeners` (e.g. on ephemeral ports), see `run`.
+    pub async fn serve(&mut self, listeners: Vec<(ListenerConfig, TcpListener)>) -> Result<(), Error> {
+        let listener_count = listeners.len();
         info!("Building RMake...");
-        let _ = self.make.build();
+        self.make.build(&self.state.providers);
 
-        println!("WebSocket server listening on: {}", self.address);
+        let mut tasks = Vec::new();
+        for (listener_config, listener) in listeners {
+            info!("WebSocket server `{}` listening on: {}", listener_config.name, listener_config.address());
+            if listener_config.public {
+                info!("Listener `{}` is in public read-only mode.", listener_config.name);
+            }
+            let context = ConnectionContext {
+                listener: listener_config.name,
+                admin: listener_config.admin && !listener_config.public,
+                public: listener_config.public,
+                authenticate: !listener_config.public,
+                peer: None,
+                client: None,
+                subscriptions: Arc::default(),
+                session: Arc::default(),
+            };
+            tasks.push(tokio::spawn(Self::accept(listener, context, self.make.clone(), self.state.clone())));
+        }
+        #[cfg(unix)]
+        if let Some(unix_socket) = &self.unix_socket {
+            let listener = Self::bind_unix(unix_socket)?;
+            info!("WebSocket server listening on unix socket: {}", unix_socket.path);
+            tasks.push(tokio::spawn(Self::accept_unix(listener, self.make.clone(), self.state.clone())));
+        }
+        #[cfg(not(unix))]
+        if self.unix_socket.is_some() {
+            warn!("Unix domain sockets are not supported on this platform. `server.unix_socket` ignored.");
+        }
+
+        systemd::notify_ready(&format!("Serving {} listeners", listener_count));
 
-        while let Ok((stream, addr)) = listener.accept().await {
-            info!("New connection from: {}", addr);
-            tokio::spawn(Self::handle_connection(stream, self.make.clone(), self.state.clone()));
+        tokio::select! {
+            _ = futures_util::future::join_all(tasks) => {},
+            _ = self.state.maintenance.drained() => {
+                info!("Maintenance: shutting down.");
+                systemd::notify_stopping("Maintenance: connections drained");
+            },
         }
 
         Ok(())
     }
 
-    async fn handle_connection(stream: TcpStream, make: MakeResponse, state: Arc<PollState>) {
-        let config = Some(WebSocketConfig::default());
+    async fn accept(listener: TcpListener, context: ConnectionContext, make: MakeResponse, state: Arc<PollState>) {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    error!("Failed to accept a connection on listener `{}`: {}", context.listener, e);
+                    tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+            };
+            info!("New connection from: {} | Listener: {}", addr, context.listener);
+            let context = ConnectionContext { peer: Some(addr.ip()), ..context.clone() };
+            Self::admit(stream, context, make.clone(), state.clone());
+        }
+    }
 
+    /// Serves `stream` in its own task, or turns it away when `server.max_connections` are open.
+    /// At most `MAX_REJECTIONS` connections are told why at once, the others are closed right away.
+    fn admit<S>(stream: S, context: ConnectionContext, make: MakeResponse, state: Arc<PollState>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        match state.connections.clone().try_acquire_owned() {
+            Ok(permit) => {
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    Self::handle_connection(stream, context, make, state).await
+                });
+            }
+            Err(_) => {
+                let max_connections = state.config.server.max_connections;
+                warn!("Rejected a connection on listener `{}`: {} connections are open", context.listener, max_connections);
+                let Ok(permit) = state.rejections.clone().try_acquire_owned() else {
+                    return;
+                };
+                let response = make.return_saturated(max_connections);
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    let reject = async {
+                        if let Ok(mut ws_stream) = accept_async(stream).await {
+                            let _ = ws_stream.send(Message::Text(response.to_string())).await;
+                            let _ = ws_stream.close(None).await;
+                        }
+                    };
+                    let _ = tokio::time::timeout(REJECT_TIMEOUT, reject).await;
+                });
+            }
+        }
+    }
+
+    /// Binds `config.path`, replacing a stale socket left by a previous run, and applies `config.mode`.
+    #[cfg(unix)]
+    fn bind_unix(config: &UnixSocketConfig) -> Result<UnixListener, Error> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        let path = std::path::Path::new(&config.path);
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                error!("Refusing to replace {}: not a socket", config.path);
+                return Err(Error::Io(std::io::Error::new(std::io::ErrorKind::AlreadyExists, config.path.clone())));
+            }
+            std::fs::remove_file(path).map_err(Error::Io)?;
+        }
+        let listener = UnixListener::bind(path).map_err(|e| {
+            error!("Error binding {}: {}", config.path, e);
+            Error::Io(e)
+        })?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(config.mode)).map_err(Error::Io)?;
+        Ok(listener)
+    }
+
+    #[cfg(unix)]
+    async fn accept_unix(listener: UnixListener, make: MakeResponse, state: Arc<PollState>) {
+        let context = ConnectionContext {
+            listener: UNIX_LISTENER.to_string(),
+            admin: false,
+            public: false,
+            authenticate: false,
+            peer: None,
+            client: None,
+            subscriptions: Arc::default(),
+            session: Arc::default(),
+        };
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _addr)) => stream,
+                Err(e) => {
+                    error!("Failed to accept a connection on unix socket: {}", e);
+                    tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+            };
+            info!("New connection on unix socket");
+            Self::admit(stream, context.clone(), make.clone(), state.clone());
+        }
+    }
 
-        let ws_stream = match accept_async_with_config(stream, config).await {
+    async fn handle_connection<S>(stream: S, context: ConnectionContext, make: MakeResponse, state: Arc<PollState>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let config = Some(WebSocketConfig::default());
+        // Subscriptions and sessions belong to the connection, not to the listener.
+        let mut context = ConnectionContext { subscriptions: Arc::default(), session: Arc::default(), ..context };
+
+        let upgrade_token = Arc::new(std::sync::Mutex::new(None));
+        let upgrade_protocol = Arc::new(std::sync::Mutex::new((Protocol::default(), Encoding::default())));
+        let callback = {
+            let (upgrade_token, upgrade_protocol) = (upgrade_token.clone(), upgrade_protocol.clone());
+            move |request: &UpgradeRequest, mut response: UpgradeResponse| -> Result<UpgradeResponse, ErrorResponse> {
+                *upgrade_token.lock().unwrap() = Self::presented_token(request);
+                *upgrade_protocol.lock().unwrap() = Self::requested_protocol(request, &mut response)?;
+                Ok(response)
+            }
+        };
+        let ws_stream = match accept_hdr_async_with_config(stream, callback, config).await {
             Ok(ws_stream) => ws_stream,
             Err(e) => {
                 error!("Error during handshake: {}", e);
@@ -108,35 +362,188 @@ impl ServerSocket {
         };
 
         let (mut write, mut read) = ws_stream.split();
-        let (tx, mut rx) = mpsc::channel::<String>(100);
+        let (protocol, encoding) = *upgrade_protocol.lock().unwrap();
+
+        // New connections are turned away while draining.
+        if state.maintenance.is_draining() {
+            let response = make.return_retry_later(state.config.server.drain_timeout_secs);
+            let _ = write.send(encoding.frame(protocol.render(response))).await;
+            let _ = write.close().await;
+            return;
+        }
+        if context.authenticate && state.auth.enabled() {
+            let from_upgrade = upgrade_token.lock().unwrap().take();
+            let (presented, in_message) = match from_upgrade {
+                Some(token) => (Some(token), false),
+                None => (Self::first_message_token(&mut read, state.config.server.auth.timeout_secs).await, true),
+            };
+            match presented.and_then(|token| state.auth.check(&token, clock::now().timestamp())) {
+                Some(client) => {
+                    info!("Connection on `{}` authenticated as `{}`", context.listener, client);
+                    if in_message {
+                        let response = make.return_success(serde_json::json!({ "authenticated": client }));
+                        let _ = write.send(encoding.frame(protocol.render(response))).await;
+                    }
+                    context.client = Some(client);
+                }
+                None => {
+                    warn!("Rejected unauthenticated connection on listener `{}`", context.listener);
+                    let response = make.return_error(Outcome::Unauthorized, "A valid token is required".to_string());
+                    let _ = write.send(encoding.frame(protocol.render(response))).await;
+                    let _ = write.close().await;
+                    return;
+                }
+            }
+        }
+        let _guard = state.maintenance.track_connection();
+        let mut notices = state.maintenance.notices();
+        // Public connections only get what `handle_public` lets through, not the news broadcast.
+        let mut news = (!context.public).then(|| state.news.subscribe());
+        let mut removals = state.removals.subscribe();
 
-        // Spawn task to handle outgoing messages
-        let write_task = tokio::spawn(async move {
-            while let Some(msg) = rx.recv().await {
-                if write.send(Message::Text(msg)).await.is_err() {
+        let (tx, mut rx) = mpsc::channel::<Value>(100);
+
+        // Spawn task to handle outgoing messages, pinging the client meanwhile. It closes the
+        // connection once every sender is dropped.
+        let heartbeat = Self::period(state.config.server.heartbeat_interval_secs);
+        let mut write_task = tokio::spawn(async move {
+            let mut pings = heartbeat.map(|period| tokio::time::interval_at(tokio::time::Instant::now() + period, period));
+            loop {
+                let ping = async {
+                    match pings.as_mut() {
+                        Some(pings) => pings.tick().await,
+                        None => std::future::pending().await,
+                    }
+                };
+                let sent = tokio::select! {
+                    msg = rx.recv() => match msg {
+                        Some(msg) => write.send(encoding.frame(protocol.render(msg))).await,
+                        None => {
+                            let _ = write.close().await;
+                            break;
+                        }
+                    },
+                    _ = ping => write.send(Message::Ping(Vec::new())).await,
+                };
+                if sent.is_err() {
                     break;
                 }
             }
         });
 
+        let push_task = state.db.clone().map(|db| {
+            tokio::spawn(Self::push_subscribed(db, context.subscriptions.clone(), context.session.clone(), tx.clone(), state.config.server.clone()))
+        });
+
         // Handle incoming messages
-        while let Some(msg) = read.next().await {
+        let make = Arc::new(make);
+        let mut requests = tokio::task::JoinSet::new();
+        let in_flight = Arc::new(Semaphore::new(state.config.server.max_requests_in_flight.max(1)));
+        let chunk_size = state.config.server.chunk_size;
+        let idle_timeout = Self::period(state.config.server.idle_timeout_secs);
+        let mut last_seen = tokio::time::Instant::now();
+        loop {
+            let idle = async move {
+                match idle_timeout {
+                    Some(timeout) => tokio::time::sleep_until(last_seen + timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+            let msg = tokio::select! {
+                msg = read.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = idle => {
+                    info!("Closing a connection idle for {:?} on listener `{}`", idle_timeout.unwrap_or_default(), context.listener);
+                    break;
+                }
+                Ok(notice) = notices.recv() => {
+                    let response = ServerResponse::new(REQUEST_INTERNAL_ERROR, Some(notice), Some(MAINTENANCE_REASON.to_string()));
+                    if tx.send(response.into_json()).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                // A lagging connection misses the results dropped in the meantime. Connections with
+                // subscriptions get the stored articles from `push_subscribed` instead.
+                Some(Ok(result)) = async {
+                    match news.as_mut() {
+                        Some(news) => Some(news.recv().await),
+                        None => None,
+                    }
+                } => {
+                    if !context.subscriptions.lock().unwrap().is_empty() {
+                        continue;
+                    }
+                    let session = context.session.lock().unwrap().clone();
+                    let sent = if session.is_empty() {
+                        let event = serde_json::json!({"event": "news", "news": result.to_json()});
+                        Self::send_response(&tx, ServerResponse::new(REQUEST_SUCCUESS, Some(event), None).into_json(), chunk_size).await
+                    } else {
+                        Self::send_matching_news(&tx, &result, &session, chunk_size).await
+                    };
+                    if !sent {
+                        break;
+                    }
+                    continue;
+                }
+                Ok(removal) = removals.recv() => {
+                    let ids = context.subscriptions.lock().unwrap().matching(&removal.article);
+                    if !ids.is_empty() && !Self::push_event(&tx, removal.to_json(&ids)).await {
+                        break;
+                    }
+                    continue;
+                }
+                Some(_) = requests.join_next(), if !requests.is_empty() => continue,
+            };
+            // Any message, pongs included, shows the client is alive.
+            last_seen = tokio::time::Instant::now();
+            // Binary frames carry the requests of MessagePack connections.
+            let msg = match msg {
+                Ok(Message::Binary(bytes)) => match encoding.decode(&bytes) {
+                    Some(text) => Ok(Message::Text(text)),
+                    None => Ok(Message::Binary(bytes)),
+                },
+                msg => msg,
+            };
             match msg {
                 Ok(Message::Text(text)) => {
                     match serde_json::from_str::<Value>(&text) {
+                        // Requests with an `id` are answered concurrently, in any order.
+                        Ok(json) if json.get("id").is_some() => {
+                            let Ok(permit) = in_flight.clone().acquire_owned().await else {
+                                break;
+                            };
+                            let (state, context, make, tx) = (state.clone(), context.clone(), make.clone(), tx.clone());
+                            let span = logging::request_span(json.get("id"));
+                            requests.spawn(
+                                async move {
+                                    let _permit = permit;
+                                    let answer = make.make(state, &context, &text).await;
+                                    Self::send_answer(&tx, answer, chunk_size).await;
+                                }
+                                .instrument(span),
+                            );
+                        }
                         Ok(_json) => {
                             let state = Arc::clone(&state);
-                            info!("Making Response...");
-                            let response = make.make(state, &text).await;
-                            info!("Sending response...");
-                            if let Err(_) = tx.send(format!("{}", &response)).await {
+                            let sent = async {
+                                info!("Making Response...");
+                                let answer = make.make(state, &context, &text).await;
+                                info!("Sending response...");
+                                Self::send_answer(&tx, answer, chunk_size).await
+                            }
+                            .instrument(logging::request_span(None))
+                            .await;
+                            if !sent {
                                 break;
                             }
                             info!("Response sent.");
                         }
                         Err(e) => {
                             error!("Failed to parse JSON: {}", e);
-                            if let Err(_) = tx.send("Invalid JSON".to_string()).await {
+                            if let Err(_) = tx.send(Value::from("Invalid JSON")).await {
                                 break;
                             }
                         }
@@ -151,154 +558,1110 @@ impl ServerSocket {
             }
         }
 
-        write_task.abort();
+        if let Some(push_task) = push_task {
+            push_task.abort();
+        }
+        requests.shutdown().await;
+        let subscriptions = std::mem::take(&mut *context.subscriptions.lock().unwrap());
+        let session = std::mem::take(&mut *context.session.lock().unwrap());
+        state.parked.park(context.client.clone(), subscriptions, session);
+        drop(tx);
+        if tokio::time::timeout(CLOSE_TIMEOUT, &mut write_task).await.is_err() {
+            write_task.abort();
+        }
+    }
+
+    /// Sends `response`, streamed in chunks of `chunk_size` items when larger (see `protocol.rs`).
+    /// `false` once the connection is closed.
+    async fn send_response(tx: &mpsc::Sender<Value>, response: Value, chunk_size: usize) -> bool {
+        for chunk in protocol::chunk(response, chunk_size) {
+            if tx.send(chunk).await.is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Sends `answer`. The items of a task result are filtered by the session and sent in chunks
+    /// of `chunk_size` as they are read, so no other copy of the result is built.
+    async fn send_answer(tx: &mpsc::Sender<Value>, answer: Answer, chunk_size: usize) -> bool {
+        match answer {
+            Answer::Response(response) => Self::send_response(tx, response, chunk_size).await,
+            Answer::TaskResult { result: Value::Array(items), session, id } => {
+                let items = items.into_iter().filter(|item| session.keeps(item));
+                Self::send_items(tx, Chunker::new(chunk_size, id), items).await
+            }
+            Answer::TaskResult { result, id, .. } => {
+                let mut response = ServerResponse::new(REQUEST_SUCCUESS, Some(result), None).into_json();
+                if let (Some(id), Some(response)) = (id, response.as_object_mut()) {
+                    response.insert("id".to_string(), id);
+                }
+                Self::send_response(tx, response, chunk_size).await
+            }
+        }
+    }
+
+    /// Pushes the articles of a polling `result` matching `session`, in chunks of `chunk_size`
+    /// serialized one by one. Nothing is pushed when none matches.
+    async fn send_matching_news(tx: &mpsc::Sender<Value>, result: &NewsResult, session: &Session, chunk_size: usize) -> bool {
+        let mut articles = result
+            .iter_articles()
+            .filter(|article| session.matches(article))
+            .filter_map(|article| to_value(article).ok())
+            .peekable();
+        if articles.peek().is_none() {
+            return true;
+        }
+        let event = serde_json::Map::from_iter([("event".to_string(), Value::from("news"))]);
+        Self::send_items(tx, Chunker::new(chunk_size, None).in_field("articles".to_string(), event), articles).await
+    }
+
+    /// Sends the chunks of `items` as they fill, then the last one. `false` once the connection is closed.
+    async fn send_items(tx: &mpsc::Sender<Value>, mut chunker: Chunker, items: impl Iterator<Item = Value>) -> bool {
+        for item in items {
+            if let Some(chunk) = chunker.push(item) {
+                if tx.send(chunk).await.is_err() {
+                    return false;
+                }
+            }
+        }
+        tx.send(chunker.finish()).await.is_ok()
+    }
+
+    /// `secs` as a period, `None` when 0.
+    fn period(secs: u64) -> Option<std::time::Duration> {
+        (secs > 0).then(|| std::time::Duration::from_secs(secs))
+    }
+
+    /// Token of an upgrade request, from its `Authorization: Bearer` header or `access_token` query parameter.
+    fn presented_token(request: &UpgradeRequest) -> Option<String> {
+        let header = request.headers().get("authorization").and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer "));
+        let query = || {
+            request.uri().query()?.split('&').find_map(|pair| pair.strip_prefix("access_token="))
+        };
+        header.or_else(query).map(str::to_string)
+    }
+
+    /// Version and encoding of the messages asked for by an upgrade request, see `protocol.rs`.
+    /// The selected subprotocol is added to the `response`.
+    fn requested_protocol(request: &UpgradeRequest, response: &mut UpgradeResponse) -> Result<(Protocol, Encoding), ErrorResponse> {
+        let offered: Vec<&str> = request
+            .headers()
+            .get_all("sec-websocket-protocol")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .collect();
+        let param = |name: &str| request.uri().query()?.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='));
+        match Protocol::negotiate(&offered, param("version"), param("encoding")) {
+            Ok((protocol, encoding)) => {
+                if !offered.is_empty() {
+                    if let Ok(value) = HeaderValue::from_str(&protocol.subprotocol(encoding)) {
+                        response.headers_mut().insert("sec-websocket-protocol", value);
+                    }
+                }
+                Ok((protocol, encoding))
+            }
+            Err(reason) => {
+                warn!("Refused a handshake: {}", reason);
+                let mut error = ErrorResponse::new(Some(reason));
+                *error.status_mut() = StatusCode::BAD_REQUEST;
+                Err(error)
+            }
+        }
+    }
+
+    /// Token of the first message, `{"auth": "<token>"}`, if it comes within `timeout_secs`.
+    async fn first_message_token<R>(read: &mut R, timeout_secs: u64) -> Option<String>
+    where
+        R: Stream<Item = Result<Message, Error>> + Unpin,
+    {
+        let message = tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), read.next()).await.ok()??.ok()?;
+        let Message::Text(text) = message else {
+            return None;
+        };
+        from_str::<Value>(&text).ok()?.get("auth")?.as_str().map(str::to_string)
+    }
+
+    /// Pushes the articles stored after the cursor of the subscriptions of the connection that
+    /// match them and its session, and the ones due again in acknowledgement mode (see `acks.rs`).
+    async fn push_subscribed(db: Arc<dyn Storage>, subscriptions: Arc<std::sync::Mutex<Subscriptions>>, session: Arc<std::sync::Mutex<Session>>, tx: mpsc::Sender<Value>, config: ServerConfig) {
+        let interval = std::time::Duration::from_secs(config.subscription_interval_secs.max(1));
+        let ack_timeout = std::time::Duration::from_secs(config.ack_timeout_secs);
+        let mut behind = false;
+        loop {
+            // Right away while resuming: more articles are waiting after a full batch.
+            if !behind {
+                tokio::time::sleep(interval).await;
+            }
+            behind = false;
+            let due = subscriptions.lock().unwrap().deliveries().map(|d| d.due(ack_timeout, config.ack_max_attempts)).unwrap_or_default();
+            for event in due {
+                if !Self::push_event(&tx, event).await {
+                    return;
+                }
+            }
+            let Some(cursor) = subscriptions.lock().unwrap().cursor() else {
+                continue;
+            };
+            let batch = match db.stored_after(cursor, SUBSCRIPTION_BATCH).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    warn!("Failed to read the articles of the subscriptions: {}", e);
+                    continue;
+                }
+            };
+            behind = batch.len() as i64 == SUBSCRIPTION_BATCH;
+            let mut last = None;
+            for stored in batch {
+                let ids = subscriptions.lock().unwrap().matching(&stored.article);
+                if !ids.is_empty() && session.lock().unwrap().matches(&stored.article) {
+                    let event = serde_json::json!({"event": "article", "subscriptions": ids, "seq": stored.seq, "stored_at": stored.stored_at, "article": stored.article});
+                    if let Some(deliveries) = subscriptions.lock().unwrap().deliveries() {
+                        // The rest waits for the acknowledgements.
+                        if deliveries.pending() >= config.max_unacked {
+                            behind = false;
+                            break;
+                        }
+                        deliveries.sent(stored.seq, event.clone());
+                    }
+                    if !Self::push_event(&tx, event).await {
+                        return;
+                    }
+                    latency::record(Stage::Delivered, [&stored.article]);
+                }
+                last = Some(stored.seq);
+            }
+            if let Some(last) = last {
+                subscriptions.lock().unwrap().advance(cursor, last);
+            }
+        }
+    }
+
+    /// Pushes `event`, `false` once the connection is closed.
+    async fn push_event(tx: &mpsc::Sender<Value>, event: Value) -> bool {
+        let response = ServerResponse::new(REQUEST_SUCCUESS, Some(event), None);
+        tx.send(response.into_json()).await.is_ok()
     }
 }
 
+/// Listener-dependent context of a connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionContext {
+    pub listener: String,
+    /// Whether `admin` requests are accepted.
+    pub admin: bool,
+    /// Whether the connection is restricted to the public read-only functions.
+    pub public: bool,
+    /// Whether a token is required, when authentication is enabled, see `auth.rs`.
+    pub authenticate: bool,
+    /// Address of the client, for TCP connections.
+    pub peer: Option<IpAddr>,
+    /// Name of the authenticated client.
+    pub client: Option<String>,
+    /// Live subscriptions of the connection, see `subscriptions.rs`.
+    pub subscriptions: Arc<std::sync::Mutex<Subscriptions>>,
+    /// Saved filters of the connection, see `session.rs`.
+    pub session: Arc<std::sync::Mutex<Session>>,
+}
+
 pub struct PollState {
-    http_client: Arc<HTTPClient>,
-    client: Arc<Client>,
-    cache: Arc<Mutex<SharedLockedCache>>,
-    config: Arc<ValueConfig>,
+    pub(crate) cache: Arc<Mutex<SharedLockedCache>>,
+    pub(crate) config: Arc<ValueConfig>,
+    pub(crate) providers: Vec<Arc<dyn NewsProvider>>,
+    pub(crate) maintenance: Arc<Maintenance>,
+    /// Storage used by the admin commands acting on stored data, if the database is reachable.
+    pub(crate) db: Option<Arc<dyn Storage>>,
+    /// Rate limiter and response cache of the public listeners.
+    pub(crate) public: Arc<PublicGate>,
+    /// Task requests in flight or just answered, see `dedup.rs`.
+    dedup: Arc<RequestDedup>,
+    /// Subscriptions of the closed connections, see `resume.rs`.
+    parked: Arc<ParkedSessions>,
+    /// Progress of the latest `backfill` admin command.
+    backfill: Arc<std::sync::Mutex<BackfillProgress>>,
+    /// Polling results forwarded to every connection, published by the polling loop (`server.poll`).
+    pub(crate) news: NewsHub,
+    /// Articles removed by the admin commands, forwarded to the matching subscriptions.
+    removals: broadcast::Sender<Arc<Removal>>,
+    auth: Arc<TokenAuth>,
+    /// One permit per open connection, see `server.max_connections`.
+    connections: Arc<Semaphore>,
+    /// One permit per connection being turned away, see `MAX_REJECTIONS`.
+    rejections: Arc<Semaphore>,
+    /// Runtime management commands, see `admin.rs`.
+    admin: Arc<AdminHandler>,
 }
 impl Default for PollState{
     fn default() -> Self {
+        Self::new(Arc::new(ValueConfig::new().unwrap()))
+    }
+}
+impl PollState {
+    pub fn new(config: Arc<ValueConfig>) -> Self {
+        let http_client = Arc::new(HTTPClient::new().unwrap());
+        let client = Arc::new(Client::new());
+        let state = Self::with_providers(config.clone(), Vec::new());
+        let providers = default_providers(http_client, client, state.cache.clone(), config);
+        Self { providers, ..state }
+    }
+
+    /// State serving `providers` instead of the default ones, e.g. mocked providers in tests.
+    pub fn with_providers(config: Arc<ValueConfig>, providers: Vec<Arc<dyn NewsProvider>>) -> Self {
         Self {
-            http_client: Arc::new(HTTPClient::new().unwrap()),
-            client: Arc::new(Client::new()),
             cache: Arc::new(Mutex::new(SharedLockedCache::new(CACHE_SIZE))),
-            config: Arc::new(ValueConfig::new().unwrap()),   
+            providers,
+            maintenance: Arc::new(Maintenance::default()),
+            db: None,
+            public: Arc::new(PublicGate::new(&config.public)),
+            dedup: Arc::new(RequestDedup::new(config.server.dedup_window_ms)),
+            parked: Arc::new(ParkedSessions::new(config.server.resume_ttl_secs)),
+            backfill: Arc::default(),
+            news: broadcast::channel(config.server.broadcast_capacity.max(1)).0,
+            removals: broadcast::channel(config.server.broadcast_capacity.max(1)).0,
+            auth: Arc::new(TokenAuth::new(&config.server.auth)),
+            connections: Arc::new(Semaphore::new(match config.server.max_connections {
+                0 => Semaphore::MAX_PERMITS,
+                max => max,
+            })),
+            rejections: Arc::new(Semaphore::new(MAX_REJECTIONS)),
+            admin: Arc::default(),
+            config,
         }
     }
+
+    pub fn with_database(mut self, db: Arc<dyn Storage>) -> Self {
+        self.db = Some(db);
+        self
+    }
 }
 struct Collection;
 impl Collection {
-    async fn get_news_from_alphavantage_unpinned(state: Arc<PollState>, args: Arc<Value>) -> Value {
-        let alphavantage_client = AlphaVantageApiClient::new(
-            state.client.clone(),
-            state.cache.clone(),
-            state.config.clone(),
-        );
-        match alphavantage_client.poll(args).await {
+    async fn get_news_from_provider_unpinned(provider: Arc<dyn NewsProvider>, args: Arc<Value>) -> Value {
+        match autoscale::track(provider.name(), provider.fetch(args)).await {
             Ok(v) => v,
-            Err(e) => Value::String(format!("AlphaVantage Client polling failed: {}", e)),
+            Err(e) => Value::String(format!("{} provider polling failed: {}", provider.name(), e)),
         }
     }
 
-    async fn get_news_from_marketaux_unpinned(state: Arc<PollState>, args: Arc<Value>) -> Value {
-        let marketaux_client = MarketAuxApiClient::new(
-            state.client.clone(),
-            state.cache.clone(),
-            state.config.clone(),
-        );
+    /// Polling function of `provider`, registered as `<name>_news_polling`.
+    fn provider_func(provider: Arc<dyn NewsProvider>) -> Func {
+        Arc::new(move |_state: Arc<PollState>, args: Arc<Value>| -> Pin<Box<dyn Future<Output = Value> + Send + 'static>> {
+            let provider = provider.clone();
+            Box::pin(async move {
+                Collection::get_news_from_provider_unpinned(provider, args).await
+            })
+        })
+    }
+
+    /// Stored articles matching one of the `keywords` param (array or comma separated), published
+    /// since the `since` param (RFC 3339, last 24 hours by default).
+    async fn keyword_news(state: Arc<PollState>, args: Arc<Value>) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return Value::String("Database is not available".to_string());
+        };
+        let keywords = Collection::list_param(&args, "keywords");
+        if keywords.is_empty() {
+            return Value::String("Missing `keywords` param".to_string());
+        }
+        let since = Collection::time_param(&args, "since", KEYWORD_LOOKBACK_HOURS);
+        let limit = args.get("limit").and_then(Value::as_i64).unwrap_or(50);
+        match db.articles_with_keywords(&keywords, &since, limit).await {
+            Ok(articles) => to_value(articles).unwrap_or_default(),
+            Err(e) => Value::String(format!("Keyword search failed: {}", e)),
+        }
+    }
 
-        match marketaux_client.poll(args).await {
-            Ok(v) => v,
-            Err(e) => Value::String(format!("MarketAux Client polling failed: {}", e)),
+    /// Stored articles in one of the internal taxonomy topics of the `topics` param (array or comma
+    /// separated), published since the `since` param (RFC 3339, last 24 hours by default).
+    async fn topic_news(state: Arc<PollState>, args: Arc<Value>) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return Value::String("Database is not available".to_string());
+        };
+        let topics = Collection::list_param(&args, "topics");
+        if topics.is_empty() {
+            return Value::String("Missing `topics` param".to_string());
+        }
+        let since = Collection::time_param(&args, "since", KEYWORD_LOOKBACK_HOURS);
+        let limit = args.get("limit").and_then(Value::as_i64).unwrap_or(50);
+        match db.articles_with_categories(&topics, &since, limit).await {
+            Ok(articles) => to_value(articles).unwrap_or_default(),
+            Err(e) => Value::String(format!("Topic search failed: {}", e)),
         }
     }
 
-    async fn get_news_from_fmp_unpinned(state: Arc<PollState>, args: Arc<Value>) -> Value {
-        let fmp_client = FMPClient::new(
-            state.http_client.clone(),
-            state.cache.clone(),
-            state.config.clone(),
-        );
+    /// Stored articles naming one of the people, organizations or locations of the `entities` param
+    /// (array or comma separated, aliases such as `Fed chair` resolved as in `entities.rs`), of the
+    /// `kind` param if given, published since the `since` param (RFC 3339, last 24 hours by default).
+    async fn entity_news(state: Arc<PollState>, args: Arc<Value>) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return Value::String("Database is not available".to_string());
+        };
+        let extractor = EntityExtractor::new(&state.config.entities);
+        let entities: Vec<String> = Collection::list_param(&args, "entities").iter().map(|name| extractor.resolve(name)).collect();
+        if entities.is_empty() {
+            return Value::String("Missing `entities` param".to_string());
+        }
+        let kind = match args.get("kind").and_then(Value::as_str) {
+            Some(kind) => match kind.parse::<EntityKind>() {
+                Ok(kind) => Some(kind),
+                Err(e) => return Value::String(e),
+            },
+            None => None,
+        };
+        let since = Collection::time_param(&args, "since", KEYWORD_LOOKBACK_HOURS);
+        let limit = args.get("limit").and_then(Value::as_i64).unwrap_or(50);
+        match db.articles_with_entities(&entities, kind, &since, limit).await {
+            Ok(articles) => to_value(articles).unwrap_or_default(),
+            Err(e) => Value::String(format!("Entity search failed: {}", e)),
+        }
+    }
 
-        match fmp_client.poll(args).await {
-            Ok(v) => v,
-            Err(e) => Value::String(format!("FMP Client polling failed: {}", e)),
+    /// Timeline of the `ticker` param between the `from` and `to` params (RFC 3339, the last
+    /// `timeline.default_days` by default), built from the stored articles and stored, see `timeline.rs`.
+    async fn ticker_timeline(state: Arc<PollState>, args: Arc<Value>) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return Value::String("Database is not available".to_string());
+        };
+        let Some(ticker) = args.get("ticker").and_then(Value::as_str).filter(|t| !t.trim().is_empty()) else {
+            return Value::String("Missing `ticker` param".to_string());
+        };
+        let config = &state.config.timeline;
+        let from = Collection::time_param(&args, "from", config.default_days as i64 * 24);
+        let to = Collection::time_param(&args, "to", 0);
+        let (Some(from), Some(to)) = (parse_timestamp(&from), parse_timestamp(&to)) else {
+            return Value::String("Invalid `from` or `to` param".to_string());
+        };
+        match timeline::build(db.as_ref(), ticker.trim(), from, to, config).await {
+            Ok(timeline) => to_value(timeline).unwrap_or_default(),
+            Err(e) => Value::String(format!("Timeline failed: {}", e)),
         }
     }
 
-    fn alphvantage_func(
-        state: Arc<PollState>,
-        args: Arc<Value>,
-    ) -> Pin<Box<dyn Future<Output = Value> + Send + 'static>> {
-        Box::pin(async move {
-            Collection::get_news_from_alphavantage_unpinned(state, args).await
-        })
+    /// Co-mention graph of the stored articles published between the `from` and `to` params (RFC
+    /// 3339, the last `comentions.window_hours` by default), restricted to the neighbours of the
+    /// `tickers` param (array or comma separated) if given, see `comentions.rs`.
+    async fn co_mentions(state: Arc<PollState>, args: Arc<Value>) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return Value::String("Database is not available".to_string());
+        };
+        let config = &state.config.comentions;
+        let from = Collection::time_param(&args, "from", config.window_hours as i64);
+        let to = Collection::time_param(&args, "to", 0);
+        let (Some(from), Some(to)) = (parse_timestamp(&from), parse_timestamp(&to)) else {
+            return Value::String("Invalid `from` or `to` param".to_string());
+        };
+        let tickers = Collection::list_param(&args, "tickers");
+        match comentions::graph(db.as_ref(), from, to, &tickers, config).await {
+            Ok(graph) => to_value(graph).unwrap_or_default(),
+            Err(e) => Value::String(format!("Co-mention graph failed: {}", e)),
+        }
     }
 
-     fn marketaux_func(
-        state: Arc<PollState>,
-        args: Arc<Value>,
-    ) -> Pin<Box<dyn Future<Output = Value> + Send + 'static>> {
-        Box::pin(async move {
-            Collection::get_news_from_marketaux_unpinned(state, args).await
-        })
+    /// Positions, exposure-weighted sentiment and exposure-ranked news of the `portfolio` param (an
+    /// object of ticker to weight) between the `from` and `to` params (RFC 3339, the last
+    /// `portfolio.window_hours` by default), see `portfolio.rs`.
+    async fn portfolio_news(state: Arc<PollState>, args: Arc<Value>) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return Value::String("Database is not available".to_string());
+        };
+        let config = &state.config.portfolio;
+        let Some(positions) = args.get("portfolio") else {
+            return Value::String("Missing `portfolio` param".to_string());
+        };
+        let portfolio = match Portfolio::from_value(positions, config) {
+            Ok(portfolio) => portfolio,
+            Err(e) => return Value::String(e),
+        };
+        let from = Collection::time_param(&args, "from", config.window_hours as i64);
+        let to = Collection::time_param(&args, "to", 0);
+        let (Some(from), Some(to)) = (parse_timestamp(&from), parse_timestamp(&to)) else {
+            return Value::String("Invalid `from` or `to` param".to_string());
+        };
+        match portfolio::news(db.as_ref(), &portfolio, from, to, config).await {
+            Ok(news) => to_value(news).unwrap_or_default(),
+            Err(e) => Value::String(format!("Portfolio news failed: {}", e)),
+        }
     }
 
-    fn fmp_func(
-        state: Arc<PollState>,
-        args: Arc<Value>,
-    ) -> Pin<Box<dyn Future<Output = Value> + Send + 'static>> {
-        Box::pin(async move {
-            Collection::get_news_from_fmp_unpinned(state, args).await
-        })
+    /// The `key` param, given as an array or as a comma separated string.
+    fn list_param(args: &Value, key: &str) -> Vec<String> {
+        match args.get(key) {
+            Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+            Some(Value::String(values)) => values.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Most frequent keywords of the articles published between the `from` and `to` params (RFC 3339,
+    /// last 24 hours by default).
+    async fn trending_keywords(state: Arc<PollState>, args: Arc<Value>) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return Value::String("Database is not available".to_string());
+        };
+        let from = Collection::time_param(&args, "from", KEYWORD_LOOKBACK_HOURS);
+        let to = Collection::time_param(&args, "to", 0);
+        let limit = args.get("limit").and_then(Value::as_i64).unwrap_or(20);
+        match db.trending_keywords(&from, &to, limit).await {
+            Ok(trending) => Value::Array(trending.into_iter()
+                .map(|(keyword, count)| serde_json::json!({ "keyword": keyword, "count": count }))
+                .collect()),
+            Err(e) => Value::String(format!("Trending keywords query failed: {}", e)),
+        }
+    }
+
+    /// The `key` param, or the time `default_hours_ago` hours ago.
+    fn time_param(args: &Value, key: &str, default_hours_ago: i64) -> String {
+        match args.get(key).and_then(Value::as_str) {
+            Some(time) => time.to_string(),
+            None => (clock::now() - chrono::Duration::hours(default_hours_ago))
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        }
+    }
+
+    fn func<F, Fut>(f: F) -> Func
+    where
+        F: Fn(Arc<PollState>, Arc<Value>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Value> + Send + 'static,
+    {
+        Arc::new(move |state, args| -> Pin<Box<dyn Future<Output = Value> + Send + 'static>> { Box::pin(f(state, args)) })
     }
 }
 
 
-type Func = fn(Arc<PollState>, Arc<Value>) -> Pin<Box<dyn Future<Output = Value> + Send + 'static>>;
+type Func = Arc<dyn Fn(Arc<PollState>, Arc<Value>) -> Pin<Box<dyn Future<Output = Value> + Send + 'static>> + Send + Sync>;
+
+/// A registered task function, as listed by the `describe` request.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FunctionSpec {
+    pub name: String,
+    pub description: String,
+    /// Provider polled, `None` for the functions querying the stored articles.
+    pub provider: Option<String>,
+    /// JSON Schema of the `params` of the task.
+    pub params: Value,
+}
+impl FunctionSpec {
+    fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            provider: None,
+            params: serde_json::json!({ "type": "object", "properties": {}, "required": [] }),
+        }
+    }
+
+    fn polling(provider: &str) -> Self {
+        let mut spec = Self::new(&format!("{}_news_polling", provider), &format!("Polls the {} API.", provider));
+        spec.provider = Some(provider.to_string());
+        // The query depends on the endpoint, see the client of the provider.
+        spec.params["additionalProperties"] = Value::Bool(true);
+        spec.param("function", serde_json::json!({ "type": "string" }), "Endpoint or fetch type, e.g. `fmp articles`.", false)
+    }
+
+    fn param(mut self, name: &str, mut schema: Value, description: &str, required: bool) -> Self {
+        schema["description"] = Value::from(description);
+        self.params["properties"][name] = schema;
+        if required {
+            if let Some(required) = self.params["required"].as_array_mut() {
+                required.push(Value::from(name));
+            }
+        }
+        self
+    }
+
+    /// Array of strings, or comma separated string.
+    fn list_param(self, name: &str, description: &str, required: bool) -> Self {
+        let schema = serde_json::json!({ "type": ["array", "string"], "items": { "type": "string" } });
+        self.param(name, schema, description, required)
+    }
+
+    fn time_param(self, name: &str, description: &str) -> Self {
+        self.param(name, serde_json::json!({ "type": "string", "format": "date-time" }), description, false)
+    }
+
+    fn limit_param(self, default: i64) -> Self {
+        let description = format!("Results returned at most, {} by default.", default);
+        self.param("limit", serde_json::json!({ "type": "integer", "minimum": 1 }), &description, false)
+    }
+}
+
+/// Answer to a request.
+pub enum Answer {
+    Response(Value),
+    /// Success of a task, sent without the items of `result` that `session` does not keep.
+    TaskResult { result: Value, session: Session, id: Option<Value> },
+}
+impl From<Value> for Answer {
+    fn from(response: Value) -> Self {
+        Answer::Response(response)
+    }
+}
 
 #[derive(Clone)]
 pub struct MakeResponse{
-    fn_map: HashMap<String, Box<Func>>,
+    fn_map: HashMap<String, Func>,
+    specs: HashMap<String, FunctionSpec>,
 }
 impl MakeResponse {
     pub fn new() -> Self {
         Self {
             fn_map: HashMap::new(),
+            specs: HashMap::new(),
+        }
+    }
+
+    fn register_function(&mut self, func: Func, spec: FunctionSpec) {
+        self.fn_map.insert(spec.name.clone(), func);
+        self.specs.insert(spec.name.clone(), spec);
+    }
+
+    pub fn build(&mut self, providers: &[Arc<dyn NewsProvider>]) {
+        for provider in providers {
+            self.register_function(Collection::provider_func(provider.clone()), FunctionSpec::polling(provider.name()));
         }
+        let spec = FunctionSpec::new("keyword_news", "Stored articles matching one of the keywords.")
+            .list_param("keywords", "Keywords to match.", true)
+            .time_param("since", "Earliest publication time, 24 hours ago by default.")
+            .limit_param(50);
+        self.register_function(Collection::func(Collection::keyword_news), spec);
+        let spec = FunctionSpec::new("trending_keywords", "Most frequent keywords of the stored articles.")
+            .time_param("from", "Earliest publication time, 24 hours ago by default.")
+            .time_param("to", "Latest publication time, now by default.")
+            .limit_param(20);
+        self.register_function(Collection::func(Collection::trending_keywords), spec);
+        let spec = FunctionSpec::new("topic_news", "Stored articles in one of the topics of the internal taxonomy.")
+            .list_param("topics", "Topics, see `taxonomy.rs`.", true)
+            .time_param("since", "Earliest publication time, 24 hours ago by default.")
+            .limit_param(50);
+        self.register_function(Collection::func(Collection::topic_news), spec);
+        let kind = serde_json::json!({ "type": "string", "enum": ["person", "organization", "location"] });
+        let spec = FunctionSpec::new("entity_news", "Stored articles naming one of the people, organizations or locations.")
+            .list_param("entities", "Entities, aliases such as `Fed chair` included.", true)
+            .param("kind", kind, "Kind of the entities.", false)
+            .time_param("since", "Earliest publication time, 24 hours ago by default.")
+            .limit_param(50);
+        self.register_function(Collection::func(Collection::entity_news), spec);
+        let spec = FunctionSpec::new("ticker_timeline", "Timeline of a ticker built from the stored articles.")
+            .param("ticker", serde_json::json!({ "type": "string" }), "Ticker, e.g. `AAPL`.", true)
+            .time_param("from", "Start of the timeline, `timeline.default_days` ago by default.")
+            .time_param("to", "End of the timeline, now by default.");
+        self.register_function(Collection::func(Collection::ticker_timeline), spec);
+        let spec = FunctionSpec::new("co_mentions", "Graph of the tickers mentioned together in the stored articles.")
+            .time_param("from", "Earliest publication time, `comentions.window_hours` ago by default.")
+            .time_param("to", "Latest publication time, now by default.")
+            .list_param("tickers", "Restricts the graph to the neighbours of these tickers.", false);
+        self.register_function(Collection::func(Collection::co_mentions), spec);
+        let portfolio = serde_json::json!({ "type": "object", "additionalProperties": { "type": "number" } });
+        let spec = FunctionSpec::new("portfolio_news", "Exposure-weighted sentiment and news of a portfolio.")
+            .param("portfolio", portfolio, "Weight of each ticker.", true)
+            .time_param("from", "Earliest publication time, `portfolio.window_hours` ago by default.")
+            .time_param("to", "Latest publication time, now by default.");
+        self.register_function(Collection::func(Collection::portfolio_news), spec);
     }
 
-    fn register_function(&mut self, where_: String, func: Func) {
-        self.fn_map.insert(where_, Box::new(func));
+    /// Registered functions by name, only the public ones on a public connection.
+    fn handle_describe(&self, state: &PollState, context: &ConnectionContext) -> Value {
+        let mut specs: Vec<&FunctionSpec> = self
+            .specs
+            .values()
+            .filter(|spec| !context.public || state.public.allows(&spec.name))
+            .collect();
+        specs.sort_by(|a, b| a.name.cmp(&b.name));
+        self.return_success(serde_json::json!({ "functions": specs }))
     }
 
-    pub fn build(&mut self) {
-        self.register_function("alphavantage_news_polling".to_string(), Collection::alphvantage_func);
-        self.register_function("marketaux_news_polling".to_string(), Collection::marketaux_func);
-        self.register_function("fmp_news_polling".to_string(), Collection::fmp_func);
+    /// Answer to the request `s`, echoing its `id` when it has one.
+    pub async fn make(&self, state: Arc<PollState>, context: &ConnectionContext, s: &str) -> Answer {
+        let id = serde_json::from_str::<Value>(s).ok().and_then(|mut request| request.get_mut("id").map(Value::take));
+        match self.answer(state, context, s).await {
+            Answer::Response(mut response) => {
+                if let (Some(id), Some(response)) = (id, response.as_object_mut()) {
+                    response.insert("id".to_string(), id);
+                }
+                Answer::Response(response)
+            }
+            Answer::TaskResult { result, session, .. } => Answer::TaskResult { result, session, id },
+        }
     }
 
-    pub async fn make(&self, state: Arc<PollState>, s: &str) -> Value {
+    async fn answer(&self, state: Arc<PollState>, context: &ConnectionContext, s: &str) -> Answer {
         println!("Parsing request...");
         let call_request = match CallParser::key_lookup_parse_json(s) {
             Ok(req) => req,
-            Err(err) => return self.return_error(Outcome::Failure, err),
+            Err(err) => return self.return_error(Outcome::Failure, err).into(),
         };
+
+        if call_request.target.to_str() == "describe" {
+            return self.handle_describe(&state, context).into();
+        }
+
+        if context.public {
+            return self.handle_public(state, context, call_request).await.into();
+        }
+
+        if call_request.target.to_str() == "admin" {
+            if !context.admin {
+                warn!("Rejected admin request on listener `{}`", context.listener);
+                return self.return_error(Outcome::NotAllowed, "Admin requests are not accepted on this listener".to_string()).into();
+            }
+            if let Some(admin_args) = call_request.args.for_admin {
+                return self.handle_admin(state, admin_args).await.into();
+            }
+        }
+
+        if call_request.target.to_str() == "subscription" {
+            if let Some(subscription_args) = call_request.args.for_subscription {
+                return self.handle_subscription(state, context, subscription_args).await.into();
+            }
+        }
+
+        if call_request.target.to_str() == "session" {
+            if let Some(session_args) = call_request.args.for_session {
+                return self.handle_session(context, session_args).into();
+            }
+        }
     
         if call_request.target.to_str() == "task" {
             if let Some(task_args) = call_request.args.for_task {
                 if let TaskFunction::AggregatedPolling = task_args.function {
-                    return self.handle_task(state, task_args).await;
+                    return self.handle_task(state, context, task_args).await;
                 }
             }
         }
     
-        self.return_error(Outcome::NotAllowed, "Invalid request".to_string())
+        self.return_error(Outcome::NotAllowed, "Invalid request".to_string()).into()
     }
-    async fn handle_task(&self, state: Arc<PollState>, task_args: TaskArgs) -> Value {
+    /// Task requests, completed and filtered by the session of the connection (see `session.rs`).
+    async fn handle_task(&self, state: Arc<PollState>, context: &ConnectionContext, task_args: TaskArgs) -> Answer {
         let where_ = task_args.look_for.where_;
         info!("Extracting Args...");
         if let Some(args) = task_args.params {
             info!("Executing task function: {}", &where_);
             if let Some(func) = self.map_func(&where_) {
-                let args = Arc::new(to_value(args).unwrap());
-                let result = func(state, args).await;
-                return self.return_success(result);
+                let session = context.session.lock().unwrap().clone();
+                let mut args: serde_json::Map<String, Value> = args.into_iter().collect();
+                session.complete(&mut args);
+                let args = Value::Object(args);
+                let result = match RequestDedup::key(context.client.as_deref(), context.peer, &where_, &args) {
+                    Some(key) => state.dedup.clone().run(key, func(state, Arc::new(args))).await,
+                    None => func(state, Arc::new(args)).await,
+                };
+                return Answer::TaskResult { result, session, id: None };
             } else {
                 error!("Invalid task function: {}", &where_);
-                return self.return_error(Outcome::Failure, format!("Invalid task function: {}", &where_));
+                return self.return_error(Outcome::Failure, format!("Invalid task function: {}", &where_)).into();
             }
         }
     
-        self.return_error(Outcome::Failure, "Invalid task arguments".to_string())
+        self.return_error(Outcome::Failure, "Invalid task arguments".to_string()).into()
     }
     
-    fn map_func(&self, where_: &String) -> Option<Box<Func>> {
+    /// Requests of a public connection: rate limited, restricted to `public.functions` and cached.
+    async fn handle_public(&self, state: Arc<PollState>, context: &ConnectionContext, call_request: CallRequest) -> Value {
+        if let Some(peer) = context.peer {
+            if let Err(retry_after_secs) = state.public.check_rate(peer) {
+                return self.return_error(Outcome::RateLimited, format!("Rate limited. Retry in {} seconds.", retry_after_secs));
+            }
+        }
+        let task_args = match (call_request.target.to_str(), call_request.args.for_task) {
+            ("task", Some(task_args)) if matches!(task_args.function, TaskFunction::AggregatedPolling) => task_args,
+            _ => return self.return_error(Outcome::NotAllowed, "Only read-only tasks are accepted on this listener".to_string()),
+        };
+        let where_ = task_args.look_for.where_;
+        let (true, Some(func)) = (state.public.allows(&where_), self.map_func(&where_)) else {
+            return self.return_error(Outcome::NotAllowed, format!("Task function `{}` is not public", where_));
+        };
+        let Some(params) = task_args.params else {
+            return self.return_error(Outcome::Failure, "Invalid task arguments".to_string());
+        };
+
+        let args = state.public.cap_limit(to_value(params).unwrap_or_default());
+        let key = format!("{}:{}", where_, args);
+        if let Some(result) = state.public.cached(&key).await {
+            return self.return_success(result);
+        }
+        let result = func(state.clone(), Arc::new(args)).await;
+        // Errors are reported as strings, they are not cached.
+        if !result.is_string() {
+            state.public.store(&key, &result).await;
+        }
+        self.return_success(result)
+    }
+
+    /// `set`/`get`/`clear` requests of the saved filters, see `session.rs`.
+    fn handle_session(&self, context: &ConnectionContext, session_args: SessionArgs) -> Value {
+        let mut session = context.session.lock().unwrap();
+        match session_args.command {
+            SessionCommand::Set => {
+                let params = session_args.params.unwrap_or_default().into_iter().collect();
+                if let Err(e) = session.set(&params) {
+                    return self.return_error(Outcome::Failure, e);
+                }
+            }
+            SessionCommand::Get => {}
+            SessionCommand::Clear => session.clear(),
+            SessionCommand::Unknown => return self.return_error(Outcome::Failure, "Unknown session command".to_string()),
+        }
+        self.return_success(serde_json::json!({ "session": *session }))
+    }
+
+    /// `subscribe`/`unsubscribe` requests, see `subscriptions.rs`.
+    async fn handle_subscription(&self, state: Arc<PollState>, context: &ConnectionContext, subscription_args: SubscriptionArgs) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return self.return_error(Outcome::InternalError, "Database is not available".to_string());
+        };
+        let params = to_value(subscription_args.params.unwrap_or_default()).unwrap_or_default();
+        match subscription_args.command {
+            SubscriptionCommand::Subscribe => {
+                // After the last article stored, or the last one received before a disconnect.
+                let after = match params.get("resume_from") {
+                    Some(seq) => match seq.as_u64() {
+                        Some(seq) => seq,
+                        None => return self.return_error(Outcome::Failure, format!("Invalid `resume_from`: {}", seq)),
+                    },
+                    None => match db.last_seq().await {
+                        Ok(seq) => seq,
+                        Err(e) => return self.return_error(Outcome::InternalError, e.to_string()),
+                    },
+                };
+                let tickers = Collection::list_param(&params, "tickers");
+                let topics = Collection::list_param(&params, "topics");
+                let mut subscriptions = context.subscriptions.lock().unwrap();
+                let subscribed = subscriptions.subscribe(&tickers, &topics, after, state.config.server.max_subscriptions);
+                if subscribed.is_some() && params.get("ack").and_then(Value::as_bool) == Some(true) {
+                    subscriptions.require_acks();
+                }
+                match subscribed {
+                    Some(subscription) => self.return_success(serde_json::json!({
+                        "subscription": subscription.id,
+                        "tickers": subscription.tickers,
+                        "topics": subscription.topics,
+                        "resume_from": after,
+                        "resume_token": state.parked.enabled().then(|| subscriptions.resume_token()),
+                    })),
+                    None => self.return_error(Outcome::Failure, format!("At most {} subscriptions per connection", state.config.server.max_subscriptions)),
+                }
+            }
+            SubscriptionCommand::Unsubscribe => {
+                let id = params.get("subscription").and_then(Value::as_str);
+                let unsubscribed = context.subscriptions.lock().unwrap().unsubscribe(id);
+                match id {
+                    Some(id) if unsubscribed == 0 => self.return_error(Outcome::NotFound, format!("No subscription {}", id)),
+                    _ => self.return_success(serde_json::json!({"unsubscribed": unsubscribed})),
+                }
+            }
+            SubscriptionCommand::Resume => {
+                let Some(token) = params.get("token").and_then(Value::as_str) else {
+                    return self.return_error(Outcome::Failure, "Missing `token`".to_string());
+                };
+                let resume_from = match params.get("resume_from") {
+                    Some(seq) => match seq.as_u64() {
+                        Some(seq) => Some(seq),
+                        None => return self.return_error(Outcome::Failure, format!("Invalid `resume_from`: {}", seq)),
+                    },
+                    None => None,
+                };
+                let mut subscriptions = context.subscriptions.lock().unwrap();
+                if !subscriptions.is_empty() {
+                    return self.return_error(Outcome::Failure, "Resume before subscribing".to_string());
+                }
+                let Some((mut resumed, session)) = state.parked.resume(token, context.client.as_deref()) else {
+                    return self.return_error(Outcome::NotFound, "Unknown or expired resume token".to_string());
+                };
+                if let Some(seq) = resume_from {
+                    resumed.rewind(seq);
+                }
+                *subscriptions = resumed;
+                *context.session.lock().unwrap() = session.clone();
+                self.return_success(serde_json::json!({
+                    "subscriptions": subscriptions.active(),
+                    "session": session,
+                    "resume_from": subscriptions.cursor(),
+                    "resume_token": subscriptions.resume_token(),
+                }))
+            }
+            SubscriptionCommand::Ack => {
+                let seqs = match params.get("seq") {
+                    Some(Value::Array(seqs)) => seqs.iter().map(Value::as_u64).collect::<Option<Vec<u64>>>(),
+                    Some(seq) => seq.as_u64().map(|seq| vec![seq]),
+                    None => None,
+                };
+                let Some(seqs) = seqs else {
+                    return self.return_error(Outcome::Failure, format!("Invalid `seq`: {}", params.get("seq").unwrap_or(&Value::Null)));
+                };
+                match context.subscriptions.lock().unwrap().deliveries() {
+                    Some(deliveries) => self.return_success(serde_json::json!({ "acked": deliveries.ack(&seqs), "pending": deliveries.pending() })),
+                    None => self.return_error(Outcome::Failure, "Subscribe with `\"ack\": true` to acknowledge the articles".to_string()),
+                }
+            }
+            SubscriptionCommand::Parked => {
+                let parked = context.subscriptions.lock().unwrap().deliveries().map(|d| d.take_parked()).unwrap_or_default();
+                self.return_success(serde_json::json!({ "parked": parked }))
+            }
+            SubscriptionCommand::Unknown => self.return_error(Outcome::Failure, "Unknown subscription command".to_string()),
+        }
+    }
+
+    async fn handle_admin(&self, state: Arc<PollState>, admin_args: AdminArgs) -> Value {
+        match admin_args.command {
+            AdminCommand::Maintenance => {
+                let timeout_secs = admin_args.params.as_ref()
+                    .and_then(|p| p.get("timeout_secs"))
+                    .and_then(Value::as_u64)
+                    .unwrap_or(state.config.server.drain_timeout_secs);
+                let started = state.maintenance.start(std::time::Duration::from_secs(timeout_secs));
+                if started {
+                    systemd::notify_status("Maintenance: draining connections");
+                }
+                self.return_success(serde_json::json!({
+                    "maintenance": true,
+                    "already_draining": !started,
+                    "open_connections": state.maintenance.connections(),
+                    "timeout_secs": timeout_secs,
+                }))
+            }
+            AdminCommand::DeleteArticle | AdminCommand::RedactArticle => self.handle_removal(state, admin_args).await,
+            AdminCommand::Purge => self.handle_purge(state, admin_args).await,
+            AdminCommand::Backfill => self.handle_backfill(state, admin_args),
+            AdminCommand::Alerts
+            | AdminCommand::AckAlert
+            | AdminCommand::ResolveAlert
+            | AdminCommand::MuteAlerts
+            | AdminCommand::UnmuteAlerts => self.handle_alerts(state, admin_args).await,
+            AdminCommand::Exports => {
+                let Some(db) = state.db.as_ref() else {
+                    return self.return_error(Outcome::InternalError, "Database is not available".to_string());
+                };
+                match db.export_runs().await {
+                    Ok(runs) => self.return_success(to_value(runs).unwrap_or_default()),
+                    Err(e) => self.return_error(Outcome::InternalError, e.to_string()),
+                }
+            }
+            AdminCommand::IssueToken => {
+                let params = admin_args.params.unwrap_or_default();
+                let Some(client) = params.get("client").and_then(Value::as_str) else {
+                    return self.return_error(Outcome::Failure, "Missing 'client' param".to_string());
+                };
+                let ttl_secs = params.get("ttl_secs").and_then(Value::as_i64).unwrap_or(TOKEN_TTL_SECS);
+                let expires_at = clock::now().timestamp() + ttl_secs;
+                match state.auth.sign(client, expires_at) {
+                    Some(token) => self.return_success(serde_json::json!({ "client": client, "token": token, "expires_at": expires_at })),
+                    None => self.return_error(Outcome::Failure, "`server.auth.signing_key` is not set".to_string()),
+                }
+            }
+            AdminCommand::Pressure => {
+                let hint = autoscale::hint(state.config.request.delay_secs as u64);
+                self.return_success(to_value(hint).unwrap_or_default())
+            }
+            AdminCommand::Status => {
+                let providers: serde_json::Map<String, Value> = state
+                    .providers
+                    .iter()
+                    .map(|provider| (provider.name().to_string(), serde_json::json!({ "health": provider.health() })))
+                    .collect();
+                self.return_success(serde_json::json!({
+                    "providers": providers,
+                    "costs": costs::report(),
+                    "freshness": freshness::report(),
+                    "latency": latency::report(),
+                    "retries": retries::report(),
+                    "quota": quota::report(),
+                }))
+            }
+            AdminCommand::CacheFlush
+            | AdminCommand::Stats
+            | AdminCommand::ReloadConfig
+            | AdminCommand::SetLogLevel => self.handle_operations(state, admin_args).await,
+            AdminCommand::Unknown => self.return_error(Outcome::NotFound, "Unknown admin command".to_string()),
+        }
+    }
+
+    /// Runtime management commands, see `admin.rs`.
+    async fn handle_operations(&self, state: Arc<PollState>, admin_args: AdminArgs) -> Value {
+        let admin = state.admin.clone();
+        let result = match admin_args.command {
+            AdminCommand::CacheFlush => Ok(admin.cache_flush(&state).await),
+            AdminCommand::Stats => Ok(admin.stats(&state).await),
+            AdminCommand::ReloadConfig => admin.reload_config(),
+            AdminCommand::SetLogLevel => {
+                let param = |name: &str| admin_args.params.as_ref().and_then(|p| p.get(name)).and_then(Value::as_str);
+                admin.set_log_level(param("level"), param("filters"))
+            }
+            _ => return self.return_error(Outcome::NotFound, "Unknown admin command".to_string()),
+        };
+        match result {
+            Ok(message) => self.return_success(message),
+            Err(e @ AdminError::Params(_)) => self.return_error(Outcome::Failure, e.to_string()),
+            Err(e) => self.return_error(Outcome::InternalError, e.to_string()),
+        }
+    }
+
+    /// Purges the content of the `source` or `author` param, see `purge.rs`.
+    async fn handle_purge(&self, state: Arc<PollState>, admin_args: AdminArgs) -> Value {
+        // Purges also reach the raw results and archives, only the MongoDB storage has them.
+        let Some(db) = state.db.as_ref().and_then(|db| db.database()) else {
+            return self.return_error(Outcome::InternalError, "Database is not available".to_string());
+        };
+        // The params are the flags of the `purge` command.
+        let params = admin_args.params.unwrap_or_default();
+        let param = |key: &str| params.get(key).and_then(Value::as_str).map(str::to_string);
+        let mode = match param("mode").map(|m| m.parse::<PurgeMode>()).transpose() {
+            Ok(mode) => mode.unwrap_or(PurgeMode::Delete),
+            Err(e) => return self.return_error(Outcome::Failure, e),
+        };
+        let archives = params
+            .get("archives")
+            .and_then(Value::as_array)
+            .map(|archives| archives.iter().filter_map(Value::as_str).map(PathBuf::from).collect())
+            .unwrap_or_default();
+        let request = match PurgeRequest::new(param("source"), param("author"), mode, archives, "admin") {
+            Ok(request) => request,
+            Err(e) => return self.return_error(Outcome::Failure, e.to_string()),
+        };
+
+        let caches = ServerCaches { providers: state.cache.as_ref(), public: state.public.as_ref() };
+        match purge::purge(db, &request, Some(caches), &state.config.purge.signing_key).await {
+            Ok(record) => self.return_success(to_value(record).unwrap_or_default()),
+            Err(e) => self.return_error(Outcome::InternalError, e.to_string()),
+        }
+    }
+
+    /// Starts the backfill of the `from`, `to` and `providers` params in the background, see
+    /// `backfill.rs`. Without params, returns the progress of the latest backfill.
+    fn handle_backfill(&self, state: Arc<PollState>, admin_args: AdminArgs) -> Value {
+        let params = admin_args.params.unwrap_or_default();
+        if params.is_empty() {
+            let progress = state.backfill.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            return self.return_success(to_value(progress).unwrap_or_default());
+        }
+        let Some(db) = state.db.clone() else {
+            return self.return_error(Outcome::InternalError, "Database is not available".to_string());
+        };
+        let backfill = BackfillRequest::from_params(Value::Object(params.into_iter().collect()), &state.config.backfill)
+            .and_then(|request| Backfill::new(&state.providers, db, &request, &state.config.backfill, state.backfill.clone()));
+        match backfill {
+            Ok(backfill) => {
+                let progress = backfill.progress();
+                tokio::spawn(async move { backfill.run().await });
+                self.return_success(to_value(progress).unwrap_or_default())
+            }
+            Err(e) => self.return_error(Outcome::Failure, e.to_string()),
+        }
+    }
+
+    /// Lists, acknowledges, resolves or mutes alerts, see `alerts.rs`. Alerts are identified by the
+    /// `rule` and `subject` params; mutes by either or both, for `hours` or until unmuted.
+    async fn handle_alerts(&self, state: Arc<PollState>, admin_args: AdminArgs) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return self.return_error(Outcome::InternalError, "Database is not available".to_string());
+        };
+        let params = admin_args.params.unwrap_or_default();
+        let param = |key: &str| params.get(key).and_then(Value::as_str).map(str::to_string);
+        let (rule, subject) = (param("rule"), param("subject"));
+        let by = param("by").unwrap_or_else(|| "admin".to_string());
+
+        let result = match admin_args.command {
+            AdminCommand::Alerts => {
+                let alert_state = match param("state") {
+                    Some(s) => match s.parse::<AlertState>() {
+                        Ok(alert_state) => Some(alert_state),
+                        Err(e) => return self.return_error(Outcome::Failure, e),
+                    },
+                    None => None,
+                };
+                match (db.alerts(alert_state).await, db.alert_mutes().await) {
+                    (Ok(alerts), Ok(mutes)) => Ok(serde_json::json!({ "alerts": alerts, "mutes": mutes })),
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                }
+            }
+            AdminCommand::AckAlert | AdminCommand::ResolveAlert => {
+                let (Some(rule), Some(subject)) = (&rule, &subject) else {
+                    return self.return_error(Outcome::Failure, "Missing `rule` or `subject` param".to_string());
+                };
+                let updated = match admin_args.command {
+                    AdminCommand::AckAlert => alerts::acknowledge(db.as_ref(), rule, subject, &by).await,
+                    _ => alerts::resolve(db.as_ref(), rule, subject).await,
+                };
+                match updated {
+                    Ok(Some(record)) => Ok(to_value(record).unwrap_or_default()),
+                    Ok(None) => return self.return_error(Outcome::NotFound, format!("No open alert `{}`", AlertRecord::id_of(rule, subject))),
+                    Err(e) => Err(e),
+                }
+            }
+            _ => {
+                if rule.is_none() && subject.is_none() {
+                    return self.return_error(Outcome::Failure, "Missing `rule` or `subject` param".to_string());
+                }
+                if matches!(admin_args.command, AdminCommand::MuteAlerts) {
+                    let hours = params.get("hours").and_then(Value::as_i64);
+                    alerts::mute(db.as_ref(), rule.as_deref(), subject.as_deref(), hours, &by).await
+                        .map(|mute| to_value(mute).unwrap_or_default())
+                } else {
+                    alerts::unmute(db.as_ref(), rule.as_deref(), subject.as_deref()).await
+                        .map(|removed| serde_json::json!({ "removed": removed }))
+                }
+            }
+        };
+        match result {
+            Ok(value) => self.return_success(value),
+            Err(e) => self.return_error(Outcome::InternalError, e.to_string()),
+        }
+    }
+
+    /// Soft-deletes or redacts the stored article identified by the `provider` and `id` params, and
+    /// tells the subscriptions matching it on every connection of the server.
+    async fn handle_removal(&self, state: Arc<PollState>, admin_args: AdminArgs) -> Value {
+        let Some(db) = state.db.as_ref() else {
+            return self.return_error(Outcome::InternalError, "Database is not available".to_string());
+        };
+        let param = |key: &str| admin_args.params.as_ref()
+            .and_then(|p| p.get(key))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let (Some(provider), Some(id)) = (param("provider"), param("id")) else {
+            return self.return_error(Outcome::Failure, "Missing `provider` or `id` param".to_string());
+        };
+        let reason = param("reason").unwrap_or_default();
+
+        let (result, event) = match admin_args.command {
+            AdminCommand::RedactArticle => (db.redact_article(&provider, &id, &reason).await, "article_redacted"),
+            _ => (db.soft_delete_article(&provider, &id, &reason).await, "article_deleted"),
+        };
+        match result {
+            Ok(Some(article)) => {
+                // Without subscribed connections, nobody is told.
+                let _ = state.removals.send(Arc::new(Removal { event, reason, article }));
+                self.return_success(serde_json::json!({
+                    "command": admin_args.command.to_str(),
+                    "provider": provider,
+                    "id": id,
+                }))
+            }
+            Ok(None) => self.return_error(Outcome::NotFound, format!("No article `{}` from `{}`", id, provider)),
+            Err(e) => self.return_error(Outcome::InternalError, e.to_string()),
+        }
+    }
+
+    /// Typed "retry later" response sent when `max_connections` accepts are already in flight.
+    fn return_saturated(&self, max_connections: usize) -> Value {
+        ServerResponse::new(
+            REQUEST_INTERNAL_ERROR,
+            Some(serde_json::json!({ "max_connections": max_connections })),
+            Some(SATURATED_REASON.to_string()),
+        ).to_json()
+    }
+
+    /// Typed "retry later" response sent to connections opened during maintenance.
+    fn return_retry_later(&self, retry_after_secs: u64) -> Value {
+        ServerResponse::new(
+            REQUEST_INTERNAL_ERROR,
+            Some(serde_json::json!({ "retry_after_secs": retry_after_secs })),
+            Some(MAINTENANCE_REASON.to_string()),
+        ).to_json()
+    }
+
+    fn map_func(&self, where_: &String) -> Option<Func> {
         if let Some(func) = self.fn_map.get(where_).cloned() {
             Some(func.clone())
         } else {
@@ -312,7 +1675,7 @@ impl MakeResponse {
     }
 
     fn return_success(&self, message: Value) -> Value {
-        ServerResponse::new(REQUEST_SUCCUESS, Some(message), None).to_json()
+        ServerResponse::new(REQUEST_SUCCUESS, Some(message), None).into_json()
     }
 
     fn return_error(&self, outcome: Outcome, reason: String) -> Value {
@@ -323,7 +1686,8 @@ impl MakeResponse {
             Outcome::NotAllowed => NOT_ALLOWED,
             Outcome::NotFound => NOT_FOUND,
             Outcome::RateLimited=> REQUEST_RATE_LIMITED,
-            Outcome::InternalError => REQUEST_INTERNAL_ERROR,
+            Outcome::Unauthorized => UNAUTHORIZED,
+            Outcome::InternalError | Outcome::Maintenance => REQUEST_INTERNAL_ERROR,
         };
         ServerResponse::new(status, None, Some(reason)).to_json()
 
@@ -349,11 +1713,763 @@ impl ServerResponse {
     pub fn to_json(&self) -> Value {
         serde_json::to_value(self).unwrap()
     }
+
+    /// Same as `to_json`, moving the message rather than copying it.
+    pub fn into_json(self) -> Value {
+        let mut response = serde_json::Map::new();
+        response.insert("status".to_string(), Value::from(self.status));
+        response.insert("message".to_string(), self.message.unwrap_or(Value::Null));
+        response.insert("reason".to_string(), self.reason.map_or(Value::Null, Value::String));
+        Value::Object(response)
+    }
     
 }
 
 ////
 pub async fn run() -> Result<(), Error> {
-    let mut server = ServerSocket::new("0.0.0.0:8080");
+    let config = Arc::new(ValueConfig::new().expect("Failed to read config file"));
+    let _lock = instance::lock_from_config(&config).map_err(|e| {
+        error!("{}", e);
+        Error::Io(std::io::Error::other(e.to_string()))
+    })?;
+    clock::configure(&config.clock);
+    chaos::configure(&config.chaos);
+    autoscale::configure(&config.autoscale);
+    costs::configure(&config.costs);
+    freshness::configure(&config.freshness);
+    latency::configure(&config.latency);
+    sentiment::configure(&config.sentiment);
+    taxonomy::configure(&config.taxonomy);
+    api_calls::configure(&config.api_calls);
+    limiter::configure(&config.task);
+    quota::configure(&config.quota);
+    captures::configure(&config.captures);
+
+    let mut state = PollState::new(config.clone());
+    match db::ClientManager::new(&config).await {
+        Ok(db_client) => {
+            let db_ops = DatabaseOps::from_config(db_client.get_client(), &config.database)
+                .with_transactions(db_client.supports_transactions())
+                .with_keywords(&config.keywords)
+                .with_entities(&config.entities)
+                .with_translator(Arc::new(Client::new()), &config.translation);
+            let db_ops = Arc::new(db_ops);
+            if config.webhook.enabled {
+                webhook::spawn(db_ops.clone(), config.webhook.clone()).await.map_err(Error::Io)?;
+            }
+            // With `server.poll`, the polling loop stores the API calls and their counts, and checks
+            // the latency SLO.
+            if !config.server.poll {
+                api_calls::spawn(db_ops.clone(), &config.api_calls);
+                quota::spawn(db_ops.clone(), &config.quota);
+                let alerter = alerts::Alerter::new(Arc::new(Client::new()), config.alerts.clone()).with_storage(db_ops.clone());
+                latency::spawn(Arc::new(alerter), &config.latency);
+            }
+            state = state.with_database(db_ops);
+        }
+        Err(e) => warn!("Database is not available, admin commands on stored data are disabled: {}", e),
+    }
+    let mut server = ServerSocket::from_state(config.clone(), state);
+    // With `server.poll`, the polling loop refreshes the filter values.
+    if config.enumerations.enabled && !config.server.poll {
+        enumerations::spawn(server.state().providers.clone(), &config.enumerations);
+    }
+    if config.server.poll {
+        let (config, hub) = (config.clone(), server.state().news.clone());
+        tokio::spawn(async move {
+            if let Err(e) = polling::poll(config, Some(hub)).await {
+                error!("Polling stopped: {}", e);
+            }
+        });
+    }
+    if config.http.enabled {
+        http::spawn(server.state(), config.http.clone()).await.map_err(Error::Io)?;
+    }
+    if config.grpc.enabled {
+        grpc::spawn(server.state(), config.grpc.clone()).await.map_err(Error::Io)?;
+    }
     server.run().await
-}
\ No newline at end of file
+}
+/// End-to-end tests of the protocol: the server is served on ephemeral ports with mocked
+/// providers and a `MemoryStorage`, and driven by websocket clients.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use async_tungstenite::tokio::{connect_async, ConnectStream};
+    use async_tungstenite::WebSocketStream;
+    use serde_json::json;
+
+    use crate::errors::{ApiError, ProviderError};
+    use crate::normalize::NormalizedArticle;
+    use crate::options::FetchType;
+    use crate::provider::{ProviderFuture, ProviderHealth};
+    use crate::storage::MemoryStorage;
+    use crate::NewsResult;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Provider echoing the args it is called with, or failing.
+    struct MockProvider {
+        name: &'static str,
+        fail: bool,
+    }
+    impl NewsProvider for MockProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn supports(&self, _fetch_type: &FetchType) -> bool {
+            true
+        }
+
+        fn fetch(&self, args: Arc<Value>) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            Box::pin(async move {
+                if self.fail {
+                    return Err(ApiError::NoEndpointProvided.into());
+                }
+                if let Some(delay_ms) = args.get("delay_ms").and_then(Value::as_u64) {
+                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                }
+                if let Some(articles) = args.get("articles").and_then(Value::as_u64) {
+                    return Ok(json!({ "provider": self.name, "data": (0..articles).collect::<Vec<_>>() }));
+                }
+                Ok(json!({ "provider": self.name, "args": *args }))
+            })
+        }
+
+        fn fetch_latest(&self) -> ProviderFuture<'_, Result<Value, ProviderError>> {
+            self.fetch(Arc::new(json!({})))
+        }
+
+        fn health(&self) -> ProviderHealth {
+            ProviderHealth::Healthy
+        }
+    }
+
+    /// Server with a main, an admin and a public listener.
+    struct TestServer {
+        main: SocketAddr,
+        admin: SocketAddr,
+        public: SocketAddr,
+        storage: Arc<MemoryStorage>,
+        news: NewsHub,
+        task: tokio::task::JoinHandle<Result<(), Error>>,
+    }
+    impl TestServer {
+        async fn start(configure: impl FnOnce(&mut ValueConfig)) -> Self {
+            let mut config = ValueConfig::from_toml(include_str!("../config.toml.example")).unwrap();
+            configure(&mut config);
+            let config = Arc::new(config);
+            let providers: Vec<Arc<dyn NewsProvider>> = vec![
+                Arc::new(MockProvider { name: "mock", fail: false }),
+                Arc::new(MockProvider { name: "broken", fail: true }),
+            ];
+            let storage = Arc::new(MemoryStorage::new());
+            let state = PollState::with_providers(config.clone(), providers).with_database(storage.clone());
+
+            let mut listeners = Vec::new();
+            let mut addresses = Vec::new();
+            for (name, admin, public) in [("main", false, false), ("admin", true, false), ("public", false, true)] {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let address = listener.local_addr().unwrap();
+                let listener_config = ListenerConfig {
+                    name: name.to_string(),
+                    host: address.ip().to_string(),
+                    port: address.port(),
+                    admin,
+                    public,
+                };
+                addresses.push(address);
+                listeners.push((listener_config, listener));
+            }
+            let mut server = ServerSocket::from_state(config, state);
+            let news = server.state().news.clone();
+            let task = tokio::spawn(async move { server.serve(listeners).await });
+            Self { main: addresses[0], admin: addresses[1], public: addresses[2], storage, news, task }
+        }
+    }
+
+    struct TestClient {
+        ws: WebSocketStream<ConnectStream>,
+    }
+    impl TestClient {
+        async fn connect(address: SocketAddr) -> Self {
+            Self::connect_url(&format!("ws://{}", address)).await
+        }
+
+        async fn connect_url(url: &str) -> Self {
+            let (ws, _) = connect_async(url).await.unwrap();
+            Self { ws }
+        }
+
+        async fn send(&mut self, text: &str) {
+            self.ws.send(Message::Text(text.to_string())).await.unwrap();
+        }
+
+        /// Next text message, parsed when it is JSON.
+        async fn receive(&mut self) -> Value {
+            loop {
+                let message = tokio::time::timeout(TIMEOUT, self.ws.next()).await
+                    .expect("no message received")
+                    .expect("connection closed")
+                    .unwrap();
+                if let Message::Text(text) = message {
+                    return from_str(&text).unwrap_or(Value::String(text));
+                }
+            }
+        }
+
+        async fn call(&mut self, request: Value) -> Value {
+            self.send(&request.to_string()).await;
+            self.receive().await
+        }
+
+        async fn close(mut self) {
+            let _ = self.ws.close(None).await;
+        }
+    }
+
+    fn request(target: &str, args: Value) -> Value {
+        json!({
+            "caller": { "id": "test", "ipaddr": "127.0.0.1", "queue": 0, "status": 0, "mode": "async" },
+            "target": target,
+            "args": args,
+        })
+    }
+
+    fn task(where_: &str, params: Value) -> Value {
+        request("task", json!({
+            "function": "aggregated_polling",
+            "count": "single",
+            "look_for": { "where_": where_ },
+            "params": params,
+        }))
+    }
+
+    fn admin(command: &str, params: Value) -> Value {
+        request("admin", json!({ "command": command, "params": params }))
+    }
+
+    #[tokio::test]
+    async fn polls_the_providers() {
+        let server = TestServer::start(|_| {}).await;
+        let mut client = TestClient::connect(server.main).await;
+
+        let response = client.call(task("mock_news_polling", json!({ "symbol": "AAPL" }))).await;
+        assert_eq!(response["status"], REQUEST_SUCCUESS);
+        assert_eq!(response["message"], json!({ "provider": "mock", "args": { "symbol": "AAPL" } }));
+
+        // Provider failures are reported in the message.
+        let response = client.call(task("broken_news_polling", json!({}))).await;
+        assert_eq!(response["status"], REQUEST_SUCCUESS);
+        assert!(response["message"].as_str().unwrap().starts_with("broken provider polling failed"));
+
+        let response = client.call(task("missing_news_polling", json!({}))).await;
+        assert_eq!(response["status"], REQUEST_FAILED);
+        assert_eq!(response["reason"], "Invalid task function: missing_news_polling");
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn reports_invalid_requests() {
+        let server = TestServer::start(|_| {}).await;
+        let mut client = TestClient::connect(server.main).await;
+
+        client.send("not json").await;
+        assert_eq!(client.receive().await, "Invalid JSON");
+        let response = client.call(json!({ "target": "task" })).await;
+        assert_eq!(response["status"], REQUEST_FAILED);
+        assert_eq!(response["reason"], "Missing 'caller' field");
+        let response = client.call(request("unknown", json!({}))).await;
+        assert_eq!(response["reason"], "Unknown target service");
+        let response = client.call(task("mock_news_polling", Value::Null)).await;
+        assert_eq!(response["reason"], "Invalid task arguments");
+        // The connection survives the errors.
+        let response = client.call(task("mock_news_polling", json!({}))).await;
+        assert_eq!(response["status"], REQUEST_SUCCUESS);
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn admin_commands_require_the_admin_listener() {
+        let server = TestServer::start(|_| {}).await;
+        server.storage.store_articles(&[NormalizedArticle::test("1").provider("mock").published_at("2024-05-01T10:00:00Z").tickers(&["AAPL"])]).await.unwrap();
+        let delete = admin("delete_article", json!({ "provider": "mock", "id": "1", "reason": "retracted" }));
+
+        let mut client = TestClient::connect(server.main).await;
+        let response = client.call(delete.clone()).await;
+        assert_eq!(response["status"], NOT_ALLOWED);
+        assert_eq!(server.storage.revision("mock", "1"), Some(0));
+
+        let mut admin_client = TestClient::connect(server.admin).await;
+        assert_eq!(admin_client.call(delete).await["status"], REQUEST_SUCCUESS);
+        let stored = server.storage.articles_with_tickers(&["AAPL".to_string()], "2024-05-01T00:00:00Z", "2024-05-02T00:00:00Z").await;
+        assert!(stored.unwrap().is_empty());
+        let missing = admin("redact_article", json!({ "provider": "mock", "id": "2" }));
+        assert_eq!(admin_client.call(missing).await["status"], NOT_FOUND);
+        assert_eq!(admin_client.call(admin("reboot", json!({}))).await["status"], NOT_FOUND);
+        // Purges need the MongoDB storage.
+        let purge = admin("purge", json!({ "source": "example.com" }));
+        assert_eq!(admin_client.call(purge).await["status"], REQUEST_INTERNAL_ERROR);
+        let pressure = admin_client.call(admin("pressure", json!({}))).await;
+        assert_eq!(pressure["status"], REQUEST_SUCCUESS);
+        assert!(pressure["message"]["suggested_interval_secs"].is_u64());
+        let status = admin_client.call(admin("status", json!({}))).await;
+        assert_eq!(status["message"]["providers"]["mock"]["health"]["status"], "healthy");
+        assert!(status["message"]["costs"]["totals"].is_object());
+        assert!(status["message"]["freshness"].is_object());
+        assert!(status["message"]["latency"].is_object());
+        assert!(status["message"]["retries"].is_object());
+        assert!(status["message"]["quota"].is_object());
+        let today = chrono::Utc::now().date_naive().to_string();
+        let backfill = admin_client.call(admin("backfill", json!({ "from": today, "providers": ["mock"] }))).await;
+        assert_eq!((backfill["status"].as_u64(), backfill["message"]["days"].as_u64()), (Some(200), Some(1)));
+        let unknown = admin("backfill", json!({ "from": today, "providers": ["fmp"] }));
+        assert_eq!(admin_client.call(unknown).await["status"], REQUEST_FAILED);
+        assert_eq!(admin_client.call(admin("backfill", json!({}))).await["message"]["from"], today.as_str());
+        client.close().await;
+        admin_client.close().await;
+    }
+
+    #[tokio::test]
+    async fn describes_the_registered_functions() {
+        let server = TestServer::start(|_| {}).await;
+        let mut client = TestClient::connect(server.main).await;
+        let response = client.call(request("describe", json!({}))).await;
+        assert_eq!(response["status"], REQUEST_SUCCUESS);
+        let functions = response["message"]["functions"].as_array().unwrap();
+        let names: Vec<&str> = functions.iter().filter_map(|f| f["name"].as_str()).collect();
+        assert!(names.contains(&"mock_news_polling") && names.contains(&"broken_news_polling"));
+        let find = |name: &str| functions.iter().find(|f| f["name"] == name).unwrap().clone();
+        assert_eq!(find("mock_news_polling")["provider"], "mock");
+        let keyword_news = find("keyword_news");
+        assert_eq!((keyword_news["provider"].clone(), keyword_news["params"]["required"].clone()), (Value::Null, json!(["keywords"])));
+        assert_eq!(keyword_news["params"]["properties"]["since"]["format"], "date-time");
+
+        // Only the public functions on a public listener.
+        let mut public = TestClient::connect(server.public).await;
+        let response = public.call(request("describe", json!({}))).await;
+        let names: Vec<Value> = response["message"]["functions"].as_array().unwrap().iter().map(|f| f["name"].clone()).collect();
+        assert_eq!(names, vec![json!("entity_news"), json!("keyword_news"), json!("topic_news"), json!("trending_keywords")]);
+        client.close().await;
+        public.close().await;
+    }
+
+    #[tokio::test]
+    async fn manages_the_running_server() {
+        let server = TestServer::start(|_| {}).await;
+        let mut client = TestClient::connect(server.main).await;
+        assert_eq!(client.call(admin("cache_flush", json!({}))).await["status"], NOT_ALLOWED);
+
+        let mut admin_client = TestClient::connect(server.admin).await;
+        let stats = admin_client.call(admin("stats", json!({}))).await;
+        assert_eq!(stats["status"], REQUEST_SUCCUESS);
+        assert_eq!(stats["message"]["connections"], 2);
+        assert_eq!(stats["message"]["providers"], json!(["mock", "broken"]));
+        let flushed = admin_client.call(admin("cache_flush", json!({}))).await;
+        assert_eq!(flushed["message"]["flushed"], json!({ "providers": 0, "public": 0 }));
+
+        let missing = admin_client.call(admin("set_log_level", json!({}))).await;
+        assert_eq!(missing["status"], REQUEST_FAILED);
+        let unknown = admin_client.call(admin("set_log_level", json!({ "level": "loud" }))).await;
+        assert_eq!(unknown["reason"], "Invalid params: Unknown log level `loud`");
+        let invalid = admin_client.call(admin("set_log_level", json!({ "filters": "marketaux=loud" }))).await;
+        assert_eq!(invalid["status"], REQUEST_FAILED);
+        assert!(invalid["reason"].as_str().unwrap().starts_with("Invalid params: Invalid log filters `marketaux=loud`"));
+        client.close().await;
+        admin_client.close().await;
+    }
+
+    #[tokio::test]
+    async fn public_listener_serves_rate_limited_queries() {
+        let server = TestServer::start(|config| config.public.burst = 3).await;
+        let article = |id: &str, keyword: &str| NormalizedArticle::test(id).published_at("2024-05-01T10:00:00Z").keywords(&[keyword]);
+        server.storage.store_articles(&[article("1", "chips"), article("2", "rates")]).await.unwrap();
+        let mut client = TestClient::connect(server.public).await;
+
+        let query = task("keyword_news", json!({ "keywords": "chips", "since": "2024-01-01T00:00:00Z" }));
+        let response = client.call(query).await;
+        assert_eq!(response["status"], REQUEST_SUCCUESS);
+        assert_eq!(response["message"].as_array().unwrap().len(), 1);
+        assert_eq!(response["message"][0]["id"], "1");
+
+        let response = client.call(task("mock_news_polling", json!({}))).await;
+        assert_eq!(response["status"], NOT_ALLOWED);
+        let response = client.call(admin("maintenance", json!({}))).await;
+        assert_eq!(response["status"], NOT_ALLOWED);
+        let response = client.call(task("keyword_news", json!({ "keywords": "rates" }))).await;
+        assert_eq!(response["status"], REQUEST_RATE_LIMITED);
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn maintenance_notifies_and_drains_the_connections() {
+        let server = TestServer::start(|config| config.server.drain_timeout_secs = 7).await;
+        let mut client = TestClient::connect(server.main).await;
+        let mut admin_client = TestClient::connect(server.admin).await;
+        // Both connections are open before maintenance starts.
+        assert_eq!(client.call(task("mock_news_polling", json!({}))).await["status"], REQUEST_SUCCUESS);
+
+        let response = admin_client.call(admin("maintenance", json!({ "timeout_secs": 5 }))).await;
+        assert_eq!(response["status"], REQUEST_SUCCUESS);
+        assert_eq!(response["message"]["open_connections"], 2);
+        let notice = client.receive().await;
+        assert_eq!(notice["status"], REQUEST_INTERNAL_ERROR);
+        assert_eq!(notice["message"]["event"], "drain");
+
+        let mut late = TestClient::connect(server.main).await;
+        let response = late.receive().await;
+        assert_eq!(response["message"]["retry_after_secs"], 7);
+
+        client.close().await;
+        admin_client.close().await;
+        tokio::time::timeout(TIMEOUT, server.task).await
+            .expect("server did not stop once drained")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn pushes_subscribed_articles() {
+        let server = TestServer::start(|config| config.server.subscription_interval_secs = 1).await;
+        let mut client = TestClient::connect(server.main).await;
+        let subscribe = |params: Value| request("subscription", json!({ "command": "subscribe", "params": params }));
+
+        let response = client.call(subscribe(json!({ "tickers": ["msft"] }))).await;
+        let token = response["message"]["resume_token"].clone();
+        assert!(token.is_string());
+        assert_eq!(response["message"], json!({ "subscription": "1", "tickers": ["MSFT"], "topics": [], "resume_from": 0, "resume_token": token }));
+        let response = client.call(subscribe(json!({ "tickers": "aapl" }))).await;
+        assert_eq!((response["message"]["subscription"].clone(), response["message"]["resume_token"].clone()), (json!("2"), token));
+
+        server.storage.store_articles(&[NormalizedArticle::test("1").tickers(&["AAPL"])]).await.unwrap();
+        let pushed = client.receive().await;
+        assert_eq!(pushed["status"], REQUEST_SUCCUESS);
+        assert_eq!(pushed["message"]["event"], "article");
+        assert_eq!(pushed["message"]["subscriptions"], json!(["2"]));
+        assert_eq!((pushed["message"]["seq"].clone(), pushed["message"]["article"]["id"].clone()), (json!(1), json!("1")));
+
+        let mut admin_client = TestClient::connect(server.admin).await;
+        let delete = admin("delete_article", json!({ "provider": "finnhub", "id": "1", "reason": "retracted" }));
+        assert_eq!(admin_client.call(delete).await["status"], REQUEST_SUCCUESS);
+        let pushed = client.receive().await;
+        assert_eq!(pushed["message"], json!({ "event": "article_deleted", "subscriptions": ["2"], "provider": "finnhub", "id": "1", "reason": "retracted" }));
+        admin_client.close().await;
+
+        let unsubscribe = |params: Value| request("subscription", json!({ "command": "unsubscribe", "params": params }));
+        assert_eq!(client.call(unsubscribe(json!({ "subscription": "3" }))).await["status"], NOT_FOUND);
+        assert_eq!(client.call(unsubscribe(json!({}))).await["message"], json!({ "unsubscribed": 2 }));
+        let response = client.call(request("subscription", json!({ "command": "watch" }))).await;
+        assert_eq!(response["reason"], "Unknown subscription command");
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn resumes_the_subscriptions_of_a_reconnecting_client() {
+        let server = TestServer::start(|config| config.server.subscription_interval_secs = 1).await;
+        let subscribe = |params: Value| request("subscription", json!({ "command": "subscribe", "params": params }));
+        let mut client = TestClient::connect(server.main).await;
+        client.call(subscribe(json!({ "tickers": ["AAPL"] }))).await;
+        server.storage.store_articles(&[NormalizedArticle::test("1").tickers(&["AAPL"])]).await.unwrap();
+        let last = client.receive().await["message"]["seq"].clone();
+        client.close().await;
+
+        // Stored while disconnected.
+        server.storage.store_articles(&[NormalizedArticle::test("2").tickers(&["AAPL"]), NormalizedArticle::test("3").tickers(&["AAPL"])]).await.unwrap();
+        let mut client = TestClient::connect(server.main).await;
+        let response = client.call(subscribe(json!({ "tickers": ["AAPL"], "resume_from": last }))).await;
+        assert_eq!(response["message"]["resume_from"], 1);
+        let missed = [client.receive().await, client.receive().await];
+        assert_eq!(missed.map(|pushed| pushed["message"]["seq"].clone()), [json!(2), json!(3)]);
+
+        let response = client.call(subscribe(json!({ "resume_from": "yesterday" }))).await;
+        assert_eq!(response["reason"], "Invalid `resume_from`: \"yesterday\"");
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn resumes_a_closed_connection_with_its_token() {
+        let server = TestServer::start(|config| config.server.subscription_interval_secs = 1).await;
+        let resume = |params: Value| request("subscription", json!({ "command": "resume", "params": params }));
+        let mut client = TestClient::connect(server.main).await;
+        let response = client.call(request("subscription", json!({ "command": "subscribe", "params": { "tickers": ["AAPL"] } }))).await;
+        let token = response["message"]["resume_token"].clone();
+        client.call(request("session", json!({ "command": "set", "params": { "tickers": ["aapl"] } }))).await;
+        server.storage.store_articles(&[NormalizedArticle::test("1").tickers(&["AAPL"])]).await.unwrap();
+        client.receive().await;
+        client.close().await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // Stored while disconnected.
+        server.storage.store_articles(&[NormalizedArticle::test("2").tickers(&["AAPL"])]).await.unwrap();
+        let mut client = TestClient::connect(server.main).await;
+        let response = client.call(resume(json!({ "token": token, "resume_from": 0 }))).await;
+        assert_eq!(response["message"]["subscriptions"], json!([{ "id": "1", "tickers": ["AAPL"], "topics": [] }]));
+        assert_eq!(response["message"]["session"]["tickers"], json!(["AAPL"]));
+        assert_eq!(response["message"]["resume_token"], token);
+        // Lost with the connection, and missed.
+        let replayed = [client.receive().await, client.receive().await];
+        assert_eq!(replayed.map(|pushed| pushed["message"]["seq"].clone()), [json!(1), json!(2)]);
+        let response = client.call(resume(json!({ "token": token }))).await;
+        assert_eq!(response["reason"], "Resume before subscribing");
+        client.close().await;
+
+        let mut other = TestClient::connect(server.main).await;
+        let response = other.call(resume(json!({ "token": "guessed" }))).await;
+        assert_eq!(response["reason"], "Unknown or expired resume token");
+        other.close().await;
+    }
+
+    #[tokio::test]
+    async fn pushes_the_unacknowledged_articles_again() {
+        let server = TestServer::start(|config| {
+            config.server.subscription_interval_secs = 1;
+            config.server.ack_timeout_secs = 1;
+            config.server.ack_max_attempts = 2;
+        })
+        .await;
+        let command = |command: &str, params: Value| request("subscription", json!({ "command": command, "params": params }));
+        let mut client = TestClient::connect(server.main).await;
+        let response = client.call(command("ack", json!({ "seq": 1 }))).await;
+        assert_eq!(response["status"], REQUEST_FAILED);
+        client.call(command("subscribe", json!({ "tickers": ["AAPL"], "ack": true }))).await;
+        server.storage.store_articles(&[NormalizedArticle::test("1").tickers(&["AAPL"]), NormalizedArticle::test("2").tickers(&["AAPL"])]).await.unwrap();
+        let pushed = [client.receive().await, client.receive().await];
+        assert_eq!(pushed.map(|pushed| pushed["message"]["seq"].clone()), [json!(1), json!(2)]);
+
+        let response = client.call(command("ack", json!({ "seq": [2, 3] }))).await;
+        assert_eq!(response["message"], json!({ "acked": 1, "pending": 1 }));
+        let redelivered = client.receive().await;
+        assert_eq!((redelivered["message"]["seq"].clone(), redelivered["message"]["redelivered"].clone()), (json!(1), json!(true)));
+        // Pushed `ack_max_attempts` times, then parked.
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        let response = client.call(command("parked", json!({}))).await;
+        assert_eq!(response["message"]["parked"].as_array().map(|parked| parked.iter().map(|e| e["seq"].clone()).collect()), Some(vec![json!(1)]));
+        let response = client.call(command("ack", json!({ "seq": "1" }))).await;
+        assert_eq!(response["reason"], "Invalid `seq`: \"1\"");
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn applies_the_session_filters() {
+        let server = TestServer::start(|_| {}).await;
+        let mut client = TestClient::connect(server.main).await;
+        let session = |command: &str, params: Value| request("session", json!({ "command": command, "params": params }));
+        server.storage.store_articles(&[NormalizedArticle::test("1").published_at("2024-05-01T10:00:00Z").tickers(&["AAPL"]).keywords(&["chips"])]).await.unwrap();
+
+        let response = client.call(session("set", json!({ "tickers": ["msft"], "language": "en" }))).await;
+        assert_eq!(response["message"], json!({ "session": { "tickers": ["MSFT"], "language": "en", "min_sentiment": null } }));
+        let response = client.call(task("mock_news_polling", json!({ "symbol": "AAPL", "language": "fr" }))).await;
+        assert_eq!(response["message"]["args"], json!({ "symbol": "AAPL", "language": "fr", "tickers": "MSFT" }));
+        let response = client.call(task("keyword_news", json!({ "keywords": ["chips"], "since": "2024-01-01T00:00:00Z" }))).await;
+        assert_eq!(response["message"], json!([]));
+
+        client.call(session("set", json!({ "tickers": "AAPL", "language": null }))).await;
+        let response = client.call(task("keyword_news", json!({ "keywords": ["chips"], "since": "2024-01-01T00:00:00Z" }))).await;
+        assert_eq!(response["message"][0]["id"], "1");
+
+        let response = client.call(session("set", json!({ "min_sentiment": "high" }))).await;
+        assert_eq!(response["status"], REQUEST_FAILED);
+        let response = client.call(session("clear", json!({}))).await;
+        assert_eq!(response["message"], json!({ "session": { "tickers": [], "language": null, "min_sentiment": null } }));
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn answers_requests_with_ids_concurrently() {
+        let server = TestServer::start(|_| {}).await;
+        let mut client = TestClient::connect(server.main).await;
+        let with_id = |id: Value, params: Value| {
+            let mut request = task("mock_news_polling", params);
+            request["id"] = id;
+            request
+        };
+        client.send(&with_id(json!("slow"), json!({ "delay_ms": 300 })).to_string()).await;
+        client.send(&with_id(json!(2), json!({})).to_string()).await;
+        let first = client.receive().await;
+        assert_eq!((first["id"].clone(), first["status"].clone()), (json!(2), json!(REQUEST_SUCCUESS)));
+        assert_eq!(client.receive().await["id"], "slow");
+
+        // Without an id, nothing is echoed.
+        let response = client.call(task("mock_news_polling", json!({}))).await;
+        assert!(response.get("id").is_none());
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn streams_large_results_in_chunks() {
+        let server = TestServer::start(|config| config.server.chunk_size = 2).await;
+        let mut client = TestClient::connect(server.main).await;
+        let mut request = task("mock_news_polling", json!({ "articles": 5 }));
+        request["id"] = json!(1);
+        client.send(&request.to_string()).await;
+        let mut items = Vec::new();
+        for seq in 0..3 {
+            let chunk = client.receive().await;
+            assert_eq!((chunk["id"].clone(), chunk["message"]["event"].clone()), (json!(1), json!("chunk")));
+            assert_eq!((chunk["message"]["seq"].as_u64(), chunk["message"]["final"].as_bool()), (Some(seq), Some(seq == 2)));
+            items.extend(chunk["message"]["items"].as_array().unwrap().clone());
+        }
+        assert_eq!(items, (0..5).map(Value::from).collect::<Vec<_>>());
+
+        let response = client.call(task("mock_news_polling", json!({ "articles": 2 }))).await;
+        assert_eq!(response["message"]["data"], json!([0, 1]));
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn serves_the_negotiated_protocol_version() {
+        use tungstenite::client::IntoClientRequest;
+
+        let server = TestServer::start(|_| {}).await;
+        let mut v1 = TestClient::connect(server.main).await;
+        let mut v2 = TestClient::connect_url(&format!("ws://{}/?version=2", server.main)).await;
+        let response = v1.call(task("mock_news_polling", json!({}))).await;
+        assert_eq!(response["message"]["provider"], "mock");
+        let response = v2.call(task("mock_news_polling", json!({}))).await;
+        assert_eq!((response["version"].as_u64(), response["ok"].as_bool()), (Some(2), Some(true)));
+        assert_eq!(response["data"]["provider"], "mock");
+        assert_eq!(v2.call(json!({ "target": "task" })).await["ok"], false);
+
+        let mut request = format!("ws://{}", server.main).into_client_request().unwrap();
+        request.headers_mut().insert("sec-websocket-protocol", HeaderValue::from_static("news_data.v1,news_data.v2"));
+        let (_ws, response) = connect_async(request).await.unwrap();
+        assert_eq!(response.headers()["sec-websocket-protocol"], "news_data.v2");
+        assert!(connect_async(format!("ws://{}/?version=9", server.main)).await.is_err());
+        v1.close().await;
+        v2.close().await;
+    }
+
+    #[tokio::test]
+    async fn forwards_polling_results() {
+        let server = TestServer::start(|_| {}).await;
+        let mut client = TestClient::connect(server.main).await;
+        // Once answered, the connection listens to the hub. Public connections do not.
+        assert_eq!(client.call(task("mock_news_polling", json!({}))).await["status"], REQUEST_SUCCUESS);
+        let mut public_client = TestClient::connect(server.public).await;
+        assert_eq!(public_client.call(task("keyword_news", json!({ "keywords": "chips" }))).await["status"], REQUEST_SUCCUESS);
+        // Subscribed connections get the stored articles instead.
+        let mut subscribed = TestClient::connect(server.main).await;
+        let subscribe = request("subscription", json!({ "command": "subscribe", "params": { "tickers": ["AAPL"] } }));
+        assert_eq!(subscribed.call(subscribe).await["status"], REQUEST_SUCCUESS);
+
+        let result: NewsResult = serde_json::from_value(json!({
+            "hash_key": "key",
+            "marketaux": null,
+            "alphavantage": null,
+            "from": "2024-05-01T09:00:00Z",
+            "to": "2024-05-01T10:00:00Z",
+            "time_range": 3600,
+            "marketaux_data_len": 0,
+            "alphavantage_data_len": 0,
+        }))
+        .unwrap();
+        assert_eq!(server.news.send(Arc::new(result)).unwrap(), 2);
+        let pushed = client.receive().await;
+        assert_eq!(pushed["status"], REQUEST_SUCCUESS);
+        assert_eq!(pushed["message"]["event"], "news");
+        assert_eq!(pushed["message"]["news"]["hash_key"], "key");
+        let answered = subscribed.call(task("mock_news_polling", json!({}))).await;
+        assert_ne!(answered["message"]["event"], "news");
+        client.close().await;
+        public_client.close().await;
+        subscribed.close().await;
+    }
+
+    #[tokio::test]
+    async fn pings_clients_and_closes_idle_connections() {
+        let server = TestServer::start(|config| {
+            config.server.heartbeat_interval_secs = 1;
+            config.server.idle_timeout_secs = 2;
+        })
+        .await;
+        let mut alive = TestClient::connect(server.main).await;
+        let mut idle = TestClient::connect(server.main).await;
+        // Reading the pings answers them.
+        for _ in 0..3 {
+            let message = tokio::time::timeout(TIMEOUT, alive.ws.next()).await.unwrap().unwrap().unwrap();
+            assert!(matches!(message, Message::Ping(_)));
+        }
+        assert_eq!(alive.call(task("mock_news_polling", json!({}))).await["status"], REQUEST_SUCCUESS);
+
+        // The other one was closed after 2 seconds without a pong.
+        loop {
+            match tokio::time::timeout(TIMEOUT, idle.ws.next()).await.unwrap() {
+                Some(Ok(Message::Ping(_))) => continue,
+                // Answering a ping may also find the connection closed.
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                other => panic!("unexpected message: {:?}", other),
+            }
+        }
+        alive.close().await;
+    }
+
+    #[tokio::test]
+    async fn turns_connections_away_once_saturated() {
+        let server = TestServer::start(|config| config.server.max_connections = 1).await;
+        let mut first = TestClient::connect(server.main).await;
+        assert_eq!(first.call(task("mock_news_polling", json!({}))).await["status"], REQUEST_SUCCUESS);
+
+        // The limit covers every listener, and a connection never completing its handshake does
+        // not hold the next rejections.
+        let _stalled = tokio::net::TcpStream::connect(server.admin).await.unwrap();
+        let started = std::time::Instant::now();
+        let mut rejected = TestClient::connect(server.admin).await;
+        let response = rejected.receive().await;
+        assert_eq!(response["status"], REQUEST_INTERNAL_ERROR);
+        assert_eq!(response["message"]["max_connections"], 1);
+        assert!(started.elapsed() < REJECT_TIMEOUT);
+        assert_eq!(first.call(task("mock_news_polling", json!({}))).await["status"], REQUEST_SUCCUESS);
+
+        // The permit is released with the connection.
+        first.close().await;
+        let mut next = None;
+        for _ in 0..50 {
+            let mut client = TestClient::connect(server.main).await;
+            // A rejected connection may be closed before the request is sent.
+            let request = Message::Text(task("mock_news_polling", json!({})).to_string());
+            if client.ws.send(request).await.is_ok() && client.receive().await["status"] == REQUEST_SUCCUESS {
+                next = Some(client);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        next.expect("no connection admitted").close().await;
+    }
+
+    #[tokio::test]
+    async fn requires_a_token_once_configured() {
+        let server = TestServer::start(|config| {
+            config.server.auth.tokens.insert("desk".to_string(), "s3cret".to_string());
+            config.server.auth.signing_key = "key".to_string();
+        })
+        .await;
+
+        let mut client = TestClient::connect(server.main).await;
+        let response = client.call(task("mock_news_polling", json!({}))).await;
+        assert_eq!((response["status"].as_u64(), response["reason"].as_str()), (Some(UNAUTHORIZED as u64), Some("A valid token is required")));
+
+        let mut client = TestClient::connect(server.main).await;
+        let response = client.call(json!({ "auth": "s3cret" })).await;
+        assert_eq!(response["message"], json!({ "authenticated": "desk" }));
+        assert_eq!(client.call(task("mock_news_polling", json!({}))).await["status"], REQUEST_SUCCUESS);
+        client.close().await;
+
+        // Signed tokens, in the upgrade request.
+        let mut admin_client = TestClient::connect_url(&format!("ws://{}/?access_token=s3cret", server.admin)).await;
+        let issued = admin_client.call(admin("issue_token", json!({ "client": "dashboard", "ttl_secs": 60 }))).await;
+        let token = issued["message"]["token"].as_str().unwrap();
+        let mut client = TestClient::connect_url(&format!("ws://{}/?access_token={}", server.main, token)).await;
+        assert_eq!(client.call(task("mock_news_polling", json!({}))).await["status"], REQUEST_SUCCUESS);
+        client.close().await;
+
+        // Public listeners stay open.
+        let mut client = TestClient::connect(server.public).await;
+        let query = task("trending_keywords", json!({ "from": "2024-01-01T00:00:00Z" }));
+        assert_eq!(client.call(query).await["status"], REQUEST_SUCCUESS);
+        client.close().await;
+    }
+}